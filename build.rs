@@ -0,0 +1,224 @@
+// Bakes the generators and circuit window tables of `JubjubBls12` into
+// `$OUT_DIR/jubjub_bls12_baked.bin`, see `JubjubBls12::baked`. The tables are
+// derived exactly as in `JubjubBls12::new`, with plain affine arithmetic. Every
+// coordinate is written as 32 little endian bytes, in this order:
+//
+// - the 5 Pedersen hash generators (x, y)
+// - the 6 fixed generators (x, y), in the order of `FixedGenerators`
+// - the Montgomery window tables of the Pedersen hash generators,
+//   5 x 63 chunks x 4 points (x, y)
+// - the window tables of the fixed generators, 6 x 84 chunks x 8 points (x, y)
+
+extern crate bellman;
+extern crate blake2_rfc_bellman_edition as blake2_rfc;
+
+use bellman::pairing::bls12_381::Fr;
+use bellman::pairing::ff::{Field, PrimeField, PrimeFieldRepr, SqrtField};
+use blake2_rfc::blake2s::Blake2s;
+
+use std::env;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+// Keep in sync with `constants.rs` and `jubjub/mod.rs`.
+const GH_FIRST_BLOCK: &'static [u8; 64] = b"096b36a5804bfacef1691e173c366a47ff5ba84a44f26ddd7e8d9f79d5b42df0";
+const PEDERSEN_HASH_GENERATORS_PERSONALIZATION: &'static [u8; 8] = b"Zcash_PH";
+const SPENDING_KEY_GENERATOR_PERSONALIZATION: &'static [u8; 8] = b"Zcash_G_";
+const PROOF_GENERATION_KEY_BASE_GENERATOR_PERSONALIZATION: &'static [u8; 8] = b"Zcash_H_";
+const VALUE_COMMITMENT_GENERATOR_PERSONALIZATION: &'static [u8; 8] = b"Zcash_cv";
+const NULLIFIER_POSITION_IN_TREE_GENERATOR_PERSONALIZATION: &'static [u8; 8] = b"Zcash_J_";
+
+const EDWARDS_D: &'static str = "19257038036680949359750312669786877991949435402254120286184196891950884077233";
+const SCALE: &'static str = "17814886934372412843466061268024708274627479829237077604635722030778476050649";
+const PEDERSEN_HASH_CHUNKS_PER_GENERATOR: usize = 63;
+const FIXED_BASE_CHUNKS_PER_GENERATOR: usize = 84;
+
+#[derive(Clone, Copy, PartialEq)]
+struct Point {
+    x: Fr,
+    y: Fr,
+}
+
+struct Curve {
+    d: Fr,
+    scale: Fr,
+}
+
+impl Curve {
+    fn zero(&self) -> Point {
+        Point { x: Fr::zero(), y: Fr::one() }
+    }
+
+    // (x1 y2 + y1 x2) / (1 + d x1 x2 y1 y2), (y1 y2 + x1 x2) / (1 - d x1 x2 y1 y2)
+    fn add(&self, p: &Point, q: &Point) -> Point {
+        let mut x1x2 = p.x;
+        x1x2.mul_assign(&q.x);
+        let mut y1y2 = p.y;
+        y1y2.mul_assign(&q.y);
+        let mut dxy = self.d;
+        dxy.mul_assign(&x1x2);
+        dxy.mul_assign(&y1y2);
+
+        let mut x = p.x;
+        x.mul_assign(&q.y);
+        let mut tmp = p.y;
+        tmp.mul_assign(&q.x);
+        x.add_assign(&tmp);
+        let mut den = Fr::one();
+        den.add_assign(&dxy);
+        x.mul_assign(&den.inverse().unwrap());
+
+        let mut y = y1y2;
+        y.add_assign(&x1x2);
+        let mut den = Fr::one();
+        den.sub_assign(&dxy);
+        y.mul_assign(&den.inverse().unwrap());
+
+        Point { x, y }
+    }
+
+    fn double(&self, p: &Point) -> Point {
+        self.add(p, p)
+    }
+
+    // x^2 = (y^2 - 1) / (d y^2 + 1), the sign bit picks the parity of x
+    fn read(&self, bytes: &[u8]) -> Option<Point> {
+        let mut repr = <Fr as PrimeField>::Repr::default();
+        repr.read_le(bytes).unwrap();
+        let sign = (repr.as_ref()[3] >> 63) == 1;
+        repr.as_mut()[3] &= 0x7fffffffffffffff;
+        let y = Fr::from_repr(repr).ok()?;
+
+        let mut y2 = y;
+        y2.square();
+        let mut den = y2;
+        den.mul_assign(&self.d);
+        den.add_assign(&Fr::one());
+        let mut x2 = y2;
+        x2.sub_assign(&Fr::one());
+        x2.mul_assign(&den.inverse()?);
+
+        let mut x = x2.sqrt()?;
+        if x.into_repr().is_odd() != sign {
+            x.negate();
+        }
+
+        Some(Point { x, y })
+    }
+
+    // (x, y) -> ((1 + y) / (1 - y), scale (1 + y) / ((1 - y) x))
+    fn to_montgomery(&self, p: &Point) -> Point {
+        let mut u = Fr::one();
+        u.add_assign(&p.y);
+        let mut tmp = Fr::one();
+        tmp.sub_assign(&p.y);
+        u.mul_assign(&tmp.inverse().unwrap());
+
+        let mut v = u;
+        v.mul_assign(&p.x.inverse().unwrap());
+        v.mul_assign(&self.scale);
+
+        Point { x: u, y: v }
+    }
+
+    fn group_hash(&self, tag: &[u8], personalization: &[u8]) -> Option<Point> {
+        let mut h = Blake2s::with_params(32, &[], &[], personalization);
+        h.update(GH_FIRST_BLOCK);
+        h.update(tag);
+        let h = h.finalize();
+
+        let p = self.read(h.as_ref())?;
+        let p = self.double(&self.double(&self.double(&p)));
+
+        if p != self.zero() {
+            Some(p)
+        } else {
+            None
+        }
+    }
+
+    fn find_group_hash(&self, m: &[u8], personalization: &[u8]) -> Point {
+        let mut tag = m.to_vec();
+        let i = tag.len();
+        tag.push(0u8);
+
+        loop {
+            let gh = self.group_hash(&tag, personalization);
+
+            assert!(tag[i] != u8::max_value());
+            tag[i] += 1;
+
+            if let Some(gh) = gh {
+                break gh;
+            }
+        }
+    }
+}
+
+fn write_point<W: Write>(writer: &mut W, p: &Point) {
+    p.x.into_repr().write_le(&mut *writer).unwrap();
+    p.y.into_repr().write_le(&mut *writer).unwrap();
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    let curve = Curve {
+        d: Fr::from_str(EDWARDS_D).unwrap(),
+        scale: Fr::from_str(SCALE).unwrap(),
+    };
+
+    let pedersen_hash_generators: Vec<Point> = (0u32..5).map(|m| {
+        curve.find_group_hash(&m.to_le_bytes(), PEDERSEN_HASH_GENERATORS_PERSONALIZATION)
+    }).collect();
+
+    let fixed_base_generators = vec![
+        curve.find_group_hash(&[], PROOF_GENERATION_KEY_BASE_GENERATOR_PERSONALIZATION),
+        curve.find_group_hash(b"r", PEDERSEN_HASH_GENERATORS_PERSONALIZATION),
+        curve.find_group_hash(&[], NULLIFIER_POSITION_IN_TREE_GENERATOR_PERSONALIZATION),
+        curve.find_group_hash(b"v", VALUE_COMMITMENT_GENERATOR_PERSONALIZATION),
+        curve.find_group_hash(b"r", VALUE_COMMITMENT_GENERATOR_PERSONALIZATION),
+        curve.find_group_hash(&[], SPENDING_KEY_GENERATOR_PERSONALIZATION),
+    ];
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let file = File::create(Path::new(&out_dir).join("jubjub_bls12_baked.bin")).unwrap();
+    let mut writer = BufWriter::new(file);
+
+    for p in pedersen_hash_generators.iter().chain(fixed_base_generators.iter()) {
+        write_point(&mut writer, p);
+    }
+
+    // g, 2g, 3g, 4g in Montgomery form, chunks are 16 times apart
+    for g in pedersen_hash_generators.iter() {
+        let mut gen = *g;
+        for _ in 0..PEDERSEN_HASH_CHUNKS_PER_GENERATOR {
+            let mut p = gen;
+            for _ in 0..4 {
+                write_point(&mut writer, &curve.to_montgomery(&p));
+                p = curve.add(&p, &gen);
+            }
+
+            for _ in 0..4 {
+                gen = curve.double(&gen);
+            }
+        }
+    }
+
+    // 0, g, ..., 7g, chunks are 8 times apart
+    for g in fixed_base_generators.iter() {
+        let mut gen = *g;
+        for _ in 0..FIXED_BASE_CHUNKS_PER_GENERATOR {
+            let mut p = curve.zero();
+            for _ in 0..8 {
+                write_point(&mut writer, &p);
+                p = curve.add(&p, &gen);
+            }
+
+            gen = p;
+        }
+    }
+
+    writer.flush().unwrap();
+}
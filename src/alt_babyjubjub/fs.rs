@@ -3,6 +3,8 @@ use bellman::pairing::ff::{adc, sbb, mac_with_carry};
 use bellman::pairing::ff::{BitIterator, Field, PrimeField, SqrtField, PrimeFieldRepr, PrimeFieldDecodingError, LegendreSymbol};
 use bellman::pairing::ff::LegendreSymbol::*;
 use super::ToUniform;
+use rand_core::{CryptoRng, RngCore};
+use random::random_field_element;
 
 // s = 2736030358979909402780800718157159386076813972158567259200215660948447373041
 const MODULUS: FsRepr = FsRepr([
@@ -35,6 +37,14 @@ const R2: FsRepr = FsRepr([
     0x0445b524f1ba50a8,
 ]);
 
+// R3 = R^3 % s
+const R3: FsRepr = FsRepr([
+    0x30886e7b42917c21,
+    0x98dae87b55d7cd2a,
+    0xeefe3d08cc608b7b,
+    0x02b4dbffb2bc97aa,
+]);
+
 // INV = -(s^{-1} mod 2^64) mod s
 const INV: u64 = 0x532ce5aebc48f5ef;
 
@@ -544,6 +554,49 @@ impl Field for Fs {
 }
 
 impl Fs {
+    /// Reduces a 512 bit little endian integer, e.g. a hash output, modulo s.
+    pub fn from_bytes_wide(bytes: &[u8; 64]) -> Fs {
+        let mut lo = FsRepr::default();
+        let mut hi = FsRepr::default();
+        LittleEndian::read_u64_into(&bytes[..32], &mut lo.0);
+        LittleEndian::read_u64_into(&bytes[32..], &mut hi.0);
+
+        // Fs(x) represents x * R^-1, so Fs(lo) * R2 + Fs(hi) * R3 = lo + hi * 2^256.
+        // Montgomery multiplication only needs one of the factors to be reduced.
+        let mut result = Fs(lo);
+        result.mul_assign(&Fs(R2));
+        let mut hi = Fs(hi);
+        hi.mul_assign(&Fs(R3));
+        result.add_assign(&hi);
+
+        result
+    }
+
+    /// A uniformly random scalar, sampled as by `Rand`.
+    pub fn random<R: RngCore + CryptoRng + ?Sized>(rng: &mut R) -> Fs {
+        random_field_element(rng)
+    }
+
+    pub fn from_u64(value: u64) -> Fs {
+        Fs::from_repr(FsRepr::from(value)).expect("u64 is smaller than the modulus")
+    }
+
+    /// Canonical little endian encoding.
+    pub fn to_bytes_le(&self) -> [u8; 32] {
+        let mut bytes = [0u8; 32];
+        LittleEndian::write_u64_into(&self.into_repr().0, &mut bytes);
+
+        bytes
+    }
+
+    /// Decodes a little endian encoding, `None` if it isn't reduced.
+    pub fn from_bytes_le(bytes: &[u8; 32]) -> Option<Fs> {
+        let mut repr = FsRepr::default();
+        LittleEndian::read_u64_into(&bytes[..], &mut repr.0);
+
+        Fs::from_repr(repr).ok()
+    }
+
     /// Determines if the element is really in the field. This is only used
     /// internally.
     #[inline(always)]
@@ -1319,3 +1372,38 @@ fn test_fs_root_of_unity() {
     );
     assert!(Fs::multiplicative_generator().sqrt().is_none());
 }
+
+#[test]
+fn test_fs_from_bytes_wide() {
+    use rand::{SeedableRng, XorShiftRng, Rng};
+    let mut rng = XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+    for _ in 0..1000 {
+        let mut bytes = [0u8; 64];
+        rng.fill_bytes(&mut bytes);
+        assert_eq!(Fs::from_bytes_wide(&bytes), Fs::to_uniform(&bytes[..]));
+    }
+
+    assert_eq!(Fs::from_bytes_wide(&[0u8; 64]), Fs::zero());
+    assert_eq!(Fs::from_bytes_wide(&[0xff; 64]), Fs::to_uniform(&[0xff; 64][..]));
+}
+
+#[test]
+fn test_fs_byte_conversions() {
+    use rand::{SeedableRng, XorShiftRng, Rand};
+    let mut rng = XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+    assert_eq!(Fs::from_u64(0), Fs::zero());
+    assert_eq!(Fs::from_u64(1), Fs::one());
+    assert_eq!(Fs::from_u64(u64::max_value()), Fs::from_str("18446744073709551615").unwrap());
+
+    for _ in 0..1000 {
+        let a = Fs::rand(&mut rng);
+        assert_eq!(Fs::from_bytes_le(&a.to_bytes_le()), Some(a));
+    }
+
+    let mut modulus = [0u8; 32];
+    LittleEndian::write_u64_into(&MODULUS.0, &mut modulus);
+    assert_eq!(Fs::from_bytes_le(&modulus), None);
+    assert_eq!(Fs::from_bytes_le(&[0xff; 32]), None);
+}
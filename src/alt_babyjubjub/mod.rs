@@ -57,7 +57,9 @@ pub use super::jubjub::{
     FixedGenerators,
     ToUniform,
     JubjubEngine,
+    JubjubEngineWithParams,
     JubjubParams,
+    SharedParams,
     edwards,
     montgomery
 };
@@ -82,6 +84,14 @@ impl JubjubEngine for Bn256 {
     type Params = AltJubjubBn256;
 }
 
+/// Same parameters under the name matching `JubjubBls12`.
+pub type JubjubBn256 = AltJubjubBn256;
+
+/// Baby Jubjub over the BN254 scalar field, as the a = -1 twisted Edwards curve with
+/// d = -168696/168700 and the Montgomery curve with A = 168698, with the Pedersen hash and
+/// fixed-base generators and their circuit window tables. This is the parameter set for
+/// proofs over BN254, e.g. verified by the Ethereum precompiles; `JubjubBls12` is the one
+/// for BLS12-381.
 pub struct AltJubjubBn256 {
     edwards_d: Fr,
     montgomery_a: Fr,
@@ -129,7 +139,40 @@ impl JubjubParams<Bn256> for AltJubjubBn256 {
     }
 }
 
+/// Parameters from `JubjubParamsBuilder::build`, which checks that the chunk counts and the
+/// window size are the ones above.
+impl From<super::jubjub::builder::CustomJubjubParams<Bn256>> for AltJubjubBn256 {
+    fn from(params: super::jubjub::builder::CustomJubjubParams<Bn256>) -> Self {
+        AltJubjubBn256 {
+            edwards_d: params.edwards_d,
+            montgomery_a: params.montgomery_a,
+            montgomery_2a: params.montgomery_2a,
+            scale: params.scale,
+            pedersen_hash_generators: params.pedersen_hash_generators,
+            pedersen_hash_exp: params.pedersen_hash_exp,
+            pedersen_circuit_generators: params.pedersen_circuit_generators,
+            fixed_base_generators: params.fixed_base_generators,
+            fixed_base_circuit_generators: params.fixed_base_circuit_generators,
+        }
+    }
+}
+
+impl JubjubEngineWithParams for Bn256 {
+    fn shared_params() -> &'static AltJubjubBn256 {
+        AltJubjubBn256::shared()
+    }
+}
+
 impl AltJubjubBn256 {
+    /// The parameters shared by the whole process, built on first use.
+    pub fn shared() -> &'static AltJubjubBn256 {
+        lazy_static! {
+            static ref SHARED: AltJubjubBn256 = AltJubjubBn256::new();
+        }
+
+        &SHARED
+    }
+
     pub fn new() -> Self {
         let montgomery_a = Fr::from_str("168698").unwrap();
         let mut montgomery_2a = montgomery_a;
@@ -16,6 +16,8 @@ use bellman::pairing::ff::{
 
 use rand::{XorShiftRng, SeedableRng, Rand};
 
+use bellman::worker::Worker;
+
 pub fn test_suite<E: JubjubEngine>(params: &E::Params) {
     test_back_and_forth::<E>(params);
     test_jubjub_params::<E>(params);
@@ -27,6 +29,11 @@ pub fn test_suite<E: JubjubEngine>(params: &E::Params) {
     test_mul_associativity::<E>(params);
     test_loworder::<E>(params);
     test_read_write::<E>(params);
+    test_to_from_bytes::<E>(params);
+    test_batch_normalize::<E>(params);
+    test_multiexp::<E>(params);
+    test_fixed_base_table::<E>(params);
+    test_subgroups::<E>(params);
 }
 
 fn is_on_mont_curve<E: JubjubEngine, P: JubjubParams<E>>(
@@ -261,6 +268,112 @@ fn test_read_write<E: JubjubEngine>(params: &E::Params) {
     }
 }
 
+fn test_to_from_bytes<E: JubjubEngine>(params: &E::Params) {
+    let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+    for _ in 0..100 {
+        let e = edwards::Point::<E, _>::rand(rng, params);
+
+        let mut v = vec![];
+        e.write(&mut v).unwrap();
+        let bytes = e.to_bytes();
+        assert_eq!(&bytes[..], &v[..]);
+
+        assert!(edwards::Point::from_bytes(&bytes, params).unwrap() == e);
+    }
+
+    // y = 2^255 - 1 is not reduced
+    let mut bytes = [0xffu8; 32];
+    bytes[31] = 0x7f;
+    assert!(edwards::Point::<E, _>::from_bytes(&bytes, params).is_none());
+
+    // x = 0 with the sign bit set decodes with `read`, but is not canonical
+    let mut bytes = edwards::Point::<E, PrimeOrder>::zero().to_bytes();
+    bytes[31] |= 0x80;
+    assert!(edwards::Point::read(&bytes[..], params).is_ok());
+    assert!(edwards::Point::<E, _>::from_bytes(&bytes, params).is_none());
+}
+
+fn test_batch_normalize<E: JubjubEngine>(params: &E::Params) {
+    let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+    let points: Vec<_> = (0..50).map(|i| {
+        let p = edwards::Point::<E, _>::rand(rng, params);
+        (0..(i % 5)).fold(p.clone(), |acc, _| acc.double(params).add(&p, params))
+    }).collect();
+
+    let mut normalized = points.clone();
+    edwards::Point::batch_normalize(&mut normalized);
+    for (p, n) in points.iter().zip(normalized.iter()) {
+        assert!(p == n);
+        assert_eq!(p.into_xy(), n.into_xy());
+    }
+
+    edwards::Point::<E, PrimeOrder>::batch_normalize(&mut []);
+}
+
+fn test_multiexp<E: JubjubEngine>(params: &E::Params) {
+    let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+    let worker = Worker::new();
+
+    // below and above the window size switch
+    for &n in [0usize, 1, 7, 40].iter() {
+        let bases: Vec<_> = (0..n).map(|_| edwards::Point::<E, _>::rand(rng, params)).collect();
+        let scalars: Vec<E::Fs> = (0..n).map(|_| E::Fs::rand(rng)).collect();
+
+        let expected = bases.iter().zip(scalars.iter())
+            .fold(edwards::Point::zero(), |acc, (b, s)| acc.add(&b.mul(*s, params), params));
+
+        assert!(edwards::multiexp(&bases, &scalars, params) == expected);
+        assert!(edwards::multiexp_multicore(&bases, &scalars, params, &worker) == expected);
+    }
+}
+
+fn test_fixed_base_table<E: JubjubEngine>(params: &E::Params) {
+    let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+    let base = edwards::Point::<E, _>::rand(rng, params);
+    for &window in [1usize, 4, 5].iter() {
+        let table = base.precompute(window, params);
+        assert_eq!(table.window(), window);
+
+        assert!(table.mul_precomputed(E::Fs::zero(), params) == edwards::Point::zero());
+        for _ in 0..10 {
+            let s = E::Fs::rand(rng);
+            assert!(table.mul_precomputed(s, params) == base.mul(s, params));
+        }
+    }
+}
+
+fn test_subgroups<E: JubjubEngine>(params: &E::Params) {
+    let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+    let mut minus_one = E::Fr::one();
+    minus_one.negate();
+    let torsion = edwards::Point::<E, _>::get_for_y(minus_one, false, params).unwrap();
+    assert!(torsion.is_small_order(params));
+    assert!(!torsion.is_torsion_free(params));
+    assert!(torsion.clone().into_subgroup(params) == edwards::Point::zero());
+
+    for _ in 0..10 {
+        let p = edwards::Point::<E, _>::rand(rng, params).mul_by_cofactor(params);
+        assert!(p.is_torsion_free(params));
+        assert!(!p.is_small_order(params));
+        assert!(p.clone().into_subgroup(params) == p);
+        assert!(p.as_unknown().into_subgroup(params) == p);
+
+        let q = p.as_unknown().add(&torsion, params);
+        assert!(!q.is_torsion_free(params));
+        assert!(q.as_prime_order(params).is_none());
+        assert!(q.clone().into_subgroup(params) == p);
+        assert!(q.clear_cofactor(params) == p.clear_cofactor(params));
+
+        let m = montgomery::Point::from_edwards(&q, params);
+        assert!(!m.is_torsion_free(params));
+        assert!(m.into_subgroup(params) == montgomery::Point::from_edwards(&p, params));
+    }
+}
+
 fn test_rand<E: JubjubEngine>(params: &E::Params) {
     let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
 
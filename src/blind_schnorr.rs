@@ -0,0 +1,180 @@
+//! Blind signing producing ordinary RedJubjub signatures.
+//!
+//! The signer commits to R' = k . P_G, the user picks blinding factors alpha, beta and computes
+//! R = R' + alpha . P_G + beta . vk, c = H*(Rbar || M) and sends c' = c + beta. The signer answers
+//! with s' = k + c' . sk and the user unblinds S = s' + alpha. Then S . P_G = R + c . vk, so (Rbar, S)
+//! is a RedJubjub signature of M that the signer can't link to the session.
+//!
+//! # Security
+//!
+//! A signer must not run sessions concurrently. With l open sessions a user can obtain l + 1
+//! valid signatures by choosing the blinded challenges with the ROS attack of Benhamouda,
+//! Lepoint, Loss, Orru and Raykova ("On the (in)security of ROS", EUROCRYPT 2021), which is
+//! practical from a few hundred sessions on. The signer has to answer or abort each session,
+//! i.e. call `sign_blinded` or drop its `SignerNonce`, before calling `commit` again. This
+//! module does not enforce it, a signer serving several users has to serialize the sessions.
+
+use bellman::pairing::ff::{Field, PrimeField};
+use rand::{Rng, Rand};
+
+use jubjub::{FixedGenerators, JubjubEngine, JubjubParams, Unknown, edwards::Point};
+use redjubjub::{PrivateKey, PublicKey, Signature, h_star, write_scalar};
+
+/// Signer's secret nonce for one session, consumed by `sign_blinded`.
+pub struct SignerNonce<E: JubjubEngine> {
+    k: E::Fs,
+}
+
+/// Signer's commitment R' sent to the user.
+#[derive(Clone)]
+pub struct SignerCommitment<E: JubjubEngine>(pub Point<E, Unknown>);
+
+/// Challenge c' the user sends to the signer, it doesn't reveal the message or the final R.
+#[derive(Clone, Copy)]
+pub struct BlindedChallenge<E: JubjubEngine>(pub E::Fs);
+
+/// Signer's response s'.
+#[derive(Clone, Copy)]
+pub struct BlindSignature<E: JubjubEngine>(pub E::Fs);
+
+/// What the user keeps between blinding the challenge and unblinding the response.
+pub struct UnblindingState<E: JubjubEngine> {
+    alpha: E::Fs,
+    rbar: [u8; 32],
+    commitment: Point<E, Unknown>,
+    blinded_challenge: E::Fs,
+}
+
+pub fn commit<E: JubjubEngine, R: Rng>(
+    rng: &mut R,
+    p_g: FixedGenerators,
+    params: &E::Params
+) -> (SignerNonce<E>, SignerCommitment<E>) {
+    let k = E::Fs::rand(rng);
+    let commitment = params.generator(p_g).mul(k, params).into();
+
+    (SignerNonce { k }, SignerCommitment(commitment))
+}
+
+/// s' = k + c' . sk
+pub fn sign_blinded<E: JubjubEngine>(
+    sk: &PrivateKey<E>,
+    nonce: SignerNonce<E>,
+    challenge: &BlindedChallenge<E>
+) -> BlindSignature<E> {
+    let mut s = challenge.0;
+    s.mul_assign(&sk.0);
+    s.add_assign(&nonce.k);
+
+    BlindSignature(s)
+}
+
+pub fn blind_challenge<E: JubjubEngine, R: Rng>(
+    rng: &mut R,
+    commitment: &SignerCommitment<E>,
+    vk: &PublicKey<E>,
+    msg: &[u8],
+    p_g: FixedGenerators,
+    params: &E::Params
+) -> (UnblindingState<E>, BlindedChallenge<E>) {
+    let alpha = E::Fs::rand(rng);
+    let beta = E::Fs::rand(rng);
+
+    // R = R' + alpha . P_G + beta . vk
+    let alpha_g: Point<E, Unknown> = params.generator(p_g).mul(alpha, params).into();
+    let r = commitment.0.add(&alpha_g, params).add(&vk.0.mul(beta, params), params);
+    let mut rbar = [0u8; 32];
+    r.write(&mut rbar[..]).expect("Jubjub points should serialize to 32 bytes");
+
+    // c' = H*(Rbar || M) + beta
+    let mut c = h_star::<E>(&rbar[..], msg);
+    c.add_assign(&beta);
+
+    let state = UnblindingState {
+        alpha,
+        rbar,
+        commitment: commitment.0.clone(),
+        blinded_challenge: c,
+    };
+
+    (state, BlindedChallenge(c))
+}
+
+/// Checks the signer's response s' . P_G = R' + c' . vk and unblinds it into a RedJubjub
+/// signature. Returns None if the response is invalid.
+pub fn unblind<E: JubjubEngine>(
+    state: UnblindingState<E>,
+    response: &BlindSignature<E>,
+    vk: &PublicKey<E>,
+    p_g: FixedGenerators,
+    params: &E::Params
+) -> Option<Signature> {
+    let lhs: Point<E, Unknown> = params.generator(p_g).mul(response.0, params).into();
+    let rhs = state.commitment.add(&vk.0.mul(state.blinded_challenge, params), params);
+    if lhs != rhs {
+        return None;
+    }
+
+    let mut s = response.0;
+    s.add_assign(&state.alpha);
+
+    let mut bytes = [0u8; 64];
+    bytes[..32].copy_from_slice(&state.rbar);
+    write_scalar::<E, &mut [u8]>(&s, &mut bytes[32..])
+        .expect("Jubjub scalars should serialize to 32 bytes");
+
+    Signature::read(&bytes[..]).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use bellman::pairing::bn256::Bn256;
+    use rand::thread_rng;
+
+    use alt_babyjubjub::AltJubjubBn256;
+
+    use super::*;
+
+    #[test]
+    fn test_blind_signature_verifies() {
+        let rng = &mut thread_rng();
+        let params = &AltJubjubBn256::new();
+        let p_g = FixedGenerators::SpendingKeyGenerator;
+        let msg = b"coin serial number";
+
+        let sk = PrivateKey::<Bn256>::generate(rng);
+        let vk = PublicKey::from_private(&sk, p_g, params);
+
+        let (nonce, commitment) = commit::<Bn256, _>(rng, p_g, params);
+        let (state, challenge) = blind_challenge(rng, &commitment, &vk, msg, p_g, params);
+        let response = sign_blinded(&sk, nonce, &challenge);
+        let signature = unblind(state, &response, &vk, p_g, params).unwrap();
+
+        assert!(vk.verify(msg, &signature, p_g, params));
+        assert!(!vk.verify(b"another serial number", &signature, p_g, params));
+
+        // the signer's view doesn't contain R
+        let mut signature_bytes = [0u8; 64];
+        signature.write(&mut signature_bytes[..]).unwrap();
+        let mut commitment_bytes = [0u8; 32];
+        commitment.0.write(&mut commitment_bytes[..]).unwrap();
+        assert!(signature_bytes[..32] != commitment_bytes[..]);
+    }
+
+    #[test]
+    fn test_unblind_rejects_wrong_response() {
+        let rng = &mut thread_rng();
+        let params = &AltJubjubBn256::new();
+        let p_g = FixedGenerators::SpendingKeyGenerator;
+
+        let sk = PrivateKey::<Bn256>::generate(rng);
+        let vk = PublicKey::from_private(&sk, p_g, params);
+        let other_sk = PrivateKey::<Bn256>::generate(rng);
+
+        let (nonce, commitment) = commit::<Bn256, _>(rng, p_g, params);
+        let (state, challenge) = blind_challenge(rng, &commitment, &vk, b"message", p_g, params);
+        let response = sign_blinded(&other_sk, nonce, &challenge);
+
+        assert!(unblind(state, &response, &vk, p_g, params).is_none());
+    }
+}
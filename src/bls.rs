@@ -0,0 +1,289 @@
+//! BLS signatures over the pairing engine with signatures in G1 and public keys in G2.
+//! Messages are hashed into G1 by try-and-increment followed by clearing the G1 cofactor of the
+//! engine, see `BlsEngine`. G2 has a cofactor, public keys from untrusted sources go through
+//! `PublicKey::from_affine`, verification rejects keys and signatures that are the identity
+//! or outside of the prime order subgroups.
+
+use bellman::pairing::{Engine, CurveAffine, GenericCurveAffine, GenericCurveProjective};
+use bellman::pairing::bls12_381::Bls12;
+use bellman::pairing::bn256::Bn256;
+use bellman::pairing::ff::{Field, PrimeField, PrimeFieldRepr, SqrtField};
+use blake2_rfc::blake2b::Blake2b;
+use rand::{Rng, Rand};
+
+use std::collections::HashSet;
+
+pub const BLS_HASH_TO_G1_PERSONALIZATION: &[u8; 16] = b"BLS_HashToG1____";
+
+/// Engines for BLS signatures.
+pub trait BlsEngine: Engine {
+    /// The cofactor of G1 in little endian 64 bit limbs.
+    const G1_COFACTOR: &'static [u64];
+}
+
+impl BlsEngine for Bn256 {
+    const G1_COFACTOR: &'static [u64] = &[1];
+}
+
+impl BlsEngine for Bls12 {
+    // 0x396c8c005555e1568c00aaab0000aaab
+    const G1_COFACTOR: &'static [u64] = &[0x8c00aaab0000aaab, 0x396c8c005555e156];
+}
+
+pub struct SecretKey<E: Engine>(pub E::Fr);
+
+#[derive(Clone)]
+pub struct PublicKey<E: Engine>(pub E::G2Affine);
+
+#[derive(Clone)]
+pub struct Signature<E: Engine>(pub E::G1Affine);
+
+/// Try-and-increment: x = BLAKE2b(M || counter) until x^3 + b is a square, the sign of y is
+/// taken from the same digest. The point is multiplied by the G1 cofactor.
+pub fn hash_to_g1<E: BlsEngine>(msg: &[u8]) -> E::G1Affine {
+    let b = E::G1Affine::b_coeff();
+    let mut cofactor = <E::Fr as PrimeField>::Repr::default();
+    cofactor.as_mut()[..E::G1_COFACTOR.len()].copy_from_slice(E::G1_COFACTOR);
+    let mut counter = 0u32;
+
+    loop {
+        let mut hasher = Blake2b::with_params(64, &[], &[], &BLS_HASH_TO_G1_PERSONALIZATION[..]);
+        hasher.update(msg);
+        hasher.update(&counter.to_le_bytes());
+        let digest = hasher.finalize();
+        counter += 1;
+
+        let mut repr = <E::Fq as PrimeField>::Repr::default();
+        let num_bytes = repr.as_ref().len() * 8;
+        repr.read_le(&digest.as_ref()[..num_bytes]).expect("digest is long enough");
+        let x = match E::Fq::from_repr(repr) {
+            Ok(x) => x,
+            Err(_) => continue,
+        };
+
+        let mut rhs = x;
+        rhs.square();
+        rhs.mul_assign(&x);
+        rhs.add_assign(&b);
+        let mut y = match rhs.sqrt() {
+            Some(y) => y,
+            None => continue,
+        };
+        if digest.as_ref()[num_bytes] & 1 == 1 {
+            y.negate();
+        }
+
+        // on the curve by construction, but not necessarily in the prime order subgroup
+        let point = E::G1Affine::from_xy_unchecked(x, y).mul(cofactor).into_affine();
+        if !point.is_zero() {
+            return point;
+        }
+    }
+}
+
+impl<E: BlsEngine> SecretKey<E> {
+    pub fn generate<R: Rng>(rng: &mut R) -> Self {
+        SecretKey(E::Fr::rand(rng))
+    }
+
+    pub fn public_key(&self) -> PublicKey<E> {
+        PublicKey(E::G2Affine::one().mul(self.0.into_repr()).into_affine())
+    }
+
+    /// sig = sk . H(M)
+    pub fn sign(&self, msg: &[u8]) -> Signature<E> {
+        Signature(hash_to_g1::<E>(msg).mul(self.0.into_repr()).into_affine())
+    }
+}
+
+// r . p == 0 for the order r of the scalar field
+fn in_prime_order_subgroup<G: CurveAffine>(p: &G) -> bool {
+    p.mul(<G::Scalar as PrimeField>::char()).is_zero()
+}
+
+// checks prod_i e(g1_i, g2_i) == 1
+fn pairing_product_is_one<E: Engine>(pairs: &[(E::G1Affine, E::G2Affine)]) -> bool {
+    let prepared: Vec<_> = pairs.iter().map(|(p, q)| (p.prepare(), q.prepare())).collect();
+    let refs: Vec<_> = prepared.iter().map(|(p, q)| (p, q)).collect();
+
+    match E::final_exponentiation(&E::miller_loop(refs.iter())) {
+        Some(result) => result == E::Fqk::one(),
+        None => false,
+    }
+}
+
+impl<E: BlsEngine> PublicKey<E> {
+    /// `None` if `point` is the identity or not in the prime order subgroup.
+    pub fn from_affine(point: E::G2Affine) -> Option<Self> {
+        let pk = PublicKey(point);
+        if pk.is_valid() {
+            Some(pk)
+        } else {
+            None
+        }
+    }
+
+    /// The checks of `from_affine`, for keys built from the field.
+    pub fn is_valid(&self) -> bool {
+        !self.0.is_zero() && in_prime_order_subgroup(&self.0)
+    }
+
+    /// e(sig, g2) == e(H(M), pk)
+    pub fn verify(&self, msg: &[u8], sig: &Signature<E>) -> bool {
+        if !self.is_valid() || !sig.is_valid() {
+            return false;
+        }
+
+        let mut minus_g2 = E::G2Affine::one();
+        minus_g2.negate();
+
+        pairing_product_is_one::<E>(&[(sig.0, minus_g2), (hash_to_g1::<E>(msg), self.0)])
+    }
+}
+
+impl<E: Engine> Signature<E> {
+    /// The signature is not the identity and in the prime order subgroup.
+    pub fn is_valid(&self) -> bool {
+        !self.0.is_zero() && in_prime_order_subgroup(&self.0)
+    }
+}
+
+/// Sums the signatures, the result is verified by `aggregate_verify`.
+pub fn aggregate<E: Engine>(signatures: &[Signature<E>]) -> Signature<E> {
+    let mut acc = E::G1::zero();
+    for sig in signatures.iter() {
+        acc.add_assign_mixed(&sig.0);
+    }
+
+    Signature(acc.into_affine())
+}
+
+/// e(sig, g2) == prod_i e(H(M_i), pk_i). The messages must be pairwise distinct, otherwise
+/// the check is vulnerable to rogue key attacks and false is returned.
+pub fn aggregate_verify<E: BlsEngine>(
+    pubkeys: &[PublicKey<E>],
+    messages: &[&[u8]],
+    sig: &Signature<E>
+) -> bool {
+    if pubkeys.len() != messages.len() || pubkeys.len() == 0 {
+        return false;
+    }
+    let distinct: HashSet<&[u8]> = messages.iter().cloned().collect();
+    if distinct.len() != messages.len() {
+        return false;
+    }
+    if !sig.is_valid() || pubkeys.iter().any(|pk| !pk.is_valid()) {
+        return false;
+    }
+
+    let mut minus_g2 = E::G2Affine::one();
+    minus_g2.negate();
+
+    let mut pairs = Vec::with_capacity(pubkeys.len() + 1);
+    pairs.push((sig.0, minus_g2));
+    for (pk, msg) in pubkeys.iter().zip(messages.iter()) {
+        pairs.push((hash_to_g1::<E>(msg), pk.0));
+    }
+
+    pairing_product_is_one::<E>(&pairs)
+}
+
+#[cfg(test)]
+mod tests {
+    use bellman::pairing::bn256::{Bn256, Fq2, G2Affine};
+    use rand::thread_rng;
+
+    use super::*;
+
+    #[test]
+    fn test_sign_verify() {
+        let rng = &mut thread_rng();
+
+        let sk = SecretKey::<Bn256>::generate(rng);
+        let pk = sk.public_key();
+        let sig = sk.sign(b"block 1");
+
+        assert!(pk.verify(b"block 1", &sig));
+        assert!(!pk.verify(b"block 2", &sig));
+        assert!(!SecretKey::<Bn256>::generate(rng).public_key().verify(b"block 1", &sig));
+        assert_eq!(hash_to_g1::<Bn256>(b"block 1"), hash_to_g1::<Bn256>(b"block 1"));
+    }
+
+    #[test]
+    fn test_sign_verify_bls12() {
+        let rng = &mut thread_rng();
+
+        // G1 of Bls12 has a cofactor, the hash has to be cleared of it
+        let h = hash_to_g1::<Bls12>(b"block 1");
+        assert!(!h.is_zero() && in_prime_order_subgroup(&h));
+
+        let sk = SecretKey::<Bls12>::generate(rng);
+        let pk = sk.public_key();
+        let sig = sk.sign(b"block 1");
+
+        assert!(pk.is_valid() && sig.is_valid());
+        assert!(pk.verify(b"block 1", &sig));
+        assert!(!pk.verify(b"block 2", &sig));
+    }
+
+    #[test]
+    fn test_aggregate_verify() {
+        let rng = &mut thread_rng();
+
+        let sks: Vec<SecretKey<Bn256>> = (0..4).map(|_| SecretKey::generate(rng)).collect();
+        let pks: Vec<PublicKey<Bn256>> = sks.iter().map(|sk| sk.public_key()).collect();
+        let messages: Vec<Vec<u8>> = (0..4).map(|i| format!("attestation {}", i).into_bytes()).collect();
+        let message_refs: Vec<&[u8]> = messages.iter().map(|m| &m[..]).collect();
+
+        let signatures: Vec<_> = sks.iter().zip(messages.iter()).map(|(sk, msg)| sk.sign(msg)).collect();
+        let aggregated = aggregate(&signatures);
+
+        assert!(aggregate_verify(&pks, &message_refs, &aggregated));
+        assert!(!aggregate_verify(&pks[..3], &message_refs[..3], &aggregated));
+
+        let mut swapped = message_refs.clone();
+        swapped.swap(0, 1);
+        assert!(!aggregate_verify(&pks, &swapped, &aggregated));
+
+        // the same message twice is rejected even for a valid aggregate
+        let signatures = vec![sks[0].sign(b"same"), sks[1].sign(b"same")];
+        assert!(!aggregate_verify(&pks[..2], &[&b"same"[..], &b"same"[..]], &aggregate(&signatures)));
+    }
+
+    // a point of the twist outside of the prime order subgroup
+    fn g2_torsion_point<R: Rng>(rng: &mut R) -> G2Affine {
+        loop {
+            let x = Fq2::rand(rng);
+            let mut rhs = x;
+            rhs.square();
+            rhs.mul_assign(&x);
+            rhs.add_assign(&G2Affine::b_coeff());
+            if let Some(y) = rhs.sqrt() {
+                let p = G2Affine::from_xy_unchecked(x, y);
+                assert!(!in_prime_order_subgroup(&p));
+
+                return p;
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_public_keys() {
+        let rng = &mut thread_rng();
+
+        let sk = SecretKey::<Bn256>::generate(rng);
+        let pk = sk.public_key();
+        assert!(PublicKey::<Bn256>::from_affine(pk.0).is_some());
+        assert!(PublicKey::<Bn256>::from_affine(G2Affine::zero()).is_none());
+
+        let torsion = g2_torsion_point(rng);
+        assert!(PublicKey::<Bn256>::from_affine(torsion).is_none());
+
+        // keys built from the field are checked by the verification too
+        let sig = sk.sign(b"block 1");
+        assert!(!PublicKey::<Bn256>(torsion).verify(b"block 1", &sig));
+        assert!(!PublicKey::<Bn256>(G2Affine::zero()).verify(b"block 1", &sig));
+        assert!(!aggregate_verify(&[pk.clone(), PublicKey(torsion)], &[&b"block 1"[..], &b"block 2"[..]], &sig));
+        assert!(!pk.verify(b"block 1", &Signature(<Bn256 as Engine>::G1Affine::zero())));
+    }
+}
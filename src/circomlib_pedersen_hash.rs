@@ -0,0 +1,363 @@
+//! Pedersen hash over Baby Jubjub compatible with circomlib (`pedersenHash.js`, `pedersen.circom`).
+//!
+//! Input bits are split into segments of 50 windows of 4 bits (b0, b1, b2, s) and hashed as
+//!
+//!   H = sum_i sum_j (1 + b0 + 2 b1 + 4 b2) * (-1)^s * 32^j * G_i
+//!
+//! where G_i are found by try-and-increment over BLAKE-256 of
+//! "PedersenGenerator_<i>_<try>" (both numbers zero padded to 32 digits) and multiplied by 8.
+//! circomlib uses the a = 168700 form of the curve, points are kept in the isomorphic a = -1
+//! form of `AltBabyJubjubBn256` here and converted by (x, y) -> (x * sqrt(-168700), y).
+
+use bellman::pairing::bn256::{Bn256, Fr};
+use bellman::pairing::ff::{Field, PrimeField, PrimeFieldRepr, SqrtField};
+use byteorder::{BigEndian, ByteOrder};
+
+use generic_twisted_edwards::{TwistedEdwardsCurveImplementor, TwistedEdwardsPoint};
+use generic_twisted_edwards::bn256::AltBabyJubjubParams;
+
+pub const CIRCOMLIB_PEDERSEN_WINDOW_SIZE: usize = 4;
+pub const CIRCOMLIB_PEDERSEN_WINDOWS_PER_SEGMENT: usize = 50;
+pub const CIRCOMLIB_PEDERSEN_GENERATOR_PREFIX: &str = "PedersenGenerator";
+/// Number of generators hardcoded in `pedersen.circom`, enough for 2000 bits of input.
+pub const CIRCOMLIB_PEDERSEN_NUM_GENERATORS: usize = 10;
+
+// Parameters of the curve in circomlib: a x^2 + y^2 = 1 + d x^2 y^2
+const CIRCOMLIB_BABYJUB_A: &str = "168700";
+const CIRCOMLIB_BABYJUB_D: &str = "168696";
+
+const BLAKE256_IV: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a,
+    0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19
+];
+
+const BLAKE256_CONSTANTS: [u32; 16] = [
+    0x243f6a88, 0x85a308d3, 0x13198a2e, 0x03707344,
+    0xa4093822, 0x299f31d0, 0x082efa98, 0xec4e6c89,
+    0x452821e6, 0x38d01377, 0xbe5466cf, 0x34e90c6c,
+    0xc0ac29b7, 0xc97c50dd, 0x3f84d5b5, 0xb5470917
+];
+
+const BLAKE256_SIGMA: [[usize; 16]; 10] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+    [11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+    [7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+    [9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+    [2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+    [12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+    [13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+    [6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+    [10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0]
+];
+
+const BLAKE256_NUM_ROUNDS: usize = 14;
+
+fn blake256_compress(h: &mut [u32; 8], block: &[u8], counter: u64) {
+    let mut m = [0u32; 16];
+    BigEndian::read_u32_into(block, &mut m);
+
+    let c = &BLAKE256_CONSTANTS;
+    let mut v = [0u32; 16];
+    v[..8].copy_from_slice(&h[..]);
+    v[8..12].copy_from_slice(&c[..4]);
+    v[12] = (counter as u32) ^ c[4];
+    v[13] = (counter as u32) ^ c[5];
+    v[14] = ((counter >> 32) as u32) ^ c[6];
+    v[15] = ((counter >> 32) as u32) ^ c[7];
+
+    for round in 0..BLAKE256_NUM_ROUNDS {
+        let s = &BLAKE256_SIGMA[round % 10];
+        let mut g = |a: usize, b: usize, cc: usize, d: usize, i: usize| {
+            v[a] = v[a].wrapping_add(v[b]).wrapping_add(m[s[2 * i]] ^ c[s[2 * i + 1]]);
+            v[d] = (v[d] ^ v[a]).rotate_right(16);
+            v[cc] = v[cc].wrapping_add(v[d]);
+            v[b] = (v[b] ^ v[cc]).rotate_right(12);
+            v[a] = v[a].wrapping_add(v[b]).wrapping_add(m[s[2 * i + 1]] ^ c[s[2 * i]]);
+            v[d] = (v[d] ^ v[a]).rotate_right(8);
+            v[cc] = v[cc].wrapping_add(v[d]);
+            v[b] = (v[b] ^ v[cc]).rotate_right(7);
+        };
+
+        g(0, 4, 8, 12, 0);
+        g(1, 5, 9, 13, 1);
+        g(2, 6, 10, 14, 2);
+        g(3, 7, 11, 15, 3);
+        g(0, 5, 10, 15, 4);
+        g(1, 6, 11, 12, 5);
+        g(2, 7, 8, 13, 6);
+        g(3, 4, 9, 14, 7);
+    }
+
+    for i in 0..8 {
+        h[i] ^= v[i] ^ v[i + 8];
+    }
+}
+
+/// BLAKE-256 (the SHA-3 finalist, not BLAKE2s) without salt, as used by circomlib to derive generators.
+pub fn blake256(input: &[u8]) -> [u8; 32] {
+    let bit_length = (input.len() as u64) * 8;
+
+    let mut padded = input.to_vec();
+    padded.push(0x80);
+    while padded.len() % 64 != 56 {
+        padded.push(0);
+    }
+    *padded.last_mut().unwrap() |= 0x01;
+    let mut length_bytes = [0u8; 8];
+    BigEndian::write_u64(&mut length_bytes, bit_length);
+    padded.extend_from_slice(&length_bytes);
+
+    let mut h = BLAKE256_IV;
+    for (i, block) in padded.chunks(64).enumerate() {
+        // the counter is the number of message bits up to the end of the block,
+        // blocks consisting of padding only use zero
+        let block_start = (i as u64) * 512;
+        let counter = if block_start >= bit_length && bit_length != 0 {
+            0
+        } else {
+            std::cmp::min(bit_length, block_start + 512)
+        };
+        blake256_compress(&mut h, block, counter);
+    }
+
+    let mut output = [0u8; 32];
+    BigEndian::write_u32_into(&h, &mut output);
+
+    output
+}
+
+// the representative in [0, (p - 1) / 2], circomlib calls the other ones negative
+fn is_negative(el: &Fr) -> bool {
+    let mut negated = *el;
+    negated.negate();
+
+    el.into_repr() > negated.into_repr()
+}
+
+#[derive(Clone, Debug)]
+pub struct CircomlibPedersenParams {
+    /// sqrt(-168700), maps circomlib coordinates to the a = -1 form by (x, y) -> (x * scale, y).
+    pub scale: Fr,
+    /// Segment generators in the a = -1 form.
+    pub generators: Vec<TwistedEdwardsPoint<Bn256>>,
+}
+
+impl CircomlibPedersenParams {
+    pub fn new(curve: &TwistedEdwardsCurveImplementor<Bn256, AltBabyJubjubParams>) -> Self {
+        Self::with_num_generators(curve, CIRCOMLIB_PEDERSEN_NUM_GENERATORS)
+    }
+
+    pub fn with_num_generators(
+        curve: &TwistedEdwardsCurveImplementor<Bn256, AltBabyJubjubParams>,
+        num_generators: usize
+    ) -> Self {
+        let mut scale = Fr::from_str(CIRCOMLIB_BABYJUB_A).unwrap();
+        scale.negate();
+        let scale = scale.sqrt().expect("-a must be a square");
+
+        let mut params = Self {
+            scale,
+            generators: Vec::with_capacity(num_generators),
+        };
+
+        for i in 0..num_generators {
+            let generator = params.find_generator(curve, i);
+            params.generators.push(generator);
+        }
+
+        params
+    }
+
+    fn find_generator(
+        &self,
+        curve: &TwistedEdwardsCurveImplementor<Bn256, AltBabyJubjubParams>,
+        index: usize
+    ) -> TwistedEdwardsPoint<Bn256> {
+        let a = Fr::from_str(CIRCOMLIB_BABYJUB_A).unwrap();
+        let d = Fr::from_str(CIRCOMLIB_BABYJUB_D).unwrap();
+
+        let mut try_index = 0usize;
+        loop {
+            let seed = format!("{}_{:032}_{:032}", CIRCOMLIB_PEDERSEN_GENERATOR_PREFIX, index, try_index);
+            try_index += 1;
+
+            let mut digest = blake256(seed.as_bytes());
+            digest[31] &= 0xbf;
+            let sign = digest[31] & 0x80 != 0;
+            digest[31] &= 0x7f;
+
+            let mut repr = <Fr as PrimeField>::Repr::default();
+            repr.read_le(&digest[..]).unwrap();
+            let y = match Fr::from_repr(repr) {
+                Ok(y) => y,
+                Err(_) => continue,
+            };
+
+            // x^2 = (1 - y^2) / (a - d y^2)
+            let mut y2 = y;
+            y2.square();
+            let mut numerator = Fr::one();
+            numerator.sub_assign(&y2);
+            let mut denominator = d;
+            denominator.mul_assign(&y2);
+            denominator.negate();
+            denominator.add_assign(&a);
+            let x2 = match denominator.inverse() {
+                Some(inv) => {
+                    let mut x2 = numerator;
+                    x2.mul_assign(&inv);
+                    x2
+                },
+                None => continue,
+            };
+            let mut x = match x2.sqrt() {
+                Some(x) => x,
+                None => continue,
+            };
+            if is_negative(&x) {
+                x.negate();
+            }
+            if sign {
+                x.negate();
+            }
+
+            let mut p = self.from_circomlib_xy(x, y);
+            for _ in 0..3 {
+                p = curve.double(&p);
+            }
+            assert!(curve.is_in_main_subgroup(&p));
+
+            return p;
+        }
+    }
+
+    pub fn from_circomlib_xy(&self, x: Fr, y: Fr) -> TwistedEdwardsPoint<Bn256> {
+        let mut x = x;
+        x.mul_assign(&self.scale);
+
+        TwistedEdwardsPoint::from_xy(x, y)
+    }
+
+    pub fn into_circomlib_xy(&self, p: &TwistedEdwardsPoint<Bn256>) -> (Fr, Fr) {
+        let (mut x, y) = p.into_xy();
+        x.mul_assign(&self.scale.inverse().unwrap());
+
+        (x, y)
+    }
+}
+
+/// Encoding of a window (b0, b1, b2, s) as (1 + b0 + 2 b1 + 4 b2, s), missing bits are zero.
+pub fn window_into_scalar(window: &[bool]) -> (u64, bool) {
+    assert!(window.len() > 0 && window.len() <= CIRCOMLIB_PEDERSEN_WINDOW_SIZE);
+    let magnitude = window.iter().take(3).enumerate().fold(1u64, |acc, (i, bit)| acc + ((*bit as u64) << i));
+    let sign = window.get(3).cloned().unwrap_or(false);
+
+    (magnitude, sign)
+}
+
+/// Pedersen hash of `bits` as a point in the a = -1 form.
+pub fn hash_to_point(
+    curve: &TwistedEdwardsCurveImplementor<Bn256, AltBabyJubjubParams>,
+    params: &CircomlibPedersenParams,
+    bits: &[bool]
+) -> TwistedEdwardsPoint<Bn256> {
+    let bits_per_segment = CIRCOMLIB_PEDERSEN_WINDOW_SIZE * CIRCOMLIB_PEDERSEN_WINDOWS_PER_SEGMENT;
+    let mut result = TwistedEdwardsPoint::identity();
+    let mut generators = params.generators.iter();
+
+    for segment in bits.chunks(bits_per_segment) {
+        let mut base = *generators.next().expect("we don't have enough generators");
+        for window in segment.chunks(CIRCOMLIB_PEDERSEN_WINDOW_SIZE) {
+            let (magnitude, sign) = window_into_scalar(window);
+            let mut p = curve.mul(&base, magnitude);
+            if sign {
+                p = curve.negate(&p);
+            }
+            result = curve.add(&result, &p);
+
+            // 32 * base for the next window
+            for _ in 0..(CIRCOMLIB_PEDERSEN_WINDOW_SIZE + 1) {
+                base = curve.double(&base);
+            }
+        }
+    }
+
+    result
+}
+
+/// Pedersen hash of `bits` in circomlib coordinates, the output of `pedersen.circom`.
+pub fn hash(
+    curve: &TwistedEdwardsCurveImplementor<Bn256, AltBabyJubjubParams>,
+    params: &CircomlibPedersenParams,
+    bits: &[bool]
+) -> (Fr, Fr) {
+    params.into_circomlib_xy(&hash_to_point(curve, params, bits))
+}
+
+/// circomlib `packPoint`: little endian y with the sign of x in the top bit.
+pub fn pack_point(x: &Fr, y: &Fr) -> [u8; 32] {
+    let mut output = [0u8; 32];
+    y.into_repr().write_le(&mut output[..]).unwrap();
+    if is_negative(x) {
+        output[31] |= 0x80;
+    }
+
+    output
+}
+
+/// Bits of `bytes` in the order of circomlib `buffer2bits`, little endian in every byte.
+pub fn bytes_into_bits(bytes: &[u8]) -> Vec<bool> {
+    bytes.iter().flat_map(|byte| (0..8).map(move |i| (byte >> i) & 1 == 1)).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use hex;
+    use rand::{Rng, XorShiftRng, SeedableRng};
+    use generic_twisted_edwards::bn256::AltBabyJubjubBn256;
+
+    #[test]
+    fn test_blake256_vectors() {
+        assert_eq!(hex::encode(blake256(b"")), "716f6e863f744b9ac22c97ec7b76ea5f5908bc5b2f67c61510bfc4751384ea7a");
+        assert_eq!(hex::encode(blake256(&[0u8])), "0ce8d4ef4dd7cd8d62dfded9d4edb0a774ae6a41929a74da23109e8f11139c87");
+        assert_eq!(hex::encode(blake256(&[0u8; 72])), "d419bad32d504fb7d44d460c42c5593fe544fa4c135dec31e21bd9abdcc22d41");
+    }
+
+    #[test]
+    fn test_generators_match_pedersen_circom() {
+        let curve = AltBabyJubjubBn256::get_implementor();
+        let params = CircomlibPedersenParams::with_num_generators(&curve, 3);
+
+        // BASE from pedersen.circom
+        let expected = [
+            ("10457101036533406547632367118273992217979173478358440826365724437999023779287", "19824078218392094440610104313265183977899662750282163392862422243483260492317"),
+            ("2671756056509184035029146175565761955751135805354291559563293617232983272177", "2663205510731142763556352975002641716101654201788071096152948830924149045094"),
+            ("5802099305472655231388284418920769829666717045250560929368476121199858275951", "5980429700218124965372158798884772646841287887664001482443826541541529227896"),
+        ];
+        for (generator, &(x, y)) in params.generators.iter().zip(expected.iter()) {
+            assert_eq!(params.into_circomlib_xy(generator), (Fr::from_str(x).unwrap(), Fr::from_str(y).unwrap()));
+        }
+    }
+
+    #[test]
+    fn test_hash_is_sum_over_segments() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let curve = AltBabyJubjubBn256::get_implementor();
+        let params = CircomlibPedersenParams::with_num_generators(&curve, 2);
+
+        let bits: Vec<bool> = (0..250).map(|_| rng.gen()).collect();
+        let mut second_segment = vec![false; 200];
+        second_segment.extend_from_slice(&bits[200..]);
+
+        // a zero window contributes G, so the first segment of zeroes is sum_j 32^j * G_0
+        let zeroes_contribution = hash_to_point(&curve, &params, &[false; 200]);
+        let full = hash_to_point(&curve, &params, &bits);
+        let first = hash_to_point(&curve, &params, &bits[..200]);
+        let second = curve.add(&hash_to_point(&curve, &params, &second_segment), &curve.negate(&zeroes_contribution));
+
+        assert_eq!(full, curve.add(&first, &second));
+        assert_eq!(hash_to_point(&curve, &params, &[]), TwistedEdwardsPoint::identity());
+    }
+}
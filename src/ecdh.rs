@@ -0,0 +1,101 @@
+//! Diffie-Hellman key agreement over Jubjub: the shared secret is [8 sk] pk_other, the cofactor
+//! is cleared so that small order components of pk_other don't leak bits of sk. A symmetric key
+//! is derived from the shared secret and the ephemeral public key by a `Kdf`.
+//! `plonk::circuit::ecdh` derives the same keys in circuit.
+
+use blake2_rfc::blake2s::Blake2s;
+
+use jubjub::{FixedGenerators, JubjubEngine, JubjubParams, PrimeOrder, Unknown, edwards::Point};
+
+pub const ECDH_KDF_PERSONALIZATION: &[u8; 8] = b"JubjbKDF";
+
+pub trait Kdf<E: JubjubEngine> {
+    /// Symmetric key from the shared secret and the ephemeral public key.
+    fn derive_key(&self, shared_secret: &Point<E, PrimeOrder>, epk: &Point<E, Unknown>) -> [u8; 32];
+}
+
+/// BLAKE2s-256(personalization, repr(shared_secret) || repr(epk)) with points in the encoding
+/// of `Point::write`.
+pub struct Blake2sKdf {
+    pub personalization: [u8; 8],
+}
+
+impl Default for Blake2sKdf {
+    fn default() -> Self {
+        Blake2sKdf { personalization: *ECDH_KDF_PERSONALIZATION }
+    }
+}
+
+impl<E: JubjubEngine> Kdf<E> for Blake2sKdf {
+    fn derive_key(&self, shared_secret: &Point<E, PrimeOrder>, epk: &Point<E, Unknown>) -> [u8; 32] {
+        let mut input = Vec::with_capacity(64);
+        shared_secret.write(&mut input).expect("writing to a vector never fails");
+        epk.write(&mut input).expect("writing to a vector never fails");
+
+        let mut h = Blake2s::with_params(32, &[], &[], &self.personalization);
+        h.update(&input);
+
+        let mut key = [0u8; 32];
+        key.copy_from_slice(h.finalize().as_ref());
+
+        key
+    }
+}
+
+/// [8 sk] pk_other
+pub fn ka_agree<E: JubjubEngine>(
+    sk: E::Fs,
+    pk_other: &Point<E, Unknown>,
+    params: &E::Params
+) -> Point<E, PrimeOrder> {
+    pk_other.mul(sk, params).mul_by_cofactor(params)
+}
+
+/// Sender side: ephemeral key esk . P_G and the key derived from [8 esk] pk_recipient.
+pub fn sender_key<E: JubjubEngine, K: Kdf<E>>(
+    kdf: &K,
+    esk: E::Fs,
+    pk_recipient: &Point<E, Unknown>,
+    p_g: FixedGenerators,
+    params: &E::Params
+) -> (Point<E, Unknown>, [u8; 32]) {
+    let epk: Point<E, Unknown> = params.generator(p_g).mul(esk, params).into();
+    let key = kdf.derive_key(&ka_agree(esk, pk_recipient, params), &epk);
+
+    (epk, key)
+}
+
+/// Recipient side: the key derived from [8 sk] epk.
+pub fn recipient_key<E: JubjubEngine, K: Kdf<E>>(
+    kdf: &K,
+    sk: E::Fs,
+    epk: &Point<E, Unknown>,
+    params: &E::Params
+) -> [u8; 32] {
+    kdf.derive_key(&ka_agree(sk, epk, params), epk)
+}
+
+#[cfg(test)]
+mod tests {
+    use bellman::pairing::bn256::Bn256;
+    use rand::{Rng, thread_rng};
+
+    use alt_babyjubjub::{AltJubjubBn256, fs::Fs};
+
+    use super::*;
+
+    #[test]
+    fn test_key_agreement() {
+        let rng = &mut thread_rng();
+        let params = &AltJubjubBn256::new();
+        let p_g = FixedGenerators::SpendingKeyGenerator;
+        let kdf = Blake2sKdf::default();
+
+        let sk: Fs = rng.gen();
+        let pk: Point<Bn256, Unknown> = params.generator(p_g).mul(sk, params).into();
+
+        let (epk, key) = sender_key(&kdf, rng.gen(), &pk, p_g, params);
+        assert_eq!(recipient_key(&kdf, sk, &epk, params), key);
+        assert!(recipient_key(&kdf, rng.gen(), &epk, params) != key);
+    }
+}
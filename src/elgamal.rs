@@ -0,0 +1,110 @@
+//! ElGamal encryption over Jubjub: (r . G, M + r . pk) for a point M, in the exponential
+//! variant M = m . G for a scalar m. Exponential ciphertexts are additively homomorphic, but
+//! decryption has to solve a discrete log, so it only works for small messages.
+//! Ciphertexts computed in circuit by `plonk::circuit::elgamal` decrypt with these functions.
+
+use bellman::pairing::ff::PrimeField;
+
+use jubjub::{FixedGenerators, JubjubEngine, JubjubParams, Unknown, edwards::Point};
+
+#[derive(Clone)]
+pub struct ElGamalCiphertext<E: JubjubEngine> {
+    pub c1: Point<E, Unknown>,
+    pub c2: Point<E, Unknown>,
+}
+
+impl<E: JubjubEngine> ElGamalCiphertext<E> {
+    /// Componentwise sum, encrypts the sum of the messages under the same key.
+    pub fn add(&self, other: &Self, params: &E::Params) -> Self {
+        ElGamalCiphertext {
+            c1: self.c1.add(&other.c1, params),
+            c2: self.c2.add(&other.c2, params),
+        }
+    }
+}
+
+pub fn encrypt<E: JubjubEngine>(
+    message: &Point<E, Unknown>,
+    pk: &Point<E, Unknown>,
+    randomness: E::Fs,
+    p_g: FixedGenerators,
+    params: &E::Params
+) -> ElGamalCiphertext<E> {
+    ElGamalCiphertext {
+        c1: params.generator(p_g).mul(randomness, params).into(),
+        c2: message.add(&pk.mul(randomness, params), params),
+    }
+}
+
+pub fn encrypt_exponential<E: JubjubEngine>(
+    message: E::Fs,
+    pk: &Point<E, Unknown>,
+    randomness: E::Fs,
+    p_g: FixedGenerators,
+    params: &E::Params
+) -> ElGamalCiphertext<E> {
+    let message = params.generator(p_g).mul(message, params).into();
+
+    encrypt(&message, pk, randomness, p_g, params)
+}
+
+/// M = c2 - sk . c1
+pub fn decrypt<E: JubjubEngine>(
+    ciphertext: &ElGamalCiphertext<E>,
+    sk: E::Fs,
+    params: &E::Params
+) -> Point<E, Unknown> {
+    ciphertext.c2.add(&ciphertext.c1.mul(sk, params).negate(), params)
+}
+
+/// Finds m <= `max_message` with M = m . G by exhaustive search.
+pub fn decrypt_exponential<E: JubjubEngine>(
+    ciphertext: &ElGamalCiphertext<E>,
+    sk: E::Fs,
+    max_message: u64,
+    p_g: FixedGenerators,
+    params: &E::Params
+) -> Option<u64> {
+    let message = decrypt(ciphertext, sk, params);
+    let generator: Point<E, Unknown> = params.generator(p_g).clone().into();
+
+    let mut candidate = Point::zero();
+    for m in 0..=max_message {
+        if candidate == message {
+            return Some(m);
+        }
+        candidate = candidate.add(&generator, params);
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use bellman::pairing::bn256::Bn256;
+    use rand::{Rng, thread_rng};
+
+    use alt_babyjubjub::{AltJubjubBn256, fs::Fs};
+
+    use super::*;
+
+    #[test]
+    fn test_exponential_elgamal() {
+        let rng = &mut thread_rng();
+        let params = &AltJubjubBn256::new();
+        let p_g = FixedGenerators::SpendingKeyGenerator;
+
+        let sk: Fs = rng.gen();
+        let pk: Point<Bn256, Unknown> = params.generator(p_g).mul(sk, params).into();
+
+        let a = encrypt_exponential(Fs::from_str("300").unwrap(), &pk, rng.gen(), p_g, params);
+        let b = encrypt_exponential(Fs::from_str("45").unwrap(), &pk, rng.gen(), p_g, params);
+        assert_eq!(decrypt_exponential(&a, sk, 1000, p_g, params), Some(300));
+        assert_eq!(decrypt_exponential(&a.add(&b, params), sk, 1000, p_g, params), Some(345));
+        assert_eq!(decrypt_exponential(&a, sk, 299, p_g, params), None);
+        assert_eq!(decrypt_exponential(&a, rng.gen(), 1000, p_g, params), None);
+
+        let message = Point::rand(rng, params);
+        assert!(decrypt(&encrypt(&message, &pk, rng.gen(), p_g, params), sk, params) == message);
+    }
+}
@@ -1,11 +1,16 @@
 use jubjub::{
     JubjubEngine,
+    JubjubParams,
     PrimeOrder,
-    edwards
+    Unknown,
+    edwards,
+    montgomery
 };
 
 use bellman::pairing::ff::{
-    PrimeField
+    Field,
+    PrimeField,
+    PrimeFieldRepr
 };
 
 use tiny_keccak::Keccak;
@@ -180,6 +185,160 @@ pub fn generic_group_hash<E: JubjubEngine, H: GroupHasher>(
     }
 }
 
+/// How `hash_to_curve` maps hash outputs to points.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MapToCurve {
+    /// Decodes digests with an incrementing counter until one is a point, like
+    /// `generic_group_hash`. The number of hash invocations depends on the input.
+    TryAndIncrement,
+    /// Hashes to two field elements and adds their images under `map_to_curve_elligator2`.
+    /// Every field element maps to a point, so the work doesn't depend on the input, and the
+    /// mapping can be verified by `plonk::circuit::hash_to_curve`.
+    Elligator2,
+}
+
+// H(personalization; len(domain) || domain || counter || msg). The domain is length prefixed and
+// the counter has a fixed size, so distinct inputs never share a preimage.
+fn domain_digest<H: GroupHasher>(
+    personalization: &[u8],
+    domain: &[u8],
+    counter: u8,
+    msg: &[u8]
+) -> Vec<u8>
+{
+    assert_eq!(personalization.len(), 8);
+    assert!(domain.len() <= 255, "domain tags are at most 255 bytes");
+
+    let mut h = H::new(personalization);
+    h.update(&[domain.len() as u8]);
+    h.update(domain);
+    h.update(&[counter]);
+    h.update(msg);
+    let h = h.finalize();
+    assert!(h.len() == 32);
+
+    h
+}
+
+fn field_from_le_bytes<F: PrimeField>(bytes: &[u8]) -> F {
+    let mut repr = F::Repr::default();
+    repr.read_le(bytes).expect("bytes fit into the representation");
+
+    F::from_repr(repr).expect("value is smaller than the modulus")
+}
+
+/// Hashes `msg` to `count` field elements. Every element is read from 496 bits of two digests,
+/// so the bias of the reduction is negligible.
+pub fn hash_to_field<E: JubjubEngine, H: GroupHasher>(
+    personalization: &[u8],
+    domain: &[u8],
+    msg: &[u8],
+    count: usize
+) -> Vec<E::Fr>
+{
+    assert!(count <= 128);
+
+    // 2^248
+    let mut shift = <E::Fr as PrimeField>::Repr::default();
+    shift.as_mut()[3] = 1u64 << 56;
+    let shift = E::Fr::from_repr(shift).unwrap();
+
+    (0..count).map(|i| {
+        let mut bytes = [0u8; 32];
+
+        bytes[..31].copy_from_slice(&domain_digest::<H>(personalization, domain, 2 * i as u8, msg)[..31]);
+        let mut u: E::Fr = field_from_le_bytes(&bytes[..]);
+
+        bytes[..31].copy_from_slice(&domain_digest::<H>(personalization, domain, 2 * i as u8 + 1, msg)[..31]);
+        let mut hi: E::Fr = field_from_le_bytes(&bytes[..]);
+        hi.mul_assign(&shift);
+        u.add_assign(&hi);
+
+        u
+    }).collect()
+}
+
+/// The non-square Z of the Elligator 2 map.
+pub fn elligator2_non_residue<E: JubjubEngine>() -> E::Fr {
+    // a generator of the multiplicative group is never a square
+    E::Fr::multiplicative_generator()
+}
+
+/// The Elligator 2 map of `u` to the Montgomery curve y^2 = x^3 + A.x^2 + x, following
+/// RFC 9380, section 6.7.1, and converted to twisted Edwards form. The result is on the curve
+/// but not necessarily in the prime order subgroup.
+pub fn map_to_curve_elligator2<E: JubjubEngine>(
+    u: E::Fr,
+    params: &E::Params
+) -> edwards::Point<E, Unknown>
+{
+    // t = Z.u^2
+    let mut t = u;
+    t.square();
+    t.mul_assign(&elligator2_non_residue::<E>());
+
+    let mut minus_a = *params.montgomery_a();
+    minus_a.negate();
+
+    // x1 = -A / (1 + t), or -A if 1 + t = 0
+    let mut x1 = minus_a;
+    let mut den = t;
+    den.add_assign(&E::Fr::one());
+    if let Some(den_inv) = den.inverse() {
+        x1.mul_assign(&den_inv);
+    }
+
+    // x2 = -x1 - A
+    let mut x2 = minus_a;
+    x2.sub_assign(&x1);
+
+    // g(x2) = t.g(x1), so exactly one of them is a square unless both are zero. The sign of
+    // y tells which one was taken.
+    let m = match montgomery::Point::<E, Unknown>::get_for_x(x1, true, params) {
+        Some(m) => m,
+        None => montgomery::Point::get_for_x(x2, false, params).expect("g(x2) is a square")
+    };
+
+    edwards::Point::from_montgomery(&m, params)
+}
+
+/// Hashes `msg` to a point of prime order, separated from other uses of the curve by
+/// `personalization` (8 bytes, as for `group_hash`) and the `domain` tag. Only
+/// `MapToCurve::Elligator2` can be verified in circuit.
+pub fn hash_to_curve<E: JubjubEngine, H: GroupHasher>(
+    personalization: &[u8],
+    domain: &[u8],
+    msg: &[u8],
+    map: MapToCurve,
+    params: &E::Params
+) -> edwards::Point<E, PrimeOrder>
+{
+    match map {
+        MapToCurve::TryAndIncrement => {
+            for counter in 0u8..=255u8 {
+                let h = domain_digest::<H>(personalization, domain, counter, msg);
+
+                if let Ok(p) = edwards::Point::<E, Unknown>::read(&h[..], params) {
+                    let p = p.mul_by_cofactor(params);
+
+                    if p != edwards::Point::zero() {
+                        return p;
+                    }
+                }
+            }
+
+            panic!("no digest decoded to a point");
+        },
+        MapToCurve::Elligator2 => {
+            let u = hash_to_field::<E, H>(personalization, domain, msg, 2);
+            let p = map_to_curve_elligator2::<E>(u[0], params);
+            let q = map_to_curve_elligator2::<E>(u[1], params);
+
+            p.add(&q, params).mul_by_cofactor(params)
+        }
+    }
+}
+
 #[test]
 fn test_generic_hash() {
     use bellman::pairing::bn256::Bn256;
@@ -222,4 +381,44 @@ fn blake2s_consistency_test() {
     let h = h.finalize().as_ref().to_vec();
     //let reference = hex!("989e1d96f8d977db95b7fcb59d26fe7f66b4e21e84cdb9387b67aa78ebd07ecf");
     //assert_eq!(reference[..], h[..]);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_elligator2_map_is_on_curve() {
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use alt_babyjubjub::AltJubjubBn256;
+    use rand::{XorShiftRng, SeedableRng, Rng};
+
+    let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+    let params = AltJubjubBn256::new();
+
+    let mut inputs: Vec<Fr> = (0..100).map(|_| rng.gen()).collect();
+    inputs.push(Fr::zero());
+    for u in inputs {
+        let p = map_to_curve_elligator2::<Bn256>(u, &params);
+        let (x, y) = p.into_xy();
+        assert!(edwards::Point::<Bn256, Unknown>::from_xy(x, y, &params).is_some());
+
+        let mut minus_u = u;
+        minus_u.negate();
+        assert!(map_to_curve_elligator2::<Bn256>(minus_u, &params) == p);
+    }
+}
+
+#[test]
+fn test_hash_to_curve_domain_separation() {
+    use bellman::pairing::bn256::Bn256;
+    use alt_babyjubjub::AltJubjubBn256;
+
+    let params = AltJubjubBn256::new();
+
+    for &map in [MapToCurve::TryAndIncrement, MapToCurve::Elligator2].iter() {
+        let p = hash_to_curve::<Bn256, BlakeHasher>(b"Hello123", b"domain", b"message", map, &params);
+        assert!(p != edwards::Point::zero());
+        assert!(hash_to_curve::<Bn256, BlakeHasher>(b"Hello123", b"domain", b"message", map, &params) == p);
+        assert!(hash_to_curve::<Bn256, BlakeHasher>(b"Hello124", b"domain", b"message", map, &params) != p);
+        assert!(hash_to_curve::<Bn256, BlakeHasher>(b"Hello123", b"domaim", b"message", map, &params) != p);
+        assert!(hash_to_curve::<Bn256, BlakeHasher>(b"Hello123", b"domain", b"messagf", map, &params) != p);
+        assert!(hash_to_curve::<Bn256, Keccak256Hasher>(b"Hello123", b"domain", b"message", map, &params) != p);
+    }
+}
@@ -0,0 +1,523 @@
+//! Parameters for Jubjub-like curves embedded into the scalar field of other engines.
+//!
+//! `JubjubParamsBuilder` takes the curve constants and the generators, checks them and
+//! computes the window tables, the result is a `CustomJubjubParams` to use as
+//! `JubjubEngine::Params` of a new engine. It builds the built-in parameter sets as well, which
+//! convert from `CustomJubjubParams`. The checks and the table generation are exposed
+//! separately, they produce the same tables as the built-in parameter sets.
+
+use bellman::pairing::ff::{Field, LegendreSymbol, PrimeField, PrimeFieldRepr, SqrtField};
+
+use std::fmt;
+
+use super::{FixedGenerators, JubjubEngine, JubjubParams, PrimeOrder, Unknown, edwards, montgomery};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JubjubParamsError {
+    /// The addition law is only complete for non-square d.
+    EdwardsDIsSquare,
+    InconsistentMontgomeryA,
+    InconsistentScale,
+    /// A^2 - 4 must be non-square.
+    InvalidMontgomeryA,
+    PedersenChunksAllowCollisions,
+    FixedBaseChunksTooFew,
+    NoPedersenGenerators,
+    WrongNumberOfFixedGenerators,
+    NotOnCurve,
+    NotInPrimeOrderSubgroup,
+    NeutralElement,
+    DuplicateGenerator,
+    /// The parameters of the engine have other chunk counts or window size built in.
+    FixedChunkCounts,
+}
+
+impl fmt::Display for JubjubParamsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let description = match *self {
+            JubjubParamsError::EdwardsDIsSquare => "d is a square",
+            JubjubParamsError::InconsistentMontgomeryA => "A doesn't match d",
+            JubjubParamsError::InconsistentScale => "scale doesn't match d",
+            JubjubParamsError::InvalidMontgomeryA => "A^2 - 4 is a square",
+            JubjubParamsError::PedersenChunksAllowCollisions => "too many Pedersen hash chunks per generator",
+            JubjubParamsError::FixedBaseChunksTooFew => "too few fixed base chunks per generator",
+            JubjubParamsError::NoPedersenGenerators => "no Pedersen hash generators",
+            JubjubParamsError::WrongNumberOfFixedGenerators => "wrong number of fixed generators",
+            JubjubParamsError::NotOnCurve => "generator is not on the curve",
+            JubjubParamsError::NotInPrimeOrderSubgroup => "generator is not in the prime order subgroup",
+            JubjubParamsError::NeutralElement => "generator is the neutral element",
+            JubjubParamsError::DuplicateGenerator => "duplicate generator",
+            JubjubParamsError::FixedChunkCounts => "chunk counts differ from the ones of the engine",
+        };
+
+        write!(f, "{}", description)
+    }
+}
+
+impl ::std::error::Error for JubjubParamsError {}
+
+/// Checks the constants of the curve -x^2 + y^2 = 1 + d x^2 y^2 and of the Montgomery curve
+/// y^2 = x^3 + A x^2 + x with scaling factor `scale` between them.
+pub fn check_curve_constants<E: JubjubEngine>(
+    edwards_d: &E::Fr,
+    montgomery_a: &E::Fr,
+    scale: &E::Fr
+) -> Result<(), JubjubParamsError> {
+    if edwards_d.legendre() != LegendreSymbol::QuadraticNonResidue {
+        return Err(JubjubParamsError::EdwardsDIsSquare);
+    }
+
+    // a - d and a + d for a = -1
+    let mut a_minus_d = E::Fr::one();
+    a_minus_d.add_assign(edwards_d);
+    a_minus_d.negate();
+    let mut a_plus_d = *edwards_d;
+    a_plus_d.sub_assign(&E::Fr::one());
+
+    // A (a - d) = 2 (a + d)
+    let mut lhs = *montgomery_a;
+    lhs.mul_assign(&a_minus_d);
+    a_plus_d.double();
+    if lhs != a_plus_d {
+        return Err(JubjubParamsError::InconsistentMontgomeryA);
+    }
+
+    // scale^2 (a - d) = 4
+    let mut lhs = *scale;
+    lhs.square();
+    lhs.mul_assign(&a_minus_d);
+    if lhs != E::Fr::from_str("4").unwrap() {
+        return Err(JubjubParamsError::InconsistentScale);
+    }
+
+    let mut tmp = *montgomery_a;
+    tmp.square();
+    tmp.sub_assign(&E::Fr::from_str("4").unwrap());
+    if tmp.legendre() != LegendreSymbol::QuadraticNonResidue {
+        return Err(JubjubParamsError::InvalidMontgomeryA);
+    }
+
+    Ok(())
+}
+
+/// Checks that Pedersen hash segments of `chunks` 4 bit chunks can't collide, i.e. the sum of
+/// the chunk values stays below (r - 1) / 2.
+pub fn check_pedersen_hash_chunks<E: JubjubEngine>(chunks: usize) -> Result<(), JubjubParamsError> {
+    let mut max = E::Fs::char();
+    max.sub_noborrow(&E::Fs::one().into_repr());
+    max.div2();
+
+    let mut cur = E::Fs::one().into_repr();
+    let mut pacc = E::Fs::zero().into_repr();
+    let mut nacc = E::Fs::char();
+    for _ in 0..chunks {
+        // tmp = cur * 4
+        let mut tmp = cur;
+        tmp.mul2();
+        tmp.mul2();
+
+        pacc.add_nocarry(&tmp);
+        nacc.sub_noborrow(&tmp);
+        if !(pacc < max && pacc < nacc) {
+            return Err(JubjubParamsError::PedersenChunksAllowCollisions);
+        }
+
+        // cur = cur * 16
+        for _ in 0..4 {
+            cur.mul2();
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that `chunks` 3 bit chunks cover a full scalar.
+pub fn check_fixed_base_chunks<E: JubjubEngine>(chunks: usize) -> Result<(), JubjubParamsError> {
+    if chunks * 3 < E::Fs::NUM_BITS as usize {
+        return Err(JubjubParamsError::FixedBaseChunksTooFew);
+    }
+
+    Ok(())
+}
+
+/// The generator (x, y) if it is on the curve and in the prime order subgroup. `params` only
+/// need the curve constants.
+pub fn check_generator<E: JubjubEngine>(
+    x: E::Fr,
+    y: E::Fr,
+    params: &E::Params
+) -> Result<edwards::Point<E, PrimeOrder>, JubjubParamsError> {
+    let p = edwards::Point::<E, Unknown>::from_xy(x, y, params).ok_or(JubjubParamsError::NotOnCurve)?;
+    let p = p.as_prime_order(params).ok_or(JubjubParamsError::NotInPrimeOrderSubgroup)?;
+    if p == edwards::Point::zero() {
+        return Err(JubjubParamsError::NeutralElement);
+    }
+
+    Ok(p)
+}
+
+fn check_distinct<E: JubjubEngine>(generators: &[edwards::Point<E, PrimeOrder>]) -> Result<(), JubjubParamsError> {
+    for (i, p1) in generators.iter().enumerate() {
+        if generators.iter().skip(i + 1).any(|p2| p1 == p2) {
+            return Err(JubjubParamsError::DuplicateGenerator);
+        }
+    }
+
+    Ok(())
+}
+
+/// Tables of j * 2^(window * i) . g for j < 2^window, for every generator.
+pub fn pedersen_hash_exp_table<E: JubjubEngine>(
+    generators: &[edwards::Point<E, PrimeOrder>],
+    window: u32,
+    params: &E::Params
+) -> Vec<Vec<Vec<edwards::Point<E, PrimeOrder>>>> {
+    let mut pedersen_hash_exp = vec![];
+    for g in generators.iter() {
+        let mut g = g.clone();
+        let mut tables = vec![];
+
+        let mut num_bits = 0;
+        while num_bits <= E::Fs::NUM_BITS {
+            let mut table = Vec::with_capacity(1 << window);
+            let mut base = edwards::Point::zero();
+            for _ in 0..(1 << window) {
+                table.push(base.clone());
+                base = base.add(&g, params);
+            }
+            tables.push(table);
+            num_bits += window;
+
+            for _ in 0..window {
+                g = g.double(params);
+            }
+        }
+
+        pedersen_hash_exp.push(tables);
+    }
+
+    pedersen_hash_exp
+}
+
+/// Montgomery coordinates of g, 2g, 3g and 4g for every chunk of every segment, the chunks are
+/// 4 bits apart.
+pub fn pedersen_circuit_generators<E: JubjubEngine>(
+    generators: &[edwards::Point<E, PrimeOrder>],
+    chunks_per_generator: usize,
+    params: &E::Params
+) -> Vec<Vec<Vec<(E::Fr, E::Fr)>>> {
+    let mut pedersen_circuit_generators = vec![];
+    for gen in generators.iter() {
+        let mut gen = montgomery::Point::from_edwards(gen, params);
+        let mut windows = vec![];
+        for _ in 0..chunks_per_generator {
+            let mut coeffs = vec![];
+            let mut g = gen.clone();
+            for _ in 0..4 {
+                coeffs.push(g.into_xy().expect("cannot produce O"));
+                g = g.add(&gen, params);
+            }
+            windows.push(coeffs);
+
+            for _ in 0..4 {
+                gen = gen.double(params);
+            }
+        }
+        pedersen_circuit_generators.push(windows);
+    }
+
+    pedersen_circuit_generators
+}
+
+/// Edwards coordinates of 0, g, ..., 7g for every 3 bit window of every generator.
+pub fn fixed_base_circuit_generators<E: JubjubEngine>(
+    generators: &[edwards::Point<E, PrimeOrder>],
+    chunks_per_generator: usize,
+    params: &E::Params
+) -> Vec<Vec<Vec<(E::Fr, E::Fr)>>> {
+    let mut fixed_base_circuit_generators = vec![];
+    for gen in generators.iter() {
+        let mut gen = gen.clone();
+        let mut windows = vec![];
+        for _ in 0..chunks_per_generator {
+            let mut coeffs = vec![(E::Fr::zero(), E::Fr::one())];
+            let mut g = gen.clone();
+            for _ in 0..7 {
+                coeffs.push(g.into_xy());
+                g = g.add(&gen, params);
+            }
+            windows.push(coeffs);
+
+            // gen = gen * 8
+            gen = g;
+        }
+        fixed_base_circuit_generators.push(windows);
+    }
+
+    fixed_base_circuit_generators
+}
+
+pub struct CustomJubjubParams<E: JubjubEngine> {
+    pub(crate) edwards_d: E::Fr,
+    pub(crate) montgomery_a: E::Fr,
+    pub(crate) montgomery_2a: E::Fr,
+    pub(crate) scale: E::Fr,
+
+    pub(crate) pedersen_hash_chunks_per_generator: usize,
+    pub(crate) fixed_base_chunks_per_generator: usize,
+    pub(crate) pedersen_hash_exp_window_size: u32,
+
+    pub(crate) pedersen_hash_generators: Vec<edwards::Point<E, PrimeOrder>>,
+    pub(crate) pedersen_hash_exp: Vec<Vec<Vec<edwards::Point<E, PrimeOrder>>>>,
+    pub(crate) pedersen_circuit_generators: Vec<Vec<Vec<(E::Fr, E::Fr)>>>,
+
+    pub(crate) fixed_base_generators: Vec<edwards::Point<E, PrimeOrder>>,
+    pub(crate) fixed_base_circuit_generators: Vec<Vec<Vec<(E::Fr, E::Fr)>>>,
+}
+
+impl<E: JubjubEngine> JubjubParams<E> for CustomJubjubParams<E> {
+    fn edwards_d(&self) -> &E::Fr { &self.edwards_d }
+    fn montgomery_a(&self) -> &E::Fr { &self.montgomery_a }
+    fn montgomery_2a(&self) -> &E::Fr { &self.montgomery_2a }
+    fn scale(&self) -> &E::Fr { &self.scale }
+    fn pedersen_hash_generators(&self) -> &[edwards::Point<E, PrimeOrder>] {
+        &self.pedersen_hash_generators
+    }
+    fn pedersen_hash_exp_table(&self) -> &[Vec<Vec<edwards::Point<E, PrimeOrder>>>] {
+        &self.pedersen_hash_exp
+    }
+    fn pedersen_hash_chunks_per_generator(&self) -> usize {
+        self.pedersen_hash_chunks_per_generator
+    }
+    fn fixed_base_chunks_per_generator(&self) -> usize {
+        self.fixed_base_chunks_per_generator
+    }
+    fn pedersen_circuit_generators(&self) -> &[Vec<Vec<(E::Fr, E::Fr)>>] {
+        &self.pedersen_circuit_generators
+    }
+    fn generator(&self, base: FixedGenerators) -> &edwards::Point<E, PrimeOrder>
+    {
+        &self.fixed_base_generators[base as usize]
+    }
+    fn circuit_generators(&self, base: FixedGenerators) -> &[Vec<(E::Fr, E::Fr)>]
+    {
+        &self.fixed_base_circuit_generators[base as usize][..]
+    }
+    fn pedersen_hash_exp_window_size(&self) -> u32 {
+        self.pedersen_hash_exp_window_size
+    }
+}
+
+/// Builder of `CustomJubjubParams`. The generators are affine Edwards coordinates, the fixed
+/// ones are indexed by `FixedGenerators`.
+pub struct JubjubParamsBuilder<E: JubjubEngine> {
+    edwards_d: E::Fr,
+    montgomery_a: E::Fr,
+    scale: E::Fr,
+
+    pedersen_hash_chunks_per_generator: usize,
+    fixed_base_chunks_per_generator: usize,
+    pedersen_hash_exp_window_size: u32,
+
+    pedersen_hash_generators: Vec<(E::Fr, E::Fr)>,
+    fixed_base_generators: Vec<(E::Fr, E::Fr)>,
+}
+
+impl<E: JubjubEngine> JubjubParamsBuilder<E> {
+    /// Chunk counts default to the smallest that cover a scalar, the exp window to 8 bits.
+    pub fn new(edwards_d: E::Fr, montgomery_a: E::Fr, scale: E::Fr) -> Self {
+        let num_bits = E::Fs::NUM_BITS as usize;
+
+        Self {
+            edwards_d,
+            montgomery_a,
+            scale,
+            pedersen_hash_chunks_per_generator: (num_bits - 1) / 4,
+            fixed_base_chunks_per_generator: (num_bits + 2) / 3,
+            pedersen_hash_exp_window_size: 8,
+            pedersen_hash_generators: vec![],
+            fixed_base_generators: vec![],
+        }
+    }
+
+    pub fn pedersen_hash_generators(mut self, generators: Vec<(E::Fr, E::Fr)>) -> Self {
+        self.pedersen_hash_generators = generators;
+        self
+    }
+
+    pub fn fixed_base_generators(mut self, generators: Vec<(E::Fr, E::Fr)>) -> Self {
+        self.fixed_base_generators = generators;
+        self
+    }
+
+    pub fn pedersen_hash_chunks_per_generator(mut self, chunks: usize) -> Self {
+        self.pedersen_hash_chunks_per_generator = chunks;
+        self
+    }
+
+    pub fn fixed_base_chunks_per_generator(mut self, chunks: usize) -> Self {
+        self.fixed_base_chunks_per_generator = chunks;
+        self
+    }
+
+    pub fn pedersen_hash_exp_window_size(mut self, window: u32) -> Self {
+        assert!(window > 0 && window <= 16);
+        self.pedersen_hash_exp_window_size = window;
+        self
+    }
+
+    fn check(&self) -> Result<(), JubjubParamsError> {
+        check_curve_constants::<E>(&self.edwards_d, &self.montgomery_a, &self.scale)?;
+        check_pedersen_hash_chunks::<E>(self.pedersen_hash_chunks_per_generator)?;
+        check_fixed_base_chunks::<E>(self.fixed_base_chunks_per_generator)?;
+
+        if self.pedersen_hash_generators.is_empty() {
+            return Err(JubjubParamsError::NoPedersenGenerators);
+        }
+        if self.fixed_base_generators.len() != FixedGenerators::Max as usize {
+            return Err(JubjubParamsError::WrongNumberOfFixedGenerators);
+        }
+
+        Ok(())
+    }
+}
+
+impl<E: JubjubEngine> JubjubParamsBuilder<E> where E::Params: From<CustomJubjubParams<E>> {
+    /// The parameters with the tables, `CustomJubjubParams` for a new engine. For an engine with
+    /// built-in parameters the chunk counts and the window size have to be the built-in ones.
+    pub fn build(self) -> Result<E::Params, JubjubParamsError> {
+        self.check()?;
+
+        // the checks and the tables need the curve constants only
+        let without_tables: E::Params = self.without_tables().into();
+        if without_tables.pedersen_hash_chunks_per_generator() != self.pedersen_hash_chunks_per_generator
+            || without_tables.fixed_base_chunks_per_generator() != self.fixed_base_chunks_per_generator
+            || without_tables.pedersen_hash_exp_window_size() != self.pedersen_hash_exp_window_size {
+            return Err(JubjubParamsError::FixedChunkCounts);
+        }
+
+        let pedersen_hash_generators = self.pedersen_hash_generators.iter()
+            .map(|&(x, y)| check_generator::<E>(x, y, &without_tables))
+            .collect::<Result<Vec<_>, _>>()?;
+        check_distinct(&pedersen_hash_generators)?;
+        let fixed_base_generators = self.fixed_base_generators.iter()
+            .map(|&(x, y)| check_generator::<E>(x, y, &without_tables))
+            .collect::<Result<Vec<_>, _>>()?;
+        check_distinct(&fixed_base_generators)?;
+
+        let mut params = self.without_tables();
+        params.pedersen_hash_exp = pedersen_hash_exp_table::<E>(&pedersen_hash_generators, params.pedersen_hash_exp_window_size, &without_tables);
+        params.pedersen_circuit_generators = pedersen_circuit_generators::<E>(&pedersen_hash_generators, params.pedersen_hash_chunks_per_generator, &without_tables);
+        params.fixed_base_circuit_generators = fixed_base_circuit_generators::<E>(&fixed_base_generators, params.fixed_base_chunks_per_generator, &without_tables);
+        params.pedersen_hash_generators = pedersen_hash_generators;
+        params.fixed_base_generators = fixed_base_generators;
+
+        Ok(params.into())
+    }
+
+    fn without_tables(&self) -> CustomJubjubParams<E> {
+        let mut montgomery_2a = self.montgomery_a;
+        montgomery_2a.double();
+
+        CustomJubjubParams {
+            edwards_d: self.edwards_d,
+            montgomery_a: self.montgomery_a,
+            montgomery_2a,
+            scale: self.scale,
+            pedersen_hash_chunks_per_generator: self.pedersen_hash_chunks_per_generator,
+            fixed_base_chunks_per_generator: self.fixed_base_chunks_per_generator,
+            pedersen_hash_exp_window_size: self.pedersen_hash_exp_window_size,
+            pedersen_hash_generators: vec![],
+            pedersen_hash_exp: vec![],
+            pedersen_circuit_generators: vec![],
+            fixed_base_generators: vec![],
+            fixed_base_circuit_generators: vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use rand::{Rng, XorShiftRng, SeedableRng};
+
+    use alt_babyjubjub::AltJubjubBn256;
+    use bellman::pairing::bls12_381::Bls12;
+    use jubjub::JubjubBls12;
+
+    use super::*;
+
+    #[test]
+    fn test_checks_accept_baby_jubjub() {
+        let params = AltJubjubBn256::new();
+        assert_eq!(check_curve_constants::<Bn256>(params.edwards_d(), params.montgomery_a(), params.scale()), Ok(()));
+        assert_eq!(check_pedersen_hash_chunks::<Bn256>(params.pedersen_hash_chunks_per_generator()), Ok(()));
+        assert_eq!(check_fixed_base_chunks::<Bn256>(params.fixed_base_chunks_per_generator()), Ok(()));
+
+        let mut wrong_scale = *params.scale();
+        wrong_scale.double();
+        assert_eq!(check_curve_constants::<Bn256>(params.edwards_d(), params.montgomery_a(), &wrong_scale), Err(JubjubParamsError::InconsistentScale));
+        assert_eq!(check_curve_constants::<Bn256>(&Fr::from_str("4").unwrap(), params.montgomery_a(), params.scale()), Err(JubjubParamsError::EdwardsDIsSquare));
+        assert_eq!(check_pedersen_hash_chunks::<Bn256>(100), Err(JubjubParamsError::PedersenChunksAllowCollisions));
+        assert_eq!(check_fixed_base_chunks::<Bn256>(params.fixed_base_chunks_per_generator() - 1), Err(JubjubParamsError::FixedBaseChunksTooFew));
+
+        // defaults of the builder are the same as the built-in chunk counts
+        let builder = JubjubParamsBuilder::<Bn256>::new(*params.edwards_d(), *params.montgomery_a(), *params.scale());
+        assert_eq!(builder.pedersen_hash_chunks_per_generator, params.pedersen_hash_chunks_per_generator());
+        assert_eq!(builder.fixed_base_chunks_per_generator, params.fixed_base_chunks_per_generator());
+    }
+
+    #[test]
+    fn test_check_generator() {
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = AltJubjubBn256::new();
+
+        let (x, y) = params.generator(FixedGenerators::SpendingKeyGenerator).into_xy();
+        assert!(check_generator::<Bn256>(x, y, &params).unwrap() == *params.generator(FixedGenerators::SpendingKeyGenerator));
+
+        let mut minus_one = Fr::one();
+        minus_one.negate();
+        assert_eq!(check_generator::<Bn256>(Fr::zero(), minus_one, &params).err(), Some(JubjubParamsError::NotInPrimeOrderSubgroup));
+        assert_eq!(check_generator::<Bn256>(Fr::zero(), Fr::one(), &params).err(), Some(JubjubParamsError::NeutralElement));
+        assert_eq!(check_generator::<Bn256>(rng.gen(), rng.gen(), &params).err(), Some(JubjubParamsError::NotOnCurve));
+    }
+
+    #[test]
+    fn test_tables_match_built_in_params() {
+        let params = AltJubjubBn256::new();
+
+        let exp = pedersen_hash_exp_table::<Bn256>(params.pedersen_hash_generators(), params.pedersen_hash_exp_window_size(), &params);
+        assert!(&exp[..] == params.pedersen_hash_exp_table());
+
+        let circuit = pedersen_circuit_generators::<Bn256>(params.pedersen_hash_generators(), params.pedersen_hash_chunks_per_generator(), &params);
+        assert_eq!(&circuit[..], params.pedersen_circuit_generators());
+
+        let p_g = FixedGenerators::NoteCommitmentRandomness;
+        let fixed = fixed_base_circuit_generators::<Bn256>(&[params.generator(p_g).clone()], params.fixed_base_chunks_per_generator(), &params);
+        assert_eq!(&fixed[0][..], params.circuit_generators(p_g));
+    }
+
+    #[test]
+    fn test_build_jubjub() {
+        let expected = JubjubBls12::new();
+        let generators = |points: &[edwards::Point<Bls12, PrimeOrder>]| points.iter().map(|p| p.into_xy()).collect::<Vec<_>>();
+        let builder = || JubjubParamsBuilder::<Bls12>::new(*expected.edwards_d(), *expected.montgomery_a(), *expected.scale())
+            .pedersen_hash_generators(generators(expected.pedersen_hash_generators()))
+            .fixed_base_generators(generators(&expected.fixed_base_generators));
+
+        // the default of 62 chunks is not the one of JubjubBls12
+        assert_eq!(builder().build().err(), Some(JubjubParamsError::FixedChunkCounts));
+
+        let params = builder().pedersen_hash_chunks_per_generator(63).build().unwrap();
+        assert_eq!(params.montgomery_2a(), expected.montgomery_2a());
+        assert!(params.pedersen_hash_generators() == expected.pedersen_hash_generators());
+        assert!(params.pedersen_hash_exp_table() == expected.pedersen_hash_exp_table());
+        assert_eq!(params.pedersen_circuit_generators(), expected.pedersen_circuit_generators());
+        for i in 0..FixedGenerators::Max as usize {
+            assert!(params.fixed_base_generators[i] == expected.fixed_base_generators[i]);
+            assert_eq!(params.fixed_base_circuit_generators[i], expected.fixed_base_circuit_generators[i]);
+        }
+
+        ::jubjub::tests::test_suite::<Bls12>(&params);
+    }
+}
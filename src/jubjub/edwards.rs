@@ -18,6 +18,11 @@ use rand::{
     Rng
 };
 
+use bellman::worker::Worker;
+
+use rand_core::{CryptoRng, RngCore};
+use random::RngCoreAdapter;
+
 use std::marker::PhantomData;
 
 use std::io::{
@@ -120,6 +125,18 @@ impl<E: JubjubEngine> Point<E, Unknown> {
         }
     }
 
+    /// Inverse of `to_bytes`. Unlike `read` this rejects the encodings that are not canonical,
+    /// i.e. y not reduced or the sign bit set for x = 0, so every point has exactly one encoding.
+    pub fn from_bytes(bytes: &[u8; 32], params: &E::Params) -> Option<Self>
+    {
+        let p = Self::read(&bytes[..], params).ok()?;
+        if p.to_bytes() != *bytes {
+            return None;
+        }
+
+        Some(p)
+    }
+
     pub fn from_xy(x: E::Fr, y: E::Fr, params: &E::Params) -> Option<Self>
     {
         // check that a point is on curve
@@ -270,6 +287,26 @@ impl<E: JubjubEngine> Point<E, Unknown> {
             }
         }
     }
+
+    /// A random point, not necessarily in the prime order subgroup.
+    pub fn random<R: RngCore + CryptoRng + ?Sized>(rng: &mut R, params: &E::Params) -> Self
+    {
+        Self::rand(&mut RngCoreAdapter(rng), params)
+    }
+}
+
+impl<E: JubjubEngine> Point<E, PrimeOrder> {
+    /// A random point of prime order, never the identity.
+    pub fn random<R: RngCore + CryptoRng + ?Sized>(rng: &mut R, params: &E::Params) -> Self
+    {
+        loop {
+            let p = Point::<E, Unknown>::random(rng, params).mul_by_cofactor(params);
+
+            if p != Point::zero() {
+                return p;
+            }
+        }
+    }
 }
 
 impl<E: JubjubEngine, Subgroup> Point<E, Subgroup> {
@@ -292,6 +329,15 @@ impl<E: JubjubEngine, Subgroup> Point<E, Subgroup> {
         y_repr.write_le(writer)
     }
 
+    /// The encoding of `write` as an array: y in little endian with the parity of x in the top bit.
+    pub fn to_bytes(&self) -> [u8; 32]
+    {
+        let mut bytes = [0u8; 32];
+        self.write(&mut bytes[..]).expect("32 bytes fit into the array");
+
+        bytes
+    }
+
     /// Convert from a Montgomery point
     pub fn from_montgomery(
         m: &montgomery::Point<E, Subgroup>,
@@ -404,13 +450,47 @@ impl<E: JubjubEngine, Subgroup> Point<E, Subgroup> {
     /// Attempts to cast this as a prime order element, failing if it's
     /// not in the prime order subgroup.
     pub fn as_prime_order(&self, params: &E::Params) -> Option<Point<E, PrimeOrder>> {
-        if self.mul(E::Fs::char(), params) == Point::zero() {
+        if self.is_torsion_free(params) {
             Some(convert_subgroup(self))
         } else {
             None
         }
     }
 
+    /// Whether the point has no small order component, i.e. it is
+    /// in the prime order subgroup.
+    pub fn is_torsion_free(&self, params: &E::Params) -> bool {
+        self.mul(E::Fs::char(), params) == Point::zero()
+    }
+
+    /// Whether the point is in the subgroup of order 8.
+    pub fn is_small_order(&self, params: &E::Params) -> bool {
+        self.double(params).double(params).double(params) == Point::zero()
+    }
+
+    /// Multiplies by the cofactor. The result is in the prime order
+    /// subgroup, but points that already were in it change as well,
+    /// see `into_subgroup` for the projection that keeps them.
+    #[must_use]
+    pub fn clear_cofactor(&self, params: &E::Params) -> Point<E, PrimeOrder> {
+        convert_subgroup(&self.double(params).double(params).double(params))
+    }
+
+    /// The prime order component of the point. Points in the prime
+    /// order subgroup are returned unchanged.
+    pub fn into_subgroup(self, params: &E::Params) -> Point<E, PrimeOrder> {
+        // P = 8^-1 . (8 . P) for P of prime order
+        let cofactor = E::Fs::from_repr(<E::Fs as PrimeField>::Repr::from(8)).unwrap();
+        let cofactor_inv = cofactor.inverse().unwrap();
+
+        self.clear_cofactor(params).mul(cofactor_inv.into_repr(), params)
+    }
+
+    /// Forgets the subgroup of the point.
+    pub fn as_unknown(&self) -> Point<E, Unknown> {
+        convert_subgroup(self)
+    }
+
     pub fn zero() -> Self {
         Point {
             x: E::Fr::zero(),
@@ -434,6 +514,33 @@ impl<E: JubjubEngine, Subgroup> Point<E, Subgroup> {
         (x, y)
     }
 
+    /// Brings all points to Z = 1 with a single inversion (Montgomery's trick), which makes
+    /// the following `into_xy` calls cheap.
+    pub fn batch_normalize(points: &mut [Self])
+    {
+        // prefix products of Z
+        let mut products = Vec::with_capacity(points.len());
+        let mut acc = E::Fr::one();
+        for p in points.iter() {
+            products.push(acc);
+            acc.mul_assign(&p.z);
+        }
+
+        // Z is never zero for points on the curve
+        let mut acc_inv = acc.inverse().expect("Z coordinates are not zero");
+        for (p, product) in points.iter_mut().zip(products.into_iter()).rev() {
+            // 1 / Z_i = (Z_0 * ... * Z_{i-1}) / (Z_0 * ... * Z_i)
+            let mut zinv = acc_inv;
+            zinv.mul_assign(&product);
+            acc_inv.mul_assign(&p.z);
+
+            p.x.mul_assign(&zinv);
+            p.y.mul_assign(&zinv);
+            p.t.mul_assign(&zinv);
+            p.z = E::Fr::one();
+        }
+    }
+
     #[must_use]
     pub fn negate(&self) -> Self {
         let mut p = self.clone();
@@ -608,3 +715,185 @@ impl<E: JubjubEngine, Subgroup> Point<E, Subgroup> {
         res
     }
 }
+
+// Pippenger's bucket method: scalars are cut into windows of `c` bits, for every window the
+// bases are put into the buckets by their digits and the buckets are summed up with a running
+// sum, so a window costs about n + 2^(c+1) additions instead of n multiplications.
+
+fn multiexp_window_size(num_bases: usize) -> usize {
+    if num_bases < 32 {
+        3
+    } else {
+        (num_bases as f64).ln().ceil() as usize
+    }
+}
+
+fn get_window(repr: &[u64], start: usize, c: usize) -> usize {
+    let limb = start / 64;
+    let offset = start % 64;
+
+    let mut window = repr[limb] >> offset;
+    if offset + c > 64 && limb + 1 < repr.len() {
+        window |= repr[limb + 1] << (64 - offset);
+    }
+
+    (window & ((1u64 << c) - 1)) as usize
+}
+
+fn multiexp_window<E: JubjubEngine, Subgroup>(
+    bases: &[Point<E, Subgroup>],
+    scalars: &[<E::Fs as PrimeField>::Repr],
+    start: usize,
+    c: usize,
+    params: &E::Params
+) -> Point<E, Subgroup>
+{
+    // bucket i holds the bases with digit i + 1
+    let mut buckets = vec![Point::zero(); (1 << c) - 1];
+    for (base, scalar) in bases.iter().zip(scalars.iter()) {
+        let digit = get_window(scalar.as_ref(), start, c);
+        if digit != 0 {
+            buckets[digit - 1] = buckets[digit - 1].add(base, params);
+        }
+    }
+
+    // sum of i * bucket_i as the sum of the suffix sums
+    let mut running_sum = Point::zero();
+    let mut result = Point::zero();
+    for bucket in buckets.iter().rev() {
+        running_sum = running_sum.add(bucket, params);
+        result = result.add(&running_sum, params);
+    }
+
+    result
+}
+
+fn combine_windows<E: JubjubEngine, Subgroup>(
+    window_sums: &[Point<E, Subgroup>],
+    c: usize,
+    params: &E::Params
+) -> Point<E, Subgroup>
+{
+    let mut result = Point::zero();
+    for window_sum in window_sums.iter().rev() {
+        for _ in 0..c {
+            result = result.double(params);
+        }
+        result = result.add(window_sum, params);
+    }
+
+    result
+}
+
+/// Sum of scalars[i] . bases[i] by Pippenger's method.
+pub fn multiexp<E: JubjubEngine, Subgroup>(
+    bases: &[Point<E, Subgroup>],
+    scalars: &[E::Fs],
+    params: &E::Params
+) -> Point<E, Subgroup>
+{
+    assert_eq!(bases.len(), scalars.len());
+
+    let scalars: Vec<_> = scalars.iter().map(|s| s.into_repr()).collect();
+    let c = multiexp_window_size(bases.len());
+    let window_sums: Vec<_> = (0..(E::Fs::NUM_BITS as usize)).step_by(c).map(|start| {
+        multiexp_window(bases, &scalars, start, c, params)
+    }).collect();
+
+    combine_windows(&window_sums, c, params)
+}
+
+/// Same as `multiexp`, but the windows are processed in parallel by the `worker`.
+pub fn multiexp_multicore<E, Subgroup>(
+    bases: &[Point<E, Subgroup>],
+    scalars: &[E::Fs],
+    params: &E::Params,
+    worker: &Worker
+) -> Point<E, Subgroup>
+    where E: JubjubEngine,
+          E::Params: Sync,
+          Subgroup: Send + Sync
+{
+    assert_eq!(bases.len(), scalars.len());
+
+    let scalars: Vec<_> = scalars.iter().map(|s| s.into_repr()).collect();
+    let c = multiexp_window_size(bases.len());
+    let starts: Vec<usize> = (0..(E::Fs::NUM_BITS as usize)).step_by(c).collect();
+    let mut window_sums = vec![Point::zero(); starts.len()];
+
+    worker.scope(starts.len(), |scope, chunk| {
+        for (sums, starts) in window_sums.chunks_mut(chunk).zip(starts.chunks(chunk)) {
+            let scalars = &scalars;
+            scope.spawn(move |_| {
+                for (sum, start) in sums.iter_mut().zip(starts.iter()) {
+                    *sum = multiexp_window(bases, scalars, *start, c, params);
+                }
+            });
+        }
+    });
+
+    combine_windows(&window_sums, c, params)
+}
+
+/// Multiples of a fixed base for every window of the scalar: entry [i][j] is
+/// j * 2^(window * i) . base, so a multiplication takes one addition per window and no doublings.
+pub struct FixedBaseTable<E: JubjubEngine, Subgroup> {
+    window: usize,
+    windows: Vec<Vec<Point<E, Subgroup>>>,
+}
+
+impl<E: JubjubEngine, Subgroup> Point<E, Subgroup> {
+    /// Precomputes the table for multiplications of this point by `window` bits at a time.
+    /// The table takes 2^window * ceil(NUM_BITS / window) points.
+    pub fn precompute(&self, window: usize, params: &E::Params) -> FixedBaseTable<E, Subgroup>
+    {
+        assert!(window > 0 && window <= 16);
+
+        let num_windows = (E::Fs::NUM_BITS as usize + window - 1) / window;
+        let mut windows = Vec::with_capacity(num_windows);
+        let mut base = self.clone();
+        for _ in 0..num_windows {
+            let mut multiples = Vec::with_capacity(1 << window);
+            let mut acc = Point::zero();
+            for _ in 0..(1 << window) {
+                multiples.push(acc.clone());
+                acc = acc.add(&base, params);
+            }
+            windows.push(multiples);
+
+            // acc is 2^window . base now
+            base = acc;
+        }
+
+        FixedBaseTable {
+            window,
+            windows,
+        }
+    }
+}
+
+impl<E: JubjubEngine, Subgroup> FixedBaseTable<E, Subgroup> {
+    pub fn window(&self) -> usize {
+        self.window
+    }
+
+    /// scalar . base
+    pub fn mul_precomputed<S: Into<<E::Fs as PrimeField>::Repr>>(
+        &self,
+        scalar: S,
+        params: &E::Params
+    ) -> Point<E, Subgroup>
+    {
+        let scalar = scalar.into();
+
+        let mut result = Point::zero();
+        for (i, multiples) in self.windows.iter().enumerate() {
+            let digit = get_window(scalar.as_ref(), i * self.window, self.window);
+            if digit != 0 {
+                result = result.add(&multiples[digit], params);
+            }
+        }
+
+        result
+    }
+}
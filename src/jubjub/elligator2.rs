@@ -0,0 +1,216 @@
+//! Elligator 2 encodings of points as strings indistinguishable from random bytes.
+//!
+//! `decode` reduces 32 bytes to a field element and applies `group_hash::map_to_curve_elligator2`,
+//! so any string decodes to a point. `encode` inverts the map: about half of the points have a
+//! representative, for the others it returns `None` and the caller starts over with a fresh
+//! point, e.g. a new ephemeral key. The field element is lifted to 256 bits by adding a random
+//! multiple of the modulus, so the unused top bits don't give the encoding away.
+
+use bellman::pairing::ff::{Field, PrimeField, PrimeFieldRepr, SqrtField};
+
+use rand::Rng;
+
+use group_hash::{elligator2_non_residue, map_to_curve_elligator2};
+use super::{JubjubEngine, JubjubParams, Unknown, edwards, montgomery};
+
+/// The representative u of `p` with even `u`, such that `map_to_curve_elligator2(u) = p`.
+/// `-u` is a representative as well.
+pub fn representative<E: JubjubEngine, Subgroup>(
+    p: &edwards::Point<E, Subgroup>,
+    params: &E::Params
+) -> Option<E::Fr>
+{
+    let (x, y) = montgomery::Point::from_edwards(p, params).into_xy()?;
+
+    // x = x1 = -A / (1 + Z.u^2) for odd y, and x = x2 = -x1 - A otherwise:
+    //      u^2 = -(x + A) / (Z.x)   or   u^2 = -x / (Z.(x + A))
+    let mut x_plus_a = x;
+    x_plus_a.add_assign(params.montgomery_a());
+    let (mut num, mut den) = if y.into_repr().is_odd() { (x_plus_a, x) } else { (x, x_plus_a) };
+    num.negate();
+    den.mul_assign(&elligator2_non_residue::<E>());
+    num.mul_assign(&den.inverse()?);
+
+    let mut u = num.sqrt()?;
+    if u.into_repr().is_odd() {
+        u.negate();
+    }
+
+    // rules out the corner cases in which the other branch of the map is taken
+    if map_to_curve_elligator2::<E>(u, params).into_xy() == p.into_xy() {
+        Some(u)
+    } else {
+        None
+    }
+}
+
+/// A random encoding of `p`, `None` if `p` is not in the image of the map.
+pub fn encode<E: JubjubEngine, Subgroup, R: Rng>(
+    p: &edwards::Point<E, Subgroup>,
+    rng: &mut R,
+    params: &E::Params
+) -> Option<[u8; 32]>
+{
+    let u = representative(p, params)?;
+
+    // the preimages of p are the lifts u + k.p and -u + k.p below 2^256, their number is not
+    // the same for u and -u: pick the sign and k < 2^256 / p uniformly and start over if the
+    // lift overflows, so that every preimage is equally likely
+    let modulus = E::Fr::char();
+    let mut max_multiples = 0;
+    let mut multiple = <E::Fr as PrimeField>::Repr::default();
+    loop {
+        let last = multiple;
+        multiple.add_nocarry(&modulus);
+        max_multiples += 1;
+        if multiple < last {
+            break;
+        }
+    }
+
+    let lift = loop {
+        let mut lift = u;
+        if rng.gen() {
+            lift.negate();
+        }
+        let mut lift = lift.into_repr();
+
+        let mut overflow = false;
+        for _ in 0..rng.gen_range(0, max_multiples) {
+            let last = lift;
+            lift.add_nocarry(&modulus);
+            overflow = overflow || lift < last;
+        }
+        if !overflow {
+            break lift;
+        }
+    };
+
+    let mut bytes = [0u8; 32];
+    lift.write_le(&mut bytes[..]).expect("representation has 32 bytes");
+
+    Some(bytes)
+}
+
+/// The point encoded by `bytes`. Every string decodes to a point, not necessarily of prime
+/// order.
+pub fn decode<E: JubjubEngine>(
+    bytes: &[u8; 32],
+    params: &E::Params
+) -> edwards::Point<E, Unknown>
+{
+    let modulus = E::Fr::char();
+    let mut repr = <E::Fr as PrimeField>::Repr::default();
+    repr.read_le(&bytes[..]).expect("representation has 32 bytes");
+    while repr >= modulus {
+        repr.sub_noborrow(&modulus);
+    }
+    let u = E::Fr::from_repr(repr).unwrap();
+
+    map_to_curve_elligator2::<E>(u, params)
+}
+
+#[cfg(test)]
+mod tests {
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use rand::{XorShiftRng, SeedableRng};
+
+    use alt_babyjubjub::AltJubjubBn256;
+
+    use super::*;
+
+    #[test]
+    fn test_representatives_invert_the_map() {
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = AltJubjubBn256::new();
+
+        for _ in 0..100 {
+            let u: Fr = rng.gen();
+            let p = map_to_curve_elligator2::<Bn256>(u, &params);
+            let r = representative(&p, &params).unwrap();
+
+            let mut minus_u = u;
+            minus_u.negate();
+            assert!(r == u || r == minus_u);
+        }
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = AltJubjubBn256::new();
+
+        let mut encoded = 0;
+        let mut high_bits = 0u8;
+        for _ in 0..200 {
+            let p = edwards::Point::<Bn256, Unknown>::rand(rng, &params);
+
+            match encode(&p, rng, &params) {
+                Some(bytes) => {
+                    assert!(decode::<Bn256>(&bytes, &params) == p);
+                    encoded += 1;
+                    high_bits |= bytes[31];
+                },
+                None => assert!(representative(&p, &params).is_none())
+            }
+        }
+
+        assert!(encoded > 50 && encoded < 150);
+        assert_eq!(high_bits & 0xc0, 0xc0);
+    }
+
+    #[test]
+    fn test_encodings_are_uniform() {
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = AltJubjubBn256::new();
+
+        let p = loop {
+            let p = edwards::Point::<Bn256, Unknown>::rand(rng, &params);
+            if representative(&p, &params).is_some() {
+                break p;
+            }
+        };
+
+        // all preimages of p below 2^256
+        let u = representative(&p, &params).unwrap();
+        let mut minus_u = u;
+        minus_u.negate();
+        let mut preimages = vec![];
+        for start in [u, minus_u].iter() {
+            let mut lift = start.into_repr();
+            loop {
+                preimages.push(lift);
+                let last = lift;
+                lift.add_nocarry(&Fr::char());
+                if lift < last {
+                    break;
+                }
+            }
+        }
+
+        let mut counts = vec![0; preimages.len()];
+        let trials = 200 * preimages.len();
+        for _ in 0..trials {
+            let bytes = encode(&p, rng, &params).unwrap();
+            let mut repr = <Fr as PrimeField>::Repr::default();
+            repr.read_le(&bytes[..]).unwrap();
+            let i = preimages.iter().position(|x| *x == repr).unwrap();
+            counts[i] += 1;
+        }
+
+        for &count in counts.iter() {
+            assert!(count > 140 && count < 260, "{:?}", counts);
+        }
+    }
+
+    #[test]
+    fn test_any_string_decodes() {
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = AltJubjubBn256::new();
+
+        for bytes in [[0u8; 32], [0xffu8; 32]].iter().cloned().chain((0..20).map(|_| rng.gen())) {
+            let (x, y) = decode::<Bn256>(&bytes, &params).into_xy();
+            assert!(edwards::Point::<Bn256, Unknown>::from_xy(x, y, &params).is_some());
+        }
+    }
+}
@@ -24,11 +24,15 @@ use bellman::pairing::{
 use bellman::pairing::ff::{
     Field,
     PrimeField,
+    PrimeFieldRepr,
     SqrtField
 };
 
 use group_hash::group_hash;
 
+use std::marker::PhantomData;
+use std::ops::Deref;
+
 use constants;
 
 use bellman::pairing::bls12_381::{
@@ -46,6 +50,16 @@ pub mod montgomery;
 /// This is an implementation of the scalar field for Jubjub.
 pub mod fs;
 
+/// Parameter sets for curves of other engines.
+pub mod builder;
+
+/// Elligator 2 encodings of points as uniform looking strings.
+pub mod elligator2;
+
+/// Serde `with` modules for canonical compressed encodings.
+#[cfg(feature = "serde-compressed")]
+pub mod serialization;
+
 #[cfg(test)]
 pub mod tests;
 
@@ -103,6 +117,45 @@ pub trait JubjubEngine: Engine {
     type Params: JubjubParams<Self>;
 }
 
+/// Engines with a parameter set shared by the whole process, built on
+/// first use.
+pub trait JubjubEngineWithParams: JubjubEngine {
+    fn shared_params() -> &'static Self::Params;
+}
+
+/// A handle to the shared parameters of `E`. It is free to copy and
+/// dereferences to `E::Params`, so gadgets can keep one instead of a
+/// borrowed parameter set and pass `&*handle` along.
+pub struct SharedParams<E: JubjubEngineWithParams>(PhantomData<E>);
+
+impl<E: JubjubEngineWithParams> SharedParams<E> {
+    pub fn new() -> Self {
+        SharedParams(PhantomData)
+    }
+}
+
+impl<E: JubjubEngineWithParams> Clone for SharedParams<E> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<E: JubjubEngineWithParams> Copy for SharedParams<E> {}
+
+impl<E: JubjubEngineWithParams> Default for SharedParams<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E: JubjubEngineWithParams> Deref for SharedParams<E> {
+    type Target = E::Params;
+
+    fn deref(&self) -> &E::Params {
+        E::shared_params()
+    }
+}
+
 /// The pre-computed parameters for Jubjub, including curve
 /// constants and various limits and window tables.
 pub trait JubjubParams<E: JubjubEngine>: Sized {
@@ -189,13 +242,55 @@ impl JubjubParams<Bls12> for JubjubBls12 {
     }
 }
 
+/// Parameters from `JubjubParamsBuilder::build`, which checks that the chunk counts and the
+/// window size are the ones above.
+impl From<builder::CustomJubjubParams<Bls12>> for JubjubBls12 {
+    fn from(params: builder::CustomJubjubParams<Bls12>) -> Self {
+        JubjubBls12 {
+            edwards_d: params.edwards_d,
+            montgomery_a: params.montgomery_a,
+            montgomery_2a: params.montgomery_2a,
+            scale: params.scale,
+            pedersen_hash_generators: params.pedersen_hash_generators,
+            pedersen_hash_exp: params.pedersen_hash_exp,
+            pedersen_circuit_generators: params.pedersen_circuit_generators,
+            fixed_base_generators: params.fixed_base_generators,
+            fixed_base_circuit_generators: params.fixed_base_circuit_generators,
+        }
+    }
+}
+
+fn read_baked_fr(reader: &mut &[u8]) -> Fr {
+    let mut repr = <Fr as PrimeField>::Repr::default();
+    repr.read_le(reader).expect("baked data is complete");
+
+    Fr::from_repr(repr).expect("baked coordinates are reduced")
+}
+
+impl JubjubEngineWithParams for Bls12 {
+    fn shared_params() -> &'static JubjubBls12 {
+        JubjubBls12::shared()
+    }
+}
+
 impl JubjubBls12 {
-    pub fn new() -> Self {
+    /// The parameters shared by the whole process, built from the baked
+    /// data on first use.
+    pub fn shared() -> &'static JubjubBls12 {
+        lazy_static! {
+            static ref SHARED: JubjubBls12 = JubjubBls12::baked();
+        }
+
+        &SHARED
+    }
+
+    /// The curve constants, with the tables left empty.
+    fn without_tables() -> Self {
         let montgomery_a = Fr::from_str("40962").unwrap();
         let mut montgomery_2a = montgomery_a;
         montgomery_2a.double();
 
-        let mut tmp_params = JubjubBls12 {
+        JubjubBls12 {
             // d = -(10240/10241)
             edwards_d: Fr::from_str("19257038036680949359750312669786877991949435402254120286184196891950884077233").unwrap(),
             // A = 40962
@@ -211,7 +306,55 @@ impl JubjubBls12 {
             pedersen_circuit_generators: vec![],
             fixed_base_generators: vec![],
             fixed_base_circuit_generators: vec![],
+        }
+    }
+
+    /// The same parameters as `new`, with the generators and the circuit window tables read
+    /// from the data baked by the build script. Only the Pedersen hash exp table, which needs
+    /// no inversions, is computed.
+    pub fn baked() -> Self {
+        const BAKED: &'static [u8] = include_bytes!(concat!(env!("OUT_DIR"), "/jubjub_bls12_baked.bin"));
+
+        let mut tmp_params = Self::without_tables();
+        let mut reader = BAKED;
+
+        let mut read_generators = |count: usize, params: &JubjubBls12| -> Vec<edwards::Point<Bls12, PrimeOrder>> {
+            (0..count).map(|_| {
+                let x = read_baked_fr(&mut reader);
+                let y = read_baked_fr(&mut reader);
+
+                builder::check_generator::<Bls12>(x, y, params).expect("baked generators are valid")
+            }).collect()
         };
+        tmp_params.pedersen_hash_generators = read_generators(5, &tmp_params);
+        tmp_params.fixed_base_generators = read_generators(FixedGenerators::Max as usize, &tmp_params);
+
+        tmp_params.pedersen_hash_exp = builder::pedersen_hash_exp_table(
+            &tmp_params.pedersen_hash_generators,
+            tmp_params.pedersen_hash_exp_window_size(),
+            &tmp_params
+        );
+
+        let mut read_windows = |generators: usize, chunks: usize, points: usize| -> Vec<Vec<Vec<(Fr, Fr)>>> {
+            (0..generators).map(|_| {
+                (0..chunks).map(|_| {
+                    (0..points).map(|_| (read_baked_fr(&mut reader), read_baked_fr(&mut reader))).collect()
+                }).collect()
+            }).collect()
+        };
+        tmp_params.pedersen_circuit_generators = read_windows(5, tmp_params.pedersen_hash_chunks_per_generator(), 4);
+        tmp_params.fixed_base_circuit_generators = read_windows(
+            FixedGenerators::Max as usize,
+            tmp_params.fixed_base_chunks_per_generator(),
+            8
+        );
+        assert!(reader.is_empty());
+
+        tmp_params
+    }
+
+    pub fn new() -> Self {
+        let mut tmp_params = Self::without_tables();
 
         fn find_group_hash<E: JubjubEngine>(
             m: &[u8],
@@ -410,6 +553,34 @@ impl JubjubBls12 {
     }
 }
 
+#[test]
+fn test_shared_params() {
+    assert!(JubjubBls12::shared() as *const _ == JubjubBls12::shared() as *const _);
+
+    let handle = SharedParams::<Bls12>::new();
+    let copy = handle;
+    assert!(&*handle as *const _ == JubjubBls12::shared() as *const _);
+    assert!(&*copy as *const _ == Bls12::shared_params() as *const _);
+}
+
+#[test]
+fn test_point_bytes() {
+    // the full suite is not run for JubjubBls12, see below
+    tests::test_to_from_bytes::<Bls12>(&JubjubBls12::new());
+}
+
+#[test]
+fn test_baked_params() {
+    let params = JubjubBls12::new();
+    let baked = JubjubBls12::baked();
+
+    assert!(params.pedersen_hash_generators == baked.pedersen_hash_generators);
+    assert!(params.pedersen_hash_exp == baked.pedersen_hash_exp);
+    assert!(params.pedersen_circuit_generators == baked.pedersen_circuit_generators);
+    assert!(params.fixed_base_generators == baked.fixed_base_generators);
+    assert!(params.fixed_base_circuit_generators == baked.fixed_base_circuit_generators);
+}
+
 // #[test]
 // fn test_jubjub_bls12() {
 //     let params = JubjubBls12::new();
@@ -192,13 +192,47 @@ impl<E: JubjubEngine, Subgroup> Point<E, Subgroup> {
     /// Attempts to cast this as a prime order element, failing if it's
     /// not in the prime order subgroup.
     pub fn as_prime_order(&self, params: &E::Params) -> Option<Point<E, PrimeOrder>> {
-        if self.mul(E::Fs::char(), params) == Point::zero() {
+        if self.is_torsion_free(params) {
             Some(convert_subgroup(self))
         } else {
             None
         }
     }
 
+    /// Whether the point has no small order component, i.e. it is
+    /// in the prime order subgroup.
+    pub fn is_torsion_free(&self, params: &E::Params) -> bool {
+        self.mul(E::Fs::char(), params) == Point::zero()
+    }
+
+    /// Whether the point is in the subgroup of order 8.
+    pub fn is_small_order(&self, params: &E::Params) -> bool {
+        self.double(params).double(params).double(params) == Point::zero()
+    }
+
+    /// Multiplies by the cofactor. The result is in the prime order
+    /// subgroup, but points that already were in it change as well,
+    /// see `into_subgroup` for the projection that keeps them.
+    #[must_use]
+    pub fn clear_cofactor(&self, params: &E::Params) -> Point<E, PrimeOrder> {
+        convert_subgroup(&self.double(params).double(params).double(params))
+    }
+
+    /// The prime order component of the point. Points in the prime
+    /// order subgroup are returned unchanged.
+    pub fn into_subgroup(self, params: &E::Params) -> Point<E, PrimeOrder> {
+        // P = 8^-1 . (8 . P) for P of prime order
+        let cofactor = E::Fs::from_repr(<E::Fs as PrimeField>::Repr::from(8)).unwrap();
+        let cofactor_inv = cofactor.inverse().unwrap();
+
+        self.clear_cofactor(params).mul(cofactor_inv.into_repr(), params)
+    }
+
+    /// Forgets the subgroup of the point.
+    pub fn as_unknown(&self) -> Point<E, Unknown> {
+        convert_subgroup(self)
+    }
+
     pub fn zero() -> Self {
         Point {
             x: E::Fr::zero(),
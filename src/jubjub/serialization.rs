@@ -0,0 +1,184 @@
+//! Serde support with canonical encodings, enabled by the `serde-compressed` feature. The
+//! modules below are meant for `#[serde(with = "...")]` and leave the derived representations
+//! of points and scalars as they are. Edwards points are the 32 bytes of
+//! `edwards::Point::to_bytes`, Montgomery points are encoded as their Edwards images and scalars
+//! as 32 little endian bytes. Decoding rejects encodings that are not canonical.
+//!
+//! Decompression needs the curve parameters, so points can only be deserialized for engines
+//! implementing `JubjubEngineWithParams`, with its shared parameters.
+
+use bellman::pairing::ff::{PrimeField, PrimeFieldRepr};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::Error;
+
+use super::{JubjubEngine, JubjubEngineWithParams, PrimeOrder, Unknown, edwards, montgomery};
+
+/// Scalars, or any other prime field elements, as 32 little endian bytes.
+pub mod scalar {
+    use super::*;
+
+    pub fn serialize<F: PrimeField, S: Serializer>(scalar: &F, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut bytes = [0u8; 32];
+        scalar.into_repr().write_le(&mut bytes[..]).expect("scalar fits into 32 bytes");
+
+        bytes.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, F: PrimeField, D: Deserializer<'de>>(deserializer: D) -> Result<F, D::Error> {
+        let bytes = <[u8; 32]>::deserialize(deserializer)?;
+        let mut repr = F::Repr::default();
+        repr.read_le(&bytes[..]).map_err(D::Error::custom)?;
+
+        F::from_repr(repr).map_err(|_| D::Error::custom("scalar is not reduced"))
+    }
+}
+
+/// Edwards points of any subgroup, decoded as points of unknown order.
+pub mod point {
+    use super::*;
+
+    pub fn serialize<E: JubjubEngine, Subgroup, S: Serializer>(
+        p: &edwards::Point<E, Subgroup>,
+        serializer: S
+    ) -> Result<S::Ok, S::Error> {
+        p.to_bytes().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, E: JubjubEngineWithParams, D: Deserializer<'de>>(
+        deserializer: D
+    ) -> Result<edwards::Point<E, Unknown>, D::Error> {
+        let bytes = <[u8; 32]>::deserialize(deserializer)?;
+
+        edwards::Point::from_bytes(&bytes, E::shared_params())
+            .ok_or_else(|| D::Error::custom("invalid point encoding"))
+    }
+}
+
+/// Edwards points of prime order, decoding rejects points outside of the subgroup.
+pub mod prime_order_point {
+    use super::*;
+
+    pub use super::point::serialize;
+
+    pub fn deserialize<'de, E: JubjubEngineWithParams, D: Deserializer<'de>>(
+        deserializer: D
+    ) -> Result<edwards::Point<E, PrimeOrder>, D::Error> {
+        super::point::deserialize::<E, D>(deserializer)?
+            .as_prime_order(E::shared_params())
+            .ok_or_else(|| D::Error::custom("point is not of prime order"))
+    }
+}
+
+/// Montgomery points of any subgroup, decoded as points of unknown order.
+pub mod montgomery_point {
+    use super::*;
+
+    pub fn serialize<E: JubjubEngineWithParams, Subgroup, S: Serializer>(
+        p: &montgomery::Point<E, Subgroup>,
+        serializer: S
+    ) -> Result<S::Ok, S::Error> {
+        super::point::serialize(&edwards::Point::from_montgomery(p, E::shared_params()), serializer)
+    }
+
+    pub fn deserialize<'de, E: JubjubEngineWithParams, D: Deserializer<'de>>(
+        deserializer: D
+    ) -> Result<montgomery::Point<E, Unknown>, D::Error> {
+        let p = super::point::deserialize::<E, D>(deserializer)?;
+
+        Ok(montgomery::Point::from_edwards(&p, E::shared_params()))
+    }
+}
+
+/// Montgomery points of prime order, decoding rejects points outside of the subgroup.
+pub mod prime_order_montgomery_point {
+    use super::*;
+
+    pub use super::montgomery_point::serialize;
+
+    pub fn deserialize<'de, E: JubjubEngineWithParams, D: Deserializer<'de>>(
+        deserializer: D
+    ) -> Result<montgomery::Point<E, PrimeOrder>, D::Error> {
+        let p = super::prime_order_point::deserialize::<E, D>(deserializer)?;
+
+        Ok(montgomery::Point::from_edwards(&p, E::shared_params()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use bellman::pairing::ff::Field;
+    use rand::{XorShiftRng, SeedableRng};
+    use serde::de::value::{Error as ValueError, SeqDeserializer};
+
+    use alt_babyjubjub::fs::Fs;
+
+    use super::*;
+
+    fn deserializer(bytes: &[u8; 32]) -> SeqDeserializer<::std::iter::Cloned<::std::slice::Iter<u8>>, ValueError> {
+        SeqDeserializer::new(bytes.iter().cloned())
+    }
+
+    #[test]
+    fn test_points_decode_canonical_encodings_only() {
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = Bn256::shared_params();
+
+        let p = edwards::Point::<Bn256, Unknown>::rand(rng, params);
+        let decoded: edwards::Point<Bn256, Unknown> = point::deserialize(deserializer(&p.to_bytes())).unwrap();
+        assert!(decoded == p);
+
+        let m = montgomery::Point::from_edwards(&p, params);
+        let decoded: montgomery::Point<Bn256, Unknown> = montgomery_point::deserialize(deserializer(&p.to_bytes())).unwrap();
+        assert!(decoded == m);
+
+        // multiples of the cofactor have prime order, adding the point of order 2 breaks that
+        let g = p.mul_by_cofactor(params);
+        let decoded: edwards::Point<Bn256, PrimeOrder> = prime_order_point::deserialize(deserializer(&g.to_bytes())).unwrap();
+        assert!(decoded == g);
+        let mut minus_one = Fr::one();
+        minus_one.negate();
+        let order_two = edwards::Point::<Bn256, Unknown>::get_for_y(minus_one, false, params).unwrap();
+        let q = edwards::Point::<Bn256, Unknown>::from(g).add(&order_two, params);
+        assert!(prime_order_point::deserialize::<Bn256, _>(deserializer(&q.to_bytes())).is_err());
+
+        let mut bytes = edwards::Point::<Bn256, PrimeOrder>::zero().to_bytes();
+        bytes[31] |= 0x80;
+        assert!(point::deserialize::<Bn256, _>(deserializer(&bytes)).is_err());
+    }
+
+    #[test]
+    fn test_scalars_decode_canonical_encodings_only() {
+        let s = Fs::from_str("1234567").unwrap();
+        let mut bytes = [0u8; 32];
+        s.into_repr().write_le(&mut bytes[..]).unwrap();
+        assert_eq!(scalar::deserialize::<Fs, _>(deserializer(&bytes)).unwrap(), s);
+
+        assert!(scalar::deserialize::<Fs, _>(deserializer(&[0xff; 32])).is_err());
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Note {
+        #[serde(with = "prime_order_point")]
+        p: edwards::Point<Bn256, PrimeOrder>,
+        #[serde(with = "scalar")]
+        s: Fs,
+    }
+
+    #[test]
+    fn test_with_attributes() {
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = Bn256::shared_params();
+
+        let p = edwards::Point::<Bn256, Unknown>::rand(rng, params).mul_by_cofactor(params);
+        let s = Fs::from_str("1234567").unwrap();
+        let mut s_bytes = [0u8; 32];
+        s.into_repr().write_le(&mut s_bytes[..]).unwrap();
+
+        let fields = vec![p.to_bytes().to_vec(), s_bytes.to_vec()];
+        let note = Note::deserialize(SeqDeserializer::<_, ValueError>::new(fields.into_iter())).unwrap();
+        assert!(note.p == p);
+        assert_eq!(note.s, s);
+    }
+}
@@ -27,6 +27,7 @@ pub fn test_suite<E: JubjubEngine>(params: &E::Params) {
     test_mul_associativity::<E>(params);
     test_loworder::<E>(params);
     test_read_write::<E>(params);
+    test_to_from_bytes::<E>(params);
 }
 
 fn is_on_mont_curve<E: JubjubEngine, P: JubjubParams<E>>(
@@ -261,6 +262,32 @@ fn test_read_write<E: JubjubEngine>(params: &E::Params) {
     }
 }
 
+pub fn test_to_from_bytes<E: JubjubEngine>(params: &E::Params) {
+    let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+    for _ in 0..100 {
+        let e = edwards::Point::<E, _>::rand(rng, params);
+
+        let mut v = vec![];
+        e.write(&mut v).unwrap();
+        let bytes = e.to_bytes();
+        assert_eq!(&bytes[..], &v[..]);
+
+        assert!(edwards::Point::from_bytes(&bytes, params).unwrap() == e);
+    }
+
+    // y = 2^255 - 1 is not reduced
+    let mut bytes = [0xffu8; 32];
+    bytes[31] = 0x7f;
+    assert!(edwards::Point::<E, _>::from_bytes(&bytes, params).is_none());
+
+    // x = 0 with the sign bit set decodes with `read`, but is not canonical
+    let mut bytes = edwards::Point::<E, PrimeOrder>::zero().to_bytes();
+    bytes[31] |= 0x80;
+    assert!(edwards::Point::read(&bytes[..], params).is_ok());
+    assert!(edwards::Point::<E, _>::from_bytes(&bytes, params).is_none());
+}
+
 fn test_rand<E: JubjubEngine>(params: &E::Params) {
     let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
 
@@ -7,6 +7,7 @@ pub extern crate bellman;
 extern crate blake2_rfc_bellman_edition as blake2_rfc;
 extern crate digest;
 extern crate rand;
+extern crate rand_core;
 extern crate byteorder;
 extern crate tiny_keccak;
 extern crate sha2;
@@ -32,20 +33,51 @@ extern crate lazy_static;
 extern crate arr_macro;
 
 
+#[cfg(feature = "wasm")]
+extern crate getrandom;
+
+#[cfg(feature = "zkinterface")]
+extern crate zkinterface;
+
+#[cfg(feature = "mnemonic")]
+extern crate unicode_normalization;
+
+#[cfg(all(target_arch = "wasm32", feature = "multicore"))]
+compile_error!("the `multicore` feature spawns threads, build for wasm32 with `--no-default-features --features plonk,wasm`");
+
 #[cfg(test)]
 extern crate hex;
 
+#[cfg(test)]
+extern crate rand_chacha;
+
 pub mod jubjub;
 pub mod alt_babyjubjub;
 pub mod group_hash;
 pub mod pedersen_hash;
+pub mod circomlib_pedersen_hash;
 pub mod primitives;
+pub mod zip32;
+#[cfg(feature = "mnemonic")]
+pub mod mnemonic;
 pub mod constants;
 pub mod redjubjub;
+pub mod musig2;
+pub mod blind_schnorr;
+pub mod bls;
+pub mod vrf;
+pub mod merkle_tree;
+pub mod elgamal;
+pub mod ecdh;
+pub mod note_encryption;
 pub mod util;
+pub mod random;
 pub mod interpolation;
 pub mod as_waksman;
 pub mod rescue;
+pub mod poseidon;
+pub mod mimc;
+pub mod sinsemilla;
 pub mod generic_twisted_edwards;
 pub mod plonk;
 
@@ -0,0 +1,214 @@
+//! Append only Merkle tree of fixed depth keeping only O(depth) nodes, hashing is the same as
+//! in `plonk::circuit::merkle_tree`: a node is Poseidon(left, right) and empty leaves are zero.
+//!
+//! The tree stores the latest left child at every height, a leaf is appended by hashing it up
+//! with these and the roots of empty subtrees. Marked leaves get their authentication paths
+//! updated on every append, so they can be used as circuit witnesses at any point.
+
+use bellman::pairing::ff::Field;
+
+use poseidon::{PoseidonEngine, poseidon_hash};
+
+use std::collections::BTreeMap;
+
+/// Poseidon(left, right)
+pub fn hash_nodes<E: PoseidonEngine>(params: &E::Params, left: &E::Fr, right: &E::Fr) -> E::Fr {
+    poseidon_hash::<E>(params, &[*left, *right])[0]
+}
+
+/// Authentication path of the leaf at `index`, siblings are ordered from the leaf level up.
+#[derive(Clone)]
+pub struct MerkleWitness<E: PoseidonEngine> {
+    pub index: u64,
+    pub leaf: E::Fr,
+    pub path: Vec<E::Fr>,
+}
+
+impl<E: PoseidonEngine> MerkleWitness<E> {
+    /// Little endian bits of the index, as taken by the circuit gadgets.
+    pub fn index_bits(&self) -> Vec<bool> {
+        (0..self.path.len()).map(|i| (self.index >> i) & 1 == 1).collect()
+    }
+
+    pub fn root(&self, params: &E::Params) -> E::Fr {
+        let mut current = self.leaf;
+        for (height, sibling) in self.path.iter().enumerate() {
+            current = if (self.index >> height) & 1 == 0 {
+                hash_nodes::<E>(params, &current, sibling)
+            } else {
+                hash_nodes::<E>(params, sibling, &current)
+            };
+        }
+
+        current
+    }
+}
+
+#[derive(Clone)]
+pub struct IncrementalMerkleTree<E: PoseidonEngine> {
+    depth: usize,
+    next_index: u64,
+    root: E::Fr,
+    /// Roots of empty subtrees by height.
+    empty_roots: Vec<E::Fr>,
+    /// Latest left child at every height, it's complete once the path goes right of it.
+    filled_subtrees: Vec<E::Fr>,
+    witnesses: BTreeMap<u64, MerkleWitness<E>>,
+}
+
+impl<E: PoseidonEngine> IncrementalMerkleTree<E> {
+    pub fn new(depth: usize, params: &E::Params) -> Self {
+        assert!(depth > 0 && depth < 64);
+
+        let mut empty_roots = Vec::with_capacity(depth + 1);
+        empty_roots.push(E::Fr::zero());
+        for height in 0..depth {
+            let node = hash_nodes::<E>(params, &empty_roots[height], &empty_roots[height]);
+            empty_roots.push(node);
+        }
+
+        Self {
+            depth,
+            next_index: 0,
+            root: empty_roots[depth],
+            filled_subtrees: empty_roots[..depth].to_vec(),
+            empty_roots,
+            witnesses: BTreeMap::new(),
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn root(&self) -> E::Fr {
+        self.root
+    }
+
+    /// Number of appended leaves.
+    pub fn size(&self) -> u64 {
+        self.next_index
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.next_index == 1u64 << self.depth
+    }
+
+    /// Root of the empty tree of the given height.
+    pub fn empty_root(&self, height: usize) -> E::Fr {
+        self.empty_roots[height]
+    }
+
+    /// Appends `leaf` and returns its index, None if the tree is full.
+    pub fn append(&mut self, leaf: E::Fr, params: &E::Params) -> Option<u64> {
+        self.append_inner(leaf, false, params)
+    }
+
+    /// Same as `append`, the leaf is tracked until it's forgotten.
+    pub fn append_and_mark(&mut self, leaf: E::Fr, params: &E::Params) -> Option<u64> {
+        self.append_inner(leaf, true, params)
+    }
+
+    fn append_inner(&mut self, leaf: E::Fr, mark: bool, params: &E::Params) -> Option<u64> {
+        if self.is_full() {
+            return None;
+        }
+        let index = self.next_index;
+
+        let mut path = Vec::with_capacity(self.depth);
+        let mut current = leaf;
+        for height in 0..self.depth {
+            // every tracked leaf having the current node as a sibling gets the new value of it
+            let node_index = index >> height;
+            for witness in self.witnesses.values_mut() {
+                if (witness.index >> height) ^ 1 == node_index {
+                    witness.path[height] = current;
+                }
+            }
+
+            current = if node_index & 1 == 0 {
+                self.filled_subtrees[height] = current;
+                path.push(self.empty_roots[height]);
+                hash_nodes::<E>(params, &current, &self.empty_roots[height])
+            } else {
+                path.push(self.filled_subtrees[height]);
+                hash_nodes::<E>(params, &self.filled_subtrees[height], &current)
+            };
+        }
+
+        self.root = current;
+        self.next_index += 1;
+        if mark {
+            self.witnesses.insert(index, MerkleWitness { index, leaf, path });
+        }
+
+        Some(index)
+    }
+
+    /// Witness for the tracked leaf at `index` against the current root.
+    pub fn witness(&self, index: u64) -> Option<&MerkleWitness<E>> {
+        self.witnesses.get(&index)
+    }
+
+    /// Stops tracking the leaf at `index`.
+    pub fn forget(&mut self, index: u64) -> bool {
+        self.witnesses.remove(&index).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use rand::{Rng, SeedableRng, XorShiftRng};
+
+    use poseidon::Bn256PoseidonParams;
+
+    use super::*;
+
+    // root of the full tree with the leaves padded by zeros
+    fn naive_root(leaves: &[Fr], depth: usize, params: &Bn256PoseidonParams) -> Fr {
+        let mut level = leaves.to_vec();
+        level.resize(1 << depth, Fr::zero());
+        while level.len() > 1 {
+            level = level.chunks(2).map(|pair| hash_nodes::<Bn256>(params, &pair[0], &pair[1])).collect();
+        }
+
+        level[0]
+    }
+
+    #[test]
+    fn test_incremental_tree_matches_full_tree() {
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = Bn256PoseidonParams::new_checked_2_into_1();
+        let depth = 4;
+
+        let mut tree = IncrementalMerkleTree::<Bn256>::new(depth, &params);
+        assert_eq!(tree.root(), naive_root(&[], depth, &params));
+
+        let mut leaves = vec![];
+        for i in 0..(1u64 << depth) {
+            let leaf: Fr = rng.gen();
+            leaves.push(leaf);
+            let index = if i % 3 == 0 {
+                tree.append_and_mark(leaf, &params)
+            } else {
+                tree.append(leaf, &params)
+            };
+            assert_eq!(index, Some(i));
+            assert_eq!(tree.root(), naive_root(&leaves, depth, &params));
+
+            // every tracked witness stays valid for the current root
+            for j in (0..=i).filter(|j| j % 3 == 0) {
+                let witness = tree.witness(j).unwrap();
+                assert_eq!(witness.leaf, leaves[j as usize]);
+                assert_eq!(witness.root(&params), tree.root());
+            }
+        }
+
+        assert!(tree.is_full());
+        assert_eq!(tree.append(Fr::one(), &params), None);
+        assert!(tree.witness(1).is_none());
+        assert!(tree.forget(3));
+        assert!(tree.witness(3).is_none());
+    }
+}
@@ -0,0 +1,224 @@
+//! MiMC-7 (MiMC-n/n with x^7) and MiMCSponge (MiMC-2n/n Feistel with x^5)
+//! over the BN254 scalar field, compatible with circomlib (`mimc7.js`, `mimcsponge.js`).
+//!
+//! Round constants are derived the same way as in circomlib: c_0 = 0 and
+//! c_i = keccak256^(i + 1)(seed) mod p, where every next hash is taken over
+//! the unreduced 32 byte output of the previous one.
+
+use bellman::pairing::ff::{Field, PrimeField};
+use bellman::pairing::Engine;
+use tiny_keccak::Keccak;
+
+pub const MIMC7_SEED: &[u8] = b"mimc";
+pub const MIMC7_NUM_ROUNDS: usize = 91;
+
+pub const MIMC_SPONGE_SEED: &[u8] = b"mimcsponge";
+pub const MIMC_SPONGE_NUM_ROUNDS: usize = 220;
+
+fn keccak256(input: &[u8]) -> [u8; 32] {
+    let mut h = Keccak::new_keccak256();
+    h.update(input);
+    let mut output = [0u8; 32];
+    h.finalize(&mut output);
+
+    output
+}
+
+/// Interprets big endian `bytes` as an integer and reduces it modulo the field characteristic.
+fn fe_from_be_bytes_reduced<F: PrimeField>(bytes: &[u8]) -> F {
+    let base = F::from_str("256").unwrap();
+    let mut result = F::zero();
+    for byte in bytes.iter() {
+        result.mul_assign(&base);
+        result.add_assign(&F::from_str(&byte.to_string()).unwrap());
+    }
+
+    result
+}
+
+/// Generates `num_rounds` round constants from `seed` as circomlib does.
+pub fn generate_round_constants<F: PrimeField>(seed: &[u8], num_rounds: usize) -> Vec<F> {
+    let mut constants = Vec::with_capacity(num_rounds);
+    constants.push(F::zero());
+
+    let mut digest = keccak256(seed);
+    for _ in 1..num_rounds {
+        digest = keccak256(&digest[..]);
+        constants.push(fe_from_be_bytes_reduced(&digest[..]));
+    }
+
+    constants
+}
+
+fn pow_5<F: PrimeField>(el: &F) -> F {
+    let mut result = *el;
+    result.square();
+    result.square();
+    result.mul_assign(el);
+
+    result
+}
+
+fn pow_7<F: PrimeField>(el: &F) -> F {
+    let mut square = *el;
+    square.square();
+    let mut result = square;
+    result.square();
+    result.mul_assign(&square);
+    result.mul_assign(el);
+
+    result
+}
+
+#[derive(Clone, Debug)]
+pub struct Mimc7Params<E: Engine> {
+    pub round_constants: Vec<E::Fr>,
+}
+
+impl<E: Engine> Mimc7Params<E> {
+    pub fn new() -> Self {
+        Self {
+            round_constants: generate_round_constants(MIMC7_SEED, MIMC7_NUM_ROUNDS),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MimcSpongeParams<E: Engine> {
+    pub round_constants: Vec<E::Fr>,
+}
+
+impl<E: Engine> MimcSpongeParams<E> {
+    pub fn new() -> Self {
+        let mut round_constants = generate_round_constants(MIMC_SPONGE_SEED, MIMC_SPONGE_NUM_ROUNDS);
+        // the last round of the Feistel network has no constant either
+        *round_constants.last_mut().unwrap() = E::Fr::zero();
+
+        Self {
+            round_constants,
+        }
+    }
+}
+
+/// MiMC-7 block cipher encryption of `x` under key `k` (circomlib `MiMC7`).
+pub fn mimc7<E: Engine>(params: &Mimc7Params<E>, x: E::Fr, k: E::Fr) -> E::Fr {
+    let mut r = x;
+    for c in params.round_constants.iter() {
+        // first round constant is zero
+        r.add_assign(c);
+        r.add_assign(&k);
+        r = pow_7(&r);
+    }
+    r.add_assign(&k);
+
+    r
+}
+
+/// Miyaguchi-Preneel style compression of multiple elements with MiMC-7 (circomlib `MultiMiMC7`).
+pub fn mimc7_multi_hash<E: Engine>(params: &Mimc7Params<E>, input: &[E::Fr], key: E::Fr) -> E::Fr {
+    let mut r = key;
+    for x in input.iter() {
+        let encrypted = mimc7(params, *x, r);
+        r.add_assign(x);
+        r.add_assign(&encrypted);
+    }
+
+    r
+}
+
+/// MiMC Feistel permutation of (xl, xr) under key `k` (circomlib `MiMCFeistel`).
+/// Returns the new (xl, xr).
+pub fn mimc_feistel<E: Engine>(params: &MimcSpongeParams<E>, xl: E::Fr, xr: E::Fr, k: E::Fr) -> (E::Fr, E::Fr) {
+    let mut xl = xl;
+    let mut xr = xr;
+    let num_rounds = params.round_constants.len();
+    for (i, c) in params.round_constants.iter().enumerate() {
+        let mut t = xl;
+        t.add_assign(&k);
+        t.add_assign(c);
+        let mut f = pow_5(&t);
+        f.add_assign(&xr);
+        if i < num_rounds - 1 {
+            xr = xl;
+            xl = f;
+        } else {
+            xr = f;
+        }
+    }
+
+    (xl, xr)
+}
+
+/// Sponge over the MiMC Feistel permutation with rate 1 and capacity 1 (circomlib `MiMCSponge`).
+pub fn mimc_sponge_hash<E: Engine>(
+    params: &MimcSpongeParams<E>,
+    input: &[E::Fr],
+    key: E::Fr,
+    num_outputs: usize
+) -> Vec<E::Fr> {
+    assert!(num_outputs > 0);
+    let mut r = E::Fr::zero();
+    let mut c = E::Fr::zero();
+    for x in input.iter() {
+        r.add_assign(x);
+        let (new_r, new_c) = mimc_feistel(params, r, c, key);
+        r = new_r;
+        c = new_c;
+    }
+
+    let mut outputs = Vec::with_capacity(num_outputs);
+    outputs.push(r);
+    for _ in 1..num_outputs {
+        let (new_r, new_c) = mimc_feistel(params, r, c, key);
+        r = new_r;
+        c = new_c;
+        outputs.push(r);
+    }
+
+    outputs
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use bellman::pairing::ff::from_hex;
+
+    #[test]
+    fn test_mimc_sponge_constants() {
+        let params = MimcSpongeParams::<Bn256>::new();
+        assert_eq!(params.round_constants.len(), MIMC_SPONGE_NUM_ROUNDS);
+        assert_eq!(params.round_constants[1], Fr::from_str("7120861356467848435263064379192047478074060781135320967663101236819528304084").unwrap());
+        assert!(params.round_constants[0].is_zero());
+        assert!(params.round_constants[MIMC_SPONGE_NUM_ROUNDS - 1].is_zero());
+    }
+
+    #[test]
+    fn test_mimc_sponge_circomlib_vector() {
+        // circomlib: mimcsponge.multiHash([1, 2], 0, 3)
+        let params = MimcSpongeParams::<Bn256>::new();
+        let input = [Fr::from_str("1").unwrap(), Fr::from_str("2").unwrap()];
+        let outputs = mimc_sponge_hash(&params, &input, Fr::zero(), 3);
+
+        let expected = [
+            "0x2bcea035a1251603f1ceaf73cd4ae89427c47075bb8e3a944039ff1e3d6d2a6f",
+            "0x2f7d340a3c24b8ef9899ab5f019b85b87354c7f6c965a19ca090321f7e5425e9",
+            "0x0cf71423c39e70b9858eaa8e1dc3ac40a09c3927dc31d12014af16066f2bdcb6",
+        ];
+        for (o, e) in outputs.iter().zip(expected.iter()) {
+            assert_eq!(*o, from_hex::<Fr>(e).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_mimc7_vector() {
+        let params = Mimc7Params::<Bn256>::new();
+        let x = Fr::from_str("1").unwrap();
+        let k = Fr::from_str("2").unwrap();
+
+        assert_eq!(mimc7(&params, x, k), from_hex::<Fr>("0x176c6eefc3fdf8d6136002d8e6f7a885bbd1c4e3957b93ddc1ec3ae7859f1a08").unwrap());
+
+        let multi = mimc7_multi_hash(&params, &[x, k], Fr::zero());
+        assert_eq!(multi, from_hex::<Fr>("0x0b91ebbd35d7448ecc13e75a7ceb1ce5bbe428090acfae0da2c3867a874ce6ea").unwrap());
+    }
+}
@@ -0,0 +1,124 @@
+//! Keys from a BIP 39 mnemonic. The phrase and the passphrase are NFKD normalized and stretched
+//! into a 64 byte seed with PBKDF2-HMAC-SHA512, 2048 iterations and the salt
+//! "mnemonic" || passphrase, as in BIP 39. The seed is the input of the ZIP 32 master key, which
+//! hashes it with BLAKE2b under the personalization "ZcashIP32Sapling", and the account key is
+//! the hardened child m/32'/coin_type'/account'.
+//!
+//! The phrase is taken as given, the words and the checksum are not validated. Wallets check
+//! them when the phrase is entered, with the word list of its language.
+
+use sha2::{Digest, Sha512};
+use unicode_normalization::UnicodeNormalization;
+
+use jubjub::JubjubEngine;
+use zip32::{ChildIndex, ExtendedSpendingKey};
+
+pub const PBKDF2_ROUNDS: u32 = 2048;
+pub const ZIP32_PURPOSE: u32 = 32;
+
+const SHA512_BLOCK_BYTES: usize = 128;
+
+fn hmac_sha512(key: &[u8], message: &[u8]) -> [u8; 64] {
+    let mut block = [0u8; SHA512_BLOCK_BYTES];
+    if key.len() > SHA512_BLOCK_BYTES {
+        block[..64].copy_from_slice(&Sha512::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let mut inner = Sha512::new();
+    inner.update(&block.iter().map(|b| b ^ 0x36).collect::<Vec<u8>>());
+    inner.update(message);
+
+    let mut outer = Sha512::new();
+    outer.update(&block.iter().map(|b| b ^ 0x5c).collect::<Vec<u8>>());
+    outer.update(&inner.finalize());
+
+    let mut result = [0u8; 64];
+    result.copy_from_slice(&outer.finalize());
+
+    result
+}
+
+// PBKDF2 with a single block of output, which is all of a SHA512 output
+fn pbkdf2_hmac_sha512(password: &[u8], salt: &[u8], rounds: u32) -> [u8; 64] {
+    let mut message = salt.to_vec();
+    message.extend_from_slice(&1u32.to_be_bytes());
+
+    let mut u = hmac_sha512(password, &message);
+    let mut result = u;
+    for _ in 1..rounds {
+        u = hmac_sha512(password, &u);
+        for (r, u) in result.iter_mut().zip(u.iter()) {
+            *r ^= u;
+        }
+    }
+
+    result
+}
+
+/// The BIP 39 seed of `mnemonic` with `passphrase`, empty if there is none.
+pub fn seed_from_mnemonic(mnemonic: &str, passphrase: &str) -> [u8; 64] {
+    let mnemonic: String = mnemonic.nfkd().collect();
+    let salt: String = "mnemonic".nfkd().chain(passphrase.nfkd()).collect();
+
+    pbkdf2_hmac_sha512(mnemonic.as_bytes(), salt.as_bytes(), PBKDF2_ROUNDS)
+}
+
+/// The master key of the seed of `mnemonic`.
+pub fn master_key_from_mnemonic<E: JubjubEngine>(mnemonic: &str, passphrase: &str) -> ExtendedSpendingKey<E> {
+    ExtendedSpendingKey::master(&seed_from_mnemonic(mnemonic, passphrase))
+}
+
+/// The key of `account` under m/32'/coin_type'/account'.
+pub fn account_key_from_mnemonic<E: JubjubEngine>(
+    mnemonic: &str,
+    passphrase: &str,
+    coin_type: u32,
+    account: u32,
+    params: &E::Params
+) -> ExtendedSpendingKey<E> {
+    let path = [
+        ChildIndex::Hardened(ZIP32_PURPOSE),
+        ChildIndex::Hardened(coin_type),
+        ChildIndex::Hardened(account),
+    ];
+
+    master_key_from_mnemonic(mnemonic, passphrase).derive_path(&path, params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alt_babyjubjub::AltJubjubBn256;
+    use bellman::pairing::bn256::Bn256;
+
+    const MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    #[test]
+    fn test_bip39_seed() {
+        // the first vector of the reference implementation
+        let seed = seed_from_mnemonic(MNEMONIC, "TREZOR");
+        assert_eq!(
+            hex::encode(&seed[..]),
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"
+        );
+    }
+
+    #[test]
+    fn test_account_keys() {
+        let params = AltJubjubBn256::new();
+
+        let a = account_key_from_mnemonic::<Bn256>(MNEMONIC, "", 1, 0, &params);
+        let b = account_key_from_mnemonic::<Bn256>(MNEMONIC, "", 1, 1, &params);
+        let c = account_key_from_mnemonic::<Bn256>(MNEMONIC, "passphrase", 1, 0, &params);
+        assert_eq!(a.depth, 3);
+        assert_eq!(a.child_index, ChildIndex::Hardened(0));
+        assert!(a.chain_code != b.chain_code);
+        assert!(a.chain_code != c.chain_code);
+
+        let again = account_key_from_mnemonic::<Bn256>(MNEMONIC, "", 1, 0, &params);
+        assert_eq!(a.chain_code, again.chain_code);
+        assert!(a.expsk.ask == again.expsk.ask);
+    }
+}
@@ -0,0 +1,247 @@
+//! MuSig2 multi-signatures producing ordinary RedJubjub signatures.
+//! See "MuSig2: Simple Two-Round Schnorr Multi-Signatures" by Nick, Ruffing and Seurin.
+//!
+//! The aggregated key is a regular `redjubjub::PublicKey` and the final signature is a regular
+//! `redjubjub::Signature`, so both the native verifier and `plonk::circuit::redjubjub` accept it.
+
+use bellman::pairing::ff::{Field, PrimeField};
+use rand::{Rng, Rand};
+
+use jubjub::{FixedGenerators, JubjubEngine, JubjubParams, Unknown, edwards::Point};
+use redjubjub::{PrivateKey, PublicKey, Signature, h_star, write_scalar};
+use util::hash_to_scalar;
+
+const MUSIG2_KEY_AGG_PERSONALIZATION: &[u8; 16] = b"MuSig2_KeyAggCof";
+const MUSIG2_NONCE_PERSONALIZATION: &[u8; 16] = b"MuSig2_NonceCoef";
+
+fn point_bytes<E: JubjubEngine>(p: &Point<E, Unknown>) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    p.write(&mut bytes[..]).expect("Jubjub points should serialize to 32 bytes");
+
+    bytes
+}
+
+/// Aggregation of the signers' public keys: X = sum_i a_i . X_i with a_i = H_agg(L, X_i),
+/// where L is the encoding of the whole ordered list of keys.
+#[derive(Clone)]
+pub struct KeyAggContext<E: JubjubEngine> {
+    coefficients: Vec<E::Fs>,
+    aggregated_key: PublicKey<E>,
+}
+
+impl<E: JubjubEngine> KeyAggContext<E> {
+    pub fn new(pubkeys: &[PublicKey<E>], params: &E::Params) -> Self {
+        assert!(pubkeys.len() > 0);
+
+        let mut encoded_list = Vec::with_capacity(pubkeys.len() * 32);
+        for pk in pubkeys.iter() {
+            encoded_list.extend_from_slice(&point_bytes(&pk.0));
+        }
+
+        let mut coefficients = Vec::with_capacity(pubkeys.len());
+        let mut aggregated_key = Point::<E, Unknown>::zero();
+        for pk in pubkeys.iter() {
+            let a = hash_to_scalar::<E>(&MUSIG2_KEY_AGG_PERSONALIZATION[..], &encoded_list, &point_bytes(&pk.0));
+            aggregated_key = aggregated_key.add(&pk.0.mul(a, params), params);
+            coefficients.push(a);
+        }
+
+        Self {
+            coefficients,
+            aggregated_key: PublicKey(aggregated_key),
+        }
+    }
+
+    pub fn aggregated_key(&self) -> &PublicKey<E> {
+        &self.aggregated_key
+    }
+
+    pub fn num_signers(&self) -> usize {
+        self.coefficients.len()
+    }
+}
+
+/// Secret nonces of a signer for a single session. It's consumed by `sign_partial`, so a nonce
+/// can't be used for two signatures.
+pub struct SecretNonce<E: JubjubEngine> {
+    r1: E::Fs,
+    r2: E::Fs,
+}
+
+/// Public nonces (R_1, R_2) sent to the other signers in the first round.
+#[derive(Clone)]
+pub struct PublicNonce<E: JubjubEngine> {
+    pub r1: Point<E, Unknown>,
+    pub r2: Point<E, Unknown>,
+}
+
+pub fn generate_nonces<E: JubjubEngine, R: Rng>(
+    rng: &mut R,
+    p_g: FixedGenerators,
+    params: &E::Params
+) -> (SecretNonce<E>, PublicNonce<E>) {
+    let secret = SecretNonce {
+        r1: E::Fs::rand(rng),
+        r2: E::Fs::rand(rng),
+    };
+    let public = PublicNonce {
+        r1: params.generator(p_g).mul(secret.r1, params).into(),
+        r2: params.generator(p_g).mul(secret.r2, params).into(),
+    };
+
+    (secret, public)
+}
+
+/// Sums the public nonces of all the signers, can be done by any party.
+pub fn aggregate_nonces<E: JubjubEngine>(nonces: &[PublicNonce<E>], params: &E::Params) -> PublicNonce<E> {
+    let mut r1 = Point::<E, Unknown>::zero();
+    let mut r2 = Point::<E, Unknown>::zero();
+    for nonce in nonces.iter() {
+        r1 = r1.add(&nonce.r1, params);
+        r2 = r2.add(&nonce.r2, params);
+    }
+
+    PublicNonce { r1, r2 }
+}
+
+/// Everything the signers derive from the aggregated key, the aggregated nonce and the message:
+/// b = H_non(X, R_1, R_2, M), R = R_1 + b . R_2 and the RedJubjub challenge c = H*(Rbar || M).
+pub struct SigningSession<E: JubjubEngine> {
+    b: E::Fs,
+    c: E::Fs,
+    rbar: [u8; 32],
+}
+
+impl<E: JubjubEngine> SigningSession<E> {
+    pub fn new(
+        key_agg: &KeyAggContext<E>,
+        aggregated_nonce: &PublicNonce<E>,
+        msg: &[u8],
+        params: &E::Params
+    ) -> Self {
+        let mut encoded = Vec::with_capacity(96);
+        encoded.extend_from_slice(&point_bytes(&key_agg.aggregated_key.0));
+        encoded.extend_from_slice(&point_bytes(&aggregated_nonce.r1));
+        encoded.extend_from_slice(&point_bytes(&aggregated_nonce.r2));
+        let b = hash_to_scalar::<E>(&MUSIG2_NONCE_PERSONALIZATION[..], &encoded, msg);
+
+        let r = aggregated_nonce.r1.add(&aggregated_nonce.r2.mul(b, params), params);
+        let rbar = point_bytes(&r);
+        let c = h_star::<E>(&rbar[..], msg);
+
+        Self { b, c, rbar }
+    }
+}
+
+pub struct PartialSignature<E: JubjubEngine>(pub E::Fs);
+
+/// Second round for the signer at `index` in the list of keys: s_i = r_1 + b . r_2 + c . a_i . sk_i.
+pub fn sign_partial<E: JubjubEngine>(
+    session: &SigningSession<E>,
+    key_agg: &KeyAggContext<E>,
+    index: usize,
+    sk: &PrivateKey<E>,
+    secret_nonce: SecretNonce<E>
+) -> PartialSignature<E> {
+    let mut s = session.c;
+    s.mul_assign(&key_agg.coefficients[index]);
+    s.mul_assign(&sk.0);
+
+    let mut r = secret_nonce.r2;
+    r.mul_assign(&session.b);
+    r.add_assign(&secret_nonce.r1);
+    s.add_assign(&r);
+
+    PartialSignature(s)
+}
+
+/// Checks s_i . P_G = R_1,i + b . R_2,i + c . a_i . X_i, so the aggregator can blame a
+/// misbehaving signer if the final signature is invalid.
+pub fn verify_partial<E: JubjubEngine>(
+    session: &SigningSession<E>,
+    key_agg: &KeyAggContext<E>,
+    index: usize,
+    pk: &PublicKey<E>,
+    public_nonce: &PublicNonce<E>,
+    partial: &PartialSignature<E>,
+    p_g: FixedGenerators,
+    params: &E::Params
+) -> bool {
+    let mut ca = session.c;
+    ca.mul_assign(&key_agg.coefficients[index]);
+
+    let rhs = public_nonce.r1
+        .add(&public_nonce.r2.mul(session.b, params), params)
+        .add(&pk.0.mul(ca, params), params);
+    let lhs: Point<E, Unknown> = params.generator(p_g).mul(partial.0, params).into();
+
+    lhs == rhs
+}
+
+/// S = sum_i s_i, (Rbar, S) is a RedJubjub signature of the message under the aggregated key.
+pub fn aggregate_partial_signatures<E: JubjubEngine>(
+    session: &SigningSession<E>,
+    partials: &[PartialSignature<E>]
+) -> Signature {
+    let mut s = E::Fs::zero();
+    for partial in partials.iter() {
+        s.add_assign(&partial.0);
+    }
+
+    let mut bytes = [0u8; 64];
+    bytes[..32].copy_from_slice(&session.rbar);
+    write_scalar::<E, &mut [u8]>(&s, &mut bytes[32..])
+        .expect("Jubjub scalars should serialize to 32 bytes");
+
+    Signature::read(&bytes[..]).expect("64 bytes are available")
+}
+
+#[cfg(test)]
+mod tests {
+    use bellman::pairing::bn256::Bn256;
+    use rand::thread_rng;
+
+    use alt_babyjubjub::AltJubjubBn256;
+
+    use super::*;
+
+    #[test]
+    fn test_musig2_signature_verifies_under_aggregated_key() {
+        let rng = &mut thread_rng();
+        let params = &AltJubjubBn256::new();
+        let p_g = FixedGenerators::SpendingKeyGenerator;
+        let msg = b"multi-owner transfer";
+
+        let sks: Vec<PrivateKey<Bn256>> = (0..3).map(|_| PrivateKey::generate(rng)).collect();
+        let pks: Vec<PublicKey<Bn256>> = sks.iter().map(|sk| PublicKey::from_private(sk, p_g, params)).collect();
+        let key_agg = KeyAggContext::new(&pks, params);
+
+        // first round
+        let (secret_nonces, public_nonces): (Vec<_>, Vec<_>) = (0..3).map(|_| generate_nonces::<Bn256, _>(rng, p_g, params)).unzip();
+        let aggregated_nonce = aggregate_nonces(&public_nonces, params);
+
+        // second round
+        let session = SigningSession::new(&key_agg, &aggregated_nonce, msg, params);
+        let partials: Vec<_> = secret_nonces.into_iter().zip(sks.iter()).enumerate()
+            .map(|(i, (nonce, sk))| sign_partial(&session, &key_agg, i, sk, nonce))
+            .collect();
+
+        for (i, partial) in partials.iter().enumerate() {
+            assert!(verify_partial(&session, &key_agg, i, &pks[i], &public_nonces[i], partial, p_g, params));
+        }
+        assert!(!verify_partial(&session, &key_agg, 0, &pks[0], &public_nonces[0], &partials[1], p_g, params));
+
+        let signature = aggregate_partial_signatures(&session, &partials);
+        assert!(key_agg.aggregated_key().verify(msg, &signature, p_g, params));
+        assert!(!key_agg.aggregated_key().verify(b"another transfer", &signature, p_g, params));
+
+        // a missing partial signature gives an invalid signature
+        let signature = aggregate_partial_signatures(&session, &partials[..2]);
+        assert!(!key_agg.aggregated_key().verify(msg, &signature, p_g, params));
+
+        // the key order matters
+        let mut reordered = pks.clone();
+        reordered.swap(0, 1);
+        assert!(KeyAggContext::new(&reordered, params).aggregated_key().0 != key_agg.aggregated_key().0);
+    }
+}
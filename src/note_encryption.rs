@@ -0,0 +1,87 @@
+//! Note encryption: the sender derives a symmetric key from a fresh ephemeral key and the
+//! recipient key with `ecdh`, and encrypts the note plaintext with a BLAKE2s keystream,
+//! block i = BLAKE2s-256(personalization, key || LE32(i)). There is no authentication tag,
+//! the proof of `plonk::circuit::note_encryption` is what binds the ciphertext to the note.
+
+use blake2_rfc::blake2s::Blake2s;
+use byteorder::{ByteOrder, LittleEndian};
+
+use ecdh::{Kdf, recipient_key, sender_key};
+use jubjub::{FixedGenerators, JubjubEngine, Unknown, edwards::Point};
+
+pub const NOTE_KEYSTREAM_PERSONALIZATION: &[u8; 8] = b"JubjbNKS";
+pub const KEYSTREAM_BLOCK_BYTES: usize = 32;
+
+/// XORs `data` with the keystream for `key`, so this both encrypts and decrypts.
+pub fn apply_keystream(key: &[u8; 32], data: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(data.len());
+    for (i, chunk) in data.chunks(KEYSTREAM_BLOCK_BYTES).enumerate() {
+        let mut counter = [0u8; 4];
+        LittleEndian::write_u32(&mut counter, i as u32);
+
+        let mut h = Blake2s::with_params(KEYSTREAM_BLOCK_BYTES, &[], &[], NOTE_KEYSTREAM_PERSONALIZATION);
+        h.update(key);
+        h.update(&counter);
+        let block = h.finalize();
+
+        result.extend(chunk.iter().zip(block.as_ref().iter()).map(|(a, b)| a ^ b));
+    }
+
+    result
+}
+
+/// Returns the ephemeral public key and the ciphertext.
+pub fn encrypt_note<E: JubjubEngine, K: Kdf<E>>(
+    kdf: &K,
+    esk: E::Fs,
+    pk_recipient: &Point<E, Unknown>,
+    plaintext: &[u8],
+    p_g: FixedGenerators,
+    params: &E::Params
+) -> (Point<E, Unknown>, Vec<u8>) {
+    let (epk, key) = sender_key(kdf, esk, pk_recipient, p_g, params);
+
+    (epk, apply_keystream(&key, plaintext))
+}
+
+pub fn decrypt_note<E: JubjubEngine, K: Kdf<E>>(
+    kdf: &K,
+    sk: E::Fs,
+    epk: &Point<E, Unknown>,
+    ciphertext: &[u8],
+    params: &E::Params
+) -> Vec<u8> {
+    let key = recipient_key(kdf, sk, epk, params);
+
+    apply_keystream(&key, ciphertext)
+}
+
+#[cfg(test)]
+mod tests {
+    use bellman::pairing::bn256::Bn256;
+    use rand::{Rng, thread_rng};
+
+    use alt_babyjubjub::{AltJubjubBn256, fs::Fs};
+    use ecdh::Blake2sKdf;
+    use jubjub::JubjubParams;
+
+    use super::*;
+
+    #[test]
+    fn test_note_encryption_roundtrip() {
+        let rng = &mut thread_rng();
+        let params = &AltJubjubBn256::new();
+        let p_g = FixedGenerators::SpendingKeyGenerator;
+        let kdf = Blake2sKdf::default();
+
+        let sk: Fs = rng.gen();
+        let pk: Point<Bn256, Unknown> = params.generator(p_g).mul(sk, params).into();
+        let plaintext: Vec<u8> = (0..75).map(|_| rng.gen()).collect();
+
+        let (epk, ciphertext) = encrypt_note(&kdf, rng.gen(), &pk, &plaintext, p_g, params);
+        assert_eq!(ciphertext.len(), plaintext.len());
+        assert!(ciphertext != plaintext);
+        assert_eq!(decrypt_note(&kdf, sk, &epk, &ciphertext, params), plaintext);
+        assert!(decrypt_note(&kdf, rng.gen(), &epk, &ciphertext, params) != plaintext);
+    }
+}
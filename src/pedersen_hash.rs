@@ -1,10 +1,19 @@
 use jubjub::*;
 use bellman::pairing::ff::{Field, PrimeField, PrimeFieldRepr};
+use bellman::worker::Worker;
 
-#[derive(Copy, Clone)]
+/// 6 bit prefix reserved for application defined personalizations, Merkle tree levels
+/// can not reach it.
+pub const CUSTOM_PERSONALIZATION_PREFIX: usize = 62;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Personalization {
     NoteCommitment,
-    MerkleTree(usize)
+    MerkleTree(usize),
+    /// Application defined domain: the reserved 6 bit prefix followed by the 32 bit tag.
+    /// Different tags are never a prefix of each other nor of the Sapling personalizations,
+    /// so the hash inputs of different domains never coincide.
+    Custom(u32)
 }
 
 impl Personalization {
@@ -13,93 +22,131 @@ impl Personalization {
             Personalization::NoteCommitment =>
                 vec![true, true, true, true, true, true],
             Personalization::MerkleTree(num) => {
-                assert!(num < 63);
+                assert!(num < CUSTOM_PERSONALIZATION_PREFIX);
 
                 (0..6).map(|i| (num >> i) & 1 == 1).collect()
+            },
+            Personalization::Custom(tag) => {
+                (0..6).map(|i| (CUSTOM_PERSONALIZATION_PREFIX >> i) & 1 == 1)
+                      .chain((0..32).map(|i| (tag >> i) & 1 == 1))
+                      .collect()
             }
         }
     }
 }
 
-pub fn pedersen_hash<E, I>(
-    personalization: Personalization,
-    bits: I,
+// Sum of the encoded 3 bit chunks of one segment times its generator, using the table of
+// precomputed multiples of the generator from `pedersen_hash_exp_table`
+fn hash_segment<E: JubjubEngine>(
+    segment: &[bool],
+    table: &[Vec<edwards::Point<E, PrimeOrder>>],
     params: &E::Params
-) -> edwards::Point<E, PrimeOrder>
-    where I: IntoIterator<Item=bool>,
-          E: JubjubEngine
-{
-    let mut bits = personalization.get_bits().into_iter().chain(bits.into_iter());
+) -> edwards::Point<E, PrimeOrder> {
+    let mut acc = E::Fs::zero();
+    let mut cur = E::Fs::one();
+
+    for chunk in segment.chunks(3) {
+        let a = chunk[0];
+        let b = chunk.get(1).cloned().unwrap_or(false);
+        let c = chunk.get(2).cloned().unwrap_or(false);
+
+        // Start computing this portion of the scalar
+        let mut tmp = cur;
+        if a {
+            tmp.add_assign(&cur);
+        }
+        cur.double(); // 2^1 * cur
+        if b {
+            tmp.add_assign(&cur);
+        }
 
-    let mut result = edwards::Point::zero();
-    let mut generators = params.pedersen_hash_exp_table().iter();
+        // conditionally negate
+        if c {
+            tmp.negate();
+        }
 
-    loop {
-        let mut acc = E::Fs::zero();
-        let mut cur = E::Fs::one();
-        let mut chunks_remaining = params.pedersen_hash_chunks_per_generator();
-        let mut encountered_bits = false;
+        acc.add_assign(&tmp);
 
-        // Grab three bits from the input
-        while let Some(a) = bits.next() {
-            encountered_bits = true;
+        cur.double(); // 2^2 * cur
+        cur.double(); // 2^3 * cur
+        cur.double(); // 2^4 * cur
+    }
 
-            let b = bits.next().unwrap_or(false);
-            let c = bits.next().unwrap_or(false);
+    let mut table = table;
+    let window = params.pedersen_hash_exp_window_size();
+    let window_mask = (1 << window) - 1;
 
-            // Start computing this portion of the scalar
-            let mut tmp = cur;
-            if a {
-                tmp.add_assign(&cur);
-            }
-            cur.double(); // 2^1 * cur
-            if b {
-                tmp.add_assign(&cur);
-            }
+    let mut acc = acc.into_repr();
 
-            // conditionally negate
-            if c {
-                tmp.negate();
-            }
+    let mut result = edwards::Point::zero();
 
-            acc.add_assign(&tmp);
+    while !acc.is_zero() {
+        let i = (acc.as_ref()[0] & window_mask) as usize;
 
-            chunks_remaining -= 1;
+        result = result.add(&table[0][i], params);
 
-            if chunks_remaining == 0 {
-                break;
-            } else {
-                cur.double(); // 2^2 * cur
-                cur.double(); // 2^3 * cur
-                cur.double(); // 2^4 * cur
-            }
-        }
+        acc.shr(window);
+        table = &table[1..];
+    }
 
-        if !encountered_bits {
-            break;
-        }
+    result
+}
 
-        let mut table: &[Vec<edwards::Point<E, _>>] = &generators.next().expect("we don't have enough generators");
-        let window = params.pedersen_hash_exp_window_size();
-        let window_mask = (1 << window) - 1;
+pub fn pedersen_hash<E, I>(
+    personalization: Personalization,
+    bits: I,
+    params: &E::Params
+) -> edwards::Point<E, PrimeOrder>
+    where I: IntoIterator<Item=bool>,
+          E: JubjubEngine
+{
+    let bits: Vec<bool> = personalization.get_bits().into_iter().chain(bits.into_iter()).collect();
+    let bits_per_segment = 3 * params.pedersen_hash_chunks_per_generator();
 
-        let mut acc = acc.into_repr();
-        
-        let mut tmp = edwards::Point::zero();
+    let mut result = edwards::Point::zero();
+    let mut generators = params.pedersen_hash_exp_table().iter();
 
-        while !acc.is_zero() {
-            let i = (acc.as_ref()[0] & window_mask) as usize;
+    for segment in bits.chunks(bits_per_segment) {
+        let table = generators.next().expect("we don't have enough generators");
+        result = result.add(&hash_segment::<E>(segment, table, params), params);
+    }
 
-            tmp = tmp.add(&table[0][i], params);
+    result
+}
 
-            acc.shr(window);
-            table = &table[1..];
+/// Same as `pedersen_hash`, but the segments are processed in parallel by the `worker`.
+/// Worth it for long inputs only, e.g. when building big Merkle trees over wide leaves.
+pub fn pedersen_hash_multicore<E, I>(
+    personalization: Personalization,
+    bits: I,
+    params: &E::Params,
+    worker: &Worker
+) -> edwards::Point<E, PrimeOrder>
+    where I: IntoIterator<Item=bool>,
+          E: JubjubEngine,
+          E::Params: Sync
+{
+    let bits: Vec<bool> = personalization.get_bits().into_iter().chain(bits.into_iter()).collect();
+    let bits_per_segment = 3 * params.pedersen_hash_chunks_per_generator();
+    let segments: Vec<&[bool]> = bits.chunks(bits_per_segment).collect();
+    let tables = params.pedersen_hash_exp_table();
+    assert!(segments.len() <= tables.len(), "we don't have enough generators");
+
+    let mut segment_hashes = vec![edwards::Point::zero(); segments.len()];
+
+    worker.scope(segments.len(), |scope, chunk| {
+        for ((hashes, segments), tables) in segment_hashes.chunks_mut(chunk)
+                                                          .zip(segments.chunks(chunk))
+                                                          .zip(tables.chunks(chunk)) {
+            scope.spawn(move |_| {
+                for ((hash, segment), table) in hashes.iter_mut().zip(segments.iter()).zip(tables.iter()) {
+                    *hash = hash_segment::<E>(segment, table, params);
+                }
+            });
         }
+    });
 
-        result = result.add(&tmp, params);
-    }
-
-    result
+    segment_hashes.iter().fold(edwards::Point::zero(), |acc, p| acc.add(p, params))
 }
 
 use alt_babyjubjub::{AltJubjubBn256};
@@ -184,3 +231,104 @@ pub fn baby_pedersen_hash<E, I>(
 
     result
 }
+
+/// Injective padding of a message of at most `max_length` bits: the message is followed
+/// by a single `1` bit and zeroes up to `max_length + 1` bits in total.
+pub fn pad_variable_length(bits: &[bool], max_length: usize) -> Vec<bool> {
+    assert!(bits.len() <= max_length);
+
+    let mut padded = Vec::with_capacity(max_length + 1);
+    padded.extend_from_slice(bits);
+    padded.push(true);
+    padded.resize(max_length + 1, false);
+
+    padded
+}
+
+/// Pedersen hash of a message of at most `max_length` bits, padded with `pad_variable_length`.
+/// Different maximal lengths should use different personalizations.
+pub fn pedersen_hash_variable_length<E: JubjubEngine>(
+    personalization: Personalization,
+    bits: &[bool],
+    max_length: usize,
+    params: &E::Params
+) -> edwards::Point<E, PrimeOrder> {
+    pedersen_hash::<E, _>(personalization, pad_variable_length(bits, max_length), params)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bellman::pairing::bn256::Bn256;
+
+    #[test]
+    fn test_personalizations_are_prefix_free() {
+        let personalizations = vec![
+            Personalization::NoteCommitment,
+            Personalization::MerkleTree(0),
+            Personalization::MerkleTree(31),
+            Personalization::MerkleTree(CUSTOM_PERSONALIZATION_PREFIX - 1),
+            Personalization::Custom(0),
+            Personalization::Custom(1),
+            Personalization::Custom(u32::max_value()),
+        ];
+
+        for (i, a) in personalizations.iter().enumerate() {
+            for (j, b) in personalizations.iter().enumerate() {
+                if i == j {
+                    continue;
+                }
+                let a_bits = a.get_bits();
+                let b_bits = b.get_bits();
+                assert!(!b_bits.starts_with(&a_bits), "{:?} is a prefix of {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_custom_personalization_separates_domains() {
+        let params = AltJubjubBn256::new();
+        let bits: Vec<bool> = (0..100).map(|i| i % 3 == 0).collect();
+
+        let a = baby_pedersen_hash::<Bn256, _>(Personalization::Custom(1), bits.clone(), &params);
+        let b = baby_pedersen_hash::<Bn256, _>(Personalization::Custom(2), bits.clone(), &params);
+        let c = baby_pedersen_hash::<Bn256, _>(Personalization::Custom(1), bits, &params);
+
+        assert!(a != b);
+        assert!(a == c);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_merkle_tree_can_not_use_reserved_prefix() {
+        Personalization::MerkleTree(CUSTOM_PERSONALIZATION_PREFIX).get_bits();
+    }
+
+    #[test]
+    fn test_variable_length_padding_is_injective() {
+        let params = AltJubjubBn256::new();
+        let personalization = Personalization::Custom(1);
+
+        let short = vec![true, false, true];
+        let mut long = short.clone();
+        long.push(false);
+
+        assert_eq!(pad_variable_length(&short, 6), vec![true, false, true, true, false, false, false]);
+        assert!(pedersen_hash_variable_length::<Bn256>(personalization, &short, 6, &params)
+            != pedersen_hash_variable_length::<Bn256>(personalization, &long, 6, &params));
+    }
+
+    #[test]
+    fn test_pedersen_hash_multicore() {
+        let params = AltJubjubBn256::new();
+        let worker = Worker::new();
+
+        for &len in [0, 1, 185, 186, 1000].iter() {
+            let bits: Vec<bool> = (0..len).map(|i| i % 5 == 1 || i % 7 == 3).collect();
+
+            let expected = baby_pedersen_hash::<Bn256, _>(Personalization::MerkleTree(7), bits.clone(), &params);
+            assert_eq!(pedersen_hash::<Bn256, _>(Personalization::MerkleTree(7), bits.clone(), &params), expected);
+            assert_eq!(pedersen_hash_multicore::<Bn256, _>(Personalization::MerkleTree(7), bits, &params, &worker), expected);
+        }
+    }
+}
@@ -0,0 +1,392 @@
+use crate::bellman::pairing::{
+    Engine,
+};
+
+use crate::bellman::pairing::ff::{
+    Field,
+    PrimeField,
+};
+
+use crate::bellman::{
+    SynthesisError,
+};
+
+use crate::bellman::plonk::better_better_cs::cs::*;
+use crate::bellman::plonk::better_better_cs::lookup_tables::*;
+
+use super::allocated_num::{AllocatedNum, Num};
+use super::boolean::Boolean;
+use super::linear_combination::LinearCombination;
+use super::hashes_with_tables::tables::add_table_once;
+use super::utils::u64_to_fe;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+// AES-128 (FIPS 197) over bits. Bytes are 8 bits with the least significant bit first and
+// blocks are 16 bytes in order, as for the other hash gadgets. XORs are done on bits, the
+// S-box is a single lookup per byte.
+
+pub const AES_SBOX_TABLE_NAME: &'static str = "aes_sbox_table";
+
+const AES_BLOCK_BYTES: usize = 16;
+const AES_128_ROUNDS: usize = 10;
+const AES_ROUND_CONSTANTS: [u8; AES_128_ROUNDS] = [0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x1b, 0x36];
+
+// multiplication in GF(2^8) modulo x^8 + x^4 + x^3 + x + 1
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut result = 0u8;
+    while b != 0 {
+        if b & 1 == 1 {
+            result ^= a;
+        }
+        let carry = a & 0x80 != 0;
+        a <<= 1;
+        if carry {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+
+    result
+}
+
+/// The AES S-box: inversion in GF(2^8) followed by the affine map.
+pub fn aes_sbox() -> [u8; 256] {
+    let mut sbox = [0u8; 256];
+    for x in 0..256usize {
+        // x^254 = x^-1, and 0 for x = 0
+        let mut inverse = 1u8;
+        for _ in 0..254 {
+            inverse = gf_mul(inverse, x as u8);
+        }
+        let inverse = if x == 0 { 0 } else { inverse };
+
+        sbox[x] = inverse ^ inverse.rotate_left(1) ^ inverse.rotate_left(2) ^ inverse.rotate_left(3) ^ inverse.rotate_left(4) ^ 0x63;
+    }
+
+    sbox
+}
+
+// every row is (x, S(x), S(x))
+#[derive(Clone)]
+pub struct AesSboxTable<E: Engine> {
+    table_entries: [Vec<E::Fr>; 2],
+    table_lookup_map: HashMap<E::Fr, E::Fr>,
+    name: &'static str,
+}
+
+impl<E: Engine> AesSboxTable<E> {
+    pub fn new(name: &'static str) -> Self {
+        let mut keys = Vec::with_capacity(256);
+        let mut values = Vec::with_capacity(256);
+        let mut map = HashMap::with_capacity(256);
+
+        for (x, s) in aes_sbox().iter().enumerate() {
+            let x = u64_to_fe(x as u64);
+            let s = u64_to_fe(*s as u64);
+            keys.push(x);
+            values.push(s);
+            map.insert(x, s);
+        }
+
+        Self {
+            table_entries: [keys, values],
+            table_lookup_map: map,
+            name,
+        }
+    }
+}
+
+impl<E: Engine> std::fmt::Debug for AesSboxTable<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AesSboxTable").finish()
+    }
+}
+
+impl<E: Engine> LookupTableInternal<E> for AesSboxTable<E> {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+    fn table_size(&self) -> usize {
+        256
+    }
+    fn num_keys(&self) -> usize {
+        1
+    }
+    fn num_values(&self) -> usize {
+        2
+    }
+    fn allows_combining(&self) -> bool {
+        true
+    }
+    fn get_table_values_for_polys(&self) -> Vec<Vec<E::Fr>> {
+        vec![self.table_entries[0].clone(), self.table_entries[1].clone(), self.table_entries[1].clone()]
+    }
+    fn table_id(&self) -> E::Fr {
+        table_id_from_string(self.name)
+    }
+    fn sort(&self, _values: &[E::Fr], _column: usize) -> Result<Vec<E::Fr>, SynthesisError> {
+        unimplemented!()
+    }
+    fn box_clone(&self) -> Box<dyn LookupTableInternal<E>> {
+        Box::from(self.clone())
+    }
+    fn column_is_trivial(&self, column_num: usize) -> bool {
+        assert!(column_num < 3);
+        false
+    }
+
+    fn is_valid_entry(&self, keys: &[E::Fr], values: &[E::Fr]) -> bool {
+        assert!(keys.len() == self.num_keys());
+        assert!(values.len() == self.num_values());
+
+        if let Some(entry) = self.table_lookup_map.get(&keys[0]) {
+            return entry == &values[0] && entry == &values[1];
+        }
+        false
+    }
+
+    fn query(&self, keys: &[E::Fr]) -> Result<Vec<E::Fr>, SynthesisError> {
+        assert!(keys.len() == self.num_keys());
+
+        if let Some(entry) = self.table_lookup_map.get(&keys[0]) {
+            return Ok(vec![*entry, *entry])
+        }
+
+        Err(SynthesisError::Unsatisfiable)
+    }
+}
+
+/// Adds the S-box table to the constraint system if it's not there yet.
+pub fn add_aes_sbox_table<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS
+) -> Result<Arc<LookupTableApplication<E>>, SynthesisError> {
+    let columns = vec![
+        PolyIdentifier::VariablesPolynomial(0),
+        PolyIdentifier::VariablesPolynomial(1),
+        PolyIdentifier::VariablesPolynomial(2)
+    ];
+
+    let table = LookupTableApplication::new(
+        AES_SBOX_TABLE_NAME,
+        AesSboxTable::new(AES_SBOX_TABLE_NAME),
+        columns,
+        None,
+        true
+    );
+
+    add_table_once(cs, table)
+}
+
+fn sub_byte<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    table: &Arc<LookupTableApplication<E>>,
+    byte: &[Boolean]
+) -> Result<Vec<Boolean>, SynthesisError> {
+    assert_eq!(byte.len(), 8);
+
+    let mut lc = LinearCombination::zero();
+    let mut coeff = E::Fr::one();
+    for bit in byte.iter() {
+        lc.add_assign_boolean_with_coeff(bit, coeff);
+        coeff.double();
+    }
+    let x = match lc.into_num(cs)? {
+        Num::Constant(value) => {
+            let s = aes_sbox()[value.into_repr().as_ref()[0] as usize];
+
+            return Ok((0..8).map(|i| Boolean::constant((s >> i) & 1 == 1)).collect());
+        },
+        Num::Variable(var) => var,
+    };
+
+    let s = match x.get_value() {
+        None => AllocatedNum::alloc(cs, || Err(SynthesisError::AssignmentMissing))?,
+        Some(val) => {
+            let values = table.query(&[val])?;
+            AllocatedNum::alloc(cs, || Ok(values[0]))?
+        },
+    };
+
+    cs.begin_gates_batch_for_step()?;
+
+    let vars = [x.get_variable(), s.get_variable(), s.get_variable(), x.get_variable()];
+    cs.allocate_variables_without_gate(
+        &vars,
+        &[]
+    )?;
+    cs.apply_single_lookup_gate(&vars[..table.width()], table.clone())?;
+
+    cs.end_gates_batch_for_step()?;
+
+    s.into_bits_le(cs, Some(8))
+}
+
+fn xor_bytes<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    a: &[Boolean],
+    b: &[Boolean]
+) -> Result<Vec<Boolean>, SynthesisError> {
+    assert_eq!(a.len(), b.len());
+
+    a.iter().zip(b.iter()).map(|(a, b)| Boolean::xor(cs, a, b)).collect()
+}
+
+// multiplication by x in GF(2^8)
+fn xtime<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    a: &[Boolean]
+) -> Result<Vec<Boolean>, SynthesisError> {
+    let top = a[7];
+
+    Ok(vec![
+        top,
+        Boolean::xor(cs, &a[0], &top)?,
+        a[1],
+        Boolean::xor(cs, &a[2], &top)?,
+        Boolean::xor(cs, &a[3], &top)?,
+        a[4],
+        a[5],
+        a[6],
+    ])
+}
+
+fn constant_byte(value: u8) -> Vec<Boolean> {
+    (0..8).map(|i| Boolean::constant((value >> i) & 1 == 1)).collect()
+}
+
+/// The 11 round keys of AES-128, each of 128 bits.
+pub fn aes128_expand_key<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    key: &[Boolean]
+) -> Result<Vec<Vec<Boolean>>, SynthesisError> {
+    assert_eq!(key.len(), 128);
+    let table = add_aes_sbox_table(cs)?;
+
+    // 4 byte words, each byte as bits
+    let mut words: Vec<Vec<Vec<Boolean>>> = key.chunks(32).map(|word| word.chunks(8).map(|b| b.to_vec()).collect()).collect();
+    for i in 4..(4 * (AES_128_ROUNDS + 1)) {
+        let mut temp = words[i - 1].clone();
+        if i % 4 == 0 {
+            temp.rotate_left(1);
+            for byte in temp.iter_mut() {
+                *byte = sub_byte(cs, &table, byte)?;
+            }
+            temp[0] = xor_bytes(cs, &temp[0], &constant_byte(AES_ROUND_CONSTANTS[i / 4 - 1]))?;
+        }
+        let mut word = Vec::with_capacity(4);
+        for (a, b) in words[i - 4].iter().zip(temp.iter()) {
+            word.push(xor_bytes(cs, a, b)?);
+        }
+        words.push(word);
+    }
+
+    Ok(words.chunks(4).map(|round_key| round_key.iter().flatten().flatten().cloned().collect()).collect())
+}
+
+/// Encrypts a 128 bit block with the round keys from `aes128_expand_key`.
+pub fn aes128_encrypt_block_with_round_keys<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    round_keys: &[Vec<Boolean>],
+    block: &[Boolean]
+) -> Result<Vec<Boolean>, SynthesisError> {
+    assert_eq!(round_keys.len(), AES_128_ROUNDS + 1);
+    assert_eq!(block.len(), 128);
+    let table = add_aes_sbox_table(cs)?;
+
+    // state byte i is at row i % 4 and column i / 4
+    let mut state = xor_bytes(cs, block, &round_keys[0])?;
+    for round in 1..=AES_128_ROUNDS {
+        let mut bytes = Vec::with_capacity(AES_BLOCK_BYTES);
+        for byte in state.chunks(8) {
+            bytes.push(sub_byte(cs, &table, byte)?);
+        }
+
+        // row r is rotated left by r
+        let shifted: Vec<Vec<Boolean>> = (0..AES_BLOCK_BYTES).map(|i| {
+            let (row, column) = (i % 4, i / 4);
+            bytes[row + 4 * ((column + row) % 4)].clone()
+        }).collect();
+
+        let mixed = if round == AES_128_ROUNDS {
+            shifted
+        } else {
+            let mut mixed = Vec::with_capacity(AES_BLOCK_BYTES);
+            for column in shifted.chunks(4) {
+                let doubled: Vec<Vec<Boolean>> = column.iter().map(|b| xtime(cs, b)).collect::<Result<_, _>>()?;
+                // b_r = 2 a_r + 3 a_{r+1} + a_{r+2} + a_{r+3}
+                for row in 0..4 {
+                    let mut acc = xor_bytes(cs, &doubled[row], &doubled[(row + 1) % 4])?;
+                    acc = xor_bytes(cs, &acc, &column[(row + 1) % 4])?;
+                    acc = xor_bytes(cs, &acc, &column[(row + 2) % 4])?;
+                    acc = xor_bytes(cs, &acc, &column[(row + 3) % 4])?;
+                    mixed.push(acc);
+                }
+            }
+            mixed
+        };
+
+        let mixed: Vec<Boolean> = mixed.into_iter().flatten().collect();
+        state = xor_bytes(cs, &mixed, &round_keys[round])?;
+    }
+
+    Ok(state)
+}
+
+/// AES-128 encryption of a single 128 bit block.
+pub fn aes128_encrypt_block<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    key: &[Boolean],
+    block: &[Boolean]
+) -> Result<Vec<Boolean>, SynthesisError> {
+    let round_keys = aes128_expand_key(cs, key)?;
+
+    aes128_encrypt_block_with_round_keys(cs, &round_keys, block)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bellman::pairing::bn256::Bn256;
+
+    fn alloc_bytes<CS: ConstraintSystem<Bn256>>(cs: &mut CS, bytes: &[u8]) -> Vec<Boolean> {
+        bytes.iter()
+            .flat_map(|&byte| (0..8).map(move |i| (byte >> i) & 1u8 == 1u8))
+            .map(|b| Boolean::alloc(cs, Some(b)).unwrap())
+            .collect()
+    }
+
+    fn into_bytes(bits: &[Boolean]) -> Vec<u8> {
+        bits.chunks(8).map(|byte| {
+            byte.iter().enumerate().fold(0u8, |acc, (i, b)| acc | ((b.get_value().unwrap() as u8) << i))
+        }).collect()
+    }
+
+    #[test]
+    fn test_aes_sbox() {
+        let sbox = aes_sbox();
+        assert_eq!(sbox[0x00], 0x63);
+        assert_eq!(sbox[0x01], 0x7c);
+        assert_eq!(sbox[0x53], 0xed);
+        assert_eq!(sbox[0xff], 0x16);
+    }
+
+    #[test]
+    fn test_aes128_fips197_vectors() {
+        let vectors = [
+            ("000102030405060708090a0b0c0d0e0f", "00112233445566778899aabbccddeeff", "69c4e0d86a7b0430d8cdb78070b4c55a"),
+            ("2b7e151628aed2a6abf7158809cf4f3c", "3243f6a8885a308d313198a2e0370734", "3925841d02dc09fbdc118597196a0b32"),
+        ];
+
+        for (key, plaintext, ciphertext) in vectors.iter() {
+            let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+            let key = alloc_bytes(&mut cs, &hex::decode(key).unwrap());
+            let plaintext = alloc_bytes(&mut cs, &hex::decode(plaintext).unwrap());
+
+            let result = aes128_encrypt_block(&mut cs, &key, &plaintext).unwrap();
+            assert!(cs.is_satisfied());
+            assert_eq!(hex::encode(into_bytes(&result)), *ciphertext);
+        }
+    }
+}
@@ -27,6 +27,7 @@ use super::utils::is_selector_specialized_gate;
 
 use super::boolean::{self, AllocatedBit, Boolean};
 use super::linear_combination::*;
+use super::tags;
 
 use crate::plonk::circuit::Assignment;
 
@@ -1187,6 +1188,87 @@ impl<E: Engine> Num<E> {
         }
     }
 
+    /// Decomposes into `E::Fr::NUM_BITS` bits and enforces that they encode the canonical
+    /// representative, so the encoding of the value is unique.
+    pub fn into_bits_le_strict<CS>(
+        &self,
+        cs: &mut CS
+    ) -> Result<Vec<Boolean>, SynthesisError>
+    where
+        CS: ConstraintSystem<E>,
+    {
+        let num_bits = E::Fr::NUM_BITS as usize;
+        tags::tagged(cs, tags::RANGE_CHECK, |cs| {
+            let bits = self.into_bits_le(cs, Some(num_bits))?;
+            if self.is_constant() {
+                return Ok(bits);
+            }
+
+            let mut modulus_minus_one = E::Fr::char();
+            modulus_minus_one.sub_noborrow(&<E::Fr as PrimeField>::Repr::from(1));
+            let modulus_bits: Vec<bool> = BitIterator::new(modulus_minus_one).collect();
+            let modulus_bits = &modulus_bits[(modulus_bits.len() - num_bits)..];
+
+            // `run` is set while the most significant bits are equal to the ones of p - 1,
+            // after that a bit can't be set where p - 1 has zero
+            let mut run = Boolean::constant(true);
+            for (bit, modulus_bit) in bits.iter().rev().zip(modulus_bits.iter()) {
+                if *modulus_bit {
+                    run = Boolean::and(cs, &run, bit)?;
+                } else {
+                    let overflow = Boolean::and(cs, &run, bit)?;
+                    Boolean::enforce_equal(cs, &overflow, &Boolean::constant(false))?;
+                }
+            }
+
+            Ok(bits)
+        })
+    }
+
+    /// Enforces that the value fits into `num_bits` bits. Uses the range table if one is
+    /// inscribed into the constraint system (see `inscribe_default_bitop_range_table`), which
+    /// costs about a gate per 16 bits, and a bit decomposition otherwise. Prefer this to
+    /// `into_bits_le` when the bits themselves are not needed.
+    pub fn enforce_bit_length<CS>(
+        &self,
+        cs: &mut CS,
+        num_bits: usize
+    ) -> Result<(), SynthesisError>
+    where
+        CS: ConstraintSystem<E>,
+    {
+        use crate::plonk::circuit::bigint_new::{
+            get_optimal_strategy,
+            constraint_bit_length_with_strategy,
+            RangeConstraintStrategy
+        };
+
+        assert!(num_bits > 0);
+        match self {
+            Num::Constant(c) => {
+                if c.into_repr().num_bits() as usize > num_bits {
+                    return Err(SynthesisError::Unsatisfiable);
+                }
+
+                Ok(())
+            },
+            Num::Variable(var) => {
+                tags::tagged(cs, tags::RANGE_CHECK, |cs| {
+                    match get_optimal_strategy(cs) {
+                        strategy @ RangeConstraintStrategy::WithBitwiseOpTable(_) => {
+                            constraint_bit_length_with_strategy(cs, var, num_bits, strategy)
+                        },
+                        _ => {
+                            self.into_bits_le(cs, Some(num_bits))?;
+
+                            Ok(())
+                        }
+                    }
+                })
+            }
+        }
+    }
+
     pub fn conditionally_select_multiple<CS: ConstraintSystem<E>, const N: usize>(
         cs: &mut CS,
         flag: &Boolean,
@@ -2467,6 +2549,50 @@ mod test {
         Width4MainGateWithDNext
     };
 
+    #[test]
+    fn test_into_bits_le_strict() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let mut minus_one = Fr::one();
+        minus_one.negate();
+
+        for value in vec![Fr::zero(), minus_one, rng.gen()].into_iter() {
+            let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+            let num = Num::alloc(&mut cs, Some(value)).unwrap();
+            let bits = num.into_bits_le_strict(&mut cs).unwrap();
+
+            assert!(cs.is_satisfied());
+            assert_eq!(bits.len(), Fr::NUM_BITS as usize);
+            let expected: Vec<bool> = BitIterator::new(value.into_repr()).collect();
+            let expected: Vec<bool> = expected.into_iter().rev().take(bits.len()).collect();
+            assert_eq!(bits.iter().map(|b| b.get_value().unwrap()).collect::<Vec<_>>(), expected);
+        }
+    }
+
+    #[test]
+    fn test_enforce_bit_length() {
+        use crate::plonk::circuit::bigint_new::inscribe_default_bitop_range_table;
+
+        let value = Fr::from_str("65535").unwrap();
+        let mut gates = vec![];
+        for with_table in [false, true].iter() {
+            let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+            if *with_table {
+                inscribe_default_bitop_range_table(&mut cs).unwrap();
+            }
+
+            let num = Num::alloc(&mut cs, Some(value)).unwrap();
+            let before = cs.n();
+            num.enforce_bit_length(&mut cs, 16).unwrap();
+            gates.push(cs.n() - before);
+            assert!(cs.is_satisfied());
+        }
+        assert!(gates[1] < gates[0]);
+
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+        assert!(Num::Constant(value).enforce_bit_length(&mut cs, 16).is_ok());
+        assert!(Num::Constant(value).enforce_bit_length(&mut cs, 15).is_err());
+    }
+
     #[test]
     fn test_multiplication() {
         let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
@@ -0,0 +1,230 @@
+use crate::bellman::pairing::{
+    Engine,
+};
+
+use crate::bellman::{
+    SynthesisError,
+};
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    ConstraintSystem,
+};
+
+use super::boolean::Boolean;
+
+// All the functions below work with LSB first bit vectors and take the shift amount
+// as LSB first bits as well. The network has one layer per bit of the shift amount:
+// layer i either keeps the vector or moves it by 2^i positions, so the cost is
+// len(bits) * len(shift) conditional selects (selects against constants are cheaper).
+// Shifting by an amount >= len(bits) is well defined: shifts produce all zeroes and
+// rotations wrap around.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ShiftKind {
+    Left,
+    Right,
+    RotateLeft,
+    RotateRight,
+}
+
+fn move_by_constant(bits: &[Boolean], by: usize, kind: ShiftKind) -> Vec<Boolean> {
+    let len = bits.len();
+    let mut result = vec![Boolean::constant(false); len];
+    for (i, r) in result.iter_mut().enumerate() {
+        *r = match kind {
+            // in LSB first order the left shift moves bits to higher indexes
+            ShiftKind::Left => {
+                if i >= by { bits[i - by] } else { Boolean::constant(false) }
+            },
+            ShiftKind::Right => {
+                if i + by < len { bits[i + by] } else { Boolean::constant(false) }
+            },
+            ShiftKind::RotateLeft => {
+                bits[(i + len - (by % len)) % len]
+            },
+            ShiftKind::RotateRight => {
+                bits[(i + by) % len]
+            },
+        };
+    }
+
+    result
+}
+
+fn barrel_shift<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    bits: &[Boolean],
+    shift: &[Boolean],
+    kind: ShiftKind
+) -> Result<Vec<Boolean>, SynthesisError> {
+    if bits.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let len = bits.len();
+    let mut current = bits.to_vec();
+
+    // 2^layer mod len, and whether 2^layer >= len, by doubling: the shift amount
+    // may have more bits than any native integer
+    let mut by = 1 % len;
+    let mut at_least_len = len == 1;
+
+    for flag in shift.iter() {
+        let layer_by = by;
+        let layer_at_least_len = at_least_len;
+        at_least_len = at_least_len || 2 * by >= len;
+        by = (2 * by) % len;
+
+        let moved = match kind {
+            ShiftKind::RotateLeft | ShiftKind::RotateRight => {
+                if layer_by == 0 {
+                    // rotation by a multiple of the length is an identity
+                    continue;
+                }
+                move_by_constant(&current, layer_by, kind)
+            },
+            ShiftKind::Left | ShiftKind::Right => {
+                if layer_at_least_len {
+                    vec![Boolean::constant(false); len]
+                } else {
+                    move_by_constant(&current, layer_by, kind)
+                }
+            }
+        };
+
+        let mut next = Vec::with_capacity(len);
+        for (m, c) in moved.iter().zip(current.iter()) {
+            next.push(Boolean::conditionally_select(cs, flag, m, c)?);
+        }
+        current = next;
+    }
+
+    Ok(current)
+}
+
+/// Shifts LSB first `bits` towards the most significant end by the amount
+/// encoded in LSB first `shift`, filling with zeroes.
+pub fn shl<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    bits: &[Boolean],
+    shift: &[Boolean]
+) -> Result<Vec<Boolean>, SynthesisError> {
+    barrel_shift(cs, bits, shift, ShiftKind::Left)
+}
+
+/// Shifts LSB first `bits` towards the least significant end by the amount
+/// encoded in LSB first `shift`, filling with zeroes.
+pub fn shr<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    bits: &[Boolean],
+    shift: &[Boolean]
+) -> Result<Vec<Boolean>, SynthesisError> {
+    barrel_shift(cs, bits, shift, ShiftKind::Right)
+}
+
+/// Rotates LSB first `bits` towards the most significant end by the amount
+/// encoded in LSB first `shift`.
+pub fn rotl<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    bits: &[Boolean],
+    shift: &[Boolean]
+) -> Result<Vec<Boolean>, SynthesisError> {
+    barrel_shift(cs, bits, shift, ShiftKind::RotateLeft)
+}
+
+/// Rotates LSB first `bits` towards the least significant end by the amount
+/// encoded in LSB first `shift`. For 32 bit words it matches `UInt32::rotr`
+/// with a witness amount.
+pub fn rotr<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    bits: &[Boolean],
+    shift: &[Boolean]
+) -> Result<Vec<Boolean>, SynthesisError> {
+    barrel_shift(cs, bits, shift, ShiftKind::RotateRight)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use crate::bellman::plonk::better_better_cs::cs::*;
+
+    fn alloc_u32<E: Engine, CS: ConstraintSystem<E>>(cs: &mut CS, value: u32, width: usize) -> Vec<Boolean> {
+        (0..width).map(|i| Boolean::alloc(cs, Some((value >> i) & 1 == 1)).unwrap()).collect()
+    }
+
+    fn into_u32(bits: &[Boolean]) -> u32 {
+        bits.iter().enumerate().fold(0u32, |acc, (i, b)| {
+            acc | ((b.get_value().unwrap() as u32) << i)
+        })
+    }
+
+    #[test]
+    fn test_barrel_shifter_against_native() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        for _ in 0..8 {
+            let value: u32 = rng.gen();
+            for amount in 0u32..40 {
+                let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+                let bits = alloc_u32(&mut cs, value, 32);
+                let shift = alloc_u32(&mut cs, amount, 6);
+
+                let left = shl(&mut cs, &bits, &shift).unwrap();
+                let right = shr(&mut cs, &bits, &shift).unwrap();
+                let rot_left = rotl(&mut cs, &bits, &shift).unwrap();
+                let rot_right = rotr(&mut cs, &bits, &shift).unwrap();
+
+                assert!(cs.is_satisfied());
+                assert_eq!(into_u32(&left), value.checked_shl(amount).unwrap_or(0));
+                assert_eq!(into_u32(&right), value.checked_shr(amount).unwrap_or(0));
+                assert_eq!(into_u32(&rot_left), value.rotate_left(amount));
+                assert_eq!(into_u32(&rot_right), value.rotate_right(amount));
+            }
+        }
+    }
+
+    #[test]
+    fn test_barrel_shifter_odd_width() {
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+        // 5 bit word 0b10011 rotated right by 3 is 0b01110
+        let bits = alloc_u32(&mut cs, 0b10011, 5);
+        let shift = alloc_u32(&mut cs, 3, 3);
+        let rotated = rotr(&mut cs, &bits, &shift).unwrap();
+
+        assert!(cs.is_satisfied());
+        assert_eq!(into_u32(&rotated), 0b01110);
+    }
+
+    #[test]
+    fn test_barrel_shifter_wide() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let width = 100;
+        let value: Vec<bool> = (0..width).map(|_| rng.gen()).collect();
+
+        // amounts with the layers above 64 set
+        let amounts = vec![3u128, 99, 100, 1 << 64, (1 << 65) + 3, (1 << 69) + (1 << 66) + 17, (1 << 70) - 1];
+        for &amount in amounts.iter() {
+            let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+            let bits: Vec<Boolean> = value.iter().map(|b| Boolean::alloc(&mut cs, Some(*b)).unwrap()).collect();
+            let shift: Vec<Boolean> = (0..70).map(|i| Boolean::alloc(&mut cs, Some((amount >> i) & 1 == 1)).unwrap()).collect();
+
+            let left = shl(&mut cs, &bits, &shift).unwrap();
+            let right = shr(&mut cs, &bits, &shift).unwrap();
+            let rot_left = rotl(&mut cs, &bits, &shift).unwrap();
+            let rot_right = rotr(&mut cs, &bits, &shift).unwrap();
+            assert!(cs.is_satisfied());
+
+            let rotation = (amount % (width as u128)) as usize;
+            for i in 0..width {
+                let expected_left = amount < width as u128 && (amount as usize) <= i && value[i - amount as usize];
+                let expected_right = amount < width as u128 && i + (amount as usize) < width && value[i + amount as usize];
+                assert_eq!(left[i].get_value().unwrap(), expected_left);
+                assert_eq!(right[i].get_value().unwrap(), expected_right);
+                assert_eq!(rot_left[i].get_value().unwrap(), value[(i + width - rotation) % width]);
+                assert_eq!(rot_right[i].get_value().unwrap(), value[(i + rotation) % width]);
+            }
+        }
+    }
+}
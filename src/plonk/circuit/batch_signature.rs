@@ -0,0 +1,299 @@
+use crate::bellman::pairing::ff::{
+    Field,
+    PrimeField,
+};
+
+use crate::bellman::{
+    SynthesisError,
+};
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    ConstraintSystem,
+};
+
+use super::allocated_num::Num;
+use super::boolean::Boolean;
+use super::blake2b::blake2b;
+use super::linear_combination::LinearCombination;
+use super::edwards::{CircuitTwistedEdwardsCurveImplementor, CircuitTwistedEdwardsPoint};
+use super::poseidon::{poseidon_hash, PoseidonCsSBox};
+use super::redjubjub::{CircuitRedJubjubSignature, REDJUBJUB_PERSONALIZATION, point_into_bits_le};
+
+use crate::plonk::circuit::bigint_new::*;
+use crate::generic_twisted_edwards::TwistedEdwardsCurveParams;
+use crate::jubjub::{FixedGenerators, JubjubEngine, JubjubParams};
+use crate::poseidon::{PoseidonEngine, PoseidonHashParams};
+
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+
+// Batched verification of RedJubjub signatures. Instead of checking
+// h_G(S_i . P_G) = h_G(R_i + c_i . vk_i) for every signature, the prover is challenged with
+// random z_i derived in circuit from all the signatures, keys and hashes, and a single equation
+//
+//     h_G(sum_i (z_i * c_i mod r) . vk_i + sum_i z_i . R_i - (sum_i z_i * S_i mod r) . P_G) = 0
+//
+// is checked by a multi-scalar multiplication that shares the doublings between all the terms.
+// The generator term is a single multiplication for the whole batch. Scalars modulo the
+// order r of P_G are handled by the nonnative field arithmetic.
+
+/// Bit length of the random coefficients z_i.
+pub const BATCH_CHALLENGE_BITS: usize = 128;
+
+// chunks of the 512 bit hash c that are certainly below the group order
+const HASH_CHUNK_BITS: usize = 250;
+
+#[derive(Clone, Debug)]
+pub struct CircuitBatchEntry<E: JubjubEngine> {
+    pub vk: CircuitTwistedEdwardsPoint<E>,
+    pub signature: CircuitRedJubjubSignature<E>,
+    /// Whole bytes, little endian bits in every byte.
+    pub message: Vec<Boolean>,
+}
+
+fn bits_into_biguint(bits: &[Boolean]) -> Option<BigUint> {
+    let mut result = BigUint::zero();
+    for bit in bits.iter().rev() {
+        result <<= 1;
+        if bit.get_value()? {
+            result += BigUint::one();
+        }
+    }
+
+    Some(result)
+}
+
+fn pack_bits<E: JubjubEngine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    bits: &[Boolean]
+) -> Result<Num<E>, SynthesisError> {
+    assert!(bits.len() <= E::Fr::CAPACITY as usize);
+
+    let mut lc = LinearCombination::zero();
+    let mut coeff = E::Fr::one();
+    for bit in bits.iter() {
+        lc.add_assign_boolean_with_coeff(bit, coeff);
+        coeff.double();
+    }
+
+    lc.into_num(cs)
+}
+
+/// Scalar given by little endian `bits`, the value must be below the group order and
+/// the circuit is unsatisfiable otherwise.
+fn alloc_scalar_from_bits<'a, E, CS>(
+    cs: &mut CS,
+    bits: &[Boolean],
+    params: &'a RnsParameters<E, E::Fs>
+) -> Result<FieldElement<'a, E, E::Fs>, SynthesisError>
+    where E: JubjubEngine, CS: ConstraintSystem<E>
+{
+    let value = bits_into_biguint(bits).map(|v| biguint_to_fe::<E::Fs>(v));
+    let mut scalar = FieldElement::alloc(cs, value, params)?;
+    scalar.normalize(cs)?;
+    let scalar_bits = scalar.decompose_into_binary_representation(cs)?;
+    assert!(bits.len() <= scalar_bits.len());
+
+    for (i, bit) in scalar_bits.iter().enumerate() {
+        let expected = bits.get(i).cloned().unwrap_or(Boolean::constant(false));
+        Boolean::enforce_equal(cs, bit, &expected)?;
+    }
+
+    Ok(scalar)
+}
+
+/// Verifies all the signatures of `entries` at once, see `redjubjub::verify_signature` for a
+/// single one. S of every signature must be reduced, as the native verifier requires.
+/// Returns the result of the check together with every R being on curve.
+pub fn batch_verify_signatures<'a, E, CS, C>(
+    cs: &mut CS,
+    curve: &CircuitTwistedEdwardsCurveImplementor<E, C>,
+    entries: &[CircuitBatchEntry<E>],
+    p_g: FixedGenerators,
+    jubjub_params: &<E as JubjubEngine>::Params,
+    poseidon_params: &<E as PoseidonEngine>::Params,
+    scalar_params: &'a RnsParameters<E, E::Fs>
+) -> Result<Boolean, SynthesisError>
+    where E: JubjubEngine + PoseidonEngine,
+          CS: ConstraintSystem<E>,
+          C: TwistedEdwardsCurveParams<E>,
+          <<E as PoseidonEngine>::Params as PoseidonHashParams<E>>::SBox: PoseidonCsSBox<E>
+{
+    assert!(entries.len() > 0);
+    assert!(BATCH_CHALLENGE_BITS < E::Fs::CAPACITY as usize);
+
+    let modulus = repr_to_biguint::<E::Fs>(&E::Fs::char());
+    let chunk_shift = |i: usize| {
+        let value = (BigUint::one() << (HASH_CHUNK_BITS * i)) % &modulus;
+        FieldElement::constant(biguint_to_fe::<E::Fs>(value), scalar_params)
+    };
+
+    let mut checks = Vec::with_capacity(entries.len() + 1);
+    let mut hashes = Vec::with_capacity(entries.len());
+    let mut transcript = Num::zero();
+
+    for entry in entries.iter() {
+        assert!(entry.message.len() % 8 == 0);
+        checks.push(curve.check_is_on_curve(cs, &entry.signature.r)?);
+
+        // c = H*(Rbar || M)
+        let mut hash_input = point_into_bits_le(cs, &entry.signature.r)?;
+        hash_input.extend_from_slice(&entry.message);
+        let c = blake2b(cs, &hash_input, &REDJUBJUB_PERSONALIZATION[..])?;
+
+        let mut transcript_input = vec![
+            transcript,
+            entry.signature.r.x,
+            entry.signature.r.y,
+            entry.vk.x,
+            entry.vk.y,
+            pack_bits(cs, &entry.signature.s)?,
+        ];
+        for chunk in c.chunks(HASH_CHUNK_BITS) {
+            transcript_input.push(pack_bits(cs, chunk)?);
+        }
+        transcript = poseidon_hash(cs, poseidon_params, &transcript_input)?[0];
+
+        hashes.push(c);
+    }
+
+    let mut terms: Vec<(CircuitTwistedEdwardsPoint<E>, Vec<Boolean>)> = Vec::with_capacity(2 * entries.len() + 1);
+    let mut s_acc = FieldElement::zero(scalar_params);
+
+    for (i, (entry, c)) in entries.iter().zip(hashes.iter()).enumerate() {
+        let challenge_input = [transcript, Num::Constant(super::utils::u64_to_fe(i as u64))];
+        let challenge = poseidon_hash(cs, poseidon_params, &challenge_input)?[0];
+        let mut z_bits = challenge.into_bits_le_strict(cs)?;
+        z_bits.truncate(BATCH_CHALLENGE_BITS);
+        let z = alloc_scalar_from_bits(cs, &z_bits, scalar_params)?;
+
+        // c mod r
+        let mut c_reduced = FieldElement::zero(scalar_params);
+        for (j, chunk) in c.chunks(HASH_CHUNK_BITS).enumerate() {
+            let chunk = alloc_scalar_from_bits(cs, chunk, scalar_params)?;
+            let shifted = chunk.mul(cs, &chunk_shift(j))?;
+            c_reduced = c_reduced.add(cs, &shifted)?;
+        }
+
+        let s = alloc_scalar_from_bits(cs, &entry.signature.s, scalar_params)?;
+        let zs = z.mul(cs, &s)?;
+        s_acc = s_acc.add(cs, &zs)?;
+
+        // non canonical bits of z * c are fine, the difference is a multiple of r
+        // that is killed by the cofactor
+        let mut zc = z.mul(cs, &c_reduced)?;
+        terms.push((entry.vk, zc.decompose_into_binary_representation(cs)?));
+        terms.push((entry.signature.r, z_bits));
+    }
+
+    let (g_x, g_y) = jubjub_params.generator(p_g).into_xy();
+    let mut minus_g_x = g_x;
+    minus_g_x.negate();
+    let minus_generator = CircuitTwistedEdwardsPoint::constant(minus_g_x, g_y);
+    terms.push((minus_generator, s_acc.decompose_into_binary_representation(cs)?));
+
+    // multi-scalar multiplication with shared doublings, MSB first
+    let max_bits = terms.iter().map(|(_, bits)| bits.len()).max().unwrap();
+    let mut acc = CircuitTwistedEdwardsPoint::zero();
+    for j in (0..max_bits).rev() {
+        acc = curve.double(cs, &acc)?;
+        for (point, bits) in terms.iter() {
+            if let Some(bit) = bits.get(j) {
+                let addend = CircuitTwistedEdwardsPoint::conditionally_select(
+                    cs, bit, point, &CircuitTwistedEdwardsPoint::zero()
+                )?;
+                acc = curve.add(cs, &acc, &addend)?;
+            }
+        }
+    }
+
+    for _ in 0..curve.implementor.get_params().log_2_cofactor() {
+        acc = curve.double(cs, &acc)?;
+    }
+    checks.push(CircuitTwistedEdwardsPoint::equals(cs, &acc, &CircuitTwistedEdwardsPoint::zero())?);
+
+    Boolean::kary_and(cs, &checks)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use bellman::pairing::bn256::Bn256;
+    use crate::bellman::plonk::better_better_cs::cs::*;
+    use crate::bellman::plonk::better_better_cs::gates::selector_optimized_with_d_next::SelectorOptimizedWidth4MainGateWithDNext;
+    use crate::plonk::circuit::Width4WithCustomGates;
+    use crate::alt_babyjubjub::{AltJubjubBn256, fs::Fs, edwards};
+    use crate::jubjub::Unknown;
+    use crate::generic_twisted_edwards::TwistedEdwardsPoint;
+    use crate::plonk::circuit::edwards::bn256::CircuitAltBabyJubjubBn256;
+    use crate::plonk::circuit::utils::fe_to_lsb_first_bits;
+    use crate::poseidon::Bn256PoseidonParams;
+    use crate::redjubjub::{PrivateKey, PublicKey};
+    use crate::bellman::pairing::ff::PrimeFieldRepr;
+
+    fn alloc_point<CS: ConstraintSystem<Bn256>>(cs: &mut CS, p: &edwards::Point<Bn256, Unknown>) -> CircuitTwistedEdwardsPoint<Bn256> {
+        let curve = CircuitAltBabyJubjubBn256::get_implementor();
+        let (x, y) = p.into_xy();
+
+        curve.alloc_point_enforce_on_curve(cs, Some(TwistedEdwardsPoint::from_xy(x, y))).unwrap()
+    }
+
+    fn alloc_bytes<CS: ConstraintSystem<Bn256>>(cs: &mut CS, bytes: &[u8]) -> Vec<Boolean> {
+        bytes.iter()
+             .flat_map(|byte| (0..8).map(move |i| (byte >> i) & 1 == 1))
+             .map(|b| Boolean::alloc(cs, Some(b)).unwrap())
+             .collect()
+    }
+
+    #[test]
+    fn test_batch_verify_signatures() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = AltJubjubBn256::new();
+        let poseidon_params = Bn256PoseidonParams::new_checked_2_into_1();
+        let curve = CircuitAltBabyJubjubBn256::get_implementor();
+        let p_g = FixedGenerators::SpendingKeyGenerator;
+
+        let messages: Vec<Vec<u8>> = (0..3).map(|i| format!("transaction {}", i).into_bytes()).collect();
+        let mut signed = vec![];
+        for msg in messages.iter() {
+            let sk = PrivateKey::<Bn256>(rng.gen());
+            let vk = PublicKey::from_private(&sk, p_g, &params);
+            let signature = sk.sign(msg, &mut rng, p_g, &params);
+            assert!(vk.verify(msg, &signature, p_g, &params));
+
+            let mut signature_bytes = vec![];
+            signature.write(&mut signature_bytes).unwrap();
+            let r = edwards::Point::<Bn256, Unknown>::read(&signature_bytes[..32], &params).unwrap();
+            let mut s_repr = <Fs as PrimeField>::Repr::default();
+            s_repr.read_le(&signature_bytes[32..]).unwrap();
+            let s = Fs::from_repr(s_repr).unwrap();
+
+            signed.push((vk, r, s));
+        }
+
+        for tampered in vec![None, Some(1)].into_iter() {
+            let mut cs = TrivialAssembly::<Bn256, Width4WithCustomGates, SelectorOptimizedWidth4MainGateWithDNext>::new();
+            inscribe_default_bitop_range_table(&mut cs).unwrap();
+            let scalar_params = RnsParameters::<Bn256, Fs>::new_optimal(&mut cs, 64usize);
+
+            let entries: Vec<_> = signed.iter().zip(messages.iter()).enumerate().map(|(i, ((vk, r, s), msg))| {
+                let msg = if tampered == Some(i) { &b"transaction X"[..] } else { &msg[..] };
+                let s_bits = fe_to_lsb_first_bits(s).into_iter().map(|b| Boolean::alloc(&mut cs, Some(b)).unwrap()).collect();
+
+                CircuitBatchEntry {
+                    vk: alloc_point(&mut cs, &vk.0),
+                    signature: CircuitRedJubjubSignature { r: alloc_point(&mut cs, r), s: s_bits },
+                    message: alloc_bytes(&mut cs, msg),
+                }
+            }).collect();
+
+            let is_valid = batch_verify_signatures(
+                &mut cs, &curve, &entries, p_g, &params, &poseidon_params, &scalar_params
+            ).unwrap();
+
+            assert!(cs.is_satisfied());
+            assert_eq!(is_valid.get_value().unwrap(), tampered.is_none());
+        }
+    }
+}
@@ -5,6 +5,7 @@ use std::sync::atomic::{AtomicUsize, Ordering};
 use crate::plonk::circuit::linear_combination::*;
 use crate::plonk::circuit::SomeArithmetizable;
 use crate::plonk::circuit::assignment::Assignment;
+use crate::plonk::circuit::tags;
 use crate::plonk::circuit::hashes_with_tables::utils::IdentifyFirstLast;
 use crate::bellman::plonk::better_better_cs::lookup_tables::LookupTableApplication;
 use num_bigint::BigUint;
@@ -136,19 +137,21 @@ impl<E: Engine> RangeCheckDecomposition<E> {
 pub fn constraint_bit_length_ext_with_strategy<E: Engine, CS: ConstraintSystem<E>>(
     cs: &mut CS, var: &AllocatedNum<E>, num_bits: usize, strategy: RangeConstraintStrategy, coarsely: bool
 ) -> Result<RangeCheckDecomposition<E>, SynthesisError> {
-    match strategy {
-        RangeConstraintStrategy::NaiveSingleBit => {
-            enforce_range_check_using_naive_approach(cs, var, num_bits)
-        },
-        RangeConstraintStrategy::CustomTwoBitGate => {
-            unreachable!();
-            enforce_range_check_using_custom_gate(cs, var, num_bits, coarsely)
-        },
-        RangeConstraintStrategy::WithBitwiseOpTable(_table_width) => {  
-            let table = cs.get_table(BITWISE_LOGICAL_OPS_TABLE_NAME).expect("should found a valid table");         
-            enforce_range_check_using_bitop_table(cs, var, num_bits, table, coarsely)
-        }    
-    }
+    tags::tagged(cs, tags::RANGE_CHECK, |cs| {
+        match strategy {
+            RangeConstraintStrategy::NaiveSingleBit => {
+                enforce_range_check_using_naive_approach(cs, var, num_bits)
+            },
+            RangeConstraintStrategy::CustomTwoBitGate => {
+                unreachable!();
+                enforce_range_check_using_custom_gate(cs, var, num_bits, coarsely)
+            },
+            RangeConstraintStrategy::WithBitwiseOpTable(_table_width) => {  
+                let table = cs.get_table(BITWISE_LOGICAL_OPS_TABLE_NAME).expect("should found a valid table");         
+                enforce_range_check_using_bitop_table(cs, var, num_bits, table, coarsely)
+            }    
+        }
+    })
 }
 
 pub fn constraint_bit_length_with_strategy<E: Engine, CS: ConstraintSystem<E>>(
@@ -0,0 +1,344 @@
+use crate::bellman::pairing::Engine;
+use crate::bellman::pairing::ff::{Field, PrimeField};
+
+use crate::bellman::{
+    SynthesisError,
+};
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    ConstraintSystem,
+};
+
+use super::allocated_num::Num;
+use super::boolean::Boolean;
+use super::linear_combination::LinearCombination;
+use super::sha256::sha256;
+use super::utils::u64_to_fe;
+
+// Bitcoin block headers, for SPV proofs. A header is 80 bytes
+//
+//      version (4) || previous block hash (32) || merkle root (32) || time (4) || bits (4) || nonce (4)
+//
+// with integers little endian, given as bits in the order of `sha256`: bytes in order, the most
+// significant bit of every byte first. The block hash is SHA-256(SHA-256(header)), read as a
+// little endian 256 bit integer it must not exceed the target encoded by the compact `bits`
+// field: mantissa . 256^(exponent - 3), the exponent being the most significant byte.
+//
+// Exponents from 3 to 32 are supported, which covers every target a valid chain can have. The
+// headers are checked against their own `bits`, the retargeting rules are not checked, so the
+// caller has to pin the difficulty, e.g. by comparing `bits` to a checkpoint.
+
+pub const HEADER_BYTES: usize = 80;
+
+const PREVIOUS_HASH_OFFSET: usize = 4;
+const BITS_OFFSET: usize = 72;
+
+const MIN_EXPONENT: usize = 3;
+const MAX_EXPONENT: usize = 32;
+
+// the byte at `offset` of bits in the order of `sha256` as a number
+fn byte_at<E: Engine>(bits: &[Boolean], offset: usize) -> LinearCombination<E> {
+    let mut lc = LinearCombination::zero();
+    let mut coeff = E::Fr::one();
+    for bit in bits[offset * 8..(offset + 1) * 8].iter().rev() {
+        lc.add_assign_boolean_with_coeff(bit, coeff);
+        coeff.double();
+    }
+
+    lc
+}
+
+// sum 256^i * bytes[i]
+fn pack_bytes<E: Engine, CS: ConstraintSystem<E>>(cs: &mut CS, bytes: &[Num<E>]) -> Result<Num<E>, SynthesisError> {
+    assert!(bytes.len() * 8 <= E::Fr::CAPACITY as usize);
+
+    let mut lc = LinearCombination::zero();
+    let mut coeff = E::Fr::one();
+    let shift = u64_to_fe::<E::Fr>(256);
+    for byte in bytes.iter() {
+        lc.add_assign_number_with_coeff(byte, coeff);
+        coeff.mul_assign(&shift);
+    }
+
+    lc.into_num(cs)
+}
+
+/// The block hash SHA-256(SHA-256(header)), 256 bits in the order of `sha256`.
+pub fn block_hash<E, CS>(
+    cs: &mut CS,
+    header: &[Boolean]
+) -> Result<Vec<Boolean>, SynthesisError>
+    where E: Engine,
+          CS: ConstraintSystem<E>
+{
+    assert_eq!(header.len(), HEADER_BYTES * 8);
+
+    let inner = sha256(cs, header)?;
+
+    sha256(cs, &inner)
+}
+
+/// The target encoded by the `bits` field of `header` as 32 little endian bytes. Enforces that
+/// the exponent is supported and the sign bit of the mantissa is not set.
+pub fn header_target<E, CS>(
+    cs: &mut CS,
+    header: &[Boolean]
+) -> Result<Vec<Num<E>>, SynthesisError>
+    where E: Engine,
+          CS: ConstraintSystem<E>
+{
+    assert_eq!(header.len(), HEADER_BYTES * 8);
+
+    // the most significant bit of the mantissa is its sign
+    Boolean::enforce_equal(cs, &header[(BITS_OFFSET + 2) * 8], &Boolean::constant(false))?;
+    let mut mantissa = Vec::with_capacity(3);
+    for i in 0..3 {
+        mantissa.push(byte_at::<E>(header, BITS_OFFSET + i).into_num(cs)?);
+    }
+    let exponent = byte_at::<E>(header, BITS_OFFSET + 3).into_num(cs)?;
+
+    // flags[e - MIN_EXPONENT] is set iff the exponent is e, exactly one of them
+    let mut flags = Vec::with_capacity(MAX_EXPONENT - MIN_EXPONENT + 1);
+    let mut flags_sum = LinearCombination::zero();
+    for e in MIN_EXPONENT..=MAX_EXPONENT {
+        let flag = Num::equals(cs, &exponent, &Num::Constant(u64_to_fe(e as u64)))?;
+        flags_sum.add_assign_boolean_with_coeff(&flag, E::Fr::one());
+        flags.push(flag);
+    }
+    let mut minus_one = E::Fr::one();
+    minus_one.negate();
+    flags_sum.add_assign_constant(minus_one);
+    flags_sum.enforce_zero(cs)?;
+
+    // byte j of the target is mantissa[i] if the exponent is j + 3 - i
+    let mut target = Vec::with_capacity(32);
+    for j in 0..32 {
+        let mut byte = LinearCombination::zero();
+        for (i, m) in mantissa.iter().enumerate() {
+            let e = j + MIN_EXPONENT - i;
+            if e >= MIN_EXPONENT && e <= MAX_EXPONENT {
+                let masked = Num::mask(cs, m, &flags[e - MIN_EXPONENT])?;
+                byte.add_assign_number_with_coeff(&masked, E::Fr::one());
+            }
+        }
+        target.push(byte.into_num(cs)?);
+    }
+
+    Ok(target)
+}
+
+// a <= b for a, b < 2^128: b - a + 2^128 has the bit 128 set iff b >= a
+fn is_less_or_equal<E, CS>(
+    cs: &mut CS,
+    a: &Num<E>,
+    b: &Num<E>
+) -> Result<Boolean, SynthesisError>
+    where E: Engine,
+          CS: ConstraintSystem<E>
+{
+    let mut shift = u64_to_fe::<E::Fr>(1 << 32);
+    shift.square();
+    shift.square();
+
+    let mut minus_one = E::Fr::one();
+    minus_one.negate();
+
+    let mut lc = LinearCombination::zero();
+    lc.add_assign_number_with_coeff(b, E::Fr::one());
+    lc.add_assign_number_with_coeff(a, minus_one);
+    lc.add_assign_constant(shift);
+    let bits = lc.into_num(cs)?.into_bits_le(cs, Some(129))?;
+
+    Ok(bits[128].clone())
+}
+
+/// Returns whether `hash`, in the order of `sha256` and read as a little endian integer, is at
+/// most `target`, given by 32 little endian bytes as by `header_target`.
+pub fn is_hash_below_target<E, CS>(
+    cs: &mut CS,
+    hash: &[Boolean],
+    target: &[Num<E>]
+) -> Result<Boolean, SynthesisError>
+    where E: Engine,
+          CS: ConstraintSystem<E>
+{
+    assert_eq!(hash.len(), 256);
+    assert_eq!(target.len(), 32);
+
+    let hash_bytes: Vec<Num<E>> = (0..32).map(|j| byte_at::<E>(hash, j).into_num(cs)).collect::<Result<_, _>>()?;
+
+    // compare the upper and the lower 128 bits
+    let hash_low = pack_bytes(cs, &hash_bytes[..16])?;
+    let hash_high = pack_bytes(cs, &hash_bytes[16..])?;
+    let target_low = pack_bytes(cs, &target[..16])?;
+    let target_high = pack_bytes(cs, &target[16..])?;
+
+    let high_is_greater_or_equal = is_less_or_equal(cs, &target_high, &hash_high)?;
+    let high_is_less = high_is_greater_or_equal.not();
+    let high_is_equal = Num::equals(cs, &hash_high, &target_high)?;
+    let low_is_less_or_equal = is_less_or_equal(cs, &hash_low, &target_low)?;
+    let equal_and_low = Boolean::and(cs, &high_is_equal, &low_is_less_or_equal)?;
+
+    Boolean::or(cs, &high_is_less, &equal_and_low)
+}
+
+/// Verifies the proof of work of `header` and returns its hash.
+pub fn verify_header<E, CS>(
+    cs: &mut CS,
+    header: &[Boolean]
+) -> Result<Vec<Boolean>, SynthesisError>
+    where E: Engine,
+          CS: ConstraintSystem<E>
+{
+    let hash = block_hash(cs, header)?;
+    let target = header_target(cs, header)?;
+    let is_valid = is_hash_below_target(cs, &hash, &target)?;
+    Boolean::enforce_equal(cs, &is_valid, &Boolean::constant(true))?;
+
+    Ok(hash)
+}
+
+/// Verifies a chain of headers: the proof of work of every header and that every header
+/// references the hash of the one before. Returns the hashes, the previous hash of the first
+/// header is left to the caller.
+pub fn verify_header_chain<E, CS>(
+    cs: &mut CS,
+    headers: &[Vec<Boolean>]
+) -> Result<Vec<Vec<Boolean>>, SynthesisError>
+    where E: Engine,
+          CS: ConstraintSystem<E>
+{
+    let mut hashes: Vec<Vec<Boolean>> = Vec::with_capacity(headers.len());
+    for header in headers.iter() {
+        if let Some(previous) = hashes.last() {
+            let reference = &header[PREVIOUS_HASH_OFFSET * 8..(PREVIOUS_HASH_OFFSET + 32) * 8];
+            for (a, b) in reference.iter().zip(previous.iter()) {
+                Boolean::enforce_equal(cs, a, b)?;
+            }
+        }
+        hashes.push(verify_header(cs, header)?);
+    }
+
+    Ok(hashes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use crate::bellman::plonk::better_better_cs::cs::*;
+    use sha2::{Digest, Sha256};
+
+    type Assembly = TrivialAssembly<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>;
+
+    const REGTEST_BITS: u32 = 0x207fffff;
+    const MAINNET_BITS: u32 = 0x1d00ffff;
+
+    fn sha256d(data: &[u8]) -> Vec<u8> {
+        Sha256::digest(&Sha256::digest(data)).to_vec()
+    }
+
+    // little endian bytes
+    fn target(bits: u32) -> Vec<u8> {
+        let exponent = (bits >> 24) as usize;
+        let mut target = vec![0u8; 32];
+        for i in 0..3 {
+            target[exponent - 3 + i] = (bits >> (8 * i)) as u8;
+        }
+
+        target
+    }
+
+    fn meets_target(hash: &[u8], target: &[u8]) -> bool {
+        hash.iter().rev().cmp(target.iter().rev()) != std::cmp::Ordering::Greater
+    }
+
+    fn header(rng: &mut XorShiftRng, previous: &[u8], bits: u32, nonce: u32) -> Vec<u8> {
+        let mut header = vec![];
+        header.extend_from_slice(&2u32.to_le_bytes());
+        header.extend_from_slice(previous);
+        header.extend((0..32).map(|_| rng.gen::<u8>()));
+        header.extend_from_slice(&1_600_000_000u32.to_le_bytes());
+        header.extend_from_slice(&bits.to_le_bytes());
+        header.extend_from_slice(&nonce.to_le_bytes());
+
+        header
+    }
+
+    // a header whose hash meets its target iff `valid`
+    fn mine(rng: &mut XorShiftRng, previous: &[u8], bits: u32, valid: bool) -> Vec<u8> {
+        let mut nonce = 0;
+        loop {
+            let header = header(rng, previous, bits, nonce);
+            if meets_target(&sha256d(&header), &target(bits)) == valid {
+                return header;
+            }
+            nonce += 1;
+        }
+    }
+
+    fn alloc_bytes(cs: &mut Assembly, bytes: &[u8]) -> Vec<Boolean> {
+        bytes.iter()
+             .flat_map(|byte| (0..8).rev().map(move |i| (byte >> i) & 1 == 1))
+             .map(|b| Boolean::alloc(cs, Some(b)).unwrap())
+             .collect()
+    }
+
+    #[test]
+    fn test_header_target() {
+        for &bits in [REGTEST_BITS, MAINNET_BITS, 0x1b0404cb, 0x170e92aa].iter() {
+            let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+            let mut cs = Assembly::new();
+            let header = alloc_bytes(&mut cs, &header(&mut rng, &[0u8; 32], bits, 0));
+
+            let target_bytes = header_target(&mut cs, &header).unwrap();
+            assert!(cs.is_satisfied());
+            let expected: Vec<Fr> = target(bits).into_iter().map(|b| u64_to_fe(b as u64)).collect();
+            assert_eq!(target_bytes.iter().map(|b| b.get_value().unwrap()).collect::<Vec<_>>(), expected);
+        }
+
+        // negative mantissa
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let mut cs = Assembly::new();
+        let header = alloc_bytes(&mut cs, &header(&mut rng, &[0u8; 32], 0x1d80ffff, 0));
+        header_target(&mut cs, &header).unwrap();
+        assert!(!cs.is_satisfied());
+    }
+
+    #[test]
+    fn test_header_chain() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        let mut headers = vec![mine(&mut rng, &[0u8; 32], REGTEST_BITS, true)];
+        for _ in 0..2 {
+            let previous = sha256d(headers.last().unwrap());
+            headers.push(mine(&mut rng, &previous, REGTEST_BITS, true));
+        }
+
+        let mut cs = Assembly::new();
+        let circuit_headers: Vec<_> = headers.iter().map(|h| alloc_bytes(&mut cs, h)).collect();
+        let hashes = verify_header_chain(&mut cs, &circuit_headers).unwrap();
+        assert!(cs.is_satisfied());
+        let hash_bytes: Vec<u8> = hashes[2].chunks(8)
+            .map(|byte| byte.iter().fold(0u8, |acc, b| (acc << 1) | b.get_value().unwrap() as u8))
+            .collect();
+        assert_eq!(hash_bytes, sha256d(&headers[2]));
+
+        // a broken link
+        let mut unlinked = headers.clone();
+        unlinked[1] = mine(&mut rng, &[1u8; 32], REGTEST_BITS, true);
+        let mut cs = Assembly::new();
+        let circuit_headers: Vec<_> = unlinked.iter().map(|h| alloc_bytes(&mut cs, h)).collect();
+        verify_header_chain(&mut cs, &circuit_headers).unwrap();
+        assert!(!cs.is_satisfied());
+
+        // not enough work
+        for &bits in [REGTEST_BITS, MAINNET_BITS].iter() {
+            let mut cs = Assembly::new();
+            let header = alloc_bytes(&mut cs, &mine(&mut rng, &[0u8; 32], bits, false));
+            verify_header(&mut cs, &header).unwrap();
+            assert!(!cs.is_satisfied());
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use crate::bellman::pairing::{
+    Engine,
+};
+
+use crate::bellman::{
+    SynthesisError,
+};
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    ConstraintSystem,
+};
+
+use super::boolean::{
+    Boolean
+};
+
+use super::multieq::MultiEq;
+use super::uint64::UInt64;
+
+// See `blake2s` for the RFC 7693 pseudocode, BLAKE2b only differs in
+// the word size, rotation constants, IV and number of rounds.
+
+const R1: usize = 32;
+const R2: usize = 24;
+const R3: usize = 16;
+const R4: usize = 63;
+
+/*
+    static const uint64_t blake2b_IV[8] =
+    {
+        0x6a09e667f3bcc908ULL, 0xbb67ae8584caa73bULL,
+        0x3c6ef372fe94f82bULL, 0xa54ff53a5f1d36f1ULL,
+        0x510e527fade682d1ULL, 0x9b05688c2b3e6c1fULL,
+        0x1f83d9abfb41bd6bULL, 0x5be0cd19137e2179ULL
+    };
+*/
+
+const IV: [u64; 8] = [
+    0x6a09e667f3bcc908, 0xbb67ae8584caa73b,
+    0x3c6ef372fe94f82b, 0xa54ff53a5f1d36f1,
+    0x510e527fade682d1, 0x9b05688c2b3e6c1f,
+    0x1f83d9abfb41bd6b, 0x5be0cd19137e2179
+];
+
+const SIGMA: [[usize; 16]; 10] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+    [11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+    [7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+    [9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+    [2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+    [12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+    [13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+    [6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+    [10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0]
+];
+
+const NUM_ROUNDS: usize = 12;
+
+fn mixing_g<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    v: &mut [UInt64],
+    a: usize,
+    b: usize,
+    c: usize,
+    d: usize,
+    x: &UInt64,
+    y: &UInt64
+) -> Result<(), SynthesisError>
+{
+    v[a] = UInt64::addmany(cs, &[v[a].clone(), v[b].clone(), x.clone()])?;
+    v[d] = v[d].xor(cs, &v[a])?.rotr(R1);
+    v[c] = UInt64::addmany(cs, &[v[c].clone(), v[d].clone()])?;
+    v[b] = v[b].xor(cs, &v[c])?.rotr(R2);
+    v[a] = UInt64::addmany(cs, &[v[a].clone(), v[b].clone(), y.clone()])?;
+    v[d] = v[d].xor(cs, &v[a])?.rotr(R3);
+    v[c] = UInt64::addmany(cs, &[v[c].clone(), v[d].clone()])?;
+    v[b] = v[b].xor(cs, &v[c])?.rotr(R4);
+
+    Ok(())
+}
+
+fn blake2b_compression<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    h: &mut [UInt64],
+    m: &[UInt64],
+    t: u128,
+    f: bool
+) -> Result<(), SynthesisError>
+{
+    assert_eq!(h.len(), 8);
+    assert_eq!(m.len(), 16);
+
+    let mut v = Vec::with_capacity(16);
+    v.extend_from_slice(h);
+    v.extend(IV.iter().map(|iv| UInt64::constant(*iv)));
+
+    assert_eq!(v.len(), 16);
+
+    v[12] = v[12].xor(cs, &UInt64::constant(t as u64))?;
+    v[13] = v[13].xor(cs, &UInt64::constant((t >> 64) as u64))?;
+
+    if f {
+        v[14] = v[14].xor(cs, &UInt64::constant(u64::max_value()))?;
+    }
+
+    {
+        let mut cs = MultiEq::new(&mut *cs);
+
+        for i in 0..NUM_ROUNDS {
+            let cs = cs.as_cs();
+
+            let s = SIGMA[i % 10];
+
+            mixing_g(cs, &mut v, 0, 4,  8, 12, &m[s[ 0]], &m[s[ 1]])?;
+            mixing_g(cs, &mut v, 1, 5,  9, 13, &m[s[ 2]], &m[s[ 3]])?;
+            mixing_g(cs, &mut v, 2, 6, 10, 14, &m[s[ 4]], &m[s[ 5]])?;
+            mixing_g(cs, &mut v, 3, 7, 11, 15, &m[s[ 6]], &m[s[ 7]])?;
+
+            mixing_g(cs, &mut v, 0, 5, 10, 15, &m[s[ 8]], &m[s[ 9]])?;
+            mixing_g(cs, &mut v, 1, 6, 11, 12, &m[s[10]], &m[s[11]])?;
+            mixing_g(cs, &mut v, 2, 7,  8, 13, &m[s[12]], &m[s[13]])?;
+            mixing_g(cs, &mut v, 3, 4,  9, 14, &m[s[14]], &m[s[15]])?;
+        }
+    }
+
+    for i in 0..8 {
+        h[i] = h[i].xor(cs, &v[i])?;
+        h[i] = h[i].xor(cs, &v[i + 8])?;
+    }
+
+    Ok(())
+}
+
+/// Unkeyed BLAKE2b with a digest of `output_len` bytes and a 16 byte personalization.
+/// Input and output bits are in little-endian order within every byte.
+pub fn blake2b_with_params<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    input: &[Boolean],
+    output_len: usize,
+    personalization: &[u8]
+) -> Result<Vec<Boolean>, SynthesisError>
+{
+    use byteorder::{ByteOrder, LittleEndian};
+
+    assert!(output_len >= 1 && output_len <= 64);
+    assert_eq!(personalization.len(), 16);
+    assert!(input.len() % 8 == 0);
+
+    let mut h = Vec::with_capacity(8);
+    h.push(UInt64::constant(IV[0] ^ 0x01010000 ^ (output_len as u64)));
+    h.push(UInt64::constant(IV[1]));
+    h.push(UInt64::constant(IV[2]));
+    h.push(UInt64::constant(IV[3]));
+    h.push(UInt64::constant(IV[4]));
+    h.push(UInt64::constant(IV[5]));
+
+    // Personalization is stored here
+    h.push(UInt64::constant(IV[6] ^ LittleEndian::read_u64(&personalization[0..8])));
+    h.push(UInt64::constant(IV[7] ^ LittleEndian::read_u64(&personalization[8..16])));
+
+    let mut blocks: Vec<Vec<UInt64>> = vec![];
+
+    for block in input.chunks(1024) {
+        let mut this_block = Vec::with_capacity(16);
+        for word in block.chunks(64) {
+            let mut tmp = word.to_vec();
+            while tmp.len() < 64 {
+                tmp.push(Boolean::constant(false));
+            }
+            this_block.push(UInt64::from_bits(&tmp));
+        }
+        while this_block.len() < 16 {
+            this_block.push(UInt64::constant(0));
+        }
+        blocks.push(this_block);
+    }
+
+    if blocks.len() == 0 {
+        blocks.push((0..16).map(|_| UInt64::constant(0)).collect());
+    }
+
+    for (i, block) in blocks[0..blocks.len() - 1].iter().enumerate() {
+        blake2b_compression(cs, &mut h, block, ((i as u128) + 1) * 128, false)?;
+    }
+
+    {
+        blake2b_compression(cs, &mut h, &blocks[blocks.len() - 1], (input.len() / 8) as u128, true)?;
+    }
+
+    let mut result: Vec<Boolean> = h.iter().flat_map(|b| b.into_bits()).collect();
+    result.truncate(output_len * 8);
+
+    Ok(result)
+}
+
+/// BLAKE2b-512 with a 16 byte personalization.
+pub fn blake2b<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    input: &[Boolean],
+    personalization: &[u8]
+) -> Result<Vec<Boolean>, SynthesisError>
+{
+    blake2b_with_params(cs, input, 64, personalization)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{XorShiftRng, SeedableRng, Rng};
+
+    use bellman::pairing::bn256::Bn256;
+
+    use blake2_rfc::blake2b::Blake2b;
+
+    use super::super::boolean::AllocatedBit;
+    use crate::bellman::plonk::better_better_cs::cs::*;
+
+    const PERSONALIZATION: &[u8; 16] = b"0123456789abcdef";
+
+    fn check_against_native(input_len: usize, output_len: usize, rng: &mut XorShiftRng) {
+        let mut h = Blake2b::with_params(output_len, &[], &[], PERSONALIZATION);
+
+        let data: Vec<u8> = (0..input_len).map(|_| rng.gen()).collect();
+
+        h.update(&data);
+
+        let hash_result = h.finalize();
+
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+
+        let mut input_bits = vec![];
+
+        for input_byte in data.into_iter() {
+            for bit_i in 0..8 {
+                input_bits.push(AllocatedBit::alloc(&mut cs, Some((input_byte >> bit_i) & 1u8 == 1u8)).unwrap().into());
+            }
+        }
+
+        let r = blake2b_with_params(&mut cs, &input_bits, output_len, PERSONALIZATION).unwrap();
+
+        assert!(cs.is_satisfied());
+        assert_eq!(r.len(), output_len * 8);
+
+        let expected = hash_result.as_ref().iter()
+                                           .flat_map(|&byte| (0..8).map(move |i| (byte >> i) & 1u8 == 1u8));
+
+        for (b, e) in r.iter().zip(expected) {
+            assert_eq!(b.get_value().unwrap(), e);
+        }
+    }
+
+    #[test]
+    fn test_blake2b_constant_constraints() {
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+        let mut rng = XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let input_bits: Vec<_> = (0..1024).map(|_| Boolean::constant(rng.gen())).collect();
+        blake2b(&mut cs, &input_bits, PERSONALIZATION).unwrap();
+        assert_eq!(cs.n(), 0);
+    }
+
+    #[test]
+    fn test_blake2b() {
+        let mut rng = XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        for input_len in vec![0, 1, 63, 127, 128, 129, 200, 256] {
+            check_against_native(input_len, 64, &mut rng);
+        }
+    }
+
+    #[test]
+    fn test_blake2b_short_output() {
+        let mut rng = XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        check_against_native(64, 32, &mut rng);
+        check_against_native(150, 20, &mut rng);
+    }
+}
@@ -287,59 +287,151 @@ fn blake2s_compression<E: Engine, CS: ConstraintSystem<E>>(
         END FUNCTION.
 */
 
-pub fn blake2s<E: Engine, CS: ConstraintSystem<E>>(
-    cs: &mut CS,
-    input: &[Boolean],
-    personalization: &[u8]
-) -> Result<Vec<Boolean>, SynthesisError>
-{
-    use byteorder::{ByteOrder, LittleEndian};
+fn block_into_words(block: &[Boolean]) -> Vec<UInt32> {
+    let mut this_block = Vec::with_capacity(16);
+    for word in block.chunks(32) {
+        let mut tmp = word.to_vec();
+        while tmp.len() < 32 {
+            tmp.push(Boolean::constant(false));
+        }
+        this_block.push(UInt32::from_bits(&tmp));
+    }
+    while this_block.len() < 16 {
+        this_block.push(UInt32::constant(0));
+    }
 
-    assert_eq!(personalization.len(), 8);
-    assert!(input.len() % 8 == 0);
+    this_block
+}
 
-    let mut h = Vec::with_capacity(8);
-    h.push(UInt32::constant(0x6A09E667 ^ 0x01010000 ^ 32));
-    h.push(UInt32::constant(0xBB67AE85));
-    h.push(UInt32::constant(0x3C6EF372));
-    h.push(UInt32::constant(0xA54FF53A));
-    h.push(UInt32::constant(0x510E527F));
-    h.push(UInt32::constant(0x9B05688C));
-
-    // Personalization is stored here
-    h.push(UInt32::constant(0x1F83D9AB ^ LittleEndian::read_u32(&personalization[0..4])));
-    h.push(UInt32::constant(0x5BE0CD19 ^ LittleEndian::read_u32(&personalization[4..8])));
-
-    let mut blocks: Vec<Vec<UInt32>> = vec![];
-
-    for block in input.chunks(512) {
-        let mut this_block = Vec::with_capacity(16);
-        for word in block.chunks(32) {
-            let mut tmp = word.to_vec();
-            while tmp.len() < 32 {
-                tmp.push(Boolean::constant(false));
-            }
-            this_block.push(UInt32::from_bits(&tmp));
+/// Incremental BLAKE2s. The last block has to be compressed with the finalization flag,
+/// so a block is only processed once there is more input after it.
+#[derive(Clone)]
+pub struct Blake2sHasher {
+    h: Vec<UInt32>,
+    buffer: Vec<Boolean>,
+    // number of bytes compressed so far
+    counter: u64,
+    output_len: usize,
+}
+
+impl Blake2sHasher {
+    /// Unkeyed BLAKE2s-256 with an 8 byte personalization.
+    pub fn new(personalization: &[u8]) -> Self {
+        Self::new_with_params(&[], 32, &[0u8; 8], personalization)
+    }
+
+    /// BLAKE2s with a full parameter block: an optional `key` of at most 32 bytes (its length is public),
+    /// a digest of `output_len` bytes, an 8 byte `salt` and an 8 byte `personalization`.
+    pub fn new_with_params(
+        key: &[Boolean],
+        output_len: usize,
+        salt: &[u8],
+        personalization: &[u8]
+    ) -> Self {
+        use byteorder::{ByteOrder, LittleEndian};
+
+        assert!(output_len >= 1 && output_len <= 32);
+        assert_eq!(salt.len(), 8);
+        assert_eq!(personalization.len(), 8);
+        assert!(key.len() % 8 == 0);
+        assert!(key.len() <= 256);
+
+        let key_len = key.len() / 8;
+
+        let mut h = Vec::with_capacity(8);
+        h.push(UInt32::constant(0x6A09E667 ^ 0x01010000 ^ ((key_len as u32) << 8) ^ (output_len as u32)));
+        h.push(UInt32::constant(0xBB67AE85));
+        h.push(UInt32::constant(0x3C6EF372));
+        h.push(UInt32::constant(0xA54FF53A));
+
+        // Salt is stored here
+        h.push(UInt32::constant(0x510E527F ^ LittleEndian::read_u32(&salt[0..4])));
+        h.push(UInt32::constant(0x9B05688C ^ LittleEndian::read_u32(&salt[4..8])));
+
+        // Personalization is stored here
+        h.push(UInt32::constant(0x1F83D9AB ^ LittleEndian::read_u32(&personalization[0..4])));
+        h.push(UInt32::constant(0x5BE0CD19 ^ LittleEndian::read_u32(&personalization[4..8])));
+
+        // The key padded with zeros to a full block goes first
+        let mut buffer = key.to_vec();
+        if key_len > 0 {
+            buffer.resize(512, Boolean::constant(false));
         }
-        while this_block.len() < 16 {
-            this_block.push(UInt32::constant(0));
+
+        Self {
+            h,
+            buffer,
+            counter: 0,
+            output_len,
         }
-        blocks.push(this_block);
     }
 
-    if blocks.len() == 0 {
-        blocks.push((0..16).map(|_| UInt32::constant(0)).collect());
-    }
+    pub fn update<E: Engine, CS: ConstraintSystem<E>>(
+        &mut self,
+        cs: &mut CS,
+        input: &[Boolean]
+    ) -> Result<(), SynthesisError>
+    {
+        self.buffer.extend_from_slice(input);
+
+        if self.buffer.len() > 512 {
+            // keep at least one bit, so the last block stays in the buffer
+            let num_full_bits = (self.buffer.len() - 1) / 512 * 512;
+            let rest = self.buffer.split_off(num_full_bits);
+            for block in self.buffer.chunks(512) {
+                self.counter += 64;
+                blake2s_compression(cs, &mut self.h, &block_into_words(block), self.counter, false)?;
+            }
+            self.buffer = rest;
+        }
 
-    for (i, block) in blocks[0..blocks.len() - 1].iter().enumerate() {
-        blake2s_compression(cs, &mut h, block, ((i as u64) + 1) * 64, false)?;
+        Ok(())
     }
 
+    /// Returns the digest, bits are in little-endian order within every byte.
+    pub fn finalize<E: Engine, CS: ConstraintSystem<E>>(
+        mut self,
+        cs: &mut CS
+    ) -> Result<Vec<Boolean>, SynthesisError>
     {
-        blake2s_compression(cs, &mut h, &blocks[blocks.len() - 1], (input.len() / 8) as u64, true)?;
+        assert!(self.buffer.len() % 8 == 0);
+
+        let t = self.counter + (self.buffer.len() / 8) as u64;
+        blake2s_compression(cs, &mut self.h, &block_into_words(&self.buffer), t, true)?;
+
+        let mut result: Vec<Boolean> = self.h.iter().flat_map(|b| b.into_bits()).collect();
+        result.truncate(self.output_len * 8);
+
+        Ok(result)
     }
+}
 
-    Ok(h.iter().flat_map(|b| b.into_bits()).collect())
+/// BLAKE2s with a full parameter block, see `Blake2sHasher::new_with_params`.
+/// Input, key and output bits are in little-endian order within every byte.
+pub fn blake2s_with_params<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    input: &[Boolean],
+    key: &[Boolean],
+    output_len: usize,
+    salt: &[u8],
+    personalization: &[u8]
+) -> Result<Vec<Boolean>, SynthesisError>
+{
+    assert!(input.len() % 8 == 0);
+
+    let mut hasher = Blake2sHasher::new_with_params(key, output_len, salt, personalization);
+    hasher.update(cs, input)?;
+
+    hasher.finalize(cs)
+}
+
+pub fn blake2s<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    input: &[Boolean],
+    personalization: &[u8]
+) -> Result<Vec<Boolean>, SynthesisError>
+{
+    blake2s_with_params(cs, input, &[], 32, &[0u8; 8], personalization)
 }
 
 #[cfg(test)]
@@ -459,4 +551,82 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn test_blake2s_with_params() {
+        let mut rng = XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        let salt = b"saltsalt";
+        for &(input_len, key_len, output_len) in [(0, 0, 16), (0, 32, 32), (3, 1, 32), (64, 16, 20), (100, 32, 1)].iter() {
+            let data: Vec<u8> = (0..input_len).map(|_| rng.gen()).collect();
+            let key: Vec<u8> = (0..key_len).map(|_| rng.gen()).collect();
+
+            let mut h = Blake2s::with_params(output_len, &key, salt, b"12345678");
+            h.update(&data);
+            let hash_result = h.finalize();
+
+            let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+
+            let mut alloc_bytes = |bytes: &[u8]| -> Vec<Boolean> {
+                bytes.iter()
+                     .flat_map(|&byte| (0..8).map(move |i| (byte >> i) & 1u8 == 1u8))
+                     .map(|b| AllocatedBit::alloc(&mut cs, Some(b)).unwrap().into())
+                     .collect()
+            };
+            let input_bits = alloc_bytes(&data);
+            let key_bits = alloc_bytes(&key);
+
+            let r = blake2s_with_params(&mut cs, &input_bits, &key_bits, output_len, salt, b"12345678").unwrap();
+
+            assert!(cs.is_satisfied());
+            assert_eq!(r.len(), output_len * 8);
+
+            let s = hash_result.as_ref().iter()
+                                        .flat_map(|&byte| (0..8).map(move |i| (byte >> i) & 1u8 == 1u8));
+            for (b, e) in r.iter().zip(s) {
+                assert_eq!(b.get_value().unwrap(), e);
+            }
+        }
+    }
+
+    #[test]
+    fn test_blake2s_streaming() {
+        let mut rng = XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        for input_len in vec![0, 64, 128, 150] {
+            let data: Vec<u8> = (0..input_len).map(|_| rng.gen()).collect();
+
+            let mut h = Blake2s::with_params(32, &[], &[], b"12345678");
+            h.update(&data);
+            let hash_result = h.finalize();
+
+            let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+            let input_bits: Vec<Boolean> = data.iter()
+                .flat_map(|&byte| (0..8).map(move |i| (byte >> i) & 1u8 == 1u8))
+                .map(|b| AllocatedBit::alloc(&mut cs, Some(b)).unwrap().into())
+                .collect();
+
+            // feed the input in uneven pieces, not aligned to bytes or blocks
+            let mut hasher = Blake2sHasher::new(b"12345678");
+            let mut start = 0;
+            for &len in [3, 509, 1, 200].iter().cycle() {
+                if start == input_bits.len() {
+                    break;
+                }
+                let end = ::std::cmp::min(start + len, input_bits.len());
+                hasher.update(&mut cs, &input_bits[start..end]).unwrap();
+                start = end;
+            }
+            let r = hasher.finalize(&mut cs).unwrap();
+
+            assert!(cs.is_satisfied());
+
+            let s = hash_result.as_ref().iter()
+                                        .flat_map(|&byte| (0..8).map(move |i| (byte >> i) & 1u8 == 1u8));
+            assert_eq!(r.len(), 256);
+            for (b, e) in r.iter().zip(s) {
+                assert_eq!(b.get_value().unwrap(), e);
+            }
+        }
+    }
 }
@@ -514,6 +514,22 @@ pub fn u64_into_boolean_vec_le<E: Engine, CS: ConstraintSystem<E>>(
     Ok(bits)
 }
 
+/// Computes the Hamming weight of the slice: the number of bits that are `true`.
+/// The count is a single linear combination with unit coefficients, so it only
+/// costs the gates needed to collapse that combination into a variable.
+pub fn popcount<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    bits: &[Boolean]
+) -> Result<AllocatedNum<E>, SynthesisError>
+{
+    let mut lc = LinearCombination::<E>::zero();
+    for bit in bits.iter() {
+        lc.add_assign_boolean_with_coeff(bit, E::Fr::one());
+    }
+
+    lc.into_allocated_num(cs)
+}
+
 // changes an order of the bits to transform bits in LSB first order into
 // LE bytes. Takes 8 bit chunks and reverses them
 pub fn le_bits_into_le_bytes(bits: Vec<Boolean>) -> Vec<Boolean> {
@@ -814,6 +830,87 @@ impl Boolean {
         }
     }
 
+    /// Perform AND over an arbitrary number of boolean operands.
+    /// Operands are reduced pairwise level by level (balanced tree), so the depth is
+    /// ceil(log2(k)) and at most k - 1 gates are used. Constant operands cost nothing,
+    /// and an empty slice evaluates to `true`.
+    pub fn kary_and<E, CS>(
+        cs: &mut CS,
+        bits: &[Self]
+    ) -> Result<Self, SynthesisError>
+        where E: Engine,
+              CS: ConstraintSystem<E>
+    {
+        Self::balanced_tree_reduce(cs, bits, true, |cs, a, b| Self::and(cs, a, b))
+    }
+
+    /// Perform OR over an arbitrary number of boolean operands.
+    /// Same balanced tree as in `kary_and`: at most k - 1 gates, depth ceil(log2(k)).
+    /// An empty slice evaluates to `false`.
+    pub fn kary_or<E, CS>(
+        cs: &mut CS,
+        bits: &[Self]
+    ) -> Result<Self, SynthesisError>
+        where E: Engine,
+              CS: ConstraintSystem<E>
+    {
+        Self::balanced_tree_reduce(cs, bits, false, |cs, a, b| Self::or(cs, a, b))
+    }
+
+    /// Returns true if at least two of the three operands are true.
+    /// Majority coincides with `sha256_maj`, so it costs at most 2 gates
+    /// (and nothing if two operands are constant).
+    pub fn majority<'a, E, CS>(
+        cs: &mut CS,
+        a: &'a Self,
+        b: &'a Self,
+        c: &'a Self,
+    ) -> Result<Self, SynthesisError>
+        where E: Engine,
+              CS: ConstraintSystem<E>
+    {
+        Self::sha256_maj(cs, a, b, c)
+    }
+
+    fn balanced_tree_reduce<E, CS, F>(
+        cs: &mut CS,
+        bits: &[Self],
+        neutral: bool,
+        op: F
+    ) -> Result<Self, SynthesisError>
+        where E: Engine,
+              CS: ConstraintSystem<E>,
+              F: Fn(&mut CS, &Self, &Self) -> Result<Self, SynthesisError>
+    {
+        // neutral constants are dropped upfront, an absorbing constant short-circuits
+        let mut layer = Vec::with_capacity(bits.len());
+        for b in bits.iter() {
+            match b {
+                Boolean::Constant(c) if *c == neutral => {},
+                Boolean::Constant(_) => return Ok(b.clone()),
+                _ => layer.push(b.clone())
+            }
+        }
+
+        if layer.is_empty() {
+            return Ok(Boolean::constant(neutral));
+        }
+
+        while layer.len() > 1 {
+            let mut next_layer = Vec::with_capacity((layer.len() + 1) / 2);
+            for pair in layer.chunks(2) {
+                if pair.len() == 2 {
+                    next_layer.push(op(cs, &pair[0], &pair[1])?);
+                } else {
+                    next_layer.push(pair[0].clone());
+                }
+            }
+            layer = next_layer;
+        }
+
+        Ok(layer.pop().unwrap())
+    }
+
     pub fn conditionally_select<E: Engine, CS: ConstraintSystem<E>>(
         cs: &mut CS,
         flag: &Self,
@@ -2207,4 +2304,100 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn test_boolean_kary_and_or() {
+        for num_operands in 0..9 {
+            for mask in 0u32..(1 << num_operands) {
+                let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+
+                let values: Vec<bool> = (0..num_operands).map(|i| (mask >> i) & 1 == 1).collect();
+                let bits: Vec<Boolean> = values.iter().enumerate().map(|(i, v)| {
+                    // mix in negated views to exercise all the pairwise variants
+                    if i % 3 == 2 {
+                        Boolean::alloc(&mut cs, Some(!*v)).unwrap().not()
+                    } else {
+                        Boolean::alloc(&mut cs, Some(*v)).unwrap()
+                    }
+                }).collect();
+
+                let gates_before = cs.n();
+                let and = Boolean::kary_and(&mut cs, &bits).unwrap();
+                let or = Boolean::kary_or(&mut cs, &bits).unwrap();
+
+                assert!(cs.is_satisfied());
+                assert_eq!(and.get_value().unwrap(), values.iter().all(|v| *v));
+                assert_eq!(or.get_value().unwrap(), values.iter().any(|v| *v));
+                assert!(cs.n() - gates_before <= 2 * (num_operands as usize).saturating_sub(1));
+            }
+        }
+    }
+
+    #[test]
+    fn test_boolean_kary_and_with_constants() {
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+        let a = Boolean::alloc(&mut cs, Some(true)).unwrap();
+        let b = Boolean::alloc(&mut cs, Some(true)).unwrap();
+
+        let gates_before = cs.n();
+        let res = Boolean::kary_and(&mut cs, &[a, Boolean::constant(true), b]).unwrap();
+        assert_eq!(res.get_value().unwrap(), true);
+        assert_eq!(cs.n() - gates_before, 1);
+
+        let res = Boolean::kary_and(&mut cs, &[a, Boolean::constant(false), b]).unwrap();
+        assert!(res.is_constant());
+        assert_eq!(res.get_value().unwrap(), false);
+
+        let res = Boolean::kary_or(&mut cs, &[a, Boolean::constant(true), b]).unwrap();
+        assert!(res.is_constant());
+        assert_eq!(res.get_value().unwrap(), true);
+
+        assert!(cs.is_satisfied());
+    }
+
+    #[test]
+    fn test_boolean_majority() {
+        for mask in 0u32..8 {
+            let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+            let values: Vec<bool> = (0..3).map(|i| (mask >> i) & 1 == 1).collect();
+            let a = Boolean::alloc(&mut cs, Some(values[0])).unwrap();
+            let b = Boolean::alloc(&mut cs, Some(values[1])).unwrap();
+            let c = Boolean::alloc(&mut cs, Some(values[2])).unwrap();
+
+            let maj = Boolean::majority(&mut cs, &a, &b, &c).unwrap();
+            let expected = values.iter().filter(|v| **v).count() >= 2;
+
+            assert!(cs.is_satisfied());
+            assert_eq!(maj.get_value().unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_popcount() {
+        use crate::plonk::circuit::utils::u64_to_fe;
+
+        for mask in 0u32..(1 << 7) {
+            let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+            let mut bits = vec![];
+            for i in 0..7 {
+                let value = (mask >> i) & 1 == 1;
+                let bit = match i {
+                    0 => Boolean::constant(value),
+                    1 => Boolean::alloc(&mut cs, Some(!value)).unwrap().not(),
+                    _ => Boolean::alloc(&mut cs, Some(value)).unwrap()
+                };
+                bits.push(bit);
+            }
+
+            let weight = popcount(&mut cs, &bits).unwrap();
+            assert!(cs.is_satisfied());
+            assert_eq!(weight.get_value().unwrap(), u64_to_fe::<Fr>(mask.count_ones() as u64));
+
+            // 4-of-7 threshold
+            let threshold = AllocatedNum::alloc_cnst(&mut cs, u64_to_fe::<Fr>(4)).unwrap();
+            let reached = AllocatedNum::equals(&mut cs, &weight, &threshold).unwrap();
+            assert_eq!(reached.get_value().unwrap(), mask.count_ones() == 4);
+            assert!(cs.is_satisfied());
+        }
+    }
 }
@@ -7,7 +7,6 @@ use super::linear_combination::*;
 use super::boolean::Boolean;
 use super::utils::*;
 use crate::plonk::circuit::Assignment;
-use crate::plonk::circuit::bigint_new::constraint_bit_length;
 
 use crate::bellman::plonk::better_better_cs::cs::{
     Variable, 
@@ -66,7 +65,7 @@ impl<E: Engine> Byte<E> {
             }
         )?;
         let num = Num::Variable(var);
-        constraint_bit_length(cs, &var, 8)?;
+        num.enforce_bit_length(cs, 8)?;
 
         Ok(
             Self {
@@ -87,11 +86,8 @@ impl<E: Engine> Byte<E> {
     }
 
     pub fn from_num<CS: ConstraintSystem<E>>(cs: &mut CS, value: Num<E>) -> Result<Self, SynthesisError> {
-        if value.is_constant() == false {
-            let var = value.get_variable();
-            constraint_bit_length(cs, &var, 8)?;
-        }
-       
+        value.enforce_bit_length(cs, 8)?;
+
         Ok(
             Self {
                 inner: value
@@ -0,0 +1,330 @@
+use crate::bellman::pairing::ff::{Field, PrimeField};
+
+use crate::bellman::{
+    SynthesisError,
+};
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    ConstraintSystem,
+};
+
+use super::allocated_num::Num;
+use super::boolean::Boolean;
+use super::edwards::{CircuitTwistedEdwardsCurveImplementor, CircuitTwistedEdwardsPoint};
+use super::linear_combination::LinearCombination;
+use super::pedersen_hash::pedersen_hash_point;
+use super::redjubjub::{point_into_bits_le, verify_signature, CircuitRedJubjubSignature};
+use super::utils::{fe_to_lsb_first_bits, u64_to_fe};
+
+use crate::generic_twisted_edwards::{TwistedEdwardsCurveParams, TwistedEdwardsPoint};
+use crate::jubjub::{edwards, FixedGenerators, JubjubEngine, JubjubParams, PrimeOrder, Unknown};
+use crate::pedersen_hash::{pedersen_hash, Personalization};
+use crate::redjubjub::{read_scalar, Signature};
+
+// Anonymous credentials. The issuer commits to the attributes of a holder (birth year, country
+// code, expiry date, ...)
+//
+//      C = PedersenHash(CREDENTIAL_PERSONALIZATION, a_0 || ... || a_{n-1}) + r . G_NoteCommitmentRandomness
+//
+// with every attribute in 64 little endian bits, and signs the encoding of C as in `Point::write`
+// with RedJubjub. The holder proves to know a credential signed by the issuer and discloses every
+// attribute according to a policy: hidden, revealed, or only shown to be above or below a
+// constant, as age > 18. Neither C nor the signature leave the circuit, so presentations of the
+// same credential can only be linked by what they reveal. The Pedersen generators of alt Baby
+// Jubjub take at most 14 attributes.
+
+pub const ATTRIBUTE_BITS: usize = 64;
+
+pub const CREDENTIAL_PERSONALIZATION: Personalization = Personalization::Custom(0x63726564);
+
+/// How an attribute is disclosed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Disclosure {
+    Hidden,
+    /// The attribute is returned by `enforce_disclosure` for the caller to make it public.
+    Revealed,
+    /// The attribute is greater than the constant.
+    GreaterThan(u64),
+    /// The attribute is less than the constant.
+    LessThan(u64),
+}
+
+/// A credential as issued, `message` is what the issuer signs.
+#[derive(Clone, Debug)]
+pub struct Credential<E: JubjubEngine> {
+    pub attributes: Vec<u64>,
+    pub r: E::Fs,
+}
+
+impl<E: JubjubEngine> Credential<E> {
+    pub fn commitment(&self, params: &E::Params) -> edwards::Point<E, PrimeOrder> {
+        let bits = self.attributes.iter().flat_map(|a| (0..ATTRIBUTE_BITS).map(move |i| (a >> i) & 1 == 1));
+        let hash = pedersen_hash::<E, _>(CREDENTIAL_PERSONALIZATION, bits, params);
+
+        params.generator(FixedGenerators::NoteCommitmentRandomness)
+              .mul(self.r, params)
+              .add(&hash, params)
+    }
+
+    pub fn message(&self, params: &E::Params) -> Vec<u8> {
+        let mut bytes = vec![];
+        self.commitment(params).write(&mut bytes).unwrap();
+
+        bytes
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CircuitCredential<E: JubjubEngine> {
+    /// Little endian bits of every attribute.
+    pub attributes: Vec<Vec<Boolean>>,
+    /// Little endian bits of r.
+    pub r: Vec<Boolean>,
+    pub signature: CircuitRedJubjubSignature<E>,
+}
+
+impl<E: JubjubEngine> CircuitCredential<E> {
+    /// Allocates a credential of `num_attributes` attributes with its signature, the witness is
+    /// unsatisfiable if the number of attributes differs or the signature doesn't parse.
+    pub fn alloc<CS, C>(
+        cs: &mut CS,
+        curve: &CircuitTwistedEdwardsCurveImplementor<E, C>,
+        num_attributes: usize,
+        witness: Option<(&Credential<E>, &Signature)>,
+        params: &E::Params
+    ) -> Result<Self, SynthesisError>
+        where CS: ConstraintSystem<E>,
+              C: TwistedEdwardsCurveParams<E>
+    {
+        if let Some((credential, _)) = witness {
+            if credential.attributes.len() != num_attributes {
+                return Err(SynthesisError::Unsatisfiable);
+            }
+        }
+
+        let mut attributes = Vec::with_capacity(num_attributes);
+        for j in 0..num_attributes {
+            let value = witness.map(|(c, _)| c.attributes[j]);
+            let bits: Result<Vec<_>, _> = (0..ATTRIBUTE_BITS).map(|i| Boolean::alloc(cs, value.map(|v| (v >> i) & 1 == 1))).collect();
+            attributes.push(bits?);
+        }
+        let r = alloc_scalar::<E, _>(cs, witness.map(|(c, _)| c.r))?;
+
+        let signature = witness.map(|(_, s)| split_signature::<E>(s, params).ok_or(SynthesisError::Unsatisfiable)).transpose()?;
+        let signature = CircuitRedJubjubSignature {
+            r: curve.alloc_point_enforce_on_curve(cs, signature.as_ref().map(|s| s.0))?,
+            s: alloc_scalar::<E, _>(cs, signature.map(|s| s.1))?,
+        };
+
+        Ok(CircuitCredential { attributes, r, signature })
+    }
+}
+
+fn alloc_scalar<E: JubjubEngine, CS: ConstraintSystem<E>>(cs: &mut CS, value: Option<E::Fs>) -> Result<Vec<Boolean>, SynthesisError> {
+    let bits = value.map(|v| fe_to_lsb_first_bits(&v));
+
+    (0..E::Fs::NUM_BITS as usize).map(|i| Boolean::alloc(cs, bits.as_ref().map(|b| b[i]))).collect()
+}
+
+fn split_signature<E: JubjubEngine>(signature: &Signature, params: &E::Params) -> Option<(TwistedEdwardsPoint<E>, E::Fs)> {
+    let mut bytes = vec![];
+    signature.write(&mut bytes).unwrap();
+    let (x, y) = edwards::Point::<E, Unknown>::read(&bytes[..32], params).ok()?.into_xy();
+    let s = read_scalar::<E, _>(&bytes[32..]).ok()?;
+
+    Some((TwistedEdwardsPoint::from_xy(x, y), s))
+}
+
+/// The commitment C to the attributes, given by little endian bits, with randomness `r`.
+pub fn credential_commitment<E, CS, C>(
+    cs: &mut CS,
+    curve: &CircuitTwistedEdwardsCurveImplementor<E, C>,
+    attributes: &[Vec<Boolean>],
+    r: &[Boolean],
+    params: &E::Params
+) -> Result<CircuitTwistedEdwardsPoint<E>, SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>,
+          C: TwistedEdwardsCurveParams<E>
+{
+    let mut contents = vec![];
+    for attribute in attributes.iter() {
+        assert_eq!(attribute.len(), ATTRIBUTE_BITS);
+        contents.extend_from_slice(attribute);
+    }
+
+    let hash = pedersen_hash_point(cs, curve, CREDENTIAL_PERSONALIZATION, &contents, params)?;
+    let (x, y) = params.generator(FixedGenerators::NoteCommitmentRandomness).into_xy();
+    let blinding = curve.mul(cs, &CircuitTwistedEdwardsPoint::constant(x, y), r)?;
+
+    curve.add(cs, &hash, &blinding)
+}
+
+/// Returns whether `credential` is signed by `issuer` with the generator `p_g`.
+pub fn verify_credential<E, CS, C>(
+    cs: &mut CS,
+    curve: &CircuitTwistedEdwardsCurveImplementor<E, C>,
+    issuer: &CircuitTwistedEdwardsPoint<E>,
+    credential: &CircuitCredential<E>,
+    p_g: FixedGenerators,
+    params: &E::Params
+) -> Result<Boolean, SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>,
+          C: TwistedEdwardsCurveParams<E>
+{
+    let commitment = credential_commitment(cs, curve, &credential.attributes, &credential.r, params)?;
+    let message = point_into_bits_le(cs, &commitment)?;
+
+    verify_signature(cs, curve, issuer, &credential.signature, &message, p_g, params)
+}
+
+// sum 2^i * bits[i]
+fn pack_bits<E: JubjubEngine>(bits: &[Boolean]) -> LinearCombination<E> {
+    let mut lc = LinearCombination::zero();
+    let mut coeff = E::Fr::one();
+    for bit in bits.iter() {
+        lc.add_assign_boolean_with_coeff(bit, coeff);
+        coeff.double();
+    }
+
+    lc
+}
+
+/// Enforces `policy` on the attributes and returns the revealed ones in order. For a, c below
+/// 2^64, a > c iff a - c - 1 fits into 64 bits, and a < c iff c - a - 1 does.
+pub fn enforce_disclosure<E, CS>(
+    cs: &mut CS,
+    attributes: &[Vec<Boolean>],
+    policy: &[Disclosure]
+) -> Result<Vec<Num<E>>, SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>
+{
+    assert_eq!(attributes.len(), policy.len());
+
+    let mut minus_one = E::Fr::one();
+    minus_one.negate();
+
+    let mut revealed = vec![];
+    for (attribute, disclosure) in attributes.iter().zip(policy.iter()) {
+        assert_eq!(attribute.len(), ATTRIBUTE_BITS);
+
+        let mut difference = LinearCombination::zero();
+        match *disclosure {
+            Disclosure::Hidden => continue,
+            Disclosure::Revealed => {
+                revealed.push(pack_bits::<E>(attribute).into_num(cs)?);
+                continue;
+            },
+            Disclosure::GreaterThan(c) => {
+                difference.add_assign(&pack_bits::<E>(attribute));
+                let mut constant = u64_to_fe::<E::Fr>(c);
+                constant.negate();
+                difference.add_assign_constant(constant);
+            },
+            Disclosure::LessThan(c) => {
+                difference.add_assign_scaled(&pack_bits::<E>(attribute), minus_one);
+                difference.add_assign_constant(u64_to_fe::<E::Fr>(c));
+            },
+        }
+        difference.add_assign_constant(minus_one);
+        difference.into_num(cs)?.enforce_bit_length(cs, ATTRIBUTE_BITS)?;
+    }
+
+    Ok(revealed)
+}
+
+/// Enforces that `credential` is signed by `issuer` and satisfies `policy`, returns the revealed
+/// attributes. The caller makes them public together with the issuer, unless the issuer is a
+/// constant of the circuit.
+pub fn present_credential<E, CS, C>(
+    cs: &mut CS,
+    curve: &CircuitTwistedEdwardsCurveImplementor<E, C>,
+    issuer: &CircuitTwistedEdwardsPoint<E>,
+    credential: &CircuitCredential<E>,
+    policy: &[Disclosure],
+    p_g: FixedGenerators,
+    params: &E::Params
+) -> Result<Vec<Num<E>>, SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>,
+          C: TwistedEdwardsCurveParams<E>
+{
+    let is_valid = verify_credential(cs, curve, issuer, credential, p_g, params)?;
+    Boolean::enforce_equal(cs, &is_valid, &Boolean::constant(true))?;
+
+    enforce_disclosure(cs, &credential.attributes, policy)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use crate::bellman::plonk::better_better_cs::cs::*;
+    use crate::alt_babyjubjub::AltJubjubBn256;
+    use crate::plonk::circuit::edwards::bn256::CircuitAltBabyJubjubBn256;
+    use crate::redjubjub::{PrivateKey, PublicKey};
+
+    // birth year, country code, expiry date
+    const POLICY: [Disclosure; 3] = [Disclosure::LessThan(2008), Disclosure::Revealed, Disclosure::GreaterThan(20261017)];
+
+    #[test]
+    fn test_credential_presentation() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = AltJubjubBn256::new();
+        let curve = CircuitAltBabyJubjubBn256::get_implementor();
+        let p_g = FixedGenerators::SpendingKeyGenerator;
+
+        let sk = PrivateKey::<Bn256>(rng.gen());
+        let issuer = PublicKey::from_private(&sk, p_g, &params);
+        let other_issuer = PublicKey::from_private(&PrivateKey::<Bn256>(rng.gen()), p_g, &params);
+
+        let credential = Credential::<Bn256> { attributes: vec![1990, 276, 20300101], r: rng.gen() };
+        let signature = sk.sign(&credential.message(&params), &mut rng, p_g, &params);
+        assert!(issuer.verify(&credential.message(&params), &signature, p_g, &params));
+
+        let underage = Credential::<Bn256> { attributes: vec![2010, 276, 20300101], r: rng.gen() };
+        let underage_signature = sk.sign(&underage.message(&params), &mut rng, p_g, &params);
+        let tampered = Credential::<Bn256> { attributes: vec![1990, 250, 20300101], r: credential.r };
+
+        let cases = vec![
+            (&issuer, &credential, &signature, true),
+            (&other_issuer, &credential, &signature, false),
+            (&issuer, &underage, &underage_signature, false),
+            (&issuer, &tampered, &signature, false),
+        ];
+        for (pk, c, s, expected) in cases.into_iter() {
+            let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+            let (x, y) = pk.0.into_xy();
+            let issuer_point = curve.alloc_point_enforce_on_curve(&mut cs, Some(TwistedEdwardsPoint::from_xy(x, y))).unwrap();
+            let circuit_credential = CircuitCredential::alloc(&mut cs, &curve, 3, Some((c, s)), &params).unwrap();
+
+            let revealed = present_credential(&mut cs, &curve, &issuer_point, &circuit_credential, &POLICY, p_g, &params).unwrap();
+            assert_eq!(cs.is_satisfied(), expected);
+            assert_eq!(revealed.len(), 1);
+            assert_eq!(revealed[0].get_value().unwrap(), u64_to_fe::<Fr>(c.attributes[1]));
+        }
+    }
+
+    #[test]
+    fn test_disclosure_bounds() {
+        for &(value, disclosure, expected) in [
+            (19u64, Disclosure::GreaterThan(18), true),
+            (18, Disclosure::GreaterThan(18), false),
+            (0, Disclosure::GreaterThan(u64::max_value()), false),
+            (u64::max_value(), Disclosure::GreaterThan(0), true),
+            (17, Disclosure::LessThan(18), true),
+            (18, Disclosure::LessThan(18), false),
+            (u64::max_value(), Disclosure::LessThan(0), false),
+        ].iter() {
+            let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+            let bits: Vec<Boolean> = (0..ATTRIBUTE_BITS).map(|i| Boolean::alloc(&mut cs, Some((value >> i) & 1 == 1)).unwrap()).collect();
+
+            let revealed = enforce_disclosure::<Bn256, _>(&mut cs, &[bits], &[disclosure]).unwrap();
+            assert!(revealed.is_empty());
+            assert_eq!(cs.is_satisfied(), expected);
+        }
+    }
+}
@@ -0,0 +1,487 @@
+use crate::bellman::pairing::{
+    Engine,
+};
+
+use crate::bellman::pairing::ff::{
+    Field,
+    PrimeField,
+};
+
+use crate::bellman::SynthesisError;
+use crate::bellman::worker::Worker;
+use crate::bellman::plonk::better_better_cs::cs::*;
+use crate::bellman::plonk::polynomials::*;
+use crate::bellman::plonk::fft::cooley_tukey_ntt::*;
+
+use crate::plonk::circuit::Assignment;
+
+use super::allocated_num::{
+    AllocatedNum
+};
+
+// Addition on a twisted Edwards curve with a = -1 in two rows
+//
+// row i:     [x1, y1, x2, y2]  - this gate
+// row i + 1: [x3, y3, t,  dt]  - main gate, dt = d * t
+//
+// t = x1 * y1 * x2 * y2
+// x3 * (1 + dt) = x1 * y2 + y1 * x2
+// y3 * (1 - dt) = x1 * x2 + y1 * y2
+//
+// The curve parameter d only appears in the main gate, so the same gate serves any curve
+
+#[derive(Clone, Debug, Hash, Default)]
+pub struct TwistedEdwardsAddCustomGate;
+
+impl TwistedEdwardsAddCustomGate {
+    fn evaluate<F: PrimeField>(this_row: [F; 4], next_row: [F; 4]) -> [F; 3] {
+        let [x1, y1, x2, y2] = this_row;
+        let [x3, y3, t, dt] = next_row;
+
+        // t - x1 * y1 * x2 * y2 = 0
+        let mut first = x1;
+        first.mul_assign(&y1);
+        first.mul_assign(&x2);
+        first.mul_assign(&y2);
+        first.negate();
+        first.add_assign(&t);
+
+        // x3 * (1 + dt) - x1 * y2 - y1 * x2 = 0
+        let mut second = x3;
+        second.mul_assign(&dt);
+        second.add_assign(&x3);
+        let mut tmp = x1;
+        tmp.mul_assign(&y2);
+        second.sub_assign(&tmp);
+        let mut tmp = y1;
+        tmp.mul_assign(&x2);
+        second.sub_assign(&tmp);
+
+        // y3 * (1 - dt) - x1 * x2 - y1 * y2 = 0
+        let mut third = y3;
+        third.mul_assign(&dt);
+        third.negate();
+        third.add_assign(&y3);
+        let mut tmp = x1;
+        tmp.mul_assign(&x2);
+        third.sub_assign(&tmp);
+        let mut tmp = y1;
+        tmp.mul_assign(&y2);
+        third.sub_assign(&tmp);
+
+        [first, second, third]
+    }
+
+    fn combine<F: PrimeField>(terms: [F; 3], challenges: &[F]) -> F {
+        let mut result = F::zero();
+        for (term, challenge) in terms.iter().zip(challenges.iter()) {
+            let mut tmp = *term;
+            tmp.mul_assign(challenge);
+            result.add_assign(&tmp);
+        }
+
+        result
+    }
+}
+
+impl<E: Engine> GateInternal<E> for TwistedEdwardsAddCustomGate {
+    fn name(&self) -> &'static str {
+        "Twisted Edwards addition custom gate, a = -1"
+    }
+
+    fn degree(&self) -> usize {
+        4
+    }
+
+    fn can_include_public_inputs(&self) -> bool {
+        false
+    }
+
+    fn all_queried_polynomials(&self) -> &'static [PolynomialInConstraint] {
+        const POLYS: [PolynomialInConstraint; 8] = [
+            PolynomialInConstraint::from_id(PolyIdentifier::VariablesPolynomial(0)),
+            PolynomialInConstraint::from_id(PolyIdentifier::VariablesPolynomial(1)),
+            PolynomialInConstraint::from_id(PolyIdentifier::VariablesPolynomial(2)),
+            PolynomialInConstraint::from_id(PolyIdentifier::VariablesPolynomial(3)),
+            PolynomialInConstraint::from_id_and_dilation(PolyIdentifier::VariablesPolynomial(0), 1),
+            PolynomialInConstraint::from_id_and_dilation(PolyIdentifier::VariablesPolynomial(1), 1),
+            PolynomialInConstraint::from_id_and_dilation(PolyIdentifier::VariablesPolynomial(2), 1),
+            PolynomialInConstraint::from_id_and_dilation(PolyIdentifier::VariablesPolynomial(3), 1),
+        ];
+
+        &POLYS
+    }
+
+    fn setup_polynomials(&self) -> &'static [PolyIdentifier] {
+        &[]
+    }
+
+    fn variable_polynomials(&self) -> &'static [PolyIdentifier] {
+        const POLYS: [PolyIdentifier; 4] = [
+            PolyIdentifier::VariablesPolynomial(0),
+            PolyIdentifier::VariablesPolynomial(1),
+            PolyIdentifier::VariablesPolynomial(2),
+            PolyIdentifier::VariablesPolynomial(3),
+        ];
+
+        &POLYS
+    }
+
+    fn benefits_from_linearization(&self) -> bool {
+        false
+    }
+
+    fn linearizes_over(&self) -> &'static [PolynomialInConstraint] {
+        &[]
+    }
+
+    fn needs_opened_for_linearization(&self) -> &'static [PolynomialInConstraint] {
+        &[]
+    }
+
+    fn num_quotient_terms(&self) -> usize {
+        3
+    }
+
+    fn verify_on_row(&self, row: usize, poly_storage: &AssembledPolynomialStorage<E>, last_row: bool) -> E::Fr {
+        assert!(last_row == false, "can not be applied at the last row");
+
+        let mut this_row = [E::Fr::zero(); 4];
+        let mut next_row = [E::Fr::zero(); 4];
+        for i in 0..4 {
+            this_row[i] = poly_storage.get_poly_at_step(PolyIdentifier::VariablesPolynomial(i), row);
+            next_row[i] = poly_storage.get_poly_at_step(PolyIdentifier::VariablesPolynomial(i), row + 1);
+        }
+
+        for term in Self::evaluate(this_row, next_row).iter() {
+            if term.is_zero() == false {
+                return *term;
+            }
+        }
+
+        E::Fr::zero()
+    }
+
+    fn put_public_inputs_into_selector_id(&self) -> Option<usize> {
+        None
+    }
+
+    fn contribute_into_quotient(
+        &self,
+        domain_size: usize,
+        poly_storage: &mut AssembledPolynomialStorage<E>,
+        monomials_storage: & AssembledPolynomialStorageForMonomialForms<E>,
+        challenges: &[E::Fr],
+        omegas_bitreversed: &BitReversedOmegas<E::Fr>,
+        _omegas_inv_bitreversed: &OmegasInvBitreversed<E::Fr>,
+        worker: &Worker
+    ) -> Result<Polynomial<E::Fr, Values>, SynthesisError> {
+        assert!(domain_size.is_power_of_two());
+        assert_eq!(challenges.len(), <Self as GateInternal<E>>::num_quotient_terms(&self));
+
+        let lde_factor = poly_storage.lde_factor;
+        assert!(lde_factor.is_power_of_two());
+
+        assert!(poly_storage.is_bitreversed);
+
+        let coset_factor = E::Fr::multiplicative_generator();
+
+        for &p in <Self as GateInternal<E>>::all_queried_polynomials(&self).into_iter() {
+            ensure_in_map_or_create(&worker,
+                p,
+                domain_size,
+                omegas_bitreversed,
+                lde_factor,
+                coset_factor,
+                monomials_storage,
+                poly_storage
+            )?;
+        }
+
+        let ldes_storage = &*poly_storage;
+
+        let a_ref = get_from_map_unchecked(
+            PolynomialInConstraint::from_id(PolyIdentifier::VariablesPolynomial(0)),
+            ldes_storage
+        );
+
+        let mut tmp = a_ref.clone(); // just allocate, we don't actually use it
+        drop(a_ref);
+
+        let polys = <Self as GateInternal<E>>::all_queried_polynomials(&self);
+        let raw_refs: Vec<&[E::Fr]> = polys.iter()
+            .map(|&p| get_from_map_unchecked(p, ldes_storage).as_ref())
+            .collect();
+
+        tmp.map_indexed(&worker,
+            |i, el| {
+                let this_row = [raw_refs[0][i], raw_refs[1][i], raw_refs[2][i], raw_refs[3][i]];
+                let next_row = [raw_refs[4][i], raw_refs[5][i], raw_refs[6][i], raw_refs[7][i]];
+
+                *el = Self::combine(Self::evaluate(this_row, next_row), challenges);
+            },
+        );
+
+        Ok(tmp)
+    }
+
+    fn contribute_into_linearization(
+        &self,
+        _domain_size: usize,
+        _at: E::Fr,
+        _queried_values: &std::collections::HashMap<PolynomialInConstraint, E::Fr>,
+        _monomials_storage: & AssembledPolynomialStorageForMonomialForms<E>,
+        _challenges: &[E::Fr],
+        _worker: &Worker
+    ) -> Result<Polynomial<E::Fr, Coefficients>, SynthesisError> {
+        unreachable!("this gate does not contribute into linearization");
+    }
+
+    fn contribute_into_verification_equation(
+        &self,
+        _domain_size: usize,
+        _at: E::Fr,
+        queried_values: &std::collections::HashMap<PolynomialInConstraint, E::Fr>,
+        challenges: &[E::Fr],
+    ) -> Result<E::Fr, SynthesisError> {
+        assert_eq!(challenges.len(), <Self as GateInternal<E>>::num_quotient_terms(&self));
+
+        let polys = <Self as GateInternal<E>>::all_queried_polynomials(&self);
+        let mut values = [E::Fr::zero(); 8];
+        for (value, p) in values.iter_mut().zip(polys.iter()) {
+            *value = *queried_values.get(p).ok_or(SynthesisError::AssignmentMissing)?;
+        }
+
+        let this_row = [values[0], values[1], values[2], values[3]];
+        let next_row = [values[4], values[5], values[6], values[7]];
+
+        Ok(Self::combine(Self::evaluate(this_row, next_row), challenges))
+    }
+
+    fn box_clone(&self) -> Box<dyn GateInternal<E>> {
+        Box::from(self.clone())
+    }
+
+    fn contribute_into_linearization_commitment(
+        &self,
+        _domain_size: usize,
+        _at: E::Fr,
+        _queried_values: &std::collections::HashMap<PolynomialInConstraint, E::Fr>,
+        _commitments_storage: &std::collections::HashMap<PolyIdentifier, E::G1Affine>,
+        _challenges: &[E::Fr],
+    ) -> Result<E::G1, SynthesisError> {
+        unreachable!("this gate does not contribute into linearization");
+    }
+}
+
+impl<E: Engine> Gate<E> for TwistedEdwardsAddCustomGate {}
+
+/// Adds (x1, y1) and (x2, y2) on the curve -x^2 + y^2 = 1 + d x^2 y^2, see the layout above.
+/// Uses two trace steps where the generic formulas take a dozen.
+pub fn apply_edwards_add<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    param_d: &E::Fr,
+    x1: &AllocatedNum<E>,
+    y1: &AllocatedNum<E>,
+    x2: &AllocatedNum<E>,
+    y2: &AllocatedNum<E>,
+) -> Result<(AllocatedNum<E>, AllocatedNum<E>), SynthesisError> {
+    assert!(CS::Params::STATE_WIDTH == 4 && CS::Params::CAN_ACCESS_NEXT_TRACE_STEP);
+
+    let values = match (x1.get_value(), y1.get_value(), x2.get_value(), y2.get_value()) {
+        (Some(x1), Some(y1), Some(x2), Some(y2)) => {
+            let mut t = x1;
+            t.mul_assign(&y1);
+            t.mul_assign(&x2);
+            t.mul_assign(&y2);
+
+            let mut dt = t;
+            dt.mul_assign(param_d);
+
+            let mut x3 = x1;
+            x3.mul_assign(&y2);
+            let mut tmp = y1;
+            tmp.mul_assign(&x2);
+            x3.add_assign(&tmp);
+            let mut denominator = E::Fr::one();
+            denominator.add_assign(&dt);
+            x3.mul_assign(&denominator.inverse().ok_or(SynthesisError::DivisionByZero)?);
+
+            let mut y3 = x1;
+            y3.mul_assign(&x2);
+            let mut tmp = y1;
+            tmp.mul_assign(&y2);
+            y3.add_assign(&tmp);
+            let mut denominator = E::Fr::one();
+            denominator.sub_assign(&dt);
+            y3.mul_assign(&denominator.inverse().ok_or(SynthesisError::DivisionByZero)?);
+
+            Some((x3, y3, t, dt))
+        },
+        _ => None,
+    };
+
+    let x3 = AllocatedNum::alloc(cs, || Ok(values.get()?.0))?;
+    let y3 = AllocatedNum::alloc(cs, || Ok(values.get()?.1))?;
+    let t = AllocatedNum::alloc(cs, || Ok(values.get()?.2))?;
+    let dt = AllocatedNum::alloc(cs, || Ok(values.get()?.3))?;
+
+    cs.new_single_gate_for_trace_step(
+        &TwistedEdwardsAddCustomGate::default(),
+        &[],
+        &[x1.get_variable(), y1.get_variable(), x2.get_variable(), y2.get_variable()],
+        &[]
+    )?;
+
+    // d * t - dt = 0
+    let mut minus_one = E::Fr::one();
+    minus_one.negate();
+
+    let mut coeffs = CS::MainGate::empty_coefficients();
+    let range_of_linear_terms = CS::MainGate::range_of_linear_terms();
+    coeffs[range_of_linear_terms.start + 2] = *param_d;
+    coeffs[range_of_linear_terms.start + 3] = minus_one;
+
+    cs.new_single_gate_for_trace_step(
+        &CS::MainGate::default(),
+        &coeffs,
+        &[x3.get_variable(), y3.get_variable(), t.get_variable(), dt.get_variable()],
+        &[]
+    )?;
+
+    Ok((x3, y3))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bellman::pairing::bn256::Bn256;
+    use crate::alt_babyjubjub::AltJubjubBn256;
+    use crate::jubjub::JubjubParams;
+    use crate::jubjub::edwards::Point;
+    use bellman::pairing::bn256::Fr;
+    use bellman::kate_commitment::{Crs, CrsForMonomialForm};
+    use rand::{SeedableRng, XorShiftRng};
+    use crate::plonk::circuit::allocated_num::Num;
+    use crate::plonk::circuit::edwards::bn256::CircuitAltBabyJubjubBn256;
+    use crate::plonk::circuit::edwards::CircuitTwistedEdwardsPoint;
+    use crate::plonk::prover::*;
+
+    type Params = PlonkCsWidth4WithNextStepAndCustomGatesParams;
+
+    // p + q and 2 * p with the gate, all coordinates are public
+    struct EdwardsAddCircuit {
+        p: Option<(Fr, Fr)>,
+        q: Option<(Fr, Fr)>,
+    }
+
+    impl Circuit<Bn256> for EdwardsAddCircuit {
+        type MainGate = Width4MainGateWithDNext;
+
+        fn declare_used_gates() -> Result<Vec<Box<dyn GateInternal<Bn256>>>, SynthesisError> {
+            Ok(
+                vec![
+                    Width4MainGateWithDNext::default().into_internal(),
+                    TwistedEdwardsAddCustomGate::default().into_internal(),
+                ]
+            )
+        }
+
+        fn synthesize<CS: ConstraintSystem<Bn256>>(&self, cs: &mut CS) -> Result<(), SynthesisError> {
+            let curve = CircuitAltBabyJubjubBn256::get_implementor();
+            let mut points = vec![];
+            for point in [self.p, self.q].iter() {
+                let x = AllocatedNum::alloc(cs, || Ok(point.get()?.0))?;
+                let y = AllocatedNum::alloc(cs, || Ok(point.get()?.1))?;
+                points.push(CircuitTwistedEdwardsPoint { x: Num::Variable(x), y: Num::Variable(y) });
+            }
+
+            let sum = curve.add_with_custom_gate(cs, &points[0], &points[1])?;
+            let doubled = curve.double_with_custom_gate(cs, &points[0])?;
+            for coord in [sum.x, sum.y, doubled.x, doubled.y].iter() {
+                coord.get_variable().inputize(cs)?;
+            }
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_edwards_add_gate() {
+        let rng = &mut XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = AltJubjubBn256::new();
+
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepAndCustomGatesParams, Width4MainGateWithDNext>::new();
+
+        for _ in 0..4 {
+            let p = Point::<Bn256, _>::rand(rng, &params);
+            let q = Point::<Bn256, _>::rand(rng, &params);
+            let (p_x, p_y) = p.into_xy();
+            let (q_x, q_y) = q.into_xy();
+
+            let coords: Vec<AllocatedNum<Bn256>> = [p_x, p_y, q_x, q_y].iter()
+                .map(|v| AllocatedNum::alloc(&mut cs, || Ok(*v)).unwrap())
+                .collect();
+
+            let before = cs.n();
+            let (x3, y3) = apply_edwards_add(&mut cs, params.edwards_d(), &coords[0], &coords[1], &coords[2], &coords[3]).unwrap();
+            assert_eq!(cs.n() - before, 2);
+
+            let (expected_x, expected_y) = p.add(&q, &params).into_xy();
+            assert_eq!(x3.get_value().unwrap(), expected_x);
+            assert_eq!(y3.get_value().unwrap(), expected_y);
+        }
+        assert!(cs.is_satisfied());
+    }
+
+    #[test]
+    fn test_edwards_add_gate_is_opt_in() {
+        let rng = &mut XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = AltJubjubBn256::new();
+        let curve = CircuitAltBabyJubjubBn256::get_implementor();
+
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepAndCustomGatesParams, Width4MainGateWithDNext>::new();
+        let mut points = vec![];
+        for _ in 0..2 {
+            let (x, y) = Point::<Bn256, _>::rand(rng, &params).into_xy();
+            let x = AllocatedNum::alloc(&mut cs, || Ok(x)).unwrap();
+            let y = AllocatedNum::alloc(&mut cs, || Ok(y)).unwrap();
+            points.push(CircuitTwistedEdwardsPoint { x: Num::Variable(x), y: Num::Variable(y) });
+        }
+
+        let before = cs.n();
+        let plain = curve.add(&mut cs, &points[0], &points[1]).unwrap();
+        assert!(cs.n() - before > 2);
+
+        let before = cs.n();
+        let custom = curve.add_with_custom_gate(&mut cs, &points[0], &points[1]).unwrap();
+        assert_eq!(cs.n() - before, 2);
+
+        assert_eq!(plain.x.get_value(), custom.x.get_value());
+        assert_eq!(plain.y.get_value(), custom.y.get_value());
+        assert!(cs.is_satisfied());
+    }
+
+    #[test]
+    fn test_edwards_add_gate_prove_and_verify() {
+        let rng = &mut XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = AltJubjubBn256::new();
+
+        let p = Point::<Bn256, _>::rand(rng, &params);
+        let q = Point::<Bn256, _>::rand(rng, &params);
+        let circuit = EdwardsAddCircuit { p: Some(p.into_xy()), q: Some(q.into_xy()) };
+
+        let crs = Crs::<Bn256, CrsForMonomialForm>::crs_42(required_crs_size::<_, Params, _>(&circuit).unwrap(), &Worker::new());
+        let keys = generate_keys::<_, Params, _>(&circuit, &crs).unwrap();
+        let proof = create_proof::<_, Params, _>(&circuit, &keys, &crs).unwrap();
+
+        let (sum_x, sum_y) = p.add(&q, &params).into_xy();
+        let (doubled_x, doubled_y) = p.double(&params).into_xy();
+        assert_eq!(proof.inputs, vec![sum_x, sum_y, doubled_x, doubled_y]);
+        assert!(verify_proof(&keys.vk, &proof).unwrap());
+
+        let mut forged = proof;
+        forged.inputs[0] = sum_y;
+        assert!(!verify_proof(&keys.vk, &forged).unwrap());
+    }
+}
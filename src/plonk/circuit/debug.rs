@@ -0,0 +1,390 @@
+use crate::bellman::pairing::Engine;
+
+use crate::bellman::pairing::ff::Field;
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    ConstraintSystem,
+    Index,
+    MainGate,
+    PlonkConstraintSystemParams,
+    PolyIdentifier,
+    TrivialAssembly,
+    Variable,
+};
+
+use crate::bellman::worker::Worker;
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::io::{self, Write};
+
+// `TrivialAssembly::is_satisfied` stops at the first failing gate and only prints its index.
+// The functions below check every row and keep the wire values, so a broken witness can be
+// debugged from a single run.
+
+/// A row of the trace at which a gate doesn't vanish.
+#[derive(Clone, Debug)]
+pub struct UnsatisfiedGate<E: Engine> {
+    pub row: usize,
+    pub gate: &'static str,
+    /// The value of the gate equation at `row`, zero for satisfied gates.
+    pub value: E::Fr,
+    /// The wire values at `row`.
+    pub wires: Vec<E::Fr>,
+    /// The wire values at `row + 1`, gates like the main gate with D next read them as well.
+    /// Empty for the last row.
+    pub next_wires: Vec<E::Fr>,
+}
+
+impl<E: Engine> fmt::Display for UnsatisfiedGate<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "gate `{}` unsatisfied at row {}, value = {}", self.gate, self.row, self.value)?;
+        for (i, w) in self.wires.iter().enumerate() {
+            writeln!(f, "    wire {} = {}", i, w)?;
+        }
+        for (i, w) in self.next_wires.iter().enumerate() {
+            writeln!(f, "    wire {} (next row) = {}", i, w)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// All gates of `assembly` that are not satisfied by its witness, ordered by row.
+pub fn which_are_unsatisfied<E, P, MG>(
+    assembly: &TrivialAssembly<E, P, MG>
+) -> Vec<UnsatisfiedGate<E>>
+    where E: Engine,
+          P: PlonkConstraintSystemParams<E>,
+          MG: MainGate<E>
+{
+    let n = assembly.n();
+    if n == 0 {
+        return vec![];
+    }
+
+    let worker = Worker::new();
+    let storage = assembly.make_assembled_poly_storage(&worker, false).expect("witness is assembled");
+
+    let wires_at = |row: usize| -> Vec<E::Fr> {
+        (0..P::STATE_WIDTH)
+            .map(|i| *storage.get_poly_at_step(PolyIdentifier::VariablesPolynomial(i), row))
+            .collect()
+    };
+
+    let mut result = vec![];
+    for (gate, density) in assembly.aux_gate_density.0.iter() {
+        for (gate_index, is_applicable) in density.iter().enumerate() {
+            if !is_applicable {
+                continue;
+            }
+
+            let row = assembly.num_input_gates + gate_index;
+            let last = row == n - 1;
+            let value = gate.verify_on_row(row, &storage, last);
+            if value.is_zero() {
+                continue;
+            }
+
+            result.push(UnsatisfiedGate {
+                row,
+                gate: gate.name(),
+                value,
+                wires: wires_at(row),
+                next_wires: if last { vec![] } else { wires_at(row + 1) },
+            });
+        }
+    }
+    result.sort_by_key(|g| g.row);
+
+    result
+}
+
+/// Prints every unsatisfied gate of `assembly` with its wire values, returns `true` if there
+/// were none.
+pub fn print_unsatisfied<E, P, MG>(
+    assembly: &TrivialAssembly<E, P, MG>
+) -> bool
+    where E: Engine,
+          P: PlonkConstraintSystemParams<E>,
+          MG: MainGate<E>
+{
+    let unsatisfied = which_are_unsatisfied(assembly);
+    for gate in unsatisfied.iter() {
+        print!("{}", gate);
+    }
+
+    unsatisfied.is_empty()
+}
+
+/// Variables that take part in too few gates to be pinned down by the circuit.
+#[derive(Clone, Debug, Default)]
+pub struct VariableUsage {
+    /// Allocated variables that don't appear in any row.
+    pub unused: Vec<Variable>,
+    /// Variables that appear in a single row, with that row. Outputs of a gadget that the
+    /// caller is still going to use look like this as well, so these need a closer look rather
+    /// than being bugs for sure.
+    pub used_once: Vec<(Variable, usize)>,
+}
+
+/// Finds the variables of `assembly` that appear in at most one row of the trace. A witness
+/// that is free to take any value is the classic cause of an unsound gadget.
+pub fn find_unconstrained_variables<E, P, MG>(
+    assembly: &TrivialAssembly<E, P, MG>
+) -> VariableUsage
+    where E: Engine,
+          P: PlonkConstraintSystemParams<E>,
+          MG: MainGate<E>
+{
+    let dummy = TrivialAssembly::<E, P, MG>::get_dummy_variable();
+
+    // rows each variable appears in, up to the second one
+    let mut rows: HashMap<Variable, Vec<usize>> = HashMap::new();
+    let storages = [(&assembly.inputs_storage, 0), (&assembly.aux_storage, assembly.num_input_gates)];
+    for &(storage, offset) in storages.iter() {
+        for i in 0..P::STATE_WIDTH {
+            let column = match storage.state_map.get(&PolyIdentifier::VariablesPolynomial(i)) {
+                Some(column) => column,
+                None => continue,
+            };
+            for (row, &var) in column.iter().enumerate() {
+                if var == dummy {
+                    continue;
+                }
+                let entry = rows.entry(var).or_insert(vec![]);
+                if entry.len() < 2 && !entry.contains(&(row + offset)) {
+                    entry.push(row + offset);
+                }
+            }
+        }
+    }
+
+    let all = (1..=assembly.num_inputs).map(|i| Variable::new_unchecked(Index::Input(i)))
+        .chain((1..=assembly.num_aux).map(|i| Variable::new_unchecked(Index::Aux(i))));
+
+    let mut usage = VariableUsage::default();
+    for var in all {
+        match rows.get(&var).map(|r| &r[..]) {
+            None => usage.unused.push(var),
+            Some(&[row]) => usage.used_once.push((var, row)),
+            _ => {},
+        }
+    }
+
+    usage
+}
+
+/// The witness of an assembly at some point, see `snapshot`.
+#[derive(Clone, Debug)]
+pub struct WitnessSnapshot<E: Engine> {
+    inputs: Vec<E::Fr>,
+    aux: Vec<E::Fr>,
+}
+
+/// Records the values of all variables of `assembly`, so a test can mutate the witness with
+/// `set_value` and restore it with `rollback` instead of synthesizing the circuit again.
+pub fn snapshot<E, P, MG>(
+    assembly: &TrivialAssembly<E, P, MG>
+) -> WitnessSnapshot<E>
+    where E: Engine,
+          P: PlonkConstraintSystemParams<E>,
+          MG: MainGate<E>
+{
+    WitnessSnapshot {
+        inputs: assembly.input_assingments.clone(),
+        aux: assembly.aux_assingments.clone(),
+    }
+}
+
+/// Restores the values of the variables that existed when `snapshot` was taken. Variables and
+/// gates added since then are kept.
+pub fn rollback<E, P, MG>(
+    assembly: &mut TrivialAssembly<E, P, MG>,
+    snapshot: &WitnessSnapshot<E>
+)
+    where E: Engine,
+          P: PlonkConstraintSystemParams<E>,
+          MG: MainGate<E>
+{
+    assert!(assembly.input_assingments.len() >= snapshot.inputs.len());
+    assert!(assembly.aux_assingments.len() >= snapshot.aux.len());
+
+    assembly.input_assingments[..snapshot.inputs.len()].copy_from_slice(&snapshot.inputs);
+    assembly.aux_assingments[..snapshot.aux.len()].copy_from_slice(&snapshot.aux);
+}
+
+/// Overwrites the value of `var`, e.g. to check that a gadget rejects a tampered witness.
+pub fn set_value<E, P, MG>(
+    assembly: &mut TrivialAssembly<E, P, MG>,
+    var: Variable,
+    value: E::Fr
+)
+    where E: Engine,
+          P: PlonkConstraintSystemParams<E>,
+          MG: MainGate<E>
+{
+    match var.get_unchecked() {
+        Index::Input(i) => assembly.input_assingments[i - 1] = value,
+        Index::Aux(0) => panic!("the dummy variable is always zero"),
+        Index::Aux(i) => assembly.aux_assingments[i - 1] = value,
+    }
+}
+
+fn dot_name(var: Variable) -> String {
+    match var.get_unchecked() {
+        Index::Input(i) => format!("input_{}", i),
+        Index::Aux(i) => format!("aux_{}", i),
+    }
+}
+
+/// Writes the trace of `assembly` as an undirected Graphviz graph, with a box for every row
+/// labelled by the gates applied in it and an edge to every variable the row touches. PLONK
+/// assemblies don't keep namespaces, so sub-circuits show up as clusters of rows rather than
+/// as a tree.
+pub fn write_dot<E, P, MG, W>(
+    assembly: &TrivialAssembly<E, P, MG>,
+    mut writer: W
+) -> io::Result<()>
+    where E: Engine,
+          P: PlonkConstraintSystemParams<E>,
+          MG: MainGate<E>,
+          W: Write
+{
+    let dummy = TrivialAssembly::<E, P, MG>::get_dummy_variable();
+
+    let mut gate_names: HashMap<usize, Vec<&'static str>> = HashMap::new();
+    for row in 0..assembly.num_input_gates {
+        gate_names.insert(row, vec!["input"]);
+    }
+    for (gate, density) in assembly.aux_gate_density.0.iter() {
+        for (gate_index, is_applicable) in density.iter().enumerate() {
+            if is_applicable {
+                gate_names.entry(assembly.num_input_gates + gate_index).or_insert(vec![]).push(gate.name());
+            }
+        }
+    }
+
+    let mut edges = BTreeSet::new();
+    let mut variables = BTreeSet::new();
+    let storages = [(&assembly.inputs_storage, 0), (&assembly.aux_storage, assembly.num_input_gates)];
+    for &(storage, offset) in storages.iter() {
+        for i in 0..P::STATE_WIDTH {
+            if let Some(column) = storage.state_map.get(&PolyIdentifier::VariablesPolynomial(i)) {
+                for (row, &var) in column.iter().enumerate() {
+                    if var != dummy {
+                        edges.insert((row + offset, dot_name(var)));
+                        variables.insert(dot_name(var));
+                    }
+                }
+            }
+        }
+    }
+
+    writeln!(writer, "graph circuit {{")?;
+    for row in 0..assembly.n() {
+        let mut names = gate_names.get(&row).cloned().unwrap_or(vec![]);
+        names.sort();
+        writeln!(writer, "    row_{} [shape=box, label=\"{}: {}\"];", row, row, names.join(", "))?;
+    }
+    for var in variables.iter() {
+        writeln!(writer, "    {} [shape=ellipse];", var)?;
+    }
+    for (row, var) in edges.iter() {
+        writeln!(writer, "    row_{} -- {};", row, var)?;
+    }
+    writeln!(writer, "}}")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use bellman::pairing::ff::PrimeField;
+    use crate::bellman::plonk::better_better_cs::cs::*;
+    use crate::plonk::circuit::allocated_num::{AllocatedNum, Num};
+
+    #[test]
+    fn test_reports_every_unsatisfied_gate() {
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+
+        let a = Num::alloc(&mut cs, Some(Fr::from_str("3").unwrap())).unwrap();
+        let b = Num::alloc(&mut cs, Some(Fr::from_str("3").unwrap())).unwrap();
+        a.enforce_equal(&mut cs, &b).unwrap();
+        assert!(which_are_unsatisfied(&cs).is_empty());
+
+        let c = Num::alloc(&mut cs, Some(Fr::from_str("4").unwrap())).unwrap();
+        a.enforce_equal(&mut cs, &c).unwrap();
+        let d = Num::alloc(&mut cs, Some(Fr::from_str("5").unwrap())).unwrap();
+        d.enforce_equal(&mut cs, &b).unwrap();
+
+        let unsatisfied = which_are_unsatisfied(&cs);
+        assert_eq!(unsatisfied.len(), 2);
+        assert!(unsatisfied[0].row < unsatisfied[1].row);
+        assert!(unsatisfied[0].wires.contains(&Fr::from_str("4").unwrap()));
+        assert!(unsatisfied[1].wires.contains(&Fr::from_str("5").unwrap()));
+        assert!(!print_unsatisfied(&cs));
+        assert!(!cs.is_satisfied());
+    }
+
+    #[test]
+    fn test_finds_unconstrained_variables() {
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+
+        let a = AllocatedNum::alloc(&mut cs, || Ok(Fr::from_str("3").unwrap())).unwrap();
+        let b = AllocatedNum::alloc(&mut cs, || Ok(Fr::from_str("5").unwrap())).unwrap();
+        let forgotten = AllocatedNum::alloc(&mut cs, || Ok(Fr::from_str("7").unwrap())).unwrap();
+        let c = a.mul(&mut cs, &b).unwrap();
+        let d = c.add(&mut cs, &a).unwrap();
+
+        let usage = find_unconstrained_variables(&cs);
+        assert_eq!(usage.unused, vec![forgotten.get_variable()]);
+        // b only meets a once, d is the output nobody consumed yet
+        let used_once: Vec<Variable> = usage.used_once.iter().map(|&(var, _)| var).collect();
+        assert!(used_once.contains(&b.get_variable()));
+        assert!(used_once.contains(&d.get_variable()));
+        assert!(!used_once.contains(&a.get_variable()));
+        assert!(!used_once.contains(&c.get_variable()));
+    }
+
+    #[test]
+    fn test_snapshot_and_rollback() {
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+
+        let a = AllocatedNum::alloc(&mut cs, || Ok(Fr::from_str("3").unwrap())).unwrap();
+        let b = AllocatedNum::alloc(&mut cs, || Ok(Fr::from_str("5").unwrap())).unwrap();
+        let c = a.mul(&mut cs, &b).unwrap();
+        assert!(cs.is_satisfied());
+
+        let original = snapshot(&cs);
+        for var in [a.get_variable(), b.get_variable(), c.get_variable()].iter() {
+            set_value(&mut cs, *var, Fr::from_str("4").unwrap());
+            assert!(!cs.is_satisfied());
+            assert_eq!(which_are_unsatisfied(&cs).len(), 1);
+
+            rollback(&mut cs, &original);
+            assert!(cs.is_satisfied());
+        }
+        assert_eq!(cs.get_value(c.get_variable()).unwrap(), Fr::from_str("15").unwrap());
+    }
+
+    #[test]
+    fn test_write_dot() {
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+
+        let a = AllocatedNum::alloc_input(&mut cs, || Ok(Fr::from_str("3").unwrap())).unwrap();
+        let b = AllocatedNum::alloc(&mut cs, || Ok(Fr::from_str("5").unwrap())).unwrap();
+        a.mul(&mut cs, &b).unwrap();
+
+        let mut dot = vec![];
+        write_dot(&cs, &mut dot).unwrap();
+        let dot = String::from_utf8(dot).unwrap();
+        assert!(dot.starts_with("graph circuit {"));
+        assert!(dot.contains("row_0 [shape=box, label=\"0: input\"];"));
+        assert!(dot.contains("row_0 -- input_1;"));
+        assert!(dot.contains("row_1 -- input_1;"));
+        assert!(dot.contains("row_1 -- aux_1;"));
+    }
+}
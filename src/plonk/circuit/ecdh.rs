@@ -0,0 +1,154 @@
+use crate::bellman::pairing::ff::PrimeField;
+
+use crate::bellman::{
+    SynthesisError,
+};
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    ConstraintSystem,
+};
+
+use super::boolean::Boolean;
+use super::blake2s::blake2s;
+use super::edwards::{CircuitTwistedEdwardsCurveImplementor, CircuitTwistedEdwardsPoint};
+
+use crate::ecdh::ECDH_KDF_PERSONALIZATION;
+use crate::generic_twisted_edwards::TwistedEdwardsCurveParams;
+use crate::jubjub::JubjubEngine;
+
+// Key agreement matching `crate::ecdh`.
+
+/// Bits of the encoding of `Point::write`: y in 255 little endian bits and the parity of x.
+/// Bytes are laid out one after another with the least significant bit first.
+pub fn point_into_bits<E, CS>(
+    cs: &mut CS,
+    p: &CircuitTwistedEdwardsPoint<E>
+) -> Result<Vec<Boolean>, SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>
+{
+    assert!(E::Fr::NUM_BITS <= 255);
+
+    let mut bits = p.y.into_bits_le_strict(cs)?;
+    bits.resize(255, Boolean::constant(false));
+    let x_bits = p.x.into_bits_le_strict(cs)?;
+    bits.push(x_bits[0].clone());
+
+    Ok(bits)
+}
+
+pub trait CircuitKdf<E: JubjubEngine> {
+    /// Bits of the key bytes with the least significant bit of every byte first, see
+    /// `crate::ecdh::Kdf`.
+    fn derive_key<CS: ConstraintSystem<E>>(
+        &self,
+        cs: &mut CS,
+        shared_secret: &CircuitTwistedEdwardsPoint<E>,
+        epk: &CircuitTwistedEdwardsPoint<E>
+    ) -> Result<Vec<Boolean>, SynthesisError>;
+}
+
+/// Matches `crate::ecdh::Blake2sKdf`.
+pub struct CircuitBlake2sKdf {
+    pub personalization: [u8; 8],
+}
+
+impl Default for CircuitBlake2sKdf {
+    fn default() -> Self {
+        CircuitBlake2sKdf { personalization: *ECDH_KDF_PERSONALIZATION }
+    }
+}
+
+impl<E: JubjubEngine> CircuitKdf<E> for CircuitBlake2sKdf {
+    fn derive_key<CS: ConstraintSystem<E>>(
+        &self,
+        cs: &mut CS,
+        shared_secret: &CircuitTwistedEdwardsPoint<E>,
+        epk: &CircuitTwistedEdwardsPoint<E>
+    ) -> Result<Vec<Boolean>, SynthesisError> {
+        let mut input = point_into_bits(cs, shared_secret)?;
+        input.extend(point_into_bits(cs, epk)?);
+
+        blake2s(cs, &input, &self.personalization)
+    }
+}
+
+/// [8 sk] pk_other, `sk` in little endian bits.
+pub fn ka_agree<E, CS, C>(
+    cs: &mut CS,
+    curve: &CircuitTwistedEdwardsCurveImplementor<E, C>,
+    sk: &[Boolean],
+    pk_other: &CircuitTwistedEdwardsPoint<E>
+) -> Result<CircuitTwistedEdwardsPoint<E>, SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>,
+          C: TwistedEdwardsCurveParams<E>
+{
+    let mut shared = curve.mul(cs, pk_other, sk)?;
+    for _ in 0..curve.implementor.get_params().log_2_cofactor() {
+        shared = curve.double(cs, &shared)?;
+    }
+
+    Ok(shared)
+}
+
+/// Key derived by `kdf` from [8 sk] pk_other and `epk`. The sender passes its ephemeral secret
+/// and the recipient key, the recipient its secret and `epk` as `pk_other`.
+pub fn derive_shared_key<E, CS, C, K>(
+    cs: &mut CS,
+    curve: &CircuitTwistedEdwardsCurveImplementor<E, C>,
+    kdf: &K,
+    sk: &[Boolean],
+    pk_other: &CircuitTwistedEdwardsPoint<E>,
+    epk: &CircuitTwistedEdwardsPoint<E>
+) -> Result<Vec<Boolean>, SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>,
+          C: TwistedEdwardsCurveParams<E>,
+          K: CircuitKdf<E>
+{
+    let shared = ka_agree(cs, curve, sk, pk_other)?;
+
+    kdf.derive_key(cs, &shared, epk)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use bellman::pairing::bn256::Bn256;
+    use crate::bellman::plonk::better_better_cs::cs::*;
+    use crate::alt_babyjubjub::{AltJubjubBn256, fs::Fs, edwards};
+    use crate::ecdh::{Blake2sKdf, sender_key};
+    use crate::jubjub::{FixedGenerators, JubjubParams, Unknown};
+    use crate::generic_twisted_edwards::TwistedEdwardsPoint;
+    use crate::plonk::circuit::edwards::bn256::CircuitAltBabyJubjubBn256;
+    use crate::plonk::circuit::utils::fe_to_lsb_first_bits;
+
+    #[test]
+    fn test_sender_key_in_circuit() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = AltJubjubBn256::new();
+        let curve = CircuitAltBabyJubjubBn256::get_implementor();
+        let p_g = FixedGenerators::SpendingKeyGenerator;
+
+        let sk: Fs = rng.gen();
+        let pk: edwards::Point<Bn256, Unknown> = params.generator(p_g).mul(sk, &params).into();
+        let esk: Fs = rng.gen();
+        let (epk, key) = sender_key(&Blake2sKdf::default(), esk, &pk, p_g, &params);
+
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+        let (pk_x, pk_y) = pk.into_xy();
+        let pk_var = curve.alloc_point_enforce_on_curve(&mut cs, Some(TwistedEdwardsPoint::from_xy(pk_x, pk_y))).unwrap();
+        let (epk_x, epk_y) = epk.into_xy();
+        let epk_var = curve.alloc_point_enforce_on_curve(&mut cs, Some(TwistedEdwardsPoint::from_xy(epk_x, epk_y))).unwrap();
+        let esk_bits: Vec<Boolean> = fe_to_lsb_first_bits(&esk).into_iter().map(|b| Boolean::alloc(&mut cs, Some(b)).unwrap()).collect();
+
+        let key_bits = derive_shared_key(&mut cs, &curve, &CircuitBlake2sKdf::default(), &esk_bits, &pk_var, &epk_var).unwrap();
+        assert!(cs.is_satisfied());
+
+        let expected_bits: Vec<bool> = key.iter().flat_map(|&byte| (0..8).map(move |i| (byte >> i) & 1u8 == 1u8)).collect();
+        let key_bits: Vec<bool> = key_bits.iter().map(|b| b.get_value().unwrap()).collect();
+        assert_eq!(key_bits, expected_bits);
+    }
+}
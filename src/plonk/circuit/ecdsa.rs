@@ -0,0 +1,325 @@
+use crate::bellman::pairing::{
+    Engine,
+    GenericCurveAffine,
+};
+
+use crate::bellman::pairing::ff::{
+    Field,
+    PrimeField,
+};
+
+use crate::bellman::{
+    SynthesisError,
+};
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    ConstraintSystem,
+};
+
+use super::boolean::Boolean;
+use super::linear_combination::LinearCombination;
+use super::keccak::keccak256;
+
+use crate::plonk::circuit::bigint_new::*;
+use crate::plonk::circuit::curve_new::sw_affine::AffinePoint;
+use crate::plonk::circuit::curve_new::sw_projective::ProjectivePoint;
+use crate::plonk::circuit::curve_new::secp256k1::PointAffine;
+use crate::plonk::circuit::curve_new::secp256k1::fr::Fr;
+
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+
+// ECDSA over secp256k1 on top of the nonnative field arithmetic. Coordinates live in the
+// base field Fq and scalars in Fr, both are 256 bits wide and q < 2n, so any canonical
+// coordinate is reduced modulo n with a single conditional subtraction of n.
+
+const SECP256K1_SCALAR_BITS: usize = 256;
+
+#[derive(Clone, Debug)]
+pub struct CircuitEcdsaSignature<'a, E: Engine> {
+    pub r: FieldElement<'a, E, Fr>,
+    pub s: FieldElement<'a, E, Fr>,
+}
+
+fn bits_into_biguint(bits: &[Boolean]) -> Option<BigUint> {
+    let mut result = BigUint::zero();
+    for bit in bits.iter().rev() {
+        result <<= 1;
+        if bit.get_value()? {
+            result += BigUint::one();
+        }
+    }
+
+    Some(result)
+}
+
+// adds the integer given by little endian bits to the lc, negated if `coeff_sign` is false
+fn add_bits_to_lc<E: Engine>(lc: &mut LinearCombination<E>, bits: &[Boolean], coeff_sign: bool) {
+    let mut coeff = E::Fr::one();
+    if !coeff_sign {
+        coeff.negate();
+    }
+    for bit in bits.iter() {
+        lc.add_assign_boolean_with_coeff(bit, coeff);
+        coeff.double();
+    }
+}
+
+/// Reduces a 256 bit integer given by little endian `bits` modulo the group order n.
+/// The result is normalized.
+pub fn reduce_into_scalar<'a, E, CS>(
+    cs: &mut CS,
+    bits: &[Boolean],
+    scalar_params: &'a RnsParameters<E, Fr>
+) -> Result<FieldElement<'a, E, Fr>, SynthesisError>
+    where E: Engine, CS: ConstraintSystem<E>
+{
+    assert_eq!(bits.len(), SECP256K1_SCALAR_BITS);
+    // the halves with the carry must fit the native field
+    assert!(E::Fr::CAPACITY as usize > SECP256K1_SCALAR_BITS / 2 + 2);
+
+    let half = SECP256K1_SCALAR_BITS / 2;
+    let modulus = repr_to_biguint::<Fr>(&Fr::char());
+    let half_mask = (BigUint::one() << half) - BigUint::one();
+    let modulus_lo = biguint_to_fe::<E::Fr>(modulus.clone() & half_mask.clone());
+    let modulus_hi = biguint_to_fe::<E::Fr>(modulus.clone() >> half);
+
+    // a = t + k * n with t < n and boolean k, it has a single solution as a < 2^256 < 2n
+    let value = bits_into_biguint(bits);
+    let k_value = value.as_ref().map(|a| a >= &modulus);
+    let t_value = match (value.as_ref(), k_value) {
+        (Some(a), Some(true)) => Some(a - &modulus),
+        (Some(a), Some(false)) => Some(a.clone()),
+        _ => None,
+    };
+    let carry_value = match (t_value.as_ref(), k_value) {
+        (Some(t), Some(k)) => {
+            let mut lo = t & &half_mask;
+            if k {
+                lo += &modulus & &half_mask;
+            }
+            Some(!(lo >> half).is_zero())
+        },
+        _ => None,
+    };
+
+    let k = Boolean::alloc(cs, k_value)?;
+    let carry = Boolean::alloc(cs, carry_value)?;
+    let mut t = FieldElement::alloc(cs, t_value.map(|t| biguint_to_fe::<Fr>(t)), scalar_params)?;
+    t.normalize(cs)?;
+    let t_bits = t.decompose_into_binary_representation(cs)?;
+    assert_eq!(t_bits.len(), SECP256K1_SCALAR_BITS);
+
+    let mut minus_one = E::Fr::one();
+    minus_one.negate();
+    let mut shift = E::Fr::one();
+    for _ in 0..half {
+        shift.double();
+    }
+
+    // a_lo - t_lo - k * n_lo + carry * 2^128 = 0
+    let mut lc = LinearCombination::zero();
+    add_bits_to_lc(&mut lc, &bits[..half], true);
+    add_bits_to_lc(&mut lc, &t_bits[..half], false);
+    let mut coeff = modulus_lo;
+    coeff.negate();
+    lc.add_assign_boolean_with_coeff(&k, coeff);
+    lc.add_assign_boolean_with_coeff(&carry, shift);
+    lc.enforce_zero(cs)?;
+
+    // a_hi - t_hi - k * n_hi - carry = 0
+    let mut lc = LinearCombination::zero();
+    add_bits_to_lc(&mut lc, &bits[half..], true);
+    add_bits_to_lc(&mut lc, &t_bits[half..], false);
+    let mut coeff = modulus_hi;
+    coeff.negate();
+    lc.add_assign_boolean_with_coeff(&k, coeff);
+    lc.add_assign_boolean_with_coeff(&carry, minus_one);
+    lc.enforce_zero(cs)?;
+
+    Ok(t)
+}
+
+/// Little endian bits of the Keccak-256 hash of `message` read as a big endian integer,
+/// as Ethereum does. `message` is in whole bytes with little endian bits in every byte.
+pub fn keccak256_message_hash<E, CS>(
+    cs: &mut CS,
+    message: &[Boolean]
+) -> Result<Vec<Boolean>, SynthesisError>
+    where E: Engine, CS: ConstraintSystem<E>
+{
+    assert!(message.len() % 8 == 0);
+    let digest = keccak256(cs, message)?;
+
+    Ok(digest.chunks(8).rev().flat_map(|byte| byte.iter().cloned()).collect())
+}
+
+/// Checks the signature (r, s) of the message hash z under `public_key`:
+/// r = (u1 . G + u2 . Q).x mod n with u1 = z / s, u2 = r / s. `message_hash` are the little
+/// endian bits of z, it's reduced modulo n inside. Returns the result of the check together with
+/// the public key being on curve and r, s being non zero, so the caller can enforce it or combine
+/// it with other conditions.
+pub fn verify_prehashed_signature<'a, E, CS>(
+    cs: &mut CS,
+    public_key: &AffinePoint<'a, E, PointAffine>,
+    signature: &CircuitEcdsaSignature<'a, E>,
+    message_hash: &[Boolean],
+    scalar_params: &'a RnsParameters<E, Fr>
+) -> Result<Boolean, SynthesisError>
+    where E: Engine, CS: ConstraintSystem<E>
+{
+    let params = public_key.x.representation_params;
+    let generator = AffinePoint::constant(PointAffine::one(), params);
+
+    let public_key_is_on_curve = public_key.is_on_curve_for_zero_a(cs, PointAffine::b_coeff())?;
+
+    let mut r = signature.r.clone();
+    r.normalize(cs)?;
+    let r_is_zero = r.is_zero(cs)?;
+    let mut s = signature.s.clone();
+    let s_is_zero = s.is_zero(cs)?;
+
+    // with zero s the check fails anyway, replace it with one to keep the inversion satisfiable
+    let safe_s = FieldElement::conditionally_select(cs, &s_is_zero, &FieldElement::one(scalar_params), &s)?;
+    let s_inv = safe_s.inverse(cs)?;
+
+    let z = reduce_into_scalar(cs, message_hash, scalar_params)?;
+    let mut u1 = z.mul(cs, &s_inv)?;
+    let mut u2 = r.mul(cs, &s_inv)?;
+
+    let mut tmp = generator.clone();
+    let u1_g = tmp.mul_by_scalar_for_prime_order_curve(cs, &mut u1)?;
+    let mut tmp = public_key.clone();
+    let u2_q = tmp.mul_by_scalar_for_prime_order_curve(cs, &mut u2)?;
+    let mut point: ProjectivePoint<E, PointAffine> = u1_g.add(cs, &u2_q)?;
+    let (mut point, is_infinity) = point.convert_to_affine_or_default(cs, &generator)?;
+
+    point.x.normalize(cs)?;
+    let x_bits = point.x.decompose_into_binary_representation(cs)?;
+    let mut x_mod_n = reduce_into_scalar(cs, &x_bits, scalar_params)?;
+    let equation_holds = FieldElement::equals(cs, &mut x_mod_n, &mut r)?;
+
+    let mut is_valid = Boolean::and(cs, &public_key_is_on_curve, &equation_holds)?;
+    for flag in vec![r_is_zero, s_is_zero, is_infinity].into_iter() {
+        is_valid = Boolean::and(cs, &is_valid, &flag.not())?;
+    }
+
+    Ok(is_valid)
+}
+
+/// Checks the signature of `message` (whole bytes, little endian bits in every byte) with
+/// z = Keccak-256(message), see `verify_prehashed_signature`.
+pub fn verify_signature<'a, E, CS>(
+    cs: &mut CS,
+    public_key: &AffinePoint<'a, E, PointAffine>,
+    signature: &CircuitEcdsaSignature<'a, E>,
+    message: &[Boolean],
+    scalar_params: &'a RnsParameters<E, Fr>
+) -> Result<Boolean, SynthesisError>
+    where E: Engine, CS: ConstraintSystem<E>
+{
+    let message_hash = keccak256_message_hash(cs, message)?;
+
+    verify_prehashed_signature(cs, public_key, signature, &message_hash, scalar_params)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use bellman::pairing::bn256::Bn256;
+    use bellman::pairing::GenericCurveProjective;
+    use crate::bellman::plonk::better_better_cs::cs::*;
+    use crate::bellman::plonk::better_better_cs::gates::selector_optimized_with_d_next::SelectorOptimizedWidth4MainGateWithDNext;
+    use crate::plonk::circuit::Width4WithCustomGates;
+    use crate::plonk::circuit::curve_new::secp256k1::fq::Fq;
+    use tiny_keccak::Keccak;
+
+    fn alloc_bytes<CS: ConstraintSystem<Bn256>>(cs: &mut CS, bytes: &[u8]) -> Vec<Boolean> {
+        bytes.iter()
+             .flat_map(|byte| (0..8).map(move |i| (byte >> i) & 1 == 1))
+             .map(|b| Boolean::alloc(cs, Some(b)).unwrap())
+             .collect()
+    }
+
+    fn message_hash(message: &[u8]) -> Fr {
+        let mut h = Keccak::new_keccak256();
+        h.update(message);
+        let mut digest = [0u8; 32];
+        h.finalize(&mut digest);
+
+        let modulus = repr_to_biguint::<Fr>(&Fr::char());
+        biguint_to_fe::<Fr>(BigUint::from_bytes_be(&digest) % modulus)
+    }
+
+    fn sign<R: Rng>(sk: Fr, message: &[u8], rng: &mut R) -> (Fr, Fr) {
+        let z = message_hash(message);
+        let k: Fr = rng.gen();
+
+        let mut point = PointAffine::one().into_projective();
+        point.mul_assign(k);
+        let (x, _) = point.into_affine().into_xy_unchecked();
+        let modulus = repr_to_biguint::<Fr>(&Fr::char());
+        let r = biguint_to_fe::<Fr>(repr_to_biguint::<Fq>(&x.into_repr()) % modulus);
+
+        // s = (z + r * sk) / k
+        let mut s = r;
+        s.mul_assign(&sk);
+        s.add_assign(&z);
+        s.mul_assign(&k.inverse().unwrap());
+
+        (r, s)
+    }
+
+    #[test]
+    fn test_ecdsa_secp256k1_keccak256_gadget() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        let sk: Fr = rng.gen();
+        let mut pk = PointAffine::one().into_projective();
+        pk.mul_assign(sk);
+        let pk = pk.into_affine();
+
+        let message = b"transfer 1 ether";
+        let (r, s) = sign(sk, message, &mut rng);
+
+        for (msg, expected) in vec![(&message[..], true), (&b"transfer 2 ether"[..], false)].into_iter() {
+            let mut cs = TrivialAssembly::<Bn256, Width4WithCustomGates, SelectorOptimizedWidth4MainGateWithDNext>::new();
+            inscribe_default_bitop_range_table(&mut cs).unwrap();
+            let params = RnsParameters::<Bn256, Fq>::new_optimal(&mut cs, 64usize);
+            let scalar_params = RnsParameters::<Bn256, Fr>::new_optimal(&mut cs, 80usize);
+
+            let public_key = AffinePoint::alloc(&mut cs, Some(pk), &params).unwrap();
+            let signature = CircuitEcdsaSignature {
+                r: FieldElement::alloc(&mut cs, Some(r), &scalar_params).unwrap(),
+                s: FieldElement::alloc(&mut cs, Some(s), &scalar_params).unwrap(),
+            };
+            let message_bits = alloc_bytes(&mut cs, msg);
+
+            let is_valid = verify_signature(&mut cs, &public_key, &signature, &message_bits, &scalar_params).unwrap();
+
+            assert!(cs.is_satisfied());
+            assert_eq!(is_valid.get_value().unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_reduce_into_scalar() {
+        let modulus = repr_to_biguint::<Fr>(&Fr::char());
+        let max = (BigUint::one() << SECP256K1_SCALAR_BITS) - BigUint::one();
+
+        for value in vec![BigUint::zero(), modulus.clone() - BigUint::one(), modulus.clone(), max].into_iter() {
+            let mut cs = TrivialAssembly::<Bn256, Width4WithCustomGates, SelectorOptimizedWidth4MainGateWithDNext>::new();
+            inscribe_default_bitop_range_table(&mut cs).unwrap();
+            let scalar_params = RnsParameters::<Bn256, Fr>::new_optimal(&mut cs, 80usize);
+
+            let bits: Vec<Boolean> = (0..SECP256K1_SCALAR_BITS)
+                .map(|i| Boolean::alloc(&mut cs, Some(((value.clone() >> i) & BigUint::one()).is_one())).unwrap())
+                .collect();
+            let reduced = reduce_into_scalar(&mut cs, &bits, &scalar_params).unwrap();
+
+            assert!(cs.is_satisfied());
+            assert_eq!(reduced.get_field_value().unwrap(), biguint_to_fe::<Fr>(value % &modulus));
+        }
+    }
+}
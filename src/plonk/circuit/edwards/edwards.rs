@@ -2,6 +2,7 @@ use crate::generic_twisted_edwards::edwards::*;
 use crate::bellman::plonk::better_better_cs::cs::ConstraintSystem;
 use crate::bellman::{Engine, Field, PrimeField, SqrtField, SynthesisError};
 use crate::plonk::circuit::Assignment;
+use crate::plonk::circuit::custom_edwards_add_gate::apply_edwards_add;
 use crate::plonk::circuit::allocated_num::{AllocatedNum, Num};
 use crate::plonk::circuit::simple_term::Term;
 use crate::plonk::circuit::{boolean::Boolean, linear_combination::LinearCombination};
@@ -65,6 +66,44 @@ impl<E: Engine, C: TwistedEdwardsCurveParams<E>> CircuitTwistedEdwardsCurveImple
 
         Ok(CircuitTwistedEdwardsPoint { x: x3, y: y3 })
     }
+    /// Same as `add`, but in two rows with `TwistedEdwardsAddCustomGate` if the constraint
+    /// system has custom gates and both points are allocated. The circuit must declare the gate.
+    pub fn add_with_custom_gate<CS: ConstraintSystem<E>>(
+        &self,
+        cs: &mut CS,
+        p: &CircuitTwistedEdwardsPoint<E>,
+        q: &CircuitTwistedEdwardsPoint<E>,
+    ) -> Result<CircuitTwistedEdwardsPoint<E>, SynthesisError> {
+        if !self.implementor.curve_params.is_param_a_equals_minus_one() {
+            unimplemented!("not yet implemented for a != -1");
+        }
+        if !(CS::Params::HAS_CUSTOM_GATES && CS::Params::STATE_WIDTH == 4 && CS::Params::CAN_ACCESS_NEXT_TRACE_STEP) {
+            return self.add(cs, p, q);
+        }
+
+        match (&p.x, &p.y, &q.x, &q.y) {
+            (Num::Variable(x1), Num::Variable(y1), Num::Variable(x2), Num::Variable(y2)) => {
+                let param_d = self.implementor.curve_params.param_d();
+                let (x3, y3) = apply_edwards_add(cs, &param_d, x1, y1, x2, y2)?;
+
+                Ok(CircuitTwistedEdwardsPoint { x: Num::Variable(x3), y: Num::Variable(y3) })
+            },
+            _ => self.add(cs, p, q),
+        }
+    }
+    /// Same as `double`, with the custom gate as in `add_with_custom_gate`: the addition
+    /// formula is complete, so the gate doubles as well.
+    pub fn double_with_custom_gate<CS: ConstraintSystem<E>>(
+        &self,
+        cs: &mut CS,
+        p: &CircuitTwistedEdwardsPoint<E>,
+    ) -> Result<CircuitTwistedEdwardsPoint<E>, SynthesisError> {
+        let has_gate = CS::Params::HAS_CUSTOM_GATES && CS::Params::STATE_WIDTH == 4 && CS::Params::CAN_ACCESS_NEXT_TRACE_STEP;
+        match (&p.x, &p.y) {
+            (Num::Variable(_), Num::Variable(_)) if has_gate => self.add_with_custom_gate(cs, p, p),
+            _ => self.double(cs, p),
+        }
+    }
     pub fn double<CS: ConstraintSystem<E>>(
         &self,
         cs: &mut CS,
@@ -272,12 +311,9 @@ impl<E: Engine, C: TwistedEdwardsCurveParams<E>> CircuitTwistedEdwardsCurveImple
 
     pub fn generator(&self) -> CircuitTwistedEdwardsPoint<E>
     {
-        let gen = self.implementor.curve_params.generator();
-        let (x, y) = gen.into_xy();
-        let x = Num::Constant(x);
-        let y = Num::Constant(y);
+        let (x, y) = self.implementor.curve_params.generator().into_xy();
 
-        CircuitTwistedEdwardsPoint { x, y }
+        CircuitTwistedEdwardsPoint::constant(x, y)
     }
 
     // pub fn generator_as_allocated_variable<CS>(&self, cs: &mut CS) -> Result<CircuitTwistedEdwardsPoint<E>, SynthesisError>
@@ -346,6 +382,14 @@ impl<E: Engine> CircuitTwistedEdwardsPoint<E> {
         }
     }
 
+    /// The point (x, y) as constants, e.g. a fixed generator. It's not checked to be on the curve.
+    pub fn constant(x: E::Fr, y: E::Fr) -> Self {
+        Self {
+            x: Num::Constant(x),
+            y: Num::Constant(y),
+        }
+    }
+
     pub fn conditionally_select<CS: ConstraintSystem<E>>(
         cs: &mut CS,
         flag: &Boolean,
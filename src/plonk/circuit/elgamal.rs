@@ -0,0 +1,119 @@
+use crate::bellman::{
+    SynthesisError,
+};
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    ConstraintSystem,
+};
+
+use super::boolean::Boolean;
+use super::edwards::{CircuitTwistedEdwardsCurveImplementor, CircuitTwistedEdwardsPoint};
+
+use crate::generic_twisted_edwards::TwistedEdwardsCurveParams;
+use crate::jubjub::{FixedGenerators, JubjubEngine, JubjubParams};
+
+// ElGamal encryption matching `crate::elgamal`. The ciphertext is meant to be exposed as public
+// input, which proves that the holder of the secret key for `pk` can decrypt the message the
+// rest of the circuit is about.
+
+#[derive(Clone, Debug)]
+pub struct CircuitElGamalCiphertext<E: JubjubEngine> {
+    pub c1: CircuitTwistedEdwardsPoint<E>,
+    pub c2: CircuitTwistedEdwardsPoint<E>,
+}
+
+/// (r . P_G, M + r . pk), `randomness` in little endian bits. `pk` is expected to be checked to
+/// be a valid key by the caller, e.g. as a public input.
+pub fn encrypt_point<E, CS, C>(
+    cs: &mut CS,
+    curve: &CircuitTwistedEdwardsCurveImplementor<E, C>,
+    message: &CircuitTwistedEdwardsPoint<E>,
+    pk: &CircuitTwistedEdwardsPoint<E>,
+    randomness: &[Boolean],
+    p_g: FixedGenerators,
+    params: &E::Params
+) -> Result<CircuitElGamalCiphertext<E>, SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>,
+          C: TwistedEdwardsCurveParams<E>
+{
+    let (x, y) = params.generator(p_g).into_xy();
+    let c1 = curve.mul(cs, &CircuitTwistedEdwardsPoint::constant(x, y), randomness)?;
+    let shared = curve.mul(cs, pk, randomness)?;
+    let c2 = curve.add(cs, message, &shared)?;
+
+    Ok(CircuitElGamalCiphertext { c1, c2 })
+}
+
+/// Same as `encrypt_point` for M = m . P_G, `message` in little endian bits.
+pub fn encrypt_exponential<E, CS, C>(
+    cs: &mut CS,
+    curve: &CircuitTwistedEdwardsCurveImplementor<E, C>,
+    message: &[Boolean],
+    pk: &CircuitTwistedEdwardsPoint<E>,
+    randomness: &[Boolean],
+    p_g: FixedGenerators,
+    params: &E::Params
+) -> Result<CircuitElGamalCiphertext<E>, SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>,
+          C: TwistedEdwardsCurveParams<E>
+{
+    let (x, y) = params.generator(p_g).into_xy();
+    let message = curve.mul(cs, &CircuitTwistedEdwardsPoint::constant(x, y), message)?;
+
+    encrypt_point(cs, curve, &message, pk, randomness, p_g, params)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use crate::bellman::pairing::ff::PrimeField;
+    use crate::bellman::plonk::better_better_cs::cs::*;
+    use crate::alt_babyjubjub::{AltJubjubBn256, fs::Fs, edwards};
+    use crate::elgamal::{ElGamalCiphertext, decrypt_exponential};
+    use crate::jubjub::Unknown;
+    use crate::generic_twisted_edwards::TwistedEdwardsPoint;
+    use crate::plonk::circuit::edwards::bn256::CircuitAltBabyJubjubBn256;
+    use crate::plonk::circuit::utils::fe_to_lsb_first_bits;
+
+    fn alloc_bits<CS: ConstraintSystem<Bn256>>(cs: &mut CS, value: &Fs) -> Vec<Boolean> {
+        fe_to_lsb_first_bits(value).into_iter().map(|b| Boolean::alloc(cs, Some(b)).unwrap()).collect()
+    }
+
+    fn into_native(p: &CircuitTwistedEdwardsPoint<Bn256>, params: &AltJubjubBn256) -> edwards::Point<Bn256, Unknown> {
+        edwards::Point::from_xy(p.x.get_value().unwrap(), p.y.get_value().unwrap(), params).unwrap()
+    }
+
+    #[test]
+    fn test_exponential_elgamal_in_circuit() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = AltJubjubBn256::new();
+        let curve = CircuitAltBabyJubjubBn256::get_implementor();
+        let p_g = FixedGenerators::SpendingKeyGenerator;
+
+        let sk: Fs = rng.gen();
+        let pk: edwards::Point<Bn256, Unknown> = params.generator(p_g).mul(sk, &params).into();
+        let message = Fs::from_str("1234").unwrap();
+        let randomness: Fs = rng.gen();
+
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+        let (pk_x, pk_y) = pk.into_xy();
+        let pk_var = curve.alloc_point_enforce_on_curve(&mut cs, Some(TwistedEdwardsPoint::from_xy(pk_x, pk_y))).unwrap();
+        let message_bits = alloc_bits(&mut cs, &message);
+        let randomness_bits = alloc_bits(&mut cs, &randomness);
+
+        let ciphertext = encrypt_exponential(&mut cs, &curve, &message_bits, &pk_var, &randomness_bits, p_g, &params).unwrap();
+        assert!(cs.is_satisfied());
+
+        let native = crate::elgamal::encrypt_exponential(message, &pk, randomness, p_g, &params);
+        let from_circuit = ElGamalCiphertext {
+            c1: into_native(&ciphertext.c1, &params),
+            c2: into_native(&ciphertext.c2, &params),
+        };
+        assert!(from_circuit.c1 == native.c1 && from_circuit.c2 == native.c2);
+        assert_eq!(decrypt_exponential(&from_circuit, sk, 2000, p_g, &params), Some(1234));
+    }
+}
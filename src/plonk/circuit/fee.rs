@@ -0,0 +1,120 @@
+use crate::bellman::pairing::Engine;
+
+use crate::bellman::{
+    SynthesisError,
+};
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    ConstraintSystem,
+};
+
+use super::allocated_num::Num;
+
+// Fees of a batch of transactions. Every transaction authorizes a maximal fee, the operator
+// charges up to it, and the fees of the batch add up to the payment of the operator. Fees are
+// 64 bit and the total is kept in 128 bits, which no number of fees that fits into a circuit
+// can overflow, and which is far from the modulus of any field it is used over.
+
+pub const FEE_BITS: usize = 64;
+pub const TOTAL_FEE_BITS: usize = 128;
+
+/// Enforces that `fee` and `max_fee` are 64 bit and `fee` <= `max_fee`.
+pub fn enforce_fee_authorized<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    fee: &Num<E>,
+    max_fee: &Num<E>
+) -> Result<(), SynthesisError> {
+    fee.enforce_bit_length(cs, FEE_BITS)?;
+    max_fee.enforce_bit_length(cs, FEE_BITS)?;
+
+    max_fee.sub(cs, fee)?.enforce_bit_length(cs, FEE_BITS)
+}
+
+/// Sum of the fees of a batch.
+#[derive(Clone, Debug)]
+pub struct FeeAccumulator<E: Engine> {
+    total: Num<E>,
+    num_fees: usize,
+}
+
+impl<E: Engine> FeeAccumulator<E> {
+    pub fn new() -> Self {
+        FeeAccumulator { total: Num::zero(), num_fees: 0 }
+    }
+
+    /// Adds `fee`, which must be range checked already, as by `enforce_fee_authorized`.
+    pub fn add<CS: ConstraintSystem<E>>(&mut self, cs: &mut CS, fee: &Num<E>) -> Result<(), SynthesisError> {
+        self.total = self.total.add(cs, fee)?;
+        self.num_fees += 1;
+
+        Ok(())
+    }
+
+    pub fn num_fees(&self) -> usize {
+        self.num_fees
+    }
+
+    /// The total, enforced to be 128 bit.
+    pub fn total<CS: ConstraintSystem<E>>(&self, cs: &mut CS) -> Result<Num<E>, SynthesisError> {
+        if let Num::Variable(_) = self.total {
+            self.total.enforce_bit_length(cs, TOTAL_FEE_BITS)?;
+        }
+
+        Ok(self.total)
+    }
+}
+
+impl<E: Engine> Default for FeeAccumulator<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use bellman::pairing::ff::PrimeField;
+    use crate::bellman::plonk::better_better_cs::cs::*;
+
+    type Assembly = TrivialAssembly<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>;
+
+    fn alloc_u64(cs: &mut Assembly, value: u64) -> Num<Bn256> {
+        Num::alloc(cs, Some(Fr::from_str(&value.to_string()).unwrap())).unwrap()
+    }
+
+    #[test]
+    fn test_fee_aggregation() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        let mut cs = Assembly::new();
+        let mut fees = FeeAccumulator::new();
+        let mut expected = 0u128;
+        for _ in 0..8 {
+            let max_fee = rng.gen::<u64>() | (1 << 63);
+            let fee = rng.gen::<u64>() % max_fee;
+            expected += fee as u128;
+
+            let fee = alloc_u64(&mut cs, fee);
+            let max_fee = alloc_u64(&mut cs, max_fee);
+            enforce_fee_authorized(&mut cs, &fee, &max_fee).unwrap();
+            fees.add(&mut cs, &fee).unwrap();
+        }
+        let total = fees.total(&mut cs).unwrap();
+
+        assert!(cs.is_satisfied());
+        assert_eq!(fees.num_fees(), 8);
+        assert_eq!(total.get_value().unwrap(), Fr::from_str(&expected.to_string()).unwrap());
+    }
+
+    #[test]
+    fn test_fee_above_authorization() {
+        let mut cs = Assembly::new();
+        let fee = alloc_u64(&mut cs, 101);
+        let max_fee = alloc_u64(&mut cs, 100);
+        enforce_fee_authorized(&mut cs, &fee, &max_fee).unwrap();
+
+        assert!(!cs.is_satisfied());
+    }
+}
@@ -0,0 +1,204 @@
+use crate::bellman::pairing::Engine;
+
+use crate::bellman::SynthesisError;
+
+use crate::bellman::plonk::better_better_cs::cs::ConstraintSystem;
+
+use crate::generic_twisted_edwards::TwistedEdwardsCurveParams;
+use crate::jubjub::JubjubEngine;
+use crate::pedersen_hash::Personalization;
+use crate::poseidon::{PoseidonEngine, PoseidonHashParams};
+
+use super::allocated_num::Num;
+use super::blake2s::blake2s;
+use super::boolean::Boolean;
+use super::edwards::{CircuitTwistedEdwardsCurveImplementor, CircuitTwistedEdwardsPoint};
+use super::pedersen_hash::pedersen_hash_point;
+use super::poseidon::{poseidon_hash, PoseidonCsSBox};
+use super::sha256::sha256;
+
+/// A circuit component with typed inputs and outputs. The gadget functions of this crate have
+/// signatures of their own, the types below wrap the common ones so they can be composed with
+/// `GadgetExt`.
+pub trait Gadget<E: Engine> {
+    type Input;
+    type Output;
+
+    fn synthesize<CS: ConstraintSystem<E>>(&self, cs: &mut CS, input: Self::Input) -> Result<Self::Output, SynthesisError>;
+}
+
+pub trait GadgetExt<E: Engine>: Gadget<E> + Sized {
+    /// Feeds the output of `self` into `next`.
+    fn then<G: Gadget<E, Input = Self::Output>>(self, next: G) -> Then<Self, G> {
+        Then { first: self, second: next }
+    }
+
+    /// Applies `self` to every element of a vector.
+    fn map_vec(self) -> MapVec<Self> {
+        MapVec { gadget: self }
+    }
+
+    /// Synthesizes `self` only if `include` is set. The flag is fixed at setup, so the shape
+    /// of the circuit doesn't depend on the witness.
+    fn when(self, include: bool) -> When<Self> {
+        When { gadget: self, include }
+    }
+}
+
+impl<E: Engine, G: Gadget<E>> GadgetExt<E> for G {}
+
+pub struct Then<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<E: Engine, A: Gadget<E>, B: Gadget<E, Input = A::Output>> Gadget<E> for Then<A, B> {
+    type Input = A::Input;
+    type Output = B::Output;
+
+    fn synthesize<CS: ConstraintSystem<E>>(&self, cs: &mut CS, input: A::Input) -> Result<B::Output, SynthesisError> {
+        let intermediate = self.first.synthesize(cs, input)?;
+
+        self.second.synthesize(cs, intermediate)
+    }
+}
+
+pub struct MapVec<G> {
+    gadget: G,
+}
+
+impl<E: Engine, G: Gadget<E>> Gadget<E> for MapVec<G> {
+    type Input = Vec<G::Input>;
+    type Output = Vec<G::Output>;
+
+    fn synthesize<CS: ConstraintSystem<E>>(&self, cs: &mut CS, input: Vec<G::Input>) -> Result<Vec<G::Output>, SynthesisError> {
+        input.into_iter().map(|x| self.gadget.synthesize(cs, x)).collect()
+    }
+}
+
+pub struct When<G> {
+    gadget: G,
+    include: bool,
+}
+
+impl<E: Engine, G: Gadget<E>> Gadget<E> for When<G> {
+    type Input = G::Input;
+    type Output = Option<G::Output>;
+
+    fn synthesize<CS: ConstraintSystem<E>>(&self, cs: &mut CS, input: G::Input) -> Result<Option<G::Output>, SynthesisError> {
+        if self.include {
+            self.gadget.synthesize(cs, input).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// `sha256`, on a whole number of bytes.
+pub struct Sha256;
+
+impl<E: Engine> Gadget<E> for Sha256 {
+    type Input = Vec<Boolean>;
+    type Output = Vec<Boolean>;
+
+    fn synthesize<CS: ConstraintSystem<E>>(&self, cs: &mut CS, input: Vec<Boolean>) -> Result<Vec<Boolean>, SynthesisError> {
+        sha256(cs, &input)
+    }
+}
+
+/// `blake2s` with a fixed personalization.
+pub struct Blake2s<'a> {
+    pub personalization: &'a [u8],
+}
+
+impl<'a, E: Engine> Gadget<E> for Blake2s<'a> {
+    type Input = Vec<Boolean>;
+    type Output = Vec<Boolean>;
+
+    fn synthesize<CS: ConstraintSystem<E>>(&self, cs: &mut CS, input: Vec<Boolean>) -> Result<Vec<Boolean>, SynthesisError> {
+        blake2s(cs, &input, self.personalization)
+    }
+}
+
+/// `poseidon_hash`.
+pub struct PoseidonHash<'a, E: PoseidonEngine> {
+    pub params: &'a E::Params,
+}
+
+impl<'a, E: PoseidonEngine> Gadget<E> for PoseidonHash<'a, E>
+    where <<E as PoseidonEngine>::Params as PoseidonHashParams<E>>::SBox: PoseidonCsSBox<E>
+{
+    type Input = Vec<Num<E>>;
+    type Output = Vec<Num<E>>;
+
+    fn synthesize<CS: ConstraintSystem<E>>(&self, cs: &mut CS, input: Vec<Num<E>>) -> Result<Vec<Num<E>>, SynthesisError> {
+        poseidon_hash(cs, self.params, &input)
+    }
+}
+
+/// `pedersen_hash_point`.
+pub struct PedersenHash<'a, E: JubjubEngine, C: TwistedEdwardsCurveParams<E>> {
+    pub curve: &'a CircuitTwistedEdwardsCurveImplementor<E, C>,
+    pub personalization: Personalization,
+    pub params: &'a E::Params,
+}
+
+impl<'a, E: JubjubEngine, C: TwistedEdwardsCurveParams<E>> Gadget<E> for PedersenHash<'a, E, C> {
+    type Input = Vec<Boolean>;
+    type Output = CircuitTwistedEdwardsPoint<E>;
+
+    fn synthesize<CS: ConstraintSystem<E>>(&self, cs: &mut CS, input: Vec<Boolean>) -> Result<CircuitTwistedEdwardsPoint<E>, SynthesisError> {
+        pedersen_hash_point(cs, self.curve, self.personalization, &input, self.params)
+    }
+}
+
+/// `Num::into_bits_le_strict`.
+pub struct BitsLeStrict;
+
+impl<E: Engine> Gadget<E> for BitsLeStrict {
+    type Input = Num<E>;
+    type Output = Vec<Boolean>;
+
+    fn synthesize<CS: ConstraintSystem<E>>(&self, cs: &mut CS, input: Num<E>) -> Result<Vec<Boolean>, SynthesisError> {
+        input.into_bits_le_strict(cs)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use bellman::pairing::ff::PrimeField;
+    use crate::bellman::plonk::better_better_cs::cs::*;
+
+    #[test]
+    fn test_composition() {
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+
+        let nums: Vec<Num<Bn256>> = ["1", "2", "3"].iter()
+            .map(|v| Num::alloc(&mut cs, Some(Fr::from_str(v).unwrap())).unwrap())
+            .collect();
+        let bits = GadgetExt::<Bn256>::map_vec(BitsLeStrict).synthesize(&mut cs, nums.clone()).unwrap();
+        assert_eq!(bits.len(), 3);
+        for (bits, num) in bits.iter().zip(nums.iter()) {
+            let expected = num.into_bits_le_strict(&mut cs).unwrap();
+            let values: Vec<_> = bits.iter().map(|b| b.get_value()).collect();
+            assert_eq!(values, expected.iter().map(|b| b.get_value()).collect::<Vec<_>>());
+        }
+
+        let input: Vec<Boolean> = bits[2][..64].to_vec();
+        let composed = GadgetExt::<Bn256>::then(Blake2s { personalization: b"12345678" }, Sha256);
+        let expected = sha256(&mut cs, &blake2s(&mut cs, &input, b"12345678").unwrap()).unwrap();
+        let digest = composed.synthesize(&mut cs, input.clone()).unwrap();
+        for (a, b) in digest.iter().zip(expected.iter()) {
+            assert_eq!(a.get_value(), b.get_value());
+        }
+
+        let before = cs.n();
+        let skipped = GadgetExt::<Bn256>::when(Sha256, false).synthesize(&mut cs, input).unwrap();
+        assert!(skipped.is_none());
+        assert_eq!(cs.n(), before);
+
+        assert!(cs.is_satisfied());
+    }
+}
@@ -0,0 +1,199 @@
+use crate::bellman::pairing::Engine;
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    ConstraintSystem,
+    Index,
+    MainGate,
+    PlonkConstraintSystemParams,
+    PolyIdentifier,
+    TrivialAssembly,
+};
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// Golden files of circuit sizes. A test synthesizes a gadget, takes its `CircuitShape` and
+// calls `check_golden`, which compares it with the entry of the same name in the file. New
+// entries are added on the fly, changed ones fail the test unless `UPDATE_GOLDEN=1` is set,
+// in which case they are overwritten. The file has one line per entry:
+//
+//      <name> gates=<n> inputs=<n> aux=<n> inputs_hash=<hex>
+
+/// The size of a synthesized circuit and a digest of where its public inputs are used.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CircuitShape {
+    pub gates: usize,
+    pub inputs: usize,
+    pub aux: usize,
+    /// BLAKE2s of the number of public inputs and the cells of the gates they are wired to, so
+    /// reordered or added inputs are noticed. The values don't enter, any witness gives the same.
+    pub inputs_hash: String,
+}
+
+impl CircuitShape {
+    pub fn from_assembly<E, P, MG>(assembly: &TrivialAssembly<E, P, MG>) -> Self
+        where E: Engine,
+              P: PlonkConstraintSystemParams<E>,
+              MG: MainGate<E>
+    {
+        // (input, column, row) of every use of an input in the gates
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&(assembly.num_inputs as u64).to_le_bytes());
+        for column in 0..P::STATE_WIDTH {
+            let variables = match assembly.aux_storage.state_map.get(&PolyIdentifier::VariablesPolynomial(column)) {
+                Some(variables) => variables,
+                None => continue,
+            };
+            for (row, var) in variables.iter().enumerate() {
+                if let Index::Input(input) = var.get_unchecked() {
+                    for position in [input, column, row].iter() {
+                        bytes.extend_from_slice(&(*position as u64).to_le_bytes());
+                    }
+                }
+            }
+        }
+        let digest = blake2s_simd::blake2s(&bytes);
+
+        CircuitShape {
+            gates: assembly.n(),
+            inputs: assembly.num_inputs,
+            aux: assembly.num_aux,
+            inputs_hash: digest.as_bytes().iter().map(|b| format!("{:02x}", b)).collect(),
+        }
+    }
+
+    fn parse(fields: &[&str]) -> Option<Self> {
+        let mut values = BTreeMap::new();
+        for field in fields.iter() {
+            let mut split = field.splitn(2, '=');
+            values.insert(split.next()?, split.next()?);
+        }
+
+        Some(CircuitShape {
+            gates: values.get("gates")?.parse().ok()?,
+            inputs: values.get("inputs")?.parse().ok()?,
+            aux: values.get("aux")?.parse().ok()?,
+            inputs_hash: values.get("inputs_hash")?.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for CircuitShape {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "gates={} inputs={} aux={} inputs_hash={}", self.gates, self.inputs, self.aux, self.inputs_hash)
+    }
+}
+
+fn read_golden(path: &Path) -> io::Result<BTreeMap<String, CircuitShape>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+
+    let mut entries = BTreeMap::new();
+    for line in contents.lines().filter(|l| !l.trim().is_empty()) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let shape = CircuitShape::parse(&fields[1..])
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("malformed line `{}`", line)))?;
+        entries.insert(fields[0].to_string(), shape);
+    }
+
+    Ok(entries)
+}
+
+fn write_golden(path: &Path, entries: &BTreeMap<String, CircuitShape>) -> io::Result<()> {
+    let mut contents = String::new();
+    for (name, shape) in entries.iter() {
+        contents.push_str(&format!("{} {}\n", name, shape));
+    }
+
+    fs::write(path, contents)
+}
+
+/// Compares `shape` with the entry `name` of the golden file at `path`, see the module
+/// comment. Panics with both shapes if they differ.
+pub fn check_golden<P: AsRef<Path>>(path: P, name: &str, shape: &CircuitShape) {
+    assert!(!name.is_empty() && !name.contains(char::is_whitespace), "names are single words");
+    let path = path.as_ref();
+    let update = env::var("UPDATE_GOLDEN").map(|v| v == "1").unwrap_or(false);
+
+    let mut entries = read_golden(path).expect("golden file is readable");
+    match entries.get(name) {
+        Some(expected) if expected == shape => return,
+        Some(expected) if !update => panic!(
+            "shape of `{}` changed, rerun with UPDATE_GOLDEN=1 if this is intended\n  expected: {}\n  actual:   {}",
+            name, expected, shape
+        ),
+        _ => {},
+    }
+
+    entries.insert(name.to_string(), shape.clone());
+    write_golden(path, &entries).expect("golden file is writable");
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use bellman::pairing::ff::PrimeField;
+    use crate::bellman::plonk::better_better_cs::cs::*;
+    use crate::plonk::circuit::allocated_num::{AllocatedNum, Num};
+
+    // adds the inputs to an accumulator which is squared `squarings` times, with the inputs
+    // added before the squarings if `inputs_first` and after them otherwise
+    fn shape(inputs: &[&str], squarings: usize, inputs_first: bool) -> CircuitShape {
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+        let mut acc = Num::alloc(&mut cs, Some(Fr::from_str("2").unwrap())).unwrap();
+        for step in 0..2 {
+            if (step == 0) == inputs_first {
+                for input in inputs.iter() {
+                    let input = AllocatedNum::alloc_input(&mut cs, || Ok(Fr::from_str(input).unwrap())).unwrap();
+                    acc = acc.add(&mut cs, &Num::Variable(input)).unwrap();
+                }
+            } else {
+                for _ in 0..squarings {
+                    acc = acc.mul(&mut cs, &acc).unwrap();
+                }
+            }
+        }
+
+        CircuitShape::from_assembly(&cs)
+    }
+
+    #[test]
+    fn test_golden_file() {
+        let path = env::temp_dir().join(format!("franklin_golden_{}.txt", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let original = shape(&["1", "2"], 3, true);
+        check_golden(&path, "squarings", &original);
+        check_golden(&path, "squarings", &original);
+        check_golden(&path, "other", &shape(&["1"], 1, true));
+        assert_eq!(read_golden(&path).unwrap().len(), 2);
+
+        // another witness has the same shape
+        check_golden(&path, "squarings", &shape(&["5", "7"], 3, true));
+
+        let more_gates = shape(&["1", "2"], 4, true);
+        assert!(more_gates.gates > original.gates);
+        let moved_inputs = shape(&["1", "2"], 3, false);
+        assert_eq!(moved_inputs.gates, original.gates);
+        assert_eq!(moved_inputs.inputs, original.inputs);
+        assert!(moved_inputs.inputs_hash != original.inputs_hash);
+
+        for changed in [more_gates, moved_inputs].iter() {
+            let path = path.clone();
+            let changed = changed.clone();
+            let result = std::panic::catch_unwind(move || check_golden(&path, "squarings", &changed));
+            assert!(result.is_err());
+        }
+        assert_eq!(read_golden(&path).unwrap()["squarings"], original);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,225 @@
+use crate::bellman::pairing::{
+    Engine,
+};
+
+use crate::bellman::pairing::ff::{
+    Field,
+    PrimeField,
+};
+
+use crate::bellman::{
+    SynthesisError,
+};
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    ConstraintSystem,
+};
+
+use crate::plonk::circuit::Assignment;
+
+use super::allocated_num::{AllocatedNum, Num};
+use super::rescue::{rescue_hash, PlonkCsSBox};
+use super::utils::u64_to_fe;
+
+use crate::rescue::{RescueEngine, RescueHashParams};
+
+// Multiset equality through a grand product: two lists {a_i} and {b_i} are equal as multisets
+// iff the polynomials prod (X + a_i) and prod (X + b_i) are equal, which we check at a random
+// point X = challenge. Soundness error is n / |F| as long as the challenge is chosen after
+// both lists are fixed: it must either be a public input that the verifier derives from the
+// lists (e.g. from the public data commitment) or be derived in-circuit by hashing all of them.
+//
+// Tuples are first compressed into single elements as sum c^j * x_j with another independent
+// challenge c, so the same check works for memory logs, sorted lists and lookups.
+
+/// Enforces that `lhs` and `rhs` are equal as multisets.
+/// Costs 2 gates per element on each side.
+pub fn enforce_multiset_equality<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    lhs: &[Num<E>],
+    rhs: &[Num<E>],
+    challenge: &Num<E>
+) -> Result<(), SynthesisError> {
+    assert_eq!(lhs.len(), rhs.len(), "multisets of different sizes can not be equal");
+
+    let lhs_product = grand_product(cs, lhs, challenge)?;
+    let rhs_product = grand_product(cs, rhs, challenge)?;
+
+    lhs_product.enforce_equal(cs, &rhs_product)
+}
+
+/// Enforces that two lists of tuples of the same width are equal as multisets.
+pub fn enforce_multiset_equality_of_tuples<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    lhs: &[Vec<Num<E>>],
+    rhs: &[Vec<Num<E>>],
+    compression_challenge: &Num<E>,
+    permutation_challenge: &Num<E>
+) -> Result<(), SynthesisError> {
+    assert_eq!(lhs.len(), rhs.len(), "multisets of different sizes can not be equal");
+    if lhs.is_empty() {
+        return Ok(());
+    }
+
+    let width = lhs[0].len();
+    assert!(lhs.iter().chain(rhs.iter()).all(|el| el.len() == width), "all the tuples must have the same width");
+
+    let challenge_powers = compute_challenge_powers(cs, compression_challenge, width)?;
+
+    let mut lhs_compressed = Vec::with_capacity(lhs.len());
+    for tuple in lhs.iter() {
+        lhs_compressed.push(compress_tuple(cs, tuple, &challenge_powers)?);
+    }
+    let mut rhs_compressed = Vec::with_capacity(rhs.len());
+    for tuple in rhs.iter() {
+        rhs_compressed.push(compress_tuple(cs, tuple, &challenge_powers)?);
+    }
+
+    enforce_multiset_equality(cs, &lhs_compressed, &rhs_compressed, permutation_challenge)
+}
+
+/// Computes prod (challenge + x_i).
+pub fn grand_product<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    elements: &[Num<E>],
+    challenge: &Num<E>
+) -> Result<Num<E>, SynthesisError> {
+    let mut product = Num::one();
+    for el in elements.iter() {
+        let factor = el.add(cs, challenge)?;
+        product = product.mul(cs, &factor)?;
+    }
+
+    Ok(product)
+}
+
+/// Returns [1, c, c^2, ..., c^(width - 1)].
+pub fn compute_challenge_powers<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    challenge: &Num<E>,
+    width: usize
+) -> Result<Vec<Num<E>>, SynthesisError> {
+    let mut powers = Vec::with_capacity(width);
+    let mut current = Num::one();
+    for i in 0..width {
+        if i != 0 {
+            current = current.mul(cs, challenge)?;
+        }
+        powers.push(current);
+    }
+
+    Ok(powers)
+}
+
+/// Computes sum c^j * x_j for precomputed powers of c.
+pub fn compress_tuple<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    tuple: &[Num<E>],
+    challenge_powers: &[Num<E>]
+) -> Result<Num<E>, SynthesisError> {
+    assert_eq!(tuple.len(), challenge_powers.len());
+
+    let mut result = Num::zero();
+    for (el, power) in tuple.iter().zip(challenge_powers.iter()) {
+        let tmp = el.mul(cs, power)?;
+        result = result.add(cs, &tmp)?;
+    }
+
+    Ok(result)
+}
+
+/// Allocates a challenge as a public input. The verifier is responsible for
+/// deriving it from the committed lists.
+pub fn alloc_challenge_as_input<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    witness: Option<E::Fr>
+) -> Result<Num<E>, SynthesisError> {
+    let allocated = AllocatedNum::alloc_input(cs, || Ok(*witness.get()?))?;
+
+    Ok(Num::Variable(allocated))
+}
+
+/// Derives `num_challenges` challenges from the transcript in-circuit: the transcript is hashed
+/// into a seed and the i-th challenge is the hash of (seed, i).
+pub fn derive_challenges_with_rescue<E: RescueEngine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    params: &E::Params,
+    transcript: &[Num<E>],
+    num_challenges: usize
+) -> Result<Vec<Num<E>>, SynthesisError>
+    where <<E as RescueEngine>::Params as RescueHashParams<E>>::SBox0: PlonkCsSBox<E>,
+          <<E as RescueEngine>::Params as RescueHashParams<E>>::SBox1: PlonkCsSBox<E>
+{
+    assert!(!transcript.is_empty());
+
+    let seed = rescue_hash(cs, params, transcript)?[0];
+
+    let mut challenges = Vec::with_capacity(num_challenges);
+    for i in 0..num_challenges {
+        let index = Num::Constant(u64_to_fe::<E::Fr>(i as u64));
+        let challenge = rescue_hash(cs, params, &[seed, index])?[0];
+        challenges.push(challenge);
+    }
+
+    Ok(challenges)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use crate::bellman::plonk::better_better_cs::cs::*;
+    use crate::plonk::circuit::Width4WithCustomGates;
+    use crate::rescue::bn256::Bn256RescueParams;
+
+    #[test]
+    fn test_multiset_equality_with_rescue_challenge() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = Bn256RescueParams::new_checked_2_into_1();
+        let mut cs = TrivialAssembly::<Bn256, Width4WithCustomGates, Width4MainGateWithDNext>::new();
+
+        let mut witness: Vec<Fr> = (0..16).map(|_| rng.gen()).collect();
+        let lhs: Vec<_> = witness.iter().map(|el| Num::alloc(&mut cs, Some(*el)).unwrap()).collect();
+        rng.shuffle(&mut witness);
+        let rhs: Vec<_> = witness.iter().map(|el| Num::alloc(&mut cs, Some(*el)).unwrap()).collect();
+
+        let mut transcript = lhs.clone();
+        transcript.extend_from_slice(&rhs);
+        let challenges = derive_challenges_with_rescue(&mut cs, &params, &transcript, 1).unwrap();
+
+        enforce_multiset_equality(&mut cs, &lhs, &rhs, &challenges[0]).unwrap();
+        assert!(cs.is_satisfied());
+    }
+
+    #[test]
+    fn test_multiset_equality_of_tuples() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+
+        let tuples: Vec<Vec<Fr>> = (0..8).map(|_| (0..3).map(|_| rng.gen()).collect()).collect();
+        let lhs: Vec<Vec<Num<Bn256>>> = tuples.iter().map(|t| t.iter().map(|el| Num::alloc(&mut cs, Some(*el)).unwrap()).collect()).collect();
+        let rhs: Vec<Vec<Num<Bn256>>> = tuples.iter().rev().map(|t| t.iter().map(|el| Num::alloc(&mut cs, Some(*el)).unwrap()).collect()).collect();
+
+        let compression_challenge = alloc_challenge_as_input(&mut cs, Some(rng.gen())).unwrap();
+        let permutation_challenge = alloc_challenge_as_input(&mut cs, Some(rng.gen())).unwrap();
+
+        enforce_multiset_equality_of_tuples(&mut cs, &lhs, &rhs, &compression_challenge, &permutation_challenge).unwrap();
+        assert!(cs.is_satisfied());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_multiset_equality_rejects_different_multisets() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+
+        let witness: Vec<Fr> = (0..4).map(|_| rng.gen()).collect();
+        let lhs: Vec<_> = witness.iter().map(|el| Num::alloc(&mut cs, Some(*el)).unwrap()).collect();
+        // same elements, but with a duplicate instead of the last one
+        let rhs: Vec<_> = [witness[0], witness[1], witness[2], witness[2]].iter().map(|el| Num::alloc(&mut cs, Some(*el)).unwrap()).collect();
+
+        let challenge = Num::Constant(rng.gen());
+        enforce_multiset_equality(&mut cs, &lhs, &rhs, &challenge).unwrap();
+    }
+}
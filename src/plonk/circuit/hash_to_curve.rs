@@ -0,0 +1,243 @@
+use crate::bellman::pairing::ff::{
+    Field,
+    PrimeField,
+    PrimeFieldRepr,
+    SqrtField,
+    LegendreSymbol,
+};
+
+use crate::bellman::{
+    SynthesisError,
+};
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    ConstraintSystem,
+};
+
+use super::allocated_num::{AllocatedNum, Num};
+use super::boolean::Boolean;
+use super::blake2s::blake2s;
+use super::edwards::{CircuitTwistedEdwardsCurveImplementor, CircuitTwistedEdwardsPoint};
+use super::linear_combination::LinearCombination;
+use super::Assignment;
+
+use crate::generic_twisted_edwards::TwistedEdwardsCurveParams;
+use crate::group_hash::elligator2_non_residue;
+use crate::jubjub::{JubjubEngine, JubjubParams};
+
+// Hash to curve matching `crate::group_hash::hash_to_curve` with `MapToCurve::Elligator2` and
+// `BlakeHasher`. Try-and-increment has no counterpart here, as the number of attempts isn't
+// known in advance.
+
+fn constant_byte_bits(byte: u8) -> impl Iterator<Item = Boolean> {
+    (0..8).map(move |i| Boolean::constant((byte >> i) & 1u8 == 1u8))
+}
+
+/// Hashes `msg` to `count` field elements. `msg` is a whole number of bytes, bits of every byte
+/// least significant first.
+pub fn hash_to_field<E, CS>(
+    cs: &mut CS,
+    personalization: &[u8],
+    domain: &[u8],
+    msg: &[Boolean],
+    count: usize
+) -> Result<Vec<Num<E>>, SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>
+{
+    assert_eq!(personalization.len(), 8);
+    assert!(domain.len() <= 255, "domain tags are at most 255 bytes");
+    assert!(msg.len() % 8 == 0);
+    assert!(count <= 128);
+
+    let mut result = Vec::with_capacity(count);
+    for i in 0..count {
+        let mut lc = LinearCombination::zero();
+        let mut coeff = E::Fr::one();
+
+        for counter in [2 * i as u8, 2 * i as u8 + 1].iter() {
+            let mut input: Vec<Boolean> = constant_byte_bits(domain.len() as u8).collect();
+            input.extend(domain.iter().flat_map(|&byte| constant_byte_bits(byte)));
+            input.extend(constant_byte_bits(*counter));
+            input.extend_from_slice(msg);
+            let digest = blake2s(cs, &input, personalization)?;
+
+            for bit in digest[..248].iter() {
+                lc.add_assign_boolean_with_coeff(bit, coeff);
+                coeff.double();
+            }
+        }
+
+        result.push(lc.into_num(cs)?);
+    }
+
+    Ok(result)
+}
+
+/// The Elligator 2 map of `u`, see `crate::group_hash::map_to_curve_elligator2`.
+pub fn map_to_curve_elligator2<E, CS>(
+    cs: &mut CS,
+    u: &Num<E>,
+    params: &E::Params
+) -> Result<CircuitTwistedEdwardsPoint<E>, SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>
+{
+    let z = elligator2_non_residue::<E>();
+    let a = Num::Constant(*params.montgomery_a());
+    let one = Num::Constant(E::Fr::one());
+
+    // t = Z.u^2. 1 + t doesn't vanish as long as -1 is a square, which holds for the fields of
+    // BN254 and BLS12-381.
+    let t = u.mul(cs, u)?.mul(cs, &Num::Constant(z))?;
+    let den = t.add(cs, &one)?;
+
+    // x1 = -A / (1 + t), x2 = -x1 - A
+    let mut minus_a = *params.montgomery_a();
+    minus_a.negate();
+    let x1 = Num::Constant(minus_a).div(cs, &den)?;
+    let x2 = Num::Constant(minus_a).sub(cs, &x1)?;
+
+    // g(x1) = x1.(x1^2 + A.x1 + 1), g(x2) = t.g(x1)
+    let x1_squared = x1.mul(cs, &x1)?;
+    let a_x1 = x1.mul(cs, &a)?;
+    let tmp = x1_squared.add_two(cs, &a_x1, &one)?;
+    let gx1 = x1.mul(cs, &tmp)?;
+    let gx2 = t.mul(cs, &gx1)?;
+
+    let gx1_value = gx1.get_value();
+    let is_square_value = gx1_value.map(|g| g.legendre() != LegendreSymbol::QuadraticNonResidue);
+    let is_square = Boolean::alloc(cs, is_square_value)?;
+
+    // y^2 = g(x) with y odd for x1 and even for x2, as in the native map
+    let y = AllocatedNum::alloc(cs, || {
+        let (g, odd) = if *is_square_value.get()? { (*gx1_value.get()?, true) } else { (*gx2.get_value().get()?, false) };
+        let mut y = g.sqrt().expect("g(x) is a square");
+        if y.into_repr().is_odd() != odd {
+            y.negate();
+        }
+        Ok(y)
+    })?;
+    let y = Num::Variable(y);
+    let g = Num::conditionally_select(cs, &is_square, &gx1, &gx2)?;
+    y.mul(cs, &y)?.enforce_equal(cs, &g)?;
+    let y_bits = y.into_bits_le_strict(cs)?;
+    Boolean::enforce_equal(cs, &y_bits[0], &is_square)?;
+
+    // x2 may only be taken if g(x1) is a nonzero non-square: Z.g(x1) is then a square and
+    // g(x1) is invertible
+    let is_not_square = is_square.not();
+    let z_gx1 = gx1.mul(cs, &Num::Constant(z))?;
+    let z_gx1 = Num::mask(cs, &z_gx1, &is_not_square)?;
+    let w = Num::Variable(AllocatedNum::alloc(cs, || {
+        if *is_square_value.get()? {
+            Ok(E::Fr::zero())
+        } else {
+            Ok(z_gx1.get_value().get()?.sqrt().expect("Z.g(x1) is a square"))
+        }
+    })?);
+    w.mul(cs, &w)?.enforce_equal(cs, &z_gx1)?;
+    let gx1_inv = Num::Variable(AllocatedNum::alloc(cs, || {
+        if *is_square_value.get()? {
+            Ok(E::Fr::zero())
+        } else {
+            Ok(gx1_value.get()?.inverse().expect("g(x1) is not zero"))
+        }
+    })?);
+    gx1.mul(cs, &gx1_inv)?.enforce_equal(cs, &Num::from_boolean_is(is_not_square))?;
+
+    let x = Num::conditionally_select(cs, &is_square, &x1, &x2)?;
+
+    // (x, y) -> (s.x / y, (x - 1) / (x + 1)). y = 0 only for (0, 0), which maps to (0, -1) by
+    // dividing by one instead.
+    let y_is_zero = y.is_zero(cs)?;
+    let den = Num::conditionally_select(cs, &y_is_zero, &one, &y)?;
+    let scaled_x = x.mul(cs, &Num::Constant(*params.scale()))?;
+    let edwards_x = scaled_x.div(cs, &den)?;
+    let edwards_y = x.sub(cs, &one)?.div(cs, &x.add(cs, &one)?)?;
+
+    Ok(CircuitTwistedEdwardsPoint {
+        x: edwards_x,
+        y: edwards_y,
+    })
+}
+
+/// Hashes `msg` to a point of prime order, see `hash_to_field` for the encoding of `msg`.
+/// `curve` has to describe the same curve as `params`.
+pub fn hash_to_curve<E, CS, C>(
+    cs: &mut CS,
+    curve: &CircuitTwistedEdwardsCurveImplementor<E, C>,
+    personalization: &[u8],
+    domain: &[u8],
+    msg: &[Boolean],
+    params: &E::Params
+) -> Result<CircuitTwistedEdwardsPoint<E>, SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>,
+          C: TwistedEdwardsCurveParams<E>
+{
+    let u = hash_to_field(cs, personalization, domain, msg, 2)?;
+    let p = map_to_curve_elligator2(cs, &u[0], params)?;
+    let q = map_to_curve_elligator2(cs, &u[1], params)?;
+
+    let mut result = curve.add(cs, &p, &q)?;
+    for _ in 0..3 {
+        result = curve.double(cs, &result)?;
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use crate::bellman::plonk::better_better_cs::cs::*;
+    use crate::alt_babyjubjub::AltJubjubBn256;
+    use crate::group_hash::{BlakeHasher, MapToCurve};
+    use crate::plonk::circuit::edwards::bn256::CircuitAltBabyJubjubBn256;
+
+    #[test]
+    fn test_elligator2_matches_native() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = AltJubjubBn256::new();
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+
+        let mut inputs: Vec<Fr> = (0..10).map(|_| rng.gen()).collect();
+        inputs.push(Fr::zero());
+        for u in inputs {
+            let (x, y) = crate::group_hash::map_to_curve_elligator2::<Bn256>(u, &params).into_xy();
+
+            let u = Num::alloc(&mut cs, Some(u)).unwrap();
+            let p = map_to_curve_elligator2(&mut cs, &u, &params).unwrap();
+            assert_eq!(p.x.get_value().unwrap(), x);
+            assert_eq!(p.y.get_value().unwrap(), y);
+        }
+
+        assert!(cs.is_satisfied());
+    }
+
+    #[test]
+    fn test_hash_to_curve_matches_native() {
+        let params = AltJubjubBn256::new();
+        let curve = CircuitAltBabyJubjubBn256::get_implementor();
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+
+        let msg = b"some message";
+        let expected = crate::group_hash::hash_to_curve::<Bn256, BlakeHasher>(
+            b"Hello123", b"domain", msg, MapToCurve::Elligator2, &params
+        );
+        let (x, y) = expected.into_xy();
+
+        let msg_bits: Vec<Boolean> = msg.iter()
+            .flat_map(|&byte| (0..8).map(move |i| (byte >> i) & 1u8 == 1u8))
+            .map(|b| Boolean::alloc(&mut cs, Some(b)).unwrap())
+            .collect();
+        let p = hash_to_curve(&mut cs, &curve, b"Hello123", b"domain", &msg_bits, &params).unwrap();
+        assert_eq!(p.x.get_value().unwrap(), x);
+        assert_eq!(p.y.get_value().unwrap(), y);
+
+        assert!(cs.is_satisfied());
+    }
+}
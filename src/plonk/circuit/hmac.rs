@@ -0,0 +1,115 @@
+use crate::bellman::pairing::{
+    Engine,
+};
+
+use crate::bellman::{
+    SynthesisError,
+};
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    ConstraintSystem,
+};
+
+use super::boolean::{
+    Boolean
+};
+
+use super::sha256::sha256;
+
+// HMAC (RFC 2104) over the SHA-256 gadget. Bits are in big-endian order within
+// every byte, the same as for `sha256`.
+
+const SHA256_BLOCK_SIZE: usize = 512;
+
+const IPAD: u8 = 0x36;
+const OPAD: u8 = 0x5c;
+
+// XOR with a constant is free: it either keeps or negates the bit
+fn xor_with_pad(key: &[Boolean], pad: u8) -> Vec<Boolean> {
+    key.iter().enumerate().map(|(i, bit)| {
+        if (pad >> (7 - i % 8)) & 1 == 1 {
+            bit.not()
+        } else {
+            bit.clone()
+        }
+    }).collect()
+}
+
+/// HMAC-SHA256 of `message` under `key`. The length of the key is public, keys longer than
+/// the block size are hashed first.
+pub fn hmac_sha256<E, CS>(
+    cs: &mut CS,
+    key: &[Boolean],
+    message: &[Boolean]
+) -> Result<Vec<Boolean>, SynthesisError>
+    where E: Engine, CS: ConstraintSystem<E>
+{
+    assert!(key.len() % 8 == 0);
+    assert!(message.len() % 8 == 0);
+
+    let mut key = if key.len() > SHA256_BLOCK_SIZE {
+        sha256(cs, key)?
+    } else {
+        key.to_vec()
+    };
+    key.resize(SHA256_BLOCK_SIZE, Boolean::constant(false));
+
+    let mut inner = xor_with_pad(&key, IPAD);
+    inner.extend_from_slice(message);
+    let inner_hash = sha256(cs, &inner)?;
+
+    let mut outer = xor_with_pad(&key, OPAD);
+    outer.extend(inner_hash);
+
+    sha256(cs, &outer)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use bellman::pairing::bn256::Bn256;
+
+    use super::super::boolean::AllocatedBit;
+    use crate::bellman::plonk::better_better_cs::cs::*;
+
+    fn alloc_bytes<CS: ConstraintSystem<Bn256>>(cs: &mut CS, data: &[u8]) -> Vec<Boolean> {
+        data.iter().flat_map(|&byte| (0..8).rev().map(move |i| (byte >> i) & 1u8 == 1u8))
+            .map(|b| AllocatedBit::alloc(cs, Some(b)).unwrap().into())
+            .collect()
+    }
+
+    fn check_hmac(key: &[u8], message: &[u8], expected: &str) {
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+        let key_bits = alloc_bytes(&mut cs, key);
+        let message_bits = alloc_bytes(&mut cs, message);
+
+        let mac = hmac_sha256(&mut cs, &key_bits, &message_bits).unwrap();
+
+        assert!(cs.is_satisfied());
+
+        let expected = hex::decode(expected).unwrap();
+        let expected_bits = expected.iter().flat_map(|&byte| (0..8).rev().map(move |i| (byte >> i) & 1u8 == 1u8));
+        assert_eq!(mac.len(), 256);
+        for (b, e) in mac.iter().zip(expected_bits) {
+            assert_eq!(b.get_value().unwrap(), e);
+        }
+    }
+
+    #[test]
+    fn test_hmac_sha256_rfc4231() {
+        // test case 2
+        check_hmac(
+            b"Jefe",
+            b"what do ya want for nothing?",
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+
+        // test case 6, the key is longer than a block
+        check_hmac(
+            &[0xaa; 131],
+            b"Test Using Larger Than Block-Size Key - Hash Key First",
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+        );
+    }
+}
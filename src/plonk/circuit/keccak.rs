@@ -0,0 +1,345 @@
+use crate::bellman::pairing::{
+    Engine,
+};
+
+use crate::bellman::{
+    SynthesisError,
+};
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    ConstraintSystem,
+};
+
+use super::boolean::{
+    Boolean
+};
+
+use super::uint64::UInt64;
+
+// Bitwise Keccak-f[1600] over 64 bit lanes. For a cheaper version that works over
+// sparse representations and lookup tables see `hashes_with_tables::keccak`.
+
+pub const KECCAK_STATE_WIDTH: usize = 25;
+pub const KECCAK_NUM_ROUNDS: usize = 24;
+
+/// Rate of Keccak-256 in bits.
+pub const KECCAK256_RATE: usize = 1088;
+
+pub const SHA3_256_RATE: usize = 1088;
+pub const SHA3_512_RATE: usize = 576;
+pub const SHAKE128_RATE: usize = 1344;
+pub const SHAKE256_RATE: usize = 1088;
+
+// FIPS-202 domain separation suffixes, the first bit is appended first
+const SHA3_DOMAIN_BITS: [bool; 2] = [false, true];
+const SHAKE_DOMAIN_BITS: [bool; 4] = [true, true, true, true];
+
+const ROUND_CONSTANTS: [u64; KECCAK_NUM_ROUNDS] = [
+    0x0000000000000001, 0x0000000000008082, 0x800000000000808a, 0x8000000080008000,
+    0x000000000000808b, 0x0000000080000001, 0x8000000080008081, 0x8000000000008009,
+    0x000000000000008a, 0x0000000000000088, 0x0000000080008009, 0x000000008000000a,
+    0x000000008000808b, 0x800000000000008b, 0x8000000000008089, 0x8000000000008003,
+    0x8000000000008002, 0x8000000000000080, 0x000000000000800a, 0x800000008000000a,
+    0x8000000080008081, 0x8000000000008080, 0x0000000080000001, 0x8000000080008008,
+];
+
+// rotation offsets for the lane (x, y) at index x + 5 * y
+const ROTATION_OFFSETS: [usize; KECCAK_STATE_WIDTH] = [
+     0,  1, 62, 28, 27,
+    36, 44,  6, 55, 20,
+     3, 10, 43, 25, 39,
+    41, 45, 15, 21,  8,
+    18,  2, 61, 56, 14
+];
+
+/// Keccak-f[1600] permutation, lane (x, y) of the state is at index x + 5 * y.
+pub fn keccak_f1600<E, CS>(
+    cs: &mut CS,
+    state: &[UInt64]
+) -> Result<Vec<UInt64>, SynthesisError>
+    where E: Engine, CS: ConstraintSystem<E>
+{
+    assert_eq!(state.len(), KECCAK_STATE_WIDTH);
+
+    let mut a = state.to_vec();
+
+    for round in 0..KECCAK_NUM_ROUNDS {
+        // theta
+        let mut c = Vec::with_capacity(5);
+        for x in 0..5 {
+            let mut tmp = a[x].xor(cs, &a[x + 5])?;
+            tmp = tmp.xor(cs, &a[x + 10])?;
+            tmp = tmp.xor(cs, &a[x + 15])?;
+            tmp = tmp.xor(cs, &a[x + 20])?;
+            c.push(tmp);
+        }
+
+        for x in 0..5 {
+            let d = c[(x + 4) % 5].xor(cs, &c[(x + 1) % 5].rotl(1))?;
+            for y in 0..5 {
+                a[x + 5 * y] = a[x + 5 * y].xor(cs, &d)?;
+            }
+        }
+
+        // rho and pi
+        let mut b = a.clone();
+        for x in 0..5 {
+            for y in 0..5 {
+                b[y + 5 * ((2 * x + 3 * y) % 5)] = a[x + 5 * y].rotl(ROTATION_OFFSETS[x + 5 * y]);
+            }
+        }
+
+        // chi
+        for x in 0..5 {
+            for y in 0..5 {
+                let tmp = b[(x + 1) % 5 + 5 * y].not().and(cs, &b[(x + 2) % 5 + 5 * y])?;
+                a[x + 5 * y] = b[x + 5 * y].xor(cs, &tmp)?;
+            }
+        }
+
+        // iota
+        a[0] = a[0].xor(cs, &UInt64::constant(ROUND_CONSTANTS[round]))?;
+    }
+
+    Ok(a)
+}
+
+/// Keccak sponge with multi-rate padding. `domain_bits` are appended to the message
+/// before the padding: none for the original Keccak, FIPS-202 functions use their own suffixes.
+/// Input and output bits are in little-endian order within every byte.
+pub(crate) fn keccak_sponge<E, CS>(
+    cs: &mut CS,
+    input: &[Boolean],
+    rate: usize,
+    domain_bits: &[bool],
+    output_len: usize
+) -> Result<Vec<Boolean>, SynthesisError>
+    where E: Engine, CS: ConstraintSystem<E>
+{
+    assert!(input.len() % 8 == 0);
+    assert!(rate % 64 == 0 && rate > 0 && rate < KECCAK_STATE_WIDTH * 64);
+
+    let mut padded = input.to_vec();
+    padded.extend(domain_bits.iter().map(|b| Boolean::constant(*b)));
+    // pad10*1
+    padded.push(Boolean::constant(true));
+    while padded.len() % rate != rate - 1 {
+        padded.push(Boolean::constant(false));
+    }
+    padded.push(Boolean::constant(true));
+
+    let mut state: Vec<_> = (0..KECCAK_STATE_WIDTH).map(|_| UInt64::constant(0)).collect();
+
+    for block in padded.chunks(rate) {
+        for (lane, word) in state.iter_mut().zip(block.chunks(64)) {
+            *lane = lane.xor(cs, &UInt64::from_bits(word))?;
+        }
+        state = keccak_f1600(cs, &state)?;
+    }
+
+    let mut output = Vec::with_capacity(output_len);
+    loop {
+        for lane in state[0..(rate / 64)].iter() {
+            output.extend(lane.into_bits());
+        }
+
+        if output.len() >= output_len {
+            break;
+        }
+
+        state = keccak_f1600(cs, &state)?;
+    }
+    output.truncate(output_len);
+
+    Ok(output)
+}
+
+/// Keccak-256 as used by Ethereum (original Keccak padding, not FIPS-202 SHA3-256).
+pub fn keccak256<E, CS>(
+    cs: &mut CS,
+    input: &[Boolean]
+) -> Result<Vec<Boolean>, SynthesisError>
+    where E: Engine, CS: ConstraintSystem<E>
+{
+    keccak_sponge(cs, input, KECCAK256_RATE, &[], 256)
+}
+
+/// FIPS-202 SHA3-256.
+pub fn sha3_256<E, CS>(
+    cs: &mut CS,
+    input: &[Boolean]
+) -> Result<Vec<Boolean>, SynthesisError>
+    where E: Engine, CS: ConstraintSystem<E>
+{
+    keccak_sponge(cs, input, SHA3_256_RATE, &SHA3_DOMAIN_BITS, 256)
+}
+
+/// FIPS-202 SHA3-512.
+pub fn sha3_512<E, CS>(
+    cs: &mut CS,
+    input: &[Boolean]
+) -> Result<Vec<Boolean>, SynthesisError>
+    where E: Engine, CS: ConstraintSystem<E>
+{
+    keccak_sponge(cs, input, SHA3_512_RATE, &SHA3_DOMAIN_BITS, 512)
+}
+
+/// FIPS-202 SHAKE128 squeezing `output_len` bits.
+pub fn shake128<E, CS>(
+    cs: &mut CS,
+    input: &[Boolean],
+    output_len: usize
+) -> Result<Vec<Boolean>, SynthesisError>
+    where E: Engine, CS: ConstraintSystem<E>
+{
+    keccak_sponge(cs, input, SHAKE128_RATE, &SHAKE_DOMAIN_BITS, output_len)
+}
+
+/// FIPS-202 SHAKE256 squeezing `output_len` bits.
+pub fn shake256<E, CS>(
+    cs: &mut CS,
+    input: &[Boolean],
+    output_len: usize
+) -> Result<Vec<Boolean>, SynthesisError>
+    where E: Engine, CS: ConstraintSystem<E>
+{
+    keccak_sponge(cs, input, SHAKE256_RATE, &SHAKE_DOMAIN_BITS, output_len)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{XorShiftRng, SeedableRng, Rng};
+
+    use bellman::pairing::bn256::Bn256;
+
+    use tiny_keccak::{Keccak, keccakf};
+
+    use super::super::boolean::AllocatedBit;
+    use crate::bellman::plonk::better_better_cs::cs::*;
+
+    fn alloc_input_bytes<CS: ConstraintSystem<Bn256>>(cs: &mut CS, data: &[u8]) -> Vec<Boolean> {
+        let mut input_bits = vec![];
+        for input_byte in data.iter() {
+            for bit_i in 0..8 {
+                input_bits.push(AllocatedBit::alloc(cs, Some((input_byte >> bit_i) & 1u8 == 1u8)).unwrap().into());
+            }
+        }
+
+        input_bits
+    }
+
+    fn check_output_bytes(output: &[Boolean], expected: &[u8]) {
+        assert_eq!(output.len(), expected.len() * 8);
+        let expected = expected.iter().flat_map(|&byte| (0..8).map(move |i| (byte >> i) & 1u8 == 1u8));
+        for (b, e) in output.iter().zip(expected) {
+            assert_eq!(b.get_value().unwrap(), e);
+        }
+    }
+
+    #[test]
+    fn test_keccak_f1600() {
+        let mut rng = XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+        let mut native = [0u64; KECCAK_STATE_WIDTH];
+        let mut state = vec![];
+        for lane in native.iter_mut() {
+            *lane = rng.gen();
+            state.push(UInt64::alloc(&mut cs, Some(*lane)).unwrap());
+        }
+
+        let result = keccak_f1600(&mut cs, &state).unwrap();
+        keccakf(&mut native);
+
+        assert!(cs.is_satisfied());
+        for (r, n) in result.iter().zip(native.iter()) {
+            assert_eq!(r.get_value().unwrap(), *n);
+        }
+    }
+
+    #[test]
+    fn test_keccak256_constant_constraints() {
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+        let out = keccak256(&mut cs, &[]).unwrap();
+        assert_eq!(cs.n(), 0);
+
+        let expected = hex::decode("c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470").unwrap();
+        check_output_bytes(&out, &expected);
+    }
+
+    #[test]
+    fn test_keccak256() {
+        let mut rng = XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        for input_len in vec![1, 32, 64, 135, 136, 137, 300] {
+            let data: Vec<u8> = (0..input_len).map(|_| rng.gen()).collect();
+
+            let mut h = Keccak::new_keccak256();
+            h.update(&data);
+            let mut expected = [0u8; 32];
+            h.finalize(&mut expected);
+
+            let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+            let input_bits = alloc_input_bytes(&mut cs, &data);
+            let out = keccak256(&mut cs, &input_bits).unwrap();
+
+            assert!(cs.is_satisfied());
+            check_output_bytes(&out, &expected);
+        }
+    }
+
+    #[test]
+    fn test_sha3() {
+        use sha3::{Digest, Sha3_256, Sha3_512};
+
+        let mut rng = XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        for input_len in vec![0, 71, 72, 73, 135, 136, 200] {
+            let data: Vec<u8> = (0..input_len).map(|_| rng.gen()).collect();
+
+            let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+            let input_bits = alloc_input_bytes(&mut cs, &data);
+
+            let out_256 = sha3_256(&mut cs, &input_bits).unwrap();
+            let out_512 = sha3_512(&mut cs, &input_bits).unwrap();
+
+            assert!(cs.is_satisfied());
+            check_output_bytes(&out_256, Sha3_256::digest(&data).as_slice());
+            check_output_bytes(&out_512, Sha3_512::digest(&data).as_slice());
+        }
+    }
+
+    #[test]
+    fn test_shake() {
+        use sha3::{Shake128, Shake256};
+        use sha3::digest::{Update, ExtendableOutput, XofReader};
+
+        let mut rng = XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        // output lengths exceeding the rate require additional squeezing
+        for &(input_len, output_len) in [(0, 32), (100, 13), (167, 200), (168, 64), (50, 300)].iter() {
+            let data: Vec<u8> = (0..input_len).map(|_| rng.gen()).collect();
+
+            let mut expected_128 = vec![0u8; output_len];
+            let mut h = Shake128::default();
+            h.update(&data);
+            h.finalize_xof().read(&mut expected_128);
+
+            let mut expected_256 = vec![0u8; output_len];
+            let mut h = Shake256::default();
+            h.update(&data);
+            h.finalize_xof().read(&mut expected_256);
+
+            let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+            let input_bits = alloc_input_bytes(&mut cs, &data);
+
+            let out_128 = shake128(&mut cs, &input_bits, output_len * 8).unwrap();
+            let out_256 = shake256(&mut cs, &input_bits, output_len * 8).unwrap();
+
+            assert!(cs.is_satisfied());
+            check_output_bytes(&out_128, &expected_128);
+            check_output_bytes(&out_256, &expected_256);
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use crate::bellman::{
+    SynthesisError,
+};
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    ConstraintSystem,
+};
+
+use super::allocated_num::Num;
+use super::boolean::Boolean;
+use super::edwards::{CircuitTwistedEdwardsCurveImplementor, CircuitTwistedEdwardsPoint};
+use super::merkle_tree::compute_root;
+use super::poseidon::{poseidon_hash, PoseidonCsSBox};
+use super::ring_signature::key_image;
+
+use crate::generic_twisted_edwards::TwistedEdwardsCurveParams;
+use crate::jubjub::{edwards, FixedGenerators, JubjubEngine, JubjubParams};
+use crate::poseidon::{PoseidonEngine, PoseidonHashParams};
+
+// Proof of ownership of one of the keys of a Poseidon Merkle tree (see `merkle_tree`), a leaf
+// is Poseidon(pk.x, pk.y). Unlike `ring_signature`, where every member is compared, the cost is
+// one fixed base multiplication and a path, so the set can be as large as the tree.
+
+/// The leaf of `pk` in the tree.
+pub fn key_leaf<E: JubjubEngine + PoseidonEngine, S>(pk: &edwards::Point<E, S>, params: &<E as PoseidonEngine>::Params) -> E::Fr {
+    let (x, y) = pk.into_xy();
+
+    crate::poseidon::poseidon_hash::<E>(params, &[x, y])[0]
+}
+
+/// Returns the root of the tree with the key sk . P_G (`sk` in little endian bits) at the
+/// position `index_bits` with the authentication path `path`, the caller compares it with the
+/// root of the allowed keys. If `key_image_base` is given the key image of `sk` is returned
+/// too, as by `ring_signature::prove_ring_membership`.
+pub fn prove_key_tree_membership<E, CS, C>(
+    cs: &mut CS,
+    curve: &CircuitTwistedEdwardsCurveImplementor<E, C>,
+    sk: &[Boolean],
+    path: &[Num<E>],
+    index_bits: &[Boolean],
+    key_image_base: Option<&CircuitTwistedEdwardsPoint<E>>,
+    p_g: FixedGenerators,
+    jubjub_params: &<E as JubjubEngine>::Params,
+    poseidon_params: &<E as PoseidonEngine>::Params
+) -> Result<(Num<E>, Option<CircuitTwistedEdwardsPoint<E>>), SynthesisError>
+    where E: JubjubEngine + PoseidonEngine,
+          CS: ConstraintSystem<E>,
+          C: TwistedEdwardsCurveParams<E>,
+          <<E as PoseidonEngine>::Params as PoseidonHashParams<E>>::SBox: PoseidonCsSBox<E>
+{
+    let (g_x, g_y) = jubjub_params.generator(p_g).into_xy();
+    let generator = CircuitTwistedEdwardsPoint::constant(g_x, g_y);
+    let pk = curve.mul(cs, &generator, sk)?;
+
+    let leaf = poseidon_hash(cs, poseidon_params, &[pk.x, pk.y])?[0];
+    let root = compute_root(cs, &leaf, path, index_bits, poseidon_params)?;
+
+    let image = match key_image_base {
+        Some(base) => Some(key_image(cs, curve, sk, base)?),
+        None => None,
+    };
+
+    Ok((root, image))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use bellman::pairing::bn256::Bn256;
+    use crate::bellman::plonk::better_better_cs::cs::*;
+    use crate::alt_babyjubjub::{AltJubjubBn256, fs::Fs};
+    use crate::merkle_tree::IncrementalMerkleTree;
+    use crate::plonk::circuit::edwards::bn256::CircuitAltBabyJubjubBn256;
+    use crate::plonk::circuit::utils::fe_to_lsb_first_bits;
+    use crate::poseidon::Bn256PoseidonParams;
+
+    const TREE_DEPTH: usize = 10;
+
+    #[test]
+    fn test_key_tree_membership() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let jubjub_params = AltJubjubBn256::new();
+        let poseidon_params = Bn256PoseidonParams::new_checked_2_into_1();
+        let curve = CircuitAltBabyJubjubBn256::get_implementor();
+        let p_g = FixedGenerators::SpendingKeyGenerator;
+
+        let sk: Fs = rng.gen();
+        let mut tree = IncrementalMerkleTree::<Bn256>::new(TREE_DEPTH, &poseidon_params);
+        for _ in 0..3 {
+            let other = jubjub_params.generator(p_g).mul(rng.gen::<Fs>(), &jubjub_params);
+            tree.append(key_leaf(&other, &poseidon_params), &poseidon_params);
+        }
+        let pk = jubjub_params.generator(p_g).mul(sk, &jubjub_params);
+        let index = tree.append_and_mark(key_leaf(&pk, &poseidon_params), &poseidon_params).unwrap();
+        let witness = tree.witness(index).unwrap().clone();
+
+        let outsider: Fs = rng.gen();
+        for (sk, expected) in vec![(sk, true), (outsider, false)].into_iter() {
+            let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+            let sk_bits: Vec<Boolean> = fe_to_lsb_first_bits(&sk).into_iter().map(|b| Boolean::alloc(&mut cs, Some(b)).unwrap()).collect();
+            let path: Vec<Num<Bn256>> = witness.path.iter().map(|p| Num::alloc(&mut cs, Some(*p)).unwrap()).collect();
+            let index_bits: Vec<Boolean> = witness.index_bits().into_iter().map(|b| Boolean::alloc(&mut cs, Some(b)).unwrap()).collect();
+
+            let (root, image) = prove_key_tree_membership(
+                &mut cs, &curve, &sk_bits, &path, &index_bits, None, p_g, &jubjub_params, &poseidon_params
+            ).unwrap();
+
+            assert!(cs.is_satisfied());
+            assert!(image.is_none());
+            assert_eq!(root.get_value().unwrap() == tree.root(), expected);
+        }
+    }
+}
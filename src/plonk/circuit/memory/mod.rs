@@ -0,0 +1,310 @@
+use crate::bellman::pairing::{
+    Engine,
+};
+
+use crate::bellman::pairing::ff::{
+    Field,
+    PrimeField,
+    PrimeFieldRepr,
+};
+
+use crate::bellman::{
+    SynthesisError,
+};
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    ConstraintSystem,
+};
+
+use super::allocated_num::Num;
+use super::boolean::Boolean;
+use super::grand_product::enforce_multiset_equality_of_tuples;
+use super::utils::u64_to_fe;
+
+use std::collections::HashMap;
+
+// Read-write memory with a permutation checked access log.
+//
+// Every read and write is appended to a log as a tuple (address, timestamp, value, is_write),
+// where timestamp is the position of the access in the log and is therefore a circuit constant.
+// Reads allocate their value as a free witness. On `finalize` the prover witnesses the same
+// tuples sorted by (address, timestamp) and we check:
+// - sorted log is a permutation of the original one (see `grand_product`);
+// - addresses in the sorted log are non-decreasing and fit into `address_bits`;
+// - for equal addresses timestamps are strictly increasing;
+// - every read returns either the previous value at the same address, or zero
+//   if it's the first access to this address (memory is zero initialized).
+//
+// Each access costs a constant number of gates plus two range checks in the sorted log,
+// instead of a 2^k mux per access.
+
+#[derive(Clone, Debug)]
+pub struct MemoryAccess<E: Engine> {
+    pub address: Num<E>,
+    pub timestamp: Num<E>,
+    pub value: Num<E>,
+    pub is_write: Boolean,
+}
+
+impl<E: Engine> Copy for MemoryAccess<E> {}
+
+impl<E: Engine> MemoryAccess<E> {
+    fn as_nums(&self) -> [Num<E>; 4] {
+        [self.address, self.timestamp, self.value, Num::from(self.is_write)]
+    }
+}
+
+#[must_use = "recorded accesses are only checked by `finalize`"]
+pub struct Memory<E: Engine> {
+    address_bits: usize,
+    log: Vec<MemoryAccess<E>>,
+    // native view of the memory content that is used to produce read witnesses
+    state: HashMap<u64, E::Fr>,
+    witness_is_complete: bool,
+}
+
+impl<E: Engine> Memory<E> {
+    /// Creates an empty (zero initialized) memory with addresses in [0, 2^address_bits).
+    pub fn new(address_bits: usize) -> Self {
+        assert!(address_bits > 0 && address_bits <= 32);
+
+        Self {
+            address_bits,
+            log: vec![],
+            state: HashMap::new(),
+            witness_is_complete: true,
+        }
+    }
+
+    pub fn num_accesses(&self) -> usize {
+        self.log.len()
+    }
+
+    fn address_witness(&mut self, address: &Num<E>) -> Option<u64> {
+        match address.get_value() {
+            Some(value) => {
+                let repr = value.into_repr();
+                assert!(repr.num_bits() as usize <= self.address_bits, "address is out of range");
+
+                Some(repr.as_ref()[0])
+            },
+            None => {
+                self.witness_is_complete = false;
+
+                None
+            }
+        }
+    }
+
+    fn next_timestamp(&self) -> Num<E> {
+        Num::Constant(u64_to_fe(self.log.len() as u64))
+    }
+
+    /// Reads a value at `address`. Returned value is unconstrained until `finalize` is called.
+    pub fn read<CS: ConstraintSystem<E>>(
+        &mut self,
+        cs: &mut CS,
+        address: &Num<E>
+    ) -> Result<Num<E>, SynthesisError> {
+        let witness = self.address_witness(address).map(|address| {
+            self.state.get(&address).cloned().unwrap_or(E::Fr::zero())
+        });
+
+        let value = Num::alloc(cs, witness)?;
+        let access = MemoryAccess {
+            address: *address,
+            timestamp: self.next_timestamp(),
+            value,
+            is_write: Boolean::constant(false),
+        };
+        self.log.push(access);
+
+        Ok(value)
+    }
+
+    /// Writes `value` at `address`.
+    pub fn write<CS: ConstraintSystem<E>>(
+        &mut self,
+        _cs: &mut CS,
+        address: &Num<E>,
+        value: &Num<E>
+    ) -> Result<(), SynthesisError> {
+        if let (Some(address), Some(value)) = (self.address_witness(address), value.get_value()) {
+            self.state.insert(address, value);
+        } else {
+            self.witness_is_complete = false;
+        }
+
+        let access = MemoryAccess {
+            address: *address,
+            timestamp: self.next_timestamp(),
+            value: *value,
+            is_write: Boolean::constant(true),
+        };
+        self.log.push(access);
+
+        Ok(())
+    }
+
+    fn sorted_log_witness(&self) -> Option<Vec<(E::Fr, E::Fr, E::Fr, bool)>> {
+        if !self.witness_is_complete {
+            return None;
+        }
+
+        let mut entries = Vec::with_capacity(self.log.len());
+        for (idx, access) in self.log.iter().enumerate() {
+            let address = access.address.get_value()?;
+            let address_key = address.into_repr().as_ref()[0];
+            let value = access.value.get_value()?;
+            let is_write = access.is_write.get_value()?;
+            entries.push((address_key, idx as u64, address, value, is_write));
+        }
+        entries.sort_by_key(|el| (el.0, el.1));
+
+        let sorted = entries.into_iter().map(|(_, ts, address, value, is_write)| {
+            (address, u64_to_fe::<E::Fr>(ts), value, is_write)
+        }).collect();
+
+        Some(sorted)
+    }
+
+    /// Checks consistency of all the recorded accesses. `derive_challenges` receives every
+    /// element of the original and the sorted logs and must return two independent challenges
+    /// (for tuple compression and for the grand product) bound to all of them, e.g. by hashing
+    /// the inputs in-circuit or by taking public inputs that the verifier derives the same way.
+    pub fn finalize<CS, F>(
+        self,
+        cs: &mut CS,
+        derive_challenges: F
+    ) -> Result<(), SynthesisError>
+        where CS: ConstraintSystem<E>,
+              F: FnOnce(&mut CS, &[Num<E>]) -> Result<(Num<E>, Num<E>), SynthesisError>
+    {
+        let sorted_witness = self.sorted_log_witness();
+        let log = self.log;
+        if log.is_empty() {
+            return Ok(());
+        }
+
+        let mut sorted = Vec::with_capacity(log.len());
+        for idx in 0..log.len() {
+            let witness = sorted_witness.as_ref().map(|el| el[idx]);
+            let access = MemoryAccess {
+                address: Num::alloc(cs, witness.map(|el| el.0))?,
+                timestamp: Num::alloc(cs, witness.map(|el| el.1))?,
+                value: Num::alloc(cs, witness.map(|el| el.2))?,
+                is_write: Boolean::alloc(cs, witness.map(|el| el.3))?,
+            };
+            sorted.push(access);
+        }
+
+        let mut transcript = Vec::with_capacity(8 * log.len());
+        for access in log.iter().chain(sorted.iter()) {
+            transcript.extend_from_slice(&access.as_nums());
+        }
+        let (compression_challenge, permutation_challenge) = derive_challenges(cs, &transcript)?;
+
+        // sorted log is a permutation of the original one
+        let original_tuples: Vec<Vec<Num<E>>> = log.iter().map(|el| el.as_nums().to_vec()).collect();
+        let sorted_tuples: Vec<Vec<Num<E>>> = sorted.iter().map(|el| el.as_nums().to_vec()).collect();
+        enforce_multiset_equality_of_tuples(
+            cs,
+            &original_tuples,
+            &sorted_tuples,
+            &compression_challenge,
+            &permutation_challenge
+        )?;
+
+        // sorted log is consistent
+        let timestamp_bits = crate::log2_floor(log.len()) as usize + 1;
+        let zero = Num::zero();
+
+        let first = &sorted[0];
+        first.address.enforce_bit_length(cs, self.address_bits)?;
+        Num::conditionally_enforce_equal(cs, &first.is_write.not(), &first.value, &zero)?;
+
+        for pair in sorted.windows(2) {
+            let (previous, current) = (&pair[0], &pair[1]);
+
+            let address_delta = current.address.sub(cs, &previous.address)?;
+            address_delta.enforce_bit_length(cs, self.address_bits)?;
+            let same_address = address_delta.is_zero(cs)?;
+
+            let mut timestamp_delta = current.timestamp.sub(cs, &previous.timestamp)?;
+            timestamp_delta = timestamp_delta.sub(cs, &Num::one())?;
+            let timestamp_delta = Num::mask(cs, &timestamp_delta, &same_address)?;
+            timestamp_delta.enforce_bit_length(cs, timestamp_bits)?;
+
+            let expected_value = Num::conditionally_select(cs, &same_address, &previous.value, &zero)?;
+            Num::conditionally_enforce_equal(cs, &current.is_write.not(), &current.value, &expected_value)?;
+        }
+
+        // all addresses are bounded by the last one
+        sorted.last().unwrap().address.enforce_bit_length(cs, self.address_bits)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use crate::bellman::plonk::better_better_cs::cs::*;
+
+    fn challenges_from_public_inputs<CS: ConstraintSystem<Bn256>>(
+        cs: &mut CS,
+        _transcript: &[Num<Bn256>]
+    ) -> Result<(Num<Bn256>, Num<Bn256>), SynthesisError> {
+        let mut rng = XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let a: Fr = rng.gen();
+        let b: Fr = rng.gen();
+
+        Ok((Num::Constant(a), Num::Constant(b)))
+    }
+
+    #[test]
+    fn test_memory_reads_and_writes() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+
+        let mut memory = Memory::<Bn256>::new(4);
+        let mut native = [Fr::zero(); 16];
+
+        for _ in 0..64 {
+            let address: u64 = rng.gen_range(0, 16);
+            let address_num = Num::alloc(&mut cs, Some(u64_to_fe(address))).unwrap();
+            if rng.gen() {
+                let value: Fr = rng.gen();
+                let value_num = Num::alloc(&mut cs, Some(value)).unwrap();
+                memory.write(&mut cs, &address_num, &value_num).unwrap();
+                native[address as usize] = value;
+            } else {
+                let value = memory.read(&mut cs, &address_num).unwrap();
+                assert_eq!(value.get_value().unwrap(), native[address as usize]);
+            }
+        }
+
+        memory.finalize(&mut cs, challenges_from_public_inputs).unwrap();
+        assert!(cs.is_satisfied());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_memory_rejects_forged_read() {
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+
+        let mut memory = Memory::<Bn256>::new(2);
+        let address = Num::alloc(&mut cs, Some(u64_to_fe(3))).unwrap();
+        let value = Num::alloc(&mut cs, Some(u64_to_fe(42))).unwrap();
+        memory.write(&mut cs, &address, &value).unwrap();
+
+        // forge the read witness by writing to the native state only
+        memory.state.insert(3, u64_to_fe(43));
+        let _ = memory.read(&mut cs, &address).unwrap();
+
+        // read consistency check trips over the forged value
+        memory.finalize(&mut cs, challenges_from_public_inputs).unwrap();
+    }
+}
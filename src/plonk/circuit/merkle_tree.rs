@@ -0,0 +1,453 @@
+use crate::bellman::pairing::Engine;
+
+use crate::bellman::{
+    SynthesisError,
+};
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    ConstraintSystem,
+};
+
+use super::allocated_num::Num;
+use super::boolean::Boolean;
+use super::poseidon::{poseidon_hash, PoseidonCsSBox};
+
+use crate::poseidon::{PoseidonEngine, PoseidonHashParams};
+
+use std::collections::{BTreeMap, BTreeSet};
+
+// Binary Merkle trees over Poseidon: a node is Poseidon(left, right). The position of a leaf
+// is given by little endian index bits, bit i is set if the node at height i is a right child.
+// Wide trees of arity 4 or 8 hash all children at once with Poseidon of the matching width,
+// the index is read in base 4 or 8 digits, each given by its little endian bits.
+
+/// Poseidon(left, right)
+pub fn hash_nodes<E, CS>(
+    cs: &mut CS,
+    left: &Num<E>,
+    right: &Num<E>,
+    params: &E::Params
+) -> Result<Num<E>, SynthesisError>
+    where E: PoseidonEngine,
+          CS: ConstraintSystem<E>,
+          <<E as PoseidonEngine>::Params as PoseidonHashParams<E>>::SBox: PoseidonCsSBox<E>
+{
+    Ok(poseidon_hash(cs, params, &[*left, *right])?[0])
+}
+
+/// Root of the tree containing `leaf` at the position `index_bits` with the authentication
+/// path `path`, siblings are ordered from the leaf level up.
+pub fn compute_root<E, CS>(
+    cs: &mut CS,
+    leaf: &Num<E>,
+    path: &[Num<E>],
+    index_bits: &[Boolean],
+    params: &E::Params
+) -> Result<Num<E>, SynthesisError>
+    where E: PoseidonEngine,
+          CS: ConstraintSystem<E>,
+          <<E as PoseidonEngine>::Params as PoseidonHashParams<E>>::SBox: PoseidonCsSBox<E>
+{
+    assert_eq!(path.len(), index_bits.len());
+
+    let mut current = *leaf;
+    for (sibling, bit) in path.iter().zip(index_bits.iter()) {
+        let (left, right) = Num::conditionally_reverse(cs, &current, sibling, bit)?;
+        current = hash_nodes(cs, &left, &right, params)?;
+    }
+
+    Ok(current)
+}
+
+/// Checks that `leaf` is in the tree with `root`.
+pub fn verify_path<E, CS>(
+    cs: &mut CS,
+    root: &Num<E>,
+    leaf: &Num<E>,
+    path: &[Num<E>],
+    index_bits: &[Boolean],
+    params: &E::Params
+) -> Result<Boolean, SynthesisError>
+    where E: PoseidonEngine,
+          CS: ConstraintSystem<E>,
+          <<E as PoseidonEngine>::Params as PoseidonHashParams<E>>::SBox: PoseidonCsSBox<E>
+{
+    let computed = compute_root(cs, leaf, path, index_bits, params)?;
+
+    Num::equals(cs, &computed, root)
+}
+
+/// Replaces `old_leaf` by `new_leaf`: enforces that `old_leaf` is in the tree with `old_root`
+/// and returns the root of the updated tree, which is computed over the same path and index.
+pub fn update_leaf<E, CS>(
+    cs: &mut CS,
+    old_root: &Num<E>,
+    old_leaf: &Num<E>,
+    new_leaf: &Num<E>,
+    path: &[Num<E>],
+    index_bits: &[Boolean],
+    params: &E::Params
+) -> Result<Num<E>, SynthesisError>
+    where E: PoseidonEngine,
+          CS: ConstraintSystem<E>,
+          <<E as PoseidonEngine>::Params as PoseidonHashParams<E>>::SBox: PoseidonCsSBox<E>
+{
+    let computed_old_root = compute_root(cs, old_leaf, path, index_bits, params)?;
+    computed_old_root.enforce_equal(cs, old_root)?;
+
+    compute_root(cs, new_leaf, path, index_bits, params)
+}
+
+/// Same as `update_leaf`, additionally enforces that the updated tree has root `new_root`.
+pub fn enforce_leaf_update<E, CS>(
+    cs: &mut CS,
+    old_root: &Num<E>,
+    new_root: &Num<E>,
+    old_leaf: &Num<E>,
+    new_leaf: &Num<E>,
+    path: &[Num<E>],
+    index_bits: &[Boolean],
+    params: &E::Params
+) -> Result<(), SynthesisError>
+    where E: PoseidonEngine,
+          CS: ConstraintSystem<E>,
+          <<E as PoseidonEngine>::Params as PoseidonHashParams<E>>::SBox: PoseidonCsSBox<E>
+{
+    let computed_new_root = update_leaf(cs, old_root, old_leaf, new_leaf, path, index_bits, params)?;
+
+    computed_new_root.enforce_equal(cs, new_root)
+}
+
+/// Positions (height, index) of the nodes a multiproof for the leaves at `indices` consists
+/// of, in the order `compute_multiproof_root` consumes them: level by level from the leaves up,
+/// by increasing index within a level. Nodes that can be computed from the leaves are left out.
+pub fn multiproof_positions(depth: usize, indices: &[u64]) -> Vec<(usize, u64)> {
+    let mut known: BTreeSet<u64> = indices.iter().cloned().collect();
+    assert_eq!(known.len(), indices.len(), "leaf indices must be distinct");
+    assert!(depth < 64 && known.iter().all(|i| *i < (1u64 << depth)));
+
+    let mut positions = vec![];
+    for height in 0..depth {
+        let mut parents = BTreeSet::new();
+        for idx in known.iter() {
+            if !known.contains(&(idx ^ 1)) {
+                positions.push((height, idx ^ 1));
+            }
+            parents.insert(idx >> 1);
+        }
+        known = parents;
+    }
+
+    positions
+}
+
+/// Root of the tree of the given depth containing `leaves` at `indices`. The indices are part
+/// of the circuit shape, `proof` holds the nodes at `multiproof_positions(depth, indices)`. Every
+/// internal node on the union of the paths is hashed once, so leaves sharing subtrees are
+/// cheaper than separate paths.
+pub fn compute_multiproof_root<E, CS>(
+    cs: &mut CS,
+    leaves: &[Num<E>],
+    indices: &[u64],
+    proof: &[Num<E>],
+    depth: usize,
+    params: &E::Params
+) -> Result<Num<E>, SynthesisError>
+    where E: PoseidonEngine,
+          CS: ConstraintSystem<E>,
+          <<E as PoseidonEngine>::Params as PoseidonHashParams<E>>::SBox: PoseidonCsSBox<E>
+{
+    assert!(leaves.len() > 0);
+    assert_eq!(leaves.len(), indices.len());
+    assert_eq!(proof.len(), multiproof_positions(depth, indices).len());
+
+    let mut known: BTreeMap<u64, Num<E>> = indices.iter().cloned().zip(leaves.iter().cloned()).collect();
+    let mut proof_nodes = proof.iter();
+
+    for _ in 0..depth {
+        let mut parents = BTreeMap::new();
+        for (idx, node) in known.iter() {
+            if parents.contains_key(&(idx >> 1)) {
+                continue;
+            }
+            let sibling = match known.get(&(idx ^ 1)) {
+                Some(sibling) => *sibling,
+                None => *proof_nodes.next().expect("proof length is checked"),
+            };
+            let (left, right) = if idx & 1 == 0 { (*node, sibling) } else { (sibling, *node) };
+            parents.insert(idx >> 1, hash_nodes(cs, &left, &right, params)?);
+        }
+        known = parents;
+    }
+
+    Ok(known[&0])
+}
+
+/// Checks that all `leaves` are in the tree with `root`, see `compute_multiproof_root`.
+pub fn verify_multiproof<E, CS>(
+    cs: &mut CS,
+    root: &Num<E>,
+    leaves: &[Num<E>],
+    indices: &[u64],
+    proof: &[Num<E>],
+    depth: usize,
+    params: &E::Params
+) -> Result<Boolean, SynthesisError>
+    where E: PoseidonEngine,
+          CS: ConstraintSystem<E>,
+          <<E as PoseidonEngine>::Params as PoseidonHashParams<E>>::SBox: PoseidonCsSBox<E>
+{
+    let computed = compute_multiproof_root(cs, leaves, indices, proof, depth, params)?;
+
+    Num::equals(cs, &computed, root)
+}
+
+/// Splits `index` into `depth` little endian base `arity` digits, each as its little endian bits.
+pub fn index_into_digits<E, CS>(
+    cs: &mut CS,
+    index: &Num<E>,
+    arity: usize,
+    depth: usize
+) -> Result<Vec<Vec<Boolean>>, SynthesisError>
+    where E: Engine,
+          CS: ConstraintSystem<E>
+{
+    assert!(arity.is_power_of_two() && arity >= 2);
+    let digit_bits = arity.trailing_zeros() as usize;
+    let bits = index.into_bits_le(cs, Some(depth * digit_bits))?;
+
+    Ok(bits.chunks(digit_bits).map(|digit| digit.to_vec()).collect())
+}
+
+// children of a node with `current` at position `digit` and the siblings in order around it:
+// child j is `current` if digit == j, siblings[j - 1] if digit < j and siblings[j] otherwise
+fn place_among_siblings<E, CS>(
+    cs: &mut CS,
+    current: &Num<E>,
+    siblings: &[Num<E>],
+    digit: &[Boolean]
+) -> Result<Vec<Num<E>>, SynthesisError>
+    where E: Engine,
+          CS: ConstraintSystem<E>
+{
+    let arity = siblings.len() + 1;
+    assert_eq!(arity, 1 << digit.len());
+
+    let mut children = Vec::with_capacity(arity);
+    let mut digit_is_smaller = Boolean::constant(false);
+    for j in 0..arity {
+        let literals: Vec<Boolean> = digit.iter().enumerate().map(|(i, bit)| {
+            if (j >> i) & 1 == 1 { bit.clone() } else { bit.not() }
+        }).collect();
+        let digit_is_j = Boolean::kary_and(cs, &literals)?;
+
+        let sibling = if j == 0 {
+            siblings[0]
+        } else if j == arity - 1 {
+            siblings[j - 1]
+        } else {
+            Num::conditionally_select(cs, &digit_is_smaller, &siblings[j - 1], &siblings[j])?
+        };
+        children.push(Num::conditionally_select(cs, &digit_is_j, current, &sibling)?);
+
+        digit_is_smaller = Boolean::or(cs, &digit_is_smaller, &digit_is_j)?;
+    }
+
+    Ok(children)
+}
+
+/// Root of the tree of arity `path[i].len() + 1` containing `leaf` at the position given by
+/// `index_digits`. At every level `path` has the other children of the node in order.
+/// `params` must be Poseidon with rate equal to the arity.
+pub fn compute_wide_root<E, CS>(
+    cs: &mut CS,
+    leaf: &Num<E>,
+    path: &[Vec<Num<E>>],
+    index_digits: &[Vec<Boolean>],
+    params: &E::Params
+) -> Result<Num<E>, SynthesisError>
+    where E: PoseidonEngine,
+          CS: ConstraintSystem<E>,
+          <<E as PoseidonEngine>::Params as PoseidonHashParams<E>>::SBox: PoseidonCsSBox<E>
+{
+    assert_eq!(path.len(), index_digits.len());
+
+    let mut current = *leaf;
+    for (siblings, digit) in path.iter().zip(index_digits.iter()) {
+        assert_eq!(siblings.len() + 1, params.rate() as usize);
+        let children = place_among_siblings(cs, &current, siblings, digit)?;
+        current = poseidon_hash(cs, params, &children)?[0];
+    }
+
+    Ok(current)
+}
+
+/// Checks that `leaf` is in the wide tree with `root`, see `compute_wide_root`.
+pub fn verify_wide_path<E, CS>(
+    cs: &mut CS,
+    root: &Num<E>,
+    leaf: &Num<E>,
+    path: &[Vec<Num<E>>],
+    index_digits: &[Vec<Boolean>],
+    params: &E::Params
+) -> Result<Boolean, SynthesisError>
+    where E: PoseidonEngine,
+          CS: ConstraintSystem<E>,
+          <<E as PoseidonEngine>::Params as PoseidonHashParams<E>>::SBox: PoseidonCsSBox<E>
+{
+    let computed = compute_wide_root(cs, leaf, path, index_digits, params)?;
+
+    Num::equals(cs, &computed, root)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use crate::bellman::plonk::better_better_cs::cs::*;
+    use crate::poseidon::{poseidon_hash as native_poseidon_hash, Bn256PoseidonParams};
+    use crate::plonk::circuit::utils::u64_to_fe;
+
+    // all levels of a full tree, leaves first
+    fn build_tree(leaves: &[Fr], params: &Bn256PoseidonParams) -> Vec<Vec<Fr>> {
+        let mut levels = vec![leaves.to_vec()];
+        while levels.last().unwrap().len() > 1 {
+            let next = levels.last().unwrap().chunks(2).map(|pair| {
+                native_poseidon_hash::<Bn256>(params, pair)[0]
+            }).collect();
+            levels.push(next);
+        }
+
+        levels
+    }
+
+    fn path_for(levels: &[Vec<Fr>], index: usize) -> Vec<Fr> {
+        levels[..levels.len() - 1].iter().enumerate().map(|(height, level)| level[(index >> height) ^ 1]).collect()
+    }
+
+    fn alloc_index<CS: ConstraintSystem<Bn256>>(cs: &mut CS, index: usize, depth: usize) -> Vec<Boolean> {
+        (0..depth).map(|i| Boolean::alloc(cs, Some((index >> i) & 1 == 1)).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_merkle_leaf_update() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = Bn256PoseidonParams::new_checked_2_into_1();
+        let depth = 3;
+        let index = 5;
+
+        let mut leaves: Vec<Fr> = (0..(1 << depth)).map(|_| rng.gen()).collect();
+        let old_levels = build_tree(&leaves, &params);
+        let old_leaf = leaves[index];
+        let new_leaf: Fr = rng.gen();
+        leaves[index] = new_leaf;
+        let new_levels = build_tree(&leaves, &params);
+        let old_root = old_levels[depth][0];
+        let new_root = new_levels[depth][0];
+        let path = path_for(&old_levels, index);
+        assert_eq!(path, path_for(&new_levels, index));
+
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+        let old_root_num = Num::alloc(&mut cs, Some(old_root)).unwrap();
+        let new_root_num = Num::alloc(&mut cs, Some(new_root)).unwrap();
+        let old_leaf_num = Num::alloc(&mut cs, Some(old_leaf)).unwrap();
+        let new_leaf_num = Num::alloc(&mut cs, Some(new_leaf)).unwrap();
+        let path_nums: Vec<_> = path.iter().map(|p| Num::alloc(&mut cs, Some(*p)).unwrap()).collect();
+        let index_bits = alloc_index(&mut cs, index, depth);
+
+        let is_member = verify_path(&mut cs, &old_root_num, &old_leaf_num, &path_nums, &index_bits, &params).unwrap();
+        assert!(is_member.get_value().unwrap());
+        enforce_leaf_update(
+            &mut cs, &old_root_num, &new_root_num, &old_leaf_num, &new_leaf_num, &path_nums, &index_bits, &params
+        ).unwrap();
+        assert!(cs.is_satisfied());
+
+        // a wrong old leaf breaks the update
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+        let old_root_num = Num::alloc(&mut cs, Some(old_root)).unwrap();
+        let wrong_leaf_num = Num::alloc(&mut cs, Some(rng.gen())).unwrap();
+        let new_leaf_num = Num::alloc(&mut cs, Some(new_leaf)).unwrap();
+        let path_nums: Vec<_> = path.iter().map(|p| Num::alloc(&mut cs, Some(*p)).unwrap()).collect();
+        let index_bits = alloc_index(&mut cs, index, depth);
+
+        let computed_new_root = update_leaf(
+            &mut cs, &old_root_num, &wrong_leaf_num, &new_leaf_num, &path_nums, &index_bits, &params
+        ).unwrap();
+        assert_eq!(computed_new_root.get_value().unwrap(), new_root);
+        assert!(!cs.is_satisfied());
+    }
+
+    #[test]
+    fn test_merkle_multiproof() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = Bn256PoseidonParams::new_checked_2_into_1();
+        let depth = 4;
+        let indices = vec![2u64, 3, 4, 13];
+
+        let leaves: Vec<Fr> = (0..(1 << depth)).map(|_| rng.gen()).collect();
+        let levels = build_tree(&leaves, &params);
+        let positions = multiproof_positions(depth, &indices);
+        // 2 and 3 are siblings and the paths of 2, 3 and 4 meet at height 2
+        assert_eq!(positions, vec![(0, 5), (0, 12), (1, 0), (1, 3), (1, 7), (2, 2)]);
+        assert!(positions.len() < indices.len() * depth);
+
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+        let root = Num::alloc(&mut cs, Some(levels[depth][0])).unwrap();
+        let leaf_nums: Vec<_> = indices.iter().map(|i| Num::alloc(&mut cs, Some(leaves[*i as usize])).unwrap()).collect();
+        let proof: Vec<_> = positions.iter().map(|(h, i)| Num::alloc(&mut cs, Some(levels[*h][*i as usize])).unwrap()).collect();
+
+        let is_valid = verify_multiproof(&mut cs, &root, &leaf_nums, &indices, &proof, depth, &params).unwrap();
+        assert!(is_valid.get_value().unwrap());
+        assert!(cs.is_satisfied());
+
+        // leaves in the wrong positions
+        let swapped = vec![indices[1], indices[0], indices[2], indices[3]];
+        let is_valid = verify_multiproof(&mut cs, &root, &leaf_nums, &swapped, &proof, depth, &params).unwrap();
+        assert!(!is_valid.get_value().unwrap());
+    }
+
+    #[test]
+    fn test_wide_merkle_paths() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        for (arity, params) in vec![
+            (4, Bn256PoseidonParams::new_checked_4_into_1()),
+            (8, Bn256PoseidonParams::new_checked_8_into_1())
+        ].into_iter() {
+            let depth = 3;
+            let leaves: Vec<Fr> = (0..arity * arity * arity).map(|_| rng.gen()).collect();
+            let mut levels = vec![leaves.clone()];
+            while levels.last().unwrap().len() > 1 {
+                let next = levels.last().unwrap().chunks(arity).map(|children| native_poseidon_hash::<Bn256>(&params, children)[0]).collect();
+                levels.push(next);
+            }
+            let root = levels[depth][0];
+
+            for &index in [0usize, arity + 1, leaves.len() - 1].iter() {
+                let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+                let root_num = Num::alloc(&mut cs, Some(root)).unwrap();
+                let leaf = Num::alloc(&mut cs, Some(leaves[index])).unwrap();
+                let index_num = Num::alloc(&mut cs, Some(u64_to_fe(index as u64))).unwrap();
+                let path: Vec<Vec<Num<Bn256>>> = (0..depth).map(|height| {
+                    let position = index / arity.pow(height as u32);
+                    let first = position - position % arity;
+                    (first..first + arity).filter(|i| *i != position).map(|i| {
+                        Num::alloc(&mut cs, Some(levels[height][i])).unwrap()
+                    }).collect()
+                }).collect();
+
+                let digits = index_into_digits(&mut cs, &index_num, arity, depth).unwrap();
+                let is_member = verify_wide_path(&mut cs, &root_num, &leaf, &path, &digits, &params).unwrap();
+                assert!(is_member.get_value().unwrap());
+                assert!(cs.is_satisfied());
+
+                // the same path for a different position
+                let other_index = Num::alloc(&mut cs, Some(u64_to_fe((index ^ 1) as u64))).unwrap();
+                let digits = index_into_digits(&mut cs, &other_index, arity, depth).unwrap();
+                let is_member = verify_wide_path(&mut cs, &root_num, &leaf, &path, &digits, &params).unwrap();
+                assert!(!is_member.get_value().unwrap());
+            }
+        }
+    }
+}
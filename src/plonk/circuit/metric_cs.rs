@@ -0,0 +1,249 @@
+use crate::bellman::pairing::Engine;
+
+use crate::bellman::pairing::ff::Field;
+
+use crate::bellman::SynthesisError;
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    ArithmeticTerm,
+    ConstraintSystem,
+    Gate,
+    GateInternal,
+    Index,
+    MainGate,
+    MainGateTerm,
+    PlonkConstraintSystemParams,
+    Variable,
+};
+
+use crate::bellman::plonk::better_better_cs::lookup_tables::{
+    LookupTableApplication,
+    MultiTableApplication,
+};
+
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+/// A constraint system that only counts. Witness closures are never called and nothing is
+/// stored per gate, so it measures the size of a gadget at a fraction of the cost of a
+/// `TrivialAssembly`. Gadgets that need witness values to choose their shape see
+/// `SynthesisError::AssignmentMissing`, as during setup.
+pub struct MetricCS<E: Engine, P: PlonkConstraintSystemParams<E>, MG: MainGate<E>> {
+    pub num_inputs: usize,
+    pub num_aux: usize,
+    pub num_input_gates: usize,
+    pub num_aux_gates: usize,
+    pub num_lookups: usize,
+    /// Gate applications by gate name. A trace step with several gates counts once for each.
+    pub gates: BTreeMap<&'static str, usize>,
+    main_gate: MG,
+    tables: Vec<Arc<LookupTableApplication<E>>>,
+    multitables: Vec<Arc<MultiTableApplication<E>>>,
+    explicit_zero: Option<Variable>,
+    explicit_one: Option<Variable>,
+    _marker: PhantomData<P>,
+}
+
+impl<E: Engine, P: PlonkConstraintSystemParams<E>, MG: MainGate<E>> MetricCS<E, P, MG> {
+    pub fn new() -> Self {
+        MetricCS {
+            num_inputs: 0,
+            num_aux: 0,
+            num_input_gates: 0,
+            num_aux_gates: 0,
+            num_lookups: 0,
+            gates: BTreeMap::new(),
+            main_gate: MG::default(),
+            tables: vec![],
+            multitables: vec![],
+            explicit_zero: None,
+            explicit_one: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// The number of trace steps, as `TrivialAssembly::n`.
+    pub fn n(&self) -> usize {
+        self.num_input_gates + self.num_aux_gates
+    }
+
+    fn explicit_constant(&mut self, value: E::Fr) -> Result<Variable, SynthesisError> {
+        let var = self.alloc(|| Ok(value))?;
+        let mut term = MainGateTerm::new();
+        term.add_assign(ArithmeticTerm::from_variable(var));
+        term.sub_assign(ArithmeticTerm::constant(value));
+        self.allocate_main_gate(term)?;
+
+        Ok(var)
+    }
+}
+
+impl<E: Engine, P: PlonkConstraintSystemParams<E>, MG: MainGate<E>> ConstraintSystem<E> for MetricCS<E, P, MG> {
+    type Params = P;
+    type MainGate = MG;
+
+    fn alloc<F>(&mut self, _value: F) -> Result<Variable, SynthesisError>
+        where F: FnOnce() -> Result<E::Fr, SynthesisError>
+    {
+        self.num_aux += 1;
+
+        Ok(Variable::new_unchecked(Index::Aux(self.num_aux)))
+    }
+
+    fn alloc_input<F>(&mut self, _value: F) -> Result<Variable, SynthesisError>
+        where F: FnOnce() -> Result<E::Fr, SynthesisError>
+    {
+        // every public input takes a row of its own
+        self.num_inputs += 1;
+        self.num_input_gates += 1;
+
+        Ok(Variable::new_unchecked(Index::Input(self.num_inputs)))
+    }
+
+    fn get_main_gate(&self) -> &MG {
+        &self.main_gate
+    }
+
+    fn begin_gates_batch_for_step(&mut self) -> Result<(), SynthesisError> {
+        Ok(())
+    }
+
+    fn new_gate_in_batch<G: Gate<E>>(
+        &mut self,
+        equation: &G,
+        _coefficients_assignments: &[E::Fr],
+        _variables_assignments: &[Variable],
+        _witness_assignments: &[E::Fr]
+    ) -> Result<(), SynthesisError> {
+        *self.gates.entry(equation.name()).or_insert(0) += 1;
+
+        Ok(())
+    }
+
+    fn end_gates_batch_for_step(&mut self) -> Result<(), SynthesisError> {
+        self.num_aux_gates += 1;
+
+        Ok(())
+    }
+
+    fn allocate_variables_without_gate(
+        &mut self,
+        _variables_assignments: &[Variable],
+        _witness_assignments: &[E::Fr]
+    ) -> Result<(), SynthesisError> {
+        Ok(())
+    }
+
+    fn get_value(&self, _variable: Variable) -> Result<E::Fr, SynthesisError> {
+        Err(SynthesisError::AssignmentMissing)
+    }
+
+    fn get_dummy_variable() -> Variable {
+        Variable::new_unchecked(Index::Aux(0))
+    }
+
+    fn get_explicit_zero(&mut self) -> Result<Variable, SynthesisError> {
+        if let Some(var) = self.explicit_zero {
+            return Ok(var);
+        }
+        let var = self.explicit_constant(E::Fr::zero())?;
+        self.explicit_zero = Some(var);
+
+        Ok(var)
+    }
+
+    fn get_explicit_one(&mut self) -> Result<Variable, SynthesisError> {
+        if let Some(var) = self.explicit_one {
+            return Ok(var);
+        }
+        let var = self.explicit_constant(E::Fr::one())?;
+        self.explicit_one = Some(var);
+
+        Ok(var)
+    }
+
+    fn add_table(&mut self, table: LookupTableApplication<E>) -> Result<Arc<LookupTableApplication<E>>, SynthesisError> {
+        assert!(self.get_table(&table.functional_name()).is_err(), "table is already added");
+        let table = Arc::new(table);
+        self.tables.push(table.clone());
+
+        Ok(table)
+    }
+
+    fn get_table(&self, functional_name: &str) -> Result<Arc<LookupTableApplication<E>>, SynthesisError> {
+        self.tables.iter()
+            .find(|t| t.functional_name() == functional_name)
+            .cloned()
+            .ok_or(SynthesisError::AssignmentMissing)
+    }
+
+    fn add_multitable(&mut self, table: MultiTableApplication<E>) -> Result<(), SynthesisError> {
+        assert!(self.get_multitable(&table.functional_name()).is_err(), "table is already added");
+        self.multitables.push(Arc::new(table));
+
+        Ok(())
+    }
+
+    fn get_multitable(&self, functional_name: &str) -> Result<Arc<MultiTableApplication<E>>, SynthesisError> {
+        self.multitables.iter()
+            .find(|t| t.functional_name() == functional_name)
+            .cloned()
+            .ok_or(SynthesisError::AssignmentMissing)
+    }
+
+    fn apply_single_lookup_gate(&mut self, _variables: &[Variable], _gate: Arc<LookupTableApplication<E>>) -> Result<(), SynthesisError> {
+        self.num_lookups += 1;
+
+        Ok(())
+    }
+
+    fn apply_multi_lookup_gate(&mut self, _variables: &[Variable], _gate: Arc<MultiTableApplication<E>>) -> Result<(), SynthesisError> {
+        self.num_lookups += 1;
+
+        Ok(())
+    }
+
+    fn get_current_step_number(&self) -> usize {
+        self.n()
+    }
+
+    fn get_current_aux_gate_number(&self) -> usize {
+        self.num_aux_gates
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use bellman::pairing::ff::PrimeField;
+    use crate::bellman::plonk::better_better_cs::cs::*;
+    use crate::plonk::circuit::allocated_num::{AllocatedNum, Num};
+    use crate::plonk::circuit::blake2s::blake2s;
+    use crate::plonk::circuit::boolean::Boolean;
+
+    fn synthesize<CS: ConstraintSystem<Bn256>>(cs: &mut CS) {
+        let a = AllocatedNum::alloc_input(cs, || Ok(Fr::from_str("3").unwrap())).unwrap();
+        let b = Num::alloc(cs, Some(Fr::from_str("5").unwrap())).unwrap();
+        let c = Num::Variable(a).mul(cs, &b).unwrap();
+        c.add(cs, &Num::Constant(Fr::one())).unwrap();
+
+        let bits: Vec<Boolean> = (0..64).map(|i| Boolean::alloc(cs, Some(i % 3 == 0)).unwrap()).collect();
+        blake2s(cs, &bits, b"12345678").unwrap();
+    }
+
+    #[test]
+    fn test_counts_match_assembly() {
+        let mut assembly = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+        synthesize(&mut assembly);
+        assert!(assembly.is_satisfied());
+
+        let mut metric = MetricCS::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+        synthesize(&mut metric);
+
+        assert_eq!(metric.n(), assembly.n());
+        assert_eq!(metric.num_inputs, 1);
+        assert_eq!(metric.gates.values().sum::<usize>(), metric.num_aux_gates);
+    }
+}
@@ -0,0 +1,179 @@
+use crate::bellman::pairing::{
+    Engine,
+};
+
+use crate::bellman::pairing::ff::{
+    Field,
+};
+
+use crate::bellman::{
+    SynthesisError,
+};
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    ConstraintSystem,
+};
+
+use super::allocated_num::Num;
+
+use crate::mimc::{Mimc7Params, MimcSpongeParams};
+
+// Gadgets matching circomlib `MiMC7`, `MultiMiMC7`, `MiMCFeistel` and `MiMCSponge`,
+// see `crate::mimc` for the native counterparts. Every round costs one addition
+// gate and a chain of multiplications: 4 for x^7 and 3 for x^5.
+
+fn pow_5<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    el: &Num<E>
+) -> Result<Num<E>, SynthesisError> {
+    let square = el.mul(cs, el)?;
+    let quad = square.mul(cs, &square)?;
+
+    quad.mul(cs, el)
+}
+
+fn pow_7<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    el: &Num<E>
+) -> Result<Num<E>, SynthesisError> {
+    let square = el.mul(cs, el)?;
+    let quad = square.mul(cs, &square)?;
+    let sixth = quad.mul(cs, &square)?;
+
+    sixth.mul(cs, el)
+}
+
+/// MiMC-7 encryption of `x` under key `k`.
+pub fn mimc7<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    params: &Mimc7Params<E>,
+    x: &Num<E>,
+    k: &Num<E>
+) -> Result<Num<E>, SynthesisError> {
+    let mut r = *x;
+    for c in params.round_constants.iter() {
+        let t = r.add_two(cs, k, &Num::Constant(*c))?;
+        r = pow_7(cs, &t)?;
+    }
+
+    r.add(cs, k)
+}
+
+/// Compression of multiple elements with MiMC-7, r_{i+1} = r_i + x_i + mimc7(x_i, r_i), r_0 = key.
+pub fn mimc7_multi_hash<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    params: &Mimc7Params<E>,
+    input: &[Num<E>],
+    key: &Num<E>
+) -> Result<Num<E>, SynthesisError> {
+    let mut r = *key;
+    for x in input.iter() {
+        let encrypted = mimc7(cs, params, x, &r)?;
+        r = r.add_two(cs, x, &encrypted)?;
+    }
+
+    Ok(r)
+}
+
+/// MiMC Feistel permutation of (xl, xr) under key `k`. Returns the new (xl, xr).
+pub fn mimc_feistel<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    params: &MimcSpongeParams<E>,
+    xl: &Num<E>,
+    xr: &Num<E>,
+    k: &Num<E>
+) -> Result<(Num<E>, Num<E>), SynthesisError> {
+    let mut xl = *xl;
+    let mut xr = *xr;
+    let num_rounds = params.round_constants.len();
+    for (i, c) in params.round_constants.iter().enumerate() {
+        let t = xl.add_two(cs, k, &Num::Constant(*c))?;
+        let t5 = pow_5(cs, &t)?;
+        let f = xr.add(cs, &t5)?;
+        if i < num_rounds - 1 {
+            xr = xl;
+            xl = f;
+        } else {
+            xr = f;
+        }
+    }
+
+    Ok((xl, xr))
+}
+
+/// MiMCSponge with rate 1 and capacity 1, returns `num_outputs` squeezed elements.
+pub fn mimc_sponge_hash<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    params: &MimcSpongeParams<E>,
+    input: &[Num<E>],
+    key: &Num<E>,
+    num_outputs: usize
+) -> Result<Vec<Num<E>>, SynthesisError> {
+    assert!(num_outputs > 0);
+    let mut r = Num::Constant(E::Fr::zero());
+    let mut c = Num::Constant(E::Fr::zero());
+    for x in input.iter() {
+        r = r.add(cs, x)?;
+        let (new_r, new_c) = mimc_feistel(cs, params, &r, &c, key)?;
+        r = new_r;
+        c = new_c;
+    }
+
+    let mut outputs = Vec::with_capacity(num_outputs);
+    outputs.push(r);
+    for _ in 1..num_outputs {
+        let (new_r, new_c) = mimc_feistel(cs, params, &r, &c, key)?;
+        r = new_r;
+        c = new_c;
+        outputs.push(r);
+    }
+
+    Ok(outputs)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use crate::bellman::plonk::better_better_cs::cs::*;
+    use crate::mimc;
+
+    #[test]
+    fn test_mimc7_gadget_matches_native() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = Mimc7Params::<Bn256>::new();
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+
+        let input: Vec<Fr> = (0..3).map(|_| rng.gen()).collect();
+        let key: Fr = rng.gen();
+        let input_nums: Vec<_> = input.iter().map(|el| Num::alloc(&mut cs, Some(*el)).unwrap()).collect();
+        let key_num = Num::alloc(&mut cs, Some(key)).unwrap();
+
+        let encrypted = mimc7(&mut cs, &params, &input_nums[0], &key_num).unwrap();
+        let hash = mimc7_multi_hash(&mut cs, &params, &input_nums, &key_num).unwrap();
+
+        assert!(cs.is_satisfied());
+        assert_eq!(encrypted.get_value().unwrap(), mimc::mimc7(&params, input[0], key));
+        assert_eq!(hash.get_value().unwrap(), mimc::mimc7_multi_hash(&params, &input, key));
+    }
+
+    #[test]
+    fn test_mimc_sponge_gadget_matches_native() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = MimcSpongeParams::<Bn256>::new();
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+
+        // Tornado style commitment: MiMCSponge(nullifier, secret) with zero key
+        let input: Vec<Fr> = (0..2).map(|_| rng.gen()).collect();
+        let input_nums: Vec<_> = input.iter().map(|el| Num::alloc(&mut cs, Some(*el)).unwrap()).collect();
+
+        let outputs = mimc_sponge_hash(&mut cs, &params, &input_nums, &Num::zero(), 2).unwrap();
+        let expected = mimc::mimc_sponge_hash(&params, &input, Fr::zero(), 2);
+
+        assert!(cs.is_satisfied());
+        for (o, e) in outputs.iter().zip(expected.iter()) {
+            assert_eq!(o.get_value().unwrap(), *e);
+        }
+    }
+}
@@ -3,11 +3,48 @@ pub mod custom_rescue_gate;
 pub mod rescue;
 pub mod linear_combination;
 pub mod boolean;
+pub mod barrel_shifter;
+pub mod mux;
+pub mod memory;
+pub mod sorting;
+pub mod grand_product;
 pub mod uint32;
+pub mod uint64;
 pub mod multieq;
+pub mod multipack;
 pub mod sha256;
+pub mod sha512;
+pub mod hmac;
 pub mod blake2s;
-//pub mod poseidon;
+pub mod blake2b;
+pub mod keccak;
+pub mod poseidon;
+pub mod mimc;
+pub mod sinsemilla;
+pub mod pedersen_hash;
+pub mod redjubjub;
+pub mod ecdsa;
+pub mod ring_signature;
+pub mod key_membership;
+pub mod credential;
+pub mod batch_signature;
+pub mod vrf;
+pub mod merkle_tree;
+pub mod rlp;
+pub mod bitcoin;
+pub mod set_membership;
+pub mod elgamal;
+pub mod ecdh;
+pub mod aes;
+pub mod note_encryption;
+pub mod note_commitment;
+pub mod nullifier;
+pub mod value_commitment;
+pub mod poseidon_encryption;
+pub mod hash_to_curve;
+pub mod public_data;
+pub mod fee;
+pub mod sponge;
 pub mod bigint;
 pub mod bigint_new;
 pub mod simple_term;
@@ -21,6 +58,16 @@ pub mod utils;
 pub mod permutation_network;
 pub mod edwards;
 pub mod custom_5th_degree_gate_optimized;
+pub mod custom_edwards_add_gate;
+pub mod debug;
+pub mod gadget;
+pub mod golden;
+pub mod metric_cs;
+pub mod snarkjs;
+pub mod tags;
+pub mod witness_cs;
+#[cfg(feature = "zkinterface")]
+pub mod zkif;
 
 pub mod assignment;
 pub mod hashes_with_tables;
@@ -0,0 +1,391 @@
+use crate::bellman::pairing::Engine;
+use crate::bellman::pairing::ff::{Field, PrimeField, PrimeFieldRepr};
+
+use crate::bellman::{
+    SynthesisError,
+};
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    ConstraintSystem,
+};
+
+use super::allocated_num::{AllocatedNum, Num};
+use super::boolean::Boolean;
+use super::byte::Byte;
+use super::linear_combination::LinearCombination;
+use super::multieq::{bytes_to_bits, bytes_to_bits_le, compute_multipacking};
+use super::utils::u64_to_fe;
+
+use crate::plonk::circuit::Assignment;
+
+// Circuit side of `multieq::compute_multipacking`: bits are packed little endian into field
+// elements of E::Fr::CAPACITY bits each, the last one taking the remainder. Several outputs are
+// concatenated before packing, so they share inputs instead of each padding its last one.
+
+/// Number of inputs `num_bits` bits are packed into.
+pub fn num_packed_inputs<E: Engine>(num_bits: usize) -> usize {
+    let capacity = E::Fr::CAPACITY as usize;
+
+    (num_bits + capacity - 1) / capacity
+}
+
+/// Number of bits that fit into `num_inputs` inputs.
+pub fn max_packed_bits<E: Engine>(num_inputs: usize) -> usize {
+    num_inputs * E::Fr::CAPACITY as usize
+}
+
+/// Packs the concatenation of `outputs` into the minimal number of public inputs and returns
+/// them, the verifier computes them with `compute_packed_inputs` or one of its byte variants.
+/// Panics if that takes more than `max_inputs` inputs.
+pub fn pack_into_inputs<E, CS>(
+    cs: &mut CS,
+    outputs: &[&[Boolean]],
+    max_inputs: usize
+) -> Result<Vec<AllocatedNum<E>>, SynthesisError>
+    where E: Engine,
+          CS: ConstraintSystem<E>
+{
+    let bits: Vec<Boolean> = outputs.iter().flat_map(|output| output.iter().cloned()).collect();
+    assert!(num_packed_inputs::<E>(bits.len()) <= max_inputs);
+
+    let mut inputs = Vec::with_capacity(num_packed_inputs::<E>(bits.len()));
+    for chunk in bits.chunks(E::Fr::CAPACITY as usize) {
+        let mut lc = LinearCombination::zero();
+        let mut coeff = E::Fr::one();
+        for bit in chunk.iter() {
+            lc.add_assign_boolean_with_coeff(bit, coeff);
+            coeff.double();
+        }
+        inputs.push(inputize_lc(cs, lc)?);
+    }
+
+    Ok(inputs)
+}
+
+// a public input enforced to be equal to `lc`
+fn inputize_lc<E, CS>(cs: &mut CS, mut lc: LinearCombination<E>) -> Result<AllocatedNum<E>, SynthesisError>
+    where E: Engine,
+          CS: ConstraintSystem<E>
+{
+    let mut minus_one = E::Fr::one();
+    minus_one.negate();
+
+    let value = lc.get_value();
+    let input = AllocatedNum::alloc_input(cs, || Ok(*value.get()?))?;
+    lc.add_assign_variable_with_coeff(&input, minus_one);
+    lc.enforce_zero(cs)?;
+
+    Ok(input)
+}
+
+/// The inputs of `pack_into_inputs` for `outputs`.
+pub fn compute_packed_inputs<E: Engine>(outputs: &[&[bool]]) -> Vec<E::Fr> {
+    let bits: Vec<bool> = outputs.iter().flat_map(|output| output.iter().cloned()).collect();
+
+    compute_multipacking::<E>(&bits)
+}
+
+/// `compute_packed_inputs` for outputs given as bytes that are allocated least significant bit
+/// first, as by `bytes_to_bits_le`.
+pub fn compute_packed_inputs_from_bytes_le<E: Engine>(outputs: &[&[u8]]) -> Vec<E::Fr> {
+    let bits: Vec<Vec<bool>> = outputs.iter().map(|output| bytes_to_bits_le(output)).collect();
+    let bits: Vec<&[bool]> = bits.iter().map(|b| &b[..]).collect();
+
+    compute_packed_inputs::<E>(&bits)
+}
+
+/// `compute_packed_inputs` for outputs given as bytes that are allocated most significant bit
+/// first, as by `bytes_to_bits` and the SHA-256 gadgets.
+pub fn compute_packed_inputs_from_bytes_be<E: Engine>(outputs: &[&[u8]]) -> Vec<E::Fr> {
+    let bits: Vec<Vec<bool>> = outputs.iter().map(|output| bytes_to_bits(output)).collect();
+    let bits: Vec<&[bool]> = bits.iter().map(|b| &b[..]).collect();
+
+    compute_packed_inputs::<E>(&bits)
+}
+
+/// `num_bits` little endian bits of `num`. Below E::Fr::NUM_BITS bits num < 2^num_bits is
+/// enforced, with E::Fr::NUM_BITS bits the decomposition is enforced to be the canonical one,
+/// so in either case the bits are determined by the value.
+pub fn unpack_into_bits<E, CS>(
+    cs: &mut CS,
+    num: &AllocatedNum<E>,
+    num_bits: usize
+) -> Result<Vec<Boolean>, SynthesisError>
+    where E: Engine,
+          CS: ConstraintSystem<E>
+{
+    assert!(num_bits <= E::Fr::NUM_BITS as usize);
+
+    if num_bits == E::Fr::NUM_BITS as usize {
+        Num::Variable(*num).into_bits_le_strict(cs)
+    } else {
+        num.into_bits_le(cs, Some(num_bits))
+    }
+}
+
+/// Inverse of `compute_multipacking` for `num_bits` bits: the little endian bits packed into
+/// `inputs`, e.g. public inputs of a circuit.
+pub fn unpack_inputs<E, CS>(
+    cs: &mut CS,
+    inputs: &[AllocatedNum<E>],
+    num_bits: usize
+) -> Result<Vec<Boolean>, SynthesisError>
+    where E: Engine,
+          CS: ConstraintSystem<E>
+{
+    let capacity = E::Fr::CAPACITY as usize;
+    assert_eq!(inputs.len(), (num_bits + capacity - 1) / capacity);
+
+    let mut bits = Vec::with_capacity(num_bits);
+    for (i, input) in inputs.iter().enumerate() {
+        let chunk_bits = std::cmp::min(capacity, num_bits - i * capacity);
+        bits.extend(unpack_into_bits(cs, input, chunk_bits)?);
+    }
+
+    Ok(bits)
+}
+
+// Bytes are packed E::Fr::CAPACITY / 8 to an input, 31 for BN254, the first byte of a chunk
+// into the lowest bits as by `byte::uniquely_encode_le_bytes_into_num`.
+
+/// Number of bytes packed into an input.
+pub fn bytes_per_input<E: Engine>() -> usize {
+    E::Fr::CAPACITY as usize / 8
+}
+
+/// Packs `bytes`, which are range checked as by `Byte::from_u8_witness`, into public inputs and
+/// returns them. The verifier computes them with `compute_byte_packing`.
+pub fn pack_bytes_into_inputs<E, CS>(
+    cs: &mut CS,
+    bytes: &[Byte<E>]
+) -> Result<Vec<AllocatedNum<E>>, SynthesisError>
+    where E: Engine,
+          CS: ConstraintSystem<E>
+{
+    let shift = u64_to_fe::<E::Fr>(256);
+
+    let mut inputs = Vec::with_capacity((bytes.len() + bytes_per_input::<E>() - 1) / bytes_per_input::<E>());
+    for chunk in bytes.chunks(bytes_per_input::<E>()) {
+        let mut lc = LinearCombination::zero();
+        let mut coeff = E::Fr::one();
+        for byte in chunk.iter() {
+            lc.add_assign_number_with_coeff(&byte.into_num(), coeff);
+            coeff.mul_assign(&shift);
+        }
+        inputs.push(inputize_lc(cs, lc)?);
+    }
+
+    Ok(inputs)
+}
+
+/// `num_bytes` range checked bytes of `num`, the lowest first, enforcing num < 256^num_bytes.
+pub fn unpack_into_bytes<E, CS>(
+    cs: &mut CS,
+    num: &AllocatedNum<E>,
+    num_bytes: usize
+) -> Result<Vec<Byte<E>>, SynthesisError>
+    where E: Engine,
+          CS: ConstraintSystem<E>
+{
+    assert!(num_bytes <= bytes_per_input::<E>());
+
+    let values = num.get_value().map(|v| {
+        let mut bytes = vec![];
+        v.into_repr().write_le(&mut bytes).unwrap();
+        bytes
+    });
+
+    let mut minus_one = E::Fr::one();
+    minus_one.negate();
+    let shift = u64_to_fe::<E::Fr>(256);
+
+    let mut bytes = Vec::with_capacity(num_bytes);
+    let mut lc = LinearCombination::zero();
+    let mut coeff = E::Fr::one();
+    for i in 0..num_bytes {
+        let byte = Byte::from_u8_witness(cs, values.as_ref().map(|v| v[i]))?;
+        lc.add_assign_number_with_coeff(&byte.into_num(), coeff);
+        coeff.mul_assign(&shift);
+        bytes.push(byte);
+    }
+    lc.add_assign_variable_with_coeff(num, minus_one);
+    lc.enforce_zero(cs)?;
+
+    Ok(bytes)
+}
+
+/// Inverse of `compute_byte_packing` for `num_bytes` bytes, e.g. of public inputs of a circuit.
+pub fn unpack_inputs_into_bytes<E, CS>(
+    cs: &mut CS,
+    inputs: &[AllocatedNum<E>],
+    num_bytes: usize
+) -> Result<Vec<Byte<E>>, SynthesisError>
+    where E: Engine,
+          CS: ConstraintSystem<E>
+{
+    let chunk_bytes = bytes_per_input::<E>();
+    assert_eq!(inputs.len(), (num_bytes + chunk_bytes - 1) / chunk_bytes);
+
+    let mut bytes = Vec::with_capacity(num_bytes);
+    for (i, input) in inputs.iter().enumerate() {
+        bytes.extend(unpack_into_bytes(cs, input, std::cmp::min(chunk_bytes, num_bytes - i * chunk_bytes))?);
+    }
+
+    Ok(bytes)
+}
+
+/// The inputs of `pack_bytes_into_inputs` for `bytes`.
+pub fn compute_byte_packing<E: Engine>(bytes: &[u8]) -> Vec<E::Fr> {
+    bytes.chunks(bytes_per_input::<E>()).map(|chunk| {
+        let mut repr = <E::Fr as PrimeField>::Repr::default();
+        let mut padded = vec![0u8; repr.as_ref().len() * 8];
+        padded[..chunk.len()].copy_from_slice(chunk);
+        repr.read_le(&padded[..]).unwrap();
+
+        E::Fr::from_repr(repr).unwrap()
+    }).collect()
+}
+
+/// The `num_bytes` bytes packed into `inputs` by `compute_byte_packing`, None if an input holds
+/// more bytes than its share.
+pub fn compute_byte_unpacking<E: Engine>(inputs: &[E::Fr], num_bytes: usize) -> Option<Vec<u8>> {
+    let chunk_bytes = bytes_per_input::<E>();
+    if inputs.len() != (num_bytes + chunk_bytes - 1) / chunk_bytes {
+        return None;
+    }
+
+    let mut bytes = Vec::with_capacity(num_bytes);
+    for (i, input) in inputs.iter().enumerate() {
+        let len = std::cmp::min(chunk_bytes, num_bytes - i * chunk_bytes);
+        let mut encoding = vec![];
+        input.into_repr().write_le(&mut encoding).unwrap();
+        if encoding[len..].iter().any(|&b| b != 0) {
+            return None;
+        }
+        bytes.extend_from_slice(&encoding[..len]);
+    }
+
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use crate::bellman::plonk::better_better_cs::cs::*;
+
+    type Assembly = TrivialAssembly<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>;
+
+    #[test]
+    fn test_unpack_inputs() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        for &num_bits in [1, 64, Fr::CAPACITY as usize, Fr::CAPACITY as usize + 1, 600].iter() {
+            let bits: Vec<bool> = (0..num_bits).map(|_| rng.gen()).collect();
+
+            let mut cs = Assembly::new();
+            let inputs: Vec<AllocatedNum<Bn256>> = compute_multipacking::<Bn256>(&bits).into_iter()
+                .map(|x| AllocatedNum::alloc_input(&mut cs, || Ok(x)).unwrap())
+                .collect();
+            let unpacked = unpack_inputs(&mut cs, &inputs, num_bits).unwrap();
+
+            assert!(cs.is_satisfied());
+            assert_eq!(unpacked.iter().map(|b| b.get_value().unwrap()).collect::<Vec<_>>(), bits);
+        }
+    }
+
+    #[test]
+    fn test_unpack_range() {
+        let mut cs = Assembly::new();
+        let num = AllocatedNum::alloc(&mut cs, || Ok(Fr::from_str("256").unwrap())).unwrap();
+        unpack_into_bits(&mut cs, &num, 8).unwrap();
+        assert!(!cs.is_satisfied());
+
+        let mut minus_one = Fr::one();
+        minus_one.negate();
+        let mut cs = Assembly::new();
+        let num = AllocatedNum::alloc(&mut cs, || Ok(minus_one)).unwrap();
+        let bits = unpack_into_bits(&mut cs, &num, Fr::NUM_BITS as usize).unwrap();
+        assert!(cs.is_satisfied());
+        assert_eq!(bits.len(), Fr::NUM_BITS as usize);
+    }
+
+    #[test]
+    fn test_pack_into_inputs() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        // a digest, an amount and a flag: 321 bits in 2 inputs instead of 4 packed one by one
+        let digest: Vec<u8> = (0..32).map(|_| rng.gen()).collect();
+        let amount: u64 = rng.gen();
+
+        let mut cs = Assembly::new();
+        let alloc_bits = |cs: &mut Assembly, bits: Vec<bool>| -> Vec<Boolean> {
+            bits.into_iter().map(|b| Boolean::alloc(cs, Some(b)).unwrap()).collect()
+        };
+        let digest_bits = alloc_bits(&mut cs, bytes_to_bits(&digest));
+        let amount_bits = alloc_bits(&mut cs, bytes_to_bits_le(&amount.to_le_bytes()));
+        let flag = [Boolean::constant(true)];
+
+        let inputs = pack_into_inputs(&mut cs, &[&digest_bits[..], &amount_bits[..], &flag[..]], 2).unwrap();
+        assert!(cs.is_satisfied());
+        assert_eq!(inputs.len(), num_packed_inputs::<Bn256>(321));
+        assert_eq!(inputs.len(), 2);
+
+        let values: Vec<Fr> = inputs.iter().map(|x| x.get_value().unwrap()).collect();
+        let mut expected_bits = bytes_to_bits(&digest);
+        expected_bits.extend(bytes_to_bits_le(&amount.to_le_bytes()));
+        expected_bits.push(true);
+        assert_eq!(values, compute_packed_inputs::<Bn256>(&[&expected_bits[..]]));
+
+        // without the flag the outputs are whole bytes
+        let mut cs = Assembly::new();
+        let digest_bits = alloc_bits(&mut cs, bytes_to_bits(&digest));
+        let inputs = pack_into_inputs(&mut cs, &[&digest_bits[..]], 2).unwrap();
+        let values: Vec<Fr> = inputs.iter().map(|x| x.get_value().unwrap()).collect();
+        assert_eq!(values, compute_packed_inputs_from_bytes_be::<Bn256>(&[&digest[..]]));
+
+        let mut cs = Assembly::new();
+        let amount_bits = alloc_bits(&mut cs, bytes_to_bits_le(&amount.to_le_bytes()));
+        let inputs = pack_into_inputs(&mut cs, &[&amount_bits[..], &amount_bits[..]], 1).unwrap();
+        let values: Vec<Fr> = inputs.iter().map(|x| x.get_value().unwrap()).collect();
+        assert_eq!(values, compute_packed_inputs_from_bytes_le::<Bn256>(&[&amount.to_le_bytes()[..], &amount.to_le_bytes()[..]]));
+        assert!(max_packed_bits::<Bn256>(1) >= 128);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_pack_into_too_few_inputs() {
+        let mut cs = Assembly::new();
+        let bits = vec![Boolean::constant(false); Fr::CAPACITY as usize + 1];
+        pack_into_inputs(&mut cs, &[&bits[..]], 1).unwrap();
+    }
+
+    #[test]
+    fn test_byte_packing() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        for &num_bytes in [1, 31, 32, 75].iter() {
+            let bytes: Vec<u8> = (0..num_bytes).map(|_| rng.gen()).collect();
+            let expected = compute_byte_packing::<Bn256>(&bytes);
+            assert_eq!(compute_byte_unpacking::<Bn256>(&expected, num_bytes), Some(bytes.clone()));
+
+            let mut cs = Assembly::new();
+            let circuit_bytes: Vec<Byte<Bn256>> = bytes.iter().map(|b| Byte::from_u8_witness(&mut cs, Some(*b)).unwrap()).collect();
+            let inputs = pack_bytes_into_inputs(&mut cs, &circuit_bytes).unwrap();
+            assert_eq!(inputs.iter().map(|x| x.get_value().unwrap()).collect::<Vec<_>>(), expected);
+
+            let unpacked = unpack_inputs_into_bytes(&mut cs, &inputs, num_bytes).unwrap();
+            assert!(cs.is_satisfied());
+            assert_eq!(unpacked.iter().map(|b| b.get_byte_value().unwrap()).collect::<Vec<_>>(), bytes);
+        }
+
+        // an input holding more bytes than announced
+        let inputs = compute_byte_packing::<Bn256>(&[1, 2, 3]);
+        assert_eq!(compute_byte_unpacking::<Bn256>(&inputs, 2), None);
+        let mut cs = Assembly::new();
+        let input = AllocatedNum::alloc(&mut cs, || Ok(inputs[0])).unwrap();
+        unpack_into_bytes(&mut cs, &input, 2).unwrap();
+        assert!(!cs.is_satisfied());
+    }
+}
@@ -0,0 +1,160 @@
+use crate::bellman::pairing::{
+    Engine,
+};
+
+use crate::bellman::{
+    SynthesisError,
+};
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    ConstraintSystem,
+};
+
+use super::allocated_num::{AllocatedNum, Num};
+use super::boolean::Boolean;
+use super::edwards::CircuitTwistedEdwardsPoint;
+
+// Index bits are LSB first: at level i pairs of the current layer are merged by
+// a select on index_bits[i], so element j of the input ends up as the result iff
+// the index encodes j. Selecting one of 2^k elements costs 2^k - 1 selects.
+fn select_by_tree<E, CS, T, F>(
+    cs: &mut CS,
+    index_bits: &[Boolean],
+    values: &[T],
+    select: F
+) -> Result<T, SynthesisError>
+    where E: Engine,
+          CS: ConstraintSystem<E>,
+          T: Clone,
+          F: Fn(&mut CS, &Boolean, &T, &T) -> Result<T, SynthesisError>
+{
+    assert!(index_bits.len() < std::mem::size_of::<usize>() * 8);
+    assert_eq!(values.len(), 1usize << index_bits.len(), "number of values must be 2^(number of index bits)");
+
+    let mut layer = values.to_vec();
+    for bit in index_bits.iter() {
+        let mut next_layer = Vec::with_capacity(layer.len() / 2);
+        for pair in layer.chunks(2) {
+            // bit set means the odd (second) element
+            next_layer.push(select(cs, bit, &pair[1], &pair[0])?);
+        }
+        layer = next_layer;
+    }
+
+    debug_assert_eq!(layer.len(), 1);
+
+    Ok(layer.pop().unwrap())
+}
+
+/// Selects `values[index]` where `index` is given by LSB first `index_bits`.
+/// `values` must contain exactly 2^k elements for k index bits.
+pub fn mux<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    index_bits: &[Boolean],
+    values: &[AllocatedNum<E>]
+) -> Result<AllocatedNum<E>, SynthesisError> {
+    select_by_tree(cs, index_bits, values, |cs, flag, a, b| {
+        AllocatedNum::conditionally_select(cs, a, b, flag)
+    })
+}
+
+/// Same as `mux`, but over `Num`, so constant tables are selected
+/// without allocating their entries first.
+pub fn mux_num<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    index_bits: &[Boolean],
+    values: &[Num<E>]
+) -> Result<Num<E>, SynthesisError> {
+    select_by_tree(cs, index_bits, values, |cs, flag, a, b| {
+        Num::conditionally_select(cs, flag, a, b)
+    })
+}
+
+/// Selects one of 2^k twisted Edwards points by LSB first `index_bits`.
+pub fn mux_point<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    index_bits: &[Boolean],
+    points: &[CircuitTwistedEdwardsPoint<E>]
+) -> Result<CircuitTwistedEdwardsPoint<E>, SynthesisError> {
+    select_by_tree(cs, index_bits, points, |cs, flag, a, b| {
+        CircuitTwistedEdwardsPoint::conditionally_select(cs, flag, a, b)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use crate::bellman::plonk::better_better_cs::cs::*;
+    use crate::alt_babyjubjub::AltJubjubBn256;
+    use crate::jubjub::{FixedGenerators, JubjubParams};
+    use crate::plonk::circuit::utils::u64_to_fe;
+
+    #[test]
+    fn test_mux() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        for num_bits in 0..5 {
+            let witness: Vec<Fr> = (0..(1 << num_bits)).map(|_| rng.gen()).collect();
+            for index in 0..(1usize << num_bits) {
+                let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+                let values: Vec<_> = witness.iter().map(|w| AllocatedNum::alloc(&mut cs, || Ok(*w)).unwrap()).collect();
+                let index_bits: Vec<_> = (0..num_bits).map(|i| Boolean::alloc(&mut cs, Some((index >> i) & 1 == 1)).unwrap()).collect();
+
+                let selected = mux(&mut cs, &index_bits, &values).unwrap();
+
+                assert!(cs.is_satisfied());
+                assert_eq!(selected.get_value().unwrap(), witness[index]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_mux_num_over_constant_table() {
+        let table: Vec<Num<Bn256>> = (0..8u64).map(|i| Num::Constant(u64_to_fe::<Fr>(i * i + 1))).collect();
+
+        for index in 0..8usize {
+            let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+            let index_bits: Vec<_> = (0..3).map(|i| Boolean::alloc(&mut cs, Some((index >> i) & 1 == 1)).unwrap()).collect();
+
+            let selected = mux_num(&mut cs, &index_bits, &table).unwrap();
+
+            assert!(cs.is_satisfied());
+            assert_eq!(selected.get_value().unwrap(), u64_to_fe::<Fr>((index * index + 1) as u64));
+        }
+    }
+
+    fn point<CS: ConstraintSystem<Bn256>>(cs: &mut CS, x: Fr, y: Fr, allocated: bool) -> CircuitTwistedEdwardsPoint<Bn256> {
+        if allocated {
+            CircuitTwistedEdwardsPoint {
+                x: Num::alloc(cs, Some(x)).unwrap(),
+                y: Num::alloc(cs, Some(y)).unwrap(),
+            }
+        } else {
+            CircuitTwistedEdwardsPoint::constant(x, y)
+        }
+    }
+
+    #[test]
+    fn test_mux_point() {
+        let params = AltJubjubBn256::new();
+        let (p_x, p_y) = params.generator(FixedGenerators::SpendingKeyGenerator).into_xy();
+        let (q_x, q_y) = params.generator(FixedGenerators::ProofGenerationKey).into_xy();
+
+        // constant and allocated points in both positions, for both values of the selector
+        for &allocated in [(false, false), (false, true), (true, false), (true, true)].iter() {
+            for &bit in [false, true].iter() {
+                let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+                let points = vec![point(&mut cs, p_x, p_y, allocated.0), point(&mut cs, q_x, q_y, allocated.1)];
+                let index_bits = vec![Boolean::alloc(&mut cs, Some(bit)).unwrap()];
+
+                let selected = mux_point(&mut cs, &index_bits, &points).unwrap();
+
+                assert!(cs.is_satisfied());
+                let expected = if bit { (q_x, q_y) } else { (p_x, p_y) };
+                assert_eq!((selected.x.get_value().unwrap(), selected.y.get_value().unwrap()), expected);
+            }
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use crate::bellman::{
+    SynthesisError,
+};
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    ConstraintSystem,
+};
+
+use super::allocated_num::Num;
+use super::boolean::Boolean;
+use super::ecdh::point_into_bits;
+use super::edwards::{CircuitTwistedEdwardsCurveImplementor, CircuitTwistedEdwardsPoint};
+use super::pedersen_hash::pedersen_hash_point;
+
+use crate::generic_twisted_edwards::TwistedEdwardsCurveParams;
+use crate::jubjub::{FixedGenerators, JubjubEngine, JubjubParams};
+use crate::pedersen_hash::Personalization;
+
+// Note commitment matching `crate::primitives::Note::cm`:
+//
+//      cm = PedersenHash(NoteCommitment, value || g_d || pk_d) + r . G_NoteCommitmentRandomness
+//
+// with the value in 64 little endian bits and the points in the encoding of `Point::write`.
+
+pub const NOTE_VALUE_BITS: usize = 64;
+
+#[derive(Clone, Debug)]
+pub struct CircuitNoteCommitment<E: JubjubEngine> {
+    pub point: CircuitTwistedEdwardsPoint<E>,
+    /// The x coordinate of `point`, which is what gets stored in the tree. The commitment is in
+    /// the prime order subgroup, where x determines the point.
+    pub cm: Num<E>,
+}
+
+/// Commits to a note of `value_bits` (64, little endian) for the address (`g_d`, `pk_d`) with
+/// randomness `r` in little endian bits. The points are expected to be on the curve.
+pub fn note_commitment<E, CS, C>(
+    cs: &mut CS,
+    curve: &CircuitTwistedEdwardsCurveImplementor<E, C>,
+    value_bits: &[Boolean],
+    g_d: &CircuitTwistedEdwardsPoint<E>,
+    pk_d: &CircuitTwistedEdwardsPoint<E>,
+    r: &[Boolean],
+    params: &E::Params
+) -> Result<CircuitNoteCommitment<E>, SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>,
+          C: TwistedEdwardsCurveParams<E>
+{
+    assert_eq!(value_bits.len(), NOTE_VALUE_BITS);
+
+    let mut contents = value_bits.to_vec();
+    contents.extend(point_into_bits(cs, g_d)?);
+    contents.extend(point_into_bits(cs, pk_d)?);
+
+    let hash = pedersen_hash_point(cs, curve, Personalization::NoteCommitment, &contents, params)?;
+    let (x, y) = params.generator(FixedGenerators::NoteCommitmentRandomness).into_xy();
+    let blinding = curve.mul(cs, &CircuitTwistedEdwardsPoint::constant(x, y), r)?;
+    let point = curve.add(cs, &hash, &blinding)?;
+
+    Ok(CircuitNoteCommitment { cm: point.x, point })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use bellman::pairing::bn256::Bn256;
+    use crate::bellman::plonk::better_better_cs::cs::*;
+    use crate::alt_babyjubjub::{AltJubjubBn256, fs::Fs, edwards};
+    use crate::generic_twisted_edwards::TwistedEdwardsPoint;
+    use crate::jubjub::PrimeOrder;
+    use crate::plonk::circuit::edwards::bn256::CircuitAltBabyJubjubBn256;
+    use crate::plonk::circuit::utils::fe_to_lsb_first_bits;
+    use crate::primitives::Note;
+
+    #[test]
+    fn test_note_commitment_in_circuit() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = AltJubjubBn256::new();
+        let curve = CircuitAltBabyJubjubBn256::get_implementor();
+
+        let g_d: edwards::Point<Bn256, PrimeOrder> = params.generator(FixedGenerators::SpendingKeyGenerator).mul(rng.gen::<Fs>(), &params);
+        let note = Note {
+            value: rng.gen(),
+            pk_d: g_d.mul(rng.gen::<Fs>(), &params),
+            g_d,
+            r: rng.gen(),
+        };
+
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+        let value_bits: Vec<Boolean> = (0..NOTE_VALUE_BITS)
+            .map(|i| Boolean::alloc(&mut cs, Some((note.value >> i) & 1 == 1)).unwrap())
+            .collect();
+        let mut alloc_point = |p: &edwards::Point<Bn256, PrimeOrder>| {
+            let (x, y) = p.into_xy();
+            curve.alloc_point_enforce_on_curve(&mut cs, Some(TwistedEdwardsPoint::from_xy(x, y))).unwrap()
+        };
+        let g_d = alloc_point(&note.g_d);
+        let pk_d = alloc_point(&note.pk_d);
+        let r: Vec<Boolean> = fe_to_lsb_first_bits(&note.r).into_iter()
+            .map(|b| Boolean::alloc(&mut cs, Some(b)).unwrap())
+            .collect();
+
+        let commitment = note_commitment(&mut cs, &curve, &value_bits, &g_d, &pk_d, &r, &params).unwrap();
+        assert!(cs.is_satisfied());
+
+        let (x, y) = note.cm_full_point(&params).into_xy();
+        assert_eq!(commitment.cm.get_value().unwrap(), note.cm(&params));
+        assert_eq!(commitment.point.x.get_value().unwrap(), x);
+        assert_eq!(commitment.point.y.get_value().unwrap(), y);
+    }
+}
@@ -0,0 +1,166 @@
+use crate::bellman::pairing::Engine;
+
+use crate::bellman::{
+    SynthesisError,
+};
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    ConstraintSystem,
+};
+
+use super::boolean::Boolean;
+use super::blake2s::blake2s;
+use super::ecdh::{CircuitKdf, derive_shared_key};
+use super::edwards::{CircuitTwistedEdwardsCurveImplementor, CircuitTwistedEdwardsPoint};
+
+use crate::generic_twisted_edwards::TwistedEdwardsCurveParams;
+use crate::jubjub::{FixedGenerators, JubjubEngine, JubjubParams};
+use crate::note_encryption::{KEYSTREAM_BLOCK_BYTES, NOTE_KEYSTREAM_PERSONALIZATION};
+
+// Note encryption matching `crate::note_encryption`. With the ephemeral key and the ciphertext
+// as public inputs `enforce_note_encryption` proves that the recipient will decrypt exactly the
+// plaintext the rest of the circuit commits to, so senders can't hand out garbage ciphertexts.
+
+/// XORs `data` with the keystream for the 256 bit `key`, bits of every byte least significant
+/// first.
+pub fn apply_keystream<E, CS>(
+    cs: &mut CS,
+    key: &[Boolean],
+    data: &[Boolean]
+) -> Result<Vec<Boolean>, SynthesisError>
+    where E: Engine,
+          CS: ConstraintSystem<E>
+{
+    assert_eq!(key.len(), 256);
+
+    let mut result = Vec::with_capacity(data.len());
+    for (i, chunk) in data.chunks(KEYSTREAM_BLOCK_BYTES * 8).enumerate() {
+        let mut input = key.to_vec();
+        input.extend((0..32).map(|j| Boolean::constant((i >> j) & 1 == 1)));
+        let block = blake2s(cs, &input, NOTE_KEYSTREAM_PERSONALIZATION)?;
+
+        for (a, b) in chunk.iter().zip(block.iter()) {
+            result.push(Boolean::xor(cs, a, b)?);
+        }
+    }
+
+    Ok(result)
+}
+
+/// The ephemeral key esk . P_G and the encryption of `plaintext` to `pk_recipient`, `esk` in
+/// little endian bits.
+pub fn encrypt_note<E, CS, C, K>(
+    cs: &mut CS,
+    curve: &CircuitTwistedEdwardsCurveImplementor<E, C>,
+    kdf: &K,
+    esk: &[Boolean],
+    pk_recipient: &CircuitTwistedEdwardsPoint<E>,
+    plaintext: &[Boolean],
+    p_g: FixedGenerators,
+    params: &E::Params
+) -> Result<(CircuitTwistedEdwardsPoint<E>, Vec<Boolean>), SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>,
+          C: TwistedEdwardsCurveParams<E>,
+          K: CircuitKdf<E>
+{
+    let (x, y) = params.generator(p_g).into_xy();
+    let generator = CircuitTwistedEdwardsPoint::constant(x, y);
+    let epk = curve.mul(cs, &generator, esk)?;
+
+    let key = derive_shared_key(cs, curve, kdf, esk, pk_recipient, &epk)?;
+    let ciphertext = apply_keystream(cs, &key, plaintext)?;
+
+    Ok((epk, ciphertext))
+}
+
+/// Enforces that `epk` and `ciphertext` are the encryption of `plaintext` to `pk_recipient`
+/// with the ephemeral secret `esk`.
+pub fn enforce_note_encryption<E, CS, C, K>(
+    cs: &mut CS,
+    curve: &CircuitTwistedEdwardsCurveImplementor<E, C>,
+    kdf: &K,
+    esk: &[Boolean],
+    pk_recipient: &CircuitTwistedEdwardsPoint<E>,
+    plaintext: &[Boolean],
+    epk: &CircuitTwistedEdwardsPoint<E>,
+    ciphertext: &[Boolean],
+    p_g: FixedGenerators,
+    params: &E::Params
+) -> Result<(), SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>,
+          C: TwistedEdwardsCurveParams<E>,
+          K: CircuitKdf<E>
+{
+    assert_eq!(plaintext.len(), ciphertext.len());
+
+    let (computed_epk, computed_ciphertext) = encrypt_note(cs, curve, kdf, esk, pk_recipient, plaintext, p_g, params)?;
+    computed_epk.x.enforce_equal(cs, &epk.x)?;
+    computed_epk.y.enforce_equal(cs, &epk.y)?;
+    for (a, b) in computed_ciphertext.iter().zip(ciphertext.iter()) {
+        Boolean::enforce_equal(cs, a, b)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use bellman::pairing::bn256::Bn256;
+    use crate::bellman::plonk::better_better_cs::cs::*;
+    use crate::alt_babyjubjub::{AltJubjubBn256, fs::Fs, edwards};
+    use crate::ecdh::Blake2sKdf;
+    use crate::jubjub::Unknown;
+    use crate::generic_twisted_edwards::TwistedEdwardsPoint;
+    use crate::plonk::circuit::ecdh::CircuitBlake2sKdf;
+    use crate::plonk::circuit::edwards::bn256::CircuitAltBabyJubjubBn256;
+    use crate::plonk::circuit::utils::fe_to_lsb_first_bits;
+
+    fn alloc_bytes<CS: ConstraintSystem<Bn256>>(cs: &mut CS, bytes: &[u8]) -> Vec<Boolean> {
+        bytes.iter()
+            .flat_map(|&byte| (0..8).map(move |i| (byte >> i) & 1u8 == 1u8))
+            .map(|b| Boolean::alloc(cs, Some(b)).unwrap())
+            .collect()
+    }
+
+    fn run(tamper: bool) -> bool {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = AltJubjubBn256::new();
+        let curve = CircuitAltBabyJubjubBn256::get_implementor();
+        let p_g = FixedGenerators::SpendingKeyGenerator;
+
+        let sk: Fs = rng.gen();
+        let pk: edwards::Point<Bn256, Unknown> = params.generator(p_g).mul(sk, &params).into();
+        let esk: Fs = rng.gen();
+        let plaintext: Vec<u8> = (0..40).map(|_| rng.gen()).collect();
+        let (epk, mut ciphertext) = crate::note_encryption::encrypt_note(&Blake2sKdf::default(), esk, &pk, &plaintext, p_g, &params);
+        if tamper {
+            ciphertext[35] ^= 1;
+        }
+
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+        let (pk_x, pk_y) = pk.into_xy();
+        let pk_var = curve.alloc_point_enforce_on_curve(&mut cs, Some(TwistedEdwardsPoint::from_xy(pk_x, pk_y))).unwrap();
+        let (epk_x, epk_y) = epk.into_xy();
+        let epk_var = curve.alloc_point_enforce_on_curve(&mut cs, Some(TwistedEdwardsPoint::from_xy(epk_x, epk_y))).unwrap();
+        let esk_bits: Vec<Boolean> = fe_to_lsb_first_bits(&esk).into_iter().map(|b| Boolean::alloc(&mut cs, Some(b)).unwrap()).collect();
+        let plaintext_bits = alloc_bytes(&mut cs, &plaintext);
+        let ciphertext_bits = alloc_bytes(&mut cs, &ciphertext);
+
+        enforce_note_encryption(
+            &mut cs, &curve, &CircuitBlake2sKdf::default(), &esk_bits, &pk_var, &plaintext_bits,
+            &epk_var, &ciphertext_bits, p_g, &params
+        ).unwrap();
+
+        cs.is_satisfied()
+    }
+
+    #[test]
+    fn test_note_encryption_in_circuit() {
+        assert!(run(false));
+        assert!(!run(true));
+    }
+}
@@ -0,0 +1,108 @@
+use crate::bellman::{
+    SynthesisError,
+};
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    ConstraintSystem,
+};
+
+use super::blake2s::blake2s;
+use super::boolean::Boolean;
+use super::ecdh::point_into_bits;
+use super::edwards::{CircuitTwistedEdwardsCurveImplementor, CircuitTwistedEdwardsPoint};
+
+use crate::constants::PRF_NF_PERSONALIZATION;
+use crate::generic_twisted_edwards::TwistedEdwardsCurveParams;
+use crate::jubjub::{FixedGenerators, JubjubEngine, JubjubParams};
+
+// Nullifier matching `crate::primitives::Note::nf`:
+//
+//      rho = cm + position . G_NullifierPosition
+//      nf  = BLAKE2s-256(PRF_NF_PERSONALIZATION, nk || rho)
+//
+// The input of BLAKE2s is 512 bits: the 256 bit encodings of `Point::write` of the nullifier
+// key nk and of rho, bytes with the least significant bit first. The output is laid out the
+// same way, bit i of byte j of the native nullifier is bit 8 * j + i of the result.
+
+pub const NOTE_POSITION_BITS: usize = 64;
+
+/// The nullifier of the note with commitment point `cm` (see `note_commitment`) at `position`
+/// (64 little endian bits) of the tree, under the nullifier key `nk` of the viewing key. The
+/// caller binds `nk` to the spend authority, e.g. by deriving it from nsk in the circuit.
+pub fn nullifier<E, CS, C>(
+    cs: &mut CS,
+    curve: &CircuitTwistedEdwardsCurveImplementor<E, C>,
+    nk: &CircuitTwistedEdwardsPoint<E>,
+    cm: &CircuitTwistedEdwardsPoint<E>,
+    position: &[Boolean],
+    params: &E::Params
+) -> Result<Vec<Boolean>, SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>,
+          C: TwistedEdwardsCurveParams<E>
+{
+    assert_eq!(position.len(), NOTE_POSITION_BITS);
+
+    let (x, y) = params.generator(FixedGenerators::NullifierPosition).into_xy();
+    let position_point = curve.mul(cs, &CircuitTwistedEdwardsPoint::constant(x, y), position)?;
+    let rho = curve.add(cs, cm, &position_point)?;
+
+    let mut preimage = point_into_bits(cs, nk)?;
+    preimage.extend(point_into_bits(cs, &rho)?);
+
+    blake2s(cs, &preimage, PRF_NF_PERSONALIZATION)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use bellman::pairing::bn256::Bn256;
+    use crate::bellman::plonk::better_better_cs::cs::*;
+    use crate::alt_babyjubjub::{AltJubjubBn256, fs::Fs, edwards};
+    use crate::generic_twisted_edwards::TwistedEdwardsPoint;
+    use crate::jubjub::PrimeOrder;
+    use crate::plonk::circuit::edwards::bn256::CircuitAltBabyJubjubBn256;
+    use crate::primitives::{Note, ProofGenerationKey};
+
+    #[test]
+    fn test_nullifier_in_circuit() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = AltJubjubBn256::new();
+        let curve = CircuitAltBabyJubjubBn256::get_implementor();
+
+        let proof_generation_key = ProofGenerationKey::<Bn256> {
+            ak: params.generator(FixedGenerators::SpendingKeyGenerator).mul(rng.gen::<Fs>(), &params),
+            nsk: rng.gen(),
+        };
+        let viewing_key = proof_generation_key.into_viewing_key(&params);
+        let g_d: edwards::Point<Bn256, PrimeOrder> = params.generator(FixedGenerators::SpendingKeyGenerator).mul(rng.gen::<Fs>(), &params);
+        let note = Note {
+            value: rng.gen(),
+            pk_d: g_d.mul(rng.gen::<Fs>(), &params),
+            g_d,
+            r: rng.gen(),
+        };
+        let position: u64 = rng.gen();
+
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+        let mut alloc_point = |p: &edwards::Point<Bn256, PrimeOrder>| {
+            let (x, y) = p.into_xy();
+            curve.alloc_point_enforce_on_curve(&mut cs, Some(TwistedEdwardsPoint::from_xy(x, y))).unwrap()
+        };
+        let nk = alloc_point(&viewing_key.nk);
+        let cm = alloc_point(&note.cm_full_point(&params));
+        let position_bits: Vec<Boolean> = (0..NOTE_POSITION_BITS)
+            .map(|i| Boolean::alloc(&mut cs, Some((position >> i) & 1 == 1)).unwrap())
+            .collect();
+
+        let nf = nullifier(&mut cs, &curve, &nk, &cm, &position_bits, &params).unwrap();
+        assert!(cs.is_satisfied());
+
+        let expected = note.nf(&viewing_key, position, &params);
+        let actual: Vec<u8> = nf.chunks(8)
+            .map(|byte| byte.iter().enumerate().fold(0u8, |acc, (i, b)| acc | ((b.get_value().unwrap() as u8) << i)))
+            .collect();
+        assert_eq!(actual, expected);
+    }
+}
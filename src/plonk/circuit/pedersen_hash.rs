@@ -0,0 +1,470 @@
+use crate::bellman::pairing::{
+    Engine,
+};
+
+use crate::bellman::pairing::bn256::Bn256;
+
+use crate::bellman::pairing::ff::{
+    Field,
+};
+
+use crate::bellman::{
+    SynthesisError,
+};
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    ConstraintSystem,
+};
+
+use super::allocated_num::Num;
+use super::boolean::Boolean;
+use super::linear_combination::LinearCombination;
+use super::edwards::{CircuitTwistedEdwardsCurveImplementor, CircuitTwistedEdwardsPoint};
+use super::utils::u64_to_fe;
+
+use crate::generic_twisted_edwards::TwistedEdwardsCurveParams;
+use crate::generic_twisted_edwards::bn256::AltBabyJubjubParams;
+use crate::circomlib_pedersen_hash::{
+    CircomlibPedersenParams,
+    CIRCOMLIB_PEDERSEN_WINDOW_SIZE,
+    CIRCOMLIB_PEDERSEN_WINDOWS_PER_SEGMENT
+};
+use crate::jubjub::{JubjubEngine, JubjubParams, PrimeOrder, edwards};
+use crate::pedersen_hash::Personalization;
+
+// Pedersen hash gadget matching `crate::pedersen_hash::pedersen_hash`, where all the
+// accumulation is done with complete twisted Edwards addition. Nothing has to be proven
+// about the intermediate points: there are no exceptional cases on segment boundaries,
+// so any input length (and any custom personalization) is handled by the same argument.
+//
+// Every 3 bit chunk (a, b, c) selects (1 + a + 2b) * 16^j * G from 4 constant points
+// (one AND and two linear combinations), negates x if c is set (2 gates) and is added to
+// the accumulator with the complete formula (around a dozen gates). This is a few gates
+// per chunk more than the Montgomery accumulation with a switch to Edwards per segment.
+
+// (1 + i) * base for i = 0..3 in affine coordinates
+fn window_points<E: JubjubEngine>(
+    base: &edwards::Point<E, PrimeOrder>,
+    params: &E::Params
+) -> Vec<(E::Fr, E::Fr)> {
+    let mut points = Vec::with_capacity(4);
+    let mut p = base.clone();
+    for _ in 0..4 {
+        points.push(p.into_xy());
+        p = p.add(base, params);
+    }
+
+    points
+}
+
+// products of all the subsets of `bits` indexed by the subset mask, the first one is empty
+fn subset_products<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    bits: &[Boolean]
+) -> Result<Vec<Boolean>, SynthesisError> {
+    let mut products = vec![Boolean::constant(true)];
+    for (i, bit) in bits.iter().enumerate() {
+        for mask in 0..(1 << i) {
+            let product = if mask == 0 {
+                *bit
+            } else {
+                Boolean::and(cs, &products[mask], bit)?
+            };
+            products.push(product);
+        }
+    }
+
+    Ok(products)
+}
+
+// multilinear polynomial taking values[j] when the bits encode j
+fn select_coordinate<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    values: &[E::Fr],
+    products: &[Boolean]
+) -> Result<Num<E>, SynthesisError> {
+    assert_eq!(values.len(), products.len());
+
+    let mut coeffs = values.to_vec();
+    let mut step = 1;
+    while step < coeffs.len() {
+        for mask in 0..coeffs.len() {
+            if mask & step != 0 {
+                let tmp = coeffs[mask ^ step];
+                coeffs[mask].sub_assign(&tmp);
+            }
+        }
+        step <<= 1;
+    }
+
+    let mut lc = LinearCombination::zero();
+    lc.add_assign_constant(coeffs[0]);
+    for (product, coeff) in products.iter().zip(coeffs.into_iter()).skip(1) {
+        lc.add_assign_boolean_with_coeff(product, coeff);
+    }
+
+    lc.into_num(cs)
+}
+
+// selects points[j] where `bits` encode j and negates it if `sign` is set
+fn lookup_window<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    points: &[(E::Fr, E::Fr)],
+    bits: &[Boolean],
+    sign: &Boolean
+) -> Result<CircuitTwistedEdwardsPoint<E>, SynthesisError> {
+    assert_eq!(points.len(), 1 << bits.len());
+
+    let products = subset_products(cs, bits)?;
+
+    let xs: Vec<E::Fr> = points.iter().map(|p| p.0).collect();
+    let ys: Vec<E::Fr> = points.iter().map(|p| p.1).collect();
+    let x = select_coordinate(cs, &xs, &products)?;
+    let y = select_coordinate(cs, &ys, &products)?;
+
+    // -(x, y) = (-x, y)
+    let mut minus_two = E::Fr::one();
+    minus_two.double();
+    minus_two.negate();
+
+    let mut multiplier = LinearCombination::zero();
+    multiplier.add_assign_constant(E::Fr::one());
+    multiplier.add_assign_boolean_with_coeff(sign, minus_two);
+    let multiplier = multiplier.into_num(cs)?;
+
+    let x = x.mul(cs, &multiplier)?;
+
+    Ok(CircuitTwistedEdwardsPoint { x, y })
+}
+
+/// Pedersen hash of `bits` under `personalization` into a curve point, matches
+/// `crate::pedersen_hash::pedersen_hash`. `curve` has to describe the same curve as `params`.
+/// The point can be used further, e.g. blinded into a commitment or accumulated with other points.
+pub fn pedersen_hash_point<E, CS, C>(
+    cs: &mut CS,
+    curve: &CircuitTwistedEdwardsCurveImplementor<E, C>,
+    personalization: Personalization,
+    bits: &[Boolean],
+    params: &E::Params
+) -> Result<CircuitTwistedEdwardsPoint<E>, SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>,
+          C: TwistedEdwardsCurveParams<E>
+{
+    let personalization: Vec<Boolean> = personalization.get_bits().into_iter().map(|b| Boolean::constant(b)).collect();
+    let all_bits: Vec<Boolean> = personalization.into_iter().chain(bits.iter().cloned()).collect();
+
+    let chunks_per_generator = params.pedersen_hash_chunks_per_generator();
+    let mut generators = params.pedersen_hash_generators().iter();
+
+    let mut result: Option<CircuitTwistedEdwardsPoint<E>> = None;
+
+    for segment in all_bits.chunks(3 * chunks_per_generator) {
+        let mut base = generators.next().expect("we don't have enough generators").clone();
+
+        for chunk in segment.chunks(3) {
+            let a = chunk[0];
+            let b = chunk.get(1).cloned().unwrap_or(Boolean::constant(false));
+            let c = chunk.get(2).cloned().unwrap_or(Boolean::constant(false));
+
+            let points = window_points::<E>(&base, params);
+            let point = lookup_window(cs, &points, &[a, b], &c)?;
+
+            result = match result {
+                None => Some(point),
+                Some(acc) => Some(curve.add(cs, &acc, &point)?),
+            };
+
+            // 16 * base for the next chunk
+            for _ in 0..4 {
+                base = base.double(params);
+            }
+        }
+    }
+
+    Ok(result.expect("personalization is never empty"))
+}
+
+/// Pedersen hash of `bits` under `personalization`, the x coordinate of `pedersen_hash_point`.
+pub fn pedersen_hash<E, CS, C>(
+    cs: &mut CS,
+    curve: &CircuitTwistedEdwardsCurveImplementor<E, C>,
+    personalization: Personalization,
+    bits: &[Boolean],
+    params: &E::Params
+) -> Result<Num<E>, SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>,
+          C: TwistedEdwardsCurveParams<E>
+{
+    let point = pedersen_hash_point(cs, curve, personalization, bits, params)?;
+
+    Ok(point.x)
+}
+
+/// Pedersen hash of the first `length` bits of `input`, where `length` is a witness not exceeding
+/// the length of `input`, matches `crate::pedersen_hash::pedersen_hash_variable_length` with the
+/// maximal length of `input.len()`. The message is padded in-circuit with a single `1` bit and
+/// zeroes, everything after the first `length` bits is ignored. The cost is the one of hashing
+/// `input.len() + 1` bits plus a few gates per bit of `input` for the padding.
+pub fn pedersen_hash_variable_length_point<E, CS, C>(
+    cs: &mut CS,
+    curve: &CircuitTwistedEdwardsCurveImplementor<E, C>,
+    personalization: Personalization,
+    input: &[Boolean],
+    length: &Num<E>,
+    params: &E::Params
+) -> Result<CircuitTwistedEdwardsPoint<E>, SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>,
+          C: TwistedEdwardsCurveParams<E>
+{
+    let max_length = input.len();
+
+    // is_end[j] is set iff length == j, exactly one of them is set
+    let mut is_end = Vec::with_capacity(max_length + 1);
+    let mut lc = LinearCombination::zero();
+    for j in 0..=max_length {
+        let flag = Num::equals(cs, length, &Num::Constant(u64_to_fe(j as u64)))?;
+        lc.add_assign_boolean_with_coeff(&flag, E::Fr::one());
+        is_end.push(flag);
+    }
+    let mut minus_one = E::Fr::one();
+    minus_one.negate();
+    lc.add_assign_constant(minus_one);
+    lc.enforce_zero(cs)?;
+
+    let mut padded = Vec::with_capacity(max_length + 1);
+    let mut is_message = Boolean::constant(true);
+    for i in 0..=max_length {
+        is_message = Boolean::and(cs, &is_message, &is_end[i].not())?;
+
+        let bit = if i < max_length {
+            Boolean::and(cs, &is_message, &input[i])?
+        } else {
+            Boolean::constant(false)
+        };
+
+        // a single '1' bit right after the message
+        padded.push(Boolean::xor(cs, &bit, &is_end[i])?);
+    }
+
+    pedersen_hash_point(cs, curve, personalization, &padded, params)
+}
+
+/// The x coordinate of `pedersen_hash_variable_length_point`.
+pub fn pedersen_hash_variable_length<E, CS, C>(
+    cs: &mut CS,
+    curve: &CircuitTwistedEdwardsCurveImplementor<E, C>,
+    personalization: Personalization,
+    input: &[Boolean],
+    length: &Num<E>,
+    params: &E::Params
+) -> Result<Num<E>, SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>,
+          C: TwistedEdwardsCurveParams<E>
+{
+    let point = pedersen_hash_variable_length_point(cs, curve, personalization, input, length, params)?;
+
+    Ok(point.x)
+}
+
+/// Pedersen hash of `bits` compatible with circomlib `pedersen.circom`, see `crate::circomlib_pedersen_hash`.
+/// The point is in the a = -1 form of the curve. Every 4 bit window costs a selection
+/// out of 8 constant points (4 ANDs and two linear combinations), a conditional negation
+/// and a complete addition.
+pub fn circomlib_pedersen_hash_point<CS: ConstraintSystem<Bn256>>(
+    cs: &mut CS,
+    curve: &CircuitTwistedEdwardsCurveImplementor<Bn256, AltBabyJubjubParams>,
+    params: &CircomlibPedersenParams,
+    bits: &[Boolean]
+) -> Result<CircuitTwistedEdwardsPoint<Bn256>, SynthesisError> {
+    let native_curve = &curve.implementor;
+    let bits_per_segment = CIRCOMLIB_PEDERSEN_WINDOW_SIZE * CIRCOMLIB_PEDERSEN_WINDOWS_PER_SEGMENT;
+    let mut generators = params.generators.iter();
+
+    let mut result: Option<CircuitTwistedEdwardsPoint<Bn256>> = None;
+
+    for segment in bits.chunks(bits_per_segment) {
+        let mut base = *generators.next().expect("we don't have enough generators");
+
+        for window in segment.chunks(CIRCOMLIB_PEDERSEN_WINDOW_SIZE) {
+            let window_bits: Vec<Boolean> = (0..3).map(|i| window.get(i).cloned().unwrap_or(Boolean::constant(false))).collect();
+            let sign = window.get(3).cloned().unwrap_or(Boolean::constant(false));
+
+            // (1 + j) * base for j = 0..7
+            let mut points = Vec::with_capacity(8);
+            let mut p = base;
+            for _ in 0..8 {
+                points.push(p.into_xy());
+                p = native_curve.add(&p, &base);
+            }
+
+            let point = lookup_window(cs, &points, &window_bits, &sign)?;
+
+            result = match result {
+                None => Some(point),
+                Some(acc) => Some(curve.add(cs, &acc, &point)?),
+            };
+
+            // 32 * base for the next window
+            for _ in 0..(CIRCOMLIB_PEDERSEN_WINDOW_SIZE + 1) {
+                base = native_curve.double(&base);
+            }
+        }
+    }
+
+    Ok(result.unwrap_or(CircuitTwistedEdwardsPoint::zero()))
+}
+
+/// Pedersen hash of `bits` in circomlib coordinates, the output of `pedersen.circom`.
+pub fn circomlib_pedersen_hash<CS: ConstraintSystem<Bn256>>(
+    cs: &mut CS,
+    curve: &CircuitTwistedEdwardsCurveImplementor<Bn256, AltBabyJubjubParams>,
+    params: &CircomlibPedersenParams,
+    bits: &[Boolean]
+) -> Result<(Num<Bn256>, Num<Bn256>), SynthesisError> {
+    let point = circomlib_pedersen_hash_point(cs, curve, params, bits)?;
+
+    let mut lc = LinearCombination::zero();
+    lc.add_assign_number_with_coeff(&point.x, params.scale.inverse().unwrap());
+    let x = lc.into_num(cs)?;
+
+    Ok((x, point.y))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use bellman::pairing::bn256::Bn256;
+    use crate::bellman::plonk::better_better_cs::cs::*;
+    use crate::alt_babyjubjub::AltJubjubBn256;
+    use crate::plonk::circuit::edwards::bn256::CircuitAltBabyJubjubBn256;
+    use crate::pedersen_hash;
+    use crate::circomlib_pedersen_hash;
+    use crate::generic_twisted_edwards::bn256::AltBabyJubjubBn256;
+    use crate::generic_twisted_edwards::{TwistedEdwardsCurveParams, TwistedEdwardsPoint};
+
+    #[test]
+    fn test_pedersen_hash_gadget_matches_native() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = AltJubjubBn256::new();
+        let curve = CircuitAltBabyJubjubBn256::get_implementor();
+
+        // crosses a segment boundary and ends with an incomplete chunk, some bits are constant
+        for &(personalization, input_len) in [
+            (Personalization::NoteCommitment, 0),
+            (Personalization::MerkleTree(3), 298),
+            (Personalization::Custom(7), 400),
+        ].iter() {
+            let input: Vec<bool> = (0..input_len).map(|_| rng.gen()).collect();
+            let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+            let input_bits: Vec<Boolean> = input.iter().enumerate().map(|(i, b)| {
+                if i % 7 == 0 {
+                    Boolean::constant(*b)
+                } else {
+                    Boolean::alloc(&mut cs, Some(*b)).unwrap()
+                }
+            }).collect();
+
+            let hash = pedersen_hash_point(&mut cs, &curve, personalization, &input_bits, &params).unwrap();
+            let expected = pedersen_hash::pedersen_hash::<Bn256, _>(personalization, input.iter().cloned(), &params);
+            let (expected_x, expected_y) = expected.into_xy();
+
+            assert!(cs.is_satisfied());
+            assert_eq!(hash.x.get_value().unwrap(), expected_x);
+            assert_eq!(hash.y.get_value().unwrap(), expected_y);
+        }
+    }
+
+    #[test]
+    fn test_pedersen_hash_variable_length_gadget() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = AltJubjubBn256::new();
+        let curve = CircuitAltBabyJubjubBn256::get_implementor();
+        let personalization = Personalization::Custom(42);
+        let max_length = 100;
+
+        for &length in [0, 1, 57, max_length].iter() {
+            // the tail after `length` is garbage and must not affect the result
+            let input: Vec<bool> = (0..max_length).map(|_| rng.gen()).collect();
+            let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+            let input_bits: Vec<Boolean> = input.iter().map(|b| Boolean::alloc(&mut cs, Some(*b)).unwrap()).collect();
+            let length_num = Num::alloc(&mut cs, Some(u64_to_fe(length as u64))).unwrap();
+
+            let hash = pedersen_hash_variable_length_point(&mut cs, &curve, personalization, &input_bits, &length_num, &params).unwrap();
+            let expected = pedersen_hash::pedersen_hash_variable_length::<Bn256>(personalization, &input[..length], max_length, &params);
+            let (expected_x, expected_y) = expected.into_xy();
+
+            assert!(cs.is_satisfied());
+            assert_eq!(hash.x.get_value().unwrap(), expected_x);
+            assert_eq!(hash.y.get_value().unwrap(), expected_y);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_pedersen_hash_variable_length_out_of_range() {
+        let params = AltJubjubBn256::new();
+        let curve = CircuitAltBabyJubjubBn256::get_implementor();
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+        let input_bits: Vec<Boolean> = (0..10).map(|_| Boolean::alloc(&mut cs, Some(true)).unwrap()).collect();
+        let length_num = Num::alloc(&mut cs, Some(u64_to_fe(11))).unwrap();
+
+        pedersen_hash_variable_length(&mut cs, &curve, Personalization::Custom(42), &input_bits, &length_num, &params).unwrap();
+    }
+
+    #[test]
+    fn test_pedersen_hash_point_commitment() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = AltJubjubBn256::new();
+        let curve = CircuitAltBabyJubjubBn256::get_implementor();
+        let personalization = Personalization::NoteCommitment;
+
+        let input: Vec<bool> = (0..64).map(|_| rng.gen()).collect();
+        let randomness: Vec<bool> = (0..32).map(|_| rng.gen()).collect();
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+        let input_bits: Vec<Boolean> = input.iter().map(|b| Boolean::alloc(&mut cs, Some(*b)).unwrap()).collect();
+        let randomness_bits: Vec<Boolean> = randomness.iter().map(|b| Boolean::alloc(&mut cs, Some(*b)).unwrap()).collect();
+
+        // hash(input) + r * H
+        let point = pedersen_hash_point(&mut cs, &curve, personalization, &input_bits, &params).unwrap();
+        let x = pedersen_hash(&mut cs, &curve, personalization, &input_bits, &params).unwrap();
+        let blinding = curve.mul(&mut cs, &curve.generator(), &randomness_bits).unwrap();
+        let commitment = curve.add(&mut cs, &point, &blinding).unwrap();
+
+        let expected = pedersen_hash::pedersen_hash::<Bn256, _>(personalization, input.iter().cloned(), &params);
+        let native_curve = &curve.implementor;
+        let (expected_x, expected_y) = expected.into_xy();
+        let randomness_msb_first: Vec<bool> = randomness.iter().rev().cloned().collect();
+        let native_blinding = native_curve.mul_by_bits(&native_curve.get_params().generator(), &randomness_msb_first);
+        let expected_commitment = native_curve.add(&TwistedEdwardsPoint::from_xy(expected_x, expected_y), &native_blinding);
+
+        assert!(cs.is_satisfied());
+        assert_eq!(x.get_value().unwrap(), expected_x);
+        assert_eq!(point.y.get_value().unwrap(), expected_y);
+        assert_eq!((commitment.x.get_value().unwrap(), commitment.y.get_value().unwrap()), expected_commitment.into_xy());
+    }
+
+    #[test]
+    fn test_circomlib_pedersen_hash_gadget_matches_native() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let native_curve = AltBabyJubjubBn256::get_implementor();
+        let curve = CircuitAltBabyJubjubBn256::get_implementor();
+        let params = CircomlibPedersenParams::with_num_generators(&native_curve, 2);
+
+        // crosses a segment boundary and ends with an incomplete window
+        for &input_len in [0, 8, 254].iter() {
+            let input: Vec<bool> = (0..input_len).map(|_| rng.gen()).collect();
+            let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+            let input_bits: Vec<Boolean> = input.iter().map(|b| Boolean::alloc(&mut cs, Some(*b)).unwrap()).collect();
+
+            let (x, y) = circomlib_pedersen_hash(&mut cs, &curve, &params, &input_bits).unwrap();
+
+            assert!(cs.is_satisfied());
+            assert_eq!((x.get_value().unwrap(), y.get_value().unwrap()), circomlib_pedersen_hash::hash(&native_curve, &params, &input));
+        }
+    }
+}
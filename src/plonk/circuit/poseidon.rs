@@ -32,8 +32,8 @@ use super::linear_combination::{
     LinearCombination
 };
 
-use poseidon_hash::{
-    PoseidonEngine, PoseidonHashParams, SBox, QuinticSBox, PowerSBox
+use crate::poseidon::{
+    PoseidonEngine, PoseidonHashParams, SBox, QuinticSBox
 };
 
 use super::custom_rescue_gate::apply_5th_power;
@@ -89,6 +89,30 @@ impl<E: Engine> PoseidonCsSBox<E> for QuinticSBox<E> {
     }
 }
 
+/// Fixed length Poseidon hash of `input`, matches `crate::poseidon::poseidon_hash`.
+pub fn poseidon_hash<E: PoseidonEngine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    params: &E::Params,
+    input: &[Num<E>]
+) -> Result<Vec<Num<E>>, SynthesisError>
+    where <<E as PoseidonEngine>::Params as PoseidonHashParams<E>>::SBox: PoseidonCsSBox<E>
+{
+    assert!(input.len() < 256);
+    let mut poseidon_gadget = StatefulPoseidonGadget::<E>::new(params);
+
+    poseidon_gadget.specialize(input.len() as u8);
+    poseidon_gadget.absorb_nums(cs, input, params)?;
+    poseidon_gadget.pad_if_necessary(params)?;
+
+    let mut result = Vec::with_capacity(params.output_len() as usize);
+    for _ in 0..params.output_len() {
+        let res_lc = poseidon_gadget.squeeze_out_single(cs, params)?;
+        result.push(res_lc.into_num(cs)?);
+    }
+
+    Ok(result)
+}
+
 enum OpMode<E: PoseidonEngine> {
     AccumulatingToAbsorb(Vec<Num<E>>),
     SqueezedInto(Vec<LinearCombination<E>>)
@@ -113,143 +137,77 @@ impl<E: PoseidonEngine> StatefulPoseidonGadget<E>
         }
     }
 
-    fn mimc_over_lcs<CS: ConstraintSystem<E>>(
+    pub fn specialize(
+        &mut self,
+        dst: u8
+    ) {
+        assert!(dst > 0);
+        match self.mode {
+            OpMode::AccumulatingToAbsorb(ref into) => {
+                assert_eq!(into.len(), 0, "can not specialize sponge that absorbed something")
+            },
+            _ => {
+                panic!("can not specialized sponge in squeezing state");
+            }
+        }
+        let dst = dst as u64;
+        let mut repr = <E::Fr as PrimeField>::Repr::default();
+        repr.as_mut()[0] = dst;
+        let as_fe = <E::Fr as PrimeField>::from_repr(repr).unwrap();
+        let last_state_elem_idx = self.internal_state.len() - 1;
+        self.internal_state[last_state_elem_idx].add_assign_constant(as_fe)
+    }
+
+    fn apply_sbox<CS: ConstraintSystem<E>>(
+        cs: &mut CS,
+        input: &Num<E>,
+        params: &E::Params
+    ) -> Result<Num<E>, SynthesisError> {
+        let sbox = params.sbox();
+        if <<<E as PoseidonEngine>::Params as PoseidonHashParams<E>>::SBox as PoseidonCsSBox<E>>::SHOULD_APPLY_FORWARD {
+            sbox.apply_constraints(cs, input, false)
+        } else {
+            sbox.apply_constraints_in_reverse(cs, input, false)
+        }
+    }
+
+    pub fn permutation_over_lcs<CS: ConstraintSystem<E>>(
         cs: &mut CS,
         state: &[LinearCombination<E>],
         params: &E::Params
     ) -> Result<Vec<LinearCombination<E>>, SynthesisError> {
         let state_len = state.len();
         assert_eq!(state_len, params.state_width() as usize);
-        let mut state = Some(state.to_vec());
-
-        let last_state_idx = state_len - 1;
+        let mut state = state.to_vec();
 
         assert!(params.num_full_rounds() % 2 == 0);
         let half_full_rounds = params.num_full_rounds() / 2;
+        let partial_rounds_end = half_full_rounds + params.num_partial_rounds();
+        let num_rounds = params.num_full_rounds() + params.num_partial_rounds();
 
-        for round in 0..half_full_rounds {
+        for round in 0..num_rounds {
             let round_constants = params.round_constants(round);
 
-            for (idx, s) in state.as_mut().unwrap().iter_mut().enumerate() {
-                s.add_assign_constant(round_constants[idx]);
-            }
-
-            let mut after_nonlin = Vec::with_capacity(state_len);
-
-            for s in state.take().unwrap().into_iter() {
-                let input = s.into_num(cs)?;
-                let sbox = params.sbox();
-                let output = if <<<E as PoseidonEngine>::Params as PoseidonHashParams<E>>::SBox as PoseidonCsSBox<E>>::SHOULD_APPLY_FORWARD {
-                    sbox.apply_constraints(cs, &input, false)?
-                } else {
-                    sbox.apply_constraints_in_reverse(cs, &input, false)?
-                };
-
-                after_nonlin.push(output);
-            }
-
-            // apply MDS
-
-            let mut new_state = Vec::with_capacity(state_len);
-
-            for i in 0..state_len {
-                let mut lc = LinearCombination::<E>::zero();
-                let mds_row = params.mds_matrix_row(i as u32);
-
-                for (s, coeff) in after_nonlin.iter().zip(mds_row.iter()){
-                    lc.add_assign_number_with_coeff(s, *coeff);
-                }
-
-                new_state.push(lc);
-            }
-
-            state = Some(new_state);
-        }
-
-        for round in half_full_rounds..(params.num_partial_rounds() + half_full_rounds) {
-            let round_constants = params.round_constants(round);
-
-            for (idx, s) in state.as_mut().unwrap().iter_mut().enumerate() {
-                s.add_assign_constant(round_constants[idx]);
-            }
-
-            let mut after_nonlin = Vec::with_capacity(state_len);
-
-            let mut st = state.take().unwrap();
-
-            let no_application: Vec<_> = st.drain(..last_state_idx).collect();
-
-            for s in no_application.into_iter() {
-                let s = s.into_num(cs)?;
-
-                after_nonlin.push(s);
-            }
-
-            // after_nonlin.extend(no_application);
-
-            for s in st.into_iter() {
-                let input = s.into_num(cs)?;
-                let sbox = params.sbox();
-                let output = if <<<E as PoseidonEngine>::Params as PoseidonHashParams<E>>::SBox as PoseidonCsSBox<E>>::SHOULD_APPLY_FORWARD {
-                    sbox.apply_constraints(cs, &input, false)?
-                } else {
-                    sbox.apply_constraints_in_reverse(cs, &input, false)?
-                };
-
-                after_nonlin.push(output);
-
-                // let mut lc = LinearCombination::<E>::zero(); 
-                // lc.add_assign_number_with_coeff(&output, E::Fr::one());
-
-                // after_nonlin.push(lc);
+            for (s, c) in state.iter_mut().zip(round_constants.iter()) {
+                s.add_assign_constant(*c);
             }
 
-            assert_eq!(after_nonlin.len(), state_len);
-
-            // apply MDS and round constants
-
-            let mut new_state = Vec::with_capacity(state_len);
-
-            for i in 0..state_len {
-                let mut lc = LinearCombination::<E>::zero();
-                let mds_row = params.mds_matrix_row(i as u32);
-
-                for (s, coeff) in after_nonlin.iter().zip(mds_row.iter()){
-                    lc.add_assign_number_with_coeff(s, *coeff);
-                }
-
-                // for (s, coeff) in after_nonlin.iter().zip(mds_row.iter()){
-                //     lc.add_assign_scaled(&s, *coeff);
-                // }
-
-                new_state.push(lc);
-            }
-
-            state = Some(new_state);
-        }
-
-        for round in (params.num_partial_rounds() + half_full_rounds)..(params.num_partial_rounds() + params.num_full_rounds()) {
-            let round_constants = params.round_constants(round);
-
-            for (idx, s) in state.as_mut().unwrap().iter_mut().enumerate() {
-                s.add_assign_constant(round_constants[idx]);
-            }
+            let is_full_round = round < half_full_rounds || round >= partial_rounds_end;
 
             let mut after_nonlin = Vec::with_capacity(state_len);
-
-            for s in state.take().unwrap().into_iter() {
+            for (idx, s) in state.into_iter().enumerate() {
                 let input = s.into_num(cs)?;
-                let sbox = params.sbox();
-                let output = if <<<E as PoseidonEngine>::Params as PoseidonHashParams<E>>::SBox as PoseidonCsSBox<E>>::SHOULD_APPLY_FORWARD {
-                    sbox.apply_constraints(cs, &input, false)?
+                // partial rounds only apply S-box to the first element
+                let output = if is_full_round || idx == 0 {
+                    Self::apply_sbox(cs, &input, params)?
                 } else {
-                    sbox.apply_constraints_in_reverse(cs, &input, false)?
+                    input
                 };
 
                 after_nonlin.push(output);
             }
 
-            // apply MDS and round constants
+            // apply MDS
 
             let mut new_state = Vec::with_capacity(state_len);
 
@@ -264,10 +222,10 @@ impl<E: PoseidonEngine> StatefulPoseidonGadget<E>
                 new_state.push(lc);
             }
 
-            state = Some(new_state);
+            state = new_state;
         }
 
-        Ok(state.unwrap())
+        Ok(state)
     }
 
     pub fn absorb_single_value<CS: ConstraintSystem<E>>(
@@ -280,7 +238,7 @@ impl<E: PoseidonEngine> StatefulPoseidonGadget<E>
             OpMode::AccumulatingToAbsorb(ref mut into) => {
                 // two cases
                 // either we have accumulated enough already and should to 
-                // a mimc round before accumulating more, or just accumulate more
+                // a permutation before accumulating more, or just accumulate more
                 let rate = params.rate() as usize;
                 if into.len() < rate {
                     into.push(value);
@@ -289,7 +247,7 @@ impl<E: PoseidonEngine> StatefulPoseidonGadget<E>
                         self.internal_state[i].add_assign_number_with_coeff(&into[i], E::Fr::one());
                     }
 
-                    self.internal_state = Self::mimc_over_lcs(
+                    self.internal_state = Self::permutation_over_lcs(
                         cs,
                         &self.internal_state, 
                         &params
@@ -318,22 +276,30 @@ impl<E: PoseidonEngine> StatefulPoseidonGadget<E>
         cs: &mut CS,
         input: &[AllocatedNum<E>],
         params: &E::Params
+    ) -> Result<(), SynthesisError>{
+        let input: Vec<_> = input.iter().map(|el| Num::Variable(*el)).collect();
+
+        self.absorb_nums(cs, &input, params)
+    }
+
+    pub fn absorb_nums<CS: ConstraintSystem<E>>(
+        &mut self,
+        cs: &mut CS,
+        input: &[Num<E>],
+        params: &E::Params
     ) -> Result<(), SynthesisError>{
         let absorbtion_len = params.rate() as usize;
-        let t = params.state_width();
-        let rate = params.rate();
-    
+
+        assert!(input.len() > 0);
         let mut absorbtion_cycles = input.len() / absorbtion_len;
         if input.len() % absorbtion_len != 0 {
             absorbtion_cycles += 1;
         }
 
-        let mut input: Vec<_> = input.iter().map(|el| Num::Variable(el.clone())).collect();
+        let mut input: Vec<_> = input.to_vec();
         input.resize(absorbtion_cycles * absorbtion_len, Num::Constant(E::Fr::one()));
-    
-        let it = input.into_iter();
-        
-        for (idx, val) in it.enumerate() {
+
+        for val in input.into_iter() {
             self.absorb_single_value(
                 cs,
                 val,
@@ -344,6 +310,23 @@ impl<E: PoseidonEngine> StatefulPoseidonGadget<E>
         Ok(())
     }
 
+    pub fn pad_if_necessary(
+        &mut self,
+        params: &E::Params
+    ) -> Result<(), SynthesisError> {
+        match self.mode {
+            OpMode::AccumulatingToAbsorb(ref mut into) => {
+                let rate = params.rate() as usize;
+                if into.len() != rate {
+                    into.resize(rate, Num::Constant(E::Fr::one()));
+                };
+            },
+            OpMode::SqueezedInto(..) => {}
+        }
+
+        Ok(())
+    }
+
     pub fn squeeze_out_single<CS: ConstraintSystem<E>>(
         &mut self,
         cs: &mut CS,
@@ -355,12 +338,12 @@ impl<E: PoseidonEngine> StatefulPoseidonGadget<E>
                 assert_eq!(into.len(), rate, "padding was necessary!");
                 // two cases
                 // either we have accumulated enough already and should to 
-                // a mimc round before accumulating more, or just accumulate more
+                // a permutation before accumulating more, or just accumulate more
                 for i in 0..rate {
                     self.internal_state[i].add_assign_number_with_coeff(&into[i], E::Fr::one());
                 }
 
-                self.internal_state = Self::mimc_over_lcs(
+                self.internal_state = Self::permutation_over_lcs(
                     cs,
                     &self.internal_state, 
                     &params
@@ -391,9 +374,8 @@ mod test {
     use rand::{SeedableRng, Rng, XorShiftRng};
     use super::*;
     use bellman::pairing::bn256::{Bn256, Fr};
-    use bellman::pairing::ff::PrimeField;
-    use poseidon_hash::{poseidon_hash, StatefulSponge};
-    use poseidon_hash::bn256::Bn256PoseidonParams;
+    use crate::poseidon::{poseidon_hash as native_poseidon_hash, StatefulSponge};
+    use crate::poseidon::{Bn256PoseidonParams, Bls12PoseidonParams};
     use crate::bellman::plonk::better_better_cs::cs::{
         TrivialAssembly, 
         PlonkCsWidth4WithNextStepParams, 
@@ -408,7 +390,9 @@ mod test {
         let params = Bn256PoseidonParams::new_checked_2_into_1();
         let input: Vec<Fr> = (0..(params.rate())).map(|_| rng.gen()).collect();
         // let input: Vec<Fr> = (0..(params.rate()+1)).map(|_| rng.gen()).collect();
-        let expected = poseidon_hash::<Bn256>(&params, &input[..]);
+        let mut native_sponge = StatefulSponge::<Bn256>::new(&params);
+        native_sponge.absorb(&input);
+        let expected = vec![native_sponge.squeeze_out_single()];
 
         {
             let mut cs = TrivialAssembly::<Bn256, 
@@ -424,25 +408,25 @@ mod test {
                     }).unwrap()
             }).collect();
 
-            let mut rescue_gadget = StatefulPoseidonGadget::<Bn256>::new(
+            let mut poseidon_gadget = StatefulPoseidonGadget::<Bn256>::new(
                 &params
             );
 
-            rescue_gadget.absorb(
+            poseidon_gadget.absorb(
                 &mut cs,
                 &input_words, 
                 &params
             ).unwrap();
 
-            let res_0 = rescue_gadget.squeeze_out_single(
+            let res_0 = poseidon_gadget.squeeze_out_single(
                 &mut cs,
                 &params
             ).unwrap();
 
             assert_eq!(res_0.get_value().unwrap(), expected[0]);
-            println!("Rescue stateful hash of {} elements taken {} constraints", input.len(), cs.n());
+            println!("Poseidon stateful hash of {} elements taken {} constraints", input.len(), cs.n());
 
-            let res_1 = rescue_gadget.squeeze_out_single(
+            let res_1 = poseidon_gadget.squeeze_out_single(
                 &mut cs,
                 &params
             ).unwrap();
@@ -464,6 +448,40 @@ mod test {
         }
     }
 
+    fn check_fixed_length_hash<E: PoseidonEngine>(params: &E::Params)
+        where <<E as PoseidonEngine>::Params as PoseidonHashParams<E>>::SBox: PoseidonCsSBox<E>
+    {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        for len in 1..(2 * params.rate() as usize + 2) {
+            let input: Vec<E::Fr> = (0..len).map(|_| rng.gen()).collect();
+            let expected = native_poseidon_hash::<E>(params, &input);
+
+            let mut cs = TrivialAssembly::<E, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+            let input_nums: Vec<_> = input.iter().map(|el| Num::alloc(&mut cs, Some(*el)).unwrap()).collect();
+
+            let output = poseidon_hash(&mut cs, params, &input_nums).unwrap();
+
+            assert!(cs.is_satisfied());
+            assert_eq!(output.len(), expected.len());
+            for (o, e) in output.iter().zip(expected.iter()) {
+                assert_eq!(o.get_value().unwrap(), *e);
+            }
+        }
+    }
+
+    #[test]
+    fn test_poseidon_fixed_length_hash_bn256() {
+        check_fixed_length_hash::<Bn256>(&Bn256PoseidonParams::new_checked_2_into_1());
+        check_fixed_length_hash::<Bn256>(&Bn256PoseidonParams::new_checked_4_into_1());
+    }
+
+    #[test]
+    fn test_poseidon_fixed_length_hash_bls12() {
+        use bellman::pairing::bls12_381::Bls12;
+        check_fixed_length_hash::<Bls12>(&Bls12PoseidonParams::new_checked_2_into_1());
+    }
+
     // #[test]
     // fn test_poseidon_hash_redshift_gadget() {
     //     use crate::bellman::plonk::better_better_cs::cs::{ConstraintSystem, Circuit};
@@ -0,0 +1,136 @@
+use crate::bellman::{
+    SynthesisError,
+};
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    ConstraintSystem,
+};
+
+use super::allocated_num::Num;
+use super::boolean::Boolean;
+use super::poseidon::PoseidonCsSBox;
+use super::sponge::{PoseidonPermutation, Sponge};
+
+use crate::poseidon::{PoseidonEngine, PoseidonHashParams};
+use crate::poseidon::encryption::encryption_domain_tag;
+
+// Poseidon duplex encryption matching `crate::poseidon::encryption`. It costs a permutation
+// per `rate` elements, so it's the cipher of choice when both sides of the encryption are
+// proven in circuit.
+
+fn keyed_sponge<'a, E, CS>(
+    cs: &mut CS,
+    params: &'a E::Params,
+    key: &Num<E>,
+    nonce: &Num<E>,
+    message_len: usize
+) -> Result<(Sponge<E, PoseidonPermutation<'a, E>>, Vec<Num<E>>), SynthesisError>
+    where E: PoseidonEngine,
+          CS: ConstraintSystem<E>,
+          <<E as PoseidonEngine>::Params as PoseidonHashParams<E>>::SBox: PoseidonCsSBox<E>
+{
+    assert!(params.rate() >= 2, "key and nonce are absorbed at once");
+
+    let mut sponge = Sponge::new_with_domain(PoseidonPermutation::<E> { params }, encryption_domain_tag(message_len));
+    let keystream = sponge.duplex(cs, &[*key, *nonce])?;
+
+    Ok((sponge, keystream))
+}
+
+/// Ciphertext elements and the tag of `message`.
+pub fn poseidon_encrypt<E, CS>(
+    cs: &mut CS,
+    params: &E::Params,
+    key: &Num<E>,
+    nonce: &Num<E>,
+    message: &[Num<E>]
+) -> Result<(Vec<Num<E>>, Num<E>), SynthesisError>
+    where E: PoseidonEngine,
+          CS: ConstraintSystem<E>,
+          <<E as PoseidonEngine>::Params as PoseidonHashParams<E>>::SBox: PoseidonCsSBox<E>
+{
+    let (mut sponge, mut keystream) = keyed_sponge(cs, params, key, nonce, message.len())?;
+
+    let mut ciphertext = Vec::with_capacity(message.len());
+    for chunk in message.chunks(params.rate() as usize) {
+        for (m, k) in chunk.iter().zip(keystream.iter()) {
+            ciphertext.push(m.add(cs, k)?);
+        }
+        keystream = sponge.duplex(cs, chunk)?;
+    }
+
+    Ok((ciphertext, keystream[0]))
+}
+
+/// Plaintext of `ciphertext` and whether `tag` is valid for it.
+pub fn poseidon_decrypt<E, CS>(
+    cs: &mut CS,
+    params: &E::Params,
+    key: &Num<E>,
+    nonce: &Num<E>,
+    ciphertext: &[Num<E>],
+    tag: &Num<E>
+) -> Result<(Vec<Num<E>>, Boolean), SynthesisError>
+    where E: PoseidonEngine,
+          CS: ConstraintSystem<E>,
+          <<E as PoseidonEngine>::Params as PoseidonHashParams<E>>::SBox: PoseidonCsSBox<E>
+{
+    let (mut sponge, mut keystream) = keyed_sponge(cs, params, key, nonce, ciphertext.len())?;
+
+    let mut message = Vec::with_capacity(ciphertext.len());
+    for chunk in ciphertext.chunks(params.rate() as usize) {
+        let mut message_chunk = Vec::with_capacity(chunk.len());
+        for (c, k) in chunk.iter().zip(keystream.iter()) {
+            message_chunk.push(c.sub(cs, k)?);
+        }
+        keystream = sponge.duplex(cs, &message_chunk)?;
+        message.extend(message_chunk);
+    }
+    let is_valid = Num::equals(cs, &keystream[0], tag)?;
+
+    Ok((message, is_valid))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use crate::bellman::plonk::better_better_cs::cs::*;
+    use crate::plonk::circuit::Width4WithCustomGates;
+    use crate::poseidon::Bn256PoseidonParams;
+
+    #[test]
+    fn test_poseidon_encryption_matches_native() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = Bn256PoseidonParams::new_checked_2_into_1();
+        let mut cs = TrivialAssembly::<Bn256, Width4WithCustomGates, Width4MainGateWithDNext>::new();
+
+        let key: Fr = rng.gen();
+        let nonce: Fr = rng.gen();
+        let message: Vec<Fr> = (0..5).map(|_| rng.gen()).collect();
+        let native = crate::poseidon::encryption::poseidon_encrypt::<Bn256>(&params, key, nonce, &message);
+
+        let key = Num::alloc(&mut cs, Some(key)).unwrap();
+        let nonce = Num::alloc(&mut cs, Some(nonce)).unwrap();
+        let message_nums: Vec<_> = message.iter().map(|el| Num::alloc(&mut cs, Some(*el)).unwrap()).collect();
+        let (ciphertext, tag) = poseidon_encrypt(&mut cs, &params, &key, &nonce, &message_nums).unwrap();
+
+        for (c, e) in ciphertext.iter().zip(native.elements.iter()) {
+            assert_eq!(c.get_value().unwrap(), *e);
+        }
+        assert_eq!(tag.get_value().unwrap(), native.tag);
+
+        let (decrypted, is_valid) = poseidon_decrypt(&mut cs, &params, &key, &nonce, &ciphertext, &tag).unwrap();
+        assert!(is_valid.get_value().unwrap());
+        for (d, m) in decrypted.iter().zip(message.iter()) {
+            assert_eq!(d.get_value().unwrap(), *m);
+        }
+
+        let wrong_tag = Num::alloc(&mut cs, Some(rng.gen())).unwrap();
+        let (_, is_valid) = poseidon_decrypt(&mut cs, &params, &key, &nonce, &ciphertext, &wrong_tag).unwrap();
+        assert!(!is_valid.get_value().unwrap());
+
+        assert!(cs.is_satisfied());
+    }
+}
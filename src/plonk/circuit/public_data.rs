@@ -0,0 +1,218 @@
+use crate::bellman::pairing::Engine;
+use crate::bellman::pairing::ff::{Field, PrimeField, PrimeFieldRepr};
+
+use crate::bellman::{
+    SynthesisError,
+};
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    ConstraintSystem,
+};
+
+use super::allocated_num::Num;
+use super::boolean::Boolean;
+use super::keccak::keccak256;
+use super::linear_combination::LinearCombination;
+use super::sha256::sha256;
+
+use sha2::{Digest, Sha256};
+use tiny_keccak::Keccak;
+
+// Public data commitment. The public outputs of a circuit (roots, amounts, addresses) are
+// serialized into bytes and hashed, and the digest, read as a big endian integer truncated to
+// its lowest E::Fr::CAPACITY bits, is the only public input. The verifier recomputes it with
+// `public_data_commitment` from the data it has to store anyway, one hash instead of a scalar
+// multiplication per input.
+//
+// Values are serialized big endian, as by `abi.encodePacked`.
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PublicDataHash {
+    Sha256,
+    /// Keccak-256 as in Ethereum.
+    Keccak256,
+}
+
+/// Public data of a circuit, serialized as it is pushed.
+#[derive(Clone, Debug, Default)]
+pub struct PublicData {
+    // little endian bits of every byte
+    bits: Vec<Boolean>,
+}
+
+impl PublicData {
+    pub fn new() -> Self {
+        PublicData { bits: vec![] }
+    }
+
+    /// Number of bytes.
+    pub fn len(&self) -> usize {
+        self.bits.len() / 8
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits.is_empty()
+    }
+
+    /// `num_bytes` big endian bytes of the number with little endian `bits`.
+    pub fn push_bits_le(&mut self, bits: &[Boolean], num_bytes: usize) {
+        assert!(bits.len() <= num_bytes * 8);
+
+        let mut bits = bits.to_vec();
+        bits.resize(num_bytes * 8, Boolean::constant(false));
+        for byte in bits.chunks(8).rev() {
+            self.bits.extend_from_slice(byte);
+        }
+    }
+
+    /// `num_bytes` big endian bytes of `num`, which is enforced to fit.
+    pub fn push_num<E: Engine, CS: ConstraintSystem<E>>(
+        &mut self,
+        cs: &mut CS,
+        num: &Num<E>,
+        num_bytes: usize
+    ) -> Result<(), SynthesisError> {
+        let bits = num.into_bits_le(cs, Some(num_bytes * 8))?;
+        self.push_bits_le(&bits, num_bytes);
+
+        Ok(())
+    }
+
+    /// A field element in (E::Fr::NUM_BITS + 7) / 8 bytes, 32 for BN254.
+    pub fn push_field_element<E: Engine, CS: ConstraintSystem<E>>(
+        &mut self,
+        cs: &mut CS,
+        num: &Num<E>
+    ) -> Result<(), SynthesisError> {
+        let bits = num.into_bits_le_strict(cs)?;
+        self.push_bits_le(&bits, (E::Fr::NUM_BITS as usize + 7) / 8);
+
+        Ok(())
+    }
+
+    /// Appends `other` if `condition`, as many zero bytes otherwise, so the layout doesn't
+    /// depend on the witness.
+    pub fn extend_if<E: Engine, CS: ConstraintSystem<E>>(
+        &mut self,
+        cs: &mut CS,
+        condition: &Boolean,
+        other: &PublicData
+    ) -> Result<(), SynthesisError> {
+        for bit in other.bits.iter() {
+            self.bits.push(Boolean::and(cs, condition, bit)?);
+        }
+
+        Ok(())
+    }
+
+    /// The commitment to the data.
+    pub fn commitment<E: Engine, CS: ConstraintSystem<E>>(
+        &self,
+        cs: &mut CS,
+        hash: PublicDataHash
+    ) -> Result<Num<E>, SynthesisError> {
+        // the digest as little endian bits of every byte
+        let digest = match hash {
+            PublicDataHash::Sha256 => {
+                let input: Vec<Boolean> = self.bits.chunks(8).flat_map(|byte| byte.iter().rev().cloned()).collect();
+                let digest = sha256(cs, &input)?;
+
+                digest.chunks(8).flat_map(|byte| byte.iter().rev().cloned()).collect()
+            },
+            PublicDataHash::Keccak256 => keccak256(cs, &self.bits)?,
+        };
+
+        let mut lc = LinearCombination::zero();
+        let mut coeff = E::Fr::one();
+        let bits = digest.chunks(8).rev().flat_map(|byte| byte.iter());
+        for bit in bits.take(E::Fr::CAPACITY as usize) {
+            lc.add_assign_boolean_with_coeff(bit, coeff);
+            coeff.double();
+        }
+
+        lc.into_num(cs)
+    }
+
+    /// Makes the commitment to the data a public input and returns it.
+    pub fn inputize<E: Engine, CS: ConstraintSystem<E>>(
+        &self,
+        cs: &mut CS,
+        hash: PublicDataHash
+    ) -> Result<Num<E>, SynthesisError> {
+        let commitment = self.commitment(cs, hash)?;
+        match commitment {
+            Num::Variable(ref var) => var.inputize(cs)?,
+            Num::Constant(_) => return Err(SynthesisError::Unsatisfiable),
+        }
+
+        Ok(commitment)
+    }
+}
+
+/// The commitment to `data`, serialized as by `PublicData`.
+pub fn public_data_commitment<F: PrimeField>(data: &[u8], hash: PublicDataHash) -> F {
+    let digest = match hash {
+        PublicDataHash::Sha256 => Sha256::digest(data).to_vec(),
+        PublicDataHash::Keccak256 => {
+            let mut h = Keccak::new_keccak256();
+            h.update(data);
+            let mut digest = vec![0u8; 32];
+            h.finalize(&mut digest);
+
+            digest
+        },
+    };
+
+    let mut repr = F::Repr::default();
+    let mut bytes = vec![0u8; repr.as_ref().len() * 8];
+    assert!(bytes.len() >= digest.len());
+    let offset = bytes.len() - digest.len();
+    bytes[offset..].copy_from_slice(&digest);
+
+    // keep the lowest CAPACITY bits
+    let num_cleared = bytes.len() * 8 - F::CAPACITY as usize;
+    for i in 0..num_cleared {
+        bytes[i / 8] &= !(0x80 >> (i % 8));
+    }
+    repr.read_be(&bytes[..]).unwrap();
+
+    F::from_repr(repr).unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use crate::bellman::plonk::better_better_cs::cs::*;
+
+    #[test]
+    fn test_public_data_commitment() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        let root: Fr = rng.gen();
+        let amount: u64 = rng.gen();
+        let address: u32 = rng.gen();
+
+        let mut bytes = vec![];
+        root.into_repr().write_be(&mut bytes).unwrap();
+        bytes.extend_from_slice(&amount.to_be_bytes());
+        bytes.extend_from_slice(&address.to_be_bytes());
+
+        for &hash in [PublicDataHash::Sha256, PublicDataHash::Keccak256].iter() {
+            let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+            let mut data = PublicData::new();
+            let root = Num::alloc(&mut cs, Some(root)).unwrap();
+            data.push_field_element(&mut cs, &root).unwrap();
+            let amount = Num::alloc(&mut cs, Some(Fr::from_str(&amount.to_string()).unwrap())).unwrap();
+            data.push_num(&mut cs, &amount, 8).unwrap();
+            let address_bits: Vec<Boolean> = (0..32).map(|i| Boolean::alloc(&mut cs, Some((address >> i) & 1 == 1)).unwrap()).collect();
+            data.push_bits_le(&address_bits, 4);
+            assert_eq!(data.len(), bytes.len());
+
+            let commitment = data.inputize(&mut cs, hash).unwrap();
+            assert!(cs.is_satisfied());
+            assert_eq!(commitment.get_value().unwrap(), public_data_commitment::<Fr>(&bytes, hash));
+        }
+    }
+}
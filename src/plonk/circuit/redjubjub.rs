@@ -0,0 +1,210 @@
+use crate::bellman::pairing::ff::{
+    PrimeField,
+};
+
+use crate::bellman::{
+    SynthesisError,
+};
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    ConstraintSystem,
+};
+
+use super::boolean::Boolean;
+use super::blake2b::blake2b;
+use super::edwards::{CircuitTwistedEdwardsCurveImplementor, CircuitTwistedEdwardsPoint};
+
+use crate::generic_twisted_edwards::TwistedEdwardsCurveParams;
+use crate::jubjub::{FixedGenerators, JubjubEngine, JubjubParams};
+
+// Gadgets matching `crate::redjubjub`. Points are in affine Edwards coordinates, the hash
+// H*(Rbar || M) is BLAKE2b-512 and is used as a 512 bit scalar without reduction, that is
+// equivalent after the multiplication by the cofactor.
+
+pub const REDJUBJUB_PERSONALIZATION: &[u8; 16] = b"Zcash_RedJubjubH";
+
+#[derive(Clone, Debug)]
+pub struct CircuitRedJubjubSignature<E: JubjubEngine> {
+    /// The point R, `verify_signature` checks it to be on curve.
+    pub r: CircuitTwistedEdwardsPoint<E>,
+    /// Little endian bits of S. S is not checked to be reduced, S and S + order(P_G)
+    /// give the same S . P_G.
+    pub s: Vec<Boolean>,
+}
+
+/// 256 bit encoding of the point as in `jubjub::edwards::Point::write`: little endian y
+/// with the parity of x in the most significant bit.
+pub fn point_into_bits_le<E: JubjubEngine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    p: &CircuitTwistedEdwardsPoint<E>
+) -> Result<Vec<Boolean>, SynthesisError> {
+    assert!(E::Fr::NUM_BITS <= 255);
+
+    let mut bits = p.y.into_bits_le_strict(cs)?;
+    bits.resize(255, Boolean::constant(false));
+
+    let x_bits = p.x.into_bits_le_strict(cs)?;
+    bits.push(x_bits[0]);
+
+    Ok(bits)
+}
+
+/// rk = ak + alpha . P_G, where `alpha` is given by little endian bits.
+pub fn randomize_public_key<E, CS, C>(
+    cs: &mut CS,
+    curve: &CircuitTwistedEdwardsCurveImplementor<E, C>,
+    ak: &CircuitTwistedEdwardsPoint<E>,
+    alpha: &[Boolean],
+    p_g: FixedGenerators,
+    params: &E::Params
+) -> Result<CircuitTwistedEdwardsPoint<E>, SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>,
+          C: TwistedEdwardsCurveParams<E>
+{
+    let (x, y) = params.generator(p_g).into_xy();
+    let generator = CircuitTwistedEdwardsPoint::constant(x, y);
+    let alpha_g = curve.mul(cs, &generator, alpha)?;
+
+    curve.add(cs, ak, &alpha_g)
+}
+
+/// Checks the signature of `message` (whole bytes, little endian bits in every byte) under `vk`:
+/// h_G(-S . P_G + R + c . vk) = 0 with c = H*(Rbar || M). Returns the result of the check
+/// together with R being on curve, so the caller can enforce it or combine it with other conditions.
+pub fn verify_signature<E, CS, C>(
+    cs: &mut CS,
+    curve: &CircuitTwistedEdwardsCurveImplementor<E, C>,
+    vk: &CircuitTwistedEdwardsPoint<E>,
+    signature: &CircuitRedJubjubSignature<E>,
+    message: &[Boolean],
+    p_g: FixedGenerators,
+    params: &E::Params
+) -> Result<Boolean, SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>,
+          C: TwistedEdwardsCurveParams<E>
+{
+    assert!(message.len() % 8 == 0);
+
+    let r_is_on_curve = curve.check_is_on_curve(cs, &signature.r)?;
+
+    // c = H*(Rbar || M)
+    let mut hash_input = point_into_bits_le(cs, &signature.r)?;
+    hash_input.extend_from_slice(message);
+    let c = blake2b(cs, &hash_input, &REDJUBJUB_PERSONALIZATION[..])?;
+
+    // R + c . vk = S . P_G up to the small order component
+    let c_vk = curve.mul(cs, vk, &c)?;
+    let mut lhs = curve.add(cs, &signature.r, &c_vk)?;
+    let (x, y) = params.generator(p_g).into_xy();
+    let generator = CircuitTwistedEdwardsPoint::constant(x, y);
+    let mut rhs = curve.mul(cs, &generator, &signature.s)?;
+
+    for _ in 0..curve.implementor.get_params().log_2_cofactor() {
+        lhs = curve.double(cs, &lhs)?;
+        rhs = curve.double(cs, &rhs)?;
+    }
+    let equation_holds = CircuitTwistedEdwardsPoint::equals(cs, &lhs, &rhs)?;
+
+    Boolean::and(cs, &r_is_on_curve, &equation_holds)
+}
+
+/// Re-randomizes `ak` by `alpha` and checks the signature under rk = ak + alpha . P_G as in the
+/// spend authorization. Returns rk and the result of the check.
+pub fn verify_randomized_signature<E, CS, C>(
+    cs: &mut CS,
+    curve: &CircuitTwistedEdwardsCurveImplementor<E, C>,
+    ak: &CircuitTwistedEdwardsPoint<E>,
+    alpha: &[Boolean],
+    signature: &CircuitRedJubjubSignature<E>,
+    message: &[Boolean],
+    p_g: FixedGenerators,
+    params: &E::Params
+) -> Result<(CircuitTwistedEdwardsPoint<E>, Boolean), SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>,
+          C: TwistedEdwardsCurveParams<E>
+{
+    let rk = randomize_public_key(cs, curve, ak, alpha, p_g, params)?;
+    let is_valid = verify_signature(cs, curve, &rk, signature, message, p_g, params)?;
+
+    Ok((rk, is_valid))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use bellman::pairing::bn256::Bn256;
+    use crate::bellman::plonk::better_better_cs::cs::*;
+    use crate::alt_babyjubjub::{AltJubjubBn256, fs::Fs, edwards};
+    use crate::jubjub::Unknown;
+    use crate::generic_twisted_edwards::TwistedEdwardsPoint;
+    use crate::plonk::circuit::edwards::bn256::CircuitAltBabyJubjubBn256;
+    use crate::plonk::circuit::utils::fe_to_lsb_first_bits;
+    use crate::redjubjub::{PrivateKey, PublicKey};
+    use crate::bellman::pairing::ff::PrimeFieldRepr;
+
+    fn alloc_bytes<CS: ConstraintSystem<Bn256>>(cs: &mut CS, bytes: &[u8]) -> Vec<Boolean> {
+        bytes.iter()
+             .flat_map(|byte| (0..8).map(move |i| (byte >> i) & 1 == 1))
+             .map(|b| Boolean::alloc(cs, Some(b)).unwrap())
+             .collect()
+    }
+
+    fn alloc_scalar<CS: ConstraintSystem<Bn256>>(cs: &mut CS, s: &Fs) -> Vec<Boolean> {
+        fe_to_lsb_first_bits(s).into_iter().map(|b| Boolean::alloc(cs, Some(b)).unwrap()).collect()
+    }
+
+    fn alloc_point<CS: ConstraintSystem<Bn256>>(cs: &mut CS, p: &edwards::Point<Bn256, Unknown>) -> CircuitTwistedEdwardsPoint<Bn256> {
+        let curve = CircuitAltBabyJubjubBn256::get_implementor();
+        let (x, y) = p.into_xy();
+
+        curve.alloc_point_enforce_on_curve(cs, Some(TwistedEdwardsPoint::from_xy(x, y))).unwrap()
+    }
+
+    #[test]
+    fn test_redjubjub_randomized_signature_gadget() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = AltJubjubBn256::new();
+        let curve = CircuitAltBabyJubjubBn256::get_implementor();
+        let p_g = FixedGenerators::SpendingKeyGenerator;
+
+        let sk = PrivateKey::<Bn256>(rng.gen());
+        let ak = PublicKey::from_private(&sk, p_g, &params);
+        let alpha: Fs = rng.gen();
+        let rsk = sk.randomize(alpha);
+        let rk = ak.randomize(alpha, p_g, &params);
+
+        let message = b"spend authorization";
+        let signature = rsk.sign(message, &mut rng, p_g, &params);
+        assert!(rk.verify(message, &signature, p_g, &params));
+
+        let mut signature_bytes = vec![];
+        signature.write(&mut signature_bytes).unwrap();
+        let r = edwards::Point::<Bn256, Unknown>::read(&signature_bytes[..32], &params).unwrap();
+        let mut s_repr = <Fs as PrimeField>::Repr::default();
+        s_repr.read_le(&signature_bytes[32..]).unwrap();
+        let s = Fs::from_repr(s_repr).unwrap();
+
+        for (msg, expected) in vec![(&message[..], true), (&b"spend authorizatioN"[..], false)].into_iter() {
+            let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+            let ak_point = alloc_point(&mut cs, &ak.0);
+            let alpha_bits = alloc_scalar(&mut cs, &alpha);
+            let circuit_signature = CircuitRedJubjubSignature {
+                r: alloc_point(&mut cs, &r),
+                s: alloc_scalar(&mut cs, &s),
+            };
+            let message_bits = alloc_bytes(&mut cs, msg);
+
+            let (circuit_rk, is_valid) = verify_randomized_signature(
+                &mut cs, &curve, &ak_point, &alpha_bits, &circuit_signature, &message_bits, p_g, &params
+            ).unwrap();
+
+            assert!(cs.is_satisfied());
+            assert_eq!((circuit_rk.x.get_value().unwrap(), circuit_rk.y.get_value().unwrap()), rk.0.into_xy());
+            assert_eq!(is_valid.get_value().unwrap(), expected);
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use crate::bellman::{
+    SynthesisError,
+};
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    ConstraintSystem,
+};
+
+use super::boolean::Boolean;
+use super::edwards::{CircuitTwistedEdwardsCurveImplementor, CircuitTwistedEdwardsPoint};
+
+use crate::generic_twisted_edwards::TwistedEdwardsCurveParams;
+use crate::jubjub::{FixedGenerators, JubjubEngine, JubjubParams};
+
+// One-out-of-many proof of key ownership by a linear scan over the ring: the public key
+// sk . P_G is computed once and compared with every member, so the cost is one fixed base
+// multiplication and a point comparison per member.
+
+/// Key image I = sk . base. With the same `base` every key has a single image whatever
+/// ring it's used in, so two proofs by the same key can be linked by comparing images.
+pub fn key_image<E, CS, C>(
+    cs: &mut CS,
+    curve: &CircuitTwistedEdwardsCurveImplementor<E, C>,
+    sk: &[Boolean],
+    base: &CircuitTwistedEdwardsPoint<E>
+) -> Result<CircuitTwistedEdwardsPoint<E>, SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>,
+          C: TwistedEdwardsCurveParams<E>
+{
+    curve.mul(cs, base, sk)
+}
+
+/// Checks that sk . P_G (`sk` in little endian bits) is one of the keys in `ring`. If
+/// `key_image_base` is given the key image of `sk` is returned too, it should be exposed as
+/// a public input for linkability. The ring keys are expected to be public inputs or constants.
+pub fn prove_ring_membership<E, CS, C>(
+    cs: &mut CS,
+    curve: &CircuitTwistedEdwardsCurveImplementor<E, C>,
+    ring: &[CircuitTwistedEdwardsPoint<E>],
+    sk: &[Boolean],
+    key_image_base: Option<&CircuitTwistedEdwardsPoint<E>>,
+    p_g: FixedGenerators,
+    params: &E::Params
+) -> Result<(Boolean, Option<CircuitTwistedEdwardsPoint<E>>), SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>,
+          C: TwistedEdwardsCurveParams<E>
+{
+    assert!(ring.len() > 0);
+
+    let (g_x, g_y) = params.generator(p_g).into_xy();
+    let generator = CircuitTwistedEdwardsPoint::constant(g_x, g_y);
+    let pk = curve.mul(cs, &generator, sk)?;
+
+    let mut matches = Vec::with_capacity(ring.len());
+    for member in ring.iter() {
+        matches.push(CircuitTwistedEdwardsPoint::equals(cs, &pk, member)?);
+    }
+    let is_member = Boolean::kary_or(cs, &matches)?;
+
+    let image = match key_image_base {
+        Some(base) => Some(key_image(cs, curve, sk, base)?),
+        None => None,
+    };
+
+    Ok((is_member, image))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use bellman::pairing::bn256::Bn256;
+    use crate::bellman::plonk::better_better_cs::cs::*;
+    use crate::alt_babyjubjub::{AltJubjubBn256, fs::Fs, edwards};
+    use crate::jubjub::Unknown;
+    use crate::generic_twisted_edwards::TwistedEdwardsPoint;
+    use crate::plonk::circuit::edwards::bn256::CircuitAltBabyJubjubBn256;
+    use crate::plonk::circuit::utils::fe_to_lsb_first_bits;
+
+    fn alloc_point<CS: ConstraintSystem<Bn256>>(cs: &mut CS, p: &edwards::Point<Bn256, Unknown>) -> CircuitTwistedEdwardsPoint<Bn256> {
+        let curve = CircuitAltBabyJubjubBn256::get_implementor();
+        let (x, y) = p.into_xy();
+
+        curve.alloc_point_enforce_on_curve(cs, Some(TwistedEdwardsPoint::from_xy(x, y))).unwrap()
+    }
+
+    #[test]
+    fn test_ring_membership_with_key_image() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = AltJubjubBn256::new();
+        let curve = CircuitAltBabyJubjubBn256::get_implementor();
+        let p_g = FixedGenerators::SpendingKeyGenerator;
+        let image_g = FixedGenerators::NullifierPosition;
+
+        let sks: Vec<Fs> = (0..5).map(|_| rng.gen()).collect();
+        let pks: Vec<edwards::Point<Bn256, Unknown>> = sks.iter().map(|sk| params.generator(p_g).mul(*sk, &params).into()).collect();
+        let outsider: Fs = rng.gen();
+
+        for (sk, expected) in vec![(sks[3], true), (outsider, false)].into_iter() {
+            let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+            let ring: Vec<_> = pks.iter().map(|pk| alloc_point(&mut cs, pk)).collect();
+            let sk_bits: Vec<Boolean> = fe_to_lsb_first_bits(&sk).into_iter().map(|b| Boolean::alloc(&mut cs, Some(b)).unwrap()).collect();
+            let (base_x, base_y) = params.generator(image_g).into_xy();
+            let base = CircuitTwistedEdwardsPoint::constant(base_x, base_y);
+
+            let (is_member, image) = prove_ring_membership(
+                &mut cs, &curve, &ring, &sk_bits, Some(&base), p_g, &params
+            ).unwrap();
+
+            assert!(cs.is_satisfied());
+            assert_eq!(is_member.get_value().unwrap(), expected);
+            let image = image.unwrap();
+            let expected_image = params.generator(image_g).mul(sk, &params).into_xy();
+            assert_eq!((image.x.get_value().unwrap(), image.y.get_value().unwrap()), expected_image);
+        }
+    }
+}
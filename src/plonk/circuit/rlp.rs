@@ -0,0 +1,495 @@
+use crate::bellman::pairing::Engine;
+
+use crate::bellman::pairing::ff::{
+    Field,
+    PrimeField,
+};
+
+use crate::bellman::{
+    SynthesisError,
+};
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    ConstraintSystem,
+};
+
+use super::allocated_num::Num;
+use super::boolean::Boolean;
+use super::byte::Byte;
+use super::linear_combination::LinearCombination;
+use super::utils::u64_to_fe;
+
+// RLP over fixed size byte buffers. Items are located by offsets into the buffer, which are
+// usually witnesses: the header at an offset is decoded in circuit and gives the payload
+// offset, payload length and the offset right after the item, so a prover can point into the
+// buffer and the circuit checks that the item is really there. Only canonical encodings are
+// accepted, otherwise the same data would have several encodings.
+
+/// Long items have at most this many length bytes, so all lengths are below 2^32.
+pub const RLP_MAX_LENGTH_BYTES: usize = 4;
+const RLP_LENGTH_BITS: usize = RLP_MAX_LENGTH_BYTES * 8;
+// offsets and lengths add up to less than 2^(RLP_LENGTH_BITS + 1)
+const RLP_COMPARISON_BITS: usize = RLP_LENGTH_BITS + 1;
+
+const RLP_STRING_OFFSET: u64 = 0x80;
+const RLP_LIST_OFFSET: u64 = 0xc0;
+const RLP_MAX_SHORT_LENGTH: u64 = 55;
+
+#[derive(Clone, Debug)]
+pub struct RlpItem<E: Engine> {
+    pub is_list: Boolean,
+    pub offset: Num<E>,
+    pub payload_offset: Num<E>,
+    pub payload_len: Num<E>,
+    /// Offset right after the item.
+    pub end: Num<E>,
+}
+
+/// data[index], zero if `index` is out of bounds.
+pub fn select_byte<E, CS>(
+    cs: &mut CS,
+    data: &[Byte<E>],
+    index: &Num<E>
+) -> Result<Byte<E>, SynthesisError>
+    where E: Engine,
+          CS: ConstraintSystem<E>
+{
+    let mut lc = LinearCombination::zero();
+    for (i, byte) in data.iter().enumerate() {
+        let is_selected = Num::equals(cs, index, &Num::Constant(u64_to_fe(i as u64)))?;
+        let masked = Num::mask(cs, &byte.inner, &is_selected)?;
+        lc.add_assign_number_with_coeff(&masked, E::Fr::one());
+    }
+
+    Ok(Byte::from_num_unconstrained(cs, lc.into_num(cs)?))
+}
+
+// a <= b for a, b < 2^RLP_COMPARISON_BITS
+fn is_less_or_equal<E, CS>(
+    cs: &mut CS,
+    a: &Num<E>,
+    b: &Num<E>
+) -> Result<Boolean, SynthesisError>
+    where E: Engine,
+          CS: ConstraintSystem<E>
+{
+    let mut minus_one = E::Fr::one();
+    minus_one.negate();
+
+    // b - a + 2^k has the bit k set iff b >= a
+    let mut lc = LinearCombination::zero();
+    lc.add_assign_number_with_coeff(b, E::Fr::one());
+    lc.add_assign_number_with_coeff(a, minus_one);
+    lc.add_assign_constant(u64_to_fe(1u64 << RLP_COMPARISON_BITS));
+    let bits = lc.into_num(cs)?.into_bits_le(cs, Some(RLP_COMPARISON_BITS + 1))?;
+
+    Ok(bits[RLP_COMPARISON_BITS].clone())
+}
+
+fn add_constant<E, CS>(cs: &mut CS, a: &Num<E>, constant: u64) -> Result<Num<E>, SynthesisError>
+    where E: Engine,
+          CS: ConstraintSystem<E>
+{
+    let mut lc = LinearCombination::zero();
+    lc.add_assign_number_with_coeff(a, E::Fr::one());
+    lc.add_assign_constant(u64_to_fe(constant));
+
+    lc.into_num(cs)
+}
+
+// decodes the header at `offset` without enforcing anything, the returned flag is set if the
+// header is canonical
+fn decode_item_header<E, CS>(
+    cs: &mut CS,
+    data: &[Byte<E>],
+    offset: &Num<E>
+) -> Result<(RlpItem<E>, Boolean), SynthesisError>
+    where E: Engine,
+          CS: ConstraintSystem<E>
+{
+    // 0x00..0x7f single byte, 0x80..0xb7 short string, 0xb8..0xbf long string,
+    // 0xc0..0xf7 short list, 0xf8..0xff long list
+    let prefix = select_byte(cs, data, offset)?;
+    let bits = prefix.inner.into_bits_le(cs, Some(8))?;
+
+    let is_long_form = Boolean::kary_and(cs, &bits[3..6])?;
+    let is_single = bits[7].not();
+    let is_list = Boolean::and(cs, &bits[7], &bits[6])?;
+    let is_long = Boolean::and(cs, &bits[7], &is_long_form)?;
+    let is_short = Boolean::and(cs, &bits[7], &is_long_form.not())?;
+
+    // the low 6 bits are the length of a short item
+    let mut short_len = LinearCombination::zero();
+    let mut coeff = E::Fr::one();
+    for bit in bits[..6].iter() {
+        short_len.add_assign_boolean_with_coeff(bit, coeff);
+        coeff.double();
+    }
+    let short_len = short_len.into_num(cs)?;
+
+    // the low 3 bits plus one are the number of length bytes of a long item
+    let mut len_of_len_is = Vec::with_capacity(RLP_MAX_LENGTH_BYTES);
+    for k in 0..RLP_MAX_LENGTH_BYTES {
+        let literals: Vec<Boolean> = (0..3).map(|i| {
+            if (k >> i) & 1 == 1 { bits[i].clone() } else { bits[i].not() }
+        }).collect();
+        len_of_len_is.push(Boolean::kary_and(cs, &literals)?);
+    }
+    let len_of_len_fits = Boolean::kary_or(cs, &len_of_len_is)?;
+
+    let mut length_bytes = Vec::with_capacity(RLP_MAX_LENGTH_BYTES);
+    let mut long_len = Num::zero();
+    let mut len_of_len = LinearCombination::zero();
+    for j in 0..RLP_MAX_LENGTH_BYTES {
+        let position = add_constant(cs, offset, j as u64 + 1)?;
+        let byte = select_byte(cs, data, &position)?;
+        let is_length_byte = Boolean::kary_or(cs, &len_of_len_is[j..])?;
+
+        let mut next = LinearCombination::zero();
+        next.add_assign_number_with_coeff(&long_len, u64_to_fe(256));
+        next.add_assign_number_with_coeff(&byte.inner, E::Fr::one());
+        let next = next.into_num(cs)?;
+        long_len = Num::conditionally_select(cs, &is_length_byte, &next, &long_len)?;
+
+        len_of_len.add_assign_boolean_with_coeff(&len_of_len_is[j], u64_to_fe(j as u64 + 1));
+        length_bytes.push(byte);
+    }
+    let len_of_len = len_of_len.into_num(cs)?;
+
+    // long form is only used for lengths above 55 and without leading zero bytes
+    let first_byte_is_zero = length_bytes[0].is_zero(cs)?;
+    let long_len_is_big = is_less_or_equal(cs, &Num::Constant(u64_to_fe(RLP_MAX_SHORT_LENGTH + 1)), &long_len)?;
+    let long_is_canonical = Boolean::kary_and(cs, &[len_of_len_fits, first_byte_is_zero.not(), long_len_is_big])?;
+    let is_bad_long = Boolean::and(cs, &is_long, &long_is_canonical.not())?;
+
+    // a single byte below 0x80 is its own encoding
+    let is_short_string = Boolean::and(cs, &is_short, &bits[6].not())?;
+    let is_len_one = Num::equals(cs, &short_len, &Num::one())?;
+    let is_one_byte_string = Boolean::and(cs, &is_short_string, &is_len_one)?;
+    let first_byte_bits = length_bytes[0].inner.into_bits_le(cs, Some(8))?;
+    let is_bad_single = Boolean::and(cs, &is_one_byte_string, &first_byte_bits[7].not())?;
+
+    let is_canonical = Boolean::and(cs, &is_bad_long.not(), &is_bad_single.not())?;
+
+    // the header is 0, 1 or 1 + len_of_len bytes
+    let mut payload_offset = LinearCombination::zero();
+    payload_offset.add_assign_number_with_coeff(offset, E::Fr::one());
+    payload_offset.add_assign_boolean_with_coeff(&bits[7], E::Fr::one());
+    let long_header_extra = Num::mask(cs, &len_of_len, &is_long)?;
+    payload_offset.add_assign_number_with_coeff(&long_header_extra, E::Fr::one());
+    let payload_offset = payload_offset.into_num(cs)?;
+
+    let multibyte_len = Num::conditionally_select(cs, &is_long, &long_len, &short_len)?;
+    let payload_len = Num::conditionally_select(cs, &is_single, &Num::one(), &multibyte_len)?;
+    let end = payload_offset.add(cs, &payload_len)?;
+
+    let item = RlpItem {
+        is_list,
+        offset: *offset,
+        payload_offset,
+        payload_len,
+        end,
+    };
+
+    Ok((item, is_canonical))
+}
+
+/// Decodes the item at `offset` and enforces that it's canonically encoded and ends not
+/// after `limit`, which must not exceed `data.len()`.
+pub fn decode_item<E, CS>(
+    cs: &mut CS,
+    data: &[Byte<E>],
+    offset: &Num<E>,
+    limit: &Num<E>
+) -> Result<RlpItem<E>, SynthesisError>
+    where E: Engine,
+          CS: ConstraintSystem<E>
+{
+    let (item, is_canonical) = decode_item_header(cs, data, offset)?;
+    let fits = is_less_or_equal(cs, &item.end, limit)?;
+    let is_valid = Boolean::and(cs, &is_canonical, &fits)?;
+    Boolean::enforce_equal(cs, &is_valid, &Boolean::constant(true))?;
+
+    Ok(item)
+}
+
+/// Decodes the items of `list`, which must have at most `max_items` of them. Always returns
+/// `max_items` items together with the actual number of them, the ones past it are garbage.
+/// The items are enforced to cover the payload of the list exactly.
+pub fn decode_list_items<E, CS>(
+    cs: &mut CS,
+    data: &[Byte<E>],
+    list: &RlpItem<E>,
+    max_items: usize
+) -> Result<(Vec<RlpItem<E>>, Num<E>), SynthesisError>
+    where E: Engine,
+          CS: ConstraintSystem<E>
+{
+    Boolean::enforce_equal(cs, &list.is_list, &Boolean::constant(true))?;
+
+    let mut items = Vec::with_capacity(max_items);
+    let mut num_items = LinearCombination::zero();
+    let mut offset = list.payload_offset;
+    for _ in 0..max_items {
+        let offset_plus_one = add_constant(cs, &offset, 1)?;
+        let is_present = is_less_or_equal(cs, &offset_plus_one, &list.end)?;
+
+        let (item, is_canonical) = decode_item_header(cs, data, &offset)?;
+        let fits = is_less_or_equal(cs, &item.end, &list.end)?;
+        let is_valid = Boolean::and(cs, &is_canonical, &fits)?;
+        let is_invalid_present = Boolean::and(cs, &is_present, &is_valid.not())?;
+        Boolean::enforce_equal(cs, &is_invalid_present, &Boolean::constant(false))?;
+
+        num_items.add_assign_boolean_with_coeff(&is_present, E::Fr::one());
+        offset = Num::conditionally_select(cs, &is_present, &item.end, &offset)?;
+        items.push(item);
+    }
+    offset.enforce_equal(cs, &list.end)?;
+
+    Ok((items, num_items.into_num(cs)?))
+}
+
+/// Encoding of the byte string made of the first `payload_len` bytes of `payload`. Returns
+/// `payload.len() + 1 + RLP_MAX_LENGTH_BYTES` bytes and the length of the encoding, the bytes
+/// past it are unspecified.
+pub fn encode_bytes<E, CS>(
+    cs: &mut CS,
+    payload: &[Byte<E>],
+    payload_len: &Num<E>
+) -> Result<(Vec<Byte<E>>, Num<E>), SynthesisError>
+    where E: Engine,
+          CS: ConstraintSystem<E>
+{
+    encode_with_header(cs, payload, payload_len, false)
+}
+
+/// Same as `encode_bytes` for a list, `payload` is the concatenation of the encoded items.
+pub fn encode_list<E, CS>(
+    cs: &mut CS,
+    payload: &[Byte<E>],
+    payload_len: &Num<E>
+) -> Result<(Vec<Byte<E>>, Num<E>), SynthesisError>
+    where E: Engine,
+          CS: ConstraintSystem<E>
+{
+    encode_with_header(cs, payload, payload_len, true)
+}
+
+fn encode_with_header<E, CS>(
+    cs: &mut CS,
+    payload: &[Byte<E>],
+    payload_len: &Num<E>,
+    is_list: bool
+) -> Result<(Vec<Byte<E>>, Num<E>), SynthesisError>
+    where E: Engine,
+          CS: ConstraintSystem<E>
+{
+    assert!(payload.len() > 0);
+
+    let len_bits = payload_len.into_bits_le(cs, Some(RLP_LENGTH_BITS))?;
+    let mut len_bytes = Vec::with_capacity(RLP_MAX_LENGTH_BYTES);
+    let mut byte_is_nonzero = Vec::with_capacity(RLP_MAX_LENGTH_BYTES);
+    for chunk in len_bits.chunks(8) {
+        let mut lc = LinearCombination::zero();
+        let mut coeff = E::Fr::one();
+        for bit in chunk.iter() {
+            lc.add_assign_boolean_with_coeff(bit, coeff);
+            coeff.double();
+        }
+        len_bytes.push(Byte::from_num_unconstrained(cs, lc.into_num(cs)?));
+        byte_is_nonzero.push(Boolean::kary_or(cs, chunk)?);
+    }
+
+    // a long length takes k + 1 bytes if byte k is the highest nonzero one
+    let mut len_of_len_is = Vec::with_capacity(RLP_MAX_LENGTH_BYTES);
+    for k in 0..RLP_MAX_LENGTH_BYTES {
+        let higher_is_nonzero = if k + 1 < RLP_MAX_LENGTH_BYTES {
+            Boolean::kary_or(cs, &byte_is_nonzero[(k + 1)..])?
+        } else {
+            Boolean::constant(false)
+        };
+        len_of_len_is.push(Boolean::and(cs, &byte_is_nonzero[k], &higher_is_nonzero.not())?);
+    }
+
+    let is_single = if is_list {
+        Boolean::constant(false)
+    } else {
+        let is_len_one = Num::equals(cs, payload_len, &Num::one())?;
+        let first_byte_bits = payload[0].inner.into_bits_le(cs, Some(8))?;
+        Boolean::and(cs, &is_len_one, &first_byte_bits[7].not())?
+    };
+    let fits_short = is_less_or_equal(cs, payload_len, &Num::Constant(u64_to_fe(RLP_MAX_SHORT_LENGTH)))?;
+    let is_short = Boolean::and(cs, &is_single.not(), &fits_short)?;
+    let is_long = fits_short.not();
+
+    let base = if is_list { RLP_LIST_OFFSET } else { RLP_STRING_OFFSET };
+    let short_prefix = add_constant(cs, payload_len, base)?;
+    let mut long_prefix = LinearCombination::zero();
+    long_prefix.add_assign_constant(u64_to_fe(base + RLP_MAX_SHORT_LENGTH));
+    for (k, flag) in len_of_len_is.iter().enumerate() {
+        long_prefix.add_assign_boolean_with_coeff(flag, u64_to_fe(k as u64 + 1));
+    }
+    let long_prefix = long_prefix.into_num(cs)?;
+    let mut prefix = LinearCombination::zero();
+    prefix.add_assign_number_with_coeff(&Num::mask(cs, &short_prefix, &is_short)?, E::Fr::one());
+    prefix.add_assign_number_with_coeff(&Num::mask(cs, &long_prefix, &is_long)?, E::Fr::one());
+    let prefix = prefix.into_num(cs)?;
+
+    // exactly one of these is set, entry h if the header is h bytes long
+    let mut header_len_is = vec![is_single, is_short];
+    for flag in len_of_len_is.iter() {
+        header_len_is.push(Boolean::and(cs, &is_long, flag)?);
+    }
+
+    let mut encoded_len = LinearCombination::zero();
+    encoded_len.add_assign_number_with_coeff(payload_len, E::Fr::one());
+    for (h, flag) in header_len_is.iter().enumerate() {
+        encoded_len.add_assign_boolean_with_coeff(flag, u64_to_fe(h as u64));
+    }
+    let encoded_len = encoded_len.into_num(cs)?;
+
+    let mut encoded = Vec::with_capacity(payload.len() + 1 + RLP_MAX_LENGTH_BYTES);
+    for i in 0..(payload.len() + 1 + RLP_MAX_LENGTH_BYTES) {
+        let mut lc = LinearCombination::zero();
+        for (h, flag) in header_len_is.iter().enumerate() {
+            // prefix, then the length bytes in big endian, then the payload
+            let byte = if i == 0 && h > 0 {
+                prefix
+            } else if i < h {
+                len_bytes[h - 1 - i].inner
+            } else if i - h < payload.len() {
+                payload[i - h].inner
+            } else {
+                continue;
+            };
+            lc.add_assign_number_with_coeff(&Num::mask(cs, &byte, flag)?, E::Fr::one());
+        }
+        encoded.push(Byte::from_num_unconstrained(cs, lc.into_num(cs)?));
+    }
+
+    Ok((encoded, encoded_len))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use crate::bellman::plonk::better_better_cs::cs::*;
+
+    fn native_header(len: usize, base: u8) -> Vec<u8> {
+        if len as u64 <= RLP_MAX_SHORT_LENGTH {
+            return vec![base + len as u8];
+        }
+        let len_bytes: Vec<u8> = (len as u64).to_be_bytes().iter().cloned().skip_while(|b| *b == 0).collect();
+        let mut header = vec![base + RLP_MAX_SHORT_LENGTH as u8 + len_bytes.len() as u8];
+        header.extend(len_bytes);
+
+        header
+    }
+
+    fn native_encode_bytes(bytes: &[u8]) -> Vec<u8> {
+        if bytes.len() == 1 && bytes[0] < 0x80 {
+            return bytes.to_vec();
+        }
+        let mut encoded = native_header(bytes.len(), RLP_STRING_OFFSET as u8);
+        encoded.extend_from_slice(bytes);
+
+        encoded
+    }
+
+    fn native_encode_list(items: &[Vec<u8>]) -> Vec<u8> {
+        let payload: Vec<u8> = items.concat();
+        let mut encoded = native_header(payload.len(), RLP_LIST_OFFSET as u8);
+        encoded.extend(payload);
+
+        encoded
+    }
+
+    fn alloc_bytes<CS: ConstraintSystem<Bn256>>(cs: &mut CS, bytes: &[u8], padded_len: usize) -> Vec<Byte<Bn256>> {
+        let mut bytes = bytes.to_vec();
+        bytes.resize(padded_len, 0);
+
+        bytes.iter().map(|b| Byte::from_u8_witness(cs, Some(*b)).unwrap()).collect()
+    }
+
+    fn value(num: &Num<Bn256>) -> Fr {
+        num.get_value().unwrap()
+    }
+
+    #[test]
+    fn test_rlp_decode_list() {
+        let long_string = vec![0x42u8; 60];
+        let nested = native_encode_list(&[native_encode_bytes(b"cat")]);
+        let items = vec![
+            native_encode_bytes(b"dog"),
+            native_encode_bytes(&long_string),
+            native_encode_bytes(&[0x05]),
+            nested.clone(),
+            native_encode_bytes(&[]),
+        ];
+        let encoded = native_encode_list(&items);
+
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+        let data = alloc_bytes(&mut cs, &encoded, encoded.len() + 8);
+        let data_len = Num::alloc(&mut cs, Some(u64_to_fe(encoded.len() as u64))).unwrap();
+
+        let list = decode_item(&mut cs, &data, &Num::zero(), &data_len).unwrap();
+        list.end.enforce_equal(&mut cs, &data_len).unwrap();
+        let (decoded, num_items) = decode_list_items(&mut cs, &data, &list, 7).unwrap();
+        assert!(cs.is_satisfied());
+
+        assert_eq!(value(&num_items), u64_to_fe(items.len() as u64));
+        assert_eq!(value(&list.payload_offset), u64_to_fe(2));
+        let expected_lens = [3u64, 60, 1, nested.len() as u64 - 1, 0];
+        let mut offset = 2;
+        for (item, (encoding, expected_len)) in decoded.iter().zip(items.iter().zip(expected_lens.iter())) {
+            assert_eq!(value(&item.offset), u64_to_fe(offset));
+            assert_eq!(value(&item.payload_len), u64_to_fe(*expected_len));
+            offset += encoding.len() as u64;
+            assert_eq!(value(&item.end), u64_to_fe(offset));
+        }
+        assert!(decoded[3].is_list.get_value().unwrap());
+        assert!(!decoded[1].is_list.get_value().unwrap());
+    }
+
+    #[test]
+    fn test_rlp_rejects_non_canonical() {
+        // 0x05 encoded as a short string, 3 encoded in long form and a long length with a leading zero
+        for encoded in vec![vec![0x81, 0x05], vec![0xb8, 0x03, 1, 2, 3], vec![0xb9, 0x00, 0x40]].into_iter() {
+            let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+            let data = alloc_bytes(&mut cs, &encoded, 80);
+            decode_item(&mut cs, &data, &Num::zero(), &Num::Constant(u64_to_fe(80))).unwrap();
+            assert!(!cs.is_satisfied());
+        }
+    }
+
+    #[test]
+    fn test_rlp_encode() {
+        let max_len = 300;
+        for bytes in vec![vec![], vec![0x05], vec![0x90], b"dog".to_vec(), vec![0x11; 55], vec![0x22; 60], vec![0x33; 300]].into_iter() {
+            let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+            let payload = alloc_bytes(&mut cs, &bytes, max_len);
+            let payload_len = Num::alloc(&mut cs, Some(u64_to_fe(bytes.len() as u64))).unwrap();
+
+            for is_list in vec![false, true].into_iter() {
+                let (encoded, encoded_len) = if is_list {
+                    encode_list(&mut cs, &payload, &payload_len).unwrap()
+                } else {
+                    encode_bytes(&mut cs, &payload, &payload_len).unwrap()
+                };
+                let expected = if is_list {
+                    let mut encoded = native_header(bytes.len(), RLP_LIST_OFFSET as u8);
+                    encoded.extend_from_slice(&bytes);
+                    encoded
+                } else {
+                    native_encode_bytes(&bytes)
+                };
+
+                assert_eq!(value(&encoded_len), u64_to_fe(expected.len() as u64));
+                let encoded: Vec<u8> = encoded[..expected.len()].iter().map(|b| b.get_byte_value().unwrap()).collect();
+                assert_eq!(encoded, expected);
+            }
+            assert!(cs.is_satisfied());
+        }
+    }
+}
@@ -0,0 +1,427 @@
+use crate::bellman::pairing::{
+    Engine,
+};
+
+use crate::bellman::pairing::ff::{
+    Field,
+    PrimeField,
+};
+
+use crate::bellman::{
+    SynthesisError,
+};
+
+use crate::bellman::plonk::better_better_cs::cs::*;
+use crate::bellman::plonk::better_better_cs::lookup_tables::*;
+
+use super::allocated_num::{AllocatedNum, Num};
+use super::boolean::Boolean;
+use super::linear_combination::LinearCombination;
+use super::hashes_with_tables::tables::add_table_once;
+use super::merkle_tree::compute_root;
+use super::poseidon::PoseidonCsSBox;
+
+use crate::plonk::circuit::Assignment;
+use crate::poseidon::{PoseidonEngine, PoseidonHashParams};
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+// Membership in a set of constants. The product (x - c_0) * ... * (x - c_{n-1}) vanishes iff x
+// is in the set and costs one gate per element. A lookup costs a single gate, but the table
+// adds n rows to the circuit, so it pays off once the same set is checked several times.
+// Non-membership in a large committed set is shown by a pair of adjacent leaves of a sorted
+// Merkle tree around the value.
+
+// acc * (x - c) as a single gate
+fn mul_by_difference<E, CS>(
+    cs: &mut CS,
+    acc: &AllocatedNum<E>,
+    x: &AllocatedNum<E>,
+    c: &E::Fr
+) -> Result<AllocatedNum<E>, SynthesisError>
+    where E: Engine,
+          CS: ConstraintSystem<E>
+{
+    let result = AllocatedNum::alloc(cs, || {
+        let mut tmp = *x.get_value().get()?;
+        tmp.sub_assign(c);
+        tmp.mul_assign(acc.get_value().get()?);
+
+        Ok(tmp)
+    })?;
+
+    let mut minus_c = *c;
+    minus_c.negate();
+
+    let mut term = MainGateTerm::new();
+    term.add_assign(ArithmeticTerm::from_variable(acc.get_variable()).mul_by_variable(x.get_variable()));
+    term.add_assign(ArithmeticTerm::from_variable_and_coeff(acc.get_variable(), minus_c));
+    term.sub_assign(ArithmeticTerm::from_variable(result.get_variable()));
+    cs.allocate_main_gate(term)?;
+
+    Ok(result)
+}
+
+// (x - c_0) * ... * (x - c_{n-1})
+fn vanishing_product<E, CS>(
+    cs: &mut CS,
+    x: &AllocatedNum<E>,
+    set: &[E::Fr]
+) -> Result<AllocatedNum<E>, SynthesisError>
+    where E: Engine,
+          CS: ConstraintSystem<E>
+{
+    assert!(set.len() > 0);
+
+    let mut acc = x.sub_constant(cs, set[0])?;
+    for c in set[1..].iter() {
+        acc = mul_by_difference(cs, &acc, x, c)?;
+    }
+
+    Ok(acc)
+}
+
+/// Enforces that `x` is one of the elements of `set`.
+pub fn enforce_membership<E, CS>(
+    cs: &mut CS,
+    x: &Num<E>,
+    set: &[E::Fr]
+) -> Result<(), SynthesisError>
+    where E: Engine,
+          CS: ConstraintSystem<E>
+{
+    match x {
+        Num::Constant(value) => {
+            if set.contains(value) {
+                Ok(())
+            } else {
+                Err(SynthesisError::Unsatisfiable)
+            }
+        },
+        Num::Variable(var) => {
+            let product = vanishing_product(cs, var, set)?;
+
+            product.assert_equal_to_constant(cs, E::Fr::zero())
+        }
+    }
+}
+
+/// Returns whether `x` is one of the elements of `set`.
+pub fn is_member<E, CS>(
+    cs: &mut CS,
+    x: &Num<E>,
+    set: &[E::Fr]
+) -> Result<Boolean, SynthesisError>
+    where E: Engine,
+          CS: ConstraintSystem<E>
+{
+    match x {
+        Num::Constant(value) => Ok(Boolean::constant(set.contains(value))),
+        Num::Variable(var) => vanishing_product(cs, var, set)?.is_zero(cs),
+    }
+}
+
+// every row is (c, c, c) for c in the set
+#[derive(Clone)]
+pub struct ConstantSetTable<E: Engine> {
+    elements: Vec<E::Fr>,
+    element_set: HashSet<E::Fr>,
+    name: &'static str,
+}
+
+impl<E: Engine> ConstantSetTable<E> {
+    pub fn new(set: &[E::Fr], name: &'static str) -> Self {
+        let mut elements = Vec::with_capacity(set.len());
+        let mut element_set = HashSet::with_capacity(set.len());
+        for c in set.iter() {
+            if element_set.insert(*c) {
+                elements.push(*c);
+            }
+        }
+        assert!(elements.len() > 0);
+
+        Self {
+            elements,
+            element_set,
+            name,
+        }
+    }
+}
+
+impl<E: Engine> std::fmt::Debug for ConstantSetTable<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConstantSetTable")
+            .field("name", &self.name)
+            .field("size", &self.elements.len())
+            .finish()
+    }
+}
+
+impl<E: Engine> LookupTableInternal<E> for ConstantSetTable<E> {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+    fn table_size(&self) -> usize {
+        self.elements.len()
+    }
+    fn num_keys(&self) -> usize {
+        1
+    }
+    fn num_values(&self) -> usize {
+        2
+    }
+    fn allows_combining(&self) -> bool {
+        true
+    }
+    fn get_table_values_for_polys(&self) -> Vec<Vec<E::Fr>> {
+        vec![self.elements.clone(), self.elements.clone(), self.elements.clone()]
+    }
+    fn table_id(&self) -> E::Fr {
+        table_id_from_string(self.name)
+    }
+    fn sort(&self, _values: &[E::Fr], _column: usize) -> Result<Vec<E::Fr>, SynthesisError> {
+        unimplemented!()
+    }
+    fn box_clone(&self) -> Box<dyn LookupTableInternal<E>> {
+        Box::from(self.clone())
+    }
+    fn column_is_trivial(&self, column_num: usize) -> bool {
+        assert!(column_num < 3);
+        false
+    }
+
+    fn is_valid_entry(&self, keys: &[E::Fr], values: &[E::Fr]) -> bool {
+        assert!(keys.len() == self.num_keys());
+        assert!(values.len() == self.num_values());
+
+        self.element_set.contains(&keys[0]) && values[0] == keys[0] && values[1] == keys[0]
+    }
+
+    fn query(&self, keys: &[E::Fr]) -> Result<Vec<E::Fr>, SynthesisError> {
+        assert!(keys.len() == self.num_keys());
+
+        if self.element_set.contains(&keys[0]) {
+            return Ok(vec![keys[0], keys[0]]);
+        }
+
+        Err(SynthesisError::Unsatisfiable)
+    }
+}
+
+/// Adds the table of `set` under `name` if there is no table with this name yet, every set
+/// needs a name of its own.
+pub fn add_constant_set_table<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    set: &[E::Fr],
+    name: &'static str
+) -> Result<Arc<LookupTableApplication<E>>, SynthesisError> {
+    let columns = vec![
+        PolyIdentifier::VariablesPolynomial(0),
+        PolyIdentifier::VariablesPolynomial(1),
+        PolyIdentifier::VariablesPolynomial(2)
+    ];
+
+    let table = LookupTableApplication::new(
+        name,
+        ConstantSetTable::new(set, name),
+        columns,
+        None,
+        true
+    );
+
+    add_table_once(cs, table)
+}
+
+/// Same as `enforce_membership` with a single lookup into the table of `set` named `name`.
+pub fn enforce_membership_by_lookup<E, CS>(
+    cs: &mut CS,
+    x: &Num<E>,
+    set: &[E::Fr],
+    name: &'static str
+) -> Result<(), SynthesisError>
+    where E: Engine,
+          CS: ConstraintSystem<E>
+{
+    let var = match x {
+        Num::Constant(value) => {
+            if set.contains(value) {
+                return Ok(());
+            } else {
+                return Err(SynthesisError::Unsatisfiable);
+            }
+        },
+        Num::Variable(var) => var.get_variable(),
+    };
+    let table = add_constant_set_table(cs, set, name)?;
+
+    cs.begin_gates_batch_for_step()?;
+
+    let vars = [var, var, var, var];
+    cs.allocate_variables_without_gate(
+        &vars,
+        &[]
+    )?;
+    cs.apply_single_lookup_gate(&vars[..table.width()], table.clone())?;
+
+    cs.end_gates_batch_for_step()?;
+
+    Ok(())
+}
+
+// sum 2^i * bits[i]
+fn pack_bits<E: Engine>(bits: &[Boolean]) -> LinearCombination<E> {
+    let mut lc = LinearCombination::zero();
+    let mut coeff = E::Fr::one();
+    for bit in bits.iter() {
+        lc.add_assign_boolean_with_coeff(bit, coeff);
+        coeff.double();
+    }
+
+    lc
+}
+
+/// Enforces that `x` is not in the sorted list committed to by the Merkle tree with `root`
+/// (see `merkle_tree`): `low` and `high` are the leaves at positions `low_index_bits` and the
+/// next one, and low < x < high. All values are compared as `value_bits` bit integers, `x` is
+/// range checked and the leaves are expected to be in range, which holds for a well formed list.
+/// The list should contain 0 and 2^value_bits - 1 so that every other value has neighbours.
+pub fn enforce_non_membership<E, CS>(
+    cs: &mut CS,
+    root: &Num<E>,
+    x: &Num<E>,
+    low: &Num<E>,
+    low_path: &[Num<E>],
+    low_index_bits: &[Boolean],
+    high: &Num<E>,
+    high_path: &[Num<E>],
+    high_index_bits: &[Boolean],
+    value_bits: usize,
+    params: &E::Params
+) -> Result<(), SynthesisError>
+    where E: PoseidonEngine,
+          CS: ConstraintSystem<E>,
+          <<E as PoseidonEngine>::Params as PoseidonHashParams<E>>::SBox: PoseidonCsSBox<E>
+{
+    assert!(value_bits < E::Fr::CAPACITY as usize);
+    assert_eq!(low_index_bits.len(), high_index_bits.len());
+
+    // the leaves are adjacent and both in the tree
+    let mut index_difference = pack_bits::<E>(high_index_bits);
+    let mut minus_one = E::Fr::one();
+    minus_one.negate();
+    index_difference.add_assign_scaled(&pack_bits::<E>(low_index_bits), minus_one);
+    index_difference.add_assign_constant(minus_one);
+    index_difference.enforce_zero(cs)?;
+
+    compute_root(cs, low, low_path, low_index_bits, params)?.enforce_equal(cs, root)?;
+    compute_root(cs, high, high_path, high_index_bits, params)?.enforce_equal(cs, root)?;
+
+    // for values below 2^value_bits, b - a - 1 fits into value_bits bits iff a < b
+    x.enforce_bit_length(cs, value_bits)?;
+    for (a, b) in [(low, x), (x, high)].iter() {
+        let mut difference = LinearCombination::zero();
+        difference.add_assign_number_with_coeff(b, E::Fr::one());
+        difference.add_assign_number_with_coeff(a, minus_one);
+        difference.add_assign_constant(minus_one);
+        difference.into_num(cs)?.enforce_bit_length(cs, value_bits)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use crate::plonk::circuit::utils::u64_to_fe;
+
+    fn chain_ids() -> Vec<Fr> {
+        vec![1u64, 10, 137, 42161, 324].into_iter().map(u64_to_fe).collect()
+    }
+
+    #[test]
+    fn test_membership_by_product() {
+        let set = chain_ids();
+
+        for (value, expected) in vec![(137u64, true), (324, true), (5, false)].into_iter() {
+            let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+            let x = Num::alloc(&mut cs, Some(u64_to_fe(value))).unwrap();
+
+            let flag = is_member(&mut cs, &x, &set).unwrap();
+            assert_eq!(flag.get_value().unwrap(), expected);
+            enforce_membership(&mut cs, &x, &set).unwrap();
+            assert_eq!(cs.is_satisfied(), expected);
+        }
+    }
+
+    #[test]
+    fn test_membership_by_lookup() {
+        let set = chain_ids();
+
+        for (value, expected) in vec![(10u64, true), (42161, true), (11, false)].into_iter() {
+            let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+            let x = Num::alloc(&mut cs, Some(u64_to_fe(value))).unwrap();
+            let y = Num::alloc(&mut cs, Some(u64_to_fe(1))).unwrap();
+
+            enforce_membership_by_lookup(&mut cs, &x, &set, "chain ids").unwrap();
+            enforce_membership_by_lookup(&mut cs, &y, &set, "chain ids").unwrap();
+            assert_eq!(cs.is_satisfied(), expected);
+        }
+    }
+
+    #[test]
+    fn test_membership_of_constants() {
+        let set = chain_ids();
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+
+        for (value, expected) in vec![(137u64, true), (5, false)].into_iter() {
+            let x = Num::Constant(u64_to_fe(value));
+            assert_eq!(is_member(&mut cs, &x, &set).unwrap().get_value().unwrap(), expected);
+            assert_eq!(enforce_membership(&mut cs, &x, &set).is_ok(), expected);
+            assert_eq!(enforce_membership_by_lookup(&mut cs, &x, &set, "chain ids").is_ok(), expected);
+        }
+        match enforce_membership(&mut cs, &Num::Constant(u64_to_fe(5)), &set) {
+            Err(SynthesisError::Unsatisfiable) => {},
+            _ => panic!("expected Unsatisfiable"),
+        }
+    }
+
+    #[test]
+    fn test_sorted_list_non_membership() {
+        use crate::merkle_tree::IncrementalMerkleTree;
+        use crate::poseidon::Bn256PoseidonParams;
+
+        let params = Bn256PoseidonParams::new_checked_2_into_1();
+        let value_bits = 32;
+        let depth = 3;
+        let list: Vec<Fr> = vec![0u64, 5, 9, 20, 33, 40, 77, (1 << 32) - 1].into_iter().map(u64_to_fe).collect();
+        let mut tree = IncrementalMerkleTree::<Bn256>::new(depth, &params);
+        for leaf in list.iter() {
+            tree.append_and_mark(*leaf, &params).unwrap();
+        }
+
+        // (x, position of the low neighbour, expected)
+        for (x, low_index, expected) in vec![(25u64, 3u64, true), (33, 3, false), (33, 4, false), (25, 2, false), (6, 1, true)].into_iter() {
+            let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+            let root = Num::alloc(&mut cs, Some(tree.root())).unwrap();
+            let x = Num::alloc(&mut cs, Some(u64_to_fe(x))).unwrap();
+
+            let mut neighbours = vec![];
+            for index in vec![low_index, low_index + 1].into_iter() {
+                let witness = tree.witness(index).unwrap();
+                let leaf = Num::alloc(&mut cs, Some(witness.leaf)).unwrap();
+                let path: Vec<_> = witness.path.iter().map(|p| Num::alloc(&mut cs, Some(*p)).unwrap()).collect();
+                let bits: Vec<_> = witness.index_bits().into_iter().map(|b| Boolean::alloc(&mut cs, Some(b)).unwrap()).collect();
+                neighbours.push((leaf, path, bits));
+            }
+            let (low, low_path, low_bits) = &neighbours[0];
+            let (high, high_path, high_bits) = &neighbours[1];
+
+            enforce_non_membership(
+                &mut cs, &root, &x, low, low_path, low_bits, high, high_path, high_bits, value_bits, &params
+            ).unwrap();
+            assert_eq!(cs.is_satisfied(), expected);
+        }
+    }
+}
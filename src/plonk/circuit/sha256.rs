@@ -45,8 +45,10 @@ use super::boolean::{
     Boolean
 };
 
+use super::allocated_num::Num;
 use super::multieq::MultiEq;
 use super::uint32::UInt32;
+use super::utils::u64_to_fe;
 
 const ROUND_CONSTANTS: [u32; 64] = [
     0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
@@ -90,38 +92,235 @@ pub fn sha256<E, CS>(
 {
     assert!(input.len() % 8 == 0);
 
-    let mut padded = input.to_vec();
-    let plen = padded.len() as u64;
-    // append a single '1' bit
-    padded.push(Boolean::constant(true));
-    // append K '0' bits, where K is the minimum number >= 0 such that L + 1 + K + 64 is a multiple of 512
-    while (padded.len() + 64) % 512 != 0 {
-        padded.push(Boolean::constant(false));
+    let mut hasher = Sha256Hasher::new();
+    hasher.update(cs, input)?;
+
+    hasher.finalize(cs)
+}
+
+/// Incremental SHA-256, so the input doesn't have to be assembled in one place.
+/// Full blocks are compressed as soon as they are available.
+#[derive(Clone)]
+pub struct Sha256Hasher {
+    state: Vec<UInt32>,
+    buffer: Vec<Boolean>,
+    length: u64,
+}
+
+impl Sha256Hasher {
+    pub fn new() -> Self {
+        Self {
+            state: get_sha256_iv(),
+            buffer: Vec::with_capacity(512),
+            length: 0,
+        }
+    }
+
+    pub fn update<E, CS>(
+        &mut self,
+        cs: &mut CS,
+        input: &[Boolean]
+    ) -> Result<(), SynthesisError>
+        where E: Engine, CS: ConstraintSystem<E>
+    {
+        self.length += input.len() as u64;
+        self.buffer.extend_from_slice(input);
+
+        if self.buffer.len() >= 512 {
+            let num_full_bits = self.buffer.len() - self.buffer.len() % 512;
+            let rest = self.buffer.split_off(num_full_bits);
+            for block in self.buffer.chunks(512) {
+                self.state = sha256_compression_function(
+                    cs,
+                    block,
+                    &self.state
+                )?;
+            }
+            self.buffer = rest;
+        }
+
+        Ok(())
+    }
+
+    /// Pads the input and returns the digest as 256 big-endian bits.
+    pub fn finalize<E, CS>(
+        mut self,
+        cs: &mut CS
+    ) -> Result<Vec<Boolean>, SynthesisError>
+        where E: Engine, CS: ConstraintSystem<E>
+    {
+        assert!(self.length % 8 == 0);
+
+        let plen = self.length;
+        let mut padding = vec![];
+        // append a single '1' bit
+        padding.push(Boolean::constant(true));
+        // append K '0' bits, where K is the minimum number >= 0 such that L + 1 + K + 64 is a multiple of 512
+        while (self.buffer.len() + padding.len() + 64) % 512 != 0 {
+            padding.push(Boolean::constant(false));
+        }
+        // append L as a 64-bit big-endian integer, making the total post-processed length a multiple of 512 bits
+        for b in (0..64).rev().map(|i| (plen >> i) & 1 == 1) {
+            padding.push(Boolean::constant(b));
+        }
+
+        self.update(cs, &padding)?;
+        assert!(self.buffer.is_empty());
+
+        Ok(self.state.into_iter()
+        .flat_map(|e| e.into_bits_be())
+        .collect())
+    }
+}
+
+// sum of mutually exclusive flags is a boolean itself
+fn sum_exclusive_flags<E, CS>(
+    cs: &mut CS,
+    flags: &[Boolean]
+) -> Result<Boolean, SynthesisError>
+    where E: Engine, CS: ConstraintSystem<E>
+{
+    let mut lc = LinearCombination::zero();
+    for flag in flags.iter() {
+        lc.add_assign_boolean_with_coeff(flag, E::Fr::one());
+    }
+
+    Ok(match lc.into_num(cs)? {
+        Num::Constant(value) => Boolean::constant(!value.is_zero()),
+        Num::Variable(var) => Boolean::from(AllocatedBit::from_allocated_num_unchecked(var)),
+    })
+}
+
+/// SHA-256 of the first `length` bytes of `input`, where `length` is a witness not exceeding
+/// the byte length of `input`. Padding is done in-circuit and everything after the first
+/// `length` bytes is ignored, the result doesn't depend on it. The cost is the one of hashing
+/// the longest possible message.
+pub fn sha256_variable_length<E, CS>(
+    cs: &mut CS,
+    input: &[Boolean],
+    length: &Num<E>
+) -> Result<Vec<Boolean>, SynthesisError>
+    where E: Engine, CS: ConstraintSystem<E>
+{
+    assert!(input.len() % 8 == 0);
+
+    let max_length = input.len() / 8;
+    let num_blocks = (max_length + 9 + 63) / 64;
+
+    // is_end[j] is set iff length == j, exactly one of them is set
+    let mut is_end = Vec::with_capacity(max_length + 1);
+    let mut lc = LinearCombination::zero();
+    for j in 0..=max_length {
+        let flag = Num::equals(cs, length, &Num::Constant(u64_to_fe(j as u64)))?;
+        lc.add_assign_boolean_with_coeff(&flag, E::Fr::one());
+        is_end.push(flag);
+    }
+    let mut minus_one = E::Fr::one();
+    minus_one.negate();
+    lc.add_assign_constant(minus_one);
+    lc.enforce_zero(cs)?;
+
+    // the length in bits is encoded in the last 64 bits of the block that contains the byte length + 8
+    let mut is_last_block = Vec::with_capacity(num_blocks);
+    for block in 0..num_blocks {
+        let flags: Vec<_> = (0..=max_length).filter(|j| (j + 8) / 64 == block)
+                                            .map(|j| is_end[j].clone())
+                                            .collect();
+        is_last_block.push(sum_exclusive_flags(cs, &flags)?);
     }
-    // append L as a 64-bit big-endian integer, making the total post-processed length a multiple of 512 bits
-    for b in (0..64).rev().map(|i| (plen >> i) & 1 == 1) {
-        padded.push(Boolean::constant(b));
+
+    let length_num_bits = (0usize.leading_zeros() - max_length.leading_zeros()) as usize;
+    let mut bit_length_le = vec![Boolean::constant(false); 3];
+    bit_length_le.extend(length.into_bits_le(cs, Some(length_num_bits))?);
+    bit_length_le.resize(64, Boolean::constant(false));
+    let bit_length_be: Vec<_> = bit_length_le.into_iter().rev().collect();
+
+    let mut padded = Vec::with_capacity(num_blocks * 512);
+    let mut is_message = Boolean::constant(true);
+    for i in 0..(num_blocks * 64) {
+        if i <= max_length {
+            is_message = Boolean::and(cs, &is_message, &is_end[i].not())?;
+        }
+
+        for bit_i in 0..8 {
+            let mut bit = if i < max_length {
+                Boolean::and(cs, &is_message, &input[i * 8 + bit_i])?
+            } else {
+                Boolean::constant(false)
+            };
+
+            // a single '1' bit right after the message
+            if bit_i == 0 && i <= max_length {
+                bit = Boolean::xor(cs, &bit, &is_end[i])?;
+            }
+
+            if i % 64 >= 56 {
+                let length_bit = &bit_length_be[(i % 64 - 56) * 8 + bit_i];
+                let length_bit = Boolean::and(cs, &is_last_block[i / 64], length_bit)?;
+                bit = Boolean::xor(cs, &bit, &length_bit)?;
+            }
+
+            padded.push(bit);
+        }
     }
-    assert!(padded.len() % 512 == 0);
 
     let mut cur = get_sha256_iv();
-    for (_i, block) in padded.chunks(512).enumerate() {
+    let mut result: Vec<Boolean> = vec![];
+    for (block_i, block) in padded.chunks(512).enumerate() {
         cur = sha256_compression_function(
             cs,
             block,
             &cur
         )?;
+
+        let block_output = cur.iter().flat_map(|e| e.into_bits_be());
+        if block_i == 0 {
+            result = block_output.collect();
+        } else {
+            result = block_output.zip(result.iter())
+                                 .map(|(new, old)| Boolean::conditionally_select(cs, &is_last_block[block_i], &new, old))
+                                 .collect::<Result<_, _>>()?;
+        }
     }
 
-    Ok(cur.into_iter()
-    .flat_map(|e| e.into_bits_be())
-    .collect())
+    Ok(result)
 }
 
 pub fn get_sha256_iv() -> Vec<UInt32> {
     IV.iter().map(|&v| UInt32::constant(v)).collect()
 }
 
+/// The initial SHA-256 state as 256 big-endian bits, to be used with `sha256_compress`.
+pub fn get_sha256_iv_bits() -> Vec<Boolean> {
+    get_sha256_iv().into_iter().flat_map(|e| e.into_bits_be()).collect()
+}
+
+/// SHA-256 compression of a 512 bit `block` into a 256 bit `state`, both are
+/// big-endian bit strings as in the standard. No padding is applied, so it can be used
+/// to build custom Merkle-Damgard constructions, double SHA-256 or truncated variants.
+pub fn sha256_compress<E, CS>(
+    cs: &mut CS,
+    state: &[Boolean],
+    block: &[Boolean]
+) -> Result<Vec<Boolean>, SynthesisError>
+    where E: Engine, CS: ConstraintSystem<E>
+{
+    assert_eq!(state.len(), 256);
+    assert_eq!(block.len(), 512);
+
+    let state: Vec<_> = state.chunks(32).map(|e| UInt32::from_bits_be(e)).collect();
+
+    Ok(sha256_compression_function(
+        cs,
+        block,
+        &state
+    )?
+    .into_iter()
+    .flat_map(|e| e.into_bits_be())
+    .collect())
+}
+
+/// Same as `sha256_compress` but the state is represented by eight 32 bit words.
 pub fn sha256_compression_function<E, CS>(
     cs: &mut CS,
     input: &[Boolean],
@@ -460,4 +659,120 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn test_variable_length() {
+        use sha2::{Sha256, Digest};
+
+        let mut rng = XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        let max_length = 120;
+        for &length in [0, 1, 55, 56, 63, 64, 119, 120].iter() {
+            let data: Vec<u8> = (0..max_length).map(|_| rng.gen()).collect();
+            let expected = Sha256::digest(&data[0..length]);
+
+            let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+            let mut input_bits = vec![];
+            for input_byte in data.iter() {
+                for bit_i in (0..8).rev() {
+                    input_bits.push(AllocatedBit::alloc(&mut cs, Some((input_byte >> bit_i) & 1u8 == 1u8)).unwrap().into());
+                }
+            }
+            let length_num = Num::alloc(&mut cs, Some(Fr::from_str(&length.to_string()).unwrap())).unwrap();
+
+            let r = sha256_variable_length(&mut cs, &input_bits, &length_num).unwrap();
+
+            assert!(cs.is_satisfied());
+
+            let expected_bits = expected.iter().flat_map(|&byte| (0..8).rev().map(move |i| (byte >> i) & 1u8 == 1u8));
+            for (b, e) in r.iter().zip(expected_bits) {
+                assert_eq!(b.get_value().unwrap(), e);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_variable_length_is_bounded() {
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+        let input_bits: Vec<_> = (0..64).map(|_| Boolean::from(AllocatedBit::alloc(&mut cs, Some(true)).unwrap())).collect();
+        let length_num = Num::alloc(&mut cs, Some(Fr::from_str("9").unwrap())).unwrap();
+
+        // the length exceeds the input, so none of the end flags can be set
+        sha256_variable_length(&mut cs, &input_bits, &length_num).unwrap();
+    }
+
+    #[test]
+    fn test_compress_chaining() {
+        use sha2::{Sha256, Digest};
+
+        let mut rng = XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        // 119 bytes are padded into exactly two blocks
+        let data: Vec<u8> = (0..119).map(|_| rng.gen()).collect();
+        let mut padded = data.clone();
+        padded.push(0x80);
+        padded.extend_from_slice(&[0u8; 6]);
+        padded.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+        assert_eq!(padded.len(), 128);
+
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+        let padded_bits: Vec<Boolean> = padded.iter()
+            .flat_map(|&byte| (0..8).rev().map(move |i| (byte >> i) & 1u8 == 1u8))
+            .map(|b| AllocatedBit::alloc(&mut cs, Some(b)).unwrap().into())
+            .collect();
+
+        let mut state = get_sha256_iv_bits();
+        for block in padded_bits.chunks(512) {
+            state = sha256_compress(&mut cs, &state, block).unwrap();
+        }
+
+        assert!(cs.is_satisfied());
+
+        let expected = Sha256::digest(&data);
+        let expected_bits = expected.iter().flat_map(|&byte| (0..8).rev().map(move |i| (byte >> i) & 1u8 == 1u8));
+        for (b, e) in state.iter().zip(expected_bits) {
+            assert_eq!(b.get_value().unwrap(), e);
+        }
+    }
+
+    #[test]
+    fn test_streaming() {
+        use sha2::{Sha256, Digest};
+
+        let mut rng = XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        // around the padding and block boundaries
+        for &input_len in [0usize, 55, 56, 64, 200].iter() {
+            let data: Vec<u8> = (0..input_len).map(|_| rng.gen()).collect();
+
+            let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+            let input_bits: Vec<Boolean> = data.iter()
+                .flat_map(|&byte| (0..8).rev().map(move |i| (byte >> i) & 1u8 == 1u8))
+                .map(|b| AllocatedBit::alloc(&mut cs, Some(b)).unwrap().into())
+                .collect();
+
+            // chunks are not aligned to bytes or blocks
+            let mut hasher = Sha256Hasher::new();
+            let mut start = 0;
+            for &len in [3, 509, 1, 700].iter().cycle() {
+                if start == input_bits.len() {
+                    break;
+                }
+                let end = ::std::cmp::min(start + len, input_bits.len());
+                hasher.update(&mut cs, &input_bits[start..end]).unwrap();
+                start = end;
+            }
+            let result = hasher.finalize(&mut cs).unwrap();
+
+            assert!(cs.is_satisfied());
+
+            let expected = Sha256::digest(&data);
+            let expected_bits = expected.iter().flat_map(|&byte| (0..8).rev().map(move |i| (byte >> i) & 1u8 == 1u8));
+            assert_eq!(result.len(), 256);
+            for (r, e) in result.iter().zip(expected_bits) {
+                assert_eq!(r.get_value().unwrap(), e);
+            }
+        }
+    }
 }
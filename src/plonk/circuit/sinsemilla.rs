@@ -0,0 +1,279 @@
+use crate::bellman::pairing::{
+    Engine,
+};
+
+use crate::bellman::pairing::ff::{
+    Field,
+    PrimeField,
+};
+
+use crate::bellman::{
+    SynthesisError,
+};
+
+use crate::bellman::plonk::better_better_cs::cs::*;
+use crate::bellman::plonk::better_better_cs::lookup_tables::*;
+
+use super::allocated_num::{AllocatedNum, Num};
+use super::boolean::Boolean;
+use super::linear_combination::LinearCombination;
+use super::edwards::{CircuitTwistedEdwardsCurveImplementor, CircuitTwistedEdwardsPoint};
+use super::hashes_with_tables::tables::add_table_once;
+use super::utils::u64_to_fe;
+
+use crate::generic_twisted_edwards::TwistedEdwardsCurveParams;
+use crate::sinsemilla::{SinsemillaParams, SINSEMILLA_K};
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+pub const SINSEMILLA_S_TABLE_NAME: &'static str = "sinsemilla_s_table";
+
+// for columns (a, b, c) asserts that (b, c) are the affine coordinates of S(a)
+#[derive(Clone)]
+pub struct SinsemillaTable<E: Engine> {
+    table_entries: [Vec<E::Fr>; 3],
+    table_lookup_map: HashMap<E::Fr, (E::Fr, E::Fr)>,
+    name: &'static str,
+}
+
+impl<E: Engine> SinsemillaTable<E> {
+    pub fn new(params: &SinsemillaParams<E>, name: &'static str) -> Self {
+        let table_size = 1 << SINSEMILLA_K;
+        assert_eq!(params.s.len(), table_size);
+
+        let mut keys = Vec::with_capacity(table_size);
+        let mut xs = Vec::with_capacity(table_size);
+        let mut ys = Vec::with_capacity(table_size);
+        let mut map = HashMap::with_capacity(table_size);
+
+        for (j, &(x, y)) in params.s.iter().enumerate() {
+            let key = u64_to_fe(j as u64);
+            keys.push(key);
+            xs.push(x);
+            ys.push(y);
+            map.insert(key, (x, y));
+        }
+
+        Self {
+            table_entries: [keys, xs, ys],
+            table_lookup_map: map,
+            name,
+        }
+    }
+}
+
+impl<E: Engine> std::fmt::Debug for SinsemillaTable<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SinsemillaTable")
+            .field("bits", &SINSEMILLA_K)
+            .finish()
+    }
+}
+
+impl<E: Engine> LookupTableInternal<E> for SinsemillaTable<E> {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+    fn table_size(&self) -> usize {
+        1 << SINSEMILLA_K
+    }
+    fn num_keys(&self) -> usize {
+        1
+    }
+    fn num_values(&self) -> usize {
+        2
+    }
+    fn allows_combining(&self) -> bool {
+        true
+    }
+    fn get_table_values_for_polys(&self) -> Vec<Vec<E::Fr>> {
+        vec![self.table_entries[0].clone(), self.table_entries[1].clone(), self.table_entries[2].clone()]
+    }
+    fn table_id(&self) -> E::Fr {
+        table_id_from_string(self.name)
+    }
+    fn sort(&self, _values: &[E::Fr], _column: usize) -> Result<Vec<E::Fr>, SynthesisError> {
+        unimplemented!()
+    }
+    fn box_clone(&self) -> Box<dyn LookupTableInternal<E>> {
+        Box::from(self.clone())
+    }
+    fn column_is_trivial(&self, column_num: usize) -> bool {
+        assert!(column_num < 3);
+        false
+    }
+
+    fn is_valid_entry(&self, keys: &[E::Fr], values: &[E::Fr]) -> bool {
+        assert!(keys.len() == self.num_keys());
+        assert!(values.len() == self.num_values());
+
+        if let Some(entry) = self.table_lookup_map.get(&keys[0]) {
+            return entry == &(values[0], values[1]);
+        }
+        false
+    }
+
+    fn query(&self, keys: &[E::Fr]) -> Result<Vec<E::Fr>, SynthesisError> {
+        assert!(keys.len() == self.num_keys());
+
+        if let Some(entry) = self.table_lookup_map.get(&keys[0]) {
+            return Ok(vec![entry.0, entry.1])
+        }
+
+        Err(SynthesisError::Unsatisfiable)
+    }
+}
+
+/// Adds the table of chunk generators to the constraint system if it's not there yet.
+pub fn add_sinsemilla_table<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    params: &SinsemillaParams<E>
+) -> Result<Arc<LookupTableApplication<E>>, SynthesisError> {
+    let columns = vec![
+        PolyIdentifier::VariablesPolynomial(0),
+        PolyIdentifier::VariablesPolynomial(1),
+        PolyIdentifier::VariablesPolynomial(2)
+    ];
+
+    let table = LookupTableApplication::new(
+        SINSEMILLA_S_TABLE_NAME,
+        SinsemillaTable::new(params, SINSEMILLA_S_TABLE_NAME),
+        columns,
+        None,
+        true
+    );
+
+    add_table_once(cs, table)
+}
+
+// one lookup row: (chunk, S(chunk).x, S(chunk).y)
+fn lookup_generator<E: Engine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    table: &Arc<LookupTableApplication<E>>,
+    chunk: &AllocatedNum<E>
+) -> Result<CircuitTwistedEdwardsPoint<E>, SynthesisError> {
+    let (x, y) = match chunk.get_value() {
+        None => (
+            AllocatedNum::alloc(cs, || Err(SynthesisError::AssignmentMissing))?,
+            AllocatedNum::alloc(cs, || Err(SynthesisError::AssignmentMissing))?
+        ),
+        Some(val) => {
+            let coordinates = table.query(&[val])?;
+            (
+                AllocatedNum::alloc(cs, || Ok(coordinates[0]))?,
+                AllocatedNum::alloc(cs, || Ok(coordinates[1]))?
+            )
+        },
+    };
+
+    cs.begin_gates_batch_for_step()?;
+
+    let vars = [chunk.get_variable(), x.get_variable(), y.get_variable(), chunk.get_variable()];
+    cs.allocate_variables_without_gate(
+        &vars,
+        &[]
+    )?;
+    cs.apply_single_lookup_gate(&vars[..table.width()], table.clone())?;
+
+    cs.end_gates_batch_for_step()?;
+
+    Ok(CircuitTwistedEdwardsPoint {
+        x: Num::Variable(x),
+        y: Num::Variable(y),
+    })
+}
+
+/// Sinsemilla hash of `message` into a curve point, matches `crate::sinsemilla::hash_to_point`.
+/// Every K bit chunk costs a lookup, a doubling and an addition.
+pub fn sinsemilla_hash_to_point<E, CS, C>(
+    cs: &mut CS,
+    curve: &CircuitTwistedEdwardsCurveImplementor<E, C>,
+    params: &SinsemillaParams<E>,
+    message: &[Boolean]
+) -> Result<CircuitTwistedEdwardsPoint<E>, SynthesisError>
+    where E: Engine,
+          CS: ConstraintSystem<E>,
+          C: TwistedEdwardsCurveParams<E>
+{
+    let table = add_sinsemilla_table(cs, params)?;
+
+    let (q_x, q_y) = params.q.into_xy();
+    let mut acc = CircuitTwistedEdwardsPoint::constant(q_x, q_y);
+
+    for chunk_bits in message.chunks(SINSEMILLA_K) {
+        let mut lc = LinearCombination::zero();
+        let mut coeff = E::Fr::one();
+        for bit in chunk_bits.iter() {
+            lc.add_assign_boolean_with_coeff(bit, coeff);
+            coeff.double();
+        }
+
+        let s = match lc.into_num(cs)? {
+            Num::Constant(value) => {
+                let index = value.into_repr().as_ref()[0] as usize;
+                let (x, y) = params.s[index];
+
+                CircuitTwistedEdwardsPoint::constant(x, y)
+            },
+            Num::Variable(chunk) => {
+                lookup_generator(cs, &table, &chunk)?
+            }
+        };
+
+        acc = curve.double(cs, &acc)?;
+        acc = curve.add(cs, &acc, &s)?;
+    }
+
+    Ok(acc)
+}
+
+/// Sinsemilla hash of `message`, the x coordinate of `sinsemilla_hash_to_point`.
+pub fn sinsemilla_hash<E, CS, C>(
+    cs: &mut CS,
+    curve: &CircuitTwistedEdwardsCurveImplementor<E, C>,
+    params: &SinsemillaParams<E>,
+    message: &[Boolean]
+) -> Result<Num<E>, SynthesisError>
+    where E: Engine,
+          CS: ConstraintSystem<E>,
+          C: TwistedEdwardsCurveParams<E>
+{
+    let point = sinsemilla_hash_to_point(cs, curve, params, message)?;
+
+    Ok(point.x)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use bellman::pairing::bn256::Bn256;
+    use crate::generic_twisted_edwards::bn256::AltBabyJubjubBn256;
+    use crate::plonk::circuit::edwards::bn256::CircuitAltBabyJubjubBn256;
+    use crate::sinsemilla;
+
+    #[test]
+    fn test_sinsemilla_gadget_matches_native() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let native_curve = AltBabyJubjubBn256::get_implementor();
+        let curve = CircuitAltBabyJubjubBn256::get_implementor();
+        let params = SinsemillaParams::new(&native_curve, b"test_domain");
+
+        // the last chunk is incomplete and some of the bits are constant
+        let message: Vec<bool> = (0..53).map(|_| rng.gen()).collect();
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+        let message_bits: Vec<Boolean> = message.iter().enumerate().map(|(i, b)| {
+            if i < 10 {
+                Boolean::constant(*b)
+            } else {
+                Boolean::alloc(&mut cs, Some(*b)).unwrap()
+            }
+        }).collect();
+
+        let hash = sinsemilla_hash(&mut cs, &curve, &params, &message_bits).unwrap();
+
+        assert!(cs.is_satisfied());
+        assert_eq!(hash.get_value().unwrap(), sinsemilla::hash(&native_curve, &params, &message));
+    }
+}
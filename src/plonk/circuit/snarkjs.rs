@@ -0,0 +1,405 @@
+use crate::bellman::pairing::Engine;
+
+use crate::bellman::pairing::ff::{Field, PrimeField, PrimeFieldRepr};
+
+use crate::bellman::SynthesisError;
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    ConstraintSystem,
+    GateInternal,
+    Index,
+    MainGate,
+    PlonkConstraintSystemParams,
+    PolyIdentifier,
+    TrivialAssembly,
+    Variable,
+    Width4MainGateWithDNext,
+};
+
+use byteorder::{LittleEndian, WriteBytesExt};
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+
+// Export of circuits in the binary formats of circom and snarkjs, see
+// https://github.com/iden3/r1csfile/blob/master/doc/r1cs_bin_format.md. A row of the width 4
+// main gate with D next
+//
+//      q_a.a + q_b.b + q_c.c + q_d.d + q_m.a.b + q_const + q_d_next.d_next = 0
+//
+// becomes the constraint (q_m.a) * (b) = -(q_a.a + q_b.b + q_c.c + q_d.d + q_const + q_d_next.d_next).
+// Wire 0 is the constant one, followed by the public inputs in the order of allocation and the
+// witness variables in the order of allocation.
+
+/// A sparse linear combination of wires.
+pub type Lc<E> = Vec<(usize, <E as Engine>::Fr)>;
+
+/// A rank one constraint system, optionally with a satisfying assignment.
+#[derive(Clone, Debug)]
+pub struct R1cs<E: Engine> {
+    pub num_public_inputs: usize,
+    pub num_wires: usize,
+    pub constraints: Vec<(Lc<E>, Lc<E>, Lc<E>)>,
+    /// All wire values, starting with the constant one. `None` if the assembly was synthesized
+    /// without a witness.
+    pub witness: Option<Vec<E::Fr>>,
+}
+
+/// The shape of one of the A, B and C matrices.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MatrixStatistics {
+    pub num_rows: usize,
+    pub num_columns: usize,
+    pub nonzero: usize,
+    /// Number of rows by their number of terms.
+    pub terms_per_row: BTreeMap<usize, usize>,
+}
+
+impl MatrixStatistics {
+    fn new<E: Engine>(lcs: &[&Lc<E>], num_columns: usize) -> Self {
+        let mut terms_per_row = BTreeMap::new();
+        for lc in lcs.iter() {
+            *terms_per_row.entry(lc.len()).or_insert(0) += 1;
+        }
+
+        MatrixStatistics {
+            num_rows: lcs.len(),
+            num_columns,
+            nonzero: lcs.iter().map(|lc| lc.len()).sum(),
+            terms_per_row,
+        }
+    }
+
+    /// The fraction of nonzero entries.
+    pub fn density(&self) -> f64 {
+        if self.num_rows == 0 {
+            return 0.0;
+        }
+
+        self.nonzero as f64 / (self.num_rows as f64 * self.num_columns as f64)
+    }
+
+    pub fn max_terms(&self) -> usize {
+        self.terms_per_row.keys().last().cloned().unwrap_or(0)
+    }
+}
+
+/// Sizes of a rank one constraint system, for comparing gadget implementations.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct R1csStatistics {
+    pub num_public_inputs: usize,
+    pub num_aux: usize,
+    pub num_constraints: usize,
+    pub a: MatrixStatistics,
+    pub b: MatrixStatistics,
+    pub c: MatrixStatistics,
+}
+
+fn add_term<E: Engine>(lc: &mut BTreeMap<usize, E::Fr>, wire: usize, coeff: E::Fr) {
+    if coeff.is_zero() {
+        return;
+    }
+
+    let entry = lc.entry(wire).or_insert(E::Fr::zero());
+    entry.add_assign(&coeff);
+    if entry.is_zero() {
+        lc.remove(&wire);
+    }
+}
+
+impl<E: Engine> R1cs<E> {
+    /// Converts the gates of `assembly`. Fails with `SynthesisError::Unsatisfiable` if the
+    /// assembly uses gates other than the main gate, which have no rank one form in general,
+    /// or has lookup tables, which the format can't express.
+    pub fn from_assembly<P: PlonkConstraintSystemParams<E>>(
+        assembly: &TrivialAssembly<E, P, Width4MainGateWithDNext>
+    ) -> Result<Self, SynthesisError> {
+        let has_lookups = !assembly.tables.is_empty() || !assembly.multitables.is_empty()
+            || assembly.num_table_lookups != 0 || assembly.num_multitable_lookups != 0;
+        if has_lookups {
+            return Err(SynthesisError::Unsatisfiable);
+        }
+
+        let main_gate = Width4MainGateWithDNext::default();
+        for (gate, density) in assembly.aux_gate_density.0.iter() {
+            if gate.name() != main_gate.name() && density.iter().any(|is_applicable| is_applicable) {
+                return Err(SynthesisError::Unsatisfiable);
+            }
+        }
+
+        let dummy = TrivialAssembly::<E, P, Width4MainGateWithDNext>::get_dummy_variable();
+        let empty = vec![];
+        let variables_at = |storage: &HashMap<PolyIdentifier, Vec<Variable>>, i: usize| -> &Vec<Variable> {
+            storage.get(&PolyIdentifier::VariablesPolynomial(i)).unwrap_or(&empty)
+        };
+
+        // public inputs occupy the A wire of the input rows
+        let mut wires = HashMap::new();
+        let mut variables = vec![];
+        for &var in variables_at(&assembly.inputs_storage.state_map, 0).iter() {
+            if !wires.contains_key(&var) {
+                wires.insert(var, variables.len() + 1);
+                variables.push(var);
+            }
+        }
+        let num_public_inputs = variables.len();
+
+        let state: Vec<&Vec<Variable>> = (0..4).map(|i| variables_at(&assembly.aux_storage.state_map, i)).collect();
+        let mut aux: Vec<Variable> = state.iter()
+            .flat_map(|column| column.iter().cloned())
+            .filter(|&var| var != dummy && !wires.contains_key(&var))
+            .collect();
+        aux.sort_by_key(|var| match var.get_unchecked() {
+            Index::Aux(i) => i,
+            Index::Input(i) => i,
+        });
+        aux.dedup();
+        for var in aux.into_iter() {
+            wires.insert(var, variables.len() + 1);
+            variables.push(var);
+        }
+
+        let no_coeffs = vec![];
+        let coeffs: Vec<&Vec<E::Fr>> = main_gate.setup_polynomials().iter()
+            .map(|id| assembly.aux_storage.setup_map.get(id).unwrap_or(&no_coeffs))
+            .collect();
+        let coeff = |term: usize, row: usize| -> E::Fr {
+            coeffs[term].get(row).cloned().unwrap_or(E::Fr::zero())
+        };
+        let wire = |var: Variable| -> Option<usize> {
+            if var == dummy { None } else { Some(wires[&var]) }
+        };
+
+        let num_rows = state[0].len();
+        let mut constraints = Vec::with_capacity(num_rows);
+        for row in 0..num_rows {
+            let mut a = BTreeMap::new();
+            let mut b = BTreeMap::new();
+            let mut c = BTreeMap::new();
+
+            let q_m = coeff(Width4MainGateWithDNext::range_of_multiplicative_term().start, row);
+            if let (Some(a_wire), Some(b_wire)) = (wire(state[0][row]), wire(state[1][row])) {
+                add_term::<E>(&mut a, a_wire, q_m);
+                if !q_m.is_zero() {
+                    add_term::<E>(&mut b, b_wire, E::Fr::one());
+                }
+            }
+
+            let mut terms: Vec<(Option<usize>, E::Fr)> = Width4MainGateWithDNext::range_of_linear_terms()
+                .zip(state.iter())
+                .map(|(term, column)| (wire(column[row]), coeff(term, row)))
+                .collect();
+            terms.push((Some(0), coeff(Width4MainGateWithDNext::index_for_constant_term(), row)));
+            if row + 1 < num_rows {
+                for term in Width4MainGateWithDNext::range_of_next_step_linear_terms() {
+                    terms.push((wire(state[3][row + 1]), coeff(term, row)));
+                }
+            }
+            for (w, mut q) in terms.into_iter() {
+                if let Some(w) = w {
+                    q.negate();
+                    add_term::<E>(&mut c, w, q);
+                }
+            }
+
+            if a.is_empty() && c.is_empty() {
+                continue;
+            }
+            constraints.push((a.into_iter().collect(), b.into_iter().collect(), c.into_iter().collect()));
+        }
+
+        let witness = variables.iter()
+            .map(|&var| assembly.get_value(var))
+            .collect::<Result<Vec<_>, _>>()
+            .ok()
+            .map(|values| Some(E::Fr::one()).into_iter().chain(values.into_iter()).collect());
+
+        Ok(R1cs {
+            num_public_inputs,
+            num_wires: variables.len() + 1,
+            constraints,
+            witness,
+        })
+    }
+
+    pub fn statistics(&self) -> R1csStatistics {
+        let a: Vec<&Lc<E>> = self.constraints.iter().map(|(a, _, _)| a).collect();
+        let b: Vec<&Lc<E>> = self.constraints.iter().map(|(_, b, _)| b).collect();
+        let c: Vec<&Lc<E>> = self.constraints.iter().map(|(_, _, c)| c).collect();
+
+        R1csStatistics {
+            num_public_inputs: self.num_public_inputs,
+            num_aux: self.num_wires - self.num_public_inputs - 1,
+            num_constraints: self.constraints.len(),
+            a: MatrixStatistics::new::<E>(&a, self.num_wires),
+            b: MatrixStatistics::new::<E>(&b, self.num_wires),
+            c: MatrixStatistics::new::<E>(&c, self.num_wires),
+        }
+    }
+
+    /// Checks the witness against every constraint, `false` if there is no witness.
+    pub fn is_satisfied(&self) -> bool {
+        let witness = match self.witness {
+            Some(ref witness) => witness,
+            None => return false,
+        };
+        let eval = |lc: &Lc<E>| -> E::Fr {
+            let mut acc = E::Fr::zero();
+            for &(wire, ref coeff) in lc.iter() {
+                let mut tmp = witness[wire];
+                tmp.mul_assign(coeff);
+                acc.add_assign(&tmp);
+            }
+            acc
+        };
+
+        self.constraints.iter().all(|(a, b, c)| {
+            let mut ab = eval(a);
+            ab.mul_assign(&eval(b));
+            ab == eval(c)
+        })
+    }
+
+    /// Writes the constraint system in the `.r1cs` format.
+    pub fn write_r1cs<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let n8 = field_size::<E>();
+        let lc_size = |lc: &Lc<E>| 4 + lc.len() * (4 + n8);
+
+        writer.write_all(b"r1cs")?;
+        writer.write_u32::<LittleEndian>(1)?;
+        writer.write_u32::<LittleEndian>(3)?;
+
+        // header
+        writer.write_u32::<LittleEndian>(1)?;
+        writer.write_u64::<LittleEndian>((4 + n8 + 4 * 4 + 8 + 4) as u64)?;
+        writer.write_u32::<LittleEndian>(n8 as u32)?;
+        E::Fr::char().write_le(&mut writer)?;
+        writer.write_u32::<LittleEndian>(self.num_wires as u32)?;
+        writer.write_u32::<LittleEndian>(0)?;
+        writer.write_u32::<LittleEndian>(self.num_public_inputs as u32)?;
+        writer.write_u32::<LittleEndian>(0)?;
+        writer.write_u64::<LittleEndian>(self.num_wires as u64)?;
+        writer.write_u32::<LittleEndian>(self.constraints.len() as u32)?;
+
+        // constraints
+        let size: usize = self.constraints.iter().map(|(a, b, c)| lc_size(a) + lc_size(b) + lc_size(c)).sum();
+        writer.write_u32::<LittleEndian>(2)?;
+        writer.write_u64::<LittleEndian>(size as u64)?;
+        for (a, b, c) in self.constraints.iter() {
+            for lc in [a, b, c].iter() {
+                writer.write_u32::<LittleEndian>(lc.len() as u32)?;
+                for &(wire, ref coeff) in lc.iter() {
+                    writer.write_u32::<LittleEndian>(wire as u32)?;
+                    coeff.into_repr().write_le(&mut writer)?;
+                }
+            }
+        }
+
+        // wire to label map, labels are the wire indices
+        writer.write_u32::<LittleEndian>(3)?;
+        writer.write_u64::<LittleEndian>((self.num_wires * 8) as u64)?;
+        for wire in 0..self.num_wires {
+            writer.write_u64::<LittleEndian>(wire as u64)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the witness in the `.wtns` format.
+    pub fn write_wtns<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let witness = self.witness.as_ref()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no witness"))?;
+        let n8 = field_size::<E>();
+
+        writer.write_all(b"wtns")?;
+        writer.write_u32::<LittleEndian>(2)?;
+        writer.write_u32::<LittleEndian>(2)?;
+
+        writer.write_u32::<LittleEndian>(1)?;
+        writer.write_u64::<LittleEndian>((4 + n8 + 4) as u64)?;
+        writer.write_u32::<LittleEndian>(n8 as u32)?;
+        E::Fr::char().write_le(&mut writer)?;
+        writer.write_u32::<LittleEndian>(witness.len() as u32)?;
+
+        writer.write_u32::<LittleEndian>(2)?;
+        writer.write_u64::<LittleEndian>((witness.len() * n8) as u64)?;
+        for value in witness.iter() {
+            value.into_repr().write_le(&mut writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn field_size<E: Engine>() -> usize {
+    <E::Fr as PrimeField>::Repr::default().as_ref().len() * 8
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use crate::bellman::plonk::better_better_cs::cs::*;
+    use crate::plonk::circuit::allocated_num::{AllocatedNum, Num};
+    use crate::plonk::circuit::set_membership::enforce_membership_by_lookup;
+
+    #[test]
+    fn test_export_product() {
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+
+        let a = AllocatedNum::alloc_input(&mut cs, || Ok(Fr::from_str("3").unwrap())).unwrap();
+        let b = Num::alloc(&mut cs, Some(Fr::from_str("5").unwrap())).unwrap();
+        let c = Num::Variable(a).mul(&mut cs, &b).unwrap();
+        let d = c.add(&mut cs, &Num::Constant(Fr::from_str("7").unwrap())).unwrap();
+        assert_eq!(d.get_value().unwrap(), Fr::from_str("22").unwrap());
+        assert!(cs.is_satisfied());
+
+        let r1cs = R1cs::from_assembly(&cs).unwrap();
+        assert_eq!(r1cs.num_public_inputs, 1);
+        assert!(r1cs.constraints.len() >= 2);
+        assert!(r1cs.is_satisfied());
+
+        let mut broken = r1cs.clone();
+        broken.witness.as_mut().unwrap()[2] = Fr::from_str("6").unwrap();
+        assert!(!broken.is_satisfied());
+
+        let stats = r1cs.statistics();
+        assert_eq!(stats.num_public_inputs, 1);
+        assert_eq!(stats.num_aux, r1cs.num_wires - 2);
+        assert_eq!(stats.num_constraints, r1cs.constraints.len());
+        assert_eq!(stats.b.terms_per_row.values().sum::<usize>(), stats.num_constraints);
+        assert_eq!(stats.a.max_terms(), 1);
+        assert!(stats.c.density() > 0.0 && stats.c.density() <= 1.0);
+
+        let mut bytes = vec![];
+        r1cs.write_r1cs(&mut bytes).unwrap();
+        assert_eq!(&bytes[..4], b"r1cs");
+        // magic, version, sections, header section and the leading words of the constraints
+        // section
+        assert_eq!(bytes.len() as u64, 12 + 12 + 64 + 12 + {
+            let mut size = [0u8; 8];
+            size.copy_from_slice(&bytes[92..100]);
+            u64::from_le_bytes(size)
+        } + 12 + 8 * r1cs.num_wires as u64);
+
+        let mut bytes = vec![];
+        r1cs.write_wtns(&mut bytes).unwrap();
+        assert_eq!(&bytes[..4], b"wtns");
+        assert_eq!(bytes.len(), 12 + 12 + 40 + 12 + 32 * r1cs.num_wires);
+    }
+
+    #[test]
+    fn test_lookups_are_rejected() {
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+
+        let set = vec![Fr::from_str("3").unwrap(), Fr::from_str("5").unwrap()];
+        let a = Num::alloc(&mut cs, Some(Fr::from_str("3").unwrap())).unwrap();
+        let b = a.mul(&mut cs, &a).unwrap();
+        assert!(R1cs::from_assembly(&cs).is_ok());
+
+        enforce_membership_by_lookup(&mut cs, &a, &set, "snarkjs test set").unwrap();
+        assert_eq!(b.get_value().unwrap(), Fr::from_str("9").unwrap());
+        assert!(cs.is_satisfied());
+        assert!(R1cs::from_assembly(&cs).is_err());
+    }
+}
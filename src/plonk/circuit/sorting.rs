@@ -0,0 +1,125 @@
+use crate::bellman::pairing::{
+    Engine,
+};
+
+use crate::bellman::pairing::ff::{
+    PrimeField,
+};
+
+use crate::bellman::{
+    SynthesisError,
+};
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    ConstraintSystem,
+};
+
+use super::allocated_num::{AllocatedNum, Num};
+use super::grand_product::enforce_multiset_equality;
+
+/// Witnesses `values` in non-decreasing order and proves that the result is sorted and
+/// is a permutation of the input. All the values must fit into `bit_length` bits,
+/// this is enforced as a side effect of the sortedness check.
+///
+/// Sortedness: the first and the last outputs are range checked and every adjacent
+/// difference is range checked to `bit_length` bits, so there is no wrap around.
+/// Permutation: grand product prod (challenge + x_i) over the input and the output
+/// with `challenge` produced by `derive_challenge` from all the inputs and outputs
+/// (e.g. an in-circuit hash of them, or a public input derived the same way).
+pub fn sort<E, CS, F>(
+    cs: &mut CS,
+    values: &[AllocatedNum<E>],
+    bit_length: usize,
+    derive_challenge: F
+) -> Result<Vec<AllocatedNum<E>>, SynthesisError>
+    where E: Engine,
+          CS: ConstraintSystem<E>,
+          F: FnOnce(&mut CS, &[Num<E>]) -> Result<Num<E>, SynthesisError>
+{
+    assert!(bit_length > 0 && bit_length < E::Fr::CAPACITY as usize);
+
+    if values.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let sorted_witness = values.iter().map(|el| el.get_value()).collect::<Option<Vec<_>>>().map(|mut witness| {
+        witness.sort_by_key(|el| el.into_repr());
+
+        witness
+    });
+
+    let mut sorted = Vec::with_capacity(values.len());
+    for idx in 0..values.len() {
+        let witness = sorted_witness.as_ref().map(|el| el[idx]);
+        let allocated = AllocatedNum::alloc(cs, || {
+            witness.ok_or(SynthesisError::AssignmentMissing)
+        })?;
+        sorted.push(allocated);
+    }
+
+    let original: Vec<Num<E>> = values.iter().map(|el| Num::Variable(*el)).collect();
+    let permuted: Vec<Num<E>> = sorted.iter().map(|el| Num::Variable(*el)).collect();
+
+    let mut transcript = original.clone();
+    transcript.extend_from_slice(&permuted);
+    let challenge = derive_challenge(cs, &transcript)?;
+    enforce_multiset_equality(cs, &original, &permuted, &challenge)?;
+
+    Num::Variable(sorted[0]).enforce_bit_length(cs, bit_length)?;
+    for pair in sorted.windows(2) {
+        let delta = pair[1].sub(cs, &pair[0])?;
+        Num::Variable(delta).enforce_bit_length(cs, bit_length)?;
+    }
+    Num::Variable(*sorted.last().unwrap()).enforce_bit_length(cs, bit_length)?;
+
+    Ok(sorted)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use crate::bellman::plonk::better_better_cs::cs::*;
+    use crate::plonk::circuit::utils::u64_to_fe;
+
+    fn constant_challenge<CS: ConstraintSystem<Bn256>>(
+        _cs: &mut CS,
+        _transcript: &[Num<Bn256>]
+    ) -> Result<Num<Bn256>, SynthesisError> {
+        let mut rng = XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        Ok(Num::Constant(rng.gen()))
+    }
+
+    #[test]
+    fn test_sort() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+
+        // small range to get some duplicates
+        let mut native: Vec<u64> = (0..32).map(|_| rng.gen_range(0, 20)).collect();
+        let values: Vec<_> = native.iter().map(|el| {
+            AllocatedNum::alloc(&mut cs, || Ok(u64_to_fe::<Fr>(*el))).unwrap()
+        }).collect();
+
+        let sorted = sort(&mut cs, &values, 16, constant_challenge).unwrap();
+
+        native.sort();
+        for (s, n) in sorted.iter().zip(native.iter()) {
+            assert_eq!(s.get_value().unwrap(), u64_to_fe::<Fr>(*n));
+        }
+        assert!(cs.is_satisfied());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_sort_rejects_out_of_range_values() {
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+        let values: Vec<_> = [3u64, 1 << 20, 5].iter().map(|el| {
+            AllocatedNum::alloc(&mut cs, || Ok(u64_to_fe::<Fr>(*el))).unwrap()
+        }).collect();
+
+        let _ = sort(&mut cs, &values, 16, constant_challenge).unwrap();
+    }
+}
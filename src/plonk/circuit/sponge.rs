@@ -0,0 +1,401 @@
+use crate::bellman::pairing::{
+    Engine,
+};
+
+use crate::bellman::{
+    SynthesisError,
+};
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    ConstraintSystem,
+};
+
+use super::allocated_num::Num;
+use super::linear_combination::LinearCombination;
+use super::uint64::UInt64;
+use super::utils::u64_to_fe;
+
+use super::keccak::{keccak_f1600, KECCAK_STATE_WIDTH};
+use super::poseidon::{PoseidonCsSBox, StatefulPoseidonGadget};
+use super::rescue::{PlonkCsSBox, StatefulRescueGadget};
+
+use crate::poseidon::{PoseidonEngine, PoseidonHashParams};
+use crate::rescue::{RescueEngine, RescueHashParams};
+
+/// Permutation gadget that can be plugged into the `Sponge`. The first `rate` elements
+/// of the state are the outer part, the rest is the capacity.
+pub trait CircuitPermutation<E: Engine> {
+    type Element: Clone;
+
+    fn state_width(&self) -> usize;
+    fn rate(&self) -> usize;
+
+    /// Initial value of the state element.
+    fn zero(&self) -> Self::Element;
+    /// Constant placed into the last capacity element to separate domains.
+    fn domain_separator(&self, tag: u64) -> Self::Element;
+
+    /// Combines an input element into the state element, e.g. field addition or XOR.
+    fn absorb_into<CS: ConstraintSystem<E>>(
+        &self,
+        cs: &mut CS,
+        state_element: &Self::Element,
+        input: &Self::Element
+    ) -> Result<Self::Element, SynthesisError>;
+
+    fn permute<CS: ConstraintSystem<E>>(
+        &self,
+        cs: &mut CS,
+        state: &[Self::Element]
+    ) -> Result<Vec<Self::Element>, SynthesisError>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SpongeMode {
+    Absorbing(usize),
+    Squeezing(usize),
+}
+
+/// Sponge and duplex constructions over any `CircuitPermutation`. Inputs are not padded,
+/// so the caller should either absorb a fixed number of elements or put the length into the domain tag.
+#[derive(Clone)]
+pub struct Sponge<E: Engine, P: CircuitPermutation<E>> {
+    permutation: P,
+    state: Vec<P::Element>,
+    mode: SpongeMode,
+}
+
+impl<E: Engine, P: CircuitPermutation<E>> Sponge<E, P> {
+    pub fn new(permutation: P) -> Self {
+        assert!(permutation.rate() > 0 && permutation.rate() < permutation.state_width());
+
+        let state = vec![permutation.zero(); permutation.state_width()];
+
+        Self {
+            permutation,
+            state,
+            mode: SpongeMode::Absorbing(0),
+        }
+    }
+
+    /// Creates a sponge in the domain `tag`, sponges with different tags are independent.
+    pub fn new_with_domain(permutation: P, tag: u64) -> Self {
+        let mut sponge = Self::new(permutation);
+        let last = sponge.state.len() - 1;
+        sponge.state[last] = sponge.permutation.domain_separator(tag);
+
+        sponge
+    }
+
+    pub fn permutation(&self) -> &P {
+        &self.permutation
+    }
+
+    fn permute<CS: ConstraintSystem<E>>(&mut self, cs: &mut CS) -> Result<(), SynthesisError> {
+        self.state = self.permutation.permute(cs, &self.state)?;
+        assert_eq!(self.state.len(), self.permutation.state_width());
+
+        Ok(())
+    }
+
+    pub fn absorb_single<CS: ConstraintSystem<E>>(
+        &mut self,
+        cs: &mut CS,
+        value: &P::Element
+    ) -> Result<(), SynthesisError> {
+        let rate = self.permutation.rate();
+        let mut pos = match self.mode {
+            SpongeMode::Absorbing(pos) => pos,
+            // the state was already permuted before squeezing
+            SpongeMode::Squeezing(_) => 0,
+        };
+
+        if pos == rate {
+            self.permute(cs)?;
+            pos = 0;
+        }
+
+        self.state[pos] = self.permutation.absorb_into(cs, &self.state[pos], value)?;
+        self.mode = SpongeMode::Absorbing(pos + 1);
+
+        Ok(())
+    }
+
+    pub fn absorb<CS: ConstraintSystem<E>>(
+        &mut self,
+        cs: &mut CS,
+        values: &[P::Element]
+    ) -> Result<(), SynthesisError> {
+        for value in values.iter() {
+            self.absorb_single(cs, value)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn squeeze_single<CS: ConstraintSystem<E>>(
+        &mut self,
+        cs: &mut CS
+    ) -> Result<P::Element, SynthesisError> {
+        let rate = self.permutation.rate();
+        let mut pos = match self.mode {
+            SpongeMode::Absorbing(_) => {
+                self.permute(cs)?;
+                0
+            },
+            SpongeMode::Squeezing(pos) => pos,
+        };
+
+        if pos == rate {
+            self.permute(cs)?;
+            pos = 0;
+        }
+
+        let output = self.state[pos].clone();
+        self.mode = SpongeMode::Squeezing(pos + 1);
+
+        Ok(output)
+    }
+
+    pub fn squeeze<CS: ConstraintSystem<E>>(
+        &mut self,
+        cs: &mut CS,
+        num_elements: usize
+    ) -> Result<Vec<P::Element>, SynthesisError> {
+        (0..num_elements).map(|_| self.squeeze_single(cs)).collect()
+    }
+
+    /// Duplex call: absorbs at most `rate` elements, permutes and returns the outer part of the state.
+    /// Can not be called while there are absorbed elements that are not permuted yet.
+    pub fn duplex<CS: ConstraintSystem<E>>(
+        &mut self,
+        cs: &mut CS,
+        values: &[P::Element]
+    ) -> Result<Vec<P::Element>, SynthesisError> {
+        let rate = self.permutation.rate();
+        assert!(values.len() <= rate);
+        match self.mode {
+            SpongeMode::Absorbing(pos) => assert_eq!(pos, 0, "can not duplex sponge that absorbed something"),
+            SpongeMode::Squeezing(_) => {},
+        }
+
+        for (s, value) in self.state.iter_mut().zip(values.iter()) {
+            *s = self.permutation.absorb_into(cs, s, value)?;
+        }
+        self.permute(cs)?;
+        // everything is squeezed already, so the next absorption doesn't need a permutation
+        self.mode = SpongeMode::Squeezing(rate);
+
+        Ok(self.state[..rate].to_vec())
+    }
+}
+
+/// Poseidon permutation over field elements.
+pub struct PoseidonPermutation<'a, E: PoseidonEngine> {
+    pub params: &'a E::Params,
+}
+
+impl<'a, E: PoseidonEngine> Clone for PoseidonPermutation<'a, E> {
+    fn clone(&self) -> Self {
+        Self { params: self.params }
+    }
+}
+
+impl<'a, E: PoseidonEngine> CircuitPermutation<E> for PoseidonPermutation<'a, E>
+    where <<E as PoseidonEngine>::Params as PoseidonHashParams<E>>::SBox: PoseidonCsSBox<E>
+{
+    type Element = Num<E>;
+
+    fn state_width(&self) -> usize {
+        self.params.state_width() as usize
+    }
+
+    fn rate(&self) -> usize {
+        self.params.rate() as usize
+    }
+
+    fn zero(&self) -> Num<E> {
+        Num::zero()
+    }
+
+    fn domain_separator(&self, tag: u64) -> Num<E> {
+        Num::Constant(u64_to_fe(tag))
+    }
+
+    fn absorb_into<CS: ConstraintSystem<E>>(&self, cs: &mut CS, state_element: &Num<E>, input: &Num<E>) -> Result<Num<E>, SynthesisError> {
+        state_element.add(cs, input)
+    }
+
+    fn permute<CS: ConstraintSystem<E>>(&self, cs: &mut CS, state: &[Num<E>]) -> Result<Vec<Num<E>>, SynthesisError> {
+        let state: Vec<_> = state.iter().map(|el| LinearCombination::from(*el)).collect();
+        let out = StatefulPoseidonGadget::<E>::permutation_over_lcs(cs, &state, self.params)?;
+
+        out.into_iter().map(|lc| lc.into_num(cs)).collect()
+    }
+}
+
+/// Rescue permutation over field elements.
+pub struct RescuePermutation<'a, E: RescueEngine> {
+    pub params: &'a E::Params,
+}
+
+impl<'a, E: RescueEngine> Clone for RescuePermutation<'a, E> {
+    fn clone(&self) -> Self {
+        Self { params: self.params }
+    }
+}
+
+impl<'a, E: RescueEngine> CircuitPermutation<E> for RescuePermutation<'a, E>
+    where <<E as RescueEngine>::Params as RescueHashParams<E>>::SBox0: PlonkCsSBox<E>,
+          <<E as RescueEngine>::Params as RescueHashParams<E>>::SBox1: PlonkCsSBox<E>
+{
+    type Element = Num<E>;
+
+    fn state_width(&self) -> usize {
+        self.params.state_width() as usize
+    }
+
+    fn rate(&self) -> usize {
+        self.params.rate() as usize
+    }
+
+    fn zero(&self) -> Num<E> {
+        Num::zero()
+    }
+
+    fn domain_separator(&self, tag: u64) -> Num<E> {
+        Num::Constant(u64_to_fe(tag))
+    }
+
+    fn absorb_into<CS: ConstraintSystem<E>>(&self, cs: &mut CS, state_element: &Num<E>, input: &Num<E>) -> Result<Num<E>, SynthesisError> {
+        state_element.add(cs, input)
+    }
+
+    fn permute<CS: ConstraintSystem<E>>(&self, cs: &mut CS, state: &[Num<E>]) -> Result<Vec<Num<E>>, SynthesisError> {
+        StatefulRescueGadget::<E>::rescue_mimc_over_nums(cs, state, self.params)
+    }
+}
+
+/// Keccak-f[1600] over 64 bit lanes with a rate of `rate_in_lanes` lanes, e.g. 17 for Keccak-256.
+#[derive(Clone, Copy, Debug)]
+pub struct KeccakPermutation {
+    pub rate_in_lanes: usize,
+}
+
+impl<E: Engine> CircuitPermutation<E> for KeccakPermutation {
+    type Element = UInt64;
+
+    fn state_width(&self) -> usize {
+        KECCAK_STATE_WIDTH
+    }
+
+    fn rate(&self) -> usize {
+        self.rate_in_lanes
+    }
+
+    fn zero(&self) -> UInt64 {
+        UInt64::constant(0)
+    }
+
+    fn domain_separator(&self, tag: u64) -> UInt64 {
+        UInt64::constant(tag)
+    }
+
+    fn absorb_into<CS: ConstraintSystem<E>>(&self, cs: &mut CS, state_element: &UInt64, input: &UInt64) -> Result<UInt64, SynthesisError> {
+        state_element.xor(cs, input)
+    }
+
+    fn permute<CS: ConstraintSystem<E>>(&self, cs: &mut CS, state: &[UInt64]) -> Result<Vec<UInt64>, SynthesisError> {
+        keccak_f1600(cs, state)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use bellman::pairing::ff::Field;
+    use crate::bellman::plonk::better_better_cs::cs::*;
+    use crate::plonk::circuit::Width4WithCustomGates;
+    use crate::poseidon::{poseidon_permutation, Bn256PoseidonParams};
+    use crate::rescue::rescue_mimc;
+    use crate::rescue::bn256::Bn256RescueParams;
+
+    #[test]
+    fn test_poseidon_sponge_matches_native() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = Bn256PoseidonParams::new_checked_2_into_1();
+        let mut cs = TrivialAssembly::<Bn256, Width4WithCustomGates, Width4MainGateWithDNext>::new();
+
+        let input: Vec<Fr> = (0..3).map(|_| rng.gen()).collect();
+        let input_nums: Vec<_> = input.iter().map(|el| Num::alloc(&mut cs, Some(*el)).unwrap()).collect();
+
+        let mut sponge = Sponge::new_with_domain(PoseidonPermutation::<Bn256> { params: &params }, 3);
+        sponge.absorb(&mut cs, &input_nums).unwrap();
+        let output = sponge.squeeze(&mut cs, 3).unwrap();
+
+        // three inputs take two permutations to absorb, three outputs take two more
+        let mut state = [input[0], input[1], u64_to_fe(3)];
+        poseidon_permutation::<Bn256>(&params, &mut state);
+        state[0].add_assign(&input[2]);
+        poseidon_permutation::<Bn256>(&params, &mut state);
+        let mut expected = vec![state[0], state[1]];
+        poseidon_permutation::<Bn256>(&params, &mut state);
+        expected.push(state[0]);
+
+        assert!(cs.is_satisfied());
+        for (o, e) in output.iter().zip(expected.iter()) {
+            assert_eq!(o.get_value().unwrap(), *e);
+        }
+    }
+
+    #[test]
+    fn test_rescue_duplex_matches_native() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = Bn256RescueParams::new_checked_2_into_1();
+        let mut cs = TrivialAssembly::<Bn256, Width4WithCustomGates, Width4MainGateWithDNext>::new();
+
+        let first: Vec<Fr> = (0..2).map(|_| rng.gen()).collect();
+        let second: Fr = rng.gen();
+
+        let mut sponge = Sponge::new(RescuePermutation::<Bn256> { params: &params });
+        let first_nums: Vec<_> = first.iter().map(|el| Num::alloc(&mut cs, Some(*el)).unwrap()).collect();
+        let out_first = sponge.duplex(&mut cs, &first_nums).unwrap();
+        let out_second = sponge.duplex(&mut cs, &[Num::alloc(&mut cs, Some(second)).unwrap()]).unwrap();
+
+        let state = rescue_mimc::<Bn256>(&params, &[first[0], first[1], Fr::zero()]);
+        let mut next_state = state.clone();
+        next_state[0].add_assign(&second);
+        let next_state = rescue_mimc::<Bn256>(&params, &next_state);
+
+        assert!(cs.is_satisfied());
+        for (o, e) in out_first.iter().chain(out_second.iter()).zip(state[..2].iter().chain(next_state[..2].iter())) {
+            assert_eq!(o.get_value().unwrap(), *e);
+        }
+    }
+
+    #[test]
+    fn test_keccak_sponge_matches_native() {
+        use tiny_keccak::keccakf;
+
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+
+        let input: Vec<u64> = (0..4).map(|_| rng.gen()).collect();
+        let input_lanes: Vec<_> = input.iter().map(|el| UInt64::alloc(&mut cs, Some(*el)).unwrap()).collect();
+
+        let mut sponge = Sponge::new_with_domain(KeccakPermutation { rate_in_lanes: 17 }, 7);
+        sponge.absorb(&mut cs, &input_lanes).unwrap();
+        let output = sponge.squeeze(&mut cs, 2).unwrap();
+
+        let mut state = [0u64; 25];
+        state[24] = 7;
+        state[..4].copy_from_slice(&input);
+        keccakf(&mut state);
+
+        assert!(cs.is_satisfied());
+        assert_eq!(output[0].get_value().unwrap(), state[0]);
+        assert_eq!(output[1].get_value().unwrap(), state[1]);
+    }
+}
@@ -0,0 +1,121 @@
+use crate::bellman::pairing::Engine;
+
+use crate::bellman::SynthesisError;
+
+use crate::bellman::plonk::better_better_cs::cs::ConstraintSystem;
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+// Audit tags for ranges of gates. Gadgets wrap their security relevant checks in `tagged`, and
+// an auditor records the tags with a `TagRecorder` while synthesizing a circuit to see which
+// rows implement, say, range checks. The constraint system API can't carry extra data, so the
+// tags are kept per thread. Nothing is recorded unless a recorder is active.
+
+/// Range checks and canonical bit decompositions.
+pub const RANGE_CHECK: &'static str = "range-check";
+
+thread_local! {
+    static TAGS: RefCell<Option<BTreeMap<&'static str, Vec<Range<usize>>>>> = RefCell::new(None);
+}
+
+/// Runs `f` and tags the gates it adds with `tag`. Tags nest, a gate can carry several.
+pub fn tagged<E, CS, R, F>(cs: &mut CS, tag: &'static str, f: F) -> Result<R, SynthesisError>
+    where E: Engine,
+          CS: ConstraintSystem<E>,
+          F: FnOnce(&mut CS) -> Result<R, SynthesisError>
+{
+    let start = cs.get_current_step_number();
+    let result = f(cs)?;
+    let end = cs.get_current_step_number();
+
+    if end > start {
+        TAGS.with(|tags| {
+            if let Some(ref mut tags) = *tags.borrow_mut() {
+                tags.entry(tag).or_insert(vec![]).push(start..end);
+            }
+        });
+    }
+
+    Ok(result)
+}
+
+/// Records the tags of the gates synthesized on this thread while it is alive.
+pub struct TagRecorder {
+    _private: (),
+}
+
+impl TagRecorder {
+    pub fn start() -> Self {
+        TAGS.with(|tags| {
+            let mut tags = tags.borrow_mut();
+            assert!(tags.is_none(), "a recorder is already active on this thread");
+            *tags = Some(BTreeMap::new());
+        });
+
+        TagRecorder { _private: () }
+    }
+
+    /// The ranges of rows tagged with `tag`, in the order they were synthesized.
+    pub fn rows(&self, tag: &str) -> Vec<Range<usize>> {
+        TAGS.with(|tags| {
+            tags.borrow().as_ref().and_then(|tags| tags.get(tag).cloned()).unwrap_or(vec![])
+        })
+    }
+
+    /// Number of gates tagged with `tag`.
+    pub fn count(&self, tag: &str) -> usize {
+        self.rows(tag).iter().map(|r| r.end - r.start).sum()
+    }
+
+    /// All tags seen so far.
+    pub fn tags(&self) -> Vec<&'static str> {
+        TAGS.with(|tags| {
+            tags.borrow().as_ref().map(|tags| tags.keys().cloned().collect()).unwrap_or(vec![])
+        })
+    }
+}
+
+impl Drop for TagRecorder {
+    fn drop(&mut self) {
+        TAGS.with(|tags| *tags.borrow_mut() = None);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use bellman::pairing::ff::PrimeField;
+    use crate::bellman::plonk::better_better_cs::cs::*;
+    use crate::plonk::circuit::allocated_num::Num;
+
+    #[test]
+    fn test_range_checks_are_tagged() {
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+
+        let a = Num::alloc(&mut cs, Some(Fr::from_str("3").unwrap())).unwrap();
+        a.into_bits_le_strict(&mut cs).unwrap();
+
+        let recorder = TagRecorder::start();
+        let before = cs.n();
+        let b = Num::alloc(&mut cs, Some(Fr::from_str("5").unwrap())).unwrap();
+        let c = a.mul(&mut cs, &b).unwrap();
+        c.into_bits_le_strict(&mut cs).unwrap();
+        let after = cs.n();
+        tagged(&mut cs, "custom", |cs| a.mul(cs, &c)).unwrap();
+
+        // only the decomposition of c is recorded, the first one ran before the recorder
+        let rows = recorder.rows(RANGE_CHECK);
+        assert_eq!(rows.len(), 1);
+        assert!(rows[0].start > before && rows[0].end == after);
+        assert_eq!(recorder.count("custom"), cs.n() - after);
+        assert_eq!(recorder.tags(), vec!["custom", RANGE_CHECK]);
+        assert!(cs.is_satisfied());
+
+        drop(recorder);
+        let recorder = TagRecorder::start();
+        assert!(recorder.tags().is_empty());
+    }
+}
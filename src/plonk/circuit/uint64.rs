@@ -0,0 +1,652 @@
+use crate::bellman::pairing::{
+    Engine,
+};
+
+use crate::bellman::pairing::ff::{
+    Field,
+    PrimeField,
+};
+
+use crate::bellman::{
+    SynthesisError,
+};
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    ConstraintSystem,
+};
+
+use super::linear_combination::{
+    LinearCombination
+};
+
+use super::boolean::{
+    AllocatedBit,
+    Boolean
+};
+
+use super::multieq::MultiEq;
+
+/// Represents an interpretation of 64 `Boolean` objects as an
+/// unsigned integer.
+#[derive(Clone)]
+pub struct UInt64 {
+    // Least significant bit first
+    bits: Vec<Boolean>,
+    value: Option<u64>
+}
+
+impl UInt64 {
+    /// Construct a constant `UInt64` from a `u64`
+    pub fn constant(value: u64) -> Self
+    {
+        let mut bits = Vec::with_capacity(64);
+
+        let mut tmp = value;
+        for _ in 0..64 {
+            bits.push(Boolean::constant(tmp & 1 == 1));
+            tmp >>= 1;
+        }
+
+        UInt64 {
+            bits: bits,
+            value: Some(value)
+        }
+    }
+
+    /// Allocate a `UInt64` in the constraint system
+    pub fn alloc<E: Engine, CS: ConstraintSystem<E>>(
+        cs: &mut CS,
+        value: Option<u64>
+    ) -> Result<Self, SynthesisError>
+    {
+        let values = match value {
+            Some(mut val) => {
+                let mut v = Vec::with_capacity(64);
+
+                for _ in 0..64 {
+                    v.push(Some(val & 1 == 1));
+                    val >>= 1;
+                }
+
+                v
+            },
+            None => vec![None; 64]
+        };
+
+        let bits = values.into_iter()
+                         .map(|v| {
+                            Ok(Boolean::from(AllocatedBit::alloc(
+                                cs,
+                                v
+                            )?))
+                         })
+                         .collect::<Result<Vec<_>, SynthesisError>>()?;
+
+        Ok(UInt64 {
+            bits: bits,
+            value: value
+        })
+    }
+
+    pub fn get_value(&self) -> Option<u64> {
+        self.value
+    }
+
+    pub fn into_bits_be(&self) -> Vec<Boolean> {
+        self.bits.iter().rev().cloned().collect()
+    }
+
+    pub fn from_bits_be(bits: &[Boolean]) -> Self {
+        assert_eq!(bits.len(), 64);
+
+        let mut value = Some(0u64);
+        for b in bits {
+            value.as_mut().map(|v| *v <<= 1);
+
+            match b.get_value() {
+                Some(true) => { value.as_mut().map(|v| *v |= 1); },
+                Some(false) => {},
+                None => { value = None; }
+            }
+        }
+
+        UInt64 {
+            value: value,
+            bits: bits.iter().rev().cloned().collect()
+        }
+    }
+
+    /// Turns this `UInt64` into its little-endian byte order representation.
+    pub fn into_bits(&self) -> Vec<Boolean> {
+        self.bits.clone()
+    }
+
+    /// Converts a little-endian byte order representation of bits into a
+    /// `UInt64`.
+    pub fn from_bits(bits: &[Boolean]) -> Self
+    {
+        assert_eq!(bits.len(), 64);
+
+        let mut value = Some(0u64);
+        for b in bits.iter().rev() {
+            value.as_mut().map(|v| *v <<= 1);
+
+            match b.get_value() {
+                Some(true) => { value.as_mut().map(|v| *v |= 1); },
+                Some(false) => {},
+                None => { value = None; }
+            }
+        }
+
+        UInt64 {
+            value: value,
+            bits: bits.to_vec()
+        }
+    }
+
+    pub fn rotr(&self, by: usize) -> Self {
+        let by = by % 64;
+
+        let new_bits = self.bits.iter()
+                                .skip(by)
+                                .chain(self.bits.iter())
+                                .take(64)
+                                .cloned()
+                                .collect();
+
+        UInt64 {
+            bits: new_bits,
+            value: self.value.map(|v| v.rotate_right(by as u32))
+        }
+    }
+
+    pub fn rotl(&self, by: usize) -> Self {
+        self.rotr(64 - by % 64)
+    }
+
+    pub fn not(&self) -> Self {
+        UInt64 {
+            bits: self.bits.iter().map(|b| b.not()).collect(),
+            value: self.value.map(|v| !v)
+        }
+    }
+
+    pub fn shr(&self, by: usize) -> Self {
+        let by = by % 64;
+
+        let fill = Boolean::constant(false);
+
+        let new_bits = self.bits
+                           .iter() // The bits are least significant first
+                           .skip(by) // Skip the bits that will be lost during the shift
+                           .chain(Some(&fill).into_iter().cycle()) // Rest will be zeros
+                           .take(64) // Only 64 bits needed!
+                           .cloned()
+                           .collect();
+
+        UInt64 {
+            bits: new_bits,
+            value: self.value.map(|v| v >> by as u32)
+        }
+    }
+
+    fn triop<E, CS, F, U>(
+        cs: &mut CS,
+        a: &Self,
+        b: &Self,
+        c: &Self,
+        tri_fn: F,
+        circuit_fn: U
+    ) -> Result<Self, SynthesisError>
+        where E: Engine,
+              CS: ConstraintSystem<E>,
+              F: Fn(u64, u64, u64) -> u64,
+              U: Fn(&mut CS, &Boolean, &Boolean, &Boolean) -> Result<Boolean, SynthesisError>
+    {
+        let new_value = match (a.value, b.value, c.value) {
+            (Some(a), Some(b), Some(c)) => {
+                Some(tri_fn(a, b, c))
+            },
+            _ => None
+        };
+
+        let bits = a.bits.iter()
+                            .zip(b.bits.iter())
+                            .zip(c.bits.iter())
+                            .map(|((a, b), c)| circuit_fn(cs, a, b, c))
+                            .collect::<Result<_, _>>()?;
+
+        Ok(UInt64 {
+            bits: bits,
+            value: new_value
+        })
+    }
+
+    /// Compute the `maj` value (a and b) xor (a and c) xor (b and c)
+    /// during SHA512.
+    pub fn sha512_maj<E, CS>(
+        cs: &mut CS,
+        a: &Self,
+        b: &Self,
+        c: &Self
+    ) -> Result<Self, SynthesisError>
+        where E: Engine,
+              CS: ConstraintSystem<E>
+    {
+        Self::triop(cs, a, b, c, |a, b, c| (a & b) ^ (a & c) ^ (b & c),
+            |cs, a, b, c| {
+                Boolean::sha256_maj(
+                    cs,
+                    a,
+                    b,
+                    c
+                )
+            }
+        )
+    }
+
+    /// Compute the `ch` value `(a and b) xor ((not a) and c)`
+    /// during SHA512.
+    pub fn sha512_ch<E, CS>(
+        cs: &mut CS,
+        a: &Self,
+        b: &Self,
+        c: &Self
+    ) -> Result<Self, SynthesisError>
+        where E: Engine,
+              CS: ConstraintSystem<E>
+    {
+        Self::triop(cs, a, b, c, |a, b, c| (a & b) ^ ((!a) & c),
+            |cs, a, b, c| {
+                Boolean::sha256_ch(
+                    cs,
+                    a,
+                    b,
+                    c
+                )
+            }
+        )
+    }
+
+    /// XOR this `UInt64` with another `UInt64`
+    pub fn xor<E: Engine, CS: ConstraintSystem<E>>(
+        &self,
+        cs: &mut CS,
+        other: &Self
+    ) -> Result<Self, SynthesisError>
+    {
+        let new_value = match (self.value, other.value) {
+            (Some(a), Some(b)) => {
+                Some(a ^ b)
+            },
+            _ => None
+        };
+
+        let bits = self.bits.iter()
+                            .zip(other.bits.iter())
+                            .map(|(a, b)| {
+                                Boolean::xor(
+                                    cs,
+                                    a,
+                                    b
+                                )
+                            })
+                            .collect::<Result<_, _>>()?;
+
+        Ok(UInt64 {
+            bits: bits,
+            value: new_value
+        })
+    }
+
+    /// AND this `UInt64` with another `UInt64`
+    pub fn and<E: Engine, CS: ConstraintSystem<E>>(
+        &self,
+        cs: &mut CS,
+        other: &Self
+    ) -> Result<Self, SynthesisError>
+    {
+        let new_value = match (self.value, other.value) {
+            (Some(a), Some(b)) => {
+                Some(a & b)
+            },
+            _ => None
+        };
+
+        let bits = self.bits.iter()
+                            .zip(other.bits.iter())
+                            .map(|(a, b)| {
+                                Boolean::and(
+                                    cs,
+                                    a,
+                                    b
+                                )
+                            })
+                            .collect::<Result<_, _>>()?;
+
+        Ok(UInt64 {
+            bits: bits,
+            value: new_value
+        })
+    }
+
+    /// Perform modular addition of several `UInt64` objects.
+    pub fn addmany<E, CS>(
+        cs: &mut CS,
+        operands: &[Self]
+    ) -> Result<Self, SynthesisError>
+        where E: Engine,
+              CS: ConstraintSystem<E>
+    {
+        // Make some arbitrary bounds for ourselves to avoid overflows
+        // in the scalar field
+        assert!(E::Fr::NUM_BITS >= 128);
+        assert!(operands.len() >= 2); // Weird trivial cases that should never happen
+        assert!(operands.len() <= 10);
+
+        // Compute the maximum value of the sum so we allocate enough bits for
+        // the result
+        let mut max_value = (operands.len() as u128) * (u64::max_value() as u128);
+
+        // Keep track of the resulting value
+        let mut result_value = Some(0u128);
+
+        // This is a linear combination that we will enforce to equal the
+        // output
+        let mut lc = LinearCombination::zero();
+
+        let mut all_constants = true;
+
+        let mut multieq_gadget = MultiEq::new(&mut *cs);
+
+        // Iterate over the operands
+        for op in operands {
+            // Accumulate the value
+            match op.value {
+                Some(val) => {
+                    result_value.as_mut().map(|v| *v += val as u128);
+                },
+                None => {
+                    // If any of our operands have unknown value, we won't
+                    // know the value of the result
+                    result_value = None;
+                }
+            }
+
+            // Iterate over each bit of the operand and add the operand to
+            // the linear combination
+            let mut coeff = E::Fr::one();
+            for bit in &op.bits {
+                lc.add_assign_boolean_with_coeff(bit, coeff);
+
+                all_constants &= bit.is_constant();
+
+                coeff.double();
+            }
+        }
+
+        // The value of the actual result is modulo 2^64
+        let modular_value = result_value.map(|v| v as u64);
+
+        if all_constants && modular_value.is_some() {
+            // We can just return a constant, rather than
+            // unpacking the result into allocated bits.
+
+            return Ok(UInt64::constant(modular_value.unwrap()));
+        }
+
+        // Storage area for the resulting bits
+        let mut result_bits = vec![];
+
+        // Linear combination representing the output,
+        // for comparison with the sum of the operands
+        let mut result_lc = LinearCombination::zero();
+
+        // Allocate each bit of the result
+        let mut coeff = E::Fr::one();
+        let mut i = 0;
+        while max_value != 0 {
+            // Allocate the bit
+            let b = AllocatedBit::alloc(
+                multieq_gadget.as_cs(),
+                result_value.map(|v| (v >> i) & 1 == 1)
+            )?;
+
+            // Add this bit to the result combination
+            result_lc.add_assign_bit_with_coeff(&b, coeff);
+
+            result_bits.push(b.into());
+
+            max_value >>= 1;
+            i += 1;
+            coeff.double();
+        }
+
+        // Enforce equality between the sum and result
+        multieq_gadget.enforce_equal(i, &lc, &result_lc);
+
+        drop(multieq_gadget); // this will synthesize constraints
+
+        // Discard carry bits that we don't care about
+        result_bits.truncate(64);
+
+        Ok(UInt64 {
+            bits: result_bits,
+            value: modular_value
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{XorShiftRng, SeedableRng, Rng};
+
+    use bellman::pairing::bn256::Bn256;
+
+    use crate::bellman::plonk::better_better_cs::cs::*;
+
+    fn check_bits(r: &UInt64, expected: u64) {
+        let mut expected = expected;
+        for b in r.bits.iter() {
+            assert_eq!(b.get_value().unwrap(), expected & 1 == 1);
+            expected >>= 1;
+        }
+    }
+
+    #[test]
+    fn test_uint64_from_bits() {
+        let mut rng = XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0653]);
+
+        for _ in 0..1000 {
+            let v = (0..64).map(|_| Boolean::constant(rng.gen())).collect::<Vec<_>>();
+
+            let b = UInt64::from_bits(&v);
+            check_bits(&b, b.value.unwrap());
+
+            for (a, b) in v.iter().zip(b.into_bits().iter()) {
+                assert_eq!(a.get_value().unwrap(), b.get_value().unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn test_uint64_xor() {
+        let mut rng = XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0653]);
+
+        for _ in 0..100 {
+            let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+
+            let a: u64 = rng.gen();
+            let b: u64 = rng.gen();
+            let c: u64 = rng.gen();
+
+            let expected = a ^ b ^ c;
+
+            let a_bit = UInt64::alloc(&mut cs, Some(a)).unwrap();
+            let b_bit = UInt64::constant(b);
+            let c_bit = UInt64::alloc(&mut cs, Some(c)).unwrap();
+
+            let r = a_bit.xor(&mut cs, &b_bit).unwrap();
+            let r = r.xor(&mut cs, &c_bit).unwrap();
+
+            assert!(cs.is_satisfied());
+            assert!(r.value == Some(expected));
+            check_bits(&r, expected);
+        }
+    }
+
+    #[test]
+    fn test_uint64_addmany_constants() {
+        let mut rng = XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        for _ in 0..100 {
+            let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+
+            let a: u64 = rng.gen();
+            let b: u64 = rng.gen();
+            let c: u64 = rng.gen();
+
+            let expected = a.wrapping_add(b).wrapping_add(c);
+
+            let r = UInt64::addmany(&mut cs, &[UInt64::constant(a), UInt64::constant(b), UInt64::constant(c)]).unwrap();
+
+            assert!(r.value == Some(expected));
+            assert!(r.bits.iter().all(|b| b.is_constant()));
+            check_bits(&r, expected);
+            assert_eq!(cs.n(), 0);
+        }
+    }
+
+    #[test]
+    fn test_uint64_addmany() {
+        let mut rng = XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        for _ in 0..100 {
+            let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+
+            let a: u64 = rng.gen();
+            let b: u64 = rng.gen();
+            let c: u64 = rng.gen();
+            let d: u64 = rng.gen();
+
+            let expected = (a ^ b).wrapping_add(c).wrapping_add(d);
+
+            let a_bit = UInt64::alloc(&mut cs, Some(a)).unwrap();
+            let b_bit = UInt64::constant(b);
+            let c_bit = UInt64::constant(c);
+            let d_bit = UInt64::alloc(&mut cs, Some(d)).unwrap();
+
+            let r = a_bit.xor(&mut cs, &b_bit).unwrap();
+            let r = UInt64::addmany(&mut cs, &[r, c_bit, d_bit]).unwrap();
+
+            assert!(cs.is_satisfied());
+            assert!(r.value == Some(expected));
+            check_bits(&r, expected);
+        }
+    }
+
+    #[test]
+    fn test_uint64_from_bits_be() {
+        let mut rng = XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0653]);
+
+        for _ in 0..1000 {
+            let v = (0..64).map(|_| Boolean::constant(rng.gen())).collect::<Vec<_>>();
+
+            let b = UInt64::from_bits_be(&v);
+            check_bits(&b, b.value.unwrap());
+
+            for (a, b) in v.iter().zip(b.into_bits_be().iter()) {
+                assert_eq!(a.get_value().unwrap(), b.get_value().unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn test_uint64_shr() {
+        let mut rng = XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        for _ in 0..50 {
+            for i in 0..64 {
+                let num: u64 = rng.gen();
+                let a = UInt64::constant(num).shr(i);
+
+                assert_eq!(a.value.unwrap(), num >> i);
+                check_bits(&a, num >> i);
+            }
+        }
+    }
+
+    #[test]
+    fn test_uint64_sha512_maj_and_ch() {
+        let mut rng = XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0653]);
+
+        for _ in 0..100 {
+            let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+
+            let a: u64 = rng.gen();
+            let b: u64 = rng.gen();
+            let c: u64 = rng.gen();
+
+            let a_bit = UInt64::alloc(&mut cs, Some(a)).unwrap();
+            let b_bit = UInt64::constant(b);
+            let c_bit = UInt64::alloc(&mut cs, Some(c)).unwrap();
+
+            let maj = UInt64::sha512_maj(&mut cs, &a_bit, &b_bit, &c_bit).unwrap();
+            let ch = UInt64::sha512_ch(&mut cs, &a_bit, &b_bit, &c_bit).unwrap();
+
+            assert!(cs.is_satisfied());
+
+            let expected_maj = (a & b) ^ (a & c) ^ (b & c);
+            let expected_ch = (a & b) ^ ((!a) & c);
+            assert!(maj.value == Some(expected_maj));
+            assert!(ch.value == Some(expected_ch));
+            check_bits(&maj, expected_maj);
+            check_bits(&ch, expected_ch);
+        }
+    }
+
+    #[test]
+    fn test_uint64_and_not() {
+        let mut rng = XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0653]);
+
+        for _ in 0..100 {
+            let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+
+            let a: u64 = rng.gen();
+            let b: u64 = rng.gen();
+            let c: u64 = rng.gen();
+
+            let expected = a ^ ((!b) & c);
+
+            let a_bit = UInt64::alloc(&mut cs, Some(a)).unwrap();
+            let b_bit = UInt64::alloc(&mut cs, Some(b)).unwrap();
+            let c_bit = UInt64::constant(c);
+
+            let r = b_bit.not().and(&mut cs, &c_bit).unwrap();
+            let r = a_bit.xor(&mut cs, &r).unwrap();
+
+            assert!(cs.is_satisfied());
+            assert!(r.value == Some(expected));
+            check_bits(&r, expected);
+        }
+    }
+
+    #[test]
+    fn test_uint64_rotr() {
+        let mut rng = XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        let mut num: u64 = rng.gen();
+
+        let a = UInt64::constant(num);
+
+        for i in 0..64 {
+            let b = a.rotr(i);
+            assert_eq!(a.bits.len(), b.bits.len());
+            assert!(b.value.unwrap() == num);
+            check_bits(&b, num);
+
+            num = num.rotate_right(1);
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use crate::bellman::{
+    SynthesisError,
+};
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    ConstraintSystem,
+};
+
+use super::boolean::Boolean;
+use super::edwards::{CircuitTwistedEdwardsCurveImplementor, CircuitTwistedEdwardsPoint};
+
+use crate::generic_twisted_edwards::TwistedEdwardsCurveParams;
+use crate::jubjub::{FixedGenerators, JubjubEngine, JubjubParams};
+
+// Value commitment matching `crate::primitives::ValueCommitment::cm`:
+//
+//      cv = value . G_ValueCommitmentValue + r . G_ValueCommitmentRandomness
+//
+// Commitments add up homomorphically, so a transfer balances if the sum of the input
+// commitments minus the sum of the output commitments commits to the fee with the sum of
+// the input randomness minus the sum of the output randomness (bsk).
+
+pub const VALUE_BITS: usize = 64;
+
+/// Commits to `value_bits` (64, little endian) with randomness `r` in little endian bits.
+pub fn value_commitment<E, CS, C>(
+    cs: &mut CS,
+    curve: &CircuitTwistedEdwardsCurveImplementor<E, C>,
+    value_bits: &[Boolean],
+    r: &[Boolean],
+    params: &E::Params
+) -> Result<CircuitTwistedEdwardsPoint<E>, SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>,
+          C: TwistedEdwardsCurveParams<E>
+{
+    assert_eq!(value_bits.len(), VALUE_BITS);
+
+    let (x, y) = params.generator(FixedGenerators::ValueCommitmentValue).into_xy();
+    let value = curve.mul(cs, &CircuitTwistedEdwardsPoint::constant(x, y), value_bits)?;
+    let (x, y) = params.generator(FixedGenerators::ValueCommitmentRandomness).into_xy();
+    let blinding = curve.mul(cs, &CircuitTwistedEdwardsPoint::constant(x, y), r)?;
+
+    curve.add(cs, &value, &blinding)
+}
+
+fn sum<E, CS, C>(
+    cs: &mut CS,
+    curve: &CircuitTwistedEdwardsCurveImplementor<E, C>,
+    points: &[CircuitTwistedEdwardsPoint<E>]
+) -> Result<CircuitTwistedEdwardsPoint<E>, SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>,
+          C: TwistedEdwardsCurveParams<E>
+{
+    let mut acc = CircuitTwistedEdwardsPoint::zero();
+    for p in points.iter() {
+        acc = curve.add(cs, &acc, p)?;
+    }
+
+    Ok(acc)
+}
+
+/// Enforces that the commitments balance:
+///
+///      sum(inputs) == sum(outputs) + fee . G_ValueCommitmentValue + bsk . G_ValueCommitmentRandomness
+///
+/// `fee` is 64 little endian bits, all zero for a transfer without fee. When the fee is public
+/// the caller takes the bits from the input, e.g. with `Num::into_bits_le`. `bsk` are the
+/// little endian bits of the binding key, the difference of the input and output randomness.
+pub fn enforce_balance<E, CS, C>(
+    cs: &mut CS,
+    curve: &CircuitTwistedEdwardsCurveImplementor<E, C>,
+    inputs: &[CircuitTwistedEdwardsPoint<E>],
+    outputs: &[CircuitTwistedEdwardsPoint<E>],
+    fee: &[Boolean],
+    bsk: &[Boolean],
+    params: &E::Params
+) -> Result<(), SynthesisError>
+    where E: JubjubEngine,
+          CS: ConstraintSystem<E>,
+          C: TwistedEdwardsCurveParams<E>
+{
+    let lhs = sum(cs, curve, inputs)?;
+    let outputs = sum(cs, curve, outputs)?;
+    let fee = value_commitment(cs, curve, fee, bsk, params)?;
+    let rhs = curve.add(cs, &outputs, &fee)?;
+
+    lhs.x.enforce_equal(cs, &rhs.x)?;
+    lhs.y.enforce_equal(cs, &rhs.y)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use bellman::pairing::bn256::Bn256;
+    use bellman::pairing::ff::Field;
+    use crate::bellman::plonk::better_better_cs::cs::*;
+    use crate::alt_babyjubjub::{AltJubjubBn256, fs::Fs};
+    use crate::plonk::circuit::edwards::bn256::CircuitAltBabyJubjubBn256;
+    use crate::plonk::circuit::utils::fe_to_lsb_first_bits;
+    use crate::primitives::ValueCommitment;
+
+    type Assembly = TrivialAssembly<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>;
+
+    fn alloc_value<CS: ConstraintSystem<Bn256>>(cs: &mut CS, value: u64) -> Vec<Boolean> {
+        (0..VALUE_BITS)
+            .map(|i| Boolean::alloc(cs, Some((value >> i) & 1 == 1)).unwrap())
+            .collect()
+    }
+
+    fn alloc_fs<CS: ConstraintSystem<Bn256>>(cs: &mut CS, fs: &Fs) -> Vec<Boolean> {
+        fe_to_lsb_first_bits(fs).into_iter()
+            .map(|b| Boolean::alloc(cs, Some(b)).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_value_commitment_in_circuit() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = AltJubjubBn256::new();
+        let curve = CircuitAltBabyJubjubBn256::get_implementor();
+
+        let commitment = ValueCommitment::<Bn256> {
+            value: rng.gen(),
+            randomness: rng.gen(),
+        };
+
+        let mut cs = Assembly::new();
+        let value_bits = alloc_value(&mut cs, commitment.value);
+        let r = alloc_fs(&mut cs, &commitment.randomness);
+        let cv = value_commitment(&mut cs, &curve, &value_bits, &r, &params).unwrap();
+        assert!(cs.is_satisfied());
+
+        let (x, y) = commitment.cm(&params).into_xy();
+        assert_eq!(cv.x.get_value().unwrap(), x);
+        assert_eq!(cv.y.get_value().unwrap(), y);
+    }
+
+    #[test]
+    fn test_enforce_balance() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = AltJubjubBn256::new();
+        let curve = CircuitAltBabyJubjubBn256::get_implementor();
+
+        let inputs = [(30u64, rng.gen::<Fs>()), (20, rng.gen())];
+        let outputs = [(45u64, rng.gen::<Fs>())];
+        let mut bsk = Fs::zero();
+        for &(_, r) in inputs.iter() {
+            bsk.add_assign(&r);
+        }
+        for &(_, r) in outputs.iter() {
+            bsk.sub_assign(&r);
+        }
+
+        for &(fee, satisfied) in [(5u64, true), (4, false)].iter() {
+            let mut cs = Assembly::new();
+            let commit = |cs: &mut Assembly, &(value, r): &(u64, Fs)| {
+                let value_bits = alloc_value(cs, value);
+                let r = alloc_fs(cs, &r);
+                value_commitment(cs, &curve, &value_bits, &r, &params).unwrap()
+            };
+            let input_cvs: Vec<_> = inputs.iter().map(|i| commit(&mut cs, i)).collect();
+            let output_cvs: Vec<_> = outputs.iter().map(|o| commit(&mut cs, o)).collect();
+            let fee_bits = alloc_value(&mut cs, fee);
+            let bsk_bits = alloc_fs(&mut cs, &bsk);
+
+            enforce_balance(&mut cs, &curve, &input_cvs, &output_cvs, &fee_bits, &bsk_bits, &params).unwrap();
+            assert_eq!(cs.is_satisfied(), satisfied);
+        }
+    }
+}
@@ -0,0 +1,280 @@
+use crate::bellman::pairing::ff::{
+    Field,
+    PrimeField,
+    SqrtField,
+    LegendreSymbol,
+};
+
+use crate::bellman::{
+    SynthesisError,
+};
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    ConstraintSystem,
+};
+
+use super::allocated_num::Num;
+use super::boolean::Boolean;
+use super::linear_combination::LinearCombination;
+use super::edwards::{CircuitTwistedEdwardsCurveImplementor, CircuitTwistedEdwardsPoint};
+use super::poseidon::{poseidon_hash, PoseidonCsSBox};
+use super::utils::u64_to_fe;
+
+use crate::generic_twisted_edwards::TwistedEdwardsCurveParams;
+use crate::jubjub::{FixedGenerators, JubjubEngine, JubjubParams};
+use crate::poseidon::{PoseidonEngine, PoseidonHashParams};
+use crate::vrf::{VRF_HASH_TO_CURVE_ATTEMPTS, VRF_CHALLENGE_BITS};
+
+// Gadgets matching `crate::vrf`. Every attempt of the hash to curve is done in circuit: the
+// prover shows for every candidate y_k whether u_k = (y_k^2 - 1) / (d * y_k^2 + 1) is a square
+// by giving w_k with w_k^2 = u_k or w_k^2 = n * u_k for a fixed non residue n, and the first
+// square is taken.
+
+#[derive(Clone, Debug)]
+pub struct CircuitVrfProof<E: JubjubEngine> {
+    pub gamma: CircuitTwistedEdwardsPoint<E>,
+    /// `VRF_CHALLENGE_BITS` little endian bits of c.
+    pub c: Vec<Boolean>,
+    /// Little endian bits of s.
+    pub s: Vec<Boolean>,
+}
+
+fn non_residue<F: PrimeField + SqrtField>() -> F {
+    let mut n = F::one();
+    loop {
+        n.add_assign(&F::one());
+        if n.legendre() == LegendreSymbol::QuadraticNonResidue {
+            return n;
+        }
+    }
+}
+
+fn pack_bits<E: JubjubEngine, CS: ConstraintSystem<E>>(
+    cs: &mut CS,
+    bits: &[Boolean]
+) -> Result<Num<E>, SynthesisError> {
+    let mut lc = LinearCombination::zero();
+    let mut coeff = E::Fr::one();
+    for bit in bits.iter() {
+        lc.add_assign_boolean_with_coeff(bit, coeff);
+        coeff.double();
+    }
+
+    lc.into_num(cs)
+}
+
+/// H(pk, alpha) of `crate::vrf::hash_to_curve`. The circuit is unsatisfiable if none of the
+/// attempts succeeds.
+pub fn hash_to_curve<E, CS, C>(
+    cs: &mut CS,
+    curve: &CircuitTwistedEdwardsCurveImplementor<E, C>,
+    pk: &CircuitTwistedEdwardsPoint<E>,
+    alpha: &[Num<E>],
+    jubjub_params: &<E as JubjubEngine>::Params,
+    poseidon_params: &<E as PoseidonEngine>::Params
+) -> Result<CircuitTwistedEdwardsPoint<E>, SynthesisError>
+    where E: JubjubEngine + PoseidonEngine,
+          CS: ConstraintSystem<E>,
+          C: TwistedEdwardsCurveParams<E>,
+          <<E as PoseidonEngine>::Params as PoseidonHashParams<E>>::SBox: PoseidonCsSBox<E>
+{
+    let n = non_residue::<E::Fr>();
+    let mut one_minus_n = E::Fr::one();
+    one_minus_n.sub_assign(&n);
+    let d = *jubjub_params.edwards_d();
+
+    let mut input = vec![pk.x, pk.y];
+    input.extend_from_slice(alpha);
+    let seed = poseidon_hash(cs, poseidon_params, &input)?[0];
+
+    let mut none_found = Boolean::constant(true);
+    let mut selected_y = Num::zero();
+    let mut selected_w = Num::zero();
+
+    for k in 0..VRF_HASH_TO_CURVE_ATTEMPTS {
+        let y = poseidon_hash(cs, poseidon_params, &[seed, Num::Constant(u64_to_fe(k as u64))])?[0];
+        let y_squared = y.mul(cs, &y)?;
+
+        // u = num / den
+        let mut num = LinearCombination::zero();
+        num.add_assign_number_with_coeff(&y_squared, E::Fr::one());
+        let mut minus_one = E::Fr::one();
+        minus_one.negate();
+        num.add_assign_constant(minus_one);
+        let num = num.into_num(cs)?;
+
+        let mut den = LinearCombination::zero();
+        den.add_assign_number_with_coeff(&y_squared, d);
+        den.add_assign_constant(E::Fr::one());
+        let den = den.into_num(cs)?;
+
+        let u = match (num.get_value(), den.get_value()) {
+            (Some(num), Some(den)) => {
+                let mut u = num;
+                u.mul_assign(&den.inverse().expect("d * y^2 + 1 is never zero"));
+                Some(u)
+            },
+            _ => None,
+        };
+        let is_square_value = u.map(|u| u.legendre() != LegendreSymbol::QuadraticNonResidue);
+        let w_value = match (u, is_square_value) {
+            (Some(u), Some(true)) => u.sqrt(),
+            (Some(mut u), Some(false)) => {
+                u.mul_assign(&n);
+                u.sqrt()
+            },
+            _ => None,
+        };
+
+        let is_square = Boolean::alloc(cs, is_square_value)?;
+        let w = Num::alloc(cs, w_value)?;
+
+        // w^2 * den = num * (is_square ? 1 : n)
+        let mut factor = LinearCombination::zero();
+        factor.add_assign_constant(n);
+        factor.add_assign_boolean_with_coeff(&is_square, one_minus_n);
+        let factor = factor.into_num(cs)?;
+
+        let w_squared = w.mul(cs, &w)?;
+        let lhs = w_squared.mul(cs, &den)?;
+        let rhs = num.mul(cs, &factor)?;
+        lhs.enforce_equal(cs, &rhs)?;
+
+        let is_selected = Boolean::and(cs, &none_found, &is_square)?;
+        selected_y = Num::conditionally_select(cs, &is_selected, &y, &selected_y)?;
+        selected_w = Num::conditionally_select(cs, &is_selected, &w, &selected_w)?;
+        none_found = Boolean::and(cs, &none_found, &is_square.not())?;
+    }
+    Boolean::enforce_equal(cs, &none_found, &Boolean::constant(false))?;
+
+    // the even root
+    let w_bits = selected_w.into_bits_le_strict(cs)?;
+    let minus_w = Num::zero().sub(cs, &selected_w)?;
+    let x = Num::conditionally_select(cs, &w_bits[0], &minus_w, &selected_w)?;
+
+    let mut h = CircuitTwistedEdwardsPoint { x, y: selected_y };
+    for _ in 0..curve.implementor.get_params().log_2_cofactor() {
+        h = curve.double(cs, &h)?;
+    }
+
+    Ok(h)
+}
+
+/// Verifies the proof of `crate::vrf::VrfPublicKey::verify` for the key `pk` and input `alpha`.
+/// Returns the output beta together with the result of the check. `pk` is expected to be a valid
+/// key in the prime order subgroup.
+pub fn verify_vrf<E, CS, C>(
+    cs: &mut CS,
+    curve: &CircuitTwistedEdwardsCurveImplementor<E, C>,
+    pk: &CircuitTwistedEdwardsPoint<E>,
+    alpha: &[Num<E>],
+    proof: &CircuitVrfProof<E>,
+    p_g: FixedGenerators,
+    jubjub_params: &<E as JubjubEngine>::Params,
+    poseidon_params: &<E as PoseidonEngine>::Params
+) -> Result<(Num<E>, Boolean), SynthesisError>
+    where E: JubjubEngine + PoseidonEngine,
+          CS: ConstraintSystem<E>,
+          C: TwistedEdwardsCurveParams<E>,
+          <<E as PoseidonEngine>::Params as PoseidonHashParams<E>>::SBox: PoseidonCsSBox<E>
+{
+    assert_eq!(proof.c.len(), VRF_CHALLENGE_BITS);
+
+    let h = hash_to_curve(cs, curve, pk, alpha, jubjub_params, poseidon_params)?;
+    let gamma_is_on_curve = curve.check_is_on_curve(cs, &proof.gamma)?;
+
+    let (g_x, g_y) = jubjub_params.generator(p_g).into_xy();
+    let generator = CircuitTwistedEdwardsPoint::constant(g_x, g_y);
+
+    // U = s . G + c . pk, V = s . H + c . Gamma
+    let s_g = curve.mul(cs, &generator, &proof.s)?;
+    let c_pk = curve.mul(cs, pk, &proof.c)?;
+    let u = curve.add(cs, &s_g, &c_pk)?;
+    let s_h = curve.mul(cs, &h, &proof.s)?;
+    let c_gamma = curve.mul(cs, &proof.gamma, &proof.c)?;
+    let v = curve.add(cs, &s_h, &c_gamma)?;
+
+    let mut input = Vec::with_capacity(10);
+    for p in [h, *pk, proof.gamma, u, v].iter() {
+        input.push(p.x);
+        input.push(p.y);
+    }
+    let hash = poseidon_hash(cs, poseidon_params, &input)?[0];
+    let mut expected_c = hash.into_bits_le_strict(cs)?;
+    expected_c.truncate(VRF_CHALLENGE_BITS);
+    let expected_c = pack_bits(cs, &expected_c)?;
+    let c = pack_bits(cs, &proof.c)?;
+    let challenge_matches = Num::equals(cs, &c, &expected_c)?;
+
+    // beta = Poseidon(8 . Gamma)
+    let mut gamma = proof.gamma;
+    for _ in 0..curve.implementor.get_params().log_2_cofactor() {
+        gamma = curve.double(cs, &gamma)?;
+    }
+    let beta = poseidon_hash(cs, poseidon_params, &[gamma.x, gamma.y])?[0];
+
+    let is_valid = Boolean::and(cs, &gamma_is_on_curve, &challenge_matches)?;
+
+    Ok((beta, is_valid))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use crate::bellman::plonk::better_better_cs::cs::*;
+    use crate::alt_babyjubjub::{AltJubjubBn256, fs::Fs, edwards};
+    use crate::jubjub::Unknown;
+    use crate::generic_twisted_edwards::TwistedEdwardsPoint;
+    use crate::plonk::circuit::edwards::bn256::CircuitAltBabyJubjubBn256;
+    use crate::plonk::circuit::utils::fe_to_lsb_first_bits;
+    use crate::poseidon::Bn256PoseidonParams;
+    use crate::vrf::VrfSecretKey;
+
+    fn alloc_point<CS: ConstraintSystem<Bn256>>(cs: &mut CS, p: &edwards::Point<Bn256, Unknown>) -> CircuitTwistedEdwardsPoint<Bn256> {
+        let curve = CircuitAltBabyJubjubBn256::get_implementor();
+        let (x, y) = p.into_xy();
+
+        curve.alloc_point_enforce_on_curve(cs, Some(TwistedEdwardsPoint::from_xy(x, y))).unwrap()
+    }
+
+    fn alloc_bits<CS: ConstraintSystem<Bn256>>(cs: &mut CS, s: &Fs, len: usize) -> Vec<Boolean> {
+        let mut bits = fe_to_lsb_first_bits(s);
+        bits.truncate(len);
+        bits.into_iter().map(|b| Boolean::alloc(cs, Some(b)).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_vrf_gadget_matches_native() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let jubjub_params = AltJubjubBn256::new();
+        let poseidon_params = Bn256PoseidonParams::new_checked_2_into_1();
+        let curve = CircuitAltBabyJubjubBn256::get_implementor();
+        let p_g = FixedGenerators::SpendingKeyGenerator;
+
+        let sk = VrfSecretKey::<Bn256>(rng.gen());
+        let pk = sk.public_key(p_g, &jubjub_params);
+        let alpha: Vec<Fr> = (0..2).map(|_| rng.gen()).collect();
+        let (beta, proof) = sk.prove(&alpha, p_g, &jubjub_params, &poseidon_params).unwrap();
+
+        for (input, expected) in vec![(alpha.clone(), true), (vec![alpha[0], alpha[0]], false)].into_iter() {
+            let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+            let pk_point = alloc_point(&mut cs, &pk.0);
+            let alpha_nums: Vec<Num<Bn256>> = input.iter().map(|a| Num::alloc(&mut cs, Some(*a)).unwrap()).collect();
+            let circuit_proof = CircuitVrfProof {
+                gamma: alloc_point(&mut cs, &proof.gamma),
+                c: alloc_bits(&mut cs, &proof.c, VRF_CHALLENGE_BITS),
+                s: alloc_bits(&mut cs, &proof.s, Fs::NUM_BITS as usize),
+            };
+
+            let (circuit_beta, is_valid) = verify_vrf(
+                &mut cs, &curve, &pk_point, &alpha_nums, &circuit_proof, p_g, &jubjub_params, &poseidon_params
+            ).unwrap();
+
+            assert!(cs.is_satisfied());
+            assert_eq!(circuit_beta.get_value().unwrap(), beta);
+            assert_eq!(is_valid.get_value().unwrap(), expected);
+        }
+    }
+}
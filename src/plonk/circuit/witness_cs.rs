@@ -0,0 +1,337 @@
+use crate::bellman::pairing::Engine;
+
+use crate::bellman::pairing::ff::Field;
+
+use crate::bellman::SynthesisError;
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    ConstraintSystem,
+    Gate,
+    Index,
+    MainGate,
+    PlonkConstraintSystemParams,
+    TrivialAssembly,
+    Variable,
+};
+
+use crate::bellman::plonk::better_better_cs::lookup_tables::{
+    LookupTableApplication,
+    MultiTableApplication,
+};
+
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+/// A constraint system that only evaluates the witness. The gates of a circuit don't depend
+/// on its inputs, so a prover can synthesize the structure once, then run the circuit against
+/// a `WitnessCS` for every new set of inputs and `install` the values into the cached
+/// assembly. Variables are matched by allocation order, so the circuit has to allocate the
+/// same variables in the same order for all inputs. Lookups are not supported: the assembly
+/// keeps its own copy of the looked up values, which `install` doesn't replace.
+pub struct WitnessCS<E: Engine, P: PlonkConstraintSystemParams<E>, MG: MainGate<E>> {
+    pub input_assignments: Vec<E::Fr>,
+    pub aux_assignments: Vec<E::Fr>,
+    num_input_gates: usize,
+    num_aux_gates: usize,
+    main_gate: MG,
+    tables: Vec<Arc<LookupTableApplication<E>>>,
+    multitables: Vec<Arc<MultiTableApplication<E>>>,
+    num_lookups: usize,
+    explicit_zero: Option<Variable>,
+    explicit_one: Option<Variable>,
+    _marker: PhantomData<P>,
+}
+
+impl<E: Engine, P: PlonkConstraintSystemParams<E>, MG: MainGate<E>> WitnessCS<E, P, MG> {
+    pub fn new() -> Self {
+        WitnessCS {
+            input_assignments: vec![],
+            aux_assignments: vec![],
+            num_input_gates: 0,
+            num_aux_gates: 0,
+            main_gate: MG::default(),
+            tables: vec![],
+            multitables: vec![],
+            num_lookups: 0,
+            explicit_zero: None,
+            explicit_one: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Replaces the witness of `assembly`, which has to be synthesized from the same circuit.
+    /// Fails with `Unsatisfiable` if the circuit uses lookups, the assembly is left untouched.
+    pub fn install(self, assembly: &mut TrivialAssembly<E, P, MG>) -> Result<(), SynthesisError> {
+        if self.num_lookups != 0 {
+            return Err(SynthesisError::Unsatisfiable);
+        }
+        assert_eq!(assembly.input_assingments.len(), self.input_assignments.len(), "circuit allocated a different number of inputs");
+        assert_eq!(assembly.aux_assingments.len(), self.aux_assignments.len(), "circuit allocated a different number of variables");
+
+        assembly.input_assingments = self.input_assignments;
+        assembly.aux_assingments = self.aux_assignments;
+
+        Ok(())
+    }
+}
+
+impl<E: Engine, P: PlonkConstraintSystemParams<E>, MG: MainGate<E>> ConstraintSystem<E> for WitnessCS<E, P, MG> {
+    type Params = P;
+    type MainGate = MG;
+
+    fn alloc<F>(&mut self, value: F) -> Result<Variable, SynthesisError>
+        where F: FnOnce() -> Result<E::Fr, SynthesisError>
+    {
+        self.aux_assignments.push(value()?);
+
+        Ok(Variable::new_unchecked(Index::Aux(self.aux_assignments.len())))
+    }
+
+    fn alloc_input<F>(&mut self, value: F) -> Result<Variable, SynthesisError>
+        where F: FnOnce() -> Result<E::Fr, SynthesisError>
+    {
+        self.input_assignments.push(value()?);
+        self.num_input_gates += 1;
+
+        Ok(Variable::new_unchecked(Index::Input(self.input_assignments.len())))
+    }
+
+    fn get_main_gate(&self) -> &MG {
+        &self.main_gate
+    }
+
+    fn begin_gates_batch_for_step(&mut self) -> Result<(), SynthesisError> {
+        Ok(())
+    }
+
+    fn new_gate_in_batch<G: Gate<E>>(
+        &mut self,
+        _equation: &G,
+        _coefficients_assignments: &[E::Fr],
+        _variables_assignments: &[Variable],
+        _witness_assignments: &[E::Fr]
+    ) -> Result<(), SynthesisError> {
+        Ok(())
+    }
+
+    fn end_gates_batch_for_step(&mut self) -> Result<(), SynthesisError> {
+        self.num_aux_gates += 1;
+
+        Ok(())
+    }
+
+    fn allocate_variables_without_gate(
+        &mut self,
+        _variables_assignments: &[Variable],
+        _witness_assignments: &[E::Fr]
+    ) -> Result<(), SynthesisError> {
+        Ok(())
+    }
+
+    fn get_value(&self, variable: Variable) -> Result<E::Fr, SynthesisError> {
+        let value = match variable.get_unchecked() {
+            Index::Aux(0) => E::Fr::zero(),
+            Index::Input(i) => self.input_assignments[i - 1],
+            Index::Aux(i) => self.aux_assignments[i - 1],
+        };
+
+        Ok(value)
+    }
+
+    fn get_dummy_variable() -> Variable {
+        Variable::new_unchecked(Index::Aux(0))
+    }
+
+    // the constants are allocated at the same point as in the assembly, their gates are not
+    // needed here
+
+    fn get_explicit_zero(&mut self) -> Result<Variable, SynthesisError> {
+        if let Some(var) = self.explicit_zero {
+            return Ok(var);
+        }
+        let var = self.alloc(|| Ok(E::Fr::zero()))?;
+        self.num_aux_gates += 1;
+        self.explicit_zero = Some(var);
+
+        Ok(var)
+    }
+
+    fn get_explicit_one(&mut self) -> Result<Variable, SynthesisError> {
+        if let Some(var) = self.explicit_one {
+            return Ok(var);
+        }
+        let var = self.alloc(|| Ok(E::Fr::one()))?;
+        self.num_aux_gates += 1;
+        self.explicit_one = Some(var);
+
+        Ok(var)
+    }
+
+    fn add_table(&mut self, table: LookupTableApplication<E>) -> Result<Arc<LookupTableApplication<E>>, SynthesisError> {
+        let table = Arc::new(table);
+        self.tables.push(table.clone());
+
+        Ok(table)
+    }
+
+    fn get_table(&self, functional_name: &str) -> Result<Arc<LookupTableApplication<E>>, SynthesisError> {
+        self.tables.iter()
+            .find(|t| t.functional_name() == functional_name)
+            .cloned()
+            .ok_or(SynthesisError::AssignmentMissing)
+    }
+
+    fn add_multitable(&mut self, table: MultiTableApplication<E>) -> Result<(), SynthesisError> {
+        self.multitables.push(Arc::new(table));
+
+        Ok(())
+    }
+
+    fn get_multitable(&self, functional_name: &str) -> Result<Arc<MultiTableApplication<E>>, SynthesisError> {
+        self.multitables.iter()
+            .find(|t| t.functional_name() == functional_name)
+            .cloned()
+            .ok_or(SynthesisError::AssignmentMissing)
+    }
+
+    fn apply_single_lookup_gate(&mut self, _variables: &[Variable], _gate: Arc<LookupTableApplication<E>>) -> Result<(), SynthesisError> {
+        self.num_lookups += 1;
+
+        Ok(())
+    }
+
+    fn apply_multi_lookup_gate(&mut self, _variables: &[Variable], _gate: Arc<MultiTableApplication<E>>) -> Result<(), SynthesisError> {
+        self.num_lookups += 1;
+
+        Ok(())
+    }
+
+    fn get_current_step_number(&self) -> usize {
+        self.num_input_gates + self.num_aux_gates
+    }
+
+    fn get_current_aux_gate_number(&self) -> usize {
+        self.num_aux_gates
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use bellman::pairing::ff::PrimeField;
+    use crate::bellman::plonk::better_better_cs::cs::*;
+    use crate::plonk::circuit::allocated_num::{AllocatedNum, Num};
+    use crate::plonk::circuit::boolean::Boolean;
+    use crate::plonk::circuit::set_membership::enforce_membership_by_lookup;
+    use crate::plonk::prover::*;
+    use crate::bellman::kate_commitment::{Crs, CrsForMonomialForm};
+    use crate::bellman::worker::Worker;
+
+    type Params = PlonkCsWidth4WithNextStepParams;
+
+    struct MulCircuit {
+        a: u64,
+        b: u64,
+    }
+
+    impl Circuit<Bn256> for MulCircuit {
+        type MainGate = Width4MainGateWithDNext;
+
+        fn declare_used_gates() -> Result<Vec<Box<dyn GateInternal<Bn256>>>, SynthesisError> {
+            Ok(vec![Width4MainGateWithDNext::default().into_internal()])
+        }
+
+        fn synthesize<CS: ConstraintSystem<Bn256>>(&self, cs: &mut CS) -> Result<(), SynthesisError> {
+            synthesize_mul(cs, self.a, self.b);
+
+            Ok(())
+        }
+    }
+
+    fn synthesize_mul<CS: ConstraintSystem<Bn256>>(cs: &mut CS, a: u64, b: u64) -> Num<Bn256> {
+        let a = AllocatedNum::alloc_input(cs, || Ok(Fr::from_str(&a.to_string()).unwrap())).unwrap();
+        let b = Num::alloc(cs, Some(Fr::from_str(&b.to_string()).unwrap())).unwrap();
+        let c = Num::Variable(a).mul(cs, &b).unwrap();
+        let is_zero = c.is_zero(cs).unwrap();
+        Boolean::enforce_equal(cs, &is_zero, &Boolean::constant(false)).unwrap();
+
+        c.add(cs, &b).unwrap()
+    }
+
+    // public x in {1, 2, 7}, checked with a table
+    struct LookupCircuit {
+        x: u64,
+    }
+
+    impl Circuit<Bn256> for LookupCircuit {
+        type MainGate = Width4MainGateWithDNext;
+
+        fn declare_used_gates() -> Result<Vec<Box<dyn GateInternal<Bn256>>>, SynthesisError> {
+            Ok(vec![Width4MainGateWithDNext::default().into_internal()])
+        }
+
+        fn synthesize<CS: ConstraintSystem<Bn256>>(&self, cs: &mut CS) -> Result<(), SynthesisError> {
+            let set: Vec<Fr> = [1, 2, 7].iter().map(|v: &u64| Fr::from_str(&v.to_string()).unwrap()).collect();
+            let x = AllocatedNum::alloc_input(cs, || Ok(Fr::from_str(&self.x.to_string()).unwrap()))?;
+
+            enforce_membership_by_lookup(cs, &Num::Variable(x), &set, "witness cs test set")
+        }
+    }
+
+    #[test]
+    fn test_witness_only_resynthesis() {
+        let mut assembly = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+        synthesize_mul(&mut assembly, 3, 5);
+        assert!(assembly.is_satisfied());
+        let n = assembly.n();
+
+        let mut witness = WitnessCS::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+        let out = synthesize_mul(&mut witness, 4, 6);
+        assert_eq!(out.get_value().unwrap(), Fr::from_str("30").unwrap());
+        assert_eq!(witness.get_current_step_number(), n);
+
+        let out = match out {
+            Num::Variable(out) => out.get_variable(),
+            Num::Constant(_) => unreachable!(),
+        };
+        witness.install(&mut assembly).unwrap();
+        assert_eq!(assembly.n(), n);
+        assert!(assembly.is_satisfied());
+        assert_eq!(assembly.get_value(out).unwrap(), Fr::from_str("30").unwrap());
+    }
+
+    #[test]
+    fn test_witness_only_proof() {
+        let circuit = MulCircuit { a: 3, b: 5 };
+        let crs = Crs::<Bn256, CrsForMonomialForm>::crs_42(required_crs_size::<_, Params, _>(&circuit).unwrap(), &Worker::new());
+        let keys = generate_keys::<_, Params, _>(&circuit, &crs).unwrap();
+
+        let mut assembly = crate::plonk::prover::synthesize::<_, Params, _>(&circuit).unwrap();
+        let mut witness = WitnessCS::<Bn256, Params, Width4MainGateWithDNext>::new();
+        MulCircuit { a: 4, b: 6 }.synthesize(&mut witness).unwrap();
+        witness.install(&mut assembly).unwrap();
+        assert!(assembly.is_satisfied());
+
+        let proof = assembly.create_proof::<MulCircuit, Transcript<Bn256>>(&Worker::new(), &keys.setup, &crs, None).unwrap();
+        assert_eq!(proof.inputs, vec![Fr::from_str("4").unwrap()]);
+        assert!(verify_proof(&keys.vk, &proof).unwrap());
+    }
+
+    #[test]
+    fn test_witness_with_lookups_is_rejected() {
+        let circuit = LookupCircuit { x: 2 };
+        let crs = Crs::<Bn256, CrsForMonomialForm>::crs_42(required_crs_size::<_, Params, _>(&circuit).unwrap(), &Worker::new());
+        let keys = generate_keys::<_, Params, _>(&circuit, &crs).unwrap();
+
+        let mut assembly = crate::plonk::prover::synthesize::<_, Params, _>(&circuit).unwrap();
+        let mut witness = WitnessCS::<Bn256, Params, Width4MainGateWithDNext>::new();
+        LookupCircuit { x: 7 }.synthesize(&mut witness).unwrap();
+        assert!(witness.install(&mut assembly).is_err());
+
+        // the assembly keeps its witness and still proves
+        let proof = assembly.create_proof::<LookupCircuit, Transcript<Bn256>>(&Worker::new(), &keys.setup, &crs, None).unwrap();
+        assert_eq!(proof.inputs, vec![Fr::from_str("2").unwrap()]);
+        assert!(verify_proof(&keys.vk, &proof).unwrap());
+    }
+}
@@ -0,0 +1,123 @@
+use crate::bellman::pairing::Engine;
+
+use crate::bellman::pairing::ff::{Field, PrimeField, PrimeFieldRepr};
+
+use zkinterface::{BilinearConstraint, CircuitHeader, ConstraintSystem, Variables, Witness};
+
+use std::io::Write;
+
+use super::snarkjs::{Lc, R1cs};
+
+// zkInterface messages for the rank one form of a circuit, see `super::snarkjs::R1cs`. Variable
+// 0 is the constant one, as the zkInterface conventions require, the public inputs are the
+// instance variables and everything else is the witness.
+
+fn encode_values<F: PrimeField>(values: &[F]) -> Vec<u8> {
+    let mut bytes = vec![];
+    for value in values.iter() {
+        value.into_repr().write_le(&mut bytes).expect("writes to a vector succeed");
+    }
+
+    bytes
+}
+
+fn variables<F: PrimeField>(ids: Vec<u64>, values: Option<&[F]>) -> Variables {
+    Variables {
+        variable_ids: ids,
+        values: values.map(encode_values),
+    }
+}
+
+fn lc_variables<E: Engine>(lc: &Lc<E>) -> Variables {
+    let ids = lc.iter().map(|&(wire, _)| wire as u64).collect();
+    let coeffs: Vec<E::Fr> = lc.iter().map(|&(_, coeff)| coeff).collect();
+
+    variables(ids, Some(&coeffs[..]))
+}
+
+/// The header, with the values of the instance variables if `r1cs` has a witness.
+pub fn circuit_header<E: Engine>(r1cs: &R1cs<E>) -> CircuitHeader {
+    let instance = 1..(r1cs.num_public_inputs + 1);
+    let mut field_maximum = E::Fr::zero();
+    field_maximum.sub_assign(&E::Fr::one());
+
+    CircuitHeader {
+        instance_variables: variables(
+            instance.clone().map(|wire| wire as u64).collect(),
+            r1cs.witness.as_ref().map(|witness| &witness[instance])
+        ),
+        free_variable_id: r1cs.num_wires as u64,
+        field_maximum: Some(encode_values(&[field_maximum])),
+        configuration: None,
+    }
+}
+
+pub fn constraint_system<E: Engine>(r1cs: &R1cs<E>) -> ConstraintSystem {
+    ConstraintSystem {
+        constraints: r1cs.constraints.iter().map(|(a, b, c)| BilinearConstraint {
+            linear_combination_a: lc_variables::<E>(a),
+            linear_combination_b: lc_variables::<E>(b),
+            linear_combination_c: lc_variables::<E>(c),
+        }).collect(),
+    }
+}
+
+/// The values of the witness variables, `None` if `r1cs` has no witness.
+pub fn witness<E: Engine>(r1cs: &R1cs<E>) -> Option<Witness> {
+    let first = r1cs.num_public_inputs + 1;
+
+    r1cs.witness.as_ref().map(|values| Witness {
+        assigned_variables: variables(
+            (first..r1cs.num_wires).map(|wire| wire as u64).collect(),
+            Some(&values[first..])
+        ),
+    })
+}
+
+/// Writes the header, the constraint system and, if present, the witness as consecutive
+/// messages.
+pub fn write_zkif<E: Engine, W: Write>(r1cs: &R1cs<E>, mut writer: W) -> zkinterface::Result<()> {
+    circuit_header(r1cs).write_into(&mut writer)?;
+    constraint_system(r1cs).write_into(&mut writer)?;
+    if let Some(witness) = witness(r1cs) {
+        witness.write_into(&mut writer)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use crate::bellman::plonk::better_better_cs::cs::*;
+    use crate::plonk::circuit::allocated_num::{AllocatedNum, Num};
+    use zkinterface::consumers::simulator::Simulator;
+
+    #[test]
+    fn test_simulate_exported_circuit() {
+        let mut cs = TrivialAssembly::<Bn256, PlonkCsWidth4WithNextStepParams, Width4MainGateWithDNext>::new();
+
+        let a = AllocatedNum::alloc_input(&mut cs, || Ok(Fr::from_str("3").unwrap())).unwrap();
+        let b = Num::alloc(&mut cs, Some(Fr::from_str("5").unwrap())).unwrap();
+        let c = Num::Variable(a).mul(&mut cs, &b).unwrap();
+        c.add(&mut cs, &Num::Constant(Fr::from_str("7").unwrap())).unwrap();
+
+        let r1cs = R1cs::from_assembly(&cs).unwrap();
+        let mut simulator = Simulator::default();
+        simulator.ingest_header(&circuit_header(&r1cs)).unwrap();
+        simulator.ingest_witness(&witness(&r1cs).unwrap()).unwrap();
+        simulator.ingest_constraint_system(&constraint_system(&r1cs)).unwrap();
+
+        let mut broken = r1cs.clone();
+        broken.witness.as_mut().unwrap()[2] = Fr::from_str("6").unwrap();
+        let mut simulator = Simulator::default();
+        simulator.ingest_header(&circuit_header(&broken)).unwrap();
+        simulator.ingest_witness(&witness(&broken).unwrap()).unwrap();
+        assert!(simulator.ingest_constraint_system(&constraint_system(&broken)).is_err());
+
+        let mut bytes = vec![];
+        write_zkif(&r1cs, &mut bytes).unwrap();
+        assert!(!bytes.is_empty());
+    }
+}
@@ -0,0 +1,693 @@
+use crate::bellman::pairing::bn256::{Bn256, Fr};
+use crate::bellman::pairing::ff::Field;
+
+use crate::bellman::SynthesisError;
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    Circuit,
+    ConstraintSystem,
+    GateInternal,
+    Gate,
+    Width4MainGateWithDNext,
+};
+
+use crate::alt_babyjubjub::AltJubjubBn256;
+use crate::plonk::circuit::allocated_num::Num;
+use crate::plonk::circuit::boolean::Boolean;
+use crate::plonk::circuit::linear_combination::LinearCombination;
+use crate::plonk::circuit::fee::{enforce_fee_authorized, FeeAccumulator, FEE_BITS, TOTAL_FEE_BITS};
+use crate::plonk::circuit::public_data::{PublicData, PublicDataHash};
+use crate::redjubjub::Signature;
+
+use super::{alloc_u64, compute_merkle_root, enforce_if, pack_bits, TreeHash};
+use super::rollup::{
+    alloc_path,
+    apply_transfer,
+    dummy_signature,
+    enforce_signature,
+    Account,
+    AccountTree,
+    CircuitAccount,
+    TransferWitness,
+    ACCOUNT_INDEX_BITS,
+    BALANCE_BITS,
+    NONCE_BITS,
+};
+
+// Batches of rollup operations over the account tree of `rollup`. A batch has a fixed number of
+// slots and every slot synthesizes all the kinds of operations the batch is built with, each
+// behind a flag of the witness. At most one flag of a slot is set, a slot without one leaves
+// the state as it is. The root is threaded through the slots: an operation is checked against
+// the root the previous slot left and passes its new root on. The fees the operations charge
+// add up, see `circuit::fee`, and the total is credited to the account of the operator after
+// the last slot.
+//
+// The only public input is the public data commitment of `circuit::public_data` to
+//
+//      old_root || slot 0 || ... || slot n - 1 || operator || total_fee || new_root
+//
+// where a slot is the public data of every kind of operation in the order of registration, all
+// zero bytes for the inactive ones, the operator is the index of its account in 4 bytes and the
+// total fee takes 16 bytes.
+//
+// An operation only learns whether it is active. Its checks are conditioned on that, its
+// allocations and range checks are not, so an inactive operation is synthesized with a dummy
+// witness that satisfies them.
+
+/// A kind of operation of a batch.
+pub trait Operation<H: TreeHash<Bn256>> {
+    type Witness: Clone;
+
+    /// The witness of the slots where the operation is inactive.
+    fn dummy_witness(&self, tree_depth: usize) -> Self::Witness;
+
+    /// Applies the operation to the tree with `old_root`, pushes its public data and returns
+    /// the new root and the fee charged. The checks are enforced if `is_active`, the returned
+    /// values are meaningless otherwise. The fee is range checked and within the authorization
+    /// of the operation regardless, and the length of the public data must not depend on the
+    /// witness.
+    fn apply<CS: ConstraintSystem<Bn256>>(
+        &self,
+        cs: &mut CS,
+        hasher: &H,
+        tree_depth: usize,
+        witness: Option<&Self::Witness>,
+        is_active: &Boolean,
+        old_root: &Num<Bn256>,
+        public_data: &mut PublicData
+    ) -> Result<(Num<Bn256>, Num<Bn256>), SynthesisError>;
+}
+
+/// The operation of a slot, `kind` is the index of the operation in the batch.
+#[derive(Clone)]
+pub struct SlotWitness<W> {
+    pub kind: usize,
+    pub witness: W,
+}
+
+/// The account of the operator in the tree after the last slot.
+#[derive(Clone)]
+pub struct OperatorWitness {
+    pub index: u32,
+    pub account: Account,
+    pub path: Vec<Fr>,
+}
+
+impl OperatorWitness {
+    /// Credits `total_fee` to the operator in `tree` and records the witness.
+    pub fn apply<H: TreeHash<Bn256>>(tree: &mut AccountTree<H>, index: u32, account: &Account, total_fee: u64) -> Self {
+        let path = tree.path(index);
+        let mut new_account = account.clone();
+        new_account.balance += total_fee;
+        tree.insert(index, &new_account);
+
+        OperatorWitness { index, account: account.clone(), path }
+    }
+}
+
+#[derive(Clone)]
+pub struct BatchWitness<W> {
+    pub old_root: Fr,
+    /// One per slot, `None` for an empty slot.
+    pub slots: Vec<Option<SlotWitness<W>>>,
+    pub operator: OperatorWitness,
+}
+
+pub struct BatchCircuit<H: TreeHash<Bn256>, O: Operation<H>> {
+    pub hasher: H,
+    pub tree_depth: usize,
+    pub operations: Vec<O>,
+    pub batch_size: usize,
+    pub public_data_hash: PublicDataHash,
+    pub witness: Option<BatchWitness<O::Witness>>,
+}
+
+/// Builds a `BatchCircuit`. The operations are registered once, their index in the order of
+/// registration is the `kind` of the slots. The slots are filled in order, the ones left over
+/// are empty. Without `old_root` the circuit has no witness, as for the setup, with it the
+/// operator is required.
+pub struct BatchCircuitBuilder<H: TreeHash<Bn256>, O: Operation<H>> {
+    hasher: H,
+    tree_depth: usize,
+    operations: Vec<O>,
+    batch_size: usize,
+    public_data_hash: PublicDataHash,
+    old_root: Option<Fr>,
+    slots: Vec<Option<SlotWitness<O::Witness>>>,
+    operator: Option<OperatorWitness>,
+}
+
+impl<H: TreeHash<Bn256>, O: Operation<H>> BatchCircuitBuilder<H, O> {
+    pub fn new(hasher: H, tree_depth: usize) -> Self {
+        BatchCircuitBuilder {
+            hasher,
+            tree_depth,
+            operations: vec![],
+            batch_size: 1,
+            public_data_hash: PublicDataHash::Sha256,
+            old_root: None,
+            slots: vec![],
+            operator: None,
+        }
+    }
+
+    pub fn operation(mut self, operation: O) -> Self {
+        self.operations.push(operation);
+        self
+    }
+
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// SHA256 by default.
+    pub fn public_data_hash(mut self, hash: PublicDataHash) -> Self {
+        self.public_data_hash = hash;
+        self
+    }
+
+    /// Starts the witness from the tree with `old_root`.
+    pub fn old_root(mut self, old_root: Fr) -> Self {
+        self.old_root = Some(old_root);
+        self
+    }
+
+    pub fn slot(mut self, kind: usize, witness: O::Witness) -> Self {
+        assert!(kind < self.operations.len(), "register the operation first");
+        self.slots.push(Some(SlotWitness { kind, witness }));
+        self
+    }
+
+    pub fn empty_slot(mut self) -> Self {
+        self.slots.push(None);
+        self
+    }
+
+    pub fn operator(mut self, operator: OperatorWitness) -> Self {
+        self.operator = Some(operator);
+        self
+    }
+
+    pub fn build(self) -> BatchCircuit<H, O> {
+        assert!(!self.operations.is_empty());
+        assert!(self.slots.len() <= self.batch_size);
+        let batch_size = self.batch_size;
+        let mut slots = self.slots;
+        let operator = self.operator;
+        let witness = self.old_root.map(|old_root| {
+            slots.resize(batch_size, None);
+
+            BatchWitness { old_root, slots, operator: operator.expect("the operator is required with a witness") }
+        });
+
+        BatchCircuit {
+            hasher: self.hasher,
+            tree_depth: self.tree_depth,
+            operations: self.operations,
+            batch_size: self.batch_size,
+            public_data_hash: self.public_data_hash,
+            witness,
+        }
+    }
+}
+
+impl<H: TreeHash<Bn256>, O: Operation<H>> Circuit<Bn256> for BatchCircuit<H, O> {
+    type MainGate = Width4MainGateWithDNext;
+
+    fn declare_used_gates() -> Result<Vec<Box<dyn GateInternal<Bn256>>>, SynthesisError> {
+        Ok(vec![Width4MainGateWithDNext::default().into_internal()])
+    }
+
+    fn synthesize<CS: ConstraintSystem<Bn256>>(&self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let witness = self.witness.as_ref();
+
+        let old_root = Num::alloc(cs, witness.map(|w| w.old_root))?;
+        let mut public_data = PublicData::new();
+        public_data.push_field_element(cs, &old_root)?;
+
+        let mut root = old_root;
+        let mut fees = FeeAccumulator::new();
+        for slot in 0..self.batch_size {
+            let slot_witness = witness.map(|w| w.slots[slot].as_ref());
+
+            let mut num_active = LinearCombination::zero();
+            for (kind, operation) in self.operations.iter().enumerate() {
+                let is_active = Boolean::alloc(cs, slot_witness.map(|s| s.map(|s| s.kind == kind).unwrap_or(false)))?;
+                num_active.add_assign_boolean_with_coeff(&is_active, Fr::one());
+
+                let dummy;
+                let operation_witness = match slot_witness {
+                    Some(Some(s)) if s.kind == kind => Some(&s.witness),
+                    Some(_) => {
+                        dummy = operation.dummy_witness(self.tree_depth);
+                        Some(&dummy)
+                    },
+                    None => None,
+                };
+                let mut operation_data = PublicData::new();
+                let (new_root, fee) = operation.apply(cs, &self.hasher, self.tree_depth, operation_witness, &is_active, &root, &mut operation_data)?;
+                root = Num::conditionally_select(cs, &is_active, &new_root, &root)?;
+                public_data.extend_if(cs, &is_active, &operation_data)?;
+                let fee = Num::conditionally_select(cs, &is_active, &fee, &Num::zero())?;
+                fees.add(cs, &fee)?;
+            }
+            num_active.into_num(cs)?.enforce_bit_length(cs, 1)?;
+        }
+
+        // pay the operator
+        let total_fee = fees.total(cs)?;
+        let operator = witness.map(|w| &w.operator);
+        let operator_index = alloc_u64(cs, operator.map(|o| o.index as u64), self.tree_depth)?;
+        let operator_account = CircuitAccount::alloc(cs, operator.map(|o| &o.account))?;
+        let path = alloc_path(cs, operator.map(|o| &o.path[..]), self.tree_depth)?;
+        let leaf = operator_account.leaf(cs, &self.hasher)?;
+        compute_merkle_root(cs, &self.hasher, &leaf, &operator_index, &path)?.enforce_equal(cs, &root)?;
+
+        let new_balance = operator_account.balance.add(cs, &total_fee)?;
+        new_balance.enforce_bit_length(cs, BALANCE_BITS)?;
+        let operator_account = CircuitAccount { balance: new_balance, ..operator_account };
+        let leaf = operator_account.leaf(cs, &self.hasher)?;
+        let new_root = compute_merkle_root(cs, &self.hasher, &leaf, &operator_index, &path)?;
+
+        public_data.push_bits_le(&operator_index, ACCOUNT_INDEX_BITS / 8);
+        public_data.push_num(cs, &total_fee, TOTAL_FEE_BITS / 8)?;
+        public_data.push_field_element(cs, &new_root)?;
+        public_data.inputize(cs, self.public_data_hash)?;
+
+        Ok(())
+    }
+}
+
+/// Credit of an account by the layer below, which authorizes it.
+#[derive(Copy, Clone, Debug)]
+pub struct Deposit {
+    pub to: u32,
+    pub amount: u64,
+}
+
+impl Deposit {
+    /// to || amount in big endian bytes.
+    pub fn public_data(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&self.to.to_be_bytes());
+        bytes.extend_from_slice(&self.amount.to_be_bytes());
+
+        bytes
+    }
+}
+
+#[derive(Clone)]
+pub struct DepositWitness {
+    pub deposit: Deposit,
+    pub to: Account,
+    pub path: Vec<Fr>,
+}
+
+impl DepositWitness {
+    /// Applies the deposit to `tree` and records the witness. The deposit is not checked.
+    pub fn apply<H: TreeHash<Bn256>>(tree: &mut AccountTree<H>, deposit: Deposit, to: &Account) -> Self {
+        let path = tree.path(deposit.to);
+        let mut new_to = to.clone();
+        new_to.balance += deposit.amount;
+        tree.insert(deposit.to, &new_to);
+
+        DepositWitness { deposit, to: to.clone(), path }
+    }
+
+    pub fn dummy(tree_depth: usize) -> Self {
+        DepositWitness {
+            deposit: Deposit { to: 0, amount: 0 },
+            to: Account::empty(),
+            path: vec![Fr::zero(); tree_depth],
+        }
+    }
+}
+
+/// Exit of `amount` from account `from`, signed by its key like a transfer.
+#[derive(Copy, Clone, Debug)]
+pub struct Withdrawal {
+    pub from: u32,
+    pub amount: u64,
+    pub max_fee: u64,
+    pub nonce: u32,
+}
+
+impl Withdrawal {
+    /// The signed message, from || amount || max_fee || nonce in little endian bytes. It is
+    /// shorter than the one of a transfer, so the signature of one is not the signature of the
+    /// other.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&self.from.to_le_bytes());
+        bytes.extend_from_slice(&self.amount.to_le_bytes());
+        bytes.extend_from_slice(&self.max_fee.to_le_bytes());
+        bytes.extend_from_slice(&self.nonce.to_le_bytes());
+
+        bytes
+    }
+
+    /// from || amount || fee in big endian bytes.
+    pub fn public_data(&self, fee: u64) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&self.from.to_be_bytes());
+        bytes.extend_from_slice(&self.amount.to_be_bytes());
+        bytes.extend_from_slice(&fee.to_be_bytes());
+
+        bytes
+    }
+}
+
+#[derive(Clone)]
+pub struct WithdrawalWitness {
+    pub withdrawal: Withdrawal,
+    /// The fee charged, up to `withdrawal.max_fee`.
+    pub fee: u64,
+    pub signature: Signature,
+    pub from: Account,
+    pub path: Vec<Fr>,
+}
+
+impl WithdrawalWitness {
+    /// Applies the withdrawal to `tree` and records the witness. The withdrawal is not checked.
+    pub fn apply<H: TreeHash<Bn256>>(
+        tree: &mut AccountTree<H>,
+        withdrawal: Withdrawal,
+        fee: u64,
+        signature: Signature,
+        from: &Account
+    ) -> Self {
+        let path = tree.path(withdrawal.from);
+        let mut new_from = from.clone();
+        new_from.balance -= withdrawal.amount + fee;
+        new_from.nonce += 1;
+        tree.insert(withdrawal.from, &new_from);
+
+        WithdrawalWitness { withdrawal, fee, signature, from: from.clone(), path }
+    }
+
+    pub fn dummy(tree_depth: usize) -> Self {
+        WithdrawalWitness {
+            withdrawal: Withdrawal { from: 0, amount: 0, max_fee: 0, nonce: 0 },
+            fee: 0,
+            signature: dummy_signature(),
+            from: Account::empty(),
+            path: vec![Fr::zero(); tree_depth],
+        }
+    }
+}
+
+/// The operations on the accounts of `rollup`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RollupOperationKind {
+    Deposit,
+    Transfer,
+    Withdrawal,
+}
+
+#[derive(Clone)]
+pub enum RollupWitness {
+    Deposit(DepositWitness),
+    Transfer(TransferWitness),
+    Withdrawal(WithdrawalWitness),
+}
+
+pub struct RollupOperation {
+    pub jubjub_params: AltJubjubBn256,
+    pub kind: RollupOperationKind,
+}
+
+impl RollupOperation {
+    pub fn new(kind: RollupOperationKind) -> Self {
+        RollupOperation { jubjub_params: AltJubjubBn256::new(), kind }
+    }
+}
+
+impl<H: TreeHash<Bn256>> Operation<H> for RollupOperation {
+    type Witness = RollupWitness;
+
+    fn dummy_witness(&self, tree_depth: usize) -> RollupWitness {
+        match self.kind {
+            RollupOperationKind::Deposit => RollupWitness::Deposit(DepositWitness::dummy(tree_depth)),
+            RollupOperationKind::Transfer => RollupWitness::Transfer(TransferWitness::dummy(tree_depth)),
+            RollupOperationKind::Withdrawal => RollupWitness::Withdrawal(WithdrawalWitness::dummy(tree_depth)),
+        }
+    }
+
+    fn apply<CS: ConstraintSystem<Bn256>>(
+        &self,
+        cs: &mut CS,
+        hasher: &H,
+        tree_depth: usize,
+        witness: Option<&RollupWitness>,
+        is_active: &Boolean,
+        old_root: &Num<Bn256>,
+        public_data: &mut PublicData
+    ) -> Result<(Num<Bn256>, Num<Bn256>), SynthesisError> {
+        let params = &self.jubjub_params;
+        let result = match self.kind {
+            RollupOperationKind::Deposit => {
+                let witness = witness.map(|w| match w {
+                    RollupWitness::Deposit(w) => Ok(w),
+                    _ => Err(SynthesisError::Unsatisfiable),
+                }).transpose()?;
+                let (root, new_root) = apply_deposit(cs, hasher, tree_depth, witness, public_data)?;
+                let root_matches = Num::equals(cs, &root, old_root)?;
+                enforce_if(cs, is_active, &root_matches)?;
+
+                (new_root, Num::zero())
+            },
+            RollupOperationKind::Transfer => {
+                let witness = witness.map(|w| match w {
+                    RollupWitness::Transfer(w) => Ok(w),
+                    _ => Err(SynthesisError::Unsatisfiable),
+                }).transpose()?;
+                let transfer = apply_transfer(cs, hasher, tree_depth, witness, is_active, params)?;
+                let root_matches = Num::equals(cs, &transfer.old_root, old_root)?;
+                enforce_if(cs, is_active, &root_matches)?;
+                transfer.push_public_data(public_data);
+
+                (transfer.new_root, transfer.fee)
+            },
+            RollupOperationKind::Withdrawal => {
+                let witness = witness.map(|w| match w {
+                    RollupWitness::Withdrawal(w) => Ok(w),
+                    _ => Err(SynthesisError::Unsatisfiable),
+                }).transpose()?;
+                let (root, new_root, fee) = apply_withdrawal(cs, hasher, tree_depth, witness, is_active, params, public_data)?;
+                let root_matches = Num::equals(cs, &root, old_root)?;
+                enforce_if(cs, is_active, &root_matches)?;
+
+                (new_root, fee)
+            },
+        };
+
+        Ok(result)
+    }
+}
+
+// the roots before and after the deposit
+fn apply_deposit<CS: ConstraintSystem<Bn256>, H: TreeHash<Bn256>>(
+    cs: &mut CS,
+    hasher: &H,
+    tree_depth: usize,
+    witness: Option<&DepositWitness>,
+    public_data: &mut PublicData
+) -> Result<(Num<Bn256>, Num<Bn256>), SynthesisError> {
+    let deposit = witness.map(|w| w.deposit);
+    let index = alloc_u64(cs, deposit.map(|d| d.to as u64), tree_depth)?;
+    let amount_bits = alloc_u64(cs, deposit.map(|d| d.amount), BALANCE_BITS)?;
+    let amount = pack_bits(cs, &amount_bits)?;
+
+    let to = CircuitAccount::alloc(cs, witness.map(|w| &w.to))?;
+    let path = alloc_path(cs, witness.map(|w| &w.path[..]), tree_depth)?;
+    let root = compute_merkle_root(cs, hasher, &to.leaf(cs, hasher)?, &index, &path)?;
+
+    let new_balance = to.balance.add(cs, &amount)?;
+    new_balance.enforce_bit_length(cs, BALANCE_BITS)?;
+    let new_to = CircuitAccount { balance: new_balance, ..to };
+    let new_root = compute_merkle_root(cs, hasher, &new_to.leaf(cs, hasher)?, &index, &path)?;
+
+    public_data.push_bits_le(&index, ACCOUNT_INDEX_BITS / 8);
+    public_data.push_bits_le(&amount_bits, BALANCE_BITS / 8);
+
+    Ok((root, new_root))
+}
+
+// the roots before and after the withdrawal and the fee
+fn apply_withdrawal<CS: ConstraintSystem<Bn256>, H: TreeHash<Bn256>>(
+    cs: &mut CS,
+    hasher: &H,
+    tree_depth: usize,
+    witness: Option<&WithdrawalWitness>,
+    is_active: &Boolean,
+    params: &AltJubjubBn256,
+    public_data: &mut PublicData
+) -> Result<(Num<Bn256>, Num<Bn256>, Num<Bn256>), SynthesisError> {
+    let withdrawal = witness.map(|w| w.withdrawal);
+    let index = alloc_u64(cs, withdrawal.map(|w| w.from as u64), tree_depth)?;
+    let amount_bits = alloc_u64(cs, withdrawal.map(|w| w.amount), BALANCE_BITS)?;
+    let amount = pack_bits(cs, &amount_bits)?;
+    let max_fee_bits = alloc_u64(cs, withdrawal.map(|w| w.max_fee), FEE_BITS)?;
+    let fee_bits = alloc_u64(cs, witness.map(|w| w.fee), FEE_BITS)?;
+    let fee = pack_bits(cs, &fee_bits)?;
+    enforce_fee_authorized(cs, &fee, &pack_bits(cs, &max_fee_bits)?)?;
+    let nonce_bits = alloc_u64(cs, withdrawal.map(|w| w.nonce as u64), NONCE_BITS)?;
+
+    let from = CircuitAccount::alloc(cs, witness.map(|w| &w.from))?;
+    let nonce_matches = Num::equals(cs, &from.nonce, &pack_bits(cs, &nonce_bits)?)?;
+    enforce_if(cs, is_active, &nonce_matches)?;
+    let path = alloc_path(cs, witness.map(|w| &w.path[..]), tree_depth)?;
+    let root = compute_merkle_root(cs, hasher, &from.leaf(cs, hasher)?, &index, &path)?;
+
+    let mut message = index.clone();
+    message.resize(ACCOUNT_INDEX_BITS, Boolean::constant(false));
+    message.extend(amount_bits.iter().cloned());
+    message.extend(max_fee_bits);
+    message.extend(nonce_bits.iter().cloned());
+    enforce_signature(cs, &from, witness.map(|w| &w.signature), &message, is_active, params)?;
+
+    let new_balance = from.balance.sub(cs, &amount)?.sub(cs, &fee)?;
+    new_balance.enforce_bit_length(cs, BALANCE_BITS)?;
+    let new_from = CircuitAccount {
+        pub_key: from.pub_key,
+        balance: new_balance,
+        nonce: from.nonce.add(cs, &Num::Constant(Fr::one()))?,
+    };
+    let new_root = compute_merkle_root(cs, hasher, &new_from.leaf(cs, hasher)?, &index, &path)?;
+
+    public_data.push_bits_le(&index, ACCOUNT_INDEX_BITS / 8);
+    public_data.push_bits_le(&amount_bits, BALANCE_BITS / 8);
+    public_data.push_bits_le(&fee_bits, FEE_BITS / 8);
+
+    Ok((root, new_root, fee))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use crate::bellman::kate_commitment::{Crs, CrsForMonomialForm};
+    use crate::bellman::plonk::better_better_cs::cs::PlonkCsWidth4WithNextStepParams;
+    use crate::bellman::worker::Worker;
+    use crate::bellman::pairing::ff::{PrimeField, PrimeFieldRepr};
+    use crate::jubjub::FixedGenerators;
+    use crate::plonk::circuit::public_data::public_data_commitment;
+    use crate::plonk::examples::PoseidonTreeHash;
+    use crate::plonk::examples::rollup::Transfer;
+    use crate::plonk::prover::*;
+    use crate::poseidon::Bn256PoseidonParams;
+    use crate::redjubjub::{PrivateKey, PublicKey};
+
+    type Params = PlonkCsWidth4WithNextStepParams;
+
+    const TREE_DEPTH: usize = 2;
+
+    fn hasher() -> PoseidonTreeHash<Bn256> {
+        PoseidonTreeHash { params: Bn256PoseidonParams::new_checked_2_into_1() }
+    }
+
+    fn builder() -> BatchCircuitBuilder<PoseidonTreeHash<Bn256>, RollupOperation> {
+        BatchCircuitBuilder::new(hasher(), TREE_DEPTH)
+            .operation(RollupOperation::new(RollupOperationKind::Deposit))
+            .operation(RollupOperation::new(RollupOperationKind::Transfer))
+            .operation(RollupOperation::new(RollupOperationKind::Withdrawal))
+            .batch_size(4)
+    }
+
+    #[test]
+    fn test_batch() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = AltJubjubBn256::new();
+        let p_g = FixedGenerators::SpendingKeyGenerator;
+
+        let alice_sk = PrivateKey::<Bn256>(rng.gen());
+        let mut alice = Account { pub_key: PublicKey::from_private(&alice_sk, p_g, &params), balance: 0, nonce: 0 };
+        let bob_sk = PrivateKey::<Bn256>(rng.gen());
+        let mut bob = Account { pub_key: PublicKey::from_private(&bob_sk, p_g, &params), balance: 10, nonce: 0 };
+
+        let operator = Account::empty();
+
+        let mut tree = AccountTree::new(hasher(), TREE_DEPTH);
+        tree.insert(0, &alice);
+        tree.insert(1, &operator);
+        tree.insert(3, &bob);
+        let old_root = tree.root();
+
+        // deposit to alice, alice pays bob, an empty slot, bob withdraws, the operator collects
+        let deposit = Deposit { to: 0, amount: 50 };
+        let deposit_data = deposit.public_data();
+        let deposit = DepositWitness::apply(&mut tree, deposit, &alice);
+        alice.balance += 50;
+
+        let transfer = Transfer { from: 0, to: 3, amount: 20, max_fee: 2, nonce: 0 };
+        let transfer_data = transfer.public_data(2);
+        let signature = alice_sk.sign(&transfer.to_bytes(), &mut rng, p_g, &params);
+        let transfer = TransferWitness::apply(&mut tree, transfer, 2, signature, &alice, &bob);
+        bob.balance += 20;
+
+        let withdrawal = Withdrawal { from: 3, amount: 25, max_fee: 5, nonce: 0 };
+        let withdrawal_data = withdrawal.public_data(1);
+        let signature = bob_sk.sign(&withdrawal.to_bytes(), &mut rng, p_g, &params);
+        let withdrawal = WithdrawalWitness::apply(&mut tree, withdrawal, 1, signature, &bob);
+
+        let operator = OperatorWitness::apply(&mut tree, 1, &operator, 3);
+        let new_root = tree.root();
+
+        let circuit = builder()
+            .old_root(old_root)
+            .slot(0, RollupWitness::Deposit(deposit))
+            .slot(1, RollupWitness::Transfer(transfer.clone()))
+            .empty_slot()
+            .slot(2, RollupWitness::Withdrawal(withdrawal.clone()))
+            .operator(operator.clone())
+            .build();
+
+        let setup = builder().build();
+        let crs = Crs::<Bn256, CrsForMonomialForm>::crs_42(required_crs_size::<_, Params, _>(&setup).unwrap(), &Worker::new());
+        let keys = generate_keys::<_, Params, _>(&setup, &crs).unwrap();
+        let proof = create_proof::<_, Params, _>(&circuit, &keys, &crs).unwrap();
+        let mut public_data = vec![];
+        old_root.into_repr().write_be(&mut public_data).unwrap();
+        let slots = [
+            [&deposit_data[..], &[0u8; 24][..], &[0u8; 20][..]],
+            [&[0u8; 12][..], &transfer_data[..], &[0u8; 20][..]],
+            [&[0u8; 12][..], &[0u8; 24][..], &[0u8; 20][..]],
+            [&[0u8; 12][..], &[0u8; 24][..], &withdrawal_data[..]],
+        ];
+        for data in slots.iter().flat_map(|slot| slot.iter()) {
+            public_data.extend_from_slice(data);
+        }
+        public_data.extend_from_slice(&1u32.to_be_bytes());
+        public_data.extend_from_slice(&3u128.to_be_bytes());
+        new_root.into_repr().write_be(&mut public_data).unwrap();
+        assert_eq!(proof.inputs, vec![public_data_commitment::<Fr>(&public_data, PublicDataHash::Sha256)]);
+        assert!(verify_proof(&keys.vk, &proof).unwrap());
+
+        // the transfer doesn't apply before the deposit, alice has nothing yet
+        let reordered = builder()
+            .old_root(old_root)
+            .slot(1, RollupWitness::Transfer(transfer))
+            .operator(operator.clone())
+            .build();
+        assert!(create_proof::<_, Params, _>(&reordered, &keys, &crs).is_err());
+
+        // a withdrawal can't pass for a transfer of the same slot
+        let mislabeled = builder()
+            .old_root(old_root)
+            .slot(1, RollupWitness::Withdrawal(withdrawal.clone()))
+            .operator(operator.clone())
+            .build();
+        assert!(create_proof::<_, Params, _>(&mislabeled, &keys, &crs).is_err());
+
+        // the operator can't charge more than authorized
+        let mut overcharged = withdrawal;
+        overcharged.fee = 6;
+        let overcharged = builder()
+            .old_root(old_root)
+            .slot(2, RollupWitness::Withdrawal(overcharged))
+            .operator(operator)
+            .build();
+        assert!(create_proof::<_, Params, _>(&overcharged, &keys, &crs).is_err());
+    }
+}
@@ -0,0 +1,212 @@
+// Complete circuits composed from the gadgets of `plonk::circuit`, meant to be forked. The
+// spend and output circuits are fixed to Bn256 with the alt Baby Jubjub curve and Poseidon.
+// All of them prove with `plonk::prover` under `PlonkCsWidth4WithNextStepParams`.
+
+pub mod spend;
+pub mod output;
+pub mod shielded_pool;
+pub mod rollup;
+pub mod batch;
+pub mod solvency;
+pub mod voting;
+
+use crate::bellman::pairing::Engine;
+use crate::bellman::pairing::bn256::Bn256;
+use crate::bellman::pairing::ff::{Field, PrimeField};
+
+use crate::bellman::SynthesisError;
+
+use crate::bellman::plonk::better_better_cs::cs::ConstraintSystem;
+
+use crate::alt_babyjubjub::{fs::Fs, edwards};
+use crate::generic_twisted_edwards::{TwistedEdwardsCurveParams, TwistedEdwardsPoint};
+use crate::generic_twisted_edwards::bn256::AltBabyJubjubParams;
+use crate::jubjub::JubjubEngine;
+use crate::pedersen_hash::{pedersen_hash, Personalization};
+use crate::plonk::circuit::allocated_num::Num;
+use crate::plonk::circuit::boolean::Boolean;
+use crate::plonk::circuit::edwards::{CircuitTwistedEdwardsCurveImplementor, CircuitTwistedEdwardsPoint};
+use crate::plonk::circuit::linear_combination::LinearCombination;
+use crate::plonk::circuit::pedersen_hash::pedersen_hash_point;
+use crate::plonk::circuit::poseidon::{poseidon_hash, PoseidonCsSBox};
+use crate::plonk::circuit::rescue::{rescue_hash, PlonkCsSBox};
+use crate::plonk::circuit::utils::fe_to_lsb_first_bits;
+use crate::poseidon::{PoseidonEngine, PoseidonHashParams};
+use crate::rescue::{RescueEngine, RescueHashParams};
+
+type Curve = CircuitTwistedEdwardsCurveImplementor<Bn256, AltBabyJubjubParams>;
+
+/// A hash to field elements, natively and in the circuit, for the trees of the templates.
+pub trait TreeHash<E: Engine> {
+    fn hash(&self, input: &[E::Fr]) -> E::Fr;
+
+    fn hash_in_circuit<CS: ConstraintSystem<E>>(&self, cs: &mut CS, input: &[Num<E>]) -> Result<Num<E>, SynthesisError>;
+}
+
+pub struct PoseidonTreeHash<E: PoseidonEngine> {
+    pub params: E::Params,
+}
+
+impl<E: PoseidonEngine> TreeHash<E> for PoseidonTreeHash<E>
+    where <<E as PoseidonEngine>::Params as PoseidonHashParams<E>>::SBox: PoseidonCsSBox<E>
+{
+    fn hash(&self, input: &[E::Fr]) -> E::Fr {
+        crate::poseidon::poseidon_hash::<E>(&self.params, input)[0]
+    }
+
+    fn hash_in_circuit<CS: ConstraintSystem<E>>(&self, cs: &mut CS, input: &[Num<E>]) -> Result<Num<E>, SynthesisError> {
+        Ok(poseidon_hash(cs, &self.params, input)?[0])
+    }
+}
+
+pub struct RescueTreeHash<E: RescueEngine> {
+    pub params: E::Params,
+}
+
+impl<E: RescueEngine> TreeHash<E> for RescueTreeHash<E>
+    where <<E as RescueEngine>::Params as RescueHashParams<E>>::SBox0: PlonkCsSBox<E>,
+          <<E as RescueEngine>::Params as RescueHashParams<E>>::SBox1: PlonkCsSBox<E>
+{
+    fn hash(&self, input: &[E::Fr]) -> E::Fr {
+        crate::rescue::rescue_hash::<E>(&self.params, input)[0]
+    }
+
+    fn hash_in_circuit<CS: ConstraintSystem<E>>(&self, cs: &mut CS, input: &[Num<E>]) -> Result<Num<E>, SynthesisError> {
+        Ok(rescue_hash(cs, &self.params, input)?[0])
+    }
+}
+
+/// The x coordinate of the Pedersen hash of the little endian bits of the inputs, every input
+/// takes `E::Fr::NUM_BITS` bits.
+pub struct PedersenTreeHash<E: JubjubEngine, C: TwistedEdwardsCurveParams<E>> {
+    pub params: E::Params,
+    pub curve: CircuitTwistedEdwardsCurveImplementor<E, C>,
+    pub personalization: Personalization,
+}
+
+impl<E: JubjubEngine, C: TwistedEdwardsCurveParams<E>> TreeHash<E> for PedersenTreeHash<E, C> {
+    fn hash(&self, input: &[E::Fr]) -> E::Fr {
+        let bits = input.iter().flat_map(|x| fe_to_lsb_first_bits(x).into_iter().take(E::Fr::NUM_BITS as usize));
+
+        pedersen_hash::<E, _>(self.personalization, bits, &self.params).into_xy().0
+    }
+
+    fn hash_in_circuit<CS: ConstraintSystem<E>>(&self, cs: &mut CS, input: &[Num<E>]) -> Result<Num<E>, SynthesisError> {
+        let mut bits = vec![];
+        for x in input.iter() {
+            bits.extend(x.into_bits_le_strict(cs)?);
+        }
+
+        Ok(pedersen_hash_point(cs, &self.curve, self.personalization, &bits, &self.params)?.x)
+    }
+}
+
+/// Root of the tree with `leaf` at `index` and the authentication path `path`, siblings are
+/// ordered from the leaf level up and a node is H(left, right).
+pub fn merkle_root<E: Engine, H: TreeHash<E>>(hasher: &H, leaf: E::Fr, index: u64, path: &[E::Fr]) -> E::Fr {
+    let mut current = leaf;
+    for (height, sibling) in path.iter().enumerate() {
+        current = if (index >> height) & 1 == 0 {
+            hasher.hash(&[current, *sibling])
+        } else {
+            hasher.hash(&[*sibling, current])
+        };
+    }
+
+    current
+}
+
+/// `merkle_root` in the circuit, the index is given by little endian bits.
+pub fn compute_merkle_root<E, CS, H>(
+    cs: &mut CS,
+    hasher: &H,
+    leaf: &Num<E>,
+    index: &[Boolean],
+    path: &[Num<E>]
+) -> Result<Num<E>, SynthesisError>
+    where E: Engine,
+          CS: ConstraintSystem<E>,
+          H: TreeHash<E>
+{
+    assert_eq!(index.len(), path.len());
+
+    let mut current = *leaf;
+    for (bit, sibling) in index.iter().zip(path.iter()) {
+        let (left, right) = Num::conditionally_reverse(cs, &current, sibling, bit)?;
+        current = hasher.hash_in_circuit(cs, &[left, right])?;
+    }
+
+    Ok(current)
+}
+
+/// `num_bits` little endian bits of `value`.
+fn alloc_u64<CS: ConstraintSystem<Bn256>>(cs: &mut CS, value: Option<u64>, num_bits: usize) -> Result<Vec<Boolean>, SynthesisError> {
+    (0..num_bits).map(|i| Boolean::alloc(cs, value.map(|v| (v >> i) & 1 == 1))).collect()
+}
+
+/// Little endian bits of a scalar.
+fn alloc_scalar<CS: ConstraintSystem<Bn256>>(cs: &mut CS, value: Option<&Fs>) -> Result<Vec<Boolean>, SynthesisError> {
+    let bits = value.map(fe_to_lsb_first_bits);
+
+    (0..Fs::NUM_BITS as usize).map(|i| Boolean::alloc(cs, bits.as_ref().map(|b| b[i]))).collect()
+}
+
+fn alloc_point<CS: ConstraintSystem<Bn256>, S>(
+    cs: &mut CS,
+    curve: &Curve,
+    value: Option<&edwards::Point<Bn256, S>>
+) -> Result<CircuitTwistedEdwardsPoint<Bn256>, SynthesisError> {
+    let value = value.map(|p| {
+        let (x, y) = p.into_xy();
+        TwistedEdwardsPoint::from_xy(x, y)
+    });
+
+    curve.alloc_point_enforce_on_curve(cs, value)
+}
+
+// [8] p is not the identity, so p is not of small order. The identity is the only point of
+// the image of [8] with x = 0, the point (0, -1) has order 2
+fn assert_not_small_order<CS: ConstraintSystem<Bn256>>(
+    cs: &mut CS,
+    curve: &Curve,
+    p: &CircuitTwistedEdwardsPoint<Bn256>
+) -> Result<(), SynthesisError> {
+    let mut q = curve.double(cs, p)?;
+    q = curve.double(cs, &q)?;
+    q = curve.double(cs, &q)?;
+
+    q.x.assert_not_zero(cs)
+}
+
+// sum 2^i * bits[i]
+fn pack_bits<E: Engine, CS: ConstraintSystem<E>>(cs: &mut CS, bits: &[Boolean]) -> Result<Num<E>, SynthesisError> {
+    assert!(bits.len() <= E::Fr::CAPACITY as usize);
+
+    let mut lc = LinearCombination::zero();
+    let mut coeff = E::Fr::one();
+    for bit in bits.iter() {
+        lc.add_assign_boolean_with_coeff(bit, coeff);
+        coeff.double();
+    }
+
+    lc.into_num(cs)
+}
+
+// enforces `condition` if `is_active`
+fn enforce_if<E: Engine, CS: ConstraintSystem<E>>(cs: &mut CS, is_active: &Boolean, condition: &Boolean) -> Result<(), SynthesisError> {
+    let is_violated = Boolean::and(cs, is_active, &condition.not())?;
+
+    Boolean::enforce_equal(cs, &is_violated, &Boolean::constant(false))
+}
+
+fn inputize<CS: ConstraintSystem<Bn256>>(cs: &mut CS, num: &Num<Bn256>) -> Result<(), SynthesisError> {
+    match num {
+        Num::Variable(ref var) => var.inputize(cs),
+        Num::Constant(_) => unreachable!("public inputs are computed from the witness"),
+    }
+}
+
+fn inputize_point<CS: ConstraintSystem<Bn256>>(cs: &mut CS, p: &CircuitTwistedEdwardsPoint<Bn256>) -> Result<(), SynthesisError> {
+    inputize(cs, &p.x)?;
+    inputize(cs, &p.y)
+}
@@ -0,0 +1,156 @@
+use crate::bellman::pairing::bn256::Bn256;
+
+use crate::bellman::SynthesisError;
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    Circuit,
+    ConstraintSystem,
+    GateInternal,
+    Gate,
+    Width4MainGateWithDNext,
+};
+
+use crate::alt_babyjubjub::{AltJubjubBn256, fs::Fs};
+use crate::plonk::circuit::edwards::bn256::CircuitAltBabyJubjubBn256;
+use crate::plonk::circuit::note_commitment::{note_commitment, NOTE_VALUE_BITS};
+use crate::plonk::circuit::value_commitment::value_commitment;
+use crate::primitives::Note;
+
+use super::{alloc_point, alloc_scalar, alloc_u64, assert_not_small_order, inputize, inputize_point};
+
+// Creation of a note, Sapling style. The public inputs, in order:
+//
+//      cv.x, cv.y     value commitment of the note value with randomness rcv
+//      epk.x, epk.y   ephemeral key of the note encryption, esk . g_d
+//      cm             note commitment, the leaf appended to the tree
+//
+// Points are checked to be on the curve, not to be in the prime order subgroup, g_d not to be of
+// small order.
+
+/// The sender's knowledge.
+#[derive(Clone)]
+pub struct OutputWitness {
+    pub note: Note<Bn256>,
+    /// Randomness of the value commitment.
+    pub rcv: Fs,
+    /// Ephemeral secret key of the note encryption.
+    pub esk: Fs,
+}
+
+pub struct OutputCircuit {
+    pub jubjub_params: AltJubjubBn256,
+    pub witness: Option<OutputWitness>,
+}
+
+impl OutputCircuit {
+    pub fn new(witness: Option<OutputWitness>) -> Self {
+        OutputCircuit {
+            jubjub_params: AltJubjubBn256::new(),
+            witness,
+        }
+    }
+}
+
+impl Circuit<Bn256> for OutputCircuit {
+    type MainGate = Width4MainGateWithDNext;
+
+    fn declare_used_gates() -> Result<Vec<Box<dyn GateInternal<Bn256>>>, SynthesisError> {
+        Ok(vec![Width4MainGateWithDNext::default().into_internal()])
+    }
+
+    fn synthesize<CS: ConstraintSystem<Bn256>>(&self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let params = &self.jubjub_params;
+        let curve = CircuitAltBabyJubjubBn256::get_implementor();
+        let witness = self.witness.as_ref();
+
+        let value = alloc_u64(cs, witness.map(|w| w.note.value), NOTE_VALUE_BITS)?;
+        let rcv = alloc_scalar(cs, witness.map(|w| &w.rcv))?;
+        let cv = value_commitment(cs, &curve, &value, &rcv, params)?;
+        inputize_point(cs, &cv)?;
+
+        let g_d = alloc_point(cs, &curve, witness.map(|w| &w.note.g_d))?;
+        assert_not_small_order(cs, &curve, &g_d)?;
+        let esk = alloc_scalar(cs, witness.map(|w| &w.esk))?;
+        let epk = curve.mul(cs, &g_d, &esk)?;
+        inputize_point(cs, &epk)?;
+
+        let pk_d = alloc_point(cs, &curve, witness.map(|w| &w.note.pk_d))?;
+        let r = alloc_scalar(cs, witness.map(|w| &w.note.r))?;
+        let cm = note_commitment(cs, &curve, &value, &g_d, &pk_d, &r, params)?;
+        inputize(cs, &cm.cm)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use crate::alt_babyjubjub::edwards;
+    use crate::bellman::kate_commitment::{Crs, CrsForMonomialForm};
+    use crate::bellman::pairing::bn256::Fr;
+    use crate::bellman::pairing::ff::Field;
+    use crate::bellman::plonk::better_better_cs::cs::{PlonkCsWidth4WithNextStepParams, TrivialAssembly};
+    use crate::bellman::worker::Worker;
+    use crate::jubjub::{FixedGenerators, JubjubParams, PrimeOrder, Unknown};
+    use crate::plonk::prover::*;
+    use crate::primitives::ValueCommitment;
+
+    type Params = PlonkCsWidth4WithNextStepParams;
+
+    #[test]
+    fn test_output_circuit() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = AltJubjubBn256::new();
+
+        let g_d: edwards::Point<Bn256, PrimeOrder> = params.generator(FixedGenerators::SpendingKeyGenerator).mul(rng.gen::<Fs>(), &params);
+        let witness = OutputWitness {
+            note: Note {
+                value: rng.gen(),
+                pk_d: g_d.mul(rng.gen::<Fs>(), &params),
+                g_d,
+                r: rng.gen(),
+            },
+            rcv: rng.gen(),
+            esk: rng.gen(),
+        };
+        let circuit = OutputCircuit::new(Some(witness.clone()));
+
+        let cv = ValueCommitment::<Bn256> { value: witness.note.value, randomness: witness.rcv }.cm(&params).into_xy();
+        let epk = witness.note.g_d.mul(witness.esk, &params).into_xy();
+        let expected_inputs = vec![cv.0, cv.1, epk.0, epk.1, witness.note.cm(&params)];
+
+        let crs = Crs::<Bn256, CrsForMonomialForm>::crs_42(required_crs_size::<_, Params, _>(&circuit).unwrap(), &Worker::new());
+        let keys = generate_keys::<_, Params, _>(&circuit, &crs).unwrap();
+        let proof = create_proof::<_, Params, _>(&circuit, &keys, &crs).unwrap();
+        assert_eq!(proof.inputs, expected_inputs);
+        assert!(verify_proof(&keys.vk, &proof).unwrap());
+
+        let mut forged = proof;
+        forged.inputs[4] = rng.gen();
+        assert!(!verify_proof(&keys.vk, &forged).unwrap());
+    }
+
+    #[test]
+    fn test_small_order_points_are_rejected() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = AltJubjubBn256::new();
+        let curve = CircuitAltBabyJubjubBn256::get_implementor();
+
+        let mut minus_one = Fr::one();
+        minus_one.negate();
+        let torsion = edwards::Point::<Bn256, Unknown>::get_for_y(minus_one, false, &params).unwrap();
+        let g_d: edwards::Point<Bn256, PrimeOrder> = params.generator(FixedGenerators::SpendingKeyGenerator).mul(rng.gen::<Fs>(), &params);
+
+        let mut cs = TrivialAssembly::<Bn256, Params, Width4MainGateWithDNext>::new();
+        let p = alloc_point(&mut cs, &curve, Some(&g_d)).unwrap();
+        assert_not_small_order(&mut cs, &curve, &p).unwrap();
+        // nor is a point with a torsion component
+        let p = alloc_point(&mut cs, &curve, Some(&g_d.as_unknown().add(&torsion, &params))).unwrap();
+        assert_not_small_order(&mut cs, &curve, &p).unwrap();
+        assert!(cs.is_satisfied());
+
+        let mut cs = TrivialAssembly::<Bn256, Params, Width4MainGateWithDNext>::new();
+        let p = alloc_point(&mut cs, &curve, Some(&torsion)).unwrap();
+        assert!(assert_not_small_order(&mut cs, &curve, &p).is_err());
+    }
+}
@@ -0,0 +1,564 @@
+use crate::bellman::pairing::bn256::{Bn256, Fr};
+use crate::bellman::pairing::ff::{Field, PrimeField, PrimeFieldRepr};
+
+use crate::bellman::SynthesisError;
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    Circuit,
+    ConstraintSystem,
+    GateInternal,
+    Gate,
+    Width4MainGateWithDNext,
+};
+
+use crate::alt_babyjubjub::{AltJubjubBn256, fs::Fs, edwards};
+use crate::jubjub::{FixedGenerators, Unknown};
+use crate::plonk::circuit::allocated_num::Num;
+use crate::plonk::circuit::boolean::Boolean;
+use crate::plonk::circuit::edwards::CircuitTwistedEdwardsPoint;
+use crate::plonk::circuit::edwards::bn256::CircuitAltBabyJubjubBn256;
+use crate::plonk::circuit::fee::{enforce_fee_authorized, FEE_BITS};
+use crate::plonk::circuit::linear_combination::LinearCombination;
+use crate::plonk::circuit::redjubjub::{verify_signature, CircuitRedJubjubSignature};
+use crate::plonk::circuit::public_data::{self, PublicData, PublicDataHash};
+use crate::redjubjub::{PublicKey, Signature};
+
+use super::{alloc_point, alloc_scalar, alloc_u64, compute_merkle_root, enforce_if, merkle_root, pack_bits, TreeHash};
+
+// Rollup transfer between two accounts of a state tree. An account is the leaf
+//
+//      H(pub_key.x, pub_key.y, balance + 2^64 . nonce)
+//
+// at its index, the hash of the tree is a parameter. Balance and nonce share an input so that a
+// Pedersen leaf stays within the generators of alt Baby Jubjub. The sender signs the transfer
+// with RedJubjub under P_G = SpendingKeyGenerator, the signed message is `Transfer::to_bytes`.
+// The nonce of the transfer is the one of the sender, which is incremented. Balances are 64 bit.
+// The sender authorizes a maximal fee and is debited the amount and the fee the operator
+// charges, see `circuit::fee`. Here the fee leaves the tree, `batch` credits it to the operator.
+//
+// The only public input is the SHA256 public data commitment of `circuit::public_data` to
+//
+//      old_root || new_root || from || to || amount || fee
+//
+// in big endian bytes, the roots take 32 bytes and the indices 4.
+
+pub const BALANCE_BITS: usize = 64;
+pub const NONCE_BITS: usize = 32;
+pub const ACCOUNT_INDEX_BITS: usize = 32;
+
+pub(super) fn u64_to_fr(value: u64) -> Fr {
+    Fr::from_repr(<Fr as PrimeField>::Repr::from(value)).unwrap()
+}
+
+// 2^64, the coefficient of the nonce in the leaf
+fn nonce_shift() -> Fr {
+    let mut shift = Fr::one();
+    for _ in 0..BALANCE_BITS {
+        shift.double();
+    }
+
+    shift
+}
+
+#[derive(Clone)]
+pub struct Account {
+    pub pub_key: PublicKey<Bn256>,
+    pub balance: u64,
+    pub nonce: u32,
+}
+
+impl Account {
+    pub fn leaf<H: TreeHash<Bn256>>(&self, hasher: &H) -> Fr {
+        let (x, y) = self.pub_key.0.into_xy();
+
+        let mut state = u64_to_fr(self.nonce as u64);
+        state.mul_assign(&nonce_shift());
+        state.add_assign(&u64_to_fr(self.balance));
+
+        hasher.hash(&[x, y, state])
+    }
+
+    /// Nothing under the identity key, for the witnesses of inactive operations.
+    pub fn empty() -> Self {
+        Account { pub_key: PublicKey(edwards::Point::zero()), balance: 0, nonce: 0 }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Transfer {
+    pub from: u32,
+    pub to: u32,
+    pub amount: u64,
+    pub max_fee: u64,
+    pub nonce: u32,
+}
+
+impl Transfer {
+    /// The signed message, from || to || amount || max_fee || nonce in little endian bytes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&self.from.to_le_bytes());
+        bytes.extend_from_slice(&self.to.to_le_bytes());
+        bytes.extend_from_slice(&self.amount.to_le_bytes());
+        bytes.extend_from_slice(&self.max_fee.to_le_bytes());
+        bytes.extend_from_slice(&self.nonce.to_le_bytes());
+
+        bytes
+    }
+
+    /// from || to || amount || fee in big endian bytes.
+    pub fn public_data(&self, fee: u64) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&self.from.to_be_bytes());
+        bytes.extend_from_slice(&self.to.to_be_bytes());
+        bytes.extend_from_slice(&self.amount.to_be_bytes());
+        bytes.extend_from_slice(&fee.to_be_bytes());
+
+        bytes
+    }
+}
+
+/// The public data commitment of a transfer charged `fee` from the tree with `old_root` to
+/// `new_root`.
+pub fn public_data_commitment(old_root: &Fr, new_root: &Fr, transfer: &Transfer, fee: u64) -> Fr {
+    let mut bytes = vec![];
+    for root in [old_root, new_root].iter() {
+        root.into_repr().write_be(&mut bytes).unwrap();
+    }
+    bytes.extend(transfer.public_data(fee));
+
+    public_data::public_data_commitment(&bytes, PublicDataHash::Sha256)
+}
+
+/// The whole state kept in memory, for tests and small trees. Empty leaves are zero.
+pub struct AccountTree<H: TreeHash<Bn256>> {
+    pub hasher: H,
+    pub depth: usize,
+    leaves: Vec<Fr>,
+}
+
+impl<H: TreeHash<Bn256>> AccountTree<H> {
+    pub fn new(hasher: H, depth: usize) -> Self {
+        assert!(depth <= ACCOUNT_INDEX_BITS && depth < 20);
+
+        AccountTree { hasher, depth, leaves: vec![Fr::zero(); 1 << depth] }
+    }
+
+    pub fn insert(&mut self, index: u32, account: &Account) {
+        self.leaves[index as usize] = account.leaf(&self.hasher);
+    }
+
+    /// Siblings from the leaf level up.
+    pub fn path(&self, index: u32) -> Vec<Fr> {
+        let mut path = Vec::with_capacity(self.depth);
+        let mut level = self.leaves.clone();
+        let mut index = index as usize;
+        for _ in 0..self.depth {
+            path.push(level[index ^ 1]);
+            level = level.chunks(2).map(|pair| self.hasher.hash(pair)).collect();
+            index >>= 1;
+        }
+
+        path
+    }
+
+    pub fn root(&self) -> Fr {
+        let leaf = self.leaves[0];
+
+        merkle_root(&self.hasher, leaf, 0, &self.path(0))
+    }
+}
+
+/// Everything the operator knows about a transfer. `to_path` is the path of the recipient
+/// after the update of the sender.
+#[derive(Clone)]
+pub struct TransferWitness {
+    pub transfer: Transfer,
+    /// The fee charged, up to `transfer.max_fee`.
+    pub fee: u64,
+    pub signature: Signature,
+    pub from: Account,
+    pub from_path: Vec<Fr>,
+    pub to: Account,
+    pub to_path: Vec<Fr>,
+}
+
+impl TransferWitness {
+    /// Applies the transfer to `tree` and records the witness. The transfer is not checked.
+    pub fn apply<H: TreeHash<Bn256>>(
+        tree: &mut AccountTree<H>,
+        transfer: Transfer,
+        fee: u64,
+        signature: Signature,
+        from: &Account,
+        to: &Account
+    ) -> Self {
+        let from_path = tree.path(transfer.from);
+        let mut new_from = from.clone();
+        new_from.balance -= transfer.amount + fee;
+        new_from.nonce += 1;
+        tree.insert(transfer.from, &new_from);
+
+        let to_path = tree.path(transfer.to);
+        let mut new_to = to.clone();
+        new_to.balance += transfer.amount;
+        tree.insert(transfer.to, &new_to);
+
+        TransferWitness {
+            transfer,
+            fee,
+            signature,
+            from: from.clone(),
+            from_path,
+            to: to.clone(),
+            to_path,
+        }
+    }
+
+    /// A transfer of nothing between empty accounts 0 and 1.
+    pub fn dummy(tree_depth: usize) -> Self {
+        assert!(tree_depth > 0);
+
+        TransferWitness {
+            transfer: Transfer { from: 0, to: 1, amount: 0, max_fee: 0, nonce: 0 },
+            fee: 0,
+            signature: dummy_signature(),
+            from: Account::empty(),
+            from_path: vec![Fr::zero(); tree_depth],
+            to: Account::empty(),
+            to_path: vec![Fr::zero(); tree_depth],
+        }
+    }
+}
+
+// R = 0, S = 0, the signature of inactive operations
+pub(super) fn dummy_signature() -> Signature {
+    let mut bytes = [0u8; 64];
+    bytes[0] = 1;
+
+    Signature::read(&bytes[..]).unwrap()
+}
+
+fn split_signature(signature: &Signature, params: &AltJubjubBn256) -> Option<(edwards::Point<Bn256, Unknown>, Fs)> {
+    let mut bytes = vec![];
+    signature.write(&mut bytes).unwrap();
+    let r = edwards::Point::<Bn256, Unknown>::read(&bytes[..32], params).ok()?;
+    let mut s = <Fs as PrimeField>::Repr::default();
+    s.read_le(&bytes[32..]).ok()?;
+
+    Some((r, Fs::from_repr(s).ok()?))
+}
+
+pub struct TransferCircuit<H: TreeHash<Bn256>> {
+    pub hasher: H,
+    pub jubjub_params: AltJubjubBn256,
+    pub tree_depth: usize,
+    pub witness: Option<TransferWitness>,
+}
+
+impl<H: TreeHash<Bn256>> TransferCircuit<H> {
+    pub fn new(hasher: H, tree_depth: usize, witness: Option<TransferWitness>) -> Self {
+        assert!(tree_depth <= ACCOUNT_INDEX_BITS);
+        if let Some(ref witness) = witness {
+            assert_eq!(witness.from_path.len(), tree_depth);
+            assert_eq!(witness.to_path.len(), tree_depth);
+        }
+
+        TransferCircuit { hasher, jubjub_params: AltJubjubBn256::new(), tree_depth, witness }
+    }
+}
+
+pub(super) struct CircuitAccount {
+    pub pub_key: CircuitTwistedEdwardsPoint<Bn256>,
+    pub balance: Num<Bn256>,
+    pub nonce: Num<Bn256>,
+}
+
+impl CircuitAccount {
+    /// The balance and the nonce are range checked, so the leaf has one split into them.
+    pub fn alloc<CS: ConstraintSystem<Bn256>>(cs: &mut CS, account: Option<&Account>) -> Result<Self, SynthesisError> {
+        Self::alloc_state(
+            cs,
+            account.map(|a| &a.pub_key.0),
+            account.map(|a| u64_to_fr(a.balance)),
+            account.map(|a| a.nonce as u64)
+        )
+    }
+
+    fn alloc_state<CS: ConstraintSystem<Bn256>>(
+        cs: &mut CS,
+        pub_key: Option<&edwards::Point<Bn256, Unknown>>,
+        balance: Option<Fr>,
+        nonce: Option<u64>
+    ) -> Result<Self, SynthesisError> {
+        let curve = CircuitAltBabyJubjubBn256::get_implementor();
+        let balance = Num::alloc(cs, balance)?;
+        balance.enforce_bit_length(cs, BALANCE_BITS)?;
+        let nonce_bits = alloc_u64(cs, nonce, NONCE_BITS)?;
+
+        Ok(CircuitAccount {
+            pub_key: alloc_point(cs, &curve, pub_key)?,
+            balance,
+            nonce: pack_bits(cs, &nonce_bits)?,
+        })
+    }
+
+    pub fn leaf<CS: ConstraintSystem<Bn256>, H: TreeHash<Bn256>>(&self, cs: &mut CS, hasher: &H) -> Result<Num<Bn256>, SynthesisError> {
+        let mut state = LinearCombination::zero();
+        state.add_assign_number_with_coeff(&self.balance, Fr::one());
+        state.add_assign_number_with_coeff(&self.nonce, nonce_shift());
+        let state = state.into_num(cs)?;
+
+        hasher.hash_in_circuit(cs, &[self.pub_key.x, self.pub_key.y, state])
+    }
+}
+
+pub(super) fn alloc_path<CS: ConstraintSystem<Bn256>>(cs: &mut CS, path: Option<&[Fr]>, tree_depth: usize) -> Result<Vec<Num<Bn256>>, SynthesisError> {
+    (0..tree_depth).map(|i| Num::alloc(cs, path.map(|p| p[i]))).collect()
+}
+
+/// The signature of `message` by `account`, enforced if `is_active`.
+pub(super) fn enforce_signature<CS: ConstraintSystem<Bn256>>(
+    cs: &mut CS,
+    account: &CircuitAccount,
+    signature: Option<&Signature>,
+    message: &[Boolean],
+    is_active: &Boolean,
+    params: &AltJubjubBn256
+) -> Result<(), SynthesisError> {
+    let curve = CircuitAltBabyJubjubBn256::get_implementor();
+    let signature = signature.map(|s| split_signature(s, params).ok_or(SynthesisError::Unsatisfiable)).transpose()?;
+    let signature = CircuitRedJubjubSignature {
+        r: alloc_point(cs, &curve, signature.as_ref().map(|s| &s.0))?,
+        s: alloc_scalar(cs, signature.as_ref().map(|s| &s.1))?,
+    };
+    let is_valid = verify_signature(cs, &curve, &account.pub_key, &signature, message, FixedGenerators::SpendingKeyGenerator, params)?;
+
+    enforce_if(cs, is_active, &is_valid)
+}
+
+/// The transfer in the circuit, its public data and the roots around it.
+pub(super) struct CircuitTransfer {
+    pub old_root: Num<Bn256>,
+    pub new_root: Num<Bn256>,
+    pub from: Vec<Boolean>,
+    pub to: Vec<Boolean>,
+    pub amount: Vec<Boolean>,
+    pub fee_bits: Vec<Boolean>,
+    pub fee: Num<Bn256>,
+}
+
+impl CircuitTransfer {
+    /// As in `Transfer::public_data`.
+    pub fn push_public_data(&self, public_data: &mut PublicData) {
+        public_data.push_bits_le(&self.from, ACCOUNT_INDEX_BITS / 8);
+        public_data.push_bits_le(&self.to, ACCOUNT_INDEX_BITS / 8);
+        public_data.push_bits_le(&self.amount, BALANCE_BITS / 8);
+        public_data.push_bits_le(&self.fee_bits, FEE_BITS / 8);
+    }
+}
+
+/// Applies the transfer of `witness`, the checks are enforced if `is_active`. The range checks
+/// are not, an inactive transfer takes `TransferWitness::dummy`.
+pub(super) fn apply_transfer<CS: ConstraintSystem<Bn256>, H: TreeHash<Bn256>>(
+    cs: &mut CS,
+    hasher: &H,
+    tree_depth: usize,
+    witness: Option<&TransferWitness>,
+    is_active: &Boolean,
+    params: &AltJubjubBn256
+) -> Result<CircuitTransfer, SynthesisError> {
+    let transfer = witness.map(|w| w.transfer);
+
+    let from_index = alloc_u64(cs, transfer.map(|t| t.from as u64), tree_depth)?;
+    let to_index = alloc_u64(cs, transfer.map(|t| t.to as u64), tree_depth)?;
+    let amount_bits = alloc_u64(cs, transfer.map(|t| t.amount), BALANCE_BITS)?;
+    let amount = pack_bits(cs, &amount_bits)?;
+    let max_fee_bits = alloc_u64(cs, transfer.map(|t| t.max_fee), FEE_BITS)?;
+    let fee_bits = alloc_u64(cs, witness.map(|w| w.fee), FEE_BITS)?;
+    let fee = pack_bits(cs, &fee_bits)?;
+    enforce_fee_authorized(cs, &fee, &pack_bits(cs, &max_fee_bits)?)?;
+    let nonce_bits = alloc_u64(cs, transfer.map(|t| t.nonce as u64), NONCE_BITS)?;
+
+    let from_is_to = Num::equals(cs, &pack_bits(cs, &from_index)?, &pack_bits(cs, &to_index)?)?;
+    enforce_if(cs, is_active, &from_is_to.not())?;
+
+    // the sender signed the transfer and is in the tree with the old root
+    let from = CircuitAccount::alloc(cs, witness.map(|w| &w.from))?;
+    let nonce = pack_bits(cs, &nonce_bits)?;
+    let nonce_matches = Num::equals(cs, &from.nonce, &nonce)?;
+    enforce_if(cs, is_active, &nonce_matches)?;
+    let from_path = alloc_path(cs, witness.map(|w| &w.from_path[..]), tree_depth)?;
+    let old_root = compute_merkle_root(cs, hasher, &from.leaf(cs, hasher)?, &from_index, &from_path)?;
+
+    let mut message = from_index.clone();
+    message.resize(ACCOUNT_INDEX_BITS, Boolean::constant(false));
+    message.extend(to_index.iter().cloned());
+    message.resize(2 * ACCOUNT_INDEX_BITS, Boolean::constant(false));
+    message.extend(amount_bits.iter().cloned());
+    message.extend(max_fee_bits);
+    message.extend(nonce_bits.iter().cloned());
+    enforce_signature(cs, &from, witness.map(|w| &w.signature), &message, is_active, params)?;
+
+    // debit the sender
+    let new_balance = from.balance.sub(cs, &amount)?.sub(cs, &fee)?;
+    new_balance.enforce_bit_length(cs, BALANCE_BITS)?;
+    let new_from = CircuitAccount {
+        pub_key: from.pub_key,
+        balance: new_balance,
+        nonce: from.nonce.add(cs, &Num::Constant(Fr::one()))?,
+    };
+    let intermediate_root = compute_merkle_root(cs, hasher, &new_from.leaf(cs, hasher)?, &from_index, &from_path)?;
+
+    // credit the recipient
+    let to = CircuitAccount::alloc(cs, witness.map(|w| &w.to))?;
+    let to_path = alloc_path(cs, witness.map(|w| &w.to_path[..]), tree_depth)?;
+    let root = compute_merkle_root(cs, hasher, &to.leaf(cs, hasher)?, &to_index, &to_path)?;
+    let root_matches = Num::equals(cs, &root, &intermediate_root)?;
+    enforce_if(cs, is_active, &root_matches)?;
+
+    let new_balance = to.balance.add(cs, &amount)?;
+    new_balance.enforce_bit_length(cs, BALANCE_BITS)?;
+    let new_to = CircuitAccount { balance: new_balance, ..to };
+    let new_root = compute_merkle_root(cs, hasher, &new_to.leaf(cs, hasher)?, &to_index, &to_path)?;
+
+    Ok(CircuitTransfer { old_root, new_root, from: from_index, to: to_index, amount: amount_bits, fee_bits, fee })
+}
+
+impl<H: TreeHash<Bn256>> Circuit<Bn256> for TransferCircuit<H> {
+    type MainGate = Width4MainGateWithDNext;
+
+    fn declare_used_gates() -> Result<Vec<Box<dyn GateInternal<Bn256>>>, SynthesisError> {
+        Ok(vec![Width4MainGateWithDNext::default().into_internal()])
+    }
+
+    fn synthesize<CS: ConstraintSystem<Bn256>>(&self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let transfer = apply_transfer(
+            cs,
+            &self.hasher,
+            self.tree_depth,
+            self.witness.as_ref(),
+            &Boolean::constant(true),
+            &self.jubjub_params
+        )?;
+
+        let mut public_data = PublicData::new();
+        public_data.push_field_element(cs, &transfer.old_root)?;
+        public_data.push_field_element(cs, &transfer.new_root)?;
+        transfer.push_public_data(&mut public_data);
+        public_data.inputize(cs, PublicDataHash::Sha256)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use crate::bellman::kate_commitment::{Crs, CrsForMonomialForm};
+    use crate::bellman::plonk::better_better_cs::cs::{PlonkCsWidth4WithNextStepParams, TrivialAssembly};
+    use crate::bellman::worker::Worker;
+    use crate::pedersen_hash::Personalization;
+    use crate::plonk::examples::{PedersenTreeHash, PoseidonTreeHash};
+    use crate::plonk::prover::*;
+    use crate::poseidon::Bn256PoseidonParams;
+    use crate::redjubjub::PrivateKey;
+
+    type Params = PlonkCsWidth4WithNextStepParams;
+
+    const TREE_DEPTH: usize = 2;
+
+    fn transfer<H: TreeHash<Bn256>>(hasher: H) {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = AltJubjubBn256::new();
+        let p_g = FixedGenerators::SpendingKeyGenerator;
+
+        let sk = PrivateKey::<Bn256>(rng.gen());
+        let from = Account { pub_key: PublicKey::from_private(&sk, p_g, &params), balance: 100, nonce: 3 };
+        let to_sk = PrivateKey::<Bn256>(rng.gen());
+        let to = Account { pub_key: PublicKey::from_private(&to_sk, p_g, &params), balance: 5, nonce: 0 };
+
+        let mut tree = AccountTree::new(hasher, TREE_DEPTH);
+        tree.insert(1, &from);
+        tree.insert(2, &to);
+        let old_root = tree.root();
+
+        let t = Transfer { from: 1, to: 2, amount: 60, max_fee: 5, nonce: 3 };
+        let signature = sk.sign(&t.to_bytes(), &mut rng, p_g, &params);
+        let witness = TransferWitness::apply(&mut tree, t, 3, signature, &from, &to);
+        let new_root = tree.root();
+
+        let circuit = TransferCircuit::new(tree.hasher, TREE_DEPTH, Some(witness.clone()));
+        let crs = Crs::<Bn256, CrsForMonomialForm>::crs_42(required_crs_size::<_, Params, _>(&circuit).unwrap(), &Worker::new());
+        let keys = generate_keys::<_, Params, _>(&circuit, &crs).unwrap();
+        let proof = create_proof::<_, Params, _>(&circuit, &keys, &crs).unwrap();
+        assert_eq!(proof.inputs, vec![public_data_commitment(&old_root, &new_root, &t, 3)]);
+        assert!(verify_proof(&keys.vk, &proof).unwrap());
+
+        // overdraft with the fee, a fee above the authorized one, the amount is not the signed
+        // one and a replay with the next nonce
+        let overdraft = Transfer { amount: 98, ..t };
+        let mut invalid = vec![];
+        let mut w = witness.clone();
+        w.transfer = overdraft;
+        w.signature = sk.sign(&overdraft.to_bytes(), &mut rng, p_g, &params);
+        invalid.push(w);
+        let mut w = witness.clone();
+        w.fee = 6;
+        invalid.push(w);
+        let mut w = witness.clone();
+        w.transfer.amount = 61;
+        invalid.push(w);
+        let mut w = witness.clone();
+        w.transfer.nonce = 4;
+        invalid.push(w);
+
+        let TransferCircuit { hasher, .. } = circuit;
+        let mut circuit = TransferCircuit::new(hasher, TREE_DEPTH, None);
+        for w in invalid.into_iter() {
+            circuit.witness = Some(w);
+            assert!(create_proof::<_, Params, _>(&circuit, &keys, &crs).is_err());
+        }
+    }
+
+    #[test]
+    fn test_balance_overflow_into_nonce() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = AltJubjubBn256::new();
+        let hasher = PoseidonTreeHash::<Bn256> { params: Bn256PoseidonParams::new_checked_2_into_1() };
+
+        let sk = PrivateKey::<Bn256>(rng.gen());
+        let account = Account { pub_key: PublicKey::from_private(&sk, FixedGenerators::SpendingKeyGenerator, &params), balance: 100, nonce: 3 };
+
+        // balance + 2^64 with the previous nonce has the same leaf, spending 2^64 - 1 of it
+        // would leave a balance of 101
+        let mut balance = u64_to_fr(account.balance);
+        balance.add_assign(&nonce_shift());
+        let amount = u64_to_fr(u64::max_value());
+
+        let mut cs = TrivialAssembly::<Bn256, Params, Width4MainGateWithDNext>::new();
+        let from = CircuitAccount::alloc_state(&mut cs, Some(&account.pub_key.0), Some(balance), Some(account.nonce as u64 - 1)).unwrap();
+        assert_eq!(from.leaf(&mut cs, &hasher).unwrap().get_value().unwrap(), account.leaf(&hasher));
+        let amount = Num::alloc(&mut cs, Some(amount)).unwrap();
+        let new_balance = from.balance.sub(&mut cs, &amount).unwrap();
+        new_balance.enforce_bit_length(&mut cs, BALANCE_BITS).unwrap();
+        assert_eq!(new_balance.get_value().unwrap(), u64_to_fr(account.balance + 1));
+
+        assert!(!cs.is_satisfied());
+    }
+
+    #[test]
+    fn test_transfer_with_poseidon() {
+        transfer(PoseidonTreeHash::<Bn256> { params: Bn256PoseidonParams::new_checked_2_into_1() });
+    }
+
+    #[test]
+    fn test_transfer_with_pedersen() {
+        transfer(PedersenTreeHash {
+            params: AltJubjubBn256::new(),
+            curve: CircuitAltBabyJubjubBn256::get_implementor(),
+            personalization: Personalization::MerkleTree(0),
+        });
+    }
+}
@@ -0,0 +1,171 @@
+use crate::bellman::pairing::Engine;
+
+use crate::bellman::SynthesisError;
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    Circuit,
+    ConstraintSystem,
+    GateInternal,
+    Gate,
+    Width4MainGateWithDNext,
+};
+
+use crate::plonk::circuit::allocated_num::Num;
+use crate::plonk::circuit::boolean::Boolean;
+
+use super::{compute_merkle_root, merkle_root, TreeHash};
+
+use std::marker::PhantomData;
+
+// Mixer style pool. A deposit appends the commitment H(secret, nullifier) to a binary Merkle
+// tree, node = H(left, right), and a withdrawal proves knowledge of the opening of a leaf
+// without telling which one. The public inputs of the withdrawal, in order:
+//
+//      root            root of the tree the leaf is in
+//      nullifier_hash  H(nullifier), the contract rejects it the second time
+//      recipient       bound to the proof so a relayer can't redirect the funds
+//
+// The hash and the depth of the tree are parameters, the tree itself is maintained outside.
+// Proofs are made with `plonk::prover` under `PlonkCsWidth4WithNextStepParams`.
+
+/// The note a depositor keeps to withdraw later.
+#[derive(Clone)]
+pub struct Deposit<E: Engine> {
+    pub secret: E::Fr,
+    pub nullifier: E::Fr,
+}
+
+impl<E: Engine> Deposit<E> {
+    /// The leaf to append to the tree.
+    pub fn commitment<H: TreeHash<E>>(&self, hasher: &H) -> E::Fr {
+        hasher.hash(&[self.secret, self.nullifier])
+    }
+
+    pub fn nullifier_hash<H: TreeHash<E>>(&self, hasher: &H) -> E::Fr {
+        hasher.hash(&[self.nullifier])
+    }
+}
+
+#[derive(Clone)]
+pub struct WithdrawWitness<E: Engine> {
+    pub deposit: Deposit<E>,
+    pub index: u64,
+    pub path: Vec<E::Fr>,
+    pub recipient: E::Fr,
+}
+
+pub struct WithdrawCircuit<E: Engine, H: TreeHash<E>> {
+    pub hasher: H,
+    pub tree_depth: usize,
+    pub witness: Option<WithdrawWitness<E>>,
+    _marker: PhantomData<E>,
+}
+
+impl<E: Engine, H: TreeHash<E>> WithdrawCircuit<E, H> {
+    pub fn new(hasher: H, tree_depth: usize, witness: Option<WithdrawWitness<E>>) -> Self {
+        assert!(tree_depth < 64);
+        if let Some(ref witness) = witness {
+            assert_eq!(witness.path.len(), tree_depth);
+        }
+
+        WithdrawCircuit { hasher, tree_depth, witness, _marker: PhantomData }
+    }
+
+    /// The public inputs of the withdrawal of `witness`.
+    pub fn public_inputs(&self, witness: &WithdrawWitness<E>) -> Vec<E::Fr> {
+        let leaf = witness.deposit.commitment(&self.hasher);
+
+        vec![
+            merkle_root(&self.hasher, leaf, witness.index, &witness.path),
+            witness.deposit.nullifier_hash(&self.hasher),
+            witness.recipient,
+        ]
+    }
+}
+
+impl<E: Engine, H: TreeHash<E>> Circuit<E> for WithdrawCircuit<E, H> {
+    type MainGate = Width4MainGateWithDNext;
+
+    fn declare_used_gates() -> Result<Vec<Box<dyn GateInternal<E>>>, SynthesisError> {
+        Ok(vec![Width4MainGateWithDNext::default().into_internal()])
+    }
+
+    fn synthesize<CS: ConstraintSystem<E>>(&self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let witness = self.witness.as_ref();
+
+        let secret = Num::alloc(cs, witness.map(|w| w.deposit.secret))?;
+        let nullifier = Num::alloc(cs, witness.map(|w| w.deposit.nullifier))?;
+        let leaf = self.hasher.hash_in_circuit(cs, &[secret, nullifier])?;
+        let nullifier_hash = self.hasher.hash_in_circuit(cs, &[nullifier])?;
+
+        let mut index = Vec::with_capacity(self.tree_depth);
+        let mut path = Vec::with_capacity(self.tree_depth);
+        for i in 0..self.tree_depth {
+            index.push(Boolean::alloc(cs, witness.map(|w| (w.index >> i) & 1 == 1))?);
+            path.push(Num::alloc(cs, witness.map(|w| w.path[i]))?);
+        }
+        let root = compute_merkle_root(cs, &self.hasher, &leaf, &index, &path)?;
+
+        let recipient = Num::alloc(cs, witness.map(|w| w.recipient))?;
+        for input in [root, nullifier_hash, recipient].iter() {
+            match input {
+                Num::Variable(ref var) => var.inputize(cs)?,
+                Num::Constant(_) => unreachable!("the inputs are computed from the witness"),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use crate::bellman::pairing::bn256::{Bn256, Fr};
+    use crate::bellman::kate_commitment::{Crs, CrsForMonomialForm};
+    use crate::bellman::plonk::better_better_cs::cs::PlonkCsWidth4WithNextStepParams;
+    use crate::bellman::worker::Worker;
+    use crate::plonk::examples::{PoseidonTreeHash, RescueTreeHash};
+    use crate::plonk::prover::*;
+    use crate::poseidon::Bn256PoseidonParams;
+    use crate::rescue::bn256::Bn256RescueParams;
+
+    type Params = PlonkCsWidth4WithNextStepParams;
+
+    const TREE_DEPTH: usize = 3;
+
+    fn withdraw<H: TreeHash<Bn256>>(hasher: H) {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        let witness = WithdrawWitness::<Bn256> {
+            deposit: Deposit { secret: rng.gen(), nullifier: rng.gen() },
+            index: 5,
+            path: (0..TREE_DEPTH).map(|_| rng.gen()).collect(),
+            recipient: rng.gen(),
+        };
+        let circuit = WithdrawCircuit::new(hasher, TREE_DEPTH, Some(witness.clone()));
+        let expected_inputs = circuit.public_inputs(&witness);
+
+        let crs = Crs::<Bn256, CrsForMonomialForm>::crs_42(required_crs_size::<_, Params, _>(&circuit).unwrap(), &Worker::new());
+        let keys = generate_keys::<_, Params, _>(&circuit, &crs).unwrap();
+        let proof = create_proof::<_, Params, _>(&circuit, &keys, &crs).unwrap();
+        assert_eq!(proof.inputs, expected_inputs);
+        assert!(verify_proof(&keys.vk, &proof).unwrap());
+
+        // the proof doesn't transfer to another recipient
+        let mut forged = proof;
+        forged.inputs[2] = rng.gen::<Fr>();
+        assert!(!verify_proof(&keys.vk, &forged).unwrap());
+    }
+
+    #[test]
+    fn test_withdraw_with_poseidon() {
+        withdraw(PoseidonTreeHash::<Bn256> { params: Bn256PoseidonParams::new_checked_2_into_1() });
+    }
+
+    #[test]
+    fn test_withdraw_with_rescue() {
+        withdraw(RescueTreeHash::<Bn256> { params: Bn256RescueParams::new_checked_2_into_1() });
+    }
+}
@@ -0,0 +1,214 @@
+use crate::bellman::pairing::Engine;
+use crate::bellman::pairing::ff::{Field, PrimeField};
+
+use crate::bellman::SynthesisError;
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    Circuit,
+    ConstraintSystem,
+    GateInternal,
+    Gate,
+    Width4MainGateWithDNext,
+};
+
+use crate::plonk::circuit::allocated_num::Num;
+
+use super::TreeHash;
+
+use std::marker::PhantomData;
+
+// Proof of solvency of a custodian. The liabilities are the leaves H(user, balance) of a full
+// binary tree, node = H(left, right), users are field elements such as hashes of account ids
+// and empty leaves are H(0, 0). The circuit opens every leaf, checks every balance to be 64 bit,
+// so that no negative balance cancels others, and compares the sum to the claimed total. The
+// public inputs, in order:
+//
+//      root    root of the tree, users check their leaf against it outside of the circuit
+//      total   the claimed total of the liabilities
+//
+// Whether the sum equals the total or is at most the total is a parameter of the circuit.
+
+pub const BALANCE_BITS: usize = 64;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SolvencyClaim {
+    /// The balances add up to the total.
+    Equal,
+    /// The balances add up to at most the total, as for assets that cover the liabilities.
+    AtMost,
+}
+
+#[derive(Clone)]
+pub struct Liability<E: Engine> {
+    pub user: E::Fr,
+    pub balance: u64,
+}
+
+impl<E: Engine> Liability<E> {
+    pub fn empty() -> Self {
+        Liability { user: E::Fr::zero(), balance: 0 }
+    }
+
+    pub fn leaf<H: TreeHash<E>>(&self, hasher: &H) -> E::Fr {
+        hasher.hash(&[self.user, u64_to_fr::<E>(self.balance)])
+    }
+}
+
+fn u64_to_fr<E: Engine>(value: u64) -> E::Fr {
+    E::Fr::from_repr(<E::Fr as PrimeField>::Repr::from(value)).unwrap()
+}
+
+fn u128_to_fr<E: Engine>(value: u128) -> E::Fr {
+    let mut result = u64_to_fr::<E>((value >> 64) as u64);
+    for _ in 0..64 {
+        result.double();
+    }
+    result.add_assign(&u64_to_fr::<E>(value as u64));
+
+    result
+}
+
+/// Root of the tree of `liabilities`, padded with empty leaves to 2^tree_depth.
+pub fn liabilities_root<E: Engine, H: TreeHash<E>>(hasher: &H, tree_depth: usize, liabilities: &[Liability<E>]) -> E::Fr {
+    assert!(liabilities.len() <= 1 << tree_depth);
+
+    let mut level: Vec<E::Fr> = liabilities.iter().map(|l| l.leaf(hasher)).collect();
+    level.resize(1 << tree_depth, Liability::<E>::empty().leaf(hasher));
+    for _ in 0..tree_depth {
+        level = level.chunks(2).map(|pair| hasher.hash(pair)).collect();
+    }
+
+    level[0]
+}
+
+pub struct SolvencyCircuit<E: Engine, H: TreeHash<E>> {
+    pub hasher: H,
+    pub tree_depth: usize,
+    pub claim: SolvencyClaim,
+    /// All the leaves, padded with empty ones.
+    pub liabilities: Option<Vec<Liability<E>>>,
+    pub total: Option<u128>,
+    _marker: PhantomData<E>,
+}
+
+impl<E: Engine, H: TreeHash<E>> SolvencyCircuit<E, H> {
+    pub fn new(
+        hasher: H,
+        tree_depth: usize,
+        claim: SolvencyClaim,
+        liabilities: Option<Vec<Liability<E>>>,
+        total: Option<u128>
+    ) -> Self {
+        // the sum takes BALANCE_BITS + tree_depth bits
+        assert!(BALANCE_BITS + tree_depth < E::Fr::CAPACITY as usize);
+        let liabilities = liabilities.map(|mut l| {
+            assert!(l.len() <= 1 << tree_depth);
+            l.resize(1 << tree_depth, Liability::empty());
+            l
+        });
+
+        SolvencyCircuit { hasher, tree_depth, claim, liabilities, total, _marker: PhantomData }
+    }
+
+    pub fn public_inputs(&self) -> Vec<E::Fr> {
+        let liabilities = self.liabilities.as_ref().expect("witness is required");
+        let total = self.total.expect("witness is required");
+
+        vec![liabilities_root(&self.hasher, self.tree_depth, liabilities), u128_to_fr::<E>(total)]
+    }
+}
+
+impl<E: Engine, H: TreeHash<E>> Circuit<E> for SolvencyCircuit<E, H> {
+    type MainGate = Width4MainGateWithDNext;
+
+    fn declare_used_gates() -> Result<Vec<Box<dyn GateInternal<E>>>, SynthesisError> {
+        Ok(vec![Width4MainGateWithDNext::default().into_internal()])
+    }
+
+    fn synthesize<CS: ConstraintSystem<E>>(&self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let liabilities = self.liabilities.as_ref();
+
+        let mut sum = Num::zero();
+        let mut level = Vec::with_capacity(1 << self.tree_depth);
+        for i in 0..(1 << self.tree_depth) {
+            let liability = liabilities.map(|l| &l[i]);
+            let user = Num::alloc(cs, liability.map(|l| l.user))?;
+            let balance = Num::alloc(cs, liability.map(|l| u64_to_fr::<E>(l.balance)))?;
+            balance.enforce_bit_length(cs, BALANCE_BITS)?;
+            sum = sum.add(cs, &balance)?;
+
+            level.push(self.hasher.hash_in_circuit(cs, &[user, balance])?);
+        }
+        for _ in 0..self.tree_depth {
+            let mut next = Vec::with_capacity(level.len() / 2);
+            for pair in level.chunks(2) {
+                next.push(self.hasher.hash_in_circuit(cs, pair)?);
+            }
+            level = next;
+        }
+        let root = level[0];
+
+        let total = Num::alloc(cs, self.total.map(u128_to_fr::<E>))?;
+        match self.claim {
+            SolvencyClaim::Equal => sum.enforce_equal(cs, &total)?,
+            SolvencyClaim::AtMost => total.sub(cs, &sum)?.enforce_bit_length(cs, BALANCE_BITS + self.tree_depth)?,
+        }
+
+        for input in [root, total].iter() {
+            match input {
+                Num::Variable(ref var) => var.inputize(cs)?,
+                Num::Constant(_) => unreachable!("the inputs are computed from the witness"),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use crate::bellman::pairing::bn256::Bn256;
+    use crate::bellman::kate_commitment::{Crs, CrsForMonomialForm};
+    use crate::bellman::plonk::better_better_cs::cs::PlonkCsWidth4WithNextStepParams;
+    use crate::bellman::worker::Worker;
+    use crate::plonk::examples::PoseidonTreeHash;
+    use crate::plonk::prover::*;
+    use crate::poseidon::Bn256PoseidonParams;
+
+    type Params = PlonkCsWidth4WithNextStepParams;
+
+    const TREE_DEPTH: usize = 2;
+
+    fn hasher() -> PoseidonTreeHash<Bn256> {
+        PoseidonTreeHash { params: Bn256PoseidonParams::new_checked_2_into_1() }
+    }
+
+    #[test]
+    fn test_solvency() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+
+        let liabilities: Vec<Liability<Bn256>> = (0..3).map(|_| Liability { user: rng.gen(), balance: rng.gen() }).collect();
+        let sum: u128 = liabilities.iter().map(|l| l.balance as u128).sum();
+
+        for &claim in [SolvencyClaim::Equal, SolvencyClaim::AtMost].iter() {
+            let setup = SolvencyCircuit::<Bn256, _>::new(hasher(), TREE_DEPTH, claim, None, None);
+            let crs = Crs::<Bn256, CrsForMonomialForm>::crs_42(required_crs_size::<_, Params, _>(&setup).unwrap(), &Worker::new());
+            let keys = generate_keys::<_, Params, _>(&setup, &crs).unwrap();
+
+            let circuit = SolvencyCircuit::new(hasher(), TREE_DEPTH, claim, Some(liabilities.clone()), Some(sum));
+            let proof = create_proof::<_, Params, _>(&circuit, &keys, &crs).unwrap();
+            assert_eq!(proof.inputs, circuit.public_inputs());
+            assert!(verify_proof(&keys.vk, &proof).unwrap());
+
+            // liabilities above the claim
+            let circuit = SolvencyCircuit::new(hasher(), TREE_DEPTH, claim, Some(liabilities.clone()), Some(sum - 1));
+            assert!(create_proof::<_, Params, _>(&circuit, &keys, &crs).is_err());
+
+            let circuit = SolvencyCircuit::new(hasher(), TREE_DEPTH, claim, Some(liabilities.clone()), Some(sum + 1));
+            let result = create_proof::<_, Params, _>(&circuit, &keys, &crs);
+            assert_eq!(result.is_ok(), claim == SolvencyClaim::AtMost);
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use crate::bellman::pairing::bn256::{Bn256, Fr};
+use crate::bellman::pairing::ff::PrimeField;
+
+use crate::bellman::SynthesisError;
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    Circuit,
+    ConstraintSystem,
+    GateInternal,
+    Gate,
+    Width4MainGateWithDNext,
+};
+
+use crate::alt_babyjubjub::{AltJubjubBn256, fs::Fs};
+use crate::constants::CRH_IVK_PERSONALIZATION;
+use crate::jubjub::{FixedGenerators, JubjubParams};
+use crate::merkle_tree::MerkleWitness;
+use crate::plonk::circuit::allocated_num::Num;
+use crate::plonk::circuit::blake2s::blake2s;
+use crate::plonk::circuit::boolean::Boolean;
+use crate::plonk::circuit::ecdh::point_into_bits;
+use crate::plonk::circuit::edwards::CircuitTwistedEdwardsPoint;
+use crate::plonk::circuit::edwards::bn256::CircuitAltBabyJubjubBn256;
+use crate::plonk::circuit::merkle_tree::compute_root;
+use crate::plonk::circuit::note_commitment::{note_commitment, NOTE_VALUE_BITS};
+use crate::plonk::circuit::nullifier::{nullifier, NOTE_POSITION_BITS};
+use crate::plonk::circuit::redjubjub::randomize_public_key;
+use crate::plonk::circuit::value_commitment::value_commitment;
+use crate::poseidon::Bn256PoseidonParams;
+use crate::primitives::{Note, ProofGenerationKey};
+
+use super::{alloc_point, alloc_scalar, alloc_u64, assert_not_small_order, inputize, inputize_point, pack_bits};
+
+// Spend of a note from a Poseidon Merkle tree of note commitments, Sapling style. The public
+// inputs, in order:
+//
+//      rk.x, rk.y     re-randomized spend authorization key, ak + ar . G_SpendingKeyGenerator
+//      cv.x, cv.y     value commitment of the note value with randomness rcv
+//      anchor         root of the tree
+//      nf             nullifier, 256 bits little endian packed into 2 field elements
+//
+// The witness proves that the note was sent to the address of the spending key, pk_d = ivk . g_d
+// with ivk = BLAKE2s(CRH_IVK_PERSONALIZATION, ak || nk) truncated to 251 bits, and that it is in
+// the tree. Points are checked to be on the curve, not to be in the prime order subgroup, ak and
+// g_d not to be of small order.
+
+/// The spender's knowledge.
+#[derive(Clone)]
+pub struct SpendWitness {
+    pub proof_generation_key: ProofGenerationKey<Bn256>,
+    pub note: Note<Bn256>,
+    /// Randomness of the value commitment.
+    pub rcv: Fs,
+    /// Re-randomization of the spend authorization key.
+    pub ar: Fs,
+    /// Authentication path of the note commitment, the index is the position of the note.
+    pub merkle_witness: MerkleWitness<Bn256>,
+}
+
+pub struct SpendCircuit {
+    pub jubjub_params: AltJubjubBn256,
+    pub poseidon_params: Bn256PoseidonParams,
+    pub tree_depth: usize,
+    pub witness: Option<SpendWitness>,
+}
+
+impl SpendCircuit {
+    pub fn new(tree_depth: usize, witness: Option<SpendWitness>) -> Self {
+        assert!(tree_depth <= NOTE_POSITION_BITS);
+        if let Some(ref witness) = witness {
+            assert_eq!(witness.merkle_witness.path.len(), tree_depth);
+        }
+
+        SpendCircuit {
+            jubjub_params: AltJubjubBn256::new(),
+            poseidon_params: Bn256PoseidonParams::new_checked_2_into_1(),
+            tree_depth,
+            witness,
+        }
+    }
+}
+
+impl Circuit<Bn256> for SpendCircuit {
+    type MainGate = Width4MainGateWithDNext;
+
+    fn declare_used_gates() -> Result<Vec<Box<dyn GateInternal<Bn256>>>, SynthesisError> {
+        Ok(vec![Width4MainGateWithDNext::default().into_internal()])
+    }
+
+    fn synthesize<CS: ConstraintSystem<Bn256>>(&self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let params = &self.jubjub_params;
+        let curve = CircuitAltBabyJubjubBn256::get_implementor();
+        let witness = self.witness.as_ref();
+
+        // spend authority: nk = nsk . G_ProofGenerationKey, rk = ak + ar . G_SpendingKeyGenerator
+        let ak = alloc_point(cs, &curve, witness.map(|w| &w.proof_generation_key.ak))?;
+        assert_not_small_order(cs, &curve, &ak)?;
+        let nsk = alloc_scalar(cs, witness.map(|w| &w.proof_generation_key.nsk))?;
+        let (x, y) = params.generator(FixedGenerators::ProofGenerationKey).into_xy();
+        let proof_generation_generator = CircuitTwistedEdwardsPoint::constant(x, y);
+        let nk = curve.mul(cs, &proof_generation_generator, &nsk)?;
+
+        let ar = alloc_scalar(cs, witness.map(|w| &w.ar))?;
+        let rk = randomize_public_key(cs, &curve, &ak, &ar, FixedGenerators::SpendingKeyGenerator, params)?;
+        inputize_point(cs, &rk)?;
+
+        // value commitment
+        let value = alloc_u64(cs, witness.map(|w| w.note.value), NOTE_VALUE_BITS)?;
+        let rcv = alloc_scalar(cs, witness.map(|w| &w.rcv))?;
+        let cv = value_commitment(cs, &curve, &value, &rcv, params)?;
+        inputize_point(cs, &cv)?;
+
+        // the note belongs to the key: pk_d = ivk . g_d
+        let mut ivk_preimage = point_into_bits(cs, &ak)?;
+        ivk_preimage.extend(point_into_bits(cs, &nk)?);
+        let mut ivk = blake2s(cs, &ivk_preimage, CRH_IVK_PERSONALIZATION)?;
+        // drop the most significant five bits as `ViewingKey::ivk`
+        ivk.truncate(251);
+
+        let g_d = alloc_point(cs, &curve, witness.map(|w| &w.note.g_d))?;
+        assert_not_small_order(cs, &curve, &g_d)?;
+        let pk_d = curve.mul(cs, &g_d, &ivk)?;
+
+        let r = alloc_scalar(cs, witness.map(|w| &w.note.r))?;
+        let cm = note_commitment(cs, &curve, &value, &g_d, &pk_d, &r, params)?;
+
+        // membership
+        let mut position = alloc_u64(cs, witness.map(|w| w.merkle_witness.index), self.tree_depth)?;
+        let mut path = Vec::with_capacity(self.tree_depth);
+        for i in 0..self.tree_depth {
+            path.push(Num::alloc(cs, witness.map(|w| w.merkle_witness.path[i]))?);
+        }
+        let anchor = compute_root(cs, &cm.cm, &path, &position, &self.poseidon_params)?;
+        inputize(cs, &anchor)?;
+
+        // nullifier
+        position.resize(NOTE_POSITION_BITS, Boolean::constant(false));
+        let nf = nullifier(cs, &curve, &nk, &cm.point, &position, params)?;
+        for chunk in nf.chunks(Fr::CAPACITY as usize) {
+            let packed = pack_bits(cs, chunk)?;
+            inputize(cs, &packed)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use crate::bellman::kate_commitment::{Crs, CrsForMonomialForm};
+    use crate::bellman::plonk::better_better_cs::cs::PlonkCsWidth4WithNextStepParams;
+    use crate::bellman::worker::Worker;
+    use crate::merkle_tree::IncrementalMerkleTree;
+    use crate::plonk::circuit::multieq::{bytes_to_bits_le, compute_multipacking};
+    use crate::plonk::prover::*;
+    use crate::primitives::ValueCommitment;
+    use crate::zip32::{ChildIndex, ExtendedSpendingKey};
+
+    type Params = PlonkCsWidth4WithNextStepParams;
+
+    const TREE_DEPTH: usize = 4;
+
+    #[test]
+    fn test_spend_circuit() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = AltJubjubBn256::new();
+        let poseidon_params = Bn256PoseidonParams::new_checked_2_into_1();
+
+        let xsk = ExtendedSpendingKey::<Bn256>::master(&[7u8; 32]).derive_child(ChildIndex::Hardened(0), &params);
+        let proof_generation_key = xsk.expsk.proof_generation_key(&params);
+        let viewing_key = proof_generation_key.into_viewing_key(&params);
+        let (_, address) = xsk.default_address(&params);
+        let note = address.create_note(rng.gen(), rng.gen(), &params).unwrap();
+
+        let mut tree = IncrementalMerkleTree::<Bn256>::new(TREE_DEPTH, &poseidon_params);
+        tree.append(rng.gen(), &poseidon_params);
+        let position = tree.append_and_mark(note.cm(&params), &poseidon_params).unwrap();
+        tree.append(rng.gen(), &poseidon_params);
+
+        let witness = SpendWitness {
+            proof_generation_key,
+            rcv: rng.gen(),
+            ar: rng.gen(),
+            merkle_witness: tree.witness(position).unwrap().clone(),
+            note,
+        };
+        let circuit = SpendCircuit::new(TREE_DEPTH, Some(witness.clone()));
+
+        let rk = viewing_key.rk(witness.ar, &params).into_xy();
+        let cv = ValueCommitment::<Bn256> { value: witness.note.value, randomness: witness.rcv }.cm(&params).into_xy();
+        let nf = witness.note.nf(&viewing_key, position, &params);
+        let mut expected_inputs = vec![rk.0, rk.1, cv.0, cv.1, tree.root()];
+        expected_inputs.extend(compute_multipacking::<Bn256>(&bytes_to_bits_le(&nf)));
+
+        let crs = Crs::<Bn256, CrsForMonomialForm>::crs_42(required_crs_size::<_, Params, _>(&circuit).unwrap(), &Worker::new());
+        let keys = generate_keys::<_, Params, _>(&circuit, &crs).unwrap();
+        let proof = create_proof::<_, Params, _>(&circuit, &keys, &crs).unwrap();
+        assert_eq!(proof.inputs, expected_inputs);
+        assert!(verify_proof(&keys.vk, &proof).unwrap());
+
+        let mut forged = proof;
+        forged.inputs[4] = rng.gen();
+        assert!(!verify_proof(&keys.vk, &forged).unwrap());
+
+        // a note of another key is not spendable
+        let mut witness = witness;
+        witness.proof_generation_key.nsk = rng.gen();
+        let circuit = SpendCircuit::new(TREE_DEPTH, Some(witness));
+        assert!(create_proof::<_, Params, _>(&circuit, &keys, &crs).is_err());
+    }
+}
@@ -0,0 +1,220 @@
+use crate::bellman::pairing::bn256::{Bn256, Fr};
+use crate::bellman::pairing::ff::{PrimeField, PrimeFieldRepr};
+
+use crate::bellman::SynthesisError;
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    Circuit,
+    ConstraintSystem,
+    GateInternal,
+    Gate,
+    Width4MainGateWithDNext,
+};
+
+use crate::alt_babyjubjub::{AltJubjubBn256, fs::Fs, edwards};
+use crate::jubjub::{FixedGenerators, JubjubParams, PrimeOrder};
+use crate::merkle_tree::MerkleWitness;
+use crate::plonk::circuit::allocated_num::Num;
+use crate::plonk::circuit::edwards::bn256::CircuitAltBabyJubjubBn256;
+use crate::plonk::circuit::key_membership::{key_leaf, prove_key_tree_membership};
+use crate::plonk::circuit::poseidon::poseidon_hash;
+use crate::plonk::circuit::set_membership::enforce_membership;
+use crate::plonk::circuit::utils::u64_to_fe;
+use crate::poseidon::Bn256PoseidonParams;
+
+use super::{alloc_scalar, alloc_u64, inputize, pack_bits};
+
+// Anonymous vote of a registered voter. The census is a Poseidon Merkle tree (see
+// `crate::merkle_tree`) of the voter keys pk = sk . G_SpendingKeyGenerator, with leaves as in
+// `key_membership`. The public inputs, in order:
+//
+//      root         root of the census
+//      election     identifier of the election
+//      nullifier    Poseidon(sk, election), the same for every vote of a voter in an election
+//      vote         the choice, one of the choices of the circuit
+//
+// The nullifier is a PRF of the election under the secret key, the contract counting the votes
+// rejects a nullifier it has seen before. Votes of a voter in different elections are unlinkable.
+
+/// A voter, registered in the census with `leaf`.
+#[derive(Clone)]
+pub struct Voter {
+    pub sk: Fs,
+}
+
+// sk as an element of Fr, which is larger than Fs
+fn scalar_to_fr(s: &Fs) -> Fr {
+    let mut bytes = vec![];
+    s.into_repr().write_le(&mut bytes).unwrap();
+    let mut repr = <Fr as PrimeField>::Repr::default();
+    repr.read_le(&bytes[..]).unwrap();
+
+    Fr::from_repr(repr).unwrap()
+}
+
+impl Voter {
+    pub fn pub_key(&self, params: &AltJubjubBn256) -> edwards::Point<Bn256, PrimeOrder> {
+        params.generator(FixedGenerators::SpendingKeyGenerator).mul(self.sk, params)
+    }
+
+    pub fn leaf(&self, jubjub_params: &AltJubjubBn256, poseidon_params: &Bn256PoseidonParams) -> Fr {
+        key_leaf(&self.pub_key(jubjub_params), poseidon_params)
+    }
+
+    pub fn nullifier(&self, election: Fr, poseidon_params: &Bn256PoseidonParams) -> Fr {
+        crate::poseidon::poseidon_hash::<Bn256>(poseidon_params, &[scalar_to_fr(&self.sk), election])[0]
+    }
+}
+
+#[derive(Clone)]
+pub struct VoteWitness {
+    pub voter: Voter,
+    /// Authentication path of the voter's leaf in the census.
+    pub merkle_witness: MerkleWitness<Bn256>,
+    pub election: Fr,
+    pub vote: u64,
+}
+
+impl VoteWitness {
+    /// The public inputs, in order.
+    pub fn public_inputs(&self, jubjub_params: &AltJubjubBn256, poseidon_params: &Bn256PoseidonParams) -> Vec<Fr> {
+        let mut merkle_witness = self.merkle_witness.clone();
+        merkle_witness.leaf = self.voter.leaf(jubjub_params, poseidon_params);
+
+        vec![
+            merkle_witness.root(poseidon_params),
+            self.election,
+            self.voter.nullifier(self.election, poseidon_params),
+            u64_to_fe(self.vote),
+        ]
+    }
+}
+
+pub struct VotingCircuit {
+    pub jubjub_params: AltJubjubBn256,
+    pub poseidon_params: Bn256PoseidonParams,
+    pub tree_depth: usize,
+    /// The valid choices.
+    pub choices: Vec<u64>,
+    pub witness: Option<VoteWitness>,
+}
+
+impl VotingCircuit {
+    pub fn new(tree_depth: usize, choices: Vec<u64>, witness: Option<VoteWitness>) -> Self {
+        assert!(!choices.is_empty());
+        if let Some(ref witness) = witness {
+            assert_eq!(witness.merkle_witness.path.len(), tree_depth);
+        }
+
+        VotingCircuit {
+            jubjub_params: AltJubjubBn256::new(),
+            poseidon_params: Bn256PoseidonParams::new_checked_2_into_1(),
+            tree_depth,
+            choices,
+            witness,
+        }
+    }
+}
+
+impl Circuit<Bn256> for VotingCircuit {
+    type MainGate = Width4MainGateWithDNext;
+
+    fn declare_used_gates() -> Result<Vec<Box<dyn GateInternal<Bn256>>>, SynthesisError> {
+        Ok(vec![Width4MainGateWithDNext::default().into_internal()])
+    }
+
+    fn synthesize<CS: ConstraintSystem<Bn256>>(&self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let params = &self.jubjub_params;
+        let curve = CircuitAltBabyJubjubBn256::get_implementor();
+        let witness = self.witness.as_ref();
+
+        // census membership of the voter key
+        let sk_bits = alloc_scalar(cs, witness.map(|w| &w.voter.sk))?;
+        let index = alloc_u64(cs, witness.map(|w| w.merkle_witness.index), self.tree_depth)?;
+        let mut path = Vec::with_capacity(self.tree_depth);
+        for i in 0..self.tree_depth {
+            path.push(Num::alloc(cs, witness.map(|w| w.merkle_witness.path[i]))?);
+        }
+        let (root, _) = prove_key_tree_membership(
+            cs, &curve, &sk_bits, &path, &index, None, FixedGenerators::SpendingKeyGenerator, params, &self.poseidon_params
+        )?;
+        inputize(cs, &root)?;
+
+        // nullifier
+        let election = Num::alloc(cs, witness.map(|w| w.election))?;
+        inputize(cs, &election)?;
+        let sk = pack_bits(cs, &sk_bits)?;
+        let nullifier = poseidon_hash(cs, &self.poseidon_params, &[sk, election])?[0];
+        inputize(cs, &nullifier)?;
+
+        // vote
+        let vote = Num::alloc(cs, witness.map(|w| u64_to_fe(w.vote)))?;
+        let choices: Vec<Fr> = self.choices.iter().map(|&c| u64_to_fe(c)).collect();
+        enforce_membership(cs, &vote, &choices)?;
+        inputize(cs, &vote)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{SeedableRng, Rng, XorShiftRng};
+    use crate::bellman::kate_commitment::{Crs, CrsForMonomialForm};
+    use crate::bellman::plonk::better_better_cs::cs::PlonkCsWidth4WithNextStepParams;
+    use crate::bellman::worker::Worker;
+    use crate::merkle_tree::IncrementalMerkleTree;
+    use crate::plonk::prover::*;
+
+    type Params = PlonkCsWidth4WithNextStepParams;
+
+    const TREE_DEPTH: usize = 4;
+
+    #[test]
+    fn test_voting_circuit() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let jubjub_params = AltJubjubBn256::new();
+        let poseidon_params = Bn256PoseidonParams::new_checked_2_into_1();
+        let choices = vec![0, 1, 2];
+
+        let voter = Voter { sk: rng.gen() };
+        let mut census = IncrementalMerkleTree::<Bn256>::new(TREE_DEPTH, &poseidon_params);
+        census.append(Voter { sk: rng.gen() }.leaf(&jubjub_params, &poseidon_params), &poseidon_params);
+        let index = census.append_and_mark(voter.leaf(&jubjub_params, &poseidon_params), &poseidon_params).unwrap();
+        census.append(Voter { sk: rng.gen() }.leaf(&jubjub_params, &poseidon_params), &poseidon_params);
+
+        let witness = VoteWitness {
+            voter: voter.clone(),
+            merkle_witness: census.witness(index).unwrap().clone(),
+            election: rng.gen(),
+            vote: 2,
+        };
+        let circuit = VotingCircuit::new(TREE_DEPTH, choices.clone(), Some(witness.clone()));
+
+        let crs = Crs::<Bn256, CrsForMonomialForm>::crs_42(required_crs_size::<_, Params, _>(&circuit).unwrap(), &Worker::new());
+        let keys = generate_keys::<_, Params, _>(&circuit, &crs).unwrap();
+        let proof = create_proof::<_, Params, _>(&circuit, &keys, &crs).unwrap();
+        assert_eq!(proof.inputs, witness.public_inputs(&jubjub_params, &poseidon_params));
+        assert_eq!(proof.inputs[0], census.root());
+        assert!(verify_proof(&keys.vk, &proof).unwrap());
+
+        // another election gives another nullifier
+        let mut other_election = witness.clone();
+        other_election.election = rng.gen();
+        assert_ne!(other_election.public_inputs(&jubjub_params, &poseidon_params)[2], proof.inputs[2]);
+
+        // an invalid choice
+        let mut invalid = witness.clone();
+        invalid.vote = 3;
+        let circuit = VotingCircuit::new(TREE_DEPTH, choices.clone(), Some(invalid));
+        assert!(create_proof::<_, Params, _>(&circuit, &keys, &crs).is_err());
+
+        // a key outside of the census: the proof is for another root
+        let mut outsider = witness.clone();
+        outsider.voter = Voter { sk: rng.gen() };
+        let circuit = VotingCircuit::new(TREE_DEPTH, choices.clone(), Some(outsider.clone()));
+        let proof = create_proof::<_, Params, _>(&circuit, &keys, &crs).unwrap();
+        assert_ne!(proof.inputs[0], census.root());
+    }
+}
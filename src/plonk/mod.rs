@@ -1 +1,3 @@
-pub mod circuit;
\ No newline at end of file
+pub mod circuit;
+pub mod examples;
+pub mod prover;
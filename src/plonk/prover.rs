@@ -0,0 +1,259 @@
+use crate::bellman::pairing::{Engine, CurveAffine, CurveProjective};
+
+use crate::bellman::pairing::ff::PrimeField;
+
+use crate::bellman::pairing::bn256::{Bn256, Fr};
+
+use crate::bellman::{ScalarEngine, SynthesisError};
+
+use crate::bellman::worker::Worker;
+
+use crate::bellman::kate_commitment::{Crs, CrsForMonomialForm};
+
+use crate::bellman::plonk::better_better_cs::cs::{
+    Circuit,
+    ConstraintSystem,
+    GateInternal,
+    Gate,
+    PlonkConstraintSystemParams,
+    TrivialAssembly,
+    Width4MainGateWithDNext,
+};
+
+use crate::bellman::plonk::better_better_cs::proof::Proof;
+use crate::bellman::plonk::better_better_cs::setup::{Setup, VerificationKey};
+use crate::bellman::plonk::better_better_cs::verifier::verify;
+use crate::bellman::plonk::commitments::transcript::keccak_transcript::RollingKeccakTranscript;
+
+use crate::plonk::circuit::allocated_num::{AllocatedNum, Num};
+use crate::plonk::circuit::custom_rescue_gate::Rescue5CustomGate;
+use crate::plonk::circuit::rescue::rescue_hash;
+use crate::plonk::circuit::Assignment;
+use crate::rescue::bn256::Bn256RescueParams;
+
+use rand::{Rand, Rng};
+
+// End to end helpers over the PLONK prover of bellman: synthesize a circuit, derive its setup
+// and verification key, prove and verify. Proofs use the rolling Keccak transcript, the one
+// the Solidity verifier expects. The CRS comes from a universal setup ceremony and has to hold
+// at least `required_crs_size` points, `Crs::crs_42` gives an insecure one for tests.
+
+pub type Transcript<E> = RollingKeccakTranscript<<E as ScalarEngine>::Fr>;
+
+/// Everything the prover needs besides the CRS, and the key for the verifier.
+pub struct Keys<E: Engine, C: Circuit<E>> {
+    pub setup: Setup<E, C>,
+    pub vk: VerificationKey<E, C>,
+}
+
+/// Synthesizes `circuit` into a finalized assembly.
+pub fn synthesize<E, P, C>(circuit: &C) -> Result<TrivialAssembly<E, P, C::MainGate>, SynthesisError>
+    where E: Engine,
+          P: PlonkConstraintSystemParams<E>,
+          C: Circuit<E>
+{
+    let mut assembly = TrivialAssembly::<E, P, C::MainGate>::new();
+    circuit.synthesize(&mut assembly)?;
+    assembly.finalize();
+
+    Ok(assembly)
+}
+
+/// The number of CRS points needed to set up and prove `circuit`.
+pub fn required_crs_size<E, P, C>(circuit: &C) -> Result<usize, SynthesisError>
+    where E: Engine,
+          P: PlonkConstraintSystemParams<E>,
+          C: Circuit<E>
+{
+    let assembly = synthesize::<E, P, C>(circuit)?;
+
+    Ok(assembly.n().next_power_of_two())
+}
+
+/// Derives the setup and the verification key. The assembly evaluates the witness on the way,
+/// so `circuit` needs one, any valid assignment gives the same keys.
+pub fn generate_keys<E, P, C>(circuit: &C, crs: &Crs<E, CrsForMonomialForm>) -> Result<Keys<E, C>, SynthesisError>
+    where E: Engine,
+          P: PlonkConstraintSystemParams<E>,
+          C: Circuit<E>
+{
+    let worker = Worker::new();
+    let assembly = synthesize::<E, P, C>(circuit)?;
+    let setup = assembly.create_setup::<C>(&worker)?;
+    let vk = VerificationKey::from_setup(&setup, &worker, crs)?;
+
+    Ok(Keys { setup, vk })
+}
+
+/// Proves `circuit`, which has to have the shape the keys were generated for.
+pub fn create_proof<E, P, C>(
+    circuit: &C,
+    keys: &Keys<E, C>,
+    crs: &Crs<E, CrsForMonomialForm>
+) -> Result<Proof<E, C>, SynthesisError>
+    where E: Engine,
+          P: PlonkConstraintSystemParams<E>,
+          C: Circuit<E>
+{
+    let worker = Worker::new();
+    let assembly = synthesize::<E, P, C>(circuit)?;
+    if !assembly.is_satisfied() {
+        return Err(SynthesisError::Unsatisfiable);
+    }
+
+    assembly.create_proof::<C, Transcript<E>>(&worker, &keys.setup, crs, None)
+}
+
+pub fn verify_proof<E: Engine, C: Circuit<E>>(vk: &VerificationKey<E, C>, proof: &Proof<E, C>) -> Result<bool, SynthesisError> {
+    verify::<E, C, Transcript<E>>(vk, proof, None)
+}
+
+pub fn proof_to_bytes<E: Engine, C: Circuit<E>>(proof: &Proof<E, C>) -> Result<Vec<u8>, SynthesisError> {
+    let mut bytes = vec![];
+    proof.write(&mut bytes)?;
+
+    Ok(bytes)
+}
+
+pub fn proof_from_bytes<E: Engine, C: Circuit<E>>(bytes: &[u8]) -> Result<Proof<E, C>, SynthesisError> {
+    Ok(Proof::read(bytes)?)
+}
+
+pub fn vk_to_bytes<E: Engine, C: Circuit<E>>(vk: &VerificationKey<E, C>) -> Result<Vec<u8>, SynthesisError> {
+    let mut bytes = vec![];
+    vk.write(&mut bytes)?;
+
+    Ok(bytes)
+}
+
+pub fn vk_from_bytes<E: Engine, C: Circuit<E>>(bytes: &[u8]) -> Result<VerificationKey<E, C>, SynthesisError> {
+    Ok(VerificationKey::read(bytes)?)
+}
+
+/// BLAKE2s of the serialized verification key. The keys are a deterministic function of the
+/// circuit and the CRS, so parties that regenerate them compare digests to agree on the circuit.
+pub fn vk_digest<E: Engine, C: Circuit<E>>(vk: &VerificationKey<E, C>) -> Result<[u8; 32], SynthesisError> {
+    let mut digest = [0u8; 32];
+    digest.copy_from_slice(blake2s_simd::blake2s(&vk_to_bytes(vk)?).as_bytes());
+
+    Ok(digest)
+}
+
+/// Checks that the CRS of a universal setup ceremony consists of successive powers of one
+/// secret: g1_bases[i] = x^i G1 and g2 bases G2, x G2. All powers are tested at once with a
+/// random linear combination, so a forged CRS passes with probability 1/|Fr|.
+pub fn validate_crs<E: Engine, R: Rng>(crs: &Crs<E, CrsForMonomialForm>, rng: &mut R) -> bool {
+    let g1 = &crs.g1_bases;
+    let g2 = &crs.g2_monomial_bases;
+    if g1.len() < 2 || g2.len() < 2 {
+        return false;
+    }
+    if g1[0] != E::G1Affine::one() || g2[0] != E::G2Affine::one() || g2[1].is_zero() {
+        return false;
+    }
+
+    // e(sum r_i x^i G1, x G2) == e(sum r_i x^(i + 1) G1, G2)
+    let mut lhs = E::G1::zero();
+    let mut rhs = E::G1::zero();
+    for pair in g1.windows(2) {
+        let r = E::Fr::rand(rng).into_repr();
+        lhs.add_assign(&pair[0].mul(r));
+        rhs.add_assign(&pair[1].mul(r));
+    }
+
+    E::pairing(lhs, g2[1]) == E::pairing(rhs, g2[0])
+}
+
+/// Knowledge of a preimage of a Rescue hash, the hash is the only public input. Meant for
+/// `PlonkCsWidth4WithNextStepAndCustomGatesParams`, which uses the custom gate for the S-box.
+pub struct RescuePreimageCircuit {
+    pub params: Bn256RescueParams,
+    pub preimage_len: usize,
+    pub preimage: Option<Vec<Fr>>,
+}
+
+impl RescuePreimageCircuit {
+    pub fn new(params: Bn256RescueParams, preimage_len: usize, preimage: Option<Vec<Fr>>) -> Self {
+        if let Some(ref preimage) = preimage {
+            assert_eq!(preimage.len(), preimage_len);
+        }
+
+        RescuePreimageCircuit { params, preimage_len, preimage }
+    }
+
+    /// The public input for `preimage`.
+    pub fn hash(&self, preimage: &[Fr]) -> Fr {
+        crate::rescue::rescue_hash::<Bn256>(&self.params, preimage)[0]
+    }
+}
+
+impl Circuit<Bn256> for RescuePreimageCircuit {
+    type MainGate = Width4MainGateWithDNext;
+
+    fn declare_used_gates() -> Result<Vec<Box<dyn GateInternal<Bn256>>>, SynthesisError> {
+        Ok(
+            vec![
+                Width4MainGateWithDNext::default().into_internal(),
+                Rescue5CustomGate::default().into_internal(),
+            ]
+        )
+    }
+
+    fn synthesize<CS: ConstraintSystem<Bn256>>(&self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let mut input = Vec::with_capacity(self.preimage_len);
+        for i in 0..self.preimage_len {
+            let value = self.preimage.as_ref().map(|p| p[i]);
+            input.push(Num::Variable(AllocatedNum::alloc(cs, || Ok(*value.get()?))?));
+        }
+
+        let digest = rescue_hash(cs, &self.params, &input)?;
+        match digest[0] {
+            Num::Variable(ref digest) => digest.inputize(cs),
+            Num::Constant(_) => unreachable!("the preimage is allocated"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bellman::plonk::better_better_cs::cs::PlonkCsWidth4WithNextStepAndCustomGatesParams;
+    use rand::{SeedableRng, XorShiftRng};
+
+    type Params = PlonkCsWidth4WithNextStepAndCustomGatesParams;
+
+    #[test]
+    fn test_prove_and_verify() {
+        let preimage = vec![Fr::from_str("1").unwrap(), Fr::from_str("2").unwrap()];
+        let circuit = RescuePreimageCircuit::new(Bn256RescueParams::new_checked_2_into_1(), 2, Some(preimage.clone()));
+
+        let crs = Crs::<Bn256, CrsForMonomialForm>::crs_42(required_crs_size::<_, Params, _>(&circuit).unwrap(), &Worker::new());
+        let keys = generate_keys::<_, Params, _>(&circuit, &crs).unwrap();
+        let again = generate_keys::<_, Params, _>(&circuit, &crs).unwrap();
+        assert_eq!(vk_digest(&keys.vk).unwrap(), vk_digest(&again.vk).unwrap());
+
+        let proof = create_proof::<_, Params, _>(&circuit, &keys, &crs).unwrap();
+        assert_eq!(proof.inputs, vec![circuit.hash(&preimage)]);
+        assert!(verify_proof(&keys.vk, &proof).unwrap());
+
+        let proof: Proof<Bn256, RescuePreimageCircuit> = proof_from_bytes(&proof_to_bytes(&proof).unwrap()).unwrap();
+        let vk: VerificationKey<Bn256, RescuePreimageCircuit> = vk_from_bytes(&vk_to_bytes(&keys.vk).unwrap()).unwrap();
+        assert!(verify_proof(&vk, &proof).unwrap());
+
+        let mut forged = proof;
+        forged.inputs[0] = Fr::from_str("3").unwrap();
+        assert!(!verify_proof(&vk, &forged).unwrap());
+    }
+
+    #[test]
+    fn test_validate_crs() {
+        let rng = &mut XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let crs = Crs::<Bn256, CrsForMonomialForm>::crs_42(16, &Worker::new());
+        assert!(validate_crs(&crs, rng));
+
+        let mut g1_bases = (*crs.g1_bases).clone();
+        g1_bases.swap(3, 4);
+        let tampered = Crs::<Bn256, CrsForMonomialForm>::new(g1_bases, (*crs.g2_monomial_bases).clone());
+        assert!(!validate_crs(&tampered, rng));
+    }
+}
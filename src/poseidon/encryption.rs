@@ -0,0 +1,127 @@
+use bellman::pairing::ff::{Field, PrimeField};
+
+use super::{PoseidonEngine, PoseidonHashParams, poseidon_permutation};
+
+// Authenticated encryption of field elements with the Poseidon duplex. The state starts with
+// the domain tag in the last capacity element, the key and the nonce are absorbed, and every
+// permutation gives `rate` keystream elements that are added to the message. The plaintext
+// chunk is then absorbed, so the state holds the ciphertext chunk before the next permutation,
+// and the first element after the last one is the tag.
+// `plonk::circuit::poseidon_encryption` is the same scheme in circuit.
+
+/// The message length goes into the domain tag, so truncated ciphertexts don't decrypt.
+/// The offset keeps tags apart from the length specialization of `poseidon_hash`.
+pub fn encryption_domain_tag(message_len: usize) -> u64 {
+    (1u64 << 32) + message_len as u64
+}
+
+#[derive(Clone)]
+pub struct PoseidonCiphertext<E: PoseidonEngine> {
+    pub elements: Vec<E::Fr>,
+    pub tag: E::Fr,
+}
+
+fn initial_state<E: PoseidonEngine>(
+    params: &E::Params,
+    key: E::Fr,
+    nonce: E::Fr,
+    message_len: usize
+) -> Vec<E::Fr> {
+    assert!(params.rate() >= 2, "key and nonce are absorbed at once");
+
+    let mut state = vec![E::Fr::zero(); params.state_width() as usize];
+    let mut repr = <E::Fr as PrimeField>::Repr::default();
+    repr.as_mut()[0] = encryption_domain_tag(message_len);
+    let last = state.len() - 1;
+    state[last] = E::Fr::from_repr(repr).unwrap();
+
+    state[0] = key;
+    state[1] = nonce;
+    poseidon_permutation::<E>(params, &mut state);
+
+    state
+}
+
+/// `nonce` must never repeat for the same key.
+pub fn poseidon_encrypt<E: PoseidonEngine>(
+    params: &E::Params,
+    key: E::Fr,
+    nonce: E::Fr,
+    message: &[E::Fr]
+) -> PoseidonCiphertext<E> {
+    let mut state = initial_state::<E>(params, key, nonce, message.len());
+
+    let mut elements = Vec::with_capacity(message.len());
+    for chunk in message.chunks(params.rate() as usize) {
+        for (s, m) in state.iter_mut().zip(chunk.iter()) {
+            s.add_assign(m);
+            elements.push(*s);
+        }
+        poseidon_permutation::<E>(params, &mut state);
+    }
+
+    PoseidonCiphertext {
+        elements,
+        tag: state[0],
+    }
+}
+
+/// Returns `None` if the tag doesn't match.
+pub fn poseidon_decrypt<E: PoseidonEngine>(
+    params: &E::Params,
+    key: E::Fr,
+    nonce: E::Fr,
+    ciphertext: &PoseidonCiphertext<E>
+) -> Option<Vec<E::Fr>> {
+    let mut state = initial_state::<E>(params, key, nonce, ciphertext.elements.len());
+
+    let mut message = Vec::with_capacity(ciphertext.elements.len());
+    for chunk in ciphertext.elements.chunks(params.rate() as usize) {
+        for (s, c) in state.iter_mut().zip(chunk.iter()) {
+            let mut m = *c;
+            m.sub_assign(s);
+            message.push(m);
+            *s = *c;
+        }
+        poseidon_permutation::<E>(params, &mut state);
+    }
+
+    if state[0] == ciphertext.tag {
+        Some(message)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::Bn256PoseidonParams;
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use rand::{Rng, XorShiftRng, SeedableRng};
+
+    #[test]
+    fn test_poseidon_encryption_roundtrip() {
+        let mut rng = XorShiftRng::from_seed([0x5dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = Bn256PoseidonParams::new_checked_2_into_1();
+
+        let key: Fr = rng.gen();
+        let nonce: Fr = rng.gen();
+        let message: Vec<Fr> = (0..5).map(|_| rng.gen()).collect();
+
+        let ciphertext = poseidon_encrypt::<Bn256>(&params, key, nonce, &message);
+        assert_eq!(ciphertext.elements.len(), message.len());
+        assert_eq!(poseidon_decrypt::<Bn256>(&params, key, nonce, &ciphertext), Some(message.clone()));
+
+        assert_eq!(poseidon_decrypt::<Bn256>(&params, rng.gen(), nonce, &ciphertext), None);
+        assert_eq!(poseidon_decrypt::<Bn256>(&params, key, rng.gen(), &ciphertext), None);
+
+        let mut tampered = ciphertext.clone();
+        tampered.elements[3].add_assign(&Fr::one());
+        assert_eq!(poseidon_decrypt::<Bn256>(&params, key, nonce, &tampered), None);
+
+        let mut truncated = ciphertext.clone();
+        truncated.elements.pop();
+        assert_eq!(poseidon_decrypt::<Bn256>(&params, key, nonce, &truncated), None);
+    }
+}
@@ -0,0 +1,143 @@
+use bellman::pairing::ff::{Field, PrimeField, PrimeFieldRepr};
+
+// Grain LFSR in self-shrinking mode as used by the reference Poseidon parameter
+// generation script (generate_parameters_grain.sage). It's initialized by
+// the description of the instance, so the same (field size, width, number of rounds)
+// always produces the same round constants and MDS matrix.
+
+const STATE_SIZE: usize = 80;
+
+pub(crate) struct GrainLfsr {
+    state: [bool; STATE_SIZE],
+    head: usize,
+}
+
+impl GrainLfsr {
+    pub(crate) fn new(field_bits: u32, state_width: u32, num_full_rounds: u32, num_partial_rounds: u32) -> Self {
+        // field is prime (1) and sbox is x^alpha (0)
+        let description = [
+            (1u32, 2usize),
+            (0, 4),
+            (field_bits, 12),
+            (state_width, 12),
+            (num_full_rounds, 10),
+            (num_partial_rounds, 10),
+        ];
+
+        let mut state = [true; STATE_SIZE];
+        let mut position = 0;
+        for &(value, width) in description.iter() {
+            assert!(value < (1u32 << width));
+            for i in (0..width).rev() {
+                state[position] = (value >> i) & 1 == 1;
+                position += 1;
+            }
+        }
+        // the rest is padded with ones
+        debug_assert_eq!(position, 50);
+
+        let mut lfsr = Self {
+            state,
+            head: 0,
+        };
+
+        for _ in 0..160 {
+            lfsr.next_raw_bit();
+        }
+
+        lfsr
+    }
+
+    fn next_raw_bit(&mut self) -> bool {
+        let at = |offset: usize| self.state[(self.head + offset) % STATE_SIZE];
+        let new_bit = at(62) ^ at(51) ^ at(38) ^ at(23) ^ at(13) ^ at(0);
+        self.state[self.head] = new_bit;
+        self.head = (self.head + 1) % STATE_SIZE;
+
+        new_bit
+    }
+
+    fn next_bit(&mut self) -> bool {
+        // self-shrinking: of every pair of bits the second one is taken
+        // if the first one is set, otherwise both are discarded
+        loop {
+            let control = self.next_raw_bit();
+            let output = self.next_raw_bit();
+            if control {
+                return output;
+            }
+        }
+    }
+
+    fn next_repr<F: PrimeField>(&mut self) -> F::Repr {
+        let mut repr = F::Repr::default();
+        // most significant bit comes first
+        for _ in 0..F::NUM_BITS {
+            repr.shl(1);
+            if self.next_bit() {
+                repr.as_mut()[0] |= 1;
+            }
+        }
+
+        repr
+    }
+
+    /// Samples a field element, discarding candidates that are not less than the modulus.
+    pub(crate) fn next_field_element<F: PrimeField>(&mut self) -> F {
+        loop {
+            if let Ok(element) = F::from_repr(self.next_repr::<F>()) {
+                return element;
+            }
+        }
+    }
+
+    /// Samples a field element, reducing candidates modulo the modulus.
+    pub(crate) fn next_field_element_reduced<F: PrimeField>(&mut self) -> F {
+        let mut repr = self.next_repr::<F>();
+        if let Ok(element) = F::from_repr(repr) {
+            return element;
+        }
+        // candidate has NUM_BITS bits, so it's less than 2p
+        repr.sub_noborrow(&F::char());
+
+        F::from_repr(repr).expect("must be reduced after a single subtraction")
+    }
+}
+
+/// Generates round constants for all the rounds (state width elements per round)
+/// and a Cauchy MDS matrix in row major order the same way as the reference implementation.
+pub(crate) fn generate_round_constants_and_mds<F: PrimeField>(
+    state_width: u32,
+    num_full_rounds: u32,
+    num_partial_rounds: u32
+) -> (Vec<F>, Vec<F>) {
+    let mut lfsr = GrainLfsr::new(F::NUM_BITS, state_width, num_full_rounds, num_partial_rounds);
+
+    let num_round_constants = ((num_full_rounds + num_partial_rounds) * state_width) as usize;
+    let round_constants: Vec<F> = (0..num_round_constants).map(|_| lfsr.next_field_element()).collect();
+
+    let t = state_width as usize;
+    'sampling: loop {
+        let elements: Vec<F> = (0..(2*t)).map(|_| lfsr.next_field_element_reduced()).collect();
+        for i in 0..elements.len() {
+            if elements[(i+1)..].contains(&elements[i]) {
+                continue 'sampling;
+            }
+        }
+
+        let (xs, ys) = elements.split_at(t);
+        let mut mds_matrix = Vec::with_capacity(t * t);
+        for x in xs.iter() {
+            for y in ys.iter() {
+                let mut sum = *x;
+                sum.add_assign(y);
+                match sum.inverse() {
+                    Some(inverse) => mds_matrix.push(inverse),
+                    None => continue 'sampling,
+                }
+            }
+        }
+
+        return (round_constants, mds_matrix);
+    }
+}
@@ -0,0 +1,473 @@
+use bellman::pairing::ff::{Field, PrimeField};
+use bellman::pairing::{Engine};
+use bellman::pairing::{bn256, bls12_381};
+
+pub use crate::rescue::{SBox, QuinticSBox};
+
+mod grain;
+pub mod encryption;
+
+use self::grain::generate_round_constants_and_mds;
+
+// Poseidon permutation (https://eprint.iacr.org/2019/458) with x^5 S-box.
+// Round constants and MDS matrix are produced by the Grain LFSR the same way
+// as by the reference parameter generation script, so the permutation matches
+// other implementations for the same field, width and number of rounds.
+// Every round adds round constants, applies the S-box to the full state in
+// the full rounds and to the first element only in the partial rounds,
+// and multiplies the state by the MDS matrix. Full rounds are split in halves
+// before and after the partial ones.
+//
+// Sponge uses the first `rate` elements of the state to absorb and squeeze,
+// the last `capacity` elements are never touched directly.
+
+/// Number of partial rounds for 128 bit security with x^5 S-box for state widths 2..=17.
+/// Same numbers hold for BN254 and BLS12-381 scalar fields.
+const NUM_PARTIAL_ROUNDS: [u32; 16] = [56, 57, 56, 60, 60, 63, 64, 63, 60, 66, 60, 65, 70, 60, 64, 68];
+const NUM_FULL_ROUNDS: u32 = 8;
+
+pub trait PoseidonHashParams<E: Engine>: Send + Sync + Sized + Clone + std::fmt::Debug + Eq {
+    type SBox: SBox<E>;
+    fn capacity(&self) -> u32;
+    fn rate(&self) -> u32;
+    fn state_width(&self) -> u32 {
+        self.capacity() + self.rate()
+    }
+    fn num_full_rounds(&self) -> u32;
+    fn num_partial_rounds(&self) -> u32;
+    fn round_constants(&self, round: u32) -> &[E::Fr];
+    fn mds_matrix_row(&self, row: u32) -> &[E::Fr];
+    fn security_level(&self) -> u32;
+    fn output_len(&self) -> u32 {
+        self.capacity()
+    }
+    fn absorbtion_cycle_len(&self) -> u32 {
+        self.rate()
+    }
+    fn compression_rate(&self) -> u32 {
+        self.absorbtion_cycle_len() / self.output_len()
+    }
+    fn sbox(&self) -> &Self::SBox;
+}
+
+pub trait PoseidonEngine: Engine {
+    type Params: PoseidonHashParams<Self>;
+}
+
+impl PoseidonEngine for bn256::Bn256 {
+    type Params = Bn256PoseidonParams;
+}
+
+impl PoseidonEngine for bls12_381::Bls12 {
+    type Params = Bls12PoseidonParams;
+}
+
+pub type Bn256PoseidonParams = PoseidonParams<bn256::Bn256>;
+pub type Bls12PoseidonParams = PoseidonParams<bls12_381::Bls12>;
+
+#[derive(Clone, Debug)]
+pub struct PoseidonParams<E: Engine> {
+    c: u32,
+    r: u32,
+    full_rounds: u32,
+    partial_rounds: u32,
+    round_constants: Vec<E::Fr>,
+    mds_matrix: Vec<E::Fr>,
+    security_level: u32,
+    sbox: QuinticSBox<E>,
+}
+
+impl<E: Engine> PartialEq for PoseidonParams<E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.c == other.c &&
+        self.r == other.r &&
+        self.full_rounds == other.full_rounds &&
+        self.partial_rounds == other.partial_rounds &&
+        self.round_constants == other.round_constants &&
+        self.mds_matrix == other.mds_matrix &&
+        self.security_level == other.security_level
+    }
+}
+
+impl<E: Engine> Eq for PoseidonParams<E> {}
+
+impl<E: Engine> PoseidonParams<E> {
+    /// Width 3 instance: 2 field elements are compressed into 1.
+    pub fn new_checked_2_into_1() -> Self {
+        Self::new_with_rate(2)
+    }
+
+    /// Width 5 instance: 4 field elements are compressed into 1, e.g. for arity 4 Merkle trees.
+    pub fn new_checked_4_into_1() -> Self {
+        Self::new_with_rate(4)
+    }
+
+    /// Width 9 instance: 8 field elements are compressed into 1, e.g. for arity 8 Merkle trees.
+    pub fn new_checked_8_into_1() -> Self {
+        Self::new_with_rate(8)
+    }
+
+    /// Instance with capacity 1 and given rate using the standard number of rounds for this width.
+    pub fn new_with_rate(rate: u32) -> Self {
+        let c = 1u32;
+        let state_width = c + rate;
+        assert!(state_width >= 2 && state_width as usize <= NUM_PARTIAL_ROUNDS.len() + 1, "unsupported state width");
+        let partial_rounds = NUM_PARTIAL_ROUNDS[(state_width - 2) as usize];
+
+        Self::new_for_params(c, rate, NUM_FULL_ROUNDS, partial_rounds, 128)
+    }
+
+    pub fn new_for_params(c: u32, r: u32, full_rounds: u32, partial_rounds: u32, security_level: u32) -> Self {
+        assert!(c > 0 && r > 0);
+        assert!(full_rounds % 2 == 0, "full rounds are split in two halves");
+        let state_width = c + r;
+
+        let (round_constants, mds_matrix) = generate_round_constants_and_mds::<E::Fr>(state_width, full_rounds, partial_rounds);
+
+        Self {
+            c,
+            r,
+            full_rounds,
+            partial_rounds,
+            round_constants,
+            mds_matrix,
+            security_level,
+            sbox: QuinticSBox { _marker: std::marker::PhantomData },
+        }
+    }
+}
+
+impl<E: Engine> PoseidonHashParams<E> for PoseidonParams<E> {
+    type SBox = QuinticSBox<E>;
+
+    fn capacity(&self) -> u32 {
+        self.c
+    }
+    fn rate(&self) -> u32 {
+        self.r
+    }
+    fn num_full_rounds(&self) -> u32 {
+        self.full_rounds
+    }
+    fn num_partial_rounds(&self) -> u32 {
+        self.partial_rounds
+    }
+    fn round_constants(&self, round: u32) -> &[E::Fr] {
+        let t = self.c + self.r;
+        let start = (t*round) as usize;
+        let end = (t*(round+1)) as usize;
+
+        &self.round_constants[start..end]
+    }
+    fn mds_matrix_row(&self, row: u32) -> &[E::Fr] {
+        let t = self.c + self.r;
+        let start = (t*row) as usize;
+        let end = (t*(row+1)) as usize;
+
+        &self.mds_matrix[start..end]
+    }
+    fn security_level(&self) -> u32 {
+        self.security_level
+    }
+    fn sbox(&self) -> &Self::SBox {
+        &self.sbox
+    }
+}
+
+pub fn poseidon_hash<E: PoseidonEngine>(
+    params: &E::Params,
+    input: &[E::Fr]
+) -> Vec<E::Fr> {
+    sponge_fixed_length::<E>(params, input)
+}
+
+fn sponge_fixed_length<E: PoseidonEngine>(
+    params: &E::Params,
+    input: &[E::Fr]
+) -> Vec<E::Fr> {
+    assert!(input.len() > 0);
+    assert!(input.len() < 256);
+    let input_len = input.len() as u64;
+    let mut state = vec![E::Fr::zero(); params.state_width() as usize];
+    // specialized for input length
+    let mut repr = <E::Fr as PrimeField>::Repr::default();
+    repr.as_mut()[0] = input_len;
+    let len_fe = <E::Fr as PrimeField>::from_repr(repr).unwrap();
+    let last_state_elem_idx = state.len() - 1;
+    state[last_state_elem_idx] = len_fe;
+
+    let rate = params.rate() as usize;
+    let mut absorbtion_cycles = input.len() / rate;
+    if input.len() % rate != 0 {
+        absorbtion_cycles += 1;
+    }
+    let padding_len = absorbtion_cycles * rate - input.len();
+    let padding = vec![E::Fr::one(); padding_len];
+
+    let mut it = input.iter().chain(&padding);
+    for _ in 0..absorbtion_cycles {
+        for i in 0..rate {
+            state[i].add_assign(&it.next().unwrap());
+        }
+        poseidon_permutation::<E>(params, &mut state);
+    }
+
+    debug_assert!(it.next().is_none());
+
+    state[..(params.output_len() as usize)].to_vec()
+}
+
+pub fn poseidon_permutation<E: PoseidonEngine>(
+    params: &E::Params,
+    state: &mut [E::Fr]
+) {
+    assert_eq!(state.len(), params.state_width() as usize);
+    let half_full_rounds = params.num_full_rounds() / 2;
+    let partial_rounds_end = half_full_rounds + params.num_partial_rounds();
+    let num_rounds = params.num_full_rounds() + params.num_partial_rounds();
+
+    let mut mds_application_scratch = vec![E::Fr::zero(); state.len()];
+
+    for round in 0..num_rounds {
+        for (s, c) in state.iter_mut().zip(params.round_constants(round).iter()) {
+            s.add_assign(c);
+        }
+
+        if round < half_full_rounds || round >= partial_rounds_end {
+            params.sbox().apply(state);
+        } else {
+            params.sbox().apply(&mut state[0..1]);
+        }
+
+        for (row, place_into) in mds_application_scratch.iter_mut().enumerate() {
+            *place_into = scalar_product::<E>(&state[..], params.mds_matrix_row(row as u32));
+        }
+
+        state.copy_from_slice(&mds_application_scratch[..]);
+    }
+}
+
+fn scalar_product<E: Engine> (input: &[E::Fr], by: &[E::Fr]) -> E::Fr {
+    assert!(input.len() == by.len());
+    let mut result = E::Fr::zero();
+    for (a, b) in input.iter().zip(by.iter()) {
+        let mut tmp = *a;
+        tmp.mul_assign(b);
+        result.add_assign(&tmp);
+    }
+
+    result
+}
+
+#[derive(Clone, Debug)]
+enum OpMode<E: PoseidonEngine> {
+    AccumulatingToAbsorb(Vec<E::Fr>),
+    SqueezedInto(Vec<E::Fr>)
+}
+
+#[derive(Clone, Debug)]
+pub struct StatefulSponge<'a, E: PoseidonEngine> {
+    params: &'a E::Params,
+    internal_state: Vec<E::Fr>,
+    mode: OpMode<E>
+}
+
+impl<'a, E: PoseidonEngine> StatefulSponge<'a, E> {
+    pub fn new(
+        params: &'a E::Params
+    ) -> Self {
+        let op = OpMode::AccumulatingToAbsorb(Vec::with_capacity(params.rate() as usize));
+
+        StatefulSponge::<_> {
+            params,
+            internal_state: vec![E::Fr::zero(); params.state_width() as usize],
+            mode: op
+        }
+    }
+
+    pub fn specialize(
+        &mut self,
+        dst: u8
+    ) {
+        match self.mode {
+            OpMode::AccumulatingToAbsorb(ref into) => {
+                assert_eq!(into.len(), 0, "can not specialize sponge that absorbed something")
+            },
+            _ => {
+                panic!("can not specialized sponge in squeezing state");
+            }
+        }
+        let dst = dst as u64;
+        let mut repr = <E::Fr as PrimeField>::Repr::default();
+        repr.as_mut()[0] = dst;
+        let as_fe = <E::Fr as PrimeField>::from_repr(repr).unwrap();
+        let last_state_elem_idx = self.internal_state.len() - 1;
+        self.internal_state[last_state_elem_idx] = as_fe;
+    }
+
+    pub fn absorb_single_value(
+        &mut self,
+        value: E::Fr
+    ) {
+        match self.mode {
+            OpMode::AccumulatingToAbsorb(ref mut into) => {
+                // either we have accumulated enough already and should do
+                // a permutation before accumulating more, or just accumulate more
+                let rate = self.params.rate() as usize;
+                if into.len() < rate {
+                    into.push(value);
+                } else {
+                    for i in 0..rate {
+                        self.internal_state[i].add_assign(&into[i]);
+                    }
+
+                    poseidon_permutation::<E>(self.params, &mut self.internal_state);
+
+                    into.truncate(0);
+                    into.push(value);
+                }
+            },
+            OpMode::SqueezedInto(_) => {
+                // we don't need anything from the output, so it's dropped
+                let mut s = Vec::with_capacity(self.params.rate() as usize);
+                s.push(value);
+
+                let op = OpMode::AccumulatingToAbsorb(s);
+                self.mode = op;
+            }
+        }
+    }
+
+    pub fn absorb(
+        &mut self,
+        input: &[E::Fr]
+    ) {
+        assert!(input.len() > 0);
+        let rate = self.params.rate() as usize;
+        let mut absorbtion_cycles = input.len() / rate;
+        if input.len() % rate != 0 {
+            absorbtion_cycles += 1;
+        }
+        let padding_len = absorbtion_cycles * rate - input.len();
+        let padding = vec![E::Fr::one(); padding_len];
+
+        let it = input.iter().chain(&padding);
+
+        for &val in it {
+            self.absorb_single_value(val);
+        }
+    }
+
+    pub fn pad_if_necessary(&mut self) {
+        match self.mode {
+            OpMode::AccumulatingToAbsorb(ref mut into) => {
+                let rate = self.params.rate() as usize;
+                if into.len() != rate {
+                    into.resize(rate, E::Fr::one());
+                }
+            },
+            OpMode::SqueezedInto(_) => {}
+        }
+    }
+
+    pub fn squeeze_out_single(
+        &mut self,
+    ) -> E::Fr {
+        match self.mode {
+            OpMode::AccumulatingToAbsorb(ref mut into) => {
+                let rate = self.params.rate() as usize;
+                assert_eq!(into.len(), rate, "padding was necessary!");
+                for i in 0..rate {
+                    self.internal_state[i].add_assign(&into[i]);
+                }
+                poseidon_permutation::<E>(self.params, &mut self.internal_state);
+
+                // we don't take full internal state, but only the rate
+                let mut sponge_output = self.internal_state[0..rate].to_vec();
+                let output = sponge_output.drain(0..1).next().unwrap();
+
+                let op = OpMode::SqueezedInto(sponge_output);
+                self.mode = op;
+
+                output
+            },
+            OpMode::SqueezedInto(ref mut into) => {
+                assert!(into.len() > 0, "squeezed state is depleted!");
+
+                into.drain(0..1).next().unwrap()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{Rng, XorShiftRng, SeedableRng};
+    use bellman::pairing::ff::from_hex;
+
+    fn check_permutation<E: PoseidonEngine>(params: &E::Params, expected: &[&str]) {
+        let mut state: Vec<E::Fr> = (0..params.state_width()).map(|i| {
+            E::Fr::from_str(&i.to_string()).unwrap()
+        }).collect();
+        poseidon_permutation::<E>(params, &mut state);
+
+        let expected: Vec<E::Fr> = expected.iter().map(|el| from_hex::<E::Fr>(el).unwrap()).collect();
+        assert_eq!(state, expected);
+    }
+
+    // vectors are taken from the reference implementation, permutation of [0, 1, ..., t - 1]
+
+    #[test]
+    fn test_bn256_poseidon_permutation_vectors() {
+        check_permutation::<bn256::Bn256>(&Bn256PoseidonParams::new_checked_2_into_1(), &[
+            "0x115cc0f5e7d690413df64c6b9662e9cf2a3617f2743245519e19607a4417189a",
+            "0x0fca49b798923ab0239de1c9e7a4a9a2210312b6a2f616d18b5a87f9b628ae29",
+            "0x0e7ae82e40091e63cbd4f16a6d16310b3729d4b6e138fcf54110e2867045a30c",
+        ]);
+
+        check_permutation::<bn256::Bn256>(&Bn256PoseidonParams::new_checked_4_into_1(), &[
+            "0x299c867db6c1fdd79dcefa40e4510b9837e60ebb1ce0663dbaa525df65250465",
+            "0x1148aaef609aa338b27dafd89bb98862d8bb2b429aceac47d86206154ffe053d",
+            "0x24febb87fed7462e23f6665ff9a0111f4044c38ee1672c1ac6b0637d34f24907",
+            "0x0eb08f6d809668a981c186beaf6110060707059576406b248e5d9cf6e78b3d3e",
+            "0x07748bc6877c9b82c8b98666ee9d0626ec7f5be4205f79ee8528ef1c4a376fc7",
+        ]);
+    }
+
+    #[test]
+    fn test_bls12_poseidon_permutation_vectors() {
+        check_permutation::<bls12_381::Bls12>(&Bls12PoseidonParams::new_checked_2_into_1(), &[
+            "0x28ce19420fc246a05553ad1e8c98f5c9d67166be2c18e9e4cb4b4e317dd2a78a",
+            "0x51f3e312c95343a896cfd8945ea82ba956c1118ce9b9859b6ea56637b4b1ddc4",
+            "0x3b2b69139b235626a0bfb56c9527ae66a7bf486ad8c11c14d1da0c69bbe0f79a",
+        ]);
+
+        check_permutation::<bls12_381::Bls12>(&Bls12PoseidonParams::new_checked_4_into_1(), &[
+            "0x2a918b9c9f9bd7bb509331c81e297b5707f6fc7393dcee1b13901a0b22202e18",
+            "0x65ebf8671739eeb11fb217f2d5c5bf4a0c3f210e3f3cd3b08b5db75675d797f7",
+            "0x2cc176fc26bc70737a696a9dfd1b636ce360ee76926d182390cdb7459cf585ce",
+            "0x4dc4e29d283afd2a491fe6aef122b9a968e74eff05341f3cc23fda1781dcb566",
+            "0x03ff622da276830b9451b88b85e6184fd6ae15c8ab3ee25a5667be8592cce3b1",
+        ]);
+    }
+
+    #[test]
+    fn test_poseidon_stateful_sponge_matches_fixed_length_hash() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let params = Bn256PoseidonParams::new_with_rate(3);
+
+        for len in 1..8 {
+            let input: Vec<bn256::Fr> = (0..len).map(|_| rng.gen()).collect();
+            let expected = poseidon_hash::<bn256::Bn256>(&params, &input);
+
+            let mut sponge = StatefulSponge::<bn256::Bn256>::new(&params);
+            sponge.specialize(len as u8);
+            sponge.absorb(&input);
+            let output = sponge.squeeze_out_single();
+
+            assert_eq!(expected.len(), 1);
+            assert_eq!(expected[0], output);
+        }
+    }
+}
@@ -23,6 +23,7 @@ use jubjub::{
     JubjubParams,
     edwards,
     PrimeOrder,
+    Unknown,
     FixedGenerators
 };
 
@@ -66,6 +67,7 @@ impl<E: JubjubEngine> ProofGenerationKey<E> {
     }
 }
 
+#[derive(Clone)]
 pub struct ViewingKey<E: JubjubEngine> {
     pub ak: edwards::Point<E, PrimeOrder>,
     pub nk: edwards::Point<E, PrimeOrder>
@@ -100,6 +102,13 @@ impl<E: JubjubEngine> ViewingKey<E> {
         let mut e = <E::Fs as PrimeField>::Repr::default();
         e.read_le(&h[..]).unwrap();
 
+        // The order of the subgroup of some curves is below 2^251, 251 bits are less than
+        // twice of it. The reduction gives the same multiples as the bits in the circuit.
+        if let Ok(ivk) = E::Fs::from_repr(e) {
+            return ivk;
+        }
+        e.sub_noborrow(&E::Fs::char());
+
         E::Fs::from_repr(e).expect("should be a valid scalar")
     }
 
@@ -131,6 +140,11 @@ impl Diversifier {
     {
         group_hash::<E>(&self.0, constants::KEY_DIVERSIFICATION_PERSONALIZATION, params)
     }
+
+    /// About half of the diversifiers are valid, the others have no g_d.
+    pub fn is_valid<E: JubjubEngine>(&self, params: &E::Params) -> bool {
+        self.g_d::<E>(params).is_some()
+    }
 }
 
 #[derive(Clone)]
@@ -140,6 +154,39 @@ pub struct PaymentAddress<E: JubjubEngine> {
 }
 
 impl<E: JubjubEngine> PaymentAddress<E> {
+    /// None if the diversifier is not valid.
+    pub fn from_parts(
+        diversifier: Diversifier,
+        pk_d: edwards::Point<E, PrimeOrder>,
+        params: &E::Params
+    ) -> Option<Self>
+    {
+        if diversifier.is_valid::<E>(params) {
+            Some(PaymentAddress { pk_d, diversifier })
+        } else {
+            None
+        }
+    }
+
+    /// The raw encoding d || pk_d, 43 bytes.
+    pub fn to_bytes(&self) -> [u8; 43] {
+        let mut bytes = [0u8; 43];
+        bytes[..11].copy_from_slice(&self.diversifier.0);
+        self.pk_d.write(&mut bytes[11..]).unwrap();
+
+        bytes
+    }
+
+    /// None if the diversifier is not valid or pk_d is not in the prime order subgroup.
+    pub fn from_bytes(bytes: &[u8; 43], params: &E::Params) -> Option<Self> {
+        let mut diversifier = Diversifier([0u8; 11]);
+        diversifier.0.copy_from_slice(&bytes[..11]);
+        let pk_d = edwards::Point::<E, Unknown>::read(&bytes[11..], params).ok()?
+            .as_prime_order(params)?;
+
+        Self::from_parts(diversifier, pk_d, params)
+    }
+
     pub fn g_d(
         &self,
         params: &E::Params
@@ -166,6 +213,7 @@ impl<E: JubjubEngine> PaymentAddress<E> {
     }
 }
 
+#[derive(Clone)]
 pub struct Note<E: JubjubEngine> {
     /// The value of the note
     pub value: u64,
@@ -188,7 +236,7 @@ impl<E: JubjubEngine> Note<E> {
     }
 
     /// Computes the note commitment, returning the full point.
-    fn cm_full_point(&self, params: &E::Params) -> edwards::Point<E, PrimeOrder>
+    pub fn cm_full_point(&self, params: &E::Params) -> edwards::Point<E, PrimeOrder>
     {
         // Calculate the note contents, as bytes
         let mut note_contents = vec![];
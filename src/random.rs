@@ -0,0 +1,71 @@
+//! Randomness from `rand_core` RNGs such as `OsRng` or `ChaCha20Rng`.
+//!
+//! The crate's older APIs take `rand 0.4` RNGs. `RngCoreAdapter` drives them with any
+//! `rand_core::RngCore`, and `edwards::Point::random`, `Fs::random` and
+//! `redjubjub::generate_keypair` take `RngCore + CryptoRng` directly.
+
+use bellman::pairing::ff::Field;
+use rand::Rand;
+use rand_core::{CryptoRng, RngCore};
+
+/// A `rand_core` RNG as a `rand 0.4` one.
+pub struct RngCoreAdapter<'a, R: RngCore + ?Sized + 'a>(pub &'a mut R);
+
+impl<'a, R: RngCore + ?Sized> ::rand::Rng for RngCoreAdapter<'a, R> {
+    fn next_u32(&mut self) -> u32 {
+        self.0.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.0.fill_bytes(dest)
+    }
+}
+
+/// A uniformly random element of any field of the crate.
+pub fn random_field_element<F: Field + Rand, R: RngCore + CryptoRng + ?Sized>(rng: &mut R) -> F {
+    F::rand(&mut RngCoreAdapter(rng))
+}
+
+#[cfg(test)]
+mod tests {
+    use bellman::pairing::bn256::Bn256;
+    use rand_chacha::ChaCha20Rng;
+    use rand_core::SeedableRng;
+
+    use alt_babyjubjub::{AltJubjubBn256, FixedGenerators, edwards, fs::Fs};
+    use jubjub::{PrimeOrder, Unknown};
+    use redjubjub::{PublicKey, generate_keypair};
+
+    use super::*;
+
+    #[test]
+    fn test_random_apis() {
+        let rng = &mut ChaCha20Rng::from_seed([7u8; 32]);
+        let params = AltJubjubBn256::new();
+        let p_g = FixedGenerators::SpendingKeyGenerator;
+
+        let a = Fs::random(rng);
+        assert!(a != Fs::random(rng));
+        let b: Fs = random_field_element(rng);
+        assert!(a != b);
+
+        // both sample the same way
+        let c: Fs = random_field_element(&mut ChaCha20Rng::from_seed([8u8; 32]));
+        assert_eq!(Fs::random(&mut ChaCha20Rng::from_seed([8u8; 32])), c);
+
+        let p = edwards::Point::<Bn256, Unknown>::random(rng, &params);
+        assert!(p != edwards::Point::<Bn256, Unknown>::random(rng, &params));
+        let g = edwards::Point::<Bn256, PrimeOrder>::random(rng, &params);
+        assert!(g != edwards::Point::zero());
+        assert!(g.is_torsion_free(&params));
+
+        let (sk, pk) = generate_keypair::<Bn256, _>(rng, p_g, &params);
+        assert!(PublicKey::from_private(&sk, p_g, &params).0 == pk.0);
+        let sig = sk.sign(b"message", &mut RngCoreAdapter(rng), p_g, &params);
+        assert!(pk.verify(b"message", &sig, p_g, &params));
+    }
+}
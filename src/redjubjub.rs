@@ -3,12 +3,14 @@
 
 use bellman::pairing::ff::{Field, PrimeField, PrimeFieldRepr};
 use rand::{Rng, Rand};
+use rand_core::{CryptoRng, RngCore};
 use std::io::{self, Read, Write};
 
-use jubjub::{FixedGenerators, JubjubEngine, JubjubParams, Unknown, edwards::Point};
+use jubjub::{FixedGenerators, JubjubEngine, JubjubParams, Unknown, edwards::{multiexp, Point}};
 use util::{hash_to_scalar};
+use random::RngCoreAdapter;
 
-fn read_scalar<E: JubjubEngine, R: Read>(reader: R) -> io::Result<E::Fs> {
+pub(crate) fn read_scalar<E: JubjubEngine, R: Read>(reader: R) -> io::Result<E::Fs> {
     let mut s_repr = <E::Fs as PrimeField>::Repr::default();
     s_repr.read_le(reader)?;
 
@@ -21,11 +23,11 @@ fn read_scalar<E: JubjubEngine, R: Read>(reader: R) -> io::Result<E::Fs> {
     }
 }
 
-fn write_scalar<E: JubjubEngine, W: Write>(s: &E::Fs, writer: W) -> io::Result<()> {
+pub(crate) fn write_scalar<E: JubjubEngine, W: Write>(s: &E::Fs, writer: W) -> io::Result<()> {
     s.into_repr().write_le(writer)
 }
 
-fn h_star<E: JubjubEngine>(a: &[u8], b: &[u8]) -> E::Fs {
+pub(crate) fn h_star<E: JubjubEngine>(a: &[u8], b: &[u8]) -> E::Fs {
     hash_to_scalar::<E>(b"Zcash_RedJubjubH", a, b)
 }
 
@@ -56,6 +58,10 @@ impl Signature {
 }
 
 impl<E: JubjubEngine> PrivateKey<E> {
+    pub fn generate<R: Rng>(rng: &mut R) -> Self {
+        PrivateKey(E::Fs::rand(rng))
+    }
+
     pub fn randomize(&self, alpha: E::Fs) -> Self {
         let mut tmp = self.0;
         tmp.add_assign(&alpha);
@@ -155,14 +161,28 @@ impl<E: JubjubEngine> PublicKey<E> {
     }
 }
 
+/// A fresh private key and its public key for the generator `p_g`.
+pub fn generate_keypair<E: JubjubEngine, R: RngCore + CryptoRng + ?Sized>(
+    rng: &mut R,
+    p_g: FixedGenerators,
+    params: &E::Params,
+) -> (PrivateKey<E>, PublicKey<E>) {
+    let sk = PrivateKey::generate(&mut RngCoreAdapter(rng));
+    let pk = PublicKey::from_private(&sk, p_g, params);
+
+    (sk, pk)
+}
+
 pub struct BatchEntry<'a, E: JubjubEngine> {
     pub vk: PublicKey<E>,
     pub msg: &'a [u8],
     pub sig: Signature,
 }
 
-// TODO: #82: This is a naive implementation currently,
-// and doesn't use multiexp.
+/// Checks all the signatures at once: sum_i z_i . (-S_i . P_G + R_i + c_i . vk_i) = 0 up to the
+/// small order component, with random z_i. The generator terms are folded into a single term
+/// and the sum is computed by one multiexp. Returns false if any signature is malformed or
+/// invalid.
 pub fn batch_verify<'a, E: JubjubEngine, R: Rng>(
     rng: &mut R,
     batch: &[BatchEntry<'a, E>],
@@ -170,10 +190,12 @@ pub fn batch_verify<'a, E: JubjubEngine, R: Rng>(
     params: &E::Params,
 ) -> bool
 {
-    let mut acc = Point::<E, Unknown>::zero();
+    let mut bases = Vec::with_capacity(2 * batch.len() + 1);
+    let mut scalars = Vec::with_capacity(2 * batch.len() + 1);
+    let mut s_acc = E::Fs::zero();
 
     for entry in batch {
-        let mut r = match Point::<E, Unknown>::read(&entry.sig.rbar[..], params) {
+        let r = match Point::<E, Unknown>::read(&entry.sig.rbar[..], params) {
             Ok(r) => r,
             Err(_) => return false,
         };
@@ -187,22 +209,74 @@ pub fn batch_verify<'a, E: JubjubEngine, R: Rng>(
         let z = E::Fs::rand(rng);
 
         s.mul_assign(&z);
-        s.negate();
-
-        r = r.mul(z, params);
+        s_acc.add_assign(&s);
 
         c.mul_assign(&z);
 
-        acc = acc.add(&r, params);
-        acc = acc.add(&entry.vk.0.mul(c, params), params);
-        acc = acc.add(&params.generator(p_g).mul(s, params).into(), params);
+        bases.push(r);
+        scalars.push(z);
+        bases.push(entry.vk.0.clone());
+        scalars.push(c);
     }
 
-    acc = acc.mul_by_cofactor(params).into();
+    s_acc.negate();
+    bases.push(params.generator(p_g).clone().into());
+    scalars.push(s_acc);
+
+    let acc: Point<E, Unknown> = multiexp(&bases, &scalars, params).mul_by_cofactor(params).into();
 
     acc.eq(&Point::zero())
 }
 
+#[cfg(feature = "serde-compressed")]
+mod serialization {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use jubjub::{JubjubEngine, JubjubEngineWithParams};
+    use jubjub::serialization::{point, scalar};
+
+    use super::{PrivateKey, PublicKey, Signature};
+
+    impl<E: JubjubEngine> Serialize for PrivateKey<E> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            scalar::serialize(&self.0, serializer)
+        }
+    }
+
+    impl<'de, E: JubjubEngine> Deserialize<'de> for PrivateKey<E> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            Ok(PrivateKey(scalar::deserialize(deserializer)?))
+        }
+    }
+
+    impl<E: JubjubEngine> Serialize for PublicKey<E> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            point::serialize(&self.0, serializer)
+        }
+    }
+
+    impl<'de, E: JubjubEngineWithParams> Deserialize<'de> for PublicKey<E> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            Ok(PublicKey(point::deserialize(deserializer)?))
+        }
+    }
+
+    // signatures are kept as bytes, they are checked by `verify`
+    impl Serialize for Signature {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            (self.rbar, self.sbar).serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Signature {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let (rbar, sbar) = Deserialize::deserialize(deserializer)?;
+
+            Ok(Signature { rbar, sbar })
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use bellman::pairing::bls12_381::Bls12;
@@ -382,6 +456,36 @@ mod baby_tests {
         assert!(!batch_verify(rng, &batch, p_g, params));
     }
 
+    #[test]
+    fn test_batch_verify_randomized_keys() {
+        let rng = &mut thread_rng();
+        let params = &AltJubjubBn256::new();
+        let p_g = FixedGenerators::SpendingKeyGenerator;
+
+        let messages: Vec<Vec<u8>> = (0..5).map(|i| format!("message {}", i).into_bytes()).collect();
+        let mut batch = vec![];
+        for msg in messages.iter() {
+            let sk = PrivateKey::<Bn256>::generate(rng);
+            let vk = PublicKey::from_private(&sk, p_g, params);
+            let alpha: Fs = rng.gen();
+            let sig = sk.randomize(alpha).sign(msg, rng, p_g, params);
+            batch.push(BatchEntry { vk: vk.randomize(alpha, p_g, params), msg: &msg[..], sig });
+        }
+
+        assert!(batch_verify(rng, &batch, p_g, params));
+        assert!(batch_verify(rng, &batch[..0], p_g, params));
+
+        batch[3].msg = &messages[2][..];
+        assert!(!batch_verify(rng, &batch, p_g, params));
+
+        batch[3].msg = &messages[3][..];
+        let mut sig_bytes = [0u8; 64];
+        batch[1].sig.write(&mut sig_bytes[..]).unwrap();
+        sig_bytes[40] ^= 1;
+        batch[1].sig = Signature::read(&sig_bytes[..]).unwrap();
+        assert!(!batch_verify(rng, &batch, p_g, params));
+    }
+
     #[test]
     fn cofactor_check() {
         let rng = &mut thread_rng();
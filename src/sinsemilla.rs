@@ -0,0 +1,169 @@
+//! Sinsemilla hash over twisted Edwards curves.
+//!
+//! Message is split into K bit chunks m_1, ..., m_n and hashed as
+//!
+//!   Acc_0 = Q(domain), Acc_i = 2 * Acc_{i-1} + S(m_i)
+//!
+//! where Q(domain) and S(0), ..., S(2^K - 1) are independent generators of the prime order
+//! subgroup. `hash` outputs the x coordinate of the resulting point, which is injective
+//! on the prime order subgroup since (x, -y) is a torsioned point. In a circuit S(m_i) is
+//! a single lookup, so every chunk costs one doubling and one addition instead of
+//! K conditional additions of Pedersen hash.
+//!
+//! Unlike the Pallas version complete Edwards formulas are used, so there are no
+//! exceptional cases and no limit on the message length. Messages are zero padded
+//! to a multiple of K bits, so messages of different lengths must use different domains.
+
+use bellman::pairing::ff::{PrimeField, PrimeFieldRepr};
+use bellman::pairing::Engine;
+use blake2_rfc::blake2s::Blake2s;
+use byteorder::{LittleEndian, WriteBytesExt};
+
+use generic_twisted_edwards::{TwistedEdwardsCurveImplementor, TwistedEdwardsCurveParams, TwistedEdwardsPoint};
+
+/// Number of message bits absorbed per step.
+pub const SINSEMILLA_K: usize = 10;
+
+pub const SINSEMILLA_Q_PERSONALIZATION: &[u8; 8] = b"SinsemQ_";
+pub const SINSEMILLA_S_PERSONALIZATION: &[u8; 8] = b"SinsemS_";
+
+/// Maps (personalization, message) to a point of the prime order subgroup by try-and-increment.
+pub fn hash_to_subgroup_point<E: Engine, C: TwistedEdwardsCurveParams<E>>(
+    curve: &TwistedEdwardsCurveImplementor<E, C>,
+    personalization: &[u8; 8],
+    message: &[u8]
+) -> TwistedEdwardsPoint<E> {
+    let mut counter = 0u32;
+    loop {
+        let mut h = Blake2s::with_params(32, &[], &[], personalization);
+        h.update(message);
+        let mut counter_bytes = [0u8; 4];
+        (&mut counter_bytes[..]).write_u32::<LittleEndian>(counter).unwrap();
+        h.update(&counter_bytes[..]);
+        let digest = h.finalize();
+
+        let sign = digest.as_ref()[31] >> 7 == 1;
+        let mut repr = <E::Fr as PrimeField>::Repr::default();
+        repr.read_le(digest.as_ref()).unwrap();
+        // drop the bits above the field size, so a candidate is valid with good probability
+        let num_words = repr.as_ref().len();
+        let excess_bits = num_words as u32 * 64 - E::Fr::NUM_BITS;
+        repr.as_mut()[num_words - 1] &= u64::max_value() >> excess_bits;
+
+        counter += 1;
+
+        let y = match E::Fr::from_repr(repr) {
+            Ok(y) => y,
+            Err(_) => continue,
+        };
+
+        if let Some(p) = curve.get_for_y(y, sign) {
+            let mut p = p;
+            for _ in 0..curve.get_params().log_2_cofactor() {
+                p = curve.double(&p);
+            }
+
+            if p != TwistedEdwardsPoint::identity() {
+                return p;
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SinsemillaParams<E: Engine> {
+    /// Initial point for this domain.
+    pub q: TwistedEdwardsPoint<E>,
+    /// Chunk generators S(0), ..., S(2^K - 1) in affine coordinates, shared by all the domains.
+    pub s: Vec<(E::Fr, E::Fr)>,
+}
+
+impl<E: Engine> SinsemillaParams<E> {
+    pub fn new<C: TwistedEdwardsCurveParams<E>>(
+        curve: &TwistedEdwardsCurveImplementor<E, C>,
+        domain: &[u8]
+    ) -> Self {
+        let q = hash_to_subgroup_point(curve, SINSEMILLA_Q_PERSONALIZATION, domain);
+
+        let mut s = Vec::with_capacity(1 << SINSEMILLA_K);
+        for j in 0..(1u32 << SINSEMILLA_K) {
+            let mut index = [0u8; 4];
+            (&mut index[..]).write_u32::<LittleEndian>(j).unwrap();
+            let point = hash_to_subgroup_point(curve, SINSEMILLA_S_PERSONALIZATION, &index[..]);
+            s.push(point.into_xy());
+        }
+
+        Self {
+            q,
+            s,
+        }
+    }
+}
+
+/// Splits the message into chunks of K bits (zero padded), bits inside of the chunk are little endian.
+pub fn message_into_chunks(message: &[bool]) -> Vec<usize> {
+    message.chunks(SINSEMILLA_K).map(|chunk| {
+        chunk.iter().enumerate().fold(0usize, |acc, (i, bit)| acc | ((*bit as usize) << i))
+    }).collect()
+}
+
+pub fn hash_to_point<E: Engine, C: TwistedEdwardsCurveParams<E>>(
+    curve: &TwistedEdwardsCurveImplementor<E, C>,
+    params: &SinsemillaParams<E>,
+    message: &[bool]
+) -> TwistedEdwardsPoint<E> {
+    let mut acc = params.q;
+    for chunk in message_into_chunks(message).into_iter() {
+        let (x, y) = params.s[chunk];
+        let s = TwistedEdwardsPoint::from_xy(x, y);
+        acc = curve.double(&acc);
+        acc = curve.add(&acc, &s);
+    }
+
+    acc
+}
+
+pub fn hash<E: Engine, C: TwistedEdwardsCurveParams<E>>(
+    curve: &TwistedEdwardsCurveImplementor<E, C>,
+    params: &SinsemillaParams<E>,
+    message: &[bool]
+) -> E::Fr {
+    let (x, _) = hash_to_point(curve, params, message).into_xy();
+
+    x
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{Rng, XorShiftRng, SeedableRng};
+    use generic_twisted_edwards::bn256::AltBabyJubjubBn256;
+
+    #[test]
+    fn test_sinsemilla_generators_are_in_subgroup() {
+        let curve = AltBabyJubjubBn256::get_implementor();
+        let params = SinsemillaParams::new(&curve, b"test_domain");
+
+        assert!(curve.is_in_main_subgroup(&params.q));
+        for &(x, y) in params.s.iter().take(16) {
+            assert!(curve.is_in_main_subgroup(&TwistedEdwardsPoint::from_xy(x, y)));
+        }
+    }
+
+    #[test]
+    fn test_sinsemilla_domain_separation() {
+        let mut rng = XorShiftRng::from_seed([0x3dbe6259, 0x8d313d76, 0x3237db17, 0xe5bc0654]);
+        let curve = AltBabyJubjubBn256::get_implementor();
+        let params_a = SinsemillaParams::new(&curve, b"domain_a");
+        let params_b = SinsemillaParams::new(&curve, b"domain_b");
+
+        let message: Vec<bool> = (0..100).map(|_| rng.gen()).collect();
+        let mut other_message = message.clone();
+        other_message[57] = !other_message[57];
+
+        let h = hash(&curve, &params_a, &message);
+        assert_eq!(h, hash(&curve, &params_a, &message));
+        assert!(h != hash(&curve, &params_b, &message));
+        assert!(h != hash(&curve, &params_a, &other_message));
+    }
+}
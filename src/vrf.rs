@@ -0,0 +1,198 @@
+//! ECVRF-style verifiable random function over Jubjub, see RFC 9381 for the general construction.
+//!
+//! Hashing and challenges use Poseidon over the scalar field of the engine, so everything can be
+//! verified cheaply in circuit by `plonk::circuit::vrf`. Hash to curve is try-and-increment:
+//! y_k = Poseidon(seed, k) for the first k such that y_k is the y coordinate of a point, x is taken
+//! even and the point is multiplied by the cofactor.
+
+use bellman::pairing::ff::{Field, PrimeField, PrimeFieldRepr};
+
+use jubjub::{FixedGenerators, JubjubEngine, JubjubParams, PrimeOrder, Unknown, edwards::Point};
+use poseidon::{PoseidonEngine, poseidon_hash};
+use util::hash_to_scalar;
+
+pub const VRF_HASH_TO_CURVE_ATTEMPTS: usize = 32;
+/// Bit length of the DLEQ challenge.
+pub const VRF_CHALLENGE_BITS: usize = 128;
+
+const VRF_NONCE_PERSONALIZATION: &[u8; 16] = b"JubjubVRF_Nonce_";
+
+pub struct VrfSecretKey<E: JubjubEngine>(pub E::Fs);
+
+#[derive(Clone)]
+pub struct VrfPublicKey<E: JubjubEngine>(pub Point<E, Unknown>);
+
+/// Gamma = sk . H and the proof (c, s) that log_G(pk) = log_H(Gamma).
+#[derive(Clone)]
+pub struct VrfProof<E: JubjubEngine> {
+    pub gamma: Point<E, Unknown>,
+    pub c: E::Fs,
+    pub s: E::Fs,
+}
+
+fn u64_to_fr<E: JubjubEngine>(value: u64) -> E::Fr {
+    let mut repr = <E::Fr as PrimeField>::Repr::default();
+    repr.as_mut()[0] = value;
+
+    E::Fr::from_repr(repr).unwrap()
+}
+
+/// H(pk, alpha), None if none of the attempts gives a point or the point is of small order.
+pub fn hash_to_curve<E: JubjubEngine + PoseidonEngine>(
+    pk: &VrfPublicKey<E>,
+    alpha: &[E::Fr],
+    jubjub_params: &<E as JubjubEngine>::Params,
+    poseidon_params: &<E as PoseidonEngine>::Params
+) -> Option<Point<E, PrimeOrder>> {
+    let (pk_x, pk_y) = pk.0.into_xy();
+    let mut input = vec![pk_x, pk_y];
+    input.extend_from_slice(alpha);
+    let seed = poseidon_hash::<E>(poseidon_params, &input)[0];
+
+    for k in 0..VRF_HASH_TO_CURVE_ATTEMPTS {
+        let y = poseidon_hash::<E>(poseidon_params, &[seed, u64_to_fr::<E>(k as u64)])[0];
+        if let Some(p) = Point::<E, Unknown>::get_for_y(y, false, jubjub_params) {
+            let h = p.mul_by_cofactor(jubjub_params);
+            if h == Point::zero() {
+                return None;
+            }
+            return Some(h);
+        }
+    }
+
+    None
+}
+
+/// Lowest `VRF_CHALLENGE_BITS` bits of Poseidon(H, pk, Gamma, U, V).
+pub fn challenge<E: JubjubEngine + PoseidonEngine>(
+    points: &[&Point<E, Unknown>; 5],
+    poseidon_params: &<E as PoseidonEngine>::Params
+) -> E::Fs {
+    let mut input = Vec::with_capacity(10);
+    for p in points.iter() {
+        let (x, y) = p.into_xy();
+        input.push(x);
+        input.push(y);
+    }
+    let hash = poseidon_hash::<E>(poseidon_params, &input)[0].into_repr();
+
+    let mut repr = <E::Fs as PrimeField>::Repr::default();
+    for i in 0..(VRF_CHALLENGE_BITS / 64) {
+        repr.as_mut()[i] = hash.as_ref()[i];
+    }
+
+    E::Fs::from_repr(repr).unwrap()
+}
+
+/// beta = Poseidon(8 . Gamma)
+pub fn proof_to_hash<E: JubjubEngine + PoseidonEngine>(
+    proof: &VrfProof<E>,
+    jubjub_params: &<E as JubjubEngine>::Params,
+    poseidon_params: &<E as PoseidonEngine>::Params
+) -> E::Fr {
+    let (x, y) = proof.gamma.mul_by_cofactor(jubjub_params).into_xy();
+
+    poseidon_hash::<E>(poseidon_params, &[x, y])[0]
+}
+
+impl<E: JubjubEngine + PoseidonEngine> VrfSecretKey<E> {
+    pub fn public_key(&self, p_g: FixedGenerators, jubjub_params: &<E as JubjubEngine>::Params) -> VrfPublicKey<E> {
+        VrfPublicKey(jubjub_params.generator(p_g).mul(self.0, jubjub_params).into())
+    }
+
+    /// Returns the output beta and the proof, None if `alpha` can't be hashed to curve.
+    /// The nonce is derived deterministically from the secret key and H.
+    pub fn prove(
+        &self,
+        alpha: &[E::Fr],
+        p_g: FixedGenerators,
+        jubjub_params: &<E as JubjubEngine>::Params,
+        poseidon_params: &<E as PoseidonEngine>::Params
+    ) -> Option<(E::Fr, VrfProof<E>)> {
+        let pk = self.public_key(p_g, jubjub_params);
+        let h: Point<E, Unknown> = hash_to_curve(&pk, alpha, jubjub_params, poseidon_params)?.into();
+        let gamma = h.mul(self.0, jubjub_params);
+
+        let mut sk_bytes = [0u8; 32];
+        self.0.into_repr().write_le(&mut sk_bytes[..]).unwrap();
+        let mut h_bytes = [0u8; 32];
+        h.write(&mut h_bytes[..]).unwrap();
+        let k = hash_to_scalar::<E>(&VRF_NONCE_PERSONALIZATION[..], &sk_bytes, &h_bytes);
+
+        let u: Point<E, Unknown> = jubjub_params.generator(p_g).mul(k, jubjub_params).into();
+        let v = h.mul(k, jubjub_params);
+        let c = challenge::<E>(&[&h, &pk.0, &gamma, &u, &v], poseidon_params);
+
+        // s = k - c * sk
+        let mut s = c;
+        s.mul_assign(&self.0);
+        s.negate();
+        s.add_assign(&k);
+
+        let proof = VrfProof { gamma, c, s };
+        let beta = proof_to_hash(&proof, jubjub_params, poseidon_params);
+
+        Some((beta, proof))
+    }
+}
+
+impl<E: JubjubEngine + PoseidonEngine> VrfPublicKey<E> {
+    /// Checks U = s . G + c . pk, V = s . H + c . Gamma and c = challenge(H, pk, Gamma, U, V).
+    /// Returns the output beta if the proof is valid.
+    pub fn verify(
+        &self,
+        alpha: &[E::Fr],
+        proof: &VrfProof<E>,
+        p_g: FixedGenerators,
+        jubjub_params: &<E as JubjubEngine>::Params,
+        poseidon_params: &<E as PoseidonEngine>::Params
+    ) -> Option<E::Fr> {
+        let h: Point<E, Unknown> = hash_to_curve(self, alpha, jubjub_params, poseidon_params)?.into();
+
+        let s_g: Point<E, Unknown> = jubjub_params.generator(p_g).mul(proof.s, jubjub_params).into();
+        let u = s_g.add(&self.0.mul(proof.c, jubjub_params), jubjub_params);
+        let v = h.mul(proof.s, jubjub_params).add(&proof.gamma.mul(proof.c, jubjub_params), jubjub_params);
+
+        if challenge::<E>(&[&h, &self.0, &proof.gamma, &u, &v], poseidon_params) != proof.c {
+            return None;
+        }
+
+        Some(proof_to_hash(proof, jubjub_params, poseidon_params))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bellman::pairing::bn256::{Bn256, Fr};
+    use rand::{Rng, thread_rng};
+
+    use alt_babyjubjub::AltJubjubBn256;
+    use poseidon::Bn256PoseidonParams;
+
+    use super::*;
+
+    #[test]
+    fn test_vrf_prove_verify() {
+        let rng = &mut thread_rng();
+        let jubjub_params = &AltJubjubBn256::new();
+        let poseidon_params = &Bn256PoseidonParams::new_checked_2_into_1();
+        let p_g = FixedGenerators::SpendingKeyGenerator;
+
+        let sk = VrfSecretKey::<Bn256>(rng.gen());
+        let pk = sk.public_key(p_g, jubjub_params);
+        let alpha: Vec<Fr> = (0..3).map(|_| rng.gen()).collect();
+
+        let (beta, proof) = sk.prove(&alpha, p_g, jubjub_params, poseidon_params).unwrap();
+        assert_eq!(pk.verify(&alpha, &proof, p_g, jubjub_params, poseidon_params), Some(beta));
+
+        // the output is unique for the key and input
+        let (beta_again, _) = sk.prove(&alpha, p_g, jubjub_params, poseidon_params).unwrap();
+        assert_eq!(beta, beta_again);
+
+        let other_alpha: Vec<Fr> = (0..3).map(|_| rng.gen()).collect();
+        assert_eq!(pk.verify(&other_alpha, &proof, p_g, jubjub_params, poseidon_params), None);
+
+        let other_pk = VrfSecretKey::<Bn256>(rng.gen()).public_key(p_g, jubjub_params);
+        assert_eq!(other_pk.verify(&alpha, &proof, p_g, jubjub_params, poseidon_params), None);
+    }
+}
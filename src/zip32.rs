@@ -0,0 +1,578 @@
+//! Hierarchical deterministic derivation of Sapling keys following ZIP 32, generic over the
+//! Jubjub engine. With `AltJubjubBn256` the keys are the ones the circuits of this crate
+//! consume, the derivation itself is the same as for Zcash.
+//!
+//! A master key is derived from a seed, children are derived by index. Hardened children
+//! (index >= 2^31) need the extended spending key, non-hardened children of the full viewing
+//! key are the viewing keys of the non-hardened children of the spending key.
+//!
+//! Diversifiers are derived from the diversifier key dk with PRF^expand instead of the FF1-AES
+//! permutation of ZIP 32, so addresses differ from the ones of Zcash wallets for the same seed.
+
+use bellman::pairing::ff::{Field, PrimeField, PrimeFieldRepr};
+
+use blake2_rfc::blake2b::Blake2b;
+use byteorder::{ByteOrder, LittleEndian};
+
+use jubjub::{FixedGenerators, JubjubEngine, JubjubParams, ToUniform};
+use primitives::{Diversifier, PaymentAddress, ProofGenerationKey, ViewingKey};
+
+pub const ZIP32_SAPLING_MASTER_PERSONALIZATION: &[u8; 16] = b"ZcashIP32Sapling";
+pub const ZIP32_SAPLING_FVFP_PERSONALIZATION: &[u8; 16] = b"ZcashSaplingFVFP";
+pub const PRF_EXPAND_PERSONALIZATION: &[u8; 16] = b"Zcash_ExpandSeed";
+/// Domain separator of PRF^expand for diversifiers, the tags below are taken by ZIP 32.
+pub const DIVERSIFIER_EXPAND_TAG: u8 = 0xd0;
+
+/// PRF^expand(sk, t) = BLAKE2b-512(PRF_EXPAND_PERSONALIZATION, sk || t)
+pub fn prf_expand(sk: &[u8], t: &[&[u8]]) -> [u8; 64] {
+    let mut h = Blake2b::with_params(64, &[], &[], PRF_EXPAND_PERSONALIZATION);
+    h.update(sk);
+    for part in t.iter() {
+        h.update(part);
+    }
+
+    let mut result = [0u8; 64];
+    result.copy_from_slice(h.finalize().as_ref());
+
+    result
+}
+
+fn to_scalar<E: JubjubEngine>(sk: &[u8], t: u8) -> E::Fs {
+    E::Fs::to_uniform(&prf_expand(sk, &[&[t]])[..])
+}
+
+fn truncate_32(bytes: &[u8; 64]) -> [u8; 32] {
+    let mut result = [0u8; 32];
+    result.copy_from_slice(&bytes[..32]);
+
+    result
+}
+
+fn split(bytes: &[u8; 64]) -> ([u8; 32], [u8; 32]) {
+    let mut left = [0u8; 32];
+    let mut right = [0u8; 32];
+    left.copy_from_slice(&bytes[..32]);
+    right.copy_from_slice(&bytes[32..]);
+
+    (left, right)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ChildIndex {
+    NonHardened(u32),
+    Hardened(u32),
+}
+
+impl ChildIndex {
+    /// Index i < 2^31 is non-hardened, i >= 2^31 is hardened child i - 2^31.
+    pub fn from_index(i: u32) -> Self {
+        if i & (1 << 31) == 0 {
+            ChildIndex::NonHardened(i)
+        } else {
+            ChildIndex::Hardened(i & !(1 << 31))
+        }
+    }
+
+    /// The index as it's encoded, hardened indices have the top bit set.
+    pub fn value(&self) -> u32 {
+        match *self {
+            ChildIndex::NonHardened(i) => {
+                assert!(i < (1 << 31));
+                i
+            },
+            ChildIndex::Hardened(i) => {
+                assert!(i < (1 << 31));
+                i | (1 << 31)
+            },
+        }
+    }
+
+    fn master() -> Self {
+        ChildIndex::NonHardened(0)
+    }
+}
+
+/// 88 bit little endian index of a diversifier.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct DiversifierIndex(pub [u8; 11]);
+
+impl DiversifierIndex {
+    pub fn new() -> Self {
+        DiversifierIndex([0u8; 11])
+    }
+
+    /// Returns false on overflow.
+    pub fn increment(&mut self) -> bool {
+        for byte in self.0.iter_mut() {
+            *byte = byte.wrapping_add(1);
+            if *byte != 0 {
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+/// The diversifier at `index`, first 11 bytes of PRF^expand(dk, DIVERSIFIER_EXPAND_TAG || index).
+/// It's valid or not, see `Diversifier::is_valid`.
+pub fn diversifier(dk: &[u8; 32], index: DiversifierIndex) -> Diversifier {
+    let mut d = [0u8; 11];
+    d.copy_from_slice(&prf_expand(dk, &[&[DIVERSIFIER_EXPAND_TAG], &index.0])[..11]);
+
+    Diversifier(d)
+}
+
+/// The first four bytes of the fingerprint of the parent full viewing key, zero for a master key.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FvkTag(pub [u8; 4]);
+
+#[derive(Clone)]
+pub struct ExpandedSpendingKey<E: JubjubEngine> {
+    pub ask: E::Fs,
+    pub nsk: E::Fs,
+    pub ovk: [u8; 32],
+}
+
+impl<E: JubjubEngine> ExpandedSpendingKey<E> {
+    pub fn from_spending_key(sk: &[u8]) -> Self {
+        ExpandedSpendingKey {
+            ask: to_scalar::<E>(sk, 0x00),
+            nsk: to_scalar::<E>(sk, 0x01),
+            ovk: truncate_32(&prf_expand(sk, &[&[0x02]])),
+        }
+    }
+
+    pub fn proof_generation_key(&self, params: &E::Params) -> ProofGenerationKey<E> {
+        ProofGenerationKey {
+            ak: params.generator(FixedGenerators::SpendingKeyGenerator).mul(self.ask, params),
+            nsk: self.nsk,
+        }
+    }
+
+    fn write(&self, bytes: &mut Vec<u8>) {
+        self.ask.into_repr().write_le(&mut *bytes).unwrap();
+        self.nsk.into_repr().write_le(&mut *bytes).unwrap();
+        bytes.extend_from_slice(&self.ovk);
+    }
+}
+
+#[derive(Clone)]
+pub struct FullViewingKey<E: JubjubEngine> {
+    pub vk: ViewingKey<E>,
+    pub ovk: [u8; 32],
+}
+
+impl<E: JubjubEngine> FullViewingKey<E> {
+    pub fn from_expanded_spending_key(expsk: &ExpandedSpendingKey<E>, params: &E::Params) -> Self {
+        FullViewingKey {
+            vk: expsk.proof_generation_key(params).into_viewing_key(params),
+            ovk: expsk.ovk,
+        }
+    }
+
+    fn write(&self, bytes: &mut Vec<u8>) {
+        self.vk.ak.write(&mut *bytes).unwrap();
+        self.vk.nk.write(&mut *bytes).unwrap();
+        bytes.extend_from_slice(&self.ovk);
+    }
+
+    /// BLAKE2b-256(ZIP32_SAPLING_FVFP_PERSONALIZATION, ak || nk || ovk)
+    pub fn fingerprint(&self) -> [u8; 32] {
+        let mut bytes = vec![];
+        self.write(&mut bytes);
+
+        let mut h = Blake2b::with_params(32, &[], &[], ZIP32_SAPLING_FVFP_PERSONALIZATION);
+        h.update(&bytes);
+
+        let mut result = [0u8; 32];
+        result.copy_from_slice(h.finalize().as_ref());
+
+        result
+    }
+
+    fn tag(&self) -> FvkTag {
+        let mut tag = [0u8; 4];
+        tag.copy_from_slice(&self.fingerprint()[..4]);
+
+        FvkTag(tag)
+    }
+}
+
+#[derive(Clone)]
+pub struct ExtendedSpendingKey<E: JubjubEngine> {
+    pub depth: u8,
+    pub parent_fvk_tag: FvkTag,
+    pub child_index: ChildIndex,
+    pub chain_code: [u8; 32],
+    pub expsk: ExpandedSpendingKey<E>,
+    /// Diversifier key.
+    pub dk: [u8; 32],
+}
+
+impl<E: JubjubEngine> ExtendedSpendingKey<E> {
+    pub fn master(seed: &[u8]) -> Self {
+        let mut h = Blake2b::with_params(64, &[], &[], ZIP32_SAPLING_MASTER_PERSONALIZATION);
+        h.update(seed);
+        let mut i = [0u8; 64];
+        i.copy_from_slice(h.finalize().as_ref());
+        let (sk, chain_code) = split(&i);
+
+        ExtendedSpendingKey {
+            depth: 0,
+            parent_fvk_tag: FvkTag([0u8; 4]),
+            child_index: ChildIndex::master(),
+            chain_code,
+            expsk: ExpandedSpendingKey::from_spending_key(&sk),
+            dk: truncate_32(&prf_expand(&sk, &[&[0x10]])),
+        }
+    }
+
+    pub fn derive_child(&self, index: ChildIndex, params: &E::Params) -> Self {
+        let fvk = FullViewingKey::from_expanded_spending_key(&self.expsk, params);
+        let mut index_bytes = [0u8; 4];
+        LittleEndian::write_u32(&mut index_bytes, index.value());
+
+        let i = match index {
+            ChildIndex::Hardened(_) => {
+                let mut key = vec![];
+                self.expsk.write(&mut key);
+                key.extend_from_slice(&self.dk);
+                prf_expand(&self.chain_code, &[&[0x11], &key, &index_bytes])
+            },
+            ChildIndex::NonHardened(_) => {
+                let mut key = vec![];
+                fvk.write(&mut key);
+                key.extend_from_slice(&self.dk);
+                prf_expand(&self.chain_code, &[&[0x12], &key, &index_bytes])
+            },
+        };
+        let (i_l, chain_code) = split(&i);
+
+        let mut ask = to_scalar::<E>(&i_l, 0x13);
+        ask.add_assign(&self.expsk.ask);
+        let mut nsk = to_scalar::<E>(&i_l, 0x14);
+        nsk.add_assign(&self.expsk.nsk);
+
+        ExtendedSpendingKey {
+            depth: self.depth + 1,
+            parent_fvk_tag: fvk.tag(),
+            child_index: index,
+            chain_code,
+            expsk: ExpandedSpendingKey {
+                ask,
+                nsk,
+                ovk: truncate_32(&prf_expand(&i_l, &[&[0x15], &self.expsk.ovk])),
+            },
+            dk: truncate_32(&prf_expand(&i_l, &[&[0x16], &self.dk])),
+        }
+    }
+
+    /// Derives along `path` starting from `self`, usually the master key.
+    pub fn derive_path(&self, path: &[ChildIndex], params: &E::Params) -> Self {
+        let mut key = self.clone();
+        for index in path.iter() {
+            key = key.derive_child(*index, params);
+        }
+
+        key
+    }
+
+    pub fn default_address(&self, params: &E::Params) -> (DiversifierIndex, PaymentAddress<E>) {
+        self.to_extended_full_viewing_key(params).default_address(params)
+    }
+
+    pub fn to_extended_full_viewing_key(&self, params: &E::Params) -> ExtendedFullViewingKey<E> {
+        ExtendedFullViewingKey {
+            depth: self.depth,
+            parent_fvk_tag: self.parent_fvk_tag,
+            child_index: self.child_index,
+            chain_code: self.chain_code,
+            fvk: FullViewingKey::from_expanded_spending_key(&self.expsk, params),
+            dk: self.dk,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ExtendedFullViewingKey<E: JubjubEngine> {
+    pub depth: u8,
+    pub parent_fvk_tag: FvkTag,
+    pub child_index: ChildIndex,
+    pub chain_code: [u8; 32],
+    pub fvk: FullViewingKey<E>,
+    /// Diversifier key.
+    pub dk: [u8; 32],
+}
+
+impl<E: JubjubEngine> ExtendedFullViewingKey<E> {
+    /// The address with the diversifier at `index`, None if the diversifier is not valid.
+    pub fn address(&self, index: DiversifierIndex, params: &E::Params) -> Option<PaymentAddress<E>> {
+        self.fvk.vk.into_payment_address(diversifier(&self.dk, index), params)
+    }
+
+    /// The first address with a valid diversifier at `index` or above.
+    pub fn find_address(&self, index: DiversifierIndex, params: &E::Params) -> Option<(DiversifierIndex, PaymentAddress<E>)> {
+        let mut index = index;
+        loop {
+            if let Some(address) = self.address(index, params) {
+                return Some((index, address));
+            }
+            if !index.increment() {
+                return None;
+            }
+        }
+    }
+
+    /// The first address, every other diversifier is valid so the search stops quickly.
+    pub fn default_address(&self, params: &E::Params) -> (DiversifierIndex, PaymentAddress<E>) {
+        self.find_address(DiversifierIndex::new(), params).expect("a valid diversifier exists")
+    }
+
+    /// The viewing key of the non-hardened child `index`, None for a hardened index.
+    pub fn derive_child(&self, index: ChildIndex, params: &E::Params) -> Option<Self> {
+        if let ChildIndex::Hardened(_) = index {
+            return None;
+        }
+
+        let mut index_bytes = [0u8; 4];
+        LittleEndian::write_u32(&mut index_bytes, index.value());
+        let mut key = vec![];
+        self.fvk.write(&mut key);
+        key.extend_from_slice(&self.dk);
+        let i = prf_expand(&self.chain_code, &[&[0x12], &key, &index_bytes]);
+        let (i_l, chain_code) = split(&i);
+
+        let ak = params.generator(FixedGenerators::SpendingKeyGenerator)
+            .mul(to_scalar::<E>(&i_l, 0x13), params)
+            .add(&self.fvk.vk.ak, params);
+        let nk = params.generator(FixedGenerators::ProofGenerationKey)
+            .mul(to_scalar::<E>(&i_l, 0x14), params)
+            .add(&self.fvk.vk.nk, params);
+
+        Some(ExtendedFullViewingKey {
+            depth: self.depth + 1,
+            parent_fvk_tag: self.fvk.tag(),
+            child_index: index,
+            chain_code,
+            fvk: FullViewingKey {
+                vk: ViewingKey { ak, nk },
+                ovk: truncate_32(&prf_expand(&i_l, &[&[0x15], &self.fvk.ovk])),
+            },
+            dk: truncate_32(&prf_expand(&i_l, &[&[0x16], &self.dk])),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alt_babyjubjub::AltJubjubBn256;
+    use bellman::pairing::bls12_381::Bls12;
+    use bellman::pairing::bn256::Bn256;
+    use jubjub::JubjubBls12;
+
+    fn assert_same_fvk(a: &ExtendedFullViewingKey<Bn256>, b: &ExtendedFullViewingKey<Bn256>) {
+        assert_eq!(a.depth, b.depth);
+        assert_eq!(a.parent_fvk_tag, b.parent_fvk_tag);
+        assert_eq!(a.child_index, b.child_index);
+        assert_eq!(a.chain_code, b.chain_code);
+        assert_eq!(a.dk, b.dk);
+        assert_eq!(a.fvk.fingerprint(), b.fvk.fingerprint());
+    }
+
+    #[test]
+    fn test_child_index() {
+        assert_eq!(ChildIndex::from_index(5), ChildIndex::NonHardened(5));
+        assert_eq!(ChildIndex::from_index((1 << 31) + 5), ChildIndex::Hardened(5));
+        assert_eq!(ChildIndex::Hardened(5).value(), (1 << 31) + 5);
+    }
+
+    #[test]
+    fn test_non_hardened_derivation_commutes() {
+        let params = AltJubjubBn256::new();
+        let master = ExtendedSpendingKey::<Bn256>::master(&[7u8; 32]);
+
+        let xsk = master.derive_path(&[ChildIndex::Hardened(32), ChildIndex::NonHardened(1)], &params);
+        let xfvk = master.derive_child(ChildIndex::Hardened(32), &params)
+            .to_extended_full_viewing_key(&params)
+            .derive_child(ChildIndex::NonHardened(1), &params)
+            .unwrap();
+        assert_same_fvk(&xsk.to_extended_full_viewing_key(&params), &xfvk);
+        assert_eq!(xsk.depth, 2);
+
+        assert!(xfvk.derive_child(ChildIndex::Hardened(0), &params).is_none());
+    }
+
+    #[test]
+    fn test_diversifier_index() {
+        let mut index = DiversifierIndex::new();
+        assert!(index.increment());
+        assert_eq!(index.0[0], 1);
+
+        let mut index = DiversifierIndex([0xff; 11]);
+        assert!(!index.increment());
+    }
+
+    #[test]
+    fn test_addresses() {
+        let params = AltJubjubBn256::new();
+        let xsk = ExtendedSpendingKey::<Bn256>::master(&[7u8; 32]).derive_child(ChildIndex::Hardened(0), &params);
+        let xfvk = xsk.to_extended_full_viewing_key(&params);
+
+        let (index, address) = xsk.default_address(&params);
+        assert_eq!(xfvk.address(index, &params).unwrap().to_bytes()[..], address.to_bytes()[..]);
+        assert!(address.diversifier.is_valid::<Bn256>(&params));
+        assert!(address.g_d(&params).unwrap().mul(xfvk.fvk.vk.ivk(), &params) == address.pk_d);
+
+        let decoded = PaymentAddress::<Bn256>::from_bytes(&address.to_bytes(), &params).unwrap();
+        assert_eq!(decoded.diversifier.0, address.diversifier.0);
+        assert!(decoded.pk_d == address.pk_d);
+
+        // the next addresses differ
+        let mut next = index;
+        next.increment();
+        let (next, other) = xfvk.find_address(next, &params).unwrap();
+        assert!(next != index);
+        assert!(other.diversifier.0 != address.diversifier.0);
+        assert!(other.pk_d != address.pk_d);
+    }
+
+    #[test]
+    fn test_hardened_derivation() {
+        let params = AltJubjubBn256::new();
+        let master = ExtendedSpendingKey::<Bn256>::master(&[7u8; 32]);
+        assert_eq!(master.parent_fvk_tag, FvkTag([0u8; 4]));
+
+        let a = master.derive_child(ChildIndex::Hardened(0), &params);
+        let b = master.derive_child(ChildIndex::Hardened(1), &params);
+        let again = master.derive_child(ChildIndex::Hardened(0), &params);
+        assert_eq!(a.chain_code, again.chain_code);
+        assert!(a.chain_code != b.chain_code);
+        assert!(a.expsk.ask != b.expsk.ask);
+
+        // a hardened child differs from the non-hardened one of the same number
+        let c = master.derive_child(ChildIndex::NonHardened(0), &params);
+        assert!(a.chain_code != c.chain_code);
+
+        let master_fvk = master.to_extended_full_viewing_key(&params);
+        assert_eq!(a.parent_fvk_tag.0[..], master_fvk.fvk.fingerprint()[..4]);
+    }
+
+    struct TestVector {
+        ask: Option<&'static str>,
+        nsk: Option<&'static str>,
+        ovk: &'static str,
+        dk: &'static str,
+        c: &'static str,
+        ak: &'static str,
+        nk: &'static str,
+        fp: &'static str,
+        parent_fvk_tag: &'static str,
+        depth: u8,
+        child_index: u32,
+    }
+
+    fn check_xfvk(xfvk: &ExtendedFullViewingKey<Bls12>, tv: &TestVector) {
+        let mut ak = vec![];
+        xfvk.fvk.vk.ak.write(&mut ak).unwrap();
+        let mut nk = vec![];
+        xfvk.fvk.vk.nk.write(&mut nk).unwrap();
+
+        assert_eq!(hex::encode(&ak), tv.ak);
+        assert_eq!(hex::encode(&nk), tv.nk);
+        assert_eq!(hex::encode(&xfvk.fvk.ovk), tv.ovk);
+        assert_eq!(hex::encode(&xfvk.dk), tv.dk);
+        assert_eq!(hex::encode(&xfvk.chain_code), tv.c);
+        assert_eq!(hex::encode(&xfvk.fvk.fingerprint()), tv.fp);
+        assert_eq!(hex::encode(&xfvk.parent_fvk_tag.0), tv.parent_fvk_tag);
+        assert_eq!(xfvk.depth, tv.depth);
+        assert_eq!(xfvk.child_index.value(), tv.child_index);
+    }
+
+    fn check_xsk(xsk: &ExtendedSpendingKey<Bls12>, tv: &TestVector, params: &JubjubBls12) {
+        let mut ask = vec![];
+        xsk.expsk.ask.into_repr().write_le(&mut ask).unwrap();
+        let mut nsk = vec![];
+        xsk.expsk.nsk.into_repr().write_le(&mut nsk).unwrap();
+
+        assert_eq!(Some(&hex::encode(&ask)[..]), tv.ask);
+        assert_eq!(Some(&hex::encode(&nsk)[..]), tv.nsk);
+        check_xfvk(&xsk.to_extended_full_viewing_key(params), tv);
+    }
+
+    /// The Sapling vectors of ZIP 32 for the seed 0, 1, ..., 31: m, m/1, m/1/2' and the
+    /// viewing key m/1/2'/3. Addresses are not compared, the diversifiers differ from ZIP 32.
+    #[test]
+    fn test_zip32_vectors() {
+        let params = JubjubBls12::new();
+        let seed: Vec<u8> = (0..32).collect();
+
+        let vectors = [
+            TestVector {
+                ask: Some("b6c00c93d36032b9a268e99e86a860776560bf0e83c1a10b51f607c954742506"),
+                nsk: Some("8204ede83b2f1fbd84f9b45d7f996e2ebd0a030ad243b48ed39f748a8821ea06"),
+                ovk: "395884890323b9d4933c021db89bcf767df21977b2ff0683848321a4df4afb21",
+                dk: "77c17cb75b7796afb39f0f3e91c924607da56fa9a20e283509bc8a3ef996a172",
+                c: "d0947c4b03bf72a37ab44f72276d1cf3fdcd7ebf3e73348b7e550d752018668e",
+                ak: "93442e5feffbff16e7217202dc7306729ffffe85af5683bce2642e3eeb5d3871",
+                nk: "dce8e7edece04b8950417f85ba57691b783c45b1a27422db1693dceb67b10106",
+                fp: "14c2713adce93a830ea83a051908b7447783f5d106c0985e02550e426f27597c",
+                parent_fvk_tag: "00000000",
+                depth: 0,
+                child_index: 0,
+            },
+            TestVector {
+                ask: Some("282bc197a516287c8ea8f68c424abad302b45cdf95407961d7b8b455267a350c"),
+                nsk: Some("e7a32988fdca1efcd6d1c4c562e629c2e96b2c3f7eda04ac4efd1810ff6bba01"),
+                ovk: "5f1381fc8886da6a02dffeefcf503c40fa8f5a36f7a7142fd81b5518c5a47474",
+                dk: "e04de832a2d791ec129ab9002b91c9e9cdeed79241a7c4960e5178d870c1b4dc",
+                c: "0147110c691a03b9d9f0ba9005c5e790a595b7f04e3329d2fa438a6705dabce6",
+                ak: "dc14b514d3a92594c21925af2f7765a547b30e73fa7b700ea1bff2e5efaaa88b",
+                nk: "6152eb7fdb252779ddcb95d217ea4b6fd34036e9adadb3b5c9cbeceb41ba452a",
+                fp: "db999e071dcb58dd93029ae697053e90edb359d1a1b7a125167efbe928068423",
+                parent_fvk_tag: "14c2713a",
+                depth: 1,
+                child_index: 1,
+            },
+            TestVector {
+                ask: Some("8be8113cee3413a71f82c41fc8da517be134049832e6825c92da6b84fee4c60d"),
+                nsk: Some("3778059dc569e7d0d32391573f951bbde92fc6b9cf614773661c5c273aa6990c"),
+                ovk: "cf81182e96223c028ce3d6eb4794d3113b95069d14c57588e193b65efc2813bc",
+                dk: "a3eda19f9eff46ca12dfa1bf10371b48d1b4a40c4d05a0d8dce0e7dc62b07b37",
+                c: "97ce15f4ed1b9739b0262a463bcb3dc9b3bd2323a9baa441ca42777383a8d435",
+                ak: "a6c5925a0f85fa4f1e405e3a4970d0c4a4b4814438f4e9d4520e20f7fdcf3841",
+                nk: "304e305916216beb7b654d8aae50ecd188fcb384bc36c00c664f307725e2ee11",
+                fp: "48c183757b5da6612a81b30e40b4acaa2d9e739512e1d2d0010e92a7f7f2fcdf",
+                parent_fvk_tag: "db999e07",
+                depth: 2,
+                child_index: (1 << 31) + 2,
+            },
+            TestVector {
+                ask: None,
+                nsk: None,
+                ovk: "69b9e0fa1c4b3deb91d53beee871156121474b8b62ef24134478dc3499691af6",
+                dk: "becb50c363bb2ed9da5c3043ceb0f1a0527bf836b29a35f7c0c9f261123be56e",
+                c: "8d937bcf81ba430d5b49afc0a403367b1fd99879ecba41be051c5a4aa7d6e7e8",
+                ak: "b185c57b509c2536c4f2d326d766c8fab25447de5375a9328d649ddabd97a6a3",
+                nk: "db88049e02d207568afc42e07db2abed500b2701c01bbff36399764b81c0664f",
+                fp: "2e08156df8dfa25b5055fc063c671535a6a65a60437d96e7930815d090f62d67",
+                parent_fvk_tag: "48c18375",
+                depth: 3,
+                child_index: 3,
+            },
+        ];
+
+        let m = ExtendedSpendingKey::<Bls12>::master(&seed);
+        let m_1 = m.derive_child(ChildIndex::NonHardened(1), &params);
+        let m_1_2h = m.derive_path(&[ChildIndex::NonHardened(1), ChildIndex::Hardened(2)], &params);
+        let m_1_2hv_3 = m_1_2h.to_extended_full_viewing_key(&params)
+            .derive_child(ChildIndex::NonHardened(3), &params)
+            .unwrap();
+
+        check_xsk(&m, &vectors[0], &params);
+        check_xsk(&m_1, &vectors[1], &params);
+        check_xsk(&m_1_2h, &vectors[2], &params);
+        check_xfvk(&m_1_2hv_3, &vectors[3]);
+    }
+}